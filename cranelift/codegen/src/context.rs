@@ -20,7 +20,7 @@ use crate::isa::TargetIsa;
 use crate::legalizer::simple_legalize;
 use crate::loop_analysis::LoopAnalysis;
 use crate::machinst::{CompiledCode, CompiledCodeStencil};
-use crate::nan_canonicalization::do_nan_canonicalization;
+use crate::nan_canonicalization::{self, do_nan_canonicalization};
 use crate::remove_constant_phis::do_remove_constant_phis;
 use crate::result::{CodegenResult, CompileResult};
 use crate::settings::{FlagsOrIsa, OptLevel};
@@ -32,7 +32,6 @@ use crate::{CompileError, timing};
 use alloc::string::String;
 use alloc::vec::Vec;
 use cranelift_control::ControlPlane;
-use target_lexicon::Architecture;
 
 #[cfg(feature = "souper-harvest")]
 use crate::souper_harvest::do_souper_harvest;
@@ -286,14 +285,7 @@ impl Context {
 
     /// Perform NaN canonicalizing rewrites on the function.
     pub fn canonicalize_nans(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
-        // Currently only RiscV64 is the only arch that may not have vector support.
-        let has_vector_support = match isa.triple().architecture {
-            Architecture::Riscv64(_) => match isa.isa_flags().iter().find(|f| f.name == "has_v") {
-                Some(value) => value.as_bool().unwrap_or(false),
-                None => false,
-            },
-            _ => true,
-        };
+        let has_vector_support = nan_canonicalization::has_vector_support(isa);
         do_nan_canonicalization(&mut self.func, has_vector_support);
         self.verify_if(isa)
     }
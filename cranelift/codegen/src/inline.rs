@@ -1261,7 +1261,7 @@ fn create_global_values(func: &mut ir::Function, callee: &ir::Function) -> u32 {
 
     func.global_values.reserve(callee.global_values.len());
     for gv in callee.global_values.values() {
-        func.global_values.push(match gv {
+        let gv = match gv {
             // These kinds of global values reference other global values, so we
             // need to fixup that reference.
             ir::GlobalValueData::Load {
@@ -1285,12 +1285,27 @@ fn create_global_values(func: &mut ir::Function, callee: &ir::Function) -> u32 {
                 global_type: *global_type,
             },
 
+            // Symbols do not reference other global values, but their names
+            // may reference the callee's user external names.
+            ir::GlobalValueData::Symbol {
+                name,
+                offset,
+                colocated,
+                tls,
+            } => ir::GlobalValueData::Symbol {
+                name: inlined_external_name(func, callee, name),
+                offset: *offset,
+                colocated: *colocated,
+                tls: *tls,
+            },
+
             // These kinds of global values do not reference other global
             // values, so we can just clone them.
-            ir::GlobalValueData::VMContext
-            | ir::GlobalValueData::Symbol { .. }
-            | ir::GlobalValueData::DynScaleTargetConst { .. } => gv.clone(),
-        });
+            ir::GlobalValueData::VMContext | ir::GlobalValueData::DynScaleTargetConst { .. } => {
+                gv.clone()
+            }
+        };
+        func.global_values.push(gv);
     }
 
     gv_offset
@@ -1321,8 +1336,9 @@ fn create_func_refs(func: &mut ir::Function, callee: &ir::Function, entity_map:
         colocated,
     } in callee.dfg.ext_funcs.values()
     {
+        let name = inlined_external_name(func, callee, name);
         func.dfg.ext_funcs.push(ir::ExtFuncData {
-            name: name.clone(),
+            name,
             signature: entity_map.inlined_sig_ref(*signature),
             colocated: *colocated,
         });
@@ -1331,6 +1347,24 @@ fn create_func_refs(func: &mut ir::Function, callee: &ir::Function, entity_map:
     offset
 }
 
+/// Translate an external name from the callee into the caller.
+///
+/// User external names are references into the function's own table of names,
+/// so they are declared in the caller's table rather than copied verbatim.
+fn inlined_external_name(
+    func: &mut ir::Function,
+    callee: &ir::Function,
+    name: &ir::ExternalName,
+) -> ir::ExternalName {
+    match name {
+        ir::ExternalName::User(name_ref) => {
+            let name = callee.params.user_named_funcs()[*name_ref].clone();
+            ir::ExternalName::User(func.declare_imported_user_function(name))
+        }
+        _ => name.clone(),
+    }
+}
+
 /// Copy stack slots from the callee into the caller.
 fn create_stack_slots(func: &mut ir::Function, callee: &ir::Function) -> u32 {
     let offset = func.sized_stack_slots.len();
@@ -1126,7 +1126,7 @@ impl ABIMachineSpec for AArch64MachineDeps {
         stackslots_size: u32,
         fixed_frame_storage_size: u32,
         outgoing_args_size: u32,
-    ) -> FrameLayout {
+    ) -> CodegenResult<FrameLayout> {
         let mut regs: Vec<Writable<RealReg>> = regs
             .iter()
             .cloned()
@@ -1157,7 +1157,7 @@ impl ABIMachineSpec for AArch64MachineDeps {
         };
 
         // Return FrameLayout structure.
-        FrameLayout {
+        Ok(FrameLayout {
             word_bytes: 8,
             incoming_args_size,
             tail_args_size,
@@ -1167,7 +1167,7 @@ impl ABIMachineSpec for AArch64MachineDeps {
             stackslots_size,
             outgoing_args_size,
             clobbered_callee_saves: regs,
-        }
+        })
     }
 
     fn retval_temp_reg(_call_conv_of_callee: isa::CallConv) -> Writable<Reg> {
@@ -7,8 +7,8 @@ use crate::isa::aarch64::settings as aarch64_settings;
 use crate::isa::unwind::systemv;
 use crate::isa::{Builder as IsaBuilder, FunctionAlignment, IsaFlagsHashKey, TargetIsa};
 use crate::machinst::{
-    CompiledCode, CompiledCodeStencil, MachInst, MachTextSectionBuilder, Reg, SigSet,
-    TextSectionBuilder, VCode, compile,
+    ABIMachineSpec, AbiSummary, CompiledCode, CompiledCodeStencil, MachInst,
+    MachTextSectionBuilder, Reg, SigSet, TextSectionBuilder, VCode, compile,
 };
 use crate::result::CodegenResult;
 use crate::settings as shared_settings;
@@ -73,7 +73,7 @@ impl TargetIsa for AArch64Backend {
     ) -> CodegenResult<CompiledCodeStencil> {
         let (vcode, regalloc_result) = self.compile_vcode(func, domtree, ctrl_plane)?;
 
-        let emit_result = vcode.emit(&regalloc_result, want_disasm, &self.flags, ctrl_plane);
+        let emit_result = vcode.emit(&regalloc_result, want_disasm, &self.flags, ctrl_plane)?;
         let frame_size = emit_result.frame_size;
         let value_labels_ranges = emit_result.value_labels_ranges;
         let buffer = emit_result.buffer;
@@ -259,6 +259,10 @@ impl TargetIsa for AArch64Backend {
         // the same as-is for now to reduce the likelihood of problems arising.
         ir::ArgumentExtension::Uext
     }
+
+    fn signature_abi_summary(&self, sig: &ir::Signature) -> CodegenResult<AbiSummary> {
+        abi::AArch64MachineDeps::signature_abi_summary(&self.flags, sig)
+    }
 }
 
 impl fmt::Display for AArch64Backend {
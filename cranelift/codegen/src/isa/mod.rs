@@ -50,7 +50,7 @@ use crate::CodegenResult;
 use crate::ir::{self, Function, Type};
 #[cfg(feature = "unwind")]
 use crate::isa::unwind::{UnwindInfoKind, systemv::RegisterMappingError};
-use crate::machinst::{CompiledCode, CompiledCodeStencil, TextSectionBuilder};
+use crate::machinst::{AbiSummary, CompiledCode, CompiledCodeStencil, TextSectionBuilder};
 use crate::settings;
 use crate::settings::Configurable;
 use crate::settings::SetResult;
@@ -74,7 +74,7 @@ pub mod aarch64;
 pub mod riscv64;
 
 #[cfg(feature = "s390x")]
-mod s390x;
+pub mod s390x;
 
 #[cfg(feature = "pulley")]
 mod pulley32;
@@ -416,6 +416,10 @@ pub trait TargetIsa: fmt::Display + Send + Sync {
     /// all ABIs for all platforms require extension of any form, so this is
     /// generally only necessary for the `default_call_conv`.
     fn default_argument_extension(&self) -> ir::ArgumentExtension;
+
+    /// Computes where the arguments and return values of `sig` are passed on
+    /// this ISA, without compiling a function which uses it.
+    fn signature_abi_summary(&self, sig: &ir::Signature) -> CodegenResult<AbiSummary>;
 }
 
 /// A wrapper around the ISA-dependent flags types which only implements `Hash`.
@@ -501,7 +501,7 @@ where
         stackslots_size: u32,
         fixed_frame_storage_size: u32,
         outgoing_args_size: u32,
-    ) -> FrameLayout {
+    ) -> CodegenResult<FrameLayout> {
         let mut regs: Vec<Writable<RealReg>> = regs
             .iter()
             .cloned()
@@ -527,7 +527,7 @@ where
             0
         };
 
-        FrameLayout {
+        Ok(FrameLayout {
             word_bytes: u32::from(P::pointer_width().bytes()),
             incoming_args_size,
             tail_args_size,
@@ -537,7 +537,7 @@ where
             stackslots_size,
             outgoing_args_size,
             clobbered_callee_saves: regs,
-        }
+        })
     }
 
     fn gen_inline_probestack(
@@ -12,7 +12,7 @@ use crate::{
     dominator_tree::DominatorTree,
     ir,
     isa::{self, IsaFlagsHashKey, OwnedTargetIsa, TargetIsa},
-    machinst::{self, CompiledCodeStencil, MachInst, SigSet, VCode},
+    machinst::{self, ABIMachineSpec, AbiSummary, CompiledCodeStencil, MachInst, SigSet, VCode},
     result::CodegenResult,
     settings::{self as shared_settings, Flags},
 };
@@ -179,7 +179,7 @@ where
 
         let want_disasm =
             want_disasm || (cfg!(feature = "trace-log") && log::log_enabled!(log::Level::Debug));
-        let emit_result = vcode.emit(&regalloc_result, want_disasm, &self.flags, ctrl_plane);
+        let emit_result = vcode.emit(&regalloc_result, want_disasm, &self.flags, ctrl_plane)?;
         let frame_size = emit_result.frame_size;
         let value_labels_ranges = emit_result.value_labels_ranges;
         let buffer = emit_result.buffer;
@@ -257,6 +257,10 @@ where
     fn default_argument_extension(&self) -> ir::ArgumentExtension {
         ir::ArgumentExtension::None
     }
+
+    fn signature_abi_summary(&self, sig: &ir::Signature) -> CodegenResult<AbiSummary> {
+        abi::PulleyMachineDeps::<P>::signature_abi_summary(&self.flags, sig)
+    }
 }
 
 /// Create a new Pulley ISA builder.
@@ -634,7 +634,7 @@ impl ABIMachineSpec for Riscv64MachineDeps {
         stackslots_size: u32,
         fixed_frame_storage_size: u32,
         outgoing_args_size: u32,
-    ) -> FrameLayout {
+    ) -> CodegenResult<FrameLayout> {
         let mut regs: Vec<Writable<RealReg>> = regs
             .iter()
             .cloned()
@@ -661,7 +661,7 @@ impl ABIMachineSpec for Riscv64MachineDeps {
         };
 
         // Return FrameLayout structure.
-        FrameLayout {
+        Ok(FrameLayout {
             word_bytes: 8,
             incoming_args_size,
             tail_args_size,
@@ -671,7 +671,7 @@ impl ABIMachineSpec for Riscv64MachineDeps {
             stackslots_size,
             outgoing_args_size,
             clobbered_callee_saves: regs,
-        }
+        })
     }
 
     fn gen_inline_probestack(
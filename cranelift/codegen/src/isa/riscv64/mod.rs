@@ -7,8 +7,8 @@ use crate::isa::{
     Builder as IsaBuilder, FunctionAlignment, IsaFlagsHashKey, OwnedTargetIsa, TargetIsa,
 };
 use crate::machinst::{
-    CompiledCode, CompiledCodeStencil, MachInst, MachTextSectionBuilder, Reg, SigSet,
-    TextSectionBuilder, VCode, compile,
+    ABIMachineSpec, AbiSummary, CompiledCode, CompiledCodeStencil, MachInst,
+    MachTextSectionBuilder, Reg, SigSet, TextSectionBuilder, VCode, compile,
 };
 use crate::result::CodegenResult;
 use crate::settings::{self as shared_settings, Flags};
@@ -74,7 +74,7 @@ impl TargetIsa for Riscv64Backend {
         let (vcode, regalloc_result) = self.compile_vcode(func, domtree, ctrl_plane)?;
 
         let want_disasm = want_disasm || log::log_enabled!(log::Level::Debug);
-        let emit_result = vcode.emit(&regalloc_result, want_disasm, &self.flags, ctrl_plane);
+        let emit_result = vcode.emit(&regalloc_result, want_disasm, &self.flags, ctrl_plane)?;
         let frame_size = emit_result.frame_size;
         let value_labels_ranges = emit_result.value_labels_ranges;
         let buffer = emit_result.buffer;
@@ -239,6 +239,10 @@ impl TargetIsa for Riscv64Backend {
         // leading to `sext` here.
         ir::ArgumentExtension::Sext
     }
+
+    fn signature_abi_summary(&self, sig: &ir::Signature) -> CodegenResult<AbiSummary> {
+        abi::Riscv64MachineDeps::signature_abi_summary(&self.flags, sig)
+    }
 }
 
 impl fmt::Display for Riscv64Backend {
@@ -253,6 +253,25 @@ fn get_vecreg_for_ret(idx: usize) -> Option<Reg> {
 /// The size of the register save area
 pub static REG_SAVE_AREA_SIZE: u32 = 160;
 
+/// The maximum size of the stack frame allocated by the prologue, 1 GiB.
+/// Frame sizes are used as signed 32-bit stack pointer adjustments and are
+/// combined with other offsets such as the register save area, so this leaves
+/// ample headroom below `i32::MAX`.
+pub const MAX_FRAME_SIZE: u32 = 1 << 30;
+
+/// Returns the size of the stack frame allocated by the prologue, excluding
+/// any incoming tail-call arguments, or `None` if it exceeds `MAX_FRAME_SIZE`.
+fn frame_size(frame_layout: &FrameLayout) -> Option<i32> {
+    let size = frame_layout
+        .outgoing_args_size
+        .checked_add(frame_layout.clobber_size)?
+        .checked_add(frame_layout.fixed_frame_storage_size)?;
+    if size > MAX_FRAME_SIZE {
+        return None;
+    }
+    i32::try_from(size).ok()
+}
+
 impl From<StackAMode> for MemArg {
     fn from(stack: StackAMode) -> MemArg {
         match stack {
@@ -673,17 +692,7 @@ impl ABIMachineSpec for S390xMachineDeps {
             // `gen_inline_probestack` is called after regalloc2, so we can
             // use the nonallocatable spilltmp register for this purpose.
             let probe_count_reg = writable_spilltmp_reg();
-            if let Ok(probe_count) = i16::try_from(probe_count) {
-                insts.push(Inst::Mov32SImm16 {
-                    rd: probe_count_reg,
-                    imm: probe_count,
-                });
-            } else {
-                insts.push(Inst::Mov32Imm {
-                    rd: probe_count_reg,
-                    imm: probe_count,
-                });
-            }
+            insts.extend(Inst::materialize_u32(probe_count_reg, probe_count));
 
             // Emit probe loop.  The guard size is assumed to fit in 16 bits.
             insts.push(Inst::StackProbeLoop {
@@ -773,9 +782,7 @@ impl ABIMachineSpec for S390xMachineDeps {
         }
 
         // Decrement stack pointer.
-        let stack_size = frame_layout.outgoing_args_size as i32
-            + frame_layout.clobber_size as i32
-            + frame_layout.fixed_frame_storage_size as i32
+        let stack_size = frame_size(frame_layout).expect("frame size checked in layout")
             - incoming_tail_args_size as i32;
         insts.extend(Self::gen_sp_reg_adjust(-stack_size));
         if flags.unwind_info() {
@@ -902,7 +909,7 @@ impl ABIMachineSpec for S390xMachineDeps {
         stackslots_size: u32,
         fixed_frame_storage_size: u32,
         mut outgoing_args_size: u32,
-    ) -> FrameLayout {
+    ) -> CodegenResult<FrameLayout> {
         assert!(
             !flags.enable_pinned_reg(),
             "Pinned register not supported on s390x"
@@ -930,6 +937,15 @@ impl ABIMachineSpec for S390xMachineDeps {
         // call instructions via the is_included_in_clobbers callback.
         // We also want to enforce saving the link register in leaf functions
         // for stack unwinding, if we're asked to preserve frame pointers.
+        //
+        // Every call-like instruction reserves the register save area for
+        // its callee in `gen_call_info`, including `try_call` and
+        // `try_call_indirect`, so a non-zero `outgoing_args_size` is exactly
+        // "this function calls something" and covers functions whose only
+        // call has exception edges. This is more precise than `is_leaf`,
+        // which is false for any function referencing a signature: functions
+        // whose only calls are `return_call`s pass their return address on
+        // to the callee untouched and need not save it.
         if outgoing_args_size > 0 {
             let link_reg = Writable::from_reg(RealReg::from(gpr_preg(14)));
             if !regs.contains(&link_reg) {
@@ -962,8 +978,7 @@ impl ABIMachineSpec for S390xMachineDeps {
             clobber_size += tail_args_size;
         }
 
-        // Return FrameLayout structure.
-        FrameLayout {
+        let frame_layout = FrameLayout {
             word_bytes: 8,
             incoming_args_size,
             // We already accounted for tail-call arguments above, so reset
@@ -975,7 +990,14 @@ impl ABIMachineSpec for S390xMachineDeps {
             stackslots_size,
             outgoing_args_size,
             clobbered_callee_saves: regs,
+        };
+
+        // Reject frames too large to allocate, which would otherwise wrap
+        // around in the prologue and epilogue stack pointer adjustments.
+        if frame_size(&frame_layout).is_none() {
+            return Err(crate::CodegenError::ImplLimitExceeded);
         }
+        Ok(frame_layout)
     }
 
     fn retval_temp_reg(_call_conv_of_callee: isa::CallConv) -> Writable<Reg> {
@@ -983,6 +1005,8 @@ impl ABIMachineSpec for S390xMachineDeps {
     }
 
     fn exception_payload_regs(call_conv: isa::CallConv) -> &'static [Reg] {
+        // `CallConv::exception_payload_types` allows at most two payloads,
+        // so there is never a payload to spill to the stack.
         const PAYLOAD_REGS: &'static [Reg] = &[gpr(6), gpr(7)];
         match call_conv {
             isa::CallConv::SystemV | isa::CallConv::Tail => PAYLOAD_REGS,
@@ -1064,6 +1088,11 @@ impl S390xMachineDeps {
                 }
                 RetLocation::Stack(amode, ty) => {
                     if let Some(spillslot) = vreg.to_reg().to_spillslot() {
+                        // In functions with a large spill area the slot may be
+                        // out of range of any displacement, in which case
+                        // `mem_finalize` materializes its offset in the
+                        // spilltmp register.  That's available here: it's
+                        // clobbered by the call and never holds a return value.
                         let temp = temp_reg(*ty);
                         insts.push(Inst::gen_load(temp, (*amode).into(), *ty));
                         lane_swap_if_needed(&mut insts, temp, *ty);
@@ -1152,9 +1181,7 @@ fn gen_restore_gprs(
     // Increment stack pointer unless it will be restored implicitly.
     // Note that implicit stack pointer restoration cannot be done in the
     // presence of either incoming or outgoing tail call arguments.
-    let stack_size = frame_layout.outgoing_args_size as i32
-        + frame_layout.clobber_size as i32
-        + frame_layout.fixed_frame_storage_size as i32;
+    let stack_size = frame_size(frame_layout).expect("frame size checked in layout");
     let implicit_sp_restore = callee_pop_size == 0
         && (call_conv != isa::CallConv::Tail || frame_layout.incoming_args_size == 0)
         && clobbered_gpr.map_or(false, |(first, _)| {
@@ -1523,3 +1550,203 @@ fn tail_create_machine_env() -> MachineEnv {
         scratch_by_class: [None, None, None],
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::AbiParam;
+    use crate::isa::s390x::abi_info;
+
+    #[derive(Debug, PartialEq)]
+    enum Loc {
+        Reg(Reg),
+        Stack(i64),
+    }
+
+    fn loc(arg: &ABIArg) -> Loc {
+        let slot = match arg {
+            ABIArg::Slots { slots, .. } => slots[0],
+            ABIArg::ImplicitPtrArg { pointer, .. } => *pointer,
+            ABIArg::StructArg { .. } => unreachable!(),
+        };
+        match slot {
+            ABIArgSlot::Reg { reg, .. } => Loc::Reg(reg.into()),
+            ABIArgSlot::Stack { offset, .. } => Loc::Stack(offset),
+        }
+    }
+
+    fn summary(
+        call_conv: isa::CallConv,
+        params: &[Type],
+        returns: &[Type],
+    ) -> CodegenResult<AbiSummary> {
+        let mut builder = settings::builder();
+        builder.enable("enable_multi_ret_implicit_sret").unwrap();
+        let flags = settings::Flags::new(builder);
+        let mut sig = Signature::new(call_conv);
+        sig.params
+            .extend(params.iter().map(|ty| AbiParam::new(*ty)));
+        sig.returns
+            .extend(returns.iter().map(|ty| AbiParam::new(*ty)));
+        S390xMachineDeps::signature_abi_summary(&flags, &sig)
+    }
+
+    #[test]
+    fn gpr_exhaustion() {
+        let s = summary(isa::CallConv::SystemV, &[types::I64; 6], &[types::I64]).unwrap();
+        let locs: Vec<_> = s.args.iter().map(loc).collect();
+        assert_eq!(
+            locs,
+            [
+                Loc::Reg(regs::gpr(2)),
+                Loc::Reg(regs::gpr(3)),
+                Loc::Reg(regs::gpr(4)),
+                Loc::Reg(regs::gpr(5)),
+                Loc::Reg(regs::gpr(6)),
+                Loc::Stack(0),
+            ]
+        );
+        assert_eq!(loc(&s.rets[0]), Loc::Reg(regs::gpr(2)));
+        assert_eq!(s.sized_stack_arg_space, 8);
+        assert_eq!(s.sized_stack_ret_space, 0);
+        assert_eq!(s.stack_ret_arg, None);
+
+        // The tail-call convention has an extra argument register, and its
+        // argument area includes the register save area.
+        let s = summary(isa::CallConv::Tail, &[types::I64; 7], &[]).unwrap();
+        assert_eq!(loc(&s.args[5]), Loc::Reg(regs::gpr(7)));
+        assert_eq!(loc(&s.args[6]), Loc::Stack(0));
+        assert_eq!(s.sized_stack_arg_space, 8 + REG_SAVE_AREA_SIZE);
+    }
+
+    #[test]
+    fn fpr_exhaustion() {
+        let s = summary(isa::CallConv::SystemV, &[types::F64; 5], &[types::F32]).unwrap();
+        let locs: Vec<_> = s.args.iter().map(loc).collect();
+        assert_eq!(
+            locs,
+            [
+                Loc::Reg(regs::vr(0)),
+                Loc::Reg(regs::vr(2)),
+                Loc::Reg(regs::vr(4)),
+                Loc::Reg(regs::vr(6)),
+                Loc::Stack(0),
+            ]
+        );
+        assert_eq!(loc(&s.rets[0]), Loc::Reg(regs::vr(0)));
+        assert_eq!(s.sized_stack_arg_space, 8);
+
+        // Smaller values are right-aligned in their stack slot.
+        let s = summary(isa::CallConv::SystemV, &[types::F32; 5], &[]).unwrap();
+        assert_eq!(loc(&s.args[4]), Loc::Stack(4));
+        assert_eq!(s.sized_stack_arg_space, 8);
+    }
+
+    #[test]
+    fn vr_exhaustion() {
+        let s = summary(isa::CallConv::SystemV, &[types::I32X4; 9], &[]).unwrap();
+        for (i, arg) in s.args[..8].iter().enumerate() {
+            assert_eq!(loc(arg), Loc::Reg(regs::vr(24 + i as u8)));
+        }
+        assert_eq!(loc(&s.args[8]), Loc::Stack(0));
+        assert_eq!(s.sized_stack_arg_space, 16);
+    }
+
+    #[test]
+    fn implicit_ptr_args() {
+        let s = summary(
+            isa::CallConv::SystemV,
+            &[types::I64, types::I128, types::I128],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(s.implicit_ptr_args().collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(loc(&s.args[1]), Loc::Reg(regs::gpr(3)));
+        assert_eq!(loc(&s.args[2]), Loc::Reg(regs::gpr(4)));
+        let offsets: Vec<_> = s
+            .args
+            .iter()
+            .filter_map(|arg| match arg {
+                ABIArg::ImplicitPtrArg { offset, ty, .. } => Some((*offset, *ty)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(offsets, [(0, types::I128), (16, types::I128)]);
+        assert_eq!(s.sized_stack_arg_space, 32);
+    }
+
+    #[test]
+    fn ret_area_ptr() {
+        let s = summary(isa::CallConv::SystemV, &[types::I64], &[types::I64; 6]).unwrap();
+        let locs: Vec<_> = s.rets.iter().map(loc).collect();
+        assert_eq!(
+            locs,
+            [
+                Loc::Reg(regs::gpr(2)),
+                Loc::Reg(regs::gpr(3)),
+                Loc::Reg(regs::gpr(4)),
+                Loc::Reg(regs::gpr(5)),
+                Loc::Stack(0),
+                Loc::Stack(8),
+            ]
+        );
+        assert_eq!(s.sized_stack_ret_space, 16);
+
+        // The return-area pointer takes the first argument register and is
+        // reported after the formal arguments.
+        assert_eq!(s.stack_ret_arg, Some(1));
+        assert_eq!(loc(&s.args[0]), Loc::Reg(regs::gpr(3)));
+        assert_eq!(loc(&s.args[1]), Loc::Reg(regs::gpr(2)));
+        assert_eq!(s.sized_stack_arg_space, 0);
+    }
+
+    const CALL_CONVS: [isa::CallConv; 2] = [isa::CallConv::SystemV, isa::CallConv::Tail];
+
+    #[test]
+    fn abi_info_arg_and_ret_regs() {
+        for call_conv in CALL_CONVS {
+            let args: Vec<_> = (0..)
+                .map_while(|i| get_intreg_for_arg(call_conv, i))
+                .collect();
+            let expected: Vec<_> = abi_info::arg_gprs(call_conv)
+                .iter()
+                .map(|&n| regs::gpr(n))
+                .collect();
+            assert_eq!(args, expected, "{call_conv}");
+
+            let rets: Vec<_> = (0..)
+                .map_while(|i| get_intreg_for_ret(call_conv, i))
+                .collect();
+            let expected: Vec<_> = abi_info::ret_gprs(call_conv)
+                .iter()
+                .map(|&n| regs::gpr(n))
+                .collect();
+            assert_eq!(rets, expected, "{call_conv}");
+        }
+
+        let args: Vec<_> = (0..).map_while(get_fltreg_for_arg).collect();
+        let expected: Vec<_> = abi_info::arg_fprs().iter().map(|&n| regs::vr(n)).collect();
+        assert_eq!(args, expected);
+        assert_eq!(abi_info::reg_save_area_size(), REG_SAVE_AREA_SIZE);
+    }
+
+    #[test]
+    fn abi_info_callee_saved() {
+        for call_conv in CALL_CONVS {
+            for (class, reg) in [
+                (abi_info::RegisterClass::Gpr, regs::gpr as fn(u8) -> Reg),
+                (abi_info::RegisterClass::Fpr, regs::vr),
+            ] {
+                let saved = abi_info::callee_saved(call_conv, class);
+                for n in 0..16 {
+                    let real = reg(n).to_real_reg().unwrap();
+                    assert_eq!(
+                        is_reg_saved_in_prologue(call_conv, real),
+                        saved.contains(&n),
+                        "{call_conv} {class:?} {n}"
+                    );
+                }
+            }
+        }
+    }
+}
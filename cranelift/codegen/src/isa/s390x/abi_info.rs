@@ -0,0 +1,76 @@
+//! Facts about the IBM Z calling conventions as implemented by this backend.
+//!
+//! Code outside of Cranelift which interoperates with generated code, such
+//! as unwinders walking its frames or trampolines calling into it, can query
+//! the register assignments and frame layout here instead of duplicating
+//! them. This matters in particular for the `tail` calling convention, which
+//! differs from the s390x ELF ABI.
+//!
+//! # Stability
+//!
+//! The values returned here describe the code this version of Cranelift
+//! generates, and are kept in sync with the backend by its tests. The
+//! functions and their signatures are covered by the usual semver guarantees
+//! of `cranelift-codegen`, but the values for [`CallConv::Tail`] may change
+//! in any release, along with the convention itself. Registers are given by
+//! their hardware number, so `2` is `%r2` for [`RegisterClass::Gpr`] and
+//! `%f2` for [`RegisterClass::Fpr`].
+
+use crate::isa::CallConv;
+use core::ops::RangeInclusive;
+
+/// A class of registers which are saved and restored by the prologue and
+/// epilogue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RegisterClass {
+    /// The general-purpose registers `%r0` to `%r15`.
+    Gpr,
+    /// The floating-point registers `%f0` to `%f15`, which overlap the
+    /// first half of the vector registers.
+    Fpr,
+}
+
+/// Returns the size in bytes of the register save area which every caller
+/// provides at the bottom of its frame, and which the backchain points to.
+pub fn reg_save_area_size() -> u32 {
+    super::abi::REG_SAVE_AREA_SIZE
+}
+
+/// Returns the general-purpose registers in which integer arguments are
+/// passed with `call_conv`, in order. Further arguments are passed on the
+/// stack.
+pub fn arg_gprs(call_conv: CallConv) -> &'static [u8] {
+    match call_conv {
+        CallConv::Tail => &[2, 3, 4, 5, 6, 7],
+        _ => &[2, 3, 4, 5, 6],
+    }
+}
+
+/// Returns the general-purpose registers in which integer return values are
+/// passed with `call_conv`, in order. Further return values are passed in
+/// memory.
+pub fn ret_gprs(call_conv: CallConv) -> &'static [u8] {
+    match call_conv {
+        CallConv::Tail => &[2, 3, 4, 5, 6, 7],
+        _ => &[2, 3, 4, 5],
+    }
+}
+
+/// Returns the floating-point registers in which floating-point arguments
+/// are passed, in order, which are the same for every calling convention.
+pub fn arg_fprs() -> &'static [u8] {
+    &[0, 2, 4, 6]
+}
+
+/// Returns the registers of `class` which a function using `call_conv` must
+/// preserve, and which the prologue therefore saves if it clobbers them.
+/// General-purpose registers are saved to the caller's register save area,
+/// see [`reg_save_area_size`], and floating-point registers to the
+/// function's own frame.
+pub fn callee_saved(call_conv: CallConv, class: RegisterClass) -> RangeInclusive<u8> {
+    match (call_conv, class) {
+        (CallConv::Tail, RegisterClass::Gpr) => 8..=15,
+        (_, RegisterClass::Gpr) => 6..=15,
+        (_, RegisterClass::Fpr) => 8..=15,
+    }
+}
@@ -163,6 +163,9 @@ pub fn mem_finalize(
                     flags: mem.get_flags(),
                 }
             } else {
+                // Out of range of any displacement, for example a spill slot
+                // in a function with a very large spill area.  The spilltmp
+                // register is reserved for this by `memarg_operands`.
                 let tmp = writable_spilltmp_reg();
                 assert!(base != tmp.to_reg());
                 if let Ok(imm) = i16::try_from(off) {
@@ -1072,6 +1075,31 @@ fn enc_vri_c(opcode: u16, v1: Reg, i2: u16, v3: Reg, m4: u8) -> [u8; 6] {
     enc
 }
 
+/// VRId-type instructions.
+///
+///   47      39 35 31 27 23 15 11  7
+///   opcode1 v1 v2 v3 -  i4 m5 rxb opcode2
+///        40 36 32 28 24 16 12   8       0
+///
+fn enc_vri_d(opcode: u16, v1: Reg, v2: Reg, v3: Reg, i4: u8, m5: u8) -> [u8; 6] {
+    let opcode1 = ((opcode >> 8) & 0xff) as u8;
+    let opcode2 = (opcode & 0xff) as u8;
+    let rxb = rxb(Some(v1), Some(v2), Some(v3), None);
+    let v1 = machreg_to_vr(v1) & 0x0f;
+    let v2 = machreg_to_vr(v2) & 0x0f;
+    let v3 = machreg_to_vr(v3) & 0x0f;
+    let m5 = m5 & 0x0f;
+
+    let mut enc: [u8; 6] = [0; 6];
+    enc[0] = opcode1;
+    enc[1] = v1 << 4 | v2;
+    enc[2] = v3 << 4;
+    enc[3] = i4;
+    enc[4] = m5 << 4 | rxb;
+    enc[5] = opcode2;
+    enc
+}
+
 /// VRRa-type instructions.
 ///
 ///   47      39 35 31 23 19 15 11  7
@@ -2370,19 +2398,28 @@ impl Inst {
                 rd,
                 ref symbol_reloc,
             } => {
-                let reg = writable_spilltmp_reg().to_reg();
-                put(sink, &enc_ri_b(OPCODE_BRAS, reg, 12));
-                let (reloc, name, offset) = match &**symbol_reloc {
-                    SymbolReloc::Absolute { name, offset } => (Reloc::Abs8, name, *offset),
-                    SymbolReloc::TlsGd { name } => (Reloc::S390xTlsGd64, name, 0),
-                };
-                sink.add_reloc(reloc, name, offset);
-                sink.put8(0);
-                let inst = Inst::Load64 {
-                    rd,
-                    mem: MemArg::reg(reg, MemFlags::trusted()),
-                };
-                inst.emit(sink, emit_info, state);
+                if let SymbolReloc::GotEntry { name } = &**symbol_reloc {
+                    // Load the address from the GOT entry, addressed PC-relative.
+                    let opcode = 0xc48; // LGRL
+                    let offset = sink.cur_offset() + 2;
+                    sink.add_reloc_at_offset(offset, Reloc::S390xGOTEnt32Dbl, name, 2);
+                    put(sink, &enc_ril_b(opcode, rd.to_reg(), 0));
+                } else {
+                    let reg = writable_spilltmp_reg().to_reg();
+                    put(sink, &enc_ri_b(OPCODE_BRAS, reg, 12));
+                    let (reloc, name, offset) = match &**symbol_reloc {
+                        SymbolReloc::Absolute { name, offset } => (Reloc::Abs8, name, *offset),
+                        SymbolReloc::TlsGd { name } => (Reloc::S390xTlsGd64, name, 0),
+                        SymbolReloc::GotEntry { .. } => unreachable!(),
+                    };
+                    sink.add_reloc(reloc, name, offset);
+                    sink.put8(0);
+                    let inst = Inst::Load64 {
+                        rd,
+                        mem: MemArg::reg(reg, MemFlags::trusted()),
+                    };
+                    inst.emit(sink, emit_info, state);
+                }
             }
 
             &Inst::FpuMove32 { rd, rn } => {
@@ -2829,6 +2866,10 @@ impl Inst {
                 let opcode = 0xe784; // VPDI
                 put(sink, &enc_vrr_c(opcode, rd.to_reg(), rn, rm, m4, 0, 0));
             }
+            &Inst::VecShiftLeftDoubleByte { rd, rn, rm, amt } => {
+                let opcode = 0xe777; // VSLDB
+                put(sink, &enc_vri_d(opcode, rd.to_reg(), rn, rm, amt & 15, 0));
+            }
             &Inst::VecIntCmp { op, rd, rn, rm } | &Inst::VecIntCmpS { op, rd, rn, rm } => {
                 let (opcode, m4) = match op {
                     VecIntCmpOp::CmpEq8x16 => (0xe7f8, 0),  // VCEQB
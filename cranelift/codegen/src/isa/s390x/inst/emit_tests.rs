@@ -5509,6 +5509,42 @@ fn test_s390x_binemit() {
         "E3102FFF7F24",
         "stg %r1, 524287(%r2)",
     ));
+
+    // Stack slots beyond the 20-bit displacement range, as found in
+    // functions with a very large spill area, are addressed through the
+    // spilltmp register.
+    insns.push((
+        Inst::Store64 {
+            rd: gpr(0),
+            mem: MemArg::SpillOffset { off: 524280 },
+        },
+        "E300FFF87F24",
+        "stg %r0, 524280(%r15)",
+    ));
+    insns.push((
+        Inst::Store64 {
+            rd: gpr(0),
+            mem: MemArg::SpillOffset { off: 524288 },
+        },
+        "C01100080000E301F0000024",
+        "lgfi %r1, 524288 ; stg %r0, 0(%r1,%r15)",
+    ));
+    insns.push((
+        Inst::Load64 {
+            rd: writable_gpr(0),
+            mem: MemArg::SlotOffset { off: 8 * 100_000 },
+        },
+        "C011000C3500E301F0000004",
+        "lgfi %r1, 800000 ; lg %r0, 0(%r1,%r15)",
+    ));
+    insns.push((
+        Inst::StoreImm64SExt16 {
+            imm: 7,
+            mem: MemArg::SpillOffset { off: 8 * 100_000 },
+        },
+        "C011000C35004111F000E54810000007",
+        "lgfi %r1, 800000 ; la %r1, 0(%r1,%r15) ; mvghi 0(%r1), 7",
+    ));
     insns.push((
         Inst::Store64 {
             rd: gpr(1),
@@ -6380,6 +6416,19 @@ fn test_s390x_binemit() {
         "A7887FFF",
         "lhi %r8, 32767",
     ));
+
+    // `Inst::materialize_u32` picks the shortest encoding for each value.
+    for (value, encoding, printing) in [
+        (0, "A7880000", "lhi %r8, 0"),
+        (0xffff_ffff, "A788FFFF", "lhi %r8, -1"),
+        (40000, "A58F9C40", "llill %r8, 40000"),
+        (0x8000_0000, "A58E8000", "llilh %r8, 32768"),
+        (0x1234_5678, "C08912345678", "iilf %r8, 305419896"),
+    ] {
+        let insts = Inst::materialize_u32(writable_gpr(8), value);
+        assert_eq!(insts.len(), 1);
+        insns.push((insts[0].clone(), encoding, printing));
+    }
     insns.push((
         Inst::Mov32Imm {
             rd: writable_gpr(8),
@@ -9893,6 +9942,26 @@ fn test_s390x_binemit() {
         "E74680005884",
         "vpdi %v20, %v6, %v8, 5",
     ));
+    insns.push((
+        Inst::VecShiftLeftDoubleByte {
+            rd: writable_vr(20),
+            rn: vr(6),
+            rm: vr(8),
+            amt: 3,
+        },
+        "E74680030877",
+        "vsldb %v20, %v6, %v8, 3",
+    ));
+    insns.push((
+        Inst::VecShiftLeftDoubleByte {
+            rd: writable_vr(20),
+            rn: vr(6),
+            rm: vr(8),
+            amt: 15,
+        },
+        "E746800F0877",
+        "vsldb %v20, %v6, %v8, 15",
+    ));
 
     insns.push((
         Inst::VecIntCmp {
@@ -8,7 +8,7 @@ use crate::machinst::*;
 use crate::{CodegenError, CodegenResult, settings};
 use alloc::boxed::Box;
 use alloc::vec::Vec;
-use smallvec::SmallVec;
+use smallvec::{SmallVec, smallvec};
 use std::fmt::Write;
 use std::string::{String, ToString};
 pub mod regs;
@@ -194,6 +194,7 @@ impl Inst {
             | Inst::VecSelect { .. }
             | Inst::VecPermute { .. }
             | Inst::VecPermuteDWImm { .. }
+            | Inst::VecShiftLeftDoubleByte { .. }
             | Inst::VecIntCmp { .. }
             | Inst::VecIntCmpS { .. }
             | Inst::VecFloatCmp { .. }
@@ -326,6 +327,23 @@ impl Inst {
         }
     }
 
+    /// Materialize a 32-bit constant into the low 32 bits of a GPR, using the
+    /// shortest encoding available for the value.  The high 32 bits of the
+    /// register are undefined afterwards.
+    pub fn materialize_u32(rd: Writable<Reg>, value: u32) -> SmallInstVec<Inst> {
+        let inst = if let Ok(imm) = i16::try_from(value as i32) {
+            // LHI: 16-bit signed immediate.
+            Inst::Mov32SImm16 { rd, imm }
+        } else if let Some(imm) = UImm16Shifted::maybe_from_u64(value.into()) {
+            // LLILL / LLILH: a single non-zero halfword.
+            Inst::Mov64UImm16Shifted { rd, imm }
+        } else {
+            // IILF: any 32-bit value.
+            Inst::Mov32Imm { rd, imm: value }
+        };
+        smallvec![inst]
+    }
+
     /// Generic constructor for a load (zero-extending where appropriate).
     pub fn gen_load(into_reg: Writable<Reg>, mem: MemArg, ty: Type) -> Inst {
         match ty {
@@ -708,7 +726,8 @@ fn s390x_get_operands(inst: &mut Inst, collector: &mut DenyReuseVisitor<impl Ope
             collector.reg_use(rm);
             collector.reg_use(ra);
         }
-        Inst::VecPermuteDWImm { rd, rn, rm, .. } => {
+        Inst::VecPermuteDWImm { rd, rn, rm, .. }
+        | Inst::VecShiftLeftDoubleByte { rd, rn, rm, .. } => {
             collector.reg_def(rd);
             collector.reg_use(rn);
             collector.reg_use(rm);
@@ -2625,6 +2644,12 @@ impl Inst {
                 let m4 = (idx1 & 1) * 4 + (idx2 & 1);
                 format!("vpdi {rd}, {rn}, {rm}, {m4}")
             }
+            &Inst::VecShiftLeftDoubleByte { rd, rn, rm, amt } => {
+                let rd = pretty_print_reg(rd.to_reg());
+                let rn = pretty_print_reg(rn);
+                let rm = pretty_print_reg(rm);
+                format!("vsldb {rd}, {rn}, {rm}, {amt}")
+            }
             &Inst::VecIntCmp { op, rd, rn, rm } | &Inst::VecIntCmpS { op, rd, rn, rm } => {
                 let op = match op {
                     VecIntCmpOp::CmpEq8x16 => "vceqb",
@@ -3330,12 +3355,16 @@ impl Inst {
                 ref symbol_reloc,
             } => {
                 let rd = pretty_print_reg(rd.to_reg());
+                if let SymbolReloc::GotEntry { name } = &**symbol_reloc {
+                    return format!("lgrl {rd}, {}@GOTENT", name.display(None));
+                }
                 let tmp = pretty_print_reg(writable_spilltmp_reg().to_reg());
                 let symbol = match &**symbol_reloc {
                     SymbolReloc::Absolute { name, offset } => {
                         format!("{} + {}", name.display(None), offset)
                     }
                     SymbolReloc::TlsGd { name } => format!("{}@tlsgd", name.display(None)),
+                    SymbolReloc::GotEntry { .. } => unreachable!(),
                 };
                 format!("bras {tmp}, 12 ; data {symbol} ; lg {rd}, 0({tmp})")
             }
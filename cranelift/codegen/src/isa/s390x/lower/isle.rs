@@ -210,6 +210,32 @@ impl generated_code::Context for IsleContext<'_, '_, MInst, S390xBackend> {
         Box::new(symbol_reloc.clone())
     }
 
+    fn materialize_u32(&mut self, ty: Type, value: u32) -> Reg {
+        let rd = self.temp_writable_reg(ty);
+        for inst in MInst::materialize_u32(rd, value) {
+            self.emit(&inst);
+        }
+        rd.to_reg()
+    }
+
+    #[inline]
+    fn is_pic(&mut self) -> Option<()> {
+        if self.backend.flags.is_pic() {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn direct_call_distance(&mut self, dist: RelocDistance) -> Option<()> {
+        if dist == RelocDistance::Near || self.backend.flags.is_pic() {
+            Some(())
+        } else {
+            None
+        }
+    }
+
     #[inline]
     fn mie3_enabled(&mut self, _: Type) -> Option<()> {
         if self.backend.isa_flags.has_mie3() {
@@ -417,6 +443,29 @@ impl generated_code::Context for IsleContext<'_, '_, MInst, S390xBackend> {
         (permute_mask, and_mask)
     }
 
+    #[inline]
+    fn shuffle_rotate_bytes(&mut self, permute_mask: u128) -> Option<(u8, u8, u8)> {
+        // Match a permute mask that selects 16 consecutive bytes, starting at
+        // byte `amt`, out of the concatenation of two (possibly identical)
+        // source operands. Operand 0 holds bytes 0..15 and operand 1 holds
+        // bytes 16..31 of the permute mask.
+        let bytes = permute_mask.to_be_bytes();
+        let start = bytes[0];
+        if start >= 32 || start % 16 == 0 {
+            return None;
+        }
+        let (src1, amt) = (start / 16, start % 16);
+        let pair = (0..16).all(|i| bytes[i as usize] == (start + i) % 32);
+        let single = (0..16).all(|i| bytes[i as usize] == (start & 16) | ((start + i) & 15));
+        if pair {
+            Some((src1, 1 - src1, amt))
+        } else if single {
+            Some((src1, src1, amt))
+        } else {
+            None
+        }
+    }
+
     #[inline]
     fn u64_from_value(&mut self, val: Value) -> Option<u64> {
         let inst = self.lower_ctx.dfg().value_def(val).inst()?;
@@ -440,6 +489,13 @@ impl generated_code::Context for IsleContext<'_, '_, MInst, S390xBackend> {
         Some(imm)
     }
 
+    #[inline]
+    fn u16_from_value(&mut self, val: Value) -> Option<u16> {
+        let constant = self.u64_from_value(val)?;
+        let imm = u16::try_from(constant).ok()?;
+        Some(imm)
+    }
+
     #[inline]
     fn u8_from_value(&mut self, val: Value) -> Option<u8> {
         let constant = self.u64_from_value(val)?;
@@ -7,8 +7,8 @@ use crate::isa::s390x::settings as s390x_settings;
 use crate::isa::unwind::systemv::RegisterMappingError;
 use crate::isa::{Builder as IsaBuilder, FunctionAlignment, IsaFlagsHashKey, TargetIsa};
 use crate::machinst::{
-    CompiledCode, CompiledCodeStencil, MachInst, MachTextSectionBuilder, Reg, SigSet,
-    TextSectionBuilder, VCode, compile,
+    ABIMachineSpec, AbiSummary, CompiledCode, CompiledCodeStencil, MachInst,
+    MachTextSectionBuilder, Reg, SigSet, TextSectionBuilder, VCode, compile,
 };
 use crate::result::CodegenResult;
 use crate::settings as shared_settings;
@@ -20,6 +20,7 @@ use target_lexicon::{Architecture, Triple};
 
 // New backend:
 mod abi;
+pub mod abi_info;
 pub(crate) mod inst;
 mod lower;
 mod settings;
@@ -73,7 +74,7 @@ impl TargetIsa for S390xBackend {
         let flags = self.flags();
         let (vcode, regalloc_result) = self.compile_vcode(func, domtree, ctrl_plane)?;
 
-        let emit_result = vcode.emit(&regalloc_result, want_disasm, flags, ctrl_plane);
+        let emit_result = vcode.emit(&regalloc_result, want_disasm, flags, ctrl_plane)?;
         let frame_size = emit_result.frame_size;
         let value_labels_ranges = emit_result.value_labels_ranges;
         let buffer = emit_result.buffer;
@@ -217,6 +218,10 @@ impl TargetIsa for S390xBackend {
         // the same as-is for now to reduce the likelihood of problems arising.
         ir::ArgumentExtension::Uext
     }
+
+    fn signature_abi_summary(&self, sig: &ir::Signature) -> CodegenResult<AbiSummary> {
+        abi::S390xMachineDeps::signature_abi_summary(&self.flags, sig)
+    }
 }
 
 impl fmt::Display for S390xBackend {
@@ -906,7 +906,7 @@ impl ABIMachineSpec for X64ABIMachineSpec {
         stackslots_size: u32,
         fixed_frame_storage_size: u32,
         outgoing_args_size: u32,
-    ) -> FrameLayout {
+    ) -> CodegenResult<FrameLayout> {
         debug_assert!(tail_args_size >= incoming_args_size);
 
         let mut regs: Vec<Writable<RealReg>> = match call_conv {
@@ -937,7 +937,7 @@ impl ABIMachineSpec for X64ABIMachineSpec {
         let setup_area_size = 16; // RBP, return address
 
         // Return FrameLayout structure.
-        FrameLayout {
+        Ok(FrameLayout {
             word_bytes: 8,
             incoming_args_size,
             tail_args_size: align_to(tail_args_size, 16),
@@ -947,7 +947,7 @@ impl ABIMachineSpec for X64ABIMachineSpec {
             stackslots_size,
             outgoing_args_size,
             clobbered_callee_saves: regs,
-        }
+        })
     }
 
     fn retval_temp_reg(_call_conv_of_callee: isa::CallConv) -> Writable<Reg> {
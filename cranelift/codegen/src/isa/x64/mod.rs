@@ -10,8 +10,8 @@ use crate::isa::unwind::systemv;
 use crate::isa::x64::settings as x64_settings;
 use crate::isa::{Builder as IsaBuilder, FunctionAlignment, IsaFlagsHashKey};
 use crate::machinst::{
-    CompiledCode, CompiledCodeStencil, MachInst, MachTextSectionBuilder, Reg, SigSet,
-    TextSectionBuilder, VCode, compile,
+    ABIMachineSpec, AbiSummary, CompiledCode, CompiledCodeStencil, MachInst,
+    MachTextSectionBuilder, Reg, SigSet, TextSectionBuilder, VCode, compile,
 };
 use crate::result::CodegenResult;
 use crate::settings::{self as shared_settings, Flags};
@@ -72,7 +72,7 @@ impl TargetIsa for X64Backend {
     ) -> CodegenResult<CompiledCodeStencil> {
         let (vcode, regalloc_result) = self.compile_vcode(func, domtree, ctrl_plane)?;
 
-        let emit_result = vcode.emit(&regalloc_result, want_disasm, &self.flags, ctrl_plane);
+        let emit_result = vcode.emit(&regalloc_result, want_disasm, &self.flags, ctrl_plane)?;
         let frame_size = emit_result.frame_size;
         let value_labels_ranges = emit_result.value_labels_ranges;
         let buffer = emit_result.buffer;
@@ -204,6 +204,10 @@ impl TargetIsa for X64Backend {
         // the same as-is for now to reduce the likelihood of problems arising.
         ir::ArgumentExtension::Uext
     }
+
+    fn signature_abi_summary(&self, sig: &ir::Signature) -> CodegenResult<AbiSummary> {
+        abi::X64ABIMachineSpec::signature_abi_summary(&self.flags, sig)
+    }
 }
 
 /// Emit unwind info for an x86 target.
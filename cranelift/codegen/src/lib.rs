@@ -68,9 +68,10 @@ pub use crate::machinst::buffer::{
     OpenPatchRegion, PatchRegion,
 };
 pub use crate::machinst::{
-    CallInfo, CompiledCode, CompiledCodeStencil, Final, MachBuffer, MachBufferFinalized, MachInst,
-    MachInstEmit, MachInstEmitState, MachLabel, RealReg, Reg, RelocDistance, TextSectionBuilder,
-    VCodeConstant, VCodeConstantData, VCodeConstants, VCodeInst, Writable,
+    ABIArg, ABIArgSlot, AbiSummary, CallInfo, CompiledCode, CompiledCodeStencil, Final, MachBuffer,
+    MachBufferFinalized, MachInst, MachInstEmit, MachInstEmitState, MachLabel, RealReg, Reg,
+    RelocDistance, TextSectionBuilder, VCodeConstant, VCodeConstantData, VCodeConstants, VCodeInst,
+    Writable,
 };
 
 mod alias_analysis;
@@ -94,6 +95,7 @@ mod value_label;
 #[cfg(feature = "souper-harvest")]
 mod souper_harvest;
 
+pub use crate::nan_canonicalization::canonicalize_nan;
 pub use crate::result::{CodegenError, CodegenResult, CompileError};
 pub use crate::take_and_replace::TakeAndReplace;
 
@@ -347,6 +347,36 @@ impl<'a> ArgsAccumulator<'a> {
     }
 }
 
+/// A summary of where a signature's arguments and return values are passed,
+/// computed by [`ABIMachineSpec::signature_abi_summary`].
+#[derive(Clone, Debug)]
+pub struct AbiSummary {
+    /// The locations of the arguments, in signature order, followed by any
+    /// extra arguments such as the return-area pointer.
+    pub args: Vec<ABIArg>,
+    /// The locations of the return values, in signature order.
+    pub rets: Vec<ABIArg>,
+    /// The stack space used by arguments, including the buffers of arguments
+    /// passed by implicit reference.
+    pub sized_stack_arg_space: u32,
+    /// The stack space used by return values which don't fit in registers.
+    pub sized_stack_ret_space: u32,
+    /// The index in `args` of the return-area pointer, if one is needed.
+    pub stack_ret_arg: Option<usize>,
+}
+
+impl AbiSummary {
+    /// Returns the indices in `args` of the arguments passed by implicit
+    /// reference.
+    pub fn implicit_ptr_args(&self) -> impl Iterator<Item = usize> + '_ {
+        self.args
+            .iter()
+            .enumerate()
+            .filter(|(_, arg)| matches!(arg, ABIArg::ImplicitPtrArg { .. }))
+            .map(|(i, _)| i)
+    }
+}
+
 /// Trait implemented by machine-specific backend to provide information about
 /// register assignments and to allow generating the specific instructions for
 /// stack loads/saves, prologues/epilogues, etc.
@@ -407,6 +437,36 @@ pub trait ABIMachineSpec {
         args: ArgsAccumulator,
     ) -> CodegenResult<(u32, Option<usize>)>;
 
+    /// Computes where the arguments and return values of `sig` are passed,
+    /// without creating a `Callee` or interning the signature in a function's
+    /// `SigSet`.
+    fn signature_abi_summary(
+        flags: &settings::Flags,
+        sig: &ir::Signature,
+    ) -> CodegenResult<AbiSummary>
+    where
+        Self: Sized,
+    {
+        let mut sigs = SigSet {
+            ir_signature_to_abi_sig: FxHashMap::default(),
+            ir_sig_ref_to_abi_sig: SecondaryMap::new(),
+            abi_args: vec![],
+            sigs: PrimaryMap::new(),
+        };
+        let data = sigs.from_func_sig::<Self>(sig, flags)?;
+
+        // `from_func_sig` pushes the return values first, then the arguments.
+        let rets_end = usize::try_from(data.rets_end).unwrap();
+        let args = sigs.abi_args.split_off(rets_end);
+        Ok(AbiSummary {
+            args,
+            rets: sigs.abi_args,
+            sized_stack_arg_space: data.sized_stack_arg_space,
+            sized_stack_ret_space: data.sized_stack_ret_space,
+            stack_ret_arg: data.stack_ret_arg.map(usize::from),
+        })
+    }
+
     /// Generate a load from the stack.
     fn gen_load_stack(mem: StackAMode, into_reg: Writable<Reg>, ty: Type) -> Self::I;
 
@@ -485,6 +545,9 @@ pub trait ABIMachineSpec {
     /// registers that are callee-saved according to the ABI, as well as the sizes
     /// of all parts of the stack frame.  The result is used to emit the prologue
     /// and epilogue routines.
+    ///
+    /// Returns `CodegenError::ImplLimitExceeded` if the frame is too large for
+    /// this architecture to set up.
     fn compute_frame_layout(
         call_conv: isa::CallConv,
         flags: &settings::Flags,
@@ -496,7 +559,7 @@ pub trait ABIMachineSpec {
         stackslots_size: u32,
         fixed_frame_storage_size: u32,
         outgoing_args_size: u32,
-    ) -> FrameLayout;
+    ) -> CodegenResult<FrameLayout>;
 
     /// Generate the usual frame-setup sequence for this architecture: e.g.,
     /// `push rbp / mov rbp, rsp` on x86-64, or `stp fp, lr, [sp, #-16]!` on
@@ -2037,6 +2100,14 @@ impl<M: ABIMachineSpec> Callee<M> {
             // overlaps a return value (and we alias to it) or not
             // (and we add a def).
             let pregs = M::exception_payload_regs(callee_conv);
+            // Payloads are only ever passed in registers: the calling
+            // convention fixes how many there are, and each backend
+            // provides a register for every one of them.
+            assert_eq!(
+                pregs.len(),
+                try_call_payloads.len(),
+                "{callee_conv} exception payloads must all have a register"
+            );
             for (i, &preg) in pregs.iter().enumerate() {
                 let vreg = try_call_payloads[i];
                 if let Some(existing) = defs.iter().find(|def| match def.location {
@@ -2169,11 +2240,17 @@ impl<M: ABIMachineSpec> Callee<M> {
         sigs: &SigSet,
         spillslots: usize,
         clobbered: Vec<Writable<RealReg>>,
-    ) {
+    ) -> CodegenResult<()> {
         let bytes = M::word_bytes();
-        let total_stacksize = self.stackslots_size + bytes * spillslots as u32;
+        let total_stacksize = u32::try_from(spillslots)
+            .ok()
+            .and_then(|spillslots| spillslots.checked_mul(bytes))
+            .and_then(|spillslots_size| spillslots_size.checked_add(self.stackslots_size))
+            .ok_or(CodegenError::ImplLimitExceeded)?;
+        // 16-align the stack.
         let mask = M::stack_align(self.call_conv) - 1;
-        let total_stacksize = (total_stacksize + mask) & !mask; // 16-align the stack.
+        let total_stacksize =
+            checked_round_up(total_stacksize, mask).ok_or(CodegenError::ImplLimitExceeded)?;
         self.frame_layout = Some(M::compute_frame_layout(
             self.call_conv,
             &self.flags,
@@ -2185,7 +2262,8 @@ impl<M: ABIMachineSpec> Callee<M> {
             self.stackslots_size,
             total_stacksize,
             self.outgoing_args_size,
-        ));
+        )?);
+        Ok(())
     }
 
     /// Generate a prologue, post-regalloc.
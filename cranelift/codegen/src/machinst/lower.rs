@@ -1096,8 +1096,10 @@ impl<'func, I: VCodeInst> Lower<'func, I> {
                     buffer.extend_from_slice(regs.regs());
                 }
                 BlockArg::TryCallExn(i) => {
-                    let reg =
-                        self.try_call_payloads.get(&branch_inst).unwrap()[i as usize].to_reg();
+                    let reg = self.try_call_payloads.get(&branch_inst).unwrap()[..]
+                        .get(i as usize)
+                        .expect("out-of-bounds `exnN` block argument should fail verification")
+                        .to_reg();
                     buffer.push(reg);
                 }
             }
@@ -732,14 +732,15 @@ impl<I: VCodeInst> VCode<I> {
     ///
     /// Returns the machine code itself, and optionally metadata
     /// and/or a disassembly, as an `EmitResult`. The `VCode` itself
-    /// is consumed by the emission process.
+    /// is consumed by the emission process. Fails if the final stack
+    /// frame is too large for the target.
     pub fn emit(
         mut self,
         regalloc: &regalloc2::Output,
         want_disasm: bool,
         flags: &settings::Flags,
         ctrl_plane: &mut ControlPlane,
-    ) -> EmitResult
+    ) -> CodegenResult<EmitResult>
     where
         I: VCodeInst,
     {
@@ -779,7 +780,7 @@ impl<I: VCodeInst> VCode<I> {
         // relatively cheap.
         let clobbers = self.compute_clobbers(regalloc);
         self.abi
-            .compute_frame_layout(&self.sigs, regalloc.num_spillslots, clobbers);
+            .compute_frame_layout(&self.sigs, regalloc.num_spillslots, clobbers)?;
 
         // Emit blocks.
         let mut cur_srcloc = None;
@@ -1103,7 +1104,7 @@ impl<I: VCodeInst> VCode<I> {
             self.compute_value_labels_ranges(regalloc, &inst_offsets[..], func_body_len);
         let frame_size = self.abi.frame_size();
 
-        EmitResult {
+        Ok(EmitResult {
             buffer: buffer.finish(&self.constants, ctrl_plane),
             bb_offsets,
             bb_edges,
@@ -1112,7 +1113,7 @@ impl<I: VCodeInst> VCode<I> {
             dynamic_stackslot_offsets: self.abi.dynamic_stackslot_offsets().clone(),
             value_labels_ranges,
             frame_size,
-        }
+        })
     }
 
     fn monotonize_inst_offsets(&self, inst_offsets: &mut [CodeOffset], func_body_len: u32) {
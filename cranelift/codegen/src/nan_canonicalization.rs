@@ -7,8 +7,10 @@ use crate::ir::condcodes::FloatCC;
 use crate::ir::immediates::{Ieee32, Ieee64};
 use crate::ir::types::{self};
 use crate::ir::{Function, Inst, InstBuilder, InstructionData, Opcode, Value};
+use crate::isa::TargetIsa;
 use crate::opts::MemFlags;
 use crate::timing;
+use target_lexicon::Architecture;
 
 /// Perform the NaN canonicalization pass.
 pub fn do_nan_canonicalization(func: &mut Function, has_vector_support: bool) {
@@ -61,6 +63,43 @@ fn add_nan_canon_seq(pos: &mut FuncCursor, inst: Inst, has_vector_support: bool)
     let new_res = pos.func.dfg.replace_result(val, val_type);
     let _next_inst = pos.next_inst().expect("block missing terminator!");
 
+    emit_nan_canon_seq(pos, new_res, Some(val), has_vector_support);
+
+    pos.prev_inst(); // Step backwards so the pass does not skip instructions.
+}
+
+/// Insert, at `pos`, the sequence of instructions that canonicalizes the
+/// floating-point value `val` and return the canonicalized value.
+///
+/// This is the same sequence that the NaN canonicalization pass appends after
+/// floating-point arithmetic, for frontends which want to canonicalize only
+/// some values themselves instead of enabling the pass for all of them.
+pub fn canonicalize_nan(pos: &mut FuncCursor, isa: &dyn TargetIsa, val: Value) -> Value {
+    emit_nan_canon_seq(pos, val, None, has_vector_support(isa))
+}
+
+/// Returns whether `isa` can use vector instructions to canonicalize NaNs.
+pub(crate) fn has_vector_support(isa: &dyn TargetIsa) -> bool {
+    // Currently only RiscV64 is the only arch that may not have vector support.
+    match isa.triple().architecture {
+        Architecture::Riscv64(_) => match isa.isa_flags().iter().find(|f| f.name == "has_v") {
+            Some(value) => value.as_bool().unwrap_or(false),
+            None => false,
+        },
+        _ => true,
+    }
+}
+
+/// Insert a sequence selecting the canonical NaN if `new_res` is a NaN and
+/// `new_res` otherwise, defining `result` if given and a fresh value if not.
+fn emit_nan_canon_seq(
+    pos: &mut FuncCursor,
+    new_res: Value,
+    result: Option<Value>,
+    has_vector_support: bool,
+) -> Value {
+    let val_type = pos.func.dfg.value_type(new_res);
+
     // Insert a comparison instruction, to check if `inst_res` is NaN (comparing
     // against NaN is always unordered). Select the canonical NaN value if `val`
     // is NaN, assign the result to `inst`.
@@ -72,55 +111,53 @@ fn add_nan_canon_seq(pos: &mut FuncCursor, inst: Inst, has_vector_support: bool)
         let is_nan = pos.ins().fcmp(comparison, new_res, new_res);
         let is_nan = pos.ins().bitcast(ty, MemFlags::new(), is_nan);
         let simd_result = pos.ins().bitselect(is_nan, canon_nan, new_res);
-        pos.ins().with_result(val).extractlane(simd_result, 0);
+        pos.ins().with_results([result]).extractlane(simd_result, 0)
     };
     let scalar_select = |pos: &mut FuncCursor, canon_nan: Value| {
         let is_nan = pos.ins().fcmp(comparison, new_res, new_res);
         pos.ins()
-            .with_result(val)
-            .select(is_nan, canon_nan, new_res);
+            .with_results([result])
+            .select(is_nan, canon_nan, new_res)
     };
 
     let vector_select = |pos: &mut FuncCursor, canon_nan: Value| {
         let is_nan = pos.ins().fcmp(comparison, new_res, new_res);
         let is_nan = pos.ins().bitcast(val_type, MemFlags::new(), is_nan);
         pos.ins()
-            .with_result(val)
-            .bitselect(is_nan, canon_nan, new_res);
+            .with_results([result])
+            .bitselect(is_nan, canon_nan, new_res)
     };
 
     match val_type {
         types::F32 => {
             let canon_nan = pos.ins().f32const(Ieee32::NAN);
             if has_vector_support {
-                vectorized_scalar_select(pos, canon_nan, types::F32X4);
+                vectorized_scalar_select(pos, canon_nan, types::F32X4)
             } else {
-                scalar_select(pos, canon_nan);
+                scalar_select(pos, canon_nan)
             }
         }
         types::F64 => {
             let canon_nan = pos.ins().f64const(Ieee64::NAN);
             if has_vector_support {
-                vectorized_scalar_select(pos, canon_nan, types::F64X2);
+                vectorized_scalar_select(pos, canon_nan, types::F64X2)
             } else {
-                scalar_select(pos, canon_nan);
+                scalar_select(pos, canon_nan)
             }
         }
         types::F32X4 => {
             let canon_nan = pos.ins().f32const(Ieee32::NAN);
             let canon_nan = pos.ins().splat(types::F32X4, canon_nan);
-            vector_select(pos, canon_nan);
+            vector_select(pos, canon_nan)
         }
         types::F64X2 => {
             let canon_nan = pos.ins().f64const(Ieee64::NAN);
             let canon_nan = pos.ins().splat(types::F64X2, canon_nan);
-            vector_select(pos, canon_nan);
+            vector_select(pos, canon_nan)
         }
         _ => {
             // Panic if the type given was not an IEEE floating point type.
             panic!("Could not canonicalize NaN: Unexpected result type found.");
         }
     }
-
-    pos.prev_inst(); // Step backwards so the pass does not skip instructions.
 }
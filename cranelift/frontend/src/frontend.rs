@@ -67,7 +67,13 @@ impl FunctionBuilderContext {
         Self::default()
     }
 
-    fn clear(&mut self) {
+    /// Clears the context, as is done automatically when a [`FunctionBuilder`]
+    /// using it is finalized.
+    ///
+    /// A context whose builder was dropped without being finalized, for
+    /// example because translating the function failed midway, must be
+    /// cleared before it's used to build another function.
+    pub fn clear(&mut self) {
         let FunctionBuilderContext {
             ssa,
             status,
@@ -338,6 +344,11 @@ impl<'a> FunctionBuilder<'a> {
         self.srcloc = srcloc;
     }
 
+    /// Get the source location that is assigned to all new instructions.
+    pub fn srcloc(&self) -> ir::SourceLoc {
+        self.srcloc
+    }
+
     /// Creates a new [`Block`] and returns its reference.
     pub fn create_block(&mut self) -> Block {
         let block = self.func.dfg.make_block();
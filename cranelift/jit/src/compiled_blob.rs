@@ -70,6 +70,9 @@ impl CompiledBlob {
                     let pcrel = i32::try_from(((what as isize) - (at as isize)) >> 1).unwrap();
                     unsafe { write_unaligned(at as *mut i32, pcrel) };
                 }
+                Reloc::S390xGOTEnt32Dbl => {
+                    panic!("GOT relocation shouldn't be generated when !is_pic");
+                }
                 Reloc::Arm64Call => {
                     let base = get_address(name);
                     // The instruction is 32 bits long.
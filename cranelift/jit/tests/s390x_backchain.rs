@@ -0,0 +1,220 @@
+//! Walks the stack backchain of JIT-compiled code on s390x, as sampling
+//! profilers do when frame pointers are preserved.
+
+#![cfg(target_arch = "s390x")]
+
+use cranelift_codegen::Context;
+use cranelift_codegen::ir::*;
+use cranelift_codegen::isa::{CallConv, OwnedTargetIsa};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::*;
+use cranelift_jit::*;
+use cranelift_module::*;
+use std::sync::Mutex;
+
+/// The size of the register save area at the bottom of every frame.
+const REG_SAVE_AREA_SIZE: usize = 160;
+
+/// The frame of `leaf`: just the register save area for its call.
+const LEAF_FRAME_SIZE: usize = REG_SAVE_AREA_SIZE;
+
+/// The incoming stack arguments of `middle`: one `i64` plus the register
+/// save area which the tail-call convention places below them.
+const MIDDLE_ARGS_SIZE: usize = 8 + REG_SAVE_AREA_SIZE;
+
+/// The frame of `middle`: its outgoing register save area, a 16-byte stack
+/// slot, and its incoming arguments, which belong to its own frame.
+const MIDDLE_FRAME_SIZE: usize = REG_SAVE_AREA_SIZE + 16 + MIDDLE_ARGS_SIZE;
+
+/// The frame of `outer`: its outgoing register save area and a 32-byte
+/// stack slot.
+const OUTER_FRAME_SIZE: usize = REG_SAVE_AREA_SIZE + 32;
+
+fn isa() -> Option<OwnedTargetIsa> {
+    let mut flag_builder = settings::builder();
+    flag_builder.set("use_colocated_libcalls", "false").unwrap();
+    flag_builder.set("is_pic", "false").unwrap();
+    flag_builder.set("preserve_frame_pointers", "true").unwrap();
+    let isa_builder = cranelift_native::builder().ok()?;
+    isa_builder.finish(settings::Flags::new(flag_builder)).ok()
+}
+
+/// A frame as found by walking the backchain from the leaf's stack pointer.
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    /// The stack pointer while the frame's function runs.
+    sp: usize,
+    /// The backchain word at `sp`, the stack pointer of the caller.
+    backchain: usize,
+    /// The return address saved in slot 14 of the register save area the
+    /// backchain points to.
+    return_address: usize,
+}
+
+/// The frames of `leaf`, `middle` and `outer`, and the backchain copy below
+/// the incoming arguments of `middle`.
+static WALK: Mutex<Option<([Frame; 3], usize)>> = Mutex::new(None);
+
+/// Called by `leaf` with its stack pointer. This only records what it finds,
+/// since panicking here would abort.
+extern "C" fn walk_backchain(sp: usize) {
+    let mut frames = [Frame {
+        sp: 0,
+        backchain: 0,
+        return_address: 0,
+    }; 3];
+    let mut sp = sp;
+    for frame in &mut frames {
+        // SAFETY: with `preserve_frame_pointers`, every frame of generated
+        // code holds the caller's stack pointer in its lowest word, and the
+        // caller's register save area holds the saved registers.
+        unsafe {
+            let backchain = *(sp as *const usize);
+            let return_address = *(backchain as *const usize).add(14);
+            *frame = Frame {
+                sp,
+                backchain,
+                return_address,
+            };
+            sp = backchain;
+        }
+    }
+    // SAFETY: the incoming argument area of `middle` is still live.
+    let copy = unsafe { *((frames[1].backchain - MIDDLE_ARGS_SIZE) as *const usize) };
+    *WALK.lock().unwrap() = Some((frames, copy));
+}
+
+/// Defines `name` with `sig`, building its body with `build`, and returns
+/// its id and code size.
+fn define(
+    module: &mut JITModule,
+    name: &str,
+    sig: Signature,
+    build: impl FnOnce(&mut JITModule, &mut FunctionBuilder),
+) -> (FuncId, usize) {
+    let id = module.declare_function(name, Linkage::Local, &sig).unwrap();
+    let mut ctx = Context::new();
+    ctx.func = Function::with_name_signature(UserFuncName::user(0, id.as_u32()), sig);
+    let mut func_ctx = FunctionBuilderContext::new();
+    {
+        let mut bcx = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+        let block = bcx.create_block();
+        bcx.append_block_params_for_function_params(block);
+        bcx.switch_to_block(block);
+        build(module, &mut bcx);
+        bcx.seal_all_blocks();
+        bcx.finalize();
+    }
+    module.define_function(id, &mut ctx).unwrap();
+    let size = ctx.compiled_code().unwrap().code_buffer().len();
+    (id, size)
+}
+
+fn signature(call_conv: CallConv, params: usize) -> Signature {
+    Signature {
+        params: vec![AbiParam::new(types::I64); params],
+        returns: vec![],
+        call_conv,
+    }
+}
+
+#[test]
+fn backchain_walk_through_tail_call_frames() {
+    let Some(isa) = isa() else {
+        return;
+    };
+    let mut builder = JITBuilder::with_isa(isa, default_libcall_names());
+    builder.symbol("walk_backchain", walk_backchain as *const u8);
+    let mut module = JITModule::new(builder);
+
+    // leaf() passes its stack pointer to `walk_backchain`.
+    let (leaf, _) = define(
+        &mut module,
+        "leaf",
+        signature(CallConv::SystemV, 0),
+        |module, bcx| {
+            let sig = signature(CallConv::SystemV, 1);
+            let walk = module
+                .declare_function("walk_backchain", Linkage::Import, &sig)
+                .unwrap();
+            let walk = module.declare_func_in_func(walk, &mut bcx.func);
+            let sp = bcx.ins().get_stack_pointer(types::I64);
+            bcx.ins().call(walk, &[sp]);
+            bcx.ins().return_(&[]);
+        },
+    );
+
+    // middle(7 x i64) uses the tail-call convention, so its last argument
+    // is passed on the stack, in its own frame.
+    let (middle, middle_size) = define(
+        &mut module,
+        "middle",
+        signature(CallConv::Tail, 7),
+        |module, bcx| {
+            bcx.create_sized_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 16, 3));
+            let leaf = module.declare_func_in_func(leaf, &mut bcx.func);
+            bcx.ins().call(leaf, &[]);
+            bcx.ins().return_(&[]);
+        },
+    );
+
+    // trampoline() tail-calls `middle`, growing its frame for the stack
+    // argument and writing the backchain copy into the overlapping area.
+    let (trampoline, _) = define(
+        &mut module,
+        "trampoline",
+        signature(CallConv::Tail, 0),
+        |module, bcx| {
+            let middle = module.declare_func_in_func(middle, &mut bcx.func);
+            let args: Vec<_> = (0..7).map(|i| bcx.ins().iconst(types::I64, i)).collect();
+            bcx.ins().return_call(middle, &args);
+        },
+    );
+
+    // outer() calls `trampoline`, and is what `middle` returns to.
+    let (outer, outer_size) = define(
+        &mut module,
+        "outer",
+        signature(CallConv::SystemV, 0),
+        |module, bcx| {
+            bcx.create_sized_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 32, 3));
+            let trampoline = module.declare_func_in_func(trampoline, &mut bcx.func);
+            bcx.ins().call(trampoline, &[]);
+            bcx.ins().return_(&[]);
+        },
+    );
+
+    module.finalize_definitions().unwrap();
+    let middle_start = module.get_finalized_function(middle) as usize;
+    let outer_start = module.get_finalized_function(outer) as usize;
+    let code = module.get_finalized_function(outer);
+    // SAFETY: `outer` takes no arguments and returns nothing.
+    let outer_fn = unsafe { std::mem::transmute::<*const u8, extern "C" fn()>(code) };
+    outer_fn();
+
+    let ([leaf, middle, outer], copy) = WALK.lock().unwrap().take().unwrap();
+    assert_eq!(leaf.backchain - leaf.sp, LEAF_FRAME_SIZE, "{leaf:x?}");
+    assert!(
+        (middle_start..middle_start + middle_size).contains(&leaf.return_address),
+        "{leaf:x?}"
+    );
+
+    // The backchain of `middle` skips its incoming arguments and points at
+    // the frame of `outer`, since `trampoline` is gone.
+    assert_eq!(middle.sp, leaf.backchain);
+    assert_eq!(
+        middle.backchain - middle.sp,
+        MIDDLE_FRAME_SIZE,
+        "{middle:x?}"
+    );
+    assert!(
+        (outer_start..outer_start + outer_size).contains(&middle.return_address),
+        "{middle:x?}"
+    );
+    // The copy of the backchain which `trampoline` wrote at the stack
+    // pointer on entry to `middle` matches the backchain of `outer`.
+    assert_eq!(copy, outer.backchain);
+
+    assert_eq!(outer.sp, middle.backchain);
+    assert_eq!(outer.backchain - outer.sp, OUTER_FRAME_SIZE, "{outer:x?}");
+}
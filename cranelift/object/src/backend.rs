@@ -859,6 +859,11 @@ impl ObjectModule {
                 encoding: RelocationEncoding::S390xDbl,
                 size: 32,
             },
+            Reloc::S390xGOTEnt32Dbl => RelocationFlags::Generic {
+                kind: RelocationKind::GotRelative,
+                encoding: RelocationEncoding::S390xDbl,
+                size: 32,
+            },
             Reloc::S390xTlsGd64 => {
                 assert_eq!(
                     self.object.format(),
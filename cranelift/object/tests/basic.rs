@@ -239,3 +239,67 @@ fn reject_nul_byte_symbol_for_data() {
         )
         .unwrap();
 }
+
+#[test]
+#[cfg(all(target_arch = "s390x", target_os = "linux"))]
+fn link_pic_shared_object_s390x() {
+    let mut flag_builder = settings::builder();
+    flag_builder.enable("is_pic").unwrap();
+    let isa_builder = cranelift_codegen::isa::lookup_by_name("s390x-unknown-linux-gnu").unwrap();
+    let isa = isa_builder
+        .finish(settings::Flags::new(flag_builder))
+        .unwrap();
+    let mut module =
+        ObjectModule::new(ObjectBuilder::new(isa, "foo", default_libcall_names()).unwrap());
+    let int = module.target_config().pointer_type();
+
+    // `function` returns the address of an imported data object plus the
+    // result of calling an imported function, so that both the GOT entry
+    // and the PLT entry are needed in the shared object.
+    let mut sig = module.make_signature();
+    sig.returns.push(AbiParam::new(int));
+    let func_id = module
+        .declare_function("function", Linkage::Export, &sig)
+        .unwrap();
+    let callee = module
+        .declare_function("callee", Linkage::Import, &sig)
+        .unwrap();
+    let data_id = module
+        .declare_data("data", Linkage::Import, false, false)
+        .unwrap();
+
+    let mut ctx = Context::new();
+    ctx.func = Function::with_name_signature(UserFuncName::user(0, func_id.as_u32()), sig);
+    let mut func_ctx = FunctionBuilderContext::new();
+    {
+        let mut bcx: FunctionBuilder = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+        let block = bcx.create_block();
+        bcx.switch_to_block(block);
+
+        let local_data = module.declare_data_in_func(data_id, &mut bcx.func);
+        let addr = bcx.ins().symbol_value(int, local_data);
+        let local_callee = module.declare_func_in_func(callee, &mut bcx.func);
+        let call = bcx.ins().call(local_callee, &[]);
+        let result = bcx.inst_results(call)[0];
+        let sum = bcx.ins().iadd(addr, result);
+        bcx.ins().return_(&[sum]);
+    }
+    module.define_function(func_id, &mut ctx).unwrap();
+
+    let bytes = module.finish().emit().unwrap();
+    let dir = std::env::temp_dir().join(format!("cranelift-object-pic-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let object = dir.join("foo.o");
+    let shared = dir.join("libfoo.so");
+    std::fs::write(&object, bytes).unwrap();
+
+    let status = std::process::Command::new("cc")
+        .arg("-shared")
+        .arg("-o")
+        .arg(&shared)
+        .arg(&object)
+        .status()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(status.success(), "linking the shared object failed");
+}
@@ -179,6 +179,14 @@ wasmtime_option_group! {
         /// Enable or disable the use of host signal handlers for traps.
         pub signals_based_traps: Option<bool>,
 
+        /// Length, in bytes, below which constant-length `memory.copy` and
+        /// `memory.fill` are compiled inline. (default: 0)
+        pub bulk_memory_inline_threshold: Option<u32>,
+
+        /// Cache the last function each `call_indirect` called successfully to
+        /// skip repeated signature checks.
+        pub call_indirect_inline_caches: Option<bool>,
+
         /// DEPRECATED: Use `-Cmemory-guard-size=N` instead.
         pub dynamic_memory_guard_size: Option<u64>,
 
@@ -240,6 +248,24 @@ wasmtime_option_group! {
         /// Whether to perform function inlining during compilation.
         pub inlining: Option<bool>,
 
+        /// How all-zero `v128` values are materialized in compiled code.
+        ///
+        /// `function` uses each function's constant pool, `splat` synthesizes
+        /// the zero inline, and `module` loads it from a single constant shared
+        /// by all of a module's functions.
+        #[serde(default)]
+        #[serde(deserialize_with = "crate::opt::cli_parse_wrapper")]
+        pub v128_zero: Option<wasmtime::V128Zero>,
+
+        /// Canonicalize NaNs only in the results of the given classes of
+        /// floating-point operators, separated by `+`.
+        ///
+        /// Classes are `arithmetic`, `fma`, `conversion`, `load`, and `lane`,
+        /// or `all` for every class.
+        #[serde(default)]
+        #[serde(deserialize_with = "crate::opt::cli_parse_wrapper")]
+        pub nan_canonicalization_classes: Option<wasmtime::NanCanonicalizationClasses>,
+
         #[prefixed = "cranelift"]
         #[serde(default)]
         /// Set a cranelift-specific option. Use `wasmtime settings` to see
@@ -312,6 +338,15 @@ wasmtime_option_group! {
         pub unknown_imports_default: Option<bool>,
         /// Enables memory error checking. (see wmemcheck.md for more info)
         pub wmemcheck: Option<bool>,
+        /// Mirror reference-typed locals into a shadow stack and check each
+        /// read of them against it.
+        ///
+        /// `trap` traps on a mismatch, `breakpoint` executes a debugger
+        /// breakpoint and continues, and `unchecked` only keeps the shadow
+        /// stack up to date.
+        #[serde(default)]
+        #[serde(deserialize_with = "crate::opt::cli_parse_wrapper")]
+        pub shadow_stack: Option<wasmtime::ShadowStackCheck>,
         /// Maximum size, in bytes, that a linear memory is allowed to reach.
         ///
         /// Growth beyond this limit will cause `memory.grow` instructions in
@@ -717,6 +752,11 @@ impl CommonOptions {
             enable => config.cranelift_nan_canonicalization(enable),
             true => err,
         }
+        match_feature! {
+            ["cranelift" : self.codegen.nan_canonicalization_classes]
+            classes => config.nan_canonicalization_classes(classes),
+            _ => err,
+        }
         match_feature! {
             ["cranelift" : self.codegen.pcc]
             enable => config.cranelift_pcc(enable),
@@ -822,12 +862,21 @@ impl CommonOptions {
         if let Some(enable) = self.opts.signals_based_traps {
             config.signals_based_traps(enable);
         }
+        if let Some(bytes) = self.opts.bulk_memory_inline_threshold {
+            config.bulk_memory_inline_threshold(bytes);
+        }
+        if let Some(enable) = self.opts.call_indirect_inline_caches {
+            config.call_indirect_inline_caches(enable);
+        }
         if let Some(enable) = self.codegen.native_unwind_info {
             config.native_unwind_info(enable);
         }
         if let Some(enable) = self.codegen.inlining {
             config.compiler_inlining(enable);
         }
+        if let Some(zero) = self.codegen.v128_zero {
+            config.v128_zero(zero);
+        }
 
         // async_stack_size enabled by either async or stack-switching, so
         // cannot directly use match_feature!
@@ -978,6 +1027,11 @@ impl CommonOptions {
             enable => config.wmemcheck(enable),
             true => err,
         }
+        match_feature! {
+            ["shadow-stack" : self.wasm.shadow_stack]
+            check => config.shadow_stack(Some(check)),
+            _ => err,
+        }
 
         Ok(config)
     }
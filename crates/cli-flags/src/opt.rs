@@ -538,6 +538,39 @@ impl WasmtimeOptionValue for wasmtime::Collector {
     }
 }
 
+impl WasmtimeOptionValue for wasmtime::ShadowStackCheck {
+    const VAL_HELP: &'static str = "=trap|breakpoint|unchecked";
+    fn parse(val: Option<&str>) -> Result<Self> {
+        String::parse(val)?.parse()
+    }
+
+    fn display(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl WasmtimeOptionValue for wasmtime::V128Zero {
+    const VAL_HELP: &'static str = "=function|splat|module";
+    fn parse(val: Option<&str>) -> Result<Self> {
+        String::parse(val)?.parse()
+    }
+
+    fn display(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl WasmtimeOptionValue for wasmtime::NanCanonicalizationClasses {
+    const VAL_HELP: &'static str = "=arithmetic+fma+conversion+load+lane|all";
+    fn parse(val: Option<&str>) -> Result<Self> {
+        String::parse(val)?.parse()
+    }
+
+    fn display(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
 impl WasmtimeOptionValue for wasmtime::MpkEnabled {
     const VAL_HELP: &'static str = "[=y|n|auto]";
     fn parse(val: Option<&str>) -> Result<Self> {
@@ -299,7 +299,7 @@ fn bounds_check_field_access(
         // max_memory_size`, since we will end up being out-of-bounds regardless
         // of the given `index`.
         env.before_unconditionally_trapping_memory_access(builder);
-        env.trap(builder, trap);
+        env.unconditional_trap(builder, trap);
         return Unreachable;
     }
 
@@ -309,7 +309,7 @@ fn bounds_check_field_access(
     // native pointer type anyway, so this is an unconditional trap.
     if pointer_bit_width < 64 && offset_and_size >= (1 << pointer_bit_width) {
         env.before_unconditionally_trapping_memory_access(builder);
-        env.trap(builder, trap);
+        env.unconditional_trap(builder, trap);
         return Unreachable;
     }
 
@@ -382,6 +382,54 @@ fn bounds_check_field_access(
         ));
     }
 
+    // Special case for memories using the custom-page-sizes proposal with a
+    // page size of 1 byte whose size can never change at runtime (the minimum
+    // and maximum are equal) and which aren't shared. Such memories can never
+    // use guard pages since their size isn't a multiple of the host page size,
+    // but their byte length is a compile-time constant. This means that the
+    // precise check
+    //
+    //     index + offset + access_size > bound
+    //
+    // can be rewritten to
+    //
+    //     index > bound - (offset + access_size)
+    //
+    // where the right-hand side is a constant. The subtraction can't wrap
+    // because we already handled `offset_and_size > max_memory_size` above.
+    // This avoids both loading the bound and the overflow check on `index +
+    // offset + access_size` that the general case below needs.
+    if let Some(static_size) = tiny_page_static_heap_size(heap, pointer_bit_width) {
+        let adjusted_bound = static_size.checked_sub(offset_and_size).unwrap();
+        let adjusted_bound_value = builder
+            .ins()
+            .iconst(env.pointer_type(), adjusted_bound as i64);
+        if pcc {
+            builder.func.dfg.facts[adjusted_bound_value] =
+                Some(Fact::constant(pointer_bit_width, adjusted_bound));
+        }
+        let oob = make_compare(
+            builder,
+            IntCC::UnsignedGreaterThan,
+            index,
+            Some(0),
+            adjusted_bound_value,
+            Some(0),
+        );
+        return Reachable(explicit_check_oob_condition_and_compute_addr(
+            env,
+            builder,
+            heap,
+            index,
+            offset,
+            access_size,
+            oob_behavior,
+            AddrPcc::static32(heap.pcc_memory_type, static_size),
+            oob,
+            trap,
+        ));
+    }
+
     // Special case for when we can rely on virtual memory, the minimum
     // byte size of this memory fits within the memory reservation, and
     // memory isn't allowed to move. In this situation we know that
@@ -967,3 +1015,20 @@ fn statically_in_bounds(
         })
         .unwrap_or(false)
 }
+
+/// Returns the constant byte size of `heap` if it is a non-shared memory with
+/// a page size of 1 byte whose minimum and maximum sizes are equal.
+///
+/// Such a memory can never be resized so its accessible range is exactly its
+/// declared byte length, which permits bounds checks against a constant. The
+/// size must additionally be representable as a native pointer.
+fn tiny_page_static_heap_size(heap: &HeapData, pointer_bit_width: u16) -> Option<u64> {
+    if heap.memory.page_size_log2 != 0 || heap.memory.shared {
+        return None;
+    }
+    let size = heap.memory.static_heap_size()?;
+    if pointer_bit_width < 64 && size >= (1 << pointer_bit_width) {
+        return None;
+    }
+    Some(size)
+}
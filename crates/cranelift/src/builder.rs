@@ -13,7 +13,9 @@ use std::fmt;
 use std::path;
 use std::sync::Arc;
 use target_lexicon::Triple;
-use wasmtime_environ::{CacheStore, CompilerBuilder, Setting, Tunables};
+use wasmtime_environ::{
+    CacheStore, CompilerBuilder, GasPolicy, ImportCallInterposition, Setting, Tunables,
+};
 
 struct Builder {
     tunables: Option<Tunables>,
@@ -22,6 +24,8 @@ struct Builder {
     cache_store: Option<Arc<dyn CacheStore>>,
     clif_dir: Option<path::PathBuf>,
     wmemcheck: bool,
+    import_call_interpositions: Vec<ImportCallInterposition>,
+    gas_policy: Option<Arc<dyn GasPolicy>>,
 }
 
 #[derive(Clone, Default)]
@@ -45,6 +49,8 @@ pub fn builder(triple: Option<Triple>) -> Result<Box<dyn CompilerBuilder>> {
         cache_store: None,
         clif_dir: None,
         wmemcheck: false,
+        import_call_interpositions: Vec::new(),
+        gas_policy: None,
     }))
 }
 
@@ -109,6 +115,8 @@ impl CompilerBuilder for Builder {
             self.linkopts.clone(),
             self.clif_dir.clone(),
             self.wmemcheck,
+            self.import_call_interpositions.clone(),
+            self.gas_policy.clone(),
         )))
     }
 
@@ -127,6 +135,16 @@ impl CompilerBuilder for Builder {
     fn wmemcheck(&mut self, enable: bool) {
         self.wmemcheck = enable;
     }
+
+    fn import_call_interpositions(&mut self, rules: &[ImportCallInterposition]) -> Result<()> {
+        self.import_call_interpositions = rules.to_vec();
+        Ok(())
+    }
+
+    fn gas_policy(&mut self, policy: Arc<dyn GasPolicy>) -> Result<()> {
+        self.gas_policy = Some(policy);
+        Ok(())
+    }
 }
 
 impl fmt::Debug for Builder {
@@ -1,4 +1,4 @@
-use crate::{Relocation, mach_reloc_to_reloc, mach_trap_to_trap};
+use crate::{Relocation, TranslationSummary, mach_reloc_to_reloc, mach_trap_to_trap};
 use cranelift_codegen::{
     Final, MachBufferFinalized, MachSrcLoc, ValueLabelsRanges, ir, isa::unwind::CfaUnwindInfo,
     isa::unwind::UnwindInfo,
@@ -50,6 +50,9 @@ pub struct CompiledFunctionMetadata {
     pub start_srcloc: FilePos,
     /// End source location.
     pub end_srcloc: FilePos,
+    /// What the translator learned about the WebAssembly function body, or
+    /// `None` for trampolines and other functions without one.
+    pub translation_summary: Option<TranslationSummary>,
 }
 
 /// Compiled function: machine code body, jump table offsets, and unwind information.
@@ -159,6 +162,17 @@ impl CompiledFunction {
     pub fn set_sized_stack_slots(&mut self, slots: ir::StackSlots) {
         self.metadata.sized_stack_slots = slots;
     }
+
+    /// Get the summary of the translation of the function's WebAssembly body,
+    /// if it has one.
+    pub fn translation_summary(&self) -> Option<&TranslationSummary> {
+        self.metadata.translation_summary.as_ref()
+    }
+
+    /// Set the translation summary in the function's metadata.
+    pub fn set_translation_summary(&mut self, summary: TranslationSummary) {
+        self.metadata.translation_summary = Some(summary);
+    }
 }
 
 // Collects an iterator of `InstructionAddressMap` into a `Vec` for insertion
@@ -1,7 +1,7 @@
 use crate::TRAP_INTERNAL_ASSERT;
 use crate::debug::DwarfSectionRelocTarget;
 use crate::func_environ::FuncEnvironment;
-use crate::translate::FuncTranslator;
+use crate::translate::{FuncTranslator, TranslationSummary};
 use crate::{BuiltinFunctionSignatures, builder::LinkOptions, wasm_call_signature};
 use crate::{CompiledFunction, ModuleTextBuilder, array_call_signature};
 use anyhow::{Context as _, Result};
@@ -30,10 +30,10 @@ use std::sync::{Arc, Mutex};
 use wasmparser::{FuncValidatorAllocations, FunctionBody};
 use wasmtime_environ::{
     AddressMapSection, BuiltinFunctionIndex, CacheStore, CompileError, CompiledFunctionBody,
-    DefinedFuncIndex, FlagValue, FuncIndex, FunctionBodyData, FunctionLoc, HostCall,
-    InliningCompiler, ModuleTranslation, ModuleTypesBuilder, PtrSize, RelocationTarget,
-    StackMapSection, StaticModuleIndex, TrapEncodingBuilder, TrapSentinel, TripleExt, Tunables,
-    VMOffsets, WasmFuncType, WasmValType,
+    DefinedFuncIndex, FlagValue, FuncIndex, FunctionBodyData, FunctionLoc, GasPolicy, HostCall,
+    ImportCallInterposition, InliningCompiler, ModuleTranslation, ModuleTypesBuilder, PtrSize,
+    RelocationTarget, StackMapSection, StaticModuleIndex, TrapEncodingBuilder, TrapSentinel,
+    TripleExt, Tunables, VMOffsets, WasmFuncType, WasmResult, WasmValType,
 };
 
 #[cfg(feature = "component-model")]
@@ -65,6 +65,9 @@ struct CompilerContext {
     incremental_cache_ctx: Option<IncrementalCacheContext>,
     validator_allocations: FuncValidatorAllocations,
     abi: Option<Abi>,
+    /// The summary of the translation of the function being compiled, handed
+    /// to its `CompiledFunction` once compilation finishes.
+    translation_summary: Option<TranslationSummary>,
 }
 
 impl Default for CompilerContext {
@@ -75,6 +78,7 @@ impl Default for CompilerContext {
             incremental_cache_ctx: None,
             validator_allocations: Default::default(),
             abi: None,
+            translation_summary: None,
         }
     }
 }
@@ -90,6 +94,8 @@ pub struct Compiler {
     clif_dir: Option<path::PathBuf>,
     #[cfg(feature = "wmemcheck")]
     pub(crate) wmemcheck: bool,
+    import_call_interpositions: Vec<ImportCallInterposition>,
+    gas_policy: Option<Arc<dyn GasPolicy>>,
 }
 
 impl Drop for Compiler {
@@ -128,6 +134,8 @@ impl Compiler {
         linkopts: LinkOptions,
         clif_dir: Option<path::PathBuf>,
         wmemcheck: bool,
+        import_call_interpositions: Vec<ImportCallInterposition>,
+        gas_policy: Option<Arc<dyn GasPolicy>>,
     ) -> Compiler {
         let _ = wmemcheck;
         Compiler {
@@ -139,78 +147,54 @@ impl Compiler {
             clif_dir,
             #[cfg(feature = "wmemcheck")]
             wmemcheck,
+            import_call_interpositions,
+            gas_policy,
         }
     }
 
-    /// Perform an indirect call from Cranelift-generated code to native code in
-    /// Wasmtime itself.
+    /// Translates the body of the defined function `func_index` to CLIF in
+    /// `func`, which should be empty.
     ///
-    /// For native platforms this is a simple `call_indirect` instruction but
-    /// for the Pulley backend this is special as it's transitioning from
-    /// Cranelift-generated bytecode to native code on the host. That requires a
-    /// special opcode in the interpreter and is modeled slightly differently in
-    /// Cranelift IR.
-    fn call_indirect_host(
+    /// Returns the summary of the translation along with whether the function
+    /// needs a GC heap.
+    pub(crate) fn translate_function(
         &self,
-        builder: &mut FunctionBuilder<'_>,
-        hostcall: impl Into<HostCall>,
-        sig: ir::SigRef,
-        addr: Value,
-        args: &[Value],
-    ) -> ir::Inst {
-        let signature = &builder.func.dfg.signatures[sig];
-
-        // When calling the host we should always be using the platform's
-        // default calling convention since it'll be calling Rust code in
-        // Wasmtime itself.
-        assert_eq!(signature.call_conv, self.isa.default_call_conv());
-
-        // If this target is actually pulley then the goal is to emit the custom
-        // `call_indirect_host` pulley opcode. That's encoded in Cranelift as a
-        // `call` instruction where the name is `colocated: false`. This will
-        // force a pulley-specific relocation to get emitted in addition to
-        // using the `call_indirect_host` instruction.
-        if self.isa.triple().is_pulley() {
-            let mut new_signature = signature.clone();
-            new_signature
-                .params
-                .insert(0, ir::AbiParam::new(self.isa.pointer_type()));
-            let new_sig = builder.func.import_signature(new_signature);
-            let name = ir::ExternalName::User(builder.func.declare_imported_user_function(
-                ir::UserExternalName {
-                    namespace: crate::NS_PULLEY_HOSTCALL,
-                    index: hostcall.into().index(),
-                },
-            ));
-            let func = builder.func.import_function(ir::ExtFuncData {
-                name,
-                signature: new_sig,
-                // This is the signal that a special `call_indirect_host`
-                // opcode is used to jump from pulley to the host.
-                colocated: false,
-            });
-            let mut raw_args = vec![addr];
-            raw_args.extend_from_slice(args);
-            return builder.ins().call(func, &raw_args);
-        }
-
-        builder.ins().call_indirect(sig, addr, args)
-    }
-}
-
-impl wasmtime_environ::Compiler for Compiler {
-    fn inlining_compiler(&self) -> Option<&dyn wasmtime_environ::InliningCompiler> {
-        Some(self)
+        translation: &ModuleTranslation<'_>,
+        types: &ModuleTypesBuilder,
+        func_index: DefinedFuncIndex,
+        input: FunctionBodyData<'_>,
+        func_translator: &mut FuncTranslator,
+        validator_allocations: FuncValidatorAllocations,
+        func: &mut ir::Function,
+    ) -> WasmResult<(TranslationSummary, bool)> {
+        let FunctionBodyData { validator, body } = input;
+        let mut validator = validator.into_validator(validator_allocations);
+        self.translate_function_with(
+            translation,
+            types,
+            func_index,
+            body.range().start,
+            func,
+            |func, func_env| func_translator.translate_body(&mut validator, body, func, func_env),
+        )
     }
 
-    fn compile_function(
+    /// Same as [`Compiler::translate_function`] except that the body is
+    /// translated by `translate_body` once `func` and the `FuncEnvironment`
+    /// for it have been set up. `body_offset` is the offset of the body within
+    /// the module, against which branch hints are resolved.
+    pub(crate) fn translate_function_with(
         &self,
         translation: &ModuleTranslation<'_>,
-        func_index: DefinedFuncIndex,
-        input: FunctionBodyData<'_>,
         types: &ModuleTypesBuilder,
-        symbol: &str,
-    ) -> Result<CompiledFunctionBody, CompileError> {
+        func_index: DefinedFuncIndex,
+        body_offset: usize,
+        func: &mut ir::Function,
+        translate_body: impl FnOnce(
+            &mut ir::Function,
+            &mut FuncEnvironment<'_>,
+        ) -> WasmResult<TranslationSummary>,
+    ) -> WasmResult<(TranslationSummary, bool)> {
         let isa = &*self.isa;
         let module = &translation.module;
         let func_index = module.func_index(func_index);
@@ -219,20 +203,18 @@ impl wasmtime_environ::Compiler for Compiler {
             .unwrap_module_type_index();
         let wasm_func_ty = types[sig].unwrap_func();
 
-        let mut compiler = self.function_compiler();
-
-        let context = &mut compiler.cx.codegen_context;
-        context.func.signature = wasm_call_signature(isa, wasm_func_ty, &self.tunables);
-        context.func.name = UserFuncName::User(UserExternalName {
+        func.signature = wasm_call_signature(isa, wasm_func_ty, &self.tunables);
+        func.name = UserFuncName::User(UserExternalName {
             namespace: crate::NS_WASM_FUNC,
             index: func_index.as_u32(),
         });
 
         if self.tunables.generate_native_debuginfo {
-            context.func.collect_debug_info();
+            func.collect_debug_info();
         }
 
         let mut func_env = FuncEnvironment::new(self, translation, types, wasm_func_ty);
+        func_env.set_call_indirect_caches(func_index);
 
         // The `stack_limit` global value below is the implementation of stack
         // overflow checks in Wasmtime.
@@ -267,36 +249,134 @@ impl wasmtime_environ::Compiler for Compiler {
         // the embedder should cause wasm to trap before it reaches that
         // (ensuring the host has enough space as well for its functionality).
         if !isa.triple().is_pulley() {
-            let vmctx = context
-                .func
-                .create_global_value(ir::GlobalValueData::VMContext);
-            let interrupts_ptr = context.func.create_global_value(ir::GlobalValueData::Load {
+            let vmctx = func.create_global_value(ir::GlobalValueData::VMContext);
+            let interrupts_ptr = func.create_global_value(ir::GlobalValueData::Load {
                 base: vmctx,
                 offset: i32::from(func_env.offsets.ptr.vmctx_store_context()).into(),
                 global_type: isa.pointer_type(),
                 flags: MemFlags::trusted().with_readonly(),
             });
-            let stack_limit = context.func.create_global_value(ir::GlobalValueData::Load {
+            let stack_limit = func.create_global_value(ir::GlobalValueData::Load {
                 base: interrupts_ptr,
                 offset: i32::from(func_env.offsets.ptr.vmstore_context_stack_limit()).into(),
                 global_type: isa.pointer_type(),
                 flags: MemFlags::trusted(),
             });
             if self.tunables.signals_based_traps {
-                context.func.stack_limit = Some(stack_limit);
+                func.stack_limit = Some(stack_limit);
             } else {
                 func_env.stack_limit_at_function_entry = Some(stack_limit);
             }
         }
-        let FunctionBodyData { validator, body } = input;
-        let mut validator =
-            validator.into_validator(mem::take(&mut compiler.cx.validator_allocations));
-        compiler.cx.func_translator.translate_body(
-            &mut validator,
-            body.clone(),
-            &mut context.func,
-            &mut func_env,
+        func_env.set_branch_hints(func_index, body_offset);
+        let summary = translate_body(func, &mut func_env)?;
+        Ok((summary, func_env.needs_gc_heap()))
+    }
+
+    /// Rules interposing on direct calls to imported functions, see
+    /// `CompilerBuilder::import_call_interpositions`.
+    pub(crate) fn import_call_interpositions(&self) -> &[ImportCallInterposition] {
+        &self.import_call_interpositions
+    }
+
+    /// The policy assigning the fuel consumed by each operator, see
+    /// `CompilerBuilder::gas_policy`.
+    pub(crate) fn gas_policy(&self) -> Option<&dyn GasPolicy> {
+        self.gas_policy.as_deref()
+    }
+
+    /// Perform an indirect call from Cranelift-generated code to native code in
+    /// Wasmtime itself.
+    ///
+    /// For native platforms this is a simple `call_indirect` instruction but
+    /// for the Pulley backend this is special as it's transitioning from
+    /// Cranelift-generated bytecode to native code on the host. That requires a
+    /// special opcode in the interpreter and is modeled slightly differently in
+    /// Cranelift IR.
+    fn call_indirect_host(
+        &self,
+        builder: &mut FunctionBuilder<'_>,
+        hostcall: impl Into<HostCall>,
+        sig: ir::SigRef,
+        addr: Value,
+        args: &[Value],
+    ) -> ir::Inst {
+        let signature = &builder.func.dfg.signatures[sig];
+
+        // When calling the host we should always be using the platform's
+        // default calling convention since it'll be calling Rust code in
+        // Wasmtime itself.
+        assert_eq!(signature.call_conv, self.isa.default_call_conv());
+
+        // If this target is actually pulley then the goal is to emit the custom
+        // `call_indirect_host` pulley opcode. That's encoded in Cranelift as a
+        // `call` instruction where the name is `colocated: false`. This will
+        // force a pulley-specific relocation to get emitted in addition to
+        // using the `call_indirect_host` instruction.
+        if self.isa.triple().is_pulley() {
+            let mut new_signature = signature.clone();
+            new_signature
+                .params
+                .insert(0, ir::AbiParam::new(self.isa.pointer_type()));
+            let new_sig = builder.func.import_signature(new_signature);
+            let name = ir::ExternalName::User(builder.func.declare_imported_user_function(
+                ir::UserExternalName {
+                    namespace: crate::NS_PULLEY_HOSTCALL,
+                    index: hostcall.into().index(),
+                },
+            ));
+            let func = builder.func.import_function(ir::ExtFuncData {
+                name,
+                signature: new_sig,
+                // This is the signal that a special `call_indirect_host`
+                // opcode is used to jump from pulley to the host.
+                colocated: false,
+            });
+            let mut raw_args = vec![addr];
+            raw_args.extend_from_slice(args);
+            return builder.ins().call(func, &raw_args);
+        }
+
+        builder.ins().call_indirect(sig, addr, args)
+    }
+}
+
+impl wasmtime_environ::Compiler for Compiler {
+    fn inlining_compiler(&self) -> Option<&dyn wasmtime_environ::InliningCompiler> {
+        Some(self)
+    }
+
+    fn compile_function(
+        &self,
+        translation: &ModuleTranslation<'_>,
+        func_index: DefinedFuncIndex,
+        input: FunctionBodyData<'_>,
+        types: &ModuleTypesBuilder,
+        symbol: &str,
+    ) -> Result<CompiledFunctionBody, CompileError> {
+        let isa = &*self.isa;
+        let mut compiler = self.function_compiler();
+        let cx = &mut compiler.cx;
+        let (summary, needs_gc_heap) = self.translate_function(
+            translation,
+            types,
+            func_index,
+            input,
+            &mut cx.func_translator,
+            mem::take(&mut cx.validator_allocations),
+            &mut cx.codegen_context.func,
         )?;
+        log::trace!(
+            "`{symbol}` max operand stack depth {}, max control depth {}, {} operators \
+             (loops: {}, calls: {}, memory: {})",
+            summary.max_stack_depth,
+            summary.max_control_depth,
+            summary.num_operators,
+            summary.has_loops,
+            summary.has_calls,
+            summary.accesses_memory,
+        );
+        compiler.cx.translation_summary = Some(summary);
 
         if self.tunables.inlining {
             compiler
@@ -312,7 +392,7 @@ impl wasmtime_environ::Compiler for Compiler {
 
         Ok(CompiledFunctionBody {
             code: Box::new(Some(compiler.cx)),
-            needs_gc_heap: func_env.needs_gc_heap(),
+            needs_gc_heap,
         })
     }
 
@@ -475,8 +555,26 @@ impl wasmtime_environ::Compiler for Compiler {
         funcs: &[(String, Box<dyn Any + Send + Sync>)],
         resolve_reloc: &dyn Fn(usize, RelocationTarget) -> usize,
     ) -> Result<Vec<(SymbolId, FunctionLoc)>> {
-        let mut builder =
-            ModuleTextBuilder::new(obj, self, self.isa.text_section_builder(funcs.len()));
+        // Constants shared between functions are placed after all functions,
+        // in the order they're first referenced, so their label indices
+        // follow the functions' indices.
+        let mut constants = Vec::new();
+        for (_, func) in funcs {
+            let func = func.downcast_ref::<CompiledFunction>().unwrap();
+            for r in func.relocations() {
+                if let RelocationTarget::ModuleConstant(c) = r.reloc_target {
+                    if !constants.contains(&c) {
+                        constants.push(c);
+                    }
+                }
+            }
+        }
+
+        let mut builder = ModuleTextBuilder::new(
+            obj,
+            self,
+            self.isa.text_section_builder(funcs.len() + constants.len()),
+        );
         if self.linkopts.force_jump_veneers {
             builder.force_veneers();
         }
@@ -487,7 +585,12 @@ impl wasmtime_environ::Compiler for Compiler {
         let mut ret = Vec::with_capacity(funcs.len());
         for (i, (sym, func)) in funcs.iter().enumerate() {
             let func = func.downcast_ref::<CompiledFunction>().unwrap();
-            let (sym, range) = builder.append_func(&sym, func, |idx| resolve_reloc(i, idx));
+            let (sym, range) = builder.append_func(&sym, func, |idx| match idx {
+                RelocationTarget::ModuleConstant(c) => {
+                    funcs.len() + constants.iter().position(|other| *other == c).unwrap()
+                }
+                _ => resolve_reloc(i, idx),
+            });
             if self.tunables.generate_address_map {
                 let addr = func.address_map();
                 addrs.push(range.clone(), &addr.instructions);
@@ -506,6 +609,10 @@ impl wasmtime_environ::Compiler for Compiler {
             ret.push((sym, info));
         }
 
+        for constant in constants {
+            builder.append_constant(constant);
+        }
+
         builder.finish();
 
         if self.tunables.generate_address_map {
@@ -1224,6 +1331,9 @@ impl FunctionCompiler<'_> {
 
         compiled_function
             .set_sized_stack_slots(std::mem::take(&mut context.func.sized_stack_slots));
+        if let Some(summary) = self.cx.translation_summary.take() {
+            compiled_function.set_translation_summary(summary);
+        }
         self.compiler.contexts.lock().unwrap().push(self.cx);
 
         Ok(compiled_function)
@@ -1370,3 +1480,73 @@ fn save_last_wasm_exit_fp_and_pc(
         ptr.vmstore_context_last_wasm_exit_pc(),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::settings;
+    use wasmtime_environ::{Compiler as _, ModuleEnvironment};
+
+    #[test]
+    fn compiled_functions_keep_their_translation_summary() {
+        let wasm = wat::parse_str(
+            r#"
+                (func (param i32) (result i32)
+                  loop
+                  end
+                  local.get 0
+                  i32.const 1
+                  i32.add)
+            "#,
+        )
+        .unwrap();
+        let isa = cranelift_native::builder()
+            .unwrap()
+            .finish(settings::Flags::new(settings::builder()))
+            .unwrap();
+        let compiler = Compiler::new(
+            Tunables::default_host(),
+            isa,
+            None,
+            LinkOptions::default(),
+            None,
+            false,
+            Vec::new(),
+            None,
+        );
+
+        let mut validator = wasmparser::Validator::new();
+        let mut types = ModuleTypesBuilder::new(&validator);
+        let mut translation =
+            ModuleEnvironment::new(compiler.tunables(), &mut validator, &mut types)
+                .translate(wasmparser::Parser::new(0), &wasm)
+                .unwrap();
+        let inputs = mem::take(&mut translation.function_body_inputs);
+        let (index, input) = inputs.into_iter().next().unwrap();
+        let body = input.body.clone();
+
+        let mut func_body = compiler
+            .compile_function(&translation, index, input, &types, "f")
+            .unwrap();
+        compiler
+            .finish_compiling(&mut func_body, Some(body), "f")
+            .unwrap();
+        let compiled = func_body.code.downcast_ref::<CompiledFunction>().unwrap();
+        let summary = compiled.translation_summary().unwrap();
+        assert_eq!(summary.max_stack_depth, 2);
+        assert_eq!(summary.num_operators, 6);
+        assert!(summary.has_loops);
+        assert!(!summary.has_calls);
+
+        // Trampolines have no WebAssembly body, and contexts reused for them
+        // don't carry over the summary of an earlier function.
+        let mut trampoline = compiler
+            .compile_array_to_wasm_trampoline(&translation, &types, index, "t")
+            .unwrap();
+        compiler
+            .finish_compiling(&mut trampoline, None, "t")
+            .unwrap();
+        let compiled = trampoline.code.downcast_ref::<CompiledFunction>().unwrap();
+        assert!(compiled.translation_summary().is_none());
+    }
+}
@@ -1,5 +1,7 @@
 mod gc;
 
+use crate::Reachability;
+use crate::bounds_checks::{BoundsCheck, bounds_check_and_compute_addr};
 use crate::compiler::Compiler;
 use crate::translate::{
     FuncTranslationStacks, GlobalVariable, Heap, HeapData, StructFieldsVec, TableData, TableSize,
@@ -19,13 +21,17 @@ use cranelift_entity::{EntityRef, PrimaryMap, SecondaryMap};
 use cranelift_frontend::Variable;
 use cranelift_frontend::{FuncInstBuilder, FunctionBuilder};
 use smallvec::SmallVec;
+use std::collections::HashMap;
 use std::mem;
 use wasmparser::{Operator, WasmFeatures};
 use wasmtime_environ::{
-    BuiltinFunctionIndex, DataIndex, ElemIndex, EngineOrModuleTypeIndex, FuncIndex, GlobalIndex,
-    IndexType, Memory, MemoryIndex, Module, ModuleInternedTypeIndex, ModuleTranslation,
-    ModuleTypesBuilder, PtrSize, Table, TableIndex, TripleExt, Tunables, TypeConvert, TypeIndex,
-    VMOffsets, WasmCompositeInnerType, WasmFuncType, WasmHeapTopType, WasmHeapType, WasmRefType,
+    BuiltinFunctionIndex, DataIndex, DefinedFuncIndex, ElemIndex, EngineOrModuleTypeIndex,
+    EntityIndex, FuncIndex, GasPolicy, GlobalIndex, ImportCallAction, IndexType, Initializer,
+    InterruptCheckPlacement, Memory, MemoryAccessInstrumentation, MemoryIndex, Module,
+    ModuleConstant, ModuleInternedTypeIndex, ModuleTranslation, ModuleTypesBuilder,
+    NanCanonicalizationClasses, PtrSize, ShadowStackCheck, Table, TableIndex, TripleExt, Tunables,
+    TypeConvert, TypeIndex, UnreachableLowering, Unsigned, V128Zero, VMOffsets,
+    WasmCompositeInnerType, WasmError, WasmFuncType, WasmHeapTopType, WasmHeapType, WasmRefType,
     WasmResult, WasmValType,
 };
 use wasmtime_environ::{FUNCREF_INIT_BIT, FUNCREF_MASK};
@@ -163,6 +169,19 @@ pub struct FuncEnvironment<'module_environment> {
 
     fuel_consumed: i64,
 
+    /// The policy assigning the fuel consumed by each operator in place of
+    /// the default costs, see `CompilerBuilder::gas_policy`.
+    gas_policy: Option<Box<dyn GasPolicy>>,
+
+    /// Whether the function being translated makes no calls to other
+    /// WebAssembly functions, used to elide its entry fuel and epoch checks
+    /// with `InterruptCheckPlacement::NoneForLeafFunctions`.
+    is_leaf_function: bool,
+
+    /// The number of reachable operators translated since the last fuel or
+    /// epoch check, used with `InterruptCheckPlacement::PerNOperators`.
+    operators_since_interrupt_check: u32,
+
     /// A `GlobalValue` in CLIF which represents the stack limit.
     ///
     /// Typically this resides in the `stack_limit` value of `ir::Function` but
@@ -171,6 +190,54 @@ pub struct FuncEnvironment<'module_environment> {
     /// always present even if this is a "leaf" function, as we have to call
     /// into the host to trap when signal handlers are disabled.
     pub(crate) stack_limit_at_function_entry: Option<ir::GlobalValue>,
+
+    /// The all-zero `v128` value shared by every `v128` local declared in this
+    /// function, created on first use in the entry block.
+    v128_zero: Option<ir::Value>,
+
+    /// The shadow stack slot of each local mirrored by the shadow stack, see
+    /// `Tunables::shadow_stack`.
+    shadow_locals: SecondaryMap<Variable, PackedOption<ir::StackSlot>>,
+
+    /// The index of the next `call_indirect` inline cache this function may
+    /// allocate, or `None` if inline caches are disabled, see
+    /// `Tunables::call_indirect_inline_caches`.
+    next_call_indirect_cache: Option<u32>,
+
+    /// The branch hints of this function along with the offset of its body
+    /// within the module, see `set_branch_hints`.
+    branch_hints: Option<(&'module_environment HashMap<u32, bool>, usize)>,
+
+    /// Instrumentation which tests install around the function's entry,
+    /// exits and body, see `FunctionHooks`.
+    #[cfg(test)]
+    pub(crate) function_hooks: Option<Box<dyn FunctionHooks + 'module_environment>>,
+}
+
+/// Instrumentation of every entry to and exit from a function, such as
+/// call-depth accounting, which tests install in a `FuncEnvironment` to
+/// exercise `translate_function_prologue` and `translate_function_epilogue`.
+#[cfg(test)]
+pub(crate) trait FunctionHooks {
+    /// Translates the instrumentation of the function's entry, see
+    /// `FuncEnvironment::translate_function_prologue`.
+    fn prologue(&mut self, builder: &mut FunctionBuilder) -> WasmResult<()>;
+
+    /// Translates the instrumentation of an exit from the function, see
+    /// `FuncEnvironment::translate_function_epilogue`.
+    fn epilogue(&mut self, builder: &mut FunctionBuilder) -> WasmResult<()>;
+
+    /// Translates instrumentation preceding the function's body, see
+    /// `FuncEnvironment::before_translate_function`.
+    fn before_body(&mut self, _builder: &mut FunctionBuilder) -> WasmResult<()> {
+        Ok(())
+    }
+
+    /// Translates instrumentation following the function's body, see
+    /// `FuncEnvironment::after_translate_function`.
+    fn after_body(&mut self, _builder: &mut FunctionBuilder) -> WasmResult<()> {
+        Ok(())
+    }
 }
 
 impl<'module_environment> FuncEnvironment<'module_environment> {
@@ -218,15 +285,75 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             epoch_ptr_var: Variable::reserved_value(),
 
             // Start with at least one fuel being consumed because even empty
-            // functions should consume at least some fuel.
-            fuel_consumed: 1,
+            // functions should consume at least some fuel. Gas policies charge
+            // exactly the operators executed, so there's no such base cost.
+            fuel_consumed: i64::from(compiler.gas_policy().is_none()),
+            gas_policy: compiler.gas_policy().map(|p| p.for_function()),
+            is_leaf_function: false,
+            operators_since_interrupt_check: 0,
 
             translation,
 
             stack_limit_at_function_entry: None,
+
+            v128_zero: None,
+            shadow_locals: SecondaryMap::default(),
+            next_call_indirect_cache: None,
+            branch_hints: None,
+            #[cfg(test)]
+            function_hooks: None,
         }
     }
 
+    /// Reserves the `call_indirect` inline caches of the defined function
+    /// `index` for use by this translation.
+    ///
+    /// This is a no-op unless `Tunables::call_indirect_inline_caches` is
+    /// enabled.
+    pub(crate) fn set_call_indirect_caches(&mut self, index: DefinedFuncIndex) {
+        self.next_call_indirect_cache = self
+            .translation
+            .call_indirect_cache_starts
+            .get(index)
+            .copied();
+    }
+
+    /// Makes the hints of the `metadata.code.branch_hint` custom section for
+    /// the function `index`, whose body starts at `body_offset` within the
+    /// module, available to `branch_hint`.
+    pub(crate) fn set_branch_hints(&mut self, index: FuncIndex, body_offset: usize) {
+        self.branch_hints = self
+            .translation
+            .branch_hints
+            .get(&index)
+            .map(|hints| (hints, body_offset));
+    }
+
+    /// Returns whether the branch of the `if` or `br_if` at offset `pos`
+    /// within the module is likely taken, if the function has a hint for it.
+    pub(crate) fn branch_hint(&self, pos: usize) -> Option<bool> {
+        let (hints, body_offset) = self.branch_hints?;
+        let offset = u32::try_from(pos.checked_sub(body_offset)?).ok()?;
+        hints.get(&offset).copied()
+    }
+
+    /// Allocates per-call-site data of `size` bytes aligned to `align` bytes
+    /// within the `VMContext`, returning its offset from the `VMContext`.
+    ///
+    /// Each call site which may use such data owns one pointer-sized slot,
+    /// reserved when the module was translated, so `size` and `align` may not
+    /// exceed the target's pointer size. Returns `None` if no call-site data
+    /// is reserved for this function.
+    pub fn alloc_call_site_data(&mut self, size: u8, align: u8) -> Option<u32> {
+        let ptr_size = self.offsets.ptr.size();
+        assert!(size <= ptr_size);
+        assert!(align.is_power_of_two() && align <= ptr_size);
+        let index = self.next_call_indirect_cache.as_mut()?;
+        let offset = self.offsets.vmctx_call_indirect_cache(*index);
+        *index += 1;
+        Some(offset)
+    }
+
     pub(crate) fn pointer_type(&self) -> ir::Type {
         self.isa.pointer_type()
     }
@@ -341,7 +468,9 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         debug_assert!(self.fuel_var.is_reserved_value());
         self.fuel_var = builder.declare_var(ir::types::I64);
         self.fuel_load_into_var(builder);
-        self.fuel_check(builder);
+        if self.interrupt_check_on_entry() {
+            self.fuel_check(builder);
+        }
     }
 
     fn fuel_function_exit(&mut self, builder: &mut FunctionBuilder<'_>) {
@@ -365,23 +494,11 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             return;
         }
 
-        self.fuel_consumed += match op {
-            // Nop and drop generate no code, so don't consume fuel for them.
-            Operator::Nop | Operator::Drop => 0,
-
-            // Control flow may create branches, but is generally cheap and
-            // free, so don't consume fuel. Note the lack of `if` since some
-            // cost is incurred with the conditional check.
-            Operator::Block { .. }
-            | Operator::Loop { .. }
-            | Operator::Unreachable
-            | Operator::Return
-            | Operator::Else
-            | Operator::End => 0,
-
-            // everything else, just call it one operation.
-            _ => 1,
+        let cost = match &mut self.gas_policy {
+            Some(policy) => gas_to_fuel(policy.charge(op)),
+            None => default_fuel_cost(op),
         };
+        self.fuel_consumed = self.fuel_consumed.saturating_add(cost);
 
         match op {
             // Exiting a function (via a return or unreachable) or otherwise
@@ -393,14 +510,28 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             // Before this we need to update the fuel counter from our own cost
             // leading up to this function call, and then we can store
             // `self.fuel_var` into `VMStoreContext`.
-            Operator::Unreachable
-            | Operator::Return
-            | Operator::CallIndirect { .. }
+            Operator::Unreachable | Operator::Return => {
+                self.fuel_increment_var(builder);
+                self.fuel_save_from_var(builder);
+            }
+
+            // Calls additionally check the remaining fuel first if the gas
+            // policy asks for it, in which case the check settles the fuel
+            // consumed so far.
+            Operator::CallIndirect { .. }
             | Operator::Call { .. }
             | Operator::ReturnCall { .. }
             | Operator::ReturnCallRef { .. }
             | Operator::ReturnCallIndirect { .. } => {
-                self.fuel_increment_var(builder);
+                if self
+                    .gas_policy
+                    .as_ref()
+                    .is_some_and(|policy| policy.check_before_calls())
+                {
+                    self.fuel_check(builder);
+                } else {
+                    self.fuel_increment_var(builder);
+                }
                 self.fuel_save_from_var(builder);
             }
 
@@ -578,9 +709,19 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         // sufficient. Then, combined with checks at every backedge
         // (loop) the longest runtime between checks is bounded by the
         // straightline length of any function body.
-        let continuation_block = builder.create_block();
-        let cur_epoch_value = self.epoch_load_current(builder);
-        self.epoch_check_full(builder, cur_epoch_value, continuation_block);
+        //
+        // Leaf functions are the exception: they can't extend that tree, so
+        // the check their caller made before calling them is enough if the
+        // embedder opted into eliding their entry checks.
+        if self.interrupt_check_on_entry() {
+            let continuation_block = builder.create_block();
+            let cur_epoch_value = self.epoch_load_current(builder);
+            self.epoch_check_full(builder, cur_epoch_value, continuation_block);
+        } else {
+            // The cached deadline still needs a value for later checks at loop
+            // headers, so load it without checking.
+            self.epoch_load_deadline_into_var(builder);
+        }
     }
 
     #[cfg(feature = "wmemcheck")]
@@ -682,6 +823,19 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         builder.switch_to_block(new_epoch_block);
     }
 
+    /// Loads the current epoch deadline from `VMStoreContext` into
+    /// `self.epoch_deadline_var`.
+    fn epoch_load_deadline_into_var(&mut self, builder: &mut FunctionBuilder) {
+        let vmstore_ctx = self.get_vmstore_context_ptr(builder);
+        let deadline = builder.ins().load(
+            ir::types::I64,
+            ir::MemFlags::trusted(),
+            vmstore_ctx,
+            ir::immediates::Offset32::new(self.offsets.ptr.vmstore_context_epoch_deadline() as i32),
+        );
+        builder.def_var(self.epoch_deadline_var, deadline);
+    }
+
     fn epoch_check_full(
         &mut self,
         builder: &mut FunctionBuilder,
@@ -691,14 +845,7 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         // We keep the deadline cached in a register to speed the checks
         // in the common case (between epoch ticks) but we want to do a
         // precise check here by reloading the cache first.
-        let vmstore_ctx = self.get_vmstore_context_ptr(builder);
-        let deadline = builder.ins().load(
-            ir::types::I64,
-            ir::MemFlags::trusted(),
-            vmstore_ctx,
-            ir::immediates::Offset32::new(self.offsets.ptr.vmstore_context_epoch_deadline() as i32),
-        );
-        builder.def_var(self.epoch_deadline_var, deadline);
+        self.epoch_load_deadline_into_var(builder);
         self.epoch_check_cached(builder, cur_epoch_value, continuation_block);
 
         let new_epoch = self.builtin_functions.new_epoch(builder.func);
@@ -881,14 +1028,18 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         builder.ins().call(free_start, &[vmctx]);
     }
 
-    #[cfg(feature = "wmemcheck")]
-    fn current_func_name(&self, builder: &mut FunctionBuilder) -> Option<&str> {
-        let func_index = match &builder.func.name {
+    fn current_func_index(&self, builder: &FunctionBuilder) -> FuncIndex {
+        match &builder.func.name {
             ir::UserFuncName::User(user) => FuncIndex::from_u32(user.index),
             _ => {
                 panic!("function name not a UserFuncName::User as expected")
             }
-        };
+        }
+    }
+
+    #[cfg(feature = "wmemcheck")]
+    fn current_func_name(&self, builder: &mut FunctionBuilder) -> Option<&str> {
+        let func_index = self.current_func_index(builder);
         self.translation
             .debuginfo
             .name_section
@@ -1587,6 +1738,22 @@ impl FuncEnvironment<'_> {
     }
 }
 
+/// How a direct call to an imported function is translated, as decided by
+/// `FuncEnvironment::on_translate_call_import`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallDisposition {
+    /// Call the imported function as usual.
+    Proceed,
+    /// Call this function, which has the same type, instead.
+    RedirectTo(FuncIndex),
+    /// Call `pre` with the call's arguments before the call and `post` with
+    /// its results after it.
+    WrapWith {
+        pre: Option<FuncIndex>,
+        post: Option<FuncIndex>,
+    },
+}
+
 struct Call<'a, 'func, 'module_env> {
     builder: &'a mut FunctionBuilder<'func>,
     env: &'a mut FuncEnvironment<'module_env>,
@@ -1745,6 +1912,22 @@ impl<'a, 'func, 'module_env> Call<'a, 'func, 'module_env> {
             cold_blocks,
         );
 
+        // Calls through untyped tables always need a runtime signature check,
+        // which an inline cache can skip if this site has already checked
+        // this exact funcref.
+        if self.env.module.tables[table_index].ref_type.heap_type == WasmHeapType::Func {
+            let ptr_size = self.env.offsets.ptr.size();
+            if let Some(cache) = self.env.alloc_call_site_data(ptr_size, ptr_size) {
+                return Ok(Some(self.cached_check_and_load_code_and_callee_vmctx(
+                    features,
+                    table_index,
+                    ty_index,
+                    funcref_ptr,
+                    cache,
+                )));
+            }
+        }
+
         // If necessary, check the signature.
         let check =
             self.check_indirect_call_type_signature(features, table_index, ty_index, funcref_ptr);
@@ -1779,6 +1962,55 @@ impl<'a, 'func, 'module_env> Call<'a, 'func, 'module_env> {
         Ok(Some(self.load_code_and_vmctx(funcref_ptr, trap_code)))
     }
 
+    /// Like `check_and_load_code_and_callee_vmctx` but consults the inline
+    /// cache at `cache_offset` within the caller's vmctx first.
+    ///
+    /// The cache holds the last funcref which passed this site's signature
+    /// check. A funcref's type never changes, so if the table entry is that
+    /// same funcref then the check is skipped. Otherwise the check is
+    /// performed and, if it passes, its funcref recorded in the cache.
+    ///
+    /// Caches start out null, so a null table entry may hit in the cache.
+    /// That's still caught by the null check when loading from the funcref.
+    fn cached_check_and_load_code_and_callee_vmctx(
+        &mut self,
+        features: &WasmFeatures,
+        table_index: TableIndex,
+        ty_index: TypeIndex,
+        funcref_ptr: ir::Value,
+        cache_offset: u32,
+    ) -> (ir::Value, ir::Value) {
+        let pointer_type = self.env.pointer_type();
+        let cache_offset = i32::try_from(cache_offset).unwrap();
+        let vmctx = self.env.vmctx_val(&mut self.builder.cursor());
+        let cached =
+            self.builder
+                .ins()
+                .load(pointer_type, ir::MemFlags::trusted(), vmctx, cache_offset);
+        let hit = self.builder.ins().icmp(IntCC::Equal, funcref_ptr, cached);
+
+        let check_block = self.builder.create_block();
+        let call_block = self.builder.create_block();
+        self.builder
+            .ins()
+            .brif(hit, call_block, &[], check_block, &[]);
+        self.builder.set_cold_block(check_block);
+
+        self.builder.switch_to_block(check_block);
+        self.builder.seal_block(check_block);
+        let check =
+            self.check_indirect_call_type_signature(features, table_index, ty_index, funcref_ptr);
+        debug_assert!(matches!(check, CheckIndirectCallTypeSignature::Runtime));
+        self.builder
+            .ins()
+            .store(ir::MemFlags::trusted(), funcref_ptr, vmctx, cache_offset);
+        self.builder.ins().jump(call_block, &[]);
+
+        self.builder.switch_to_block(call_block);
+        self.builder.seal_block(call_block);
+        self.load_code_and_vmctx(funcref_ptr, Some(crate::TRAP_INDIRECT_CALL_TO_NULL))
+    }
+
     fn check_indirect_call_type_signature(
         &mut self,
         features: &WasmFeatures,
@@ -1845,7 +2077,8 @@ impl<'a, 'func, 'module_env> Call<'a, 'func, 'module_env> {
                             );
                         }
                     }
-                    self.env.trap(self.builder, crate::TRAP_BAD_SIGNATURE);
+                    self.env
+                        .unconditional_trap(self.builder, crate::TRAP_BAD_SIGNATURE);
                     return CheckIndirectCallTypeSignature::StaticTrap;
                 }
             }
@@ -2145,6 +2378,102 @@ impl FuncEnvironment<'_> {
         self.wasm_func_ty.params()[index - 2].is_vmgcref_type_and_not_i31()
     }
 
+    /// Returns whether the given signature parameter is a WebAssembly local that
+    /// is mirrored by the shadow stack.
+    pub fn param_needs_shadow(&self, _signature: &ir::Signature, index: usize) -> bool {
+        // Skip the caller and callee vmctx.
+        index >= 2
+            && self.shadow_stack().is_some()
+            && matches!(self.wasm_func_ty.params()[index - 2], WasmValType::Ref(_))
+    }
+
+    /// Returns whether locals of the given type are mirrored by the shadow
+    /// stack.
+    pub fn local_needs_shadow(&self, ty: wasmparser::ValType) -> bool {
+        self.shadow_stack().is_some() && matches!(ty, wasmparser::ValType::Ref(_))
+    }
+
+    fn shadow_stack(&self) -> Option<ShadowStackCheck> {
+        if cfg!(feature = "shadow-stack") {
+            self.tunables.shadow_stack
+        } else {
+            None
+        }
+    }
+
+    /// Allocates the shadow stack slot for `local`, of Cranelift type `ty`,
+    /// and stores its initial value, if any, into it.
+    pub fn declare_shadow_local(
+        &mut self,
+        builder: &mut FunctionBuilder,
+        local: Variable,
+        ty: ir::Type,
+        init: Option<ir::Value>,
+    ) {
+        let slot = builder.func.create_sized_stack_slot(ir::StackSlotData::new(
+            ir::StackSlotKind::ExplicitSlot,
+            ty.bytes(),
+            u8::try_from(ty.bytes().ilog2()).unwrap(),
+        ));
+        self.shadow_locals[local] = slot.into();
+        if let Some(init) = init {
+            builder.ins().stack_store(init, slot, 0);
+        }
+    }
+
+    /// Mirrors a new definition of `local` into its shadow stack slot, if it
+    /// has one.
+    pub fn shadow_local_set(
+        &mut self,
+        builder: &mut FunctionBuilder,
+        local: Variable,
+        val: ir::Value,
+    ) {
+        if let Some(slot) = self.shadow_locals[local].expand() {
+            builder.ins().stack_store(val, slot, 0);
+        }
+    }
+
+    /// Checks that the value `val` read from `local` matches the local's shadow
+    /// copy, if it has one, as configured by `Tunables::shadow_stack`.
+    pub fn shadow_local_check(
+        &mut self,
+        builder: &mut FunctionBuilder,
+        local: Variable,
+        val: ir::Value,
+    ) {
+        let Some(slot) = self.shadow_locals[local].expand() else {
+            return;
+        };
+        let check = match self.shadow_stack() {
+            Some(ShadowStackCheck::Unchecked) | None => return,
+            Some(check) => check,
+        };
+        let ty = builder.func.dfg.value_type(val);
+        let shadow = builder.ins().stack_load(ty, slot, 0);
+        let mismatch = builder.ins().icmp(IntCC::NotEqual, shadow, val);
+        match check {
+            ShadowStackCheck::Trap => self.trapnz(builder, mismatch, TRAP_INTERNAL_ASSERT),
+            ShadowStackCheck::Breakpoint => {
+                let breakpoint_block = builder.create_block();
+                let continuation_block = builder.create_block();
+                builder.set_cold_block(breakpoint_block);
+                builder
+                    .ins()
+                    .brif(mismatch, breakpoint_block, &[], continuation_block, &[]);
+                builder.seal_block(breakpoint_block);
+
+                builder.switch_to_block(breakpoint_block);
+                builder.ins().debugtrap();
+                builder.ins().jump(continuation_block, &[]);
+                builder.seal_block(continuation_block);
+
+                builder.switch_to_block(continuation_block);
+            }
+            ShadowStackCheck::Unchecked => unreachable!(),
+        }
+    }
+
     pub fn sig_ref_result_needs_stack_map(&self, sig_ref: ir::SigRef, index: usize) -> bool {
         let wasm_func_ty = self.sig_ref_to_ty[sig_ref].as_ref().unwrap();
         wasm_func_ty.returns()[index].is_vmgcref_type_and_not_i31()
@@ -2776,7 +3105,121 @@ impl FuncEnvironment<'_> {
         callee: ir::FuncRef,
         call_args: &[ir::Value],
     ) -> WasmResult<ir::Inst> {
-        Call::new(builder, self).direct_call(callee_index, callee, call_args)
+        match self.on_translate_call_import(callee_index)? {
+            CallDisposition::Proceed => {
+                Call::new(builder, self).direct_call(callee_index, callee, call_args)
+            }
+            CallDisposition::RedirectTo(target) => {
+                let target_ref = self.get_or_create_func_ref(builder.func, target);
+                Call::new(builder, self).direct_call(target, target_ref, call_args)
+            }
+            CallDisposition::WrapWith { pre, post } => {
+                if let Some(pre) = pre {
+                    let pre_ref = self.get_or_create_func_ref(builder.func, pre);
+                    Call::new(builder, self).direct_call(pre, pre_ref, call_args)?;
+                }
+                let call = Call::new(builder, self).direct_call(callee_index, callee, call_args)?;
+                if let Some(post) = post {
+                    let results = builder.inst_results(call).to_vec();
+                    let post_ref = self.get_or_create_func_ref(builder.func, post);
+                    Call::new(builder, self).direct_call(post, post_ref, &results)?;
+                }
+                Ok(call)
+            }
+        }
+    }
+
+    /// Decides how a direct call to the function `index` is translated
+    /// according to the compiler's import call interpositions, see
+    /// `CompilerBuilder::import_call_interpositions`.
+    ///
+    /// Returns an error if the first interposition matching the import names
+    /// a function which isn't imported by this module or whose type doesn't
+    /// fit the call.
+    pub fn on_translate_call_import(&mut self, index: FuncIndex) -> WasmResult<CallDisposition> {
+        let rules = self.compiler.import_call_interpositions();
+        if rules.is_empty() || !self.module.is_imported_function(index) {
+            return Ok(CallDisposition::Proceed);
+        }
+        let Some((module, name)) = self.imported_func_name(index) else {
+            return Ok(CallDisposition::Proceed);
+        };
+        let Some(rule) = rules.iter().find(|r| r.module == module && r.name == name) else {
+            return Ok(CallDisposition::Proceed);
+        };
+
+        let ty = self.imported_func_type(index);
+        let lookup =
+            |(m, n): &(String, String), params: &[WasmValType], returns: &[WasmValType]| {
+                let target = self.imported_func_index(m, n).ok_or_else(|| {
+                    WasmError::User(format!(
+                        "calls to `{module}::{name}` are interposed with `{m}::{n}`, \
+                         which is not an imported function"
+                    ))
+                })?;
+                let target_ty = self.imported_func_type(target);
+                if target_ty.params() != params || target_ty.returns() != returns {
+                    return Err(WasmError::User(format!(
+                        "calls to `{module}::{name}` of type `{ty}` cannot be interposed \
+                         with `{m}::{n}` of type `{target_ty}`"
+                    )));
+                }
+                Ok(target)
+            };
+        match &rule.action {
+            ImportCallAction::Redirect(m, n) => Ok(CallDisposition::RedirectTo(lookup(
+                &(m.clone(), n.clone()),
+                ty.params(),
+                ty.returns(),
+            )?)),
+            ImportCallAction::Wrap { pre, post } => Ok(CallDisposition::WrapWith {
+                pre: pre
+                    .as_ref()
+                    .map(|pre| lookup(pre, ty.params(), &[]))
+                    .transpose()?,
+                post: post
+                    .as_ref()
+                    .map(|post| lookup(post, ty.returns(), &[]))
+                    .transpose()?,
+            }),
+        }
+    }
+
+    /// Returns the module and field names under which the function `index`
+    /// is imported, if it is imported.
+    fn imported_func_name(
+        &self,
+        index: FuncIndex,
+    ) -> Option<(&'module_environment str, &'module_environment str)> {
+        let module = self.module;
+        module.initializers.iter().find_map(|init| match init {
+            Initializer::Import {
+                name,
+                field,
+                index: EntityIndex::Function(i),
+            } if *i == index => Some((name.as_str(), field.as_str())),
+            _ => None,
+        })
+    }
+
+    /// Returns the index of the function imported as `module::name`, if any.
+    fn imported_func_index(&self, module: &str, name: &str) -> Option<FuncIndex> {
+        self.module.initializers.iter().find_map(|init| match init {
+            Initializer::Import {
+                name: m,
+                field: n,
+                index: EntityIndex::Function(i),
+            } if m == module && n == name => Some(*i),
+            _ => None,
+        })
+    }
+
+    fn imported_func_type(&self, index: FuncIndex) -> &'module_environment WasmFuncType {
+        let types = self.types;
+        let ty = self.module.functions[index]
+            .signature
+            .unwrap_module_type_index();
+        types[ty].unwrap_func()
     }
 
     pub fn translate_call_ref(
@@ -2796,7 +3239,29 @@ impl FuncEnvironment<'_> {
         callee: ir::FuncRef,
         call_args: &[ir::Value],
     ) -> WasmResult<()> {
-        Call::new_tail(builder, self).direct_call(callee_index, callee, call_args)?;
+        match self.on_translate_call_import(callee_index)? {
+            CallDisposition::Proceed => {
+                Call::new_tail(builder, self).direct_call(callee_index, callee, call_args)?;
+            }
+            CallDisposition::RedirectTo(target) => {
+                let target_ref = self.get_or_create_func_ref(builder.func, target);
+                Call::new_tail(builder, self).direct_call(target, target_ref, call_args)?;
+            }
+            CallDisposition::WrapWith { pre, post } => {
+                // Nothing can run after a tail call returns.
+                if post.is_some() {
+                    return Err(WasmError::Unsupported(format!(
+                        "tail call to function {} cannot be followed by a post-call function",
+                        callee_index.as_u32()
+                    )));
+                }
+                if let Some(pre) = pre {
+                    let pre_ref = self.get_or_create_func_ref(builder.func, pre);
+                    Call::new(builder, self).direct_call(pre, pre_ref, call_args)?;
+                }
+                Call::new_tail(builder, self).direct_call(callee_index, callee, call_args)?;
+            }
+        }
         Ok(())
     }
 
@@ -3030,7 +3495,13 @@ impl FuncEnvironment<'_> {
         dst: ir::Value,
         src: ir::Value,
         len: ir::Value,
-    ) -> WasmResult<()> {
+    ) -> WasmResult<Reachability<()>> {
+        if src_index == dst_index {
+            if let Some(len) = self.inline_bulk_memory_len(builder, dst_index, len) {
+                return Ok(self.translate_inline_memory_copy(builder, dst_index, dst, src, len));
+            }
+        }
+
         let mut pos = builder.cursor();
         let vmctx = self.vmctx_val(&mut pos);
 
@@ -3054,7 +3525,7 @@ impl FuncEnvironment<'_> {
         pos.ins()
             .call(memory_copy, &[vmctx, dst_index, dst, src_index, src, len]);
 
-        Ok(())
+        Ok(Reachability::Reachable(()))
     }
 
     pub fn translate_memory_fill(
@@ -3064,7 +3535,11 @@ impl FuncEnvironment<'_> {
         dst: ir::Value,
         val: ir::Value,
         len: ir::Value,
-    ) -> WasmResult<()> {
+    ) -> WasmResult<Reachability<()>> {
+        if let Some(len) = self.inline_bulk_memory_len(builder, memory_index, len) {
+            return Ok(self.translate_inline_memory_fill(builder, memory_index, dst, val, len));
+        }
+
         let mut pos = builder.cursor();
         let memory_fill = self.builtin_functions.memory_fill(&mut pos.func);
         let dst = self.cast_index_to_i64(&mut pos, dst, self.memory(memory_index).idx_type);
@@ -3077,7 +3552,162 @@ impl FuncEnvironment<'_> {
             &[memory_vmctx, defined_memory_index, dst, val, len],
         );
 
-        Ok(())
+        Ok(Reachability::Reachable(()))
+    }
+
+    /// Returns the length of a `memory.copy` or `memory.fill` of
+    /// `memory_index` if it's translated inline rather than as a call to the
+    /// builtin.
+    ///
+    /// Only constant lengths below `Tunables::bulk_memory_inline_threshold`
+    /// (and below 256) of unshared 32-bit memories are translated inline.
+    fn inline_bulk_memory_len(
+        &self,
+        builder: &FunctionBuilder<'_>,
+        memory_index: MemoryIndex,
+        len: ir::Value,
+    ) -> Option<u8> {
+        let memory = self.memory(memory_index);
+        if memory.idx_type != IndexType::I32 || memory.shared || self.proof_carrying_code() {
+            return None;
+        }
+        let inst = builder.func.dfg.value_def(len).inst()?;
+        let ir::InstructionData::UnaryImm {
+            opcode: ir::Opcode::Iconst,
+            imm,
+        } = builder.func.dfg.insts[inst]
+        else {
+            return None;
+        };
+        let len = imm.zero_extend_from_width(32).bits().unsigned();
+        let threshold = u64::from(self.tunables.bulk_memory_inline_threshold);
+        if len >= threshold {
+            return None;
+        }
+        u8::try_from(len).ok()
+    }
+
+    /// Traps unless the `len` bytes at `index` are within the current bounds
+    /// of `heap`.
+    ///
+    /// The check is made against the memory's current length, as the builtins
+    /// do, so that a bulk memory operation which is out of bounds traps before
+    /// it accesses memory, even when the accesses themselves wouldn't need an
+    /// explicit bounds check.
+    fn check_bulk_memory_range(
+        &mut self,
+        builder: &mut FunctionBuilder<'_>,
+        heap: &HeapData,
+        index: ir::Value,
+        len: u8,
+    ) {
+        let pointer_type = self.pointer_type();
+        let index = builder.ins().uextend(I64, index);
+        let len = builder.ins().iconst(I64, i64::from(len));
+        let end = builder.ins().iadd(index, len);
+        let mut bound = builder.ins().global_value(pointer_type, heap.bound);
+        if pointer_type != I64 {
+            bound = builder.ins().uextend(I64, bound);
+        }
+        let out_of_bounds = builder.ins().icmp(IntCC::UnsignedGreaterThan, end, bound);
+        self.trapnz(builder, out_of_bounds, ir::TrapCode::HEAP_OUT_OF_BOUNDS);
+    }
+
+    /// Returns the native address of the `len` bytes at `index` in `heap`,
+    /// which `check_bulk_memory_range` has already checked.
+    fn bulk_memory_addr(
+        &mut self,
+        builder: &mut FunctionBuilder<'_>,
+        heap: &HeapData,
+        index: ir::Value,
+        len: u8,
+    ) -> Reachability<ir::Value> {
+        bounds_check_and_compute_addr(
+            builder,
+            self,
+            heap,
+            index,
+            BoundsCheck::StaticOffset {
+                offset: 0,
+                access_size: len,
+            },
+            ir::TrapCode::HEAP_OUT_OF_BOUNDS,
+        )
+    }
+
+    /// Translates a `memory.copy` of `len` bytes within one memory as loads of
+    /// all of the source bytes followed by stores of all of them, which gives
+    /// overlapping ranges the same result as the builtin.
+    fn translate_inline_memory_copy(
+        &mut self,
+        builder: &mut FunctionBuilder<'_>,
+        memory_index: MemoryIndex,
+        dst: ir::Value,
+        src: ir::Value,
+        len: u8,
+    ) -> Reachability<()> {
+        let heap = self.get_or_create_heap(builder.func, memory_index);
+        let heap = self.heaps[heap].clone();
+        // Both ranges are checked before any memory is accessed, like the
+        // builtin does.
+        self.check_bulk_memory_range(builder, &heap, dst, len);
+        self.check_bulk_memory_range(builder, &heap, src, len);
+        if len == 0 {
+            return Reachability::Reachable(());
+        }
+        let Reachability::Reachable(dst) = self.bulk_memory_addr(builder, &heap, dst, len) else {
+            return Reachability::Unreachable;
+        };
+        let Reachability::Reachable(src) = self.bulk_memory_addr(builder, &heap, src, len) else {
+            return Reachability::Unreachable;
+        };
+
+        let flags = bulk_memory_flags();
+        let chunks = bulk_memory_chunks(len);
+        let values = chunks
+            .iter()
+            .map(|&(offset, ty)| builder.ins().load(ty, flags, src, offset))
+            .collect::<SmallVec<[_; 8]>>();
+        for (&(offset, _), value) in chunks.iter().zip(values) {
+            builder.ins().store(flags, value, dst, offset);
+        }
+        Reachability::Reachable(())
+    }
+
+    /// Translates a `memory.fill` of `len` bytes as stores of `val` splatted
+    /// to the width of each store.
+    fn translate_inline_memory_fill(
+        &mut self,
+        builder: &mut FunctionBuilder<'_>,
+        memory_index: MemoryIndex,
+        dst: ir::Value,
+        val: ir::Value,
+        len: u8,
+    ) -> Reachability<()> {
+        let heap = self.get_or_create_heap(builder.func, memory_index);
+        let heap = self.heaps[heap].clone();
+        self.check_bulk_memory_range(builder, &heap, dst, len);
+        if len == 0 {
+            return Reachability::Reachable(());
+        }
+        let Reachability::Reachable(dst) = self.bulk_memory_addr(builder, &heap, dst, len) else {
+            return Reachability::Unreachable;
+        };
+
+        let flags = bulk_memory_flags();
+        let byte = builder.ins().ireduce(I8, val);
+        let byte = builder.ins().uextend(I64, byte);
+        let ones = builder.ins().iconst(I64, 0x0101_0101_0101_0101);
+        let splat = builder.ins().imul(byte, ones);
+        for (offset, ty) in bulk_memory_chunks(len) {
+            let value = if ty == I64 {
+                splat
+            } else {
+                builder.ins().ireduce(ty, splat)
+            };
+            builder.ins().store(flags, value, dst, offset);
+        }
+        Reachability::Reachable(())
     }
 
     pub fn translate_memory_init(
@@ -3268,19 +3898,54 @@ impl FuncEnvironment<'_> {
     }
 
     pub fn translate_loop_header(&mut self, builder: &mut FunctionBuilder) -> WasmResult<()> {
-        // Additionally if enabled check how much fuel we have remaining to see
-        // if we've run out by this point.
         if self.tunables.consume_fuel {
-            self.fuel_check(builder);
+            if let Some(policy) = &mut self.gas_policy {
+                let cost = gas_to_fuel(policy.charge_loop_iteration());
+                self.fuel_consumed = self.fuel_consumed.saturating_add(cost);
+            }
         }
+        if self.tunables.interrupt_check_placement != InterruptCheckPlacement::PerFunction {
+            self.interrupt_check(builder);
+        }
+        Ok(())
+    }
 
-        // If we are performing epoch-based interruption, check to see
-        // if the epoch counter has changed.
+    /// Checks, if enabled, how much fuel we have remaining to see if we've run
+    /// out by this point and whether the epoch counter has changed.
+    fn interrupt_check(&mut self, builder: &mut FunctionBuilder) {
+        if self.tunables.consume_fuel {
+            self.fuel_check(builder);
+        }
         if self.tunables.epoch_interruption {
             self.epoch_check(builder);
         }
+        self.operators_since_interrupt_check = 0;
+    }
 
-        Ok(())
+    /// Whether fuel and epoch checks are performed on entry to the function
+    /// being translated.
+    fn interrupt_check_on_entry(&self) -> bool {
+        match self.tunables.interrupt_check_placement {
+            InterruptCheckPlacement::NoneForLeafFunctions => !self.is_leaf_function,
+            InterruptCheckPlacement::PerFunction
+            | InterruptCheckPlacement::PerLoop
+            | InterruptCheckPlacement::PerNOperators(_) => true,
+        }
+    }
+
+    /// Returns whether `set_leaf_function` needs to be called before
+    /// translating a function.
+    pub fn needs_leaf_function_info(&self) -> bool {
+        (self.tunables.consume_fuel || self.tunables.epoch_interruption)
+            && self.tunables.interrupt_check_placement
+                == InterruptCheckPlacement::NoneForLeafFunctions
+    }
+
+    /// Records whether the function about to be translated makes no calls to
+    /// other WebAssembly functions. Functions are assumed not to be leaves
+    /// unless told otherwise.
+    pub fn set_leaf_function(&mut self, is_leaf: bool) {
+        self.is_leaf_function = is_leaf;
     }
 
     pub fn before_translate_operator(
@@ -3293,6 +3958,16 @@ impl FuncEnvironment<'_> {
         if self.tunables.consume_fuel {
             self.fuel_before_op(op, builder, state.reachable());
         }
+
+        if let InterruptCheckPlacement::PerNOperators(n) = self.tunables.interrupt_check_placement {
+            if (self.tunables.consume_fuel || self.tunables.epoch_interruption) && state.reachable()
+            {
+                self.operators_since_interrupt_check += 1;
+                if self.operators_since_interrupt_check >= n {
+                    state.emit_if_reachable(builder, |builder| self.interrupt_check(builder));
+                }
+            }
+        }
         Ok(())
     }
 
@@ -3301,14 +3976,99 @@ impl FuncEnvironment<'_> {
         op: &Operator,
         _operand_types: Option<&[WasmValType]>,
         builder: &mut FunctionBuilder,
-        state: &FuncTranslationStacks,
+        state: &mut FuncTranslationStacks,
     ) -> WasmResult<()> {
-        if self.tunables.consume_fuel && state.reachable() {
-            self.fuel_after_op(op, builder);
+        if self.tunables.consume_fuel {
+            state.emit_if_reachable(builder, |builder| self.fuel_after_op(op, builder));
+        }
+        if self.canonicalize_nans_after(op) && state.reachable() && !builder.is_unreachable() {
+            let val = state.pop1();
+            let val = cranelift_codegen::canonicalize_nan(&mut builder.cursor(), self.isa, val);
+            state.push1(val);
         }
         Ok(())
     }
 
+    /// Returns whether the floating-point result of `op` has its NaNs
+    /// canonicalized during translation, per `Tunables::nan_canonicalization`.
+    fn canonicalize_nans_after(&self, op: &Operator) -> bool {
+        let Some(classes) = self.tunables.nan_canonicalization else {
+            return false;
+        };
+        let class = match op {
+            Operator::F32Add
+            | Operator::F32Sub
+            | Operator::F32Mul
+            | Operator::F32Div
+            | Operator::F32Min
+            | Operator::F32Max
+            | Operator::F32Sqrt
+            | Operator::F32Ceil
+            | Operator::F32Floor
+            | Operator::F32Trunc
+            | Operator::F32Nearest
+            | Operator::F64Add
+            | Operator::F64Sub
+            | Operator::F64Mul
+            | Operator::F64Div
+            | Operator::F64Min
+            | Operator::F64Max
+            | Operator::F64Sqrt
+            | Operator::F64Ceil
+            | Operator::F64Floor
+            | Operator::F64Trunc
+            | Operator::F64Nearest
+            | Operator::F32x4Add
+            | Operator::F32x4Sub
+            | Operator::F32x4Mul
+            | Operator::F32x4Div
+            | Operator::F32x4Min
+            | Operator::F32x4Max
+            | Operator::F32x4RelaxedMin
+            | Operator::F32x4RelaxedMax
+            | Operator::F32x4Sqrt
+            | Operator::F32x4Ceil
+            | Operator::F32x4Floor
+            | Operator::F32x4Trunc
+            | Operator::F32x4Nearest
+            | Operator::F64x2Add
+            | Operator::F64x2Sub
+            | Operator::F64x2Mul
+            | Operator::F64x2Div
+            | Operator::F64x2Min
+            | Operator::F64x2Max
+            | Operator::F64x2RelaxedMin
+            | Operator::F64x2RelaxedMax
+            | Operator::F64x2Sqrt
+            | Operator::F64x2Ceil
+            | Operator::F64x2Floor
+            | Operator::F64x2Trunc
+            | Operator::F64x2Nearest => NanCanonicalizationClasses::ARITHMETIC,
+
+            Operator::F32x4RelaxedMadd
+            | Operator::F32x4RelaxedNmadd
+            | Operator::F64x2RelaxedMadd
+            | Operator::F64x2RelaxedNmadd => NanCanonicalizationClasses::FMA,
+
+            Operator::F32DemoteF64
+            | Operator::F64PromoteF32
+            | Operator::F32x4DemoteF64x2Zero
+            | Operator::F64x2PromoteLowF32x4 => NanCanonicalizationClasses::CONVERSION,
+
+            Operator::F32Load { .. } | Operator::F64Load { .. } => NanCanonicalizationClasses::LOAD,
+
+            Operator::F32x4ExtractLane { .. }
+            | Operator::F32x4ReplaceLane { .. }
+            | Operator::F32x4Splat
+            | Operator::F64x2ExtractLane { .. }
+            | Operator::F64x2ReplaceLane { .. }
+            | Operator::F64x2Splat => NanCanonicalizationClasses::LANE,
+
+            _ => return false,
+        };
+        classes.contains(class)
+    }
+
     pub fn before_unconditionally_trapping_memory_access(&mut self, builder: &mut FunctionBuilder) {
         if self.tunables.consume_fuel {
             self.fuel_increment_var(builder);
@@ -3316,11 +4076,55 @@ impl FuncEnvironment<'_> {
         }
     }
 
+    /// Invoked once per function, right after its parameters have been
+    /// declared and before its locals are declared or any of its body is
+    /// translated, with `builder` positioned in the entry block.
+    ///
+    /// This is the place for instrumentation which must observe every entry
+    /// to the function, such as call-depth accounting, and is paired with
+    /// `translate_function_epilogue`. It runs before
+    /// `before_translate_function`, and so before the function's stack limit
+    /// is checked. Wasmtime itself doesn't instrument function entries, so
+    /// this only translates the hooks installed by tests.
+    pub fn translate_function_prologue(&mut self, builder: &mut FunctionBuilder) -> WasmResult<()> {
+        #[cfg(test)]
+        if let Some(hooks) = &mut self.function_hooks {
+            return hooks.prologue(builder);
+        }
+        let _ = builder;
+        Ok(())
+    }
+
+    /// Invoked in reachable code right before each `return` the function
+    /// emits, both for explicit `return` operators and for the implicit
+    /// return at the end of the body, and right before each tail call, since
+    /// the callee's prologue runs in place of the function's frame.
+    ///
+    /// Tail calls run this before the callee is looked up, so an indirect
+    /// tail call which traps because of its callee has already run it. It
+    /// isn't run when the function is left by a trap or by unwinding, so
+    /// instrumentation which must stay balanced has to be reset by the
+    /// embedder when that happens. Like `translate_function_prologue` this
+    /// only translates the hooks installed by tests.
+    pub fn translate_function_epilogue(&mut self, builder: &mut FunctionBuilder) -> WasmResult<()> {
+        #[cfg(test)]
+        if let Some(hooks) = &mut self.function_hooks {
+            return hooks.epilogue(builder);
+        }
+        let _ = builder;
+        Ok(())
+    }
+
     pub fn before_translate_function(
         &mut self,
         builder: &mut FunctionBuilder,
         _state: &FuncTranslationStacks,
     ) -> WasmResult<()> {
+        #[cfg(test)]
+        if let Some(hooks) = &mut self.function_hooks {
+            hooks.before_body(builder)?;
+        }
+
         // If an explicit stack limit is requested, emit one here at the start
         // of the function.
         if let Some(gv) = self.stack_limit_at_function_entry {
@@ -3358,12 +4162,88 @@ impl FuncEnvironment<'_> {
         builder: &mut FunctionBuilder,
         state: &FuncTranslationStacks,
     ) -> WasmResult<()> {
+        #[cfg(test)]
+        if let Some(hooks) = &mut self.function_hooks {
+            hooks.after_body(builder)?;
+        }
+
         if self.tunables.consume_fuel && state.reachable() {
             self.fuel_function_exit(builder);
         }
         Ok(())
     }
 
+    /// Returns an all-zero `I8X16` value used to initialize `v128` locals.
+    ///
+    /// The value is created once per function, so this must only be called
+    /// while the builder is positioned in the entry block (as it is while
+    /// locals are declared) for the value to dominate all of its uses.
+    /// Depending on `Tunables::v128_zero` the zero either comes from the
+    /// function's constant pool, is splatted from an integer zero, or is
+    /// loaded from the module's shared `ModuleConstant::V128Zero`.
+    pub fn v128_zero(&mut self, builder: &mut FunctionBuilder) -> ir::Value {
+        if let Some(zero) = self.v128_zero {
+            return zero;
+        }
+        let zero = match self.tunables.v128_zero {
+            V128Zero::Splat => {
+                let zero = builder.ins().iconst(I8, 0);
+                builder.ins().splat(I8X16, zero)
+            }
+            V128Zero::ModuleConstant if self.supports_module_constants() => {
+                let addr = self.module_constant_addr(builder, ModuleConstant::V128Zero);
+                let flags = MemFlags::trusted().with_readonly().with_can_move();
+                builder.ins().load(I8X16, flags, addr, 0)
+            }
+            V128Zero::FunctionConstant | V128Zero::ModuleConstant => {
+                let handle = builder.func.dfg.constants.insert([0; 16].to_vec().into());
+                builder.ins().vconst(I8X16, handle)
+            }
+        };
+        self.v128_zero = Some(zero);
+        zero
+    }
+
+    /// Returns whether module constants can be referenced by address on this
+    /// target.
+    ///
+    /// References are resolved while the text section is assembled, which is
+    /// only possible for the PC-relative addressing that x86-64 uses for
+    /// colocated symbols in non-PIC code.
+    fn supports_module_constants(&self) -> bool {
+        self.is_x86() && !self.isa.flags().is_pic()
+    }
+
+    /// Returns the address of `constant` within the text section of the module
+    /// being compiled.
+    fn module_constant_addr(
+        &self,
+        builder: &mut FunctionBuilder,
+        constant: ModuleConstant,
+    ) -> ir::Value {
+        let name = builder
+            .func
+            .declare_imported_user_function(ir::UserExternalName {
+                namespace: crate::NS_WASMTIME_MODULE_CONSTANT,
+                index: constant.index(),
+            });
+        let gv = builder
+            .func
+            .create_global_value(ir::GlobalValueData::Symbol {
+                name: ir::ExternalName::User(name),
+                offset: Imm64::new(0),
+                colocated: true,
+                tls: false,
+            });
+        builder.ins().global_value(self.pointer_type(), gv)
+    }
+
+    /// Returns whether locals which are always assigned before being read
+    /// skip their initialization at function entry.
+    pub fn lazy_local_init(&self) -> bool {
+        self.tunables.lazy_local_init
+    }
+
     pub fn relaxed_simd_deterministic(&self) -> bool {
         self.tunables.relaxed_simd_deterministic
     }
@@ -3444,6 +4324,74 @@ impl FuncEnvironment<'_> {
         let _ = (builder, val_size, addr, offset);
     }
 
+    /// Returns how accesses of `memory` are instrumented, or `None` if they
+    /// aren't.
+    pub fn memory_access_instrumentation(
+        &self,
+        memory: MemoryIndex,
+    ) -> Option<MemoryAccessInstrumentation> {
+        self.tunables.memory_access_instrumentation.filter(|_| {
+            self.tunables
+                .instrumented_memories
+                .contains(memory.as_u32())
+        })
+    }
+
+    /// Instruments an access of `size` bytes at `index + offset` in `memory`,
+    /// if accesses of `memory` are instrumented.
+    ///
+    /// This must be called after the access has been bounds checked and
+    /// before the access itself.
+    pub fn before_memory_access(
+        &mut self,
+        builder: &mut FunctionBuilder,
+        memory: MemoryIndex,
+        index: ir::Value,
+        offset: u64,
+        size: u8,
+        is_store: bool,
+    ) {
+        match self.memory_access_instrumentation(memory) {
+            None => {}
+            Some(MemoryAccessInstrumentation::Count) => {
+                let vmstore_ctx = self.get_vmstore_context_ptr(builder);
+                let accesses_offset = ir::immediates::Offset32::new(
+                    self.offsets.ptr.vmstore_context_memory_accesses() as i32,
+                );
+                let accesses =
+                    builder
+                        .ins()
+                        .load(I64, ir::MemFlags::trusted(), vmstore_ctx, accesses_offset);
+                let one = builder.ins().iconst(I64, 1);
+                let accesses = builder.ins().iadd(accesses, one);
+                builder.ins().store(
+                    ir::MemFlags::trusted(),
+                    accesses,
+                    vmstore_ctx,
+                    accesses_offset,
+                );
+            }
+            Some(MemoryAccessInstrumentation::Callback) => {
+                let mut addr = index;
+                if builder.func.dfg.value_type(addr) != I64 {
+                    addr = builder.ins().uextend(I64, addr);
+                }
+                if offset != 0 {
+                    let offset = builder.ins().iconst(I64, offset as i64);
+                    addr = builder.ins().iadd(addr, offset);
+                }
+                let memory_access = self.builtin_functions.memory_access(builder.func);
+                let vmctx = self.vmctx_val(&mut builder.cursor());
+                let memory = builder.ins().iconst(I32, i64::from(memory.as_u32()));
+                let size = builder.ins().iconst(I32, i64::from(size));
+                let is_store = builder.ins().iconst(I32, i64::from(is_store));
+                builder
+                    .ins()
+                    .call(memory_access, &[vmctx, memory, addr, size, is_store]);
+            }
+        }
+    }
+
     pub fn update_global(
         &mut self,
         builder: &mut FunctionBuilder,
@@ -3782,6 +4730,45 @@ impl FuncEnvironment<'_> {
         &*self.isa
     }
 
+    /// Translates the `unreachable` operator, notifying the host before the
+    /// trap if `unreachable` is lowered to do so.
+    pub fn translate_unreachable(&mut self, builder: &mut FunctionBuilder) {
+        if self.tunables.unreachable_lowering != UnreachableLowering::Trap {
+            self.notify_host_of_trap(builder, crate::TRAP_UNREACHABLE);
+        }
+        self.trap(builder, crate::TRAP_UNREACHABLE);
+    }
+
+    /// Raises a trap which translation inserted unconditionally, such as for
+    /// a statically out-of-bounds memory access, notifying the host before
+    /// the trap if such traps are lowered to do so.
+    pub fn unconditional_trap(&mut self, builder: &mut FunctionBuilder, trap: ir::TrapCode) {
+        if self.tunables.unreachable_lowering
+            == UnreachableLowering::NotifyHostOnAllUnconditionalTraps
+        {
+            self.notify_host_of_trap(builder, trap);
+        }
+        self.trap(builder, trap);
+    }
+
+    /// Calls the `trap_with_context` builtin with the index of the current
+    /// function and the offset of the current operator within the module.
+    fn notify_host_of_trap(&mut self, builder: &mut FunctionBuilder, trap: ir::TrapCode) {
+        let Some(trap) = crate::clif_trap_to_env_trap(trap) else {
+            return;
+        };
+        let func_index = self.current_func_index(builder);
+        let wasm_offset = builder.srcloc().bits();
+        let libcall = self.builtin_functions.trap_with_context(builder.func);
+        let vmctx = self.vmctx_val(&mut builder.cursor());
+        let func_index = builder.ins().iconst(I32, i64::from(func_index.as_u32()));
+        let wasm_offset = builder.ins().iconst(I32, i64::from(wasm_offset));
+        let trap_code = builder.ins().iconst(I8, i64::from(trap as u8));
+        builder
+            .ins()
+            .call(libcall, &[vmctx, func_index, wasm_offset, trap_code]);
+    }
+
     pub fn trap(&mut self, builder: &mut FunctionBuilder, trap: ir::TrapCode) {
         match (
             self.clif_instruction_traps_enabled(),
@@ -3892,6 +4879,9 @@ impl FuncEnvironment<'_> {
         ty: ir::Type,
         val: ir::Value,
     ) -> ir::Value {
+        if self.tunables.nontrapping_float_to_int_override {
+            return builder.ins().fcvt_to_sint_sat(ty, val);
+        }
         // NB: for now avoid translating this entire instruction to CLIF and
         // just do it in a libcall.
         if !self.clif_instruction_traps_enabled() {
@@ -3906,6 +4896,9 @@ impl FuncEnvironment<'_> {
         ty: ir::Type,
         val: ir::Value,
     ) -> ir::Value {
+        if self.tunables.nontrapping_float_to_int_override {
+            return builder.ins().fcvt_to_uint_sat(ty, val);
+        }
         if !self.clif_instruction_traps_enabled() {
             self.guard_fcvt_to_int(builder, ty, val, false);
         }
@@ -3948,6 +4941,33 @@ impl FuncEnvironment<'_> {
     }
 }
 
+/// Returns the fuel consumed by `op` without a gas policy.
+fn default_fuel_cost(op: &Operator<'_>) -> i64 {
+    match op {
+        // Nop and drop generate no code, so don't consume fuel for them.
+        Operator::Nop | Operator::Drop => 0,
+
+        // Control flow may create branches, but is generally cheap and
+        // free, so don't consume fuel. Note the lack of `if` since some
+        // cost is incurred with the conditional check.
+        Operator::Block { .. }
+        | Operator::Loop { .. }
+        | Operator::Unreachable
+        | Operator::Return
+        | Operator::Else
+        | Operator::End => 0,
+
+        // everything else, just call it one operation.
+        _ => 1,
+    }
+}
+
+/// Converts gas charged by a `GasPolicy` to fuel, saturating charges which
+/// no amount of fuel could pay for.
+fn gas_to_fuel(gas: u64) -> i64 {
+    i64::try_from(gas).unwrap_or(i64::MAX)
+}
+
 // Helper function to convert an `IndexType` to an `ir::Type`.
 //
 // Implementing From/Into trait for `IndexType` or `ir::Type` would
@@ -3972,3 +4992,26 @@ pub fn use_stack_switching_libcalls() {
     let _ = BuiltinFunctions::table_grow_cont_obj;
     let _ = BuiltinFunctions::table_fill_cont_obj;
 }
+
+/// The flags of the loads and stores of a bulk memory operation translated
+/// inline, which are the same as those of any other access to a linear memory.
+fn bulk_memory_flags() -> MemFlags {
+    let mut flags = MemFlags::new();
+    flags.set_endianness(ir::Endianness::Little);
+    flags.set_alias_region(Some(ir::AliasRegion::Heap));
+    flags
+}
+
+/// Splits `len` bytes into the offsets and types of the fewest loads or stores
+/// of at most 8 bytes which cover them.
+fn bulk_memory_chunks(len: u8) -> SmallVec<[(i32, ir::Type); 8]> {
+    let mut chunks = SmallVec::new();
+    let mut offset = 0;
+    for ty in [I64, I32, I16, I8] {
+        while u32::from(len) - offset >= ty.bytes() {
+            chunks.push((i32::try_from(offset).unwrap(), ty));
+            offset += ty.bytes();
+        }
+    }
+    chunks
+}
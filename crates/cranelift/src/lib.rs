@@ -25,8 +25,8 @@ use cranelift_entity::PrimaryMap;
 
 use target_lexicon::Architecture;
 use wasmtime_environ::{
-    BuiltinFunctionIndex, FlagValue, FuncIndex, RelocationTarget, Trap, TrapInformation, Tunables,
-    WasmFuncType, WasmHeapTopType, WasmHeapType, WasmValType,
+    BuiltinFunctionIndex, FlagValue, FuncIndex, ModuleConstant, RelocationTarget, Trap,
+    TrapInformation, Tunables, WasmFuncType, WasmHeapTopType, WasmHeapType, WasmValType,
 };
 
 pub use builder::builder;
@@ -36,6 +36,8 @@ mod obj;
 pub use obj::*;
 mod compiled_function;
 pub use compiled_function::*;
+mod single_function;
+pub use single_function::*;
 
 mod bounds_checks;
 mod builder;
@@ -45,6 +47,7 @@ mod func_environ;
 mod translate;
 
 use self::compiler::Compiler;
+pub use self::translate::{BlockOrigin, ControlBlockPart, ControlFrameKind, TranslationSummary};
 
 const TRAP_INTERNAL_ASSERT: TrapCode = TrapCode::unwrap_user(1);
 const TRAP_OFFSET: u8 = 2;
@@ -239,6 +242,11 @@ pub const NS_WASMTIME_BUILTIN: u32 = 1;
 /// in the pulley_interpreter crate.
 pub const NS_PULLEY_HOSTCALL: u32 = 2;
 
+/// Namespace for constants shared by all functions of a module, such as the
+/// all-zero `v128`. The index is the `ModuleConstant` being referenced. These
+/// constants are placed after the module's functions in the text section.
+pub const NS_WASMTIME_MODULE_CONSTANT: u32 = 3;
+
 /// A record of a relocation to perform.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Relocation {
@@ -318,6 +326,9 @@ fn mach_reloc_to_reloc(
                     RelocationTarget::Builtin(BuiltinFunctionIndex::from_u32(name.index))
                 }
                 NS_PULLEY_HOSTCALL => RelocationTarget::PulleyHostcall(name.index),
+                NS_WASMTIME_MODULE_CONSTANT => {
+                    RelocationTarget::ModuleConstant(ModuleConstant::from_u32(name.index).unwrap())
+                }
                 _ => panic!("unknown namespace {}", name.namespace),
             }
         }
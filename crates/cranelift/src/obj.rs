@@ -24,7 +24,7 @@ use object::write::{Object, SectionId, StandardSegment, Symbol, SymbolId, Symbol
 use object::{Architecture, SectionFlags, SectionKind, SymbolFlags, SymbolKind, SymbolScope};
 use std::ops::Range;
 use wasmtime_environ::obj;
-use wasmtime_environ::{Compiler, TripleExt, Unsigned};
+use wasmtime_environ::{Compiler, ModuleConstant, TripleExt, Unsigned};
 
 const TEXT_SECTION_NAME: &[u8] = b".text";
 
@@ -152,8 +152,11 @@ impl<'a> ModuleTextBuilder<'a> {
                 // call between functions. The `text` field is given priority to
                 // resolve this relocation before we actually emit an object
                 // file, but if it can't handle it then we pass through the
-                // relocation.
-                RelocationTarget::Wasm(_) | RelocationTarget::Builtin(_) => {
+                // relocation. Module constants are placed in the text section
+                // like functions are and are resolved the same way.
+                RelocationTarget::Wasm(_)
+                | RelocationTarget::Builtin(_)
+                | RelocationTarget::ModuleConstant(_) => {
                     let target = resolve_reloc_target(r.reloc_target);
                     if self
                         .text
@@ -208,6 +211,20 @@ impl<'a> ModuleTextBuilder<'a> {
         (symbol_id, off..off + body_len)
     }
 
+    /// Appends the module constant `constant` to this object.
+    ///
+    /// Constants are labeled like functions are, so this counts as one of the
+    /// functions the text section builder was created for and relocations
+    /// against `constant` must resolve to the index of this call.
+    pub fn append_constant(&mut self, constant: ModuleConstant) {
+        self.text.append(
+            true,
+            constant.data(),
+            constant.align(),
+            &mut self.ctrl_plane,
+        );
+    }
+
     /// Forces "veneers" to be used for inter-function calls in the text
     /// section which means that in-bounds optimized addresses are never used.
     ///
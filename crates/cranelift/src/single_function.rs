@@ -0,0 +1,276 @@
+//! Translation of a single function of a module to CLIF, for tooling which
+//! wants to inspect the CLIF Wasmtime generates without compiling the module.
+
+use crate::builder::LinkOptions;
+use crate::compiler::Compiler;
+use crate::translate::{FuncTranslator, TranslationSummary};
+use anyhow::{Result, anyhow};
+use cranelift_codegen::ir;
+use cranelift_codegen::isa::OwnedTargetIsa;
+use cranelift_codegen::print_errors::pretty_verifier_error;
+use wasmparser::{Operator, Parser, ValType, Validator, WasmFeatures};
+use wasmtime_environ::{
+    DefinedFuncIndex, FunctionBodyData, ModuleEnvironment, ModuleTranslation, ModuleTypesBuilder,
+    Tunables, WasmResult,
+};
+
+/// A function translated by [`translate_single_function`] or
+/// [`translate_single_function_operators`].
+#[derive(Debug)]
+pub struct TranslatedFunction {
+    /// The CLIF of the function.
+    pub func: ir::Function,
+    /// Facts gathered while translating the function.
+    pub summary: TranslationSummary,
+}
+
+/// Translates the function with index `index` among the functions defined by
+/// the module `wasm` to CLIF for `isa`, as Wasmtime would before compiling it.
+///
+/// The module is parsed and validated with `features`, but the bodies of its
+/// other functions are neither validated nor translated. When `verify` is set
+/// the CLIF verifier is run on the translated function and any errors it finds
+/// are returned.
+pub fn translate_single_function(
+    isa: OwnedTargetIsa,
+    tunables: Tunables,
+    features: WasmFeatures,
+    wasm: &[u8],
+    index: DefinedFuncIndex,
+    verify: bool,
+) -> Result<TranslatedFunction> {
+    translate(
+        isa,
+        tunables,
+        features,
+        wasm,
+        index,
+        verify,
+        |compiler, translation, types, input, func| {
+            compiler.translate_function(
+                translation,
+                types,
+                index,
+                input,
+                &mut FuncTranslator::new(),
+                Default::default(),
+                func,
+            )
+        },
+    )
+}
+
+/// Same as [`translate_single_function`] except that the body of the function
+/// is replaced with already-decoded local declarations and operators, for
+/// tooling which synthesizes code without encoding it.
+///
+/// Each operator is paired with the offset used for its source location and
+/// for error messages, and `ops` must include the function's final `end`. The
+/// operators are validated against the function's signature when `validate`
+/// is set. Otherwise they must be valid nonetheless, since invalid ones may
+/// cause panics or invalid CLIF, and operators whose translation depends on
+/// the type of a reference operand, such as `ref.is_null`, are rejected.
+pub fn translate_single_function_operators<'a>(
+    isa: OwnedTargetIsa,
+    tunables: Tunables,
+    features: WasmFeatures,
+    wasm: &[u8],
+    index: DefinedFuncIndex,
+    locals: &[(u32, ValType)],
+    ops: impl IntoIterator<Item = (Operator<'a>, usize)>,
+    validate: bool,
+    verify: bool,
+) -> Result<TranslatedFunction> {
+    translate(
+        isa,
+        tunables,
+        features,
+        wasm,
+        index,
+        verify,
+        |compiler, translation, types, input, func| {
+            let mut validator = input.validator.into_validator(Default::default());
+            let mut translator = FuncTranslator::new();
+            compiler.translate_function_with(
+                translation,
+                types,
+                index,
+                input.body.range().start,
+                func,
+                |func, func_env| {
+                    if validate {
+                        translator.translate_operators(&mut validator, locals, ops, func, func_env)
+                    } else {
+                        translator.translate_operators_unchecked(
+                            &mut validator,
+                            locals,
+                            ops,
+                            func,
+                            func_env,
+                        )
+                    }
+                },
+            )
+        },
+    )
+}
+
+/// Shared implementation of the functions above, which translates the
+/// function with index `index` with `translate_body`.
+fn translate(
+    isa: OwnedTargetIsa,
+    tunables: Tunables,
+    features: WasmFeatures,
+    wasm: &[u8],
+    index: DefinedFuncIndex,
+    verify: bool,
+    translate_body: impl FnOnce(
+        &Compiler,
+        &ModuleTranslation<'_>,
+        &ModuleTypesBuilder,
+        FunctionBodyData<'_>,
+        &mut ir::Function,
+    ) -> WasmResult<(TranslationSummary, bool)>,
+) -> Result<TranslatedFunction> {
+    let compiler = Compiler::new(
+        tunables,
+        isa,
+        None,
+        LinkOptions::default(),
+        None,
+        false,
+        Vec::new(),
+        None,
+    );
+
+    let mut validator = Validator::new_with_features(features);
+    let mut types = ModuleTypesBuilder::new(&validator);
+    let mut translation = ModuleEnvironment::new(compiler.tunables(), &mut validator, &mut types)
+        .translate(Parser::new(0), wasm)?;
+    let (_, input) = core::mem::take(&mut translation.function_body_inputs)
+        .into_iter()
+        .find(|(i, _)| *i == index)
+        .ok_or_else(|| anyhow!("module does not define a function with index {index:?}"))?;
+
+    let mut func = ir::Function::new();
+    let (summary, _needs_gc_heap) =
+        translate_body(&compiler, &translation, &types, input, &mut func)?;
+
+    if verify {
+        cranelift_codegen::verify_function(&func, compiler.isa())
+            .map_err(|errors| anyhow!(pretty_verifier_error(&func, None, errors)))?;
+    }
+
+    Ok(TranslatedFunction { func, summary })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{translate_single_function, translate_single_function_operators};
+    use cranelift_codegen::ir::UserFuncName;
+    use cranelift_codegen::isa::OwnedTargetIsa;
+    use cranelift_codegen::settings;
+    use wasmparser::{Operator, WasmFeatures};
+    use wasmtime_environ::{DefinedFuncIndex, Tunables};
+
+    /// A module with three functions.
+    const MODULE: &str = r#"
+        (func (param i32 i32) (result i32)
+          local.get 0
+          local.get 1
+          i32.add)
+        (func (param i64) (result i64)
+          local.get 0
+          i64.const 1
+          i64.add)
+        (func (param i32 i32) (result i32)
+          local.get 0
+          local.get 1
+          i32.mul)
+    "#;
+
+    fn isa() -> OwnedTargetIsa {
+        cranelift_native::builder()
+            .unwrap()
+            .finish(settings::Flags::new(settings::builder()))
+            .unwrap()
+    }
+
+    fn translate(index: u32) -> anyhow::Result<super::TranslatedFunction> {
+        translate_single_function(
+            isa(),
+            Tunables::default_host(),
+            WasmFeatures::default(),
+            &wat::parse_str(MODULE).unwrap(),
+            DefinedFuncIndex::from_u32(index),
+            true,
+        )
+    }
+
+    #[test]
+    fn translates_one_function_of_many() {
+        let translated = translate(1).unwrap();
+        assert_eq!(translated.func.name, UserFuncName::user(0, 1));
+        let clif = translated.func.display().to_string();
+        assert!(clif.contains("iadd"), "{clif}");
+        assert!(!clif.contains("imul"), "{clif}");
+        assert_eq!(translated.summary.num_operators, 4);
+
+        let translated = translate(2).unwrap();
+        let clif = translated.func.display().to_string();
+        assert!(clif.contains("imul"), "{clif}");
+    }
+
+    #[test]
+    fn missing_function_is_an_error() {
+        let err = translate(3).unwrap_err();
+        assert!(err.to_string().contains("does not define"), "{err}");
+    }
+
+    fn translate_operators(
+        index: u32,
+        ops: Vec<Operator<'static>>,
+        validate: bool,
+    ) -> anyhow::Result<super::TranslatedFunction> {
+        translate_single_function_operators(
+            isa(),
+            Tunables::default_host(),
+            WasmFeatures::default(),
+            &wat::parse_str(MODULE).unwrap(),
+            DefinedFuncIndex::from_u32(index),
+            &[],
+            ops.into_iter().zip(0..),
+            validate,
+            true,
+        )
+    }
+
+    #[test]
+    fn translates_decoded_operators() {
+        for validate in [true, false] {
+            let ops = vec![
+                Operator::LocalGet { local_index: 0 },
+                Operator::LocalGet { local_index: 1 },
+                Operator::I32Sub,
+                Operator::End,
+            ];
+            let translated = translate_operators(0, ops, validate).unwrap();
+            assert_eq!(translated.func.name, UserFuncName::user(0, 0));
+            let clif = translated.func.display().to_string();
+            assert!(clif.contains("isub"), "{clif}");
+            assert!(!clif.contains("iadd"), "{clif}");
+        }
+    }
+
+    #[test]
+    fn invalid_decoded_operators_are_an_error_when_validated() {
+        // `i64.sub` doesn't match the `i32` parameters of the function.
+        let ops = vec![
+            Operator::LocalGet { local_index: 0 },
+            Operator::LocalGet { local_index: 1 },
+            Operator::I64Sub,
+            Operator::End,
+        ];
+        assert!(translate_operators(0, ops, true).is_err());
+    }
+}
@@ -116,9 +116,13 @@ macro_rules! unwrap_or_return_unreachable_state {
 }
 
 /// Translates wasm operators into Cranelift IR instructions.
+///
+/// `pos` is the offset of `op` within the module, which is used to look up
+/// its branch hint, if any.
 pub fn translate_operator(
     validator: &mut FuncValidator<impl WasmModuleResources>,
     op: &Operator,
+    pos: usize,
     operand_types: Option<&[WasmValType]>,
     builder: &mut FunctionBuilder,
     stack: &mut FuncTranslationStacks,
@@ -145,7 +149,9 @@ pub fn translate_operator(
          *  disappear in the Cranelift Code
          ***********************************************************************************/
         Operator::LocalGet { local_index } => {
-            let val = builder.use_var(Variable::from_u32(*local_index));
+            let local = Variable::from_u32(*local_index);
+            let val = builder.use_var(local);
+            environ.shadow_local_check(builder, local, val);
             stack.push1(val);
             let label = ValueLabel::from_u32(*local_index);
             builder.set_val_label(val, label);
@@ -159,7 +165,9 @@ pub fn translate_operator(
                 val = optionally_bitcast_vector(val, I8X16, builder);
             }
 
-            builder.def_var(Variable::from_u32(*local_index), val);
+            let local = Variable::from_u32(*local_index);
+            builder.def_var(local, val);
+            environ.shadow_local_set(builder, local, val);
             let label = ValueLabel::from_u32(*local_index);
             builder.set_val_label(val, label);
         }
@@ -172,7 +180,9 @@ pub fn translate_operator(
                 val = optionally_bitcast_vector(val, I8X16, builder);
             }
 
-            builder.def_var(Variable::from_u32(*local_index), val);
+            let local = Variable::from_u32(*local_index);
+            builder.def_var(local, val);
+            environ.shadow_local_set(builder, local, val);
             let label = ValueLabel::from_u32(*local_index);
             builder.set_val_label(val, label);
         }
@@ -226,7 +236,7 @@ pub fn translate_operator(
             // We do nothing
         }
         Operator::Unreachable => {
-            environ.trap(builder, crate::TRAP_UNREACHABLE);
+            environ.translate_unreachable(builder);
             stack.reachable = false;
         }
         /***************************** Control flow blocks **********************************
@@ -255,17 +265,19 @@ pub fn translate_operator(
             // Pop the initial `Block` actuals and replace them with the `Block`'s
             // params since control flow joins at the top of the loop.
             stack.popn(params.len());
-            stack
-                .stack
-                .extend_from_slice(builder.block_params(loop_body));
+            stack.pushn(builder.block_params(loop_body));
 
             builder.switch_to_block(loop_body);
             environ.translate_loop_header(builder)?;
         }
         Operator::If { blockty } => {
             let val = stack.pop1();
+            let hint = environ.branch_hint(pos);
 
             let next_block = builder.create_block();
+            if hint == Some(false) {
+                builder.set_cold_block(next_block);
+            }
             let (params, results) = blocktype_params_results(validator, *blockty)?;
             let (destination, else_data) = if params.clone().eq(results.clone()) {
                 // It is possible there is no `else` block, so we will only
@@ -288,6 +300,7 @@ pub fn translate_operator(
                     ElseData::NoElse {
                         branch_inst,
                         placeholder: destination,
+                        else_is_cold: hint == Some(true),
                     },
                 )
             } else {
@@ -295,6 +308,9 @@ pub fn translate_operator(
                 // so we eagerly allocate the `else` block here.
                 let destination = block_with_params(builder, results.clone(), environ)?;
                 let else_block = block_with_params(builder, params.clone(), environ)?;
+                if hint == Some(true) {
+                    builder.set_cold_block(else_block);
+                }
                 canonicalise_brif(
                     builder,
                     val,
@@ -351,12 +367,16 @@ pub fn translate_operator(
                             ElseData::NoElse {
                                 branch_inst,
                                 placeholder,
+                                else_is_cold,
                             } => {
                                 let (params, _results) =
                                     blocktype_params_results(validator, blocktype)?;
                                 debug_assert_eq!(params.len(), num_return_values);
                                 let else_block =
                                     block_with_params(builder, params.clone(), environ)?;
+                                if else_is_cold {
+                                    builder.set_cold_block(else_block);
+                                }
                                 canonicalise_then_jump(
                                     builder,
                                     destination,
@@ -425,9 +445,7 @@ pub fn translate_operator(
             }
 
             frame.truncate_value_stack_to_original_size(&mut stack.stack);
-            stack
-                .stack
-                .extend_from_slice(builder.block_params(next_block));
+            stack.pushn(builder.block_params(next_block));
         }
         /**************************** Branch instructions *********************************
          * The branch instructions all have as arguments a target nesting level, which
@@ -468,7 +486,10 @@ pub fn translate_operator(
             stack.popn(return_count);
             stack.reachable = false;
         }
-        Operator::BrIf { relative_depth } => translate_br_if(*relative_depth, builder, stack),
+        Operator::BrIf { relative_depth } => {
+            let hint = environ.branch_hint(pos);
+            translate_br_if(*relative_depth, hint, builder, stack)
+        }
         Operator::BrTable { targets } => {
             let default = targets.default();
             let mut min_depth = default;
@@ -560,6 +581,7 @@ pub fn translate_operator(
                 let frame = &mut stack.control_stack[0];
                 frame.num_return_values()
             };
+            environ.translate_function_epilogue(builder)?;
             {
                 let return_args = stack.peekn_mut(return_count);
                 environ.handle_before_return(&return_args, builder);
@@ -674,6 +696,7 @@ pub fn translate_operator(
                 builder,
             );
 
+            environ.translate_function_epilogue(builder)?;
             environ.translate_return_call(builder, function_index, fref, args)?;
 
             stack.popn(num_args);
@@ -695,6 +718,7 @@ pub fn translate_operator(
             let args = stack.peekn_mut(num_args);
             bitcast_wasm_params(environ, sigref, args, builder);
 
+            environ.translate_function_epilogue(builder)?;
             environ.translate_return_call_indirect(
                 builder,
                 validator.features(),
@@ -721,6 +745,7 @@ pub fn translate_operator(
             let args = stack.peekn_mut(num_args);
             bitcast_wasm_params(environ, sigref, args, builder);
 
+            environ.translate_function_epilogue(builder)?;
             environ.translate_return_call_ref(builder, sigref, callee, stack.peekn(num_args))?;
 
             stack.popn(num_args);
@@ -840,49 +865,49 @@ pub fn translate_operator(
         }
         Operator::V128Load8x8S { memarg } => {
             //TODO(#6829): add before_load() and before_store() hooks for SIMD loads and stores.
-            let (flags, _, base) = unwrap_or_return_unreachable_state!(
+            let (flags, base) = unwrap_or_return_unreachable_state!(
                 stack,
-                prepare_addr(memarg, 8, builder, stack, environ)?
+                prepare_extending_load_addr(memarg, builder, stack, environ)?
             );
             let loaded = builder.ins().sload8x8(flags, base, 0);
             stack.push1(loaded);
         }
         Operator::V128Load8x8U { memarg } => {
-            let (flags, _, base) = unwrap_or_return_unreachable_state!(
+            let (flags, base) = unwrap_or_return_unreachable_state!(
                 stack,
-                prepare_addr(memarg, 8, builder, stack, environ)?
+                prepare_extending_load_addr(memarg, builder, stack, environ)?
             );
             let loaded = builder.ins().uload8x8(flags, base, 0);
             stack.push1(loaded);
         }
         Operator::V128Load16x4S { memarg } => {
-            let (flags, _, base) = unwrap_or_return_unreachable_state!(
+            let (flags, base) = unwrap_or_return_unreachable_state!(
                 stack,
-                prepare_addr(memarg, 8, builder, stack, environ)?
+                prepare_extending_load_addr(memarg, builder, stack, environ)?
             );
             let loaded = builder.ins().sload16x4(flags, base, 0);
             stack.push1(loaded);
         }
         Operator::V128Load16x4U { memarg } => {
-            let (flags, _, base) = unwrap_or_return_unreachable_state!(
+            let (flags, base) = unwrap_or_return_unreachable_state!(
                 stack,
-                prepare_addr(memarg, 8, builder, stack, environ)?
+                prepare_extending_load_addr(memarg, builder, stack, environ)?
             );
             let loaded = builder.ins().uload16x4(flags, base, 0);
             stack.push1(loaded);
         }
         Operator::V128Load32x2S { memarg } => {
-            let (flags, _, base) = unwrap_or_return_unreachable_state!(
+            let (flags, base) = unwrap_or_return_unreachable_state!(
                 stack,
-                prepare_addr(memarg, 8, builder, stack, environ)?
+                prepare_extending_load_addr(memarg, builder, stack, environ)?
             );
             let loaded = builder.ins().sload32x2(flags, base, 0);
             stack.push1(loaded);
         }
         Operator::V128Load32x2U { memarg } => {
-            let (flags, _, base) = unwrap_or_return_unreachable_state!(
+            let (flags, base) = unwrap_or_return_unreachable_state!(
                 stack,
-                prepare_addr(memarg, 8, builder, stack, environ)?
+                prepare_extending_load_addr(memarg, builder, stack, environ)?
             );
             let loaded = builder.ins().uload32x2(flags, base, 0);
             stack.push1(loaded);
@@ -1503,7 +1528,11 @@ pub fn translate_operator(
             let len = stack.pop1();
             let src_pos = stack.pop1();
             let dst_pos = stack.pop1();
-            environ.translate_memory_copy(builder, src_index, dst_index, dst_pos, src_pos, len)?;
+            unwrap_or_return_unreachable_state!(
+                stack,
+                environ
+                    .translate_memory_copy(builder, src_index, dst_index, dst_pos, src_pos, len)?
+            );
         }
         Operator::MemoryFill { mem } => {
             let mem = MemoryIndex::from_u32(*mem);
@@ -1511,7 +1540,10 @@ pub fn translate_operator(
             let len = stack.pop1();
             let val = stack.pop1();
             let dest = stack.pop1();
-            environ.translate_memory_fill(builder, mem, dest, val, len)?;
+            unwrap_or_return_unreachable_state!(
+                stack,
+                environ.translate_memory_fill(builder, mem, dest, val, len)?
+            );
         }
         Operator::MemoryInit { data_index, mem } => {
             let mem = MemoryIndex::from_u32(*mem);
@@ -3043,6 +3075,7 @@ fn translate_unreachable_operator(
                 ElseData::NoElse {
                     branch_inst: ir::Inst::reserved_value(),
                     placeholder: ir::Block::reserved_value(),
+                    else_is_cold: false,
                 },
                 0,
                 0,
@@ -3073,10 +3106,14 @@ fn translate_unreachable_operator(
                             ElseData::NoElse {
                                 branch_inst,
                                 placeholder,
+                                else_is_cold,
                             } => {
                                 let (params, _results) =
                                     blocktype_params_results(validator, blocktype)?;
                                 let else_block = block_with_params(builder, params, environ)?;
+                                if else_is_cold {
+                                    builder.set_cold_block(else_block);
+                                }
                                 let frame = stack.control_stack.last().unwrap();
                                 frame.truncate_value_stack_to_else_params(&mut stack.stack);
 
@@ -3150,7 +3187,7 @@ fn translate_unreachable_operator(
 
                 // And add the return values of the block but only if the next block is reachable
                 // (which corresponds to testing if the stack depth is 1)
-                value_stack.extend_from_slice(builder.block_params(frame.following_code()));
+                stack.pushn(builder.block_params(frame.following_code()));
                 stack.reachable = true;
             }
         }
@@ -3381,6 +3418,26 @@ fn align_atomic_addr(
     }
 }
 
+/// Like `prepare_addr` but for the 8-byte loads of the extending SIMD load
+/// operators, such as `v128.load8x8_s`, which are translated without
+/// `translate_load`.
+///
+/// Returns `None` when the Wasm access will unconditionally trap.
+fn prepare_extending_load_addr(
+    memarg: &MemArg,
+    builder: &mut FunctionBuilder,
+    stack: &mut FuncTranslationStacks,
+    environ: &mut FuncEnvironment<'_>,
+) -> WasmResult<Reachability<(MemFlags, Value)>> {
+    let (flags, index, base) = match prepare_addr(memarg, 8, builder, stack, environ)? {
+        Reachability::Unreachable => return Ok(Reachability::Unreachable),
+        Reachability::Reachable(a) => a,
+    };
+    let memory = MemoryIndex::from_u32(memarg.memory);
+    environ.before_memory_access(builder, memory, index, memarg.offset, 8, false);
+    Ok(Reachability::Reachable((flags, base)))
+}
+
 /// Like `prepare_addr` but for atomic accesses.
 ///
 /// Returns `None` when the Wasm access will unconditionally trap.
@@ -3414,6 +3471,15 @@ fn translate_load(
         };
 
     environ.before_load(builder, mem_op_size, wasm_index, memarg.offset);
+    let memory = MemoryIndex::from_u32(memarg.memory);
+    environ.before_memory_access(
+        builder,
+        memory,
+        wasm_index,
+        memarg.offset,
+        mem_op_size,
+        false,
+    );
 
     let (load, dfg) = builder
         .ins()
@@ -3440,6 +3506,15 @@ fn translate_store(
     );
 
     environ.before_store(builder, mem_op_size, wasm_index, memarg.offset);
+    let memory = MemoryIndex::from_u32(memarg.memory);
+    environ.before_memory_access(
+        builder,
+        memory,
+        wasm_index,
+        memarg.offset,
+        mem_op_size,
+        true,
+    );
 
     builder
         .ins()
@@ -3497,16 +3572,13 @@ fn translate_atomic_rmw(
         arg2 = builder.ins().ireduce(access_ty, arg2);
     }
 
-    let (flags, _, addr) = unwrap_or_return_unreachable_state!(
+    let access_size = u8::try_from(access_ty.bytes()).unwrap();
+    let (flags, index, addr) = unwrap_or_return_unreachable_state!(
         stack,
-        prepare_atomic_addr(
-            memarg,
-            u8::try_from(access_ty.bytes()).unwrap(),
-            builder,
-            stack,
-            environ,
-        )?
+        prepare_atomic_addr(memarg, access_size, builder, stack, environ)?
     );
+    let memory = MemoryIndex::from_u32(memarg.memory);
+    environ.before_memory_access(builder, memory, index, memarg.offset, access_size, true);
 
     let mut res = builder.ins().atomic_rmw(access_ty, flags, op, addr, arg2);
     if access_ty != widened_ty {
@@ -3554,16 +3626,13 @@ fn translate_atomic_cas(
         replacement = builder.ins().ireduce(access_ty, replacement);
     }
 
-    let (flags, _, addr) = unwrap_or_return_unreachable_state!(
+    let access_size = u8::try_from(access_ty.bytes()).unwrap();
+    let (flags, index, addr) = unwrap_or_return_unreachable_state!(
         stack,
-        prepare_atomic_addr(
-            memarg,
-            u8::try_from(access_ty.bytes()).unwrap(),
-            builder,
-            stack,
-            environ,
-        )?
+        prepare_atomic_addr(memarg, access_size, builder, stack, environ)?
     );
+    let memory = MemoryIndex::from_u32(memarg.memory);
+    environ.before_memory_access(builder, memory, index, memarg.offset, access_size, true);
     let mut res = builder.ins().atomic_cas(flags, addr, expected, replacement);
     if access_ty != widened_ty {
         res = builder.ins().uextend(widened_ty, res);
@@ -3597,16 +3666,13 @@ fn translate_atomic_load(
     };
     assert!(w_ty_ok && widened_ty.bytes() >= access_ty.bytes());
 
-    let (flags, _, addr) = unwrap_or_return_unreachable_state!(
+    let access_size = u8::try_from(access_ty.bytes()).unwrap();
+    let (flags, index, addr) = unwrap_or_return_unreachable_state!(
         stack,
-        prepare_atomic_addr(
-            memarg,
-            u8::try_from(access_ty.bytes()).unwrap(),
-            builder,
-            stack,
-            environ,
-        )?
+        prepare_atomic_addr(memarg, access_size, builder, stack, environ)?
     );
+    let memory = MemoryIndex::from_u32(memarg.memory);
+    environ.before_memory_access(builder, memory, index, memarg.offset, access_size, false);
     let mut res = builder.ins().atomic_load(access_ty, flags, addr);
     if access_ty != widened_ty {
         res = builder.ins().uextend(widened_ty, res);
@@ -3646,16 +3712,13 @@ fn translate_atomic_store(
         data = builder.ins().ireduce(access_ty, data);
     }
 
-    let (flags, _, addr) = unwrap_or_return_unreachable_state!(
+    let access_size = u8::try_from(access_ty.bytes()).unwrap();
+    let (flags, index, addr) = unwrap_or_return_unreachable_state!(
         stack,
-        prepare_atomic_addr(
-            memarg,
-            u8::try_from(access_ty.bytes()).unwrap(),
-            builder,
-            stack,
-            environ,
-        )?
+        prepare_atomic_addr(memarg, access_size, builder, stack, environ)?
     );
+    let memory = MemoryIndex::from_u32(memarg.memory);
+    environ.before_memory_access(builder, memory, index, memarg.offset, access_size, true);
     builder.ins().atomic_store(flags, data, addr);
     Ok(())
 }
@@ -3690,14 +3753,35 @@ fn translate_vector_fcmp(
     stack.push1(builder.ins().fcmp(cc, bitcast_a, bitcast_b))
 }
 
+/// Translates a `br_if`, laying out the unlikely path out of line if `hint`
+/// says whether the branch is likely taken.
 fn translate_br_if(
     relative_depth: u32,
+    hint: Option<bool>,
     builder: &mut FunctionBuilder,
     stack: &mut FuncTranslationStacks,
 ) {
     let val = stack.pop1();
     let (br_destination, inputs) = translate_br_if_args(relative_depth, stack);
     let next_block = builder.create_block();
+    match hint {
+        Some(true) => builder.set_cold_block(next_block),
+        Some(false) => {
+            // The destination is shared with other branches, so the branch
+            // goes through a cold block of its own which jumps there.
+            let taken_block = builder.create_block();
+            builder.set_cold_block(taken_block);
+            builder.ins().brif(val, taken_block, &[], next_block, &[]);
+            builder.seal_block(taken_block); // The only predecessor is the current block.
+            builder.switch_to_block(taken_block);
+            canonicalise_then_jump(builder, br_destination, inputs);
+
+            builder.seal_block(next_block); // The only predecessor is the `brif` above.
+            builder.switch_to_block(next_block);
+            return;
+        }
+        None => {}
+    }
     canonicalise_brif(builder, val, br_destination, inputs, next_block, &[]);
 
     builder.seal_block(next_block); // The only predecessor is the current block.
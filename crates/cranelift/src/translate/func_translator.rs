@@ -7,20 +7,197 @@
 use crate::func_environ::FuncEnvironment;
 use crate::translate::TargetEnvironment;
 use crate::translate::code_translator::{bitcast_wasm_returns, translate_operator};
-use crate::translate::stack::FuncTranslationStacks;
+use crate::translate::stack::{
+    ControlFrameKind, ControlStackFrame, ElseData, FuncTranslationStacks,
+};
 use crate::translate::translation_utils::get_vmctx_value_label;
 use cranelift_codegen::entity::EntityRef;
-use cranelift_codegen::ir::{self, Block, InstBuilder, ValueLabel};
+use cranelift_codegen::ir::{self, Block, InstBuilder, UserFuncName, ValueLabel};
 use cranelift_codegen::timing;
 use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
-use wasmparser::{BinaryReader, FuncValidator, FunctionBody, OperatorsReader, WasmModuleResources};
-use wasmtime_environ::{TypeConvert, WasmResult};
+use wasmparser::{
+    BinaryReader, FuncValidator, FunctionBody, Operator, OperatorsReader, WasmModuleResources,
+};
+use wasmtime_environ::{
+    DefinedFuncIndex, FuncIndex, TypeConvert, WasmError, WasmResult, wasm_unsupported,
+};
+
+/// Facts about a function gathered while translating it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TranslationSummary {
+    /// The largest number of values on the operand stack at any point during
+    /// translation.
+    ///
+    /// This is an upper bound of the WebAssembly operand stack depth: while an
+    /// `if` with parameters is open, the translator keeps a second copy of its
+    /// parameters on the stack for the `else` arm.
+    pub max_stack_depth: usize,
+    /// The deepest nesting of control frames, including the frame of the
+    /// function body itself.
+    pub max_control_depth: usize,
+    /// The number of operators in the body, including the final `end` and any
+    /// operators in unreachable code.
+    pub num_operators: usize,
+    /// Whether the body contains a `loop`.
+    pub has_loops: bool,
+    /// Whether the body contains a call to another WebAssembly function.
+    pub has_calls: bool,
+    /// Whether any reachable operator accesses a linear memory.
+    pub accesses_memory: bool,
+    /// Where each CLIF block of the translated function came from, indexed by
+    /// `Block::index`.
+    ///
+    /// Every block created during translation has exactly one entry, including
+    /// blocks that are never inserted into the layout.
+    pub block_origins: Vec<BlockOrigin>,
+}
+
+/// Why the translator created a CLIF block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockOrigin {
+    /// The entry block of the function.
+    FunctionEntry,
+    /// The block that returns from the function, which is where the implicit
+    /// block of the function body exits to.
+    FunctionExit,
+    /// A block of a `block`, `loop`, or `if` control structure whose opening
+    /// operator is at bytecode `offset`.
+    Control {
+        /// The kind of control structure.
+        kind: ControlFrameKind,
+        /// Which part of the control structure the block holds.
+        part: ControlBlockPart,
+        /// The bytecode offset of the `block`, `loop`, or `if` operator.
+        offset: u32,
+    },
+    /// A block created by the `br_table` at bytecode `offset` to pass
+    /// arguments along one of its edges.
+    BranchTableEdge {
+        /// The bytecode offset of the `br_table` operator.
+        offset: u32,
+    },
+    /// A block created for the translator's or environment's own purposes,
+    /// such as bounds checks, calls, or fuel checks, while translating the
+    /// operator at bytecode `offset`.
+    ///
+    /// Blocks created by the function prologue, epilogue, and other
+    /// function-level hooks use the offset of the start or end of the body.
+    Internal {
+        /// The bytecode offset of the operator being translated.
+        offset: u32,
+    },
+}
+
+/// The part of a Wasm control structure held by a block with a
+/// [`BlockOrigin::Control`] origin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlBlockPart {
+    /// The body of a `loop`, which branches to the loop target.
+    LoopHeader,
+    /// The consequent of an `if`.
+    Then,
+    /// The alternative of an `if`.
+    Else,
+    /// The code following the control structure's `end`, which branches to a
+    /// `block` or `if` target.
+    End,
+}
+
+impl TranslationSummary {
+    fn record_operator(&mut self, op: &Operator<'_>) {
+        self.num_operators += 1;
+        match op {
+            Operator::Loop { .. } => self.has_loops = true,
+            op if is_wasm_call(op) => self.has_calls = true,
+            _ => {}
+        }
+    }
+
+    /// Records `origin` for each block created since the last recorded one.
+    fn record_blocks(&mut self, builder: &FunctionBuilder, origin: impl Fn(Block) -> BlockOrigin) {
+        for index in self.block_origins.len()..builder.func.dfg.num_blocks() {
+            self.block_origins.push(origin(Block::new(index)));
+        }
+    }
+
+    /// Records the origin of the blocks created while translating `op` at
+    /// bytecode offset `pos`, identifying those of the control structure it
+    /// opens or continues.
+    fn record_operator_blocks(
+        &mut self,
+        op: &Operator<'_>,
+        pos: usize,
+        builder: &FunctionBuilder,
+        stack: &FuncTranslationStacks,
+    ) {
+        let offset = offset_of(pos);
+        let frame = stack.control_stack.last();
+        let current = builder.current_block();
+        // An `else` belongs to the `if` whose end block is the destination of
+        // the innermost frame.
+        let if_offset = match frame.and_then(|f| self.block_origins.get(f.following_code().index()))
+        {
+            Some(BlockOrigin::Control { offset, .. }) => *offset,
+            _ => offset,
+        };
+        let control = |kind, part, offset| BlockOrigin::Control { kind, part, offset };
+        self.record_blocks(builder, |block| match (op, frame) {
+            (Operator::Block { .. }, Some(ControlStackFrame::Block { destination, .. }))
+                if block == *destination =>
+            {
+                control(ControlFrameKind::Block, ControlBlockPart::End, offset)
+            }
+            (Operator::Loop { .. }, Some(ControlStackFrame::Loop { header, .. }))
+                if block == *header =>
+            {
+                control(ControlFrameKind::Loop, ControlBlockPart::LoopHeader, offset)
+            }
+            (Operator::Loop { .. }, Some(ControlStackFrame::Loop { destination, .. }))
+                if block == *destination =>
+            {
+                control(ControlFrameKind::Loop, ControlBlockPart::End, offset)
+            }
+            (Operator::If { .. }, Some(ControlStackFrame::If { destination, .. }))
+                if block == *destination =>
+            {
+                control(ControlFrameKind::If, ControlBlockPart::End, offset)
+            }
+            (
+                Operator::If { .. },
+                Some(ControlStackFrame::If {
+                    else_data: ElseData::WithElse { else_block },
+                    ..
+                }),
+            ) if block == *else_block => {
+                control(ControlFrameKind::If, ControlBlockPart::Else, offset)
+            }
+            (Operator::If { .. }, Some(ControlStackFrame::If { .. })) if Some(block) == current => {
+                control(ControlFrameKind::If, ControlBlockPart::Then, offset)
+            }
+            (Operator::Else, Some(ControlStackFrame::If { .. })) if Some(block) == current => {
+                control(ControlFrameKind::If, ControlBlockPart::Else, if_offset)
+            }
+            (Operator::BrTable { .. }, _) => BlockOrigin::BranchTableEdge { offset },
+            _ => BlockOrigin::Internal { offset },
+        });
+    }
+
+    fn finish(&mut self, stack: &FuncTranslationStacks, environ: &FuncEnvironment<'_>) {
+        self.max_stack_depth = stack.max_stack_depth;
+        self.max_control_depth = stack.max_control_depth;
+        self.accesses_memory = !environ.heaps().is_empty();
+    }
+}
 
 /// WebAssembly to Cranelift IR function translator.
 ///
 /// A `FuncTranslator` is used to translate a binary WebAssembly function into Cranelift IR guided
 /// by a `FuncEnvironment` object. A single translator instance can be reused to translate multiple
 /// functions which will reduce heap allocation traffic.
+///
+/// Reuse is supported after a translation fails too, whether because the function is invalid or
+/// because a `FuncEnvironment` hook returned an error: each translation starts by discarding
+/// whatever state the previous one left behind, see [`FuncTranslator::reset`].
 pub struct FuncTranslator {
     func_ctx: FunctionBuilderContext,
     state: FuncTranslationStacks,
@@ -35,6 +212,18 @@ impl FuncTranslator {
         }
     }
 
+    /// Discard the state left behind by a translation which failed midway,
+    /// such as open control frames and the partially built function's
+    /// variables, keeping the translator's allocations.
+    ///
+    /// Every translation does this before it starts, so calling it is only
+    /// necessary before using [`FuncTranslator::context`] directly after a
+    /// failed translation.
+    pub fn reset(&mut self) {
+        self.func_ctx.clear();
+        self.state.clear();
+    }
+
     /// Returns the underlying `FunctionBuilderContext` that this translator
     /// uses.
     pub fn context(&mut self) -> &mut FunctionBuilderContext {
@@ -57,8 +246,9 @@ impl FuncTranslator {
         body: FunctionBody<'_>,
         func: &mut ir::Function,
         environ: &mut FuncEnvironment<'_>,
-    ) -> WasmResult<()> {
+    ) -> WasmResult<TranslationSummary> {
         let _tt = timing::wasm_translate_function();
+        self.reset();
         let mut reader = body.get_binary_reader();
         log::trace!(
             "translate({} bytes, {}{})",
@@ -66,44 +256,225 @@ impl FuncTranslator {
             func.name,
             func.signature
         );
-        debug_assert_eq!(func.dfg.num_blocks(), 0, "Function must be empty");
-        debug_assert_eq!(func.dfg.num_insts(), 0, "Function must be empty");
+        log::debug!(
+            "translating function {} (defined index {:?})",
+            func.name,
+            defined_func_index(func, environ)
+        );
+
+        let mut summary = TranslationSummary::default();
+        let (mut builder, num_params) = begin_function(
+            func,
+            &mut self.func_ctx,
+            &mut self.state,
+            reader.original_position(),
+            environ,
+            &mut summary,
+        )?;
+        parse_local_decls(&mut reader, &mut builder, num_params, environ, validator)?;
+        if environ.needs_leaf_function_info() {
+            environ.set_leaf_function(is_leaf_function(&reader));
+        }
+        parse_function_body(
+            validator,
+            reader,
+            &mut builder,
+            &mut self.state,
+            environ,
+            &mut summary,
+        )?;
+        summary.finish(&self.state, environ);
+
+        builder.finalize();
+        log::trace!("translated Wasm to CLIF:\n{}", func.display());
+        Ok(summary)
+    }
 
-        let mut builder = FunctionBuilder::new(func, &mut self.func_ctx);
-        builder.set_srcloc(cur_srcloc(&reader));
-        let entry_block = builder.create_block();
-        builder.append_block_params_for_function_params(entry_block);
-        builder.switch_to_block(entry_block);
-        builder.seal_block(entry_block); // Declare all predecessors known.
+    /// Translate a WebAssembly function from already-decoded operators.
+    ///
+    /// This is the same as [`FuncTranslator::translate_body`] except that the
+    /// local declarations and the operators of the body are supplied directly
+    /// rather than being parsed from the binary encoding. Each operator is
+    /// paired with the offset used for its source location and for error
+    /// messages, and `ops` must include the function's final `end`.
+    ///
+    /// The operators are still fed through `validator`, which translation
+    /// relies on for block types and operand types, so synthesized code does
+    /// not need to be re-encoded but it does need to be valid. Functions
+    /// translated this way are never treated as leaf functions when placing
+    /// fuel and epoch checks, and always initialize all of their locals.
+    pub fn translate_operators<'a>(
+        &mut self,
+        validator: &mut FuncValidator<impl WasmModuleResources>,
+        locals: &[(u32, wasmparser::ValType)],
+        ops: impl IntoIterator<Item = (Operator<'a>, usize)>,
+        func: &mut ir::Function,
+        environ: &mut FuncEnvironment<'_>,
+    ) -> WasmResult<TranslationSummary> {
+        self.translate_decoded(validator, locals, ops, func, environ, true)
+    }
 
-        // Make sure the entry block is inserted in the layout before we make any callbacks to
-        // `environ`. The callback functions may need to insert things in the entry block.
-        builder.ensure_inserted_block();
+    /// Translate a WebAssembly function from already-decoded operators
+    /// without validating them.
+    ///
+    /// This is the same as [`FuncTranslator::translate_operators`] except
+    /// that `validator` is only consulted for the module's types and enabled
+    /// features, and isn't fed the locals or operators, which saves the cost
+    /// of validating trusted code such as synthesized trampolines. The
+    /// operators must nevertheless be valid: invalid ones may cause panics or
+    /// invalid CLIF.
+    ///
+    /// Without validation operand types aren't tracked, so the environment's
+    /// operator hooks receive `None` for them, and operators whose
+    /// translation depends on the type of a reference operand, such as
+    /// `ref.is_null` and `br_on_cast`, are rejected.
+    pub fn translate_operators_unchecked<'a>(
+        &mut self,
+        validator: &mut FuncValidator<impl WasmModuleResources>,
+        locals: &[(u32, wasmparser::ValType)],
+        ops: impl IntoIterator<Item = (Operator<'a>, usize)>,
+        func: &mut ir::Function,
+        environ: &mut FuncEnvironment<'_>,
+    ) -> WasmResult<TranslationSummary> {
+        self.translate_decoded(validator, locals, ops, func, environ, false)
+    }
 
-        let num_params = declare_wasm_parameters(&mut builder, entry_block, environ);
+    /// Shared implementation of [`FuncTranslator::translate_operators`] and
+    /// [`FuncTranslator::translate_operators_unchecked`], which validates the
+    /// operators if `checked` is set.
+    fn translate_decoded<'a>(
+        &mut self,
+        validator: &mut FuncValidator<impl WasmModuleResources>,
+        locals: &[(u32, wasmparser::ValType)],
+        ops: impl IntoIterator<Item = (Operator<'a>, usize)>,
+        func: &mut ir::Function,
+        environ: &mut FuncEnvironment<'_>,
+        checked: bool,
+    ) -> WasmResult<TranslationSummary> {
+        let _tt = timing::wasm_translate_function();
+        self.reset();
+        log::trace!("translate(operators, {}{})", func.name, func.signature);
+        log::debug!(
+            "translating function {} (defined index {:?})",
+            func.name,
+            defined_func_index(func, environ)
+        );
 
-        // Set up the translation state with a single pushed control block representing the whole
-        // function and its return values.
-        let exit_block = builder.create_block();
-        builder.append_block_params_for_function_returns(exit_block);
-        self.state.initialize(&builder.func.signature, exit_block);
+        let mut ops = ops.into_iter().peekable();
+        let start = ops.peek().map_or(0, |(_, pos)| *pos);
+        let mut summary = TranslationSummary::default();
+        let (mut builder, num_params) = begin_function(
+            func,
+            &mut self.func_ctx,
+            &mut self.state,
+            start,
+            environ,
+            &mut summary,
+        )?;
 
-        parse_local_decls(&mut reader, &mut builder, num_params, environ, validator)?;
-        parse_function_body(validator, reader, &mut builder, &mut self.state, environ)?;
+        let mut next_local = num_params;
+        for (count, ty) in locals {
+            if checked {
+                validator.define_locals(start, *count, *ty)?;
+            }
+            declare_locals(&mut builder, *count, *ty, &mut next_local, None, environ)?;
+        }
+
+        let stack = &mut self.state;
+        environ
+            .before_translate_function(&mut builder, stack)
+            .map_err(hook_error(&builder, start))?;
+        summary.record_blocks(&builder, |_| BlockOrigin::Internal {
+            offset: offset_of(start),
+        });
+        let mut operand_types = vec![];
+        let mut end = start;
+        for (op, pos) in ops {
+            builder.set_srcloc(srcloc_at(pos));
+            translate_function_operator(
+                validator,
+                checked,
+                &op,
+                pos,
+                &mut operand_types,
+                &mut builder,
+                stack,
+                environ,
+                &mut summary,
+            )?;
+            end = pos + 1;
+        }
+        if checked {
+            validator.finish(end)?;
+        }
+        finish_function_body(&mut builder, stack, environ, end, &mut summary)?;
+        summary.finish(stack, environ);
 
         builder.finalize();
         log::trace!("translated Wasm to CLIF:\n{}", func.display());
-        Ok(())
+        Ok(summary)
     }
 }
 
+/// Set up the entry and exit blocks of `func` and the translation state for a
+/// new function, returning the builder along with the number of locals
+/// declared for the function's parameters.
+///
+/// The environment's function prologue is translated here, at offset `pos`,
+/// once the parameters have been declared.
+fn begin_function<'a>(
+    func: &'a mut ir::Function,
+    func_ctx: &'a mut FunctionBuilderContext,
+    state: &mut FuncTranslationStacks,
+    pos: usize,
+    environ: &mut FuncEnvironment<'_>,
+    summary: &mut TranslationSummary,
+) -> WasmResult<(FunctionBuilder<'a>, usize)> {
+    debug_assert_eq!(func.dfg.num_blocks(), 0, "Function must be empty");
+    debug_assert_eq!(func.dfg.num_insts(), 0, "Function must be empty");
+
+    let mut builder = FunctionBuilder::new(func, func_ctx);
+    builder.set_srcloc(srcloc_at(pos));
+    let entry_block = builder.create_block();
+    builder.append_block_params_for_function_params(entry_block);
+    builder.switch_to_block(entry_block);
+    builder.seal_block(entry_block); // Declare all predecessors known.
+
+    // Make sure the entry block is inserted in the layout before we make any callbacks to
+    // `environ`. The callback functions may need to insert things in the entry block.
+    builder.ensure_inserted_block();
+
+    let num_params = declare_wasm_parameters(&mut builder, entry_block, environ);
+    environ
+        .translate_function_prologue(&mut builder)
+        .map_err(hook_error(&builder, pos))?;
+    summary.record_blocks(&builder, |block| {
+        if block == entry_block {
+            BlockOrigin::FunctionEntry
+        } else {
+            BlockOrigin::Internal {
+                offset: offset_of(pos),
+            }
+        }
+    });
+
+    // Set up the translation state with a single pushed control block representing the whole
+    // function and its return values.
+    let exit_block = builder.create_block();
+    builder.append_block_params_for_function_returns(exit_block);
+    state.initialize(&builder.func.signature, exit_block);
+    summary.record_blocks(&builder, |_| BlockOrigin::FunctionExit);
+
+    Ok((builder, num_params))
+}
+
 /// Declare local variables for the signature parameters that correspond to WebAssembly locals.
 ///
 /// Return the number of local variables declared.
 fn declare_wasm_parameters(
     builder: &mut FunctionBuilder,
     entry_block: Block,
-    environ: &FuncEnvironment<'_>,
+    environ: &mut FuncEnvironment<'_>,
 ) -> usize {
     let sig_len = builder.func.signature.params.len();
     let mut next_local = 0;
@@ -123,6 +494,15 @@ fn declare_wasm_parameters(
 
             let param_value = builder.block_params(entry_block)[i];
             builder.def_var(local, param_value);
+
+            if environ.param_needs_shadow(&builder.func.signature, i) {
+                environ.declare_shadow_local(
+                    builder,
+                    local,
+                    param_type.value_type,
+                    Some(param_value),
+                );
+            }
         }
         if param_type.purpose == ir::ArgumentPurpose::VMContext {
             let param_value = builder.block_params(entry_block)[i];
@@ -143,16 +523,35 @@ fn parse_local_decls(
     environ: &mut FuncEnvironment<'_>,
     validator: &mut FuncValidator<impl WasmModuleResources>,
 ) -> WasmResult<()> {
-    let mut next_local = num_params;
     let local_count = reader.read_var_u32()?;
-
+    let mut decls = Vec::new();
+    let mut num_locals = num_params;
     for _ in 0..local_count {
-        builder.set_srcloc(cur_srcloc(reader));
+        let srcloc = cur_srcloc(reader);
         let pos = reader.original_position();
         let count = reader.read_var_u32()?;
         let ty = reader.read()?;
         validator.define_locals(pos, count, ty)?;
-        declare_locals(builder, count, ty, &mut next_local, environ)?;
+        decls.push((srcloc, count, ty));
+        num_locals += count as usize;
+    }
+
+    // The reader is now positioned at the body's operators.
+    let read_before_set = environ
+        .lazy_local_init()
+        .then(|| locals_read_before_set(reader, num_locals));
+
+    let mut next_local = num_params;
+    for (srcloc, count, ty) in decls {
+        builder.set_srcloc(srcloc);
+        declare_locals(
+            builder,
+            count,
+            ty,
+            &mut next_local,
+            read_before_set.as_deref(),
+            environ,
+        )?;
     }
 
     Ok(())
@@ -160,45 +559,49 @@ fn parse_local_decls(
 
 /// Declare `count` local variables of the same type, starting from `next_local`.
 ///
+/// Locals are initialized to 0, except for those of a non-reference type which
+/// `read_before_set`, when given, reports are never read before being set.
+///
 /// Fail if too many locals are declared in the function, or if the type is not valid for a local.
 fn declare_locals(
     builder: &mut FunctionBuilder,
     count: u32,
     wasm_type: wasmparser::ValType,
     next_local: &mut usize,
+    read_before_set: Option<&[bool]>,
     environ: &mut FuncEnvironment<'_>,
 ) -> WasmResult<()> {
-    // All locals are initialized to 0.
     use wasmparser::ValType::*;
+    let needs_init = |local: usize| {
+        matches!(wasm_type, Ref(_)) || read_before_set.map_or(true, |read| read[local])
+    };
+    let any_needs_init = (*next_local..*next_local + count as usize).any(needs_init);
     let (ty, init, needs_stack_map) = match wasm_type {
         I32 => (
             ir::types::I32,
-            Some(builder.ins().iconst(ir::types::I32, 0)),
+            any_needs_init.then(|| builder.ins().iconst(ir::types::I32, 0)),
             false,
         ),
         I64 => (
             ir::types::I64,
-            Some(builder.ins().iconst(ir::types::I64, 0)),
+            any_needs_init.then(|| builder.ins().iconst(ir::types::I64, 0)),
             false,
         ),
         F32 => (
             ir::types::F32,
-            Some(builder.ins().f32const(ir::immediates::Ieee32::with_bits(0))),
+            any_needs_init.then(|| builder.ins().f32const(ir::immediates::Ieee32::with_bits(0))),
             false,
         ),
         F64 => (
             ir::types::F64,
-            Some(builder.ins().f64const(ir::immediates::Ieee64::with_bits(0))),
+            any_needs_init.then(|| builder.ins().f64const(ir::immediates::Ieee64::with_bits(0))),
+            false,
+        ),
+        V128 => (
+            ir::types::I8X16,
+            any_needs_init.then(|| environ.v128_zero(builder)),
             false,
         ),
-        V128 => {
-            let constant_handle = builder.func.dfg.constants.insert([0; 16].to_vec().into());
-            (
-                ir::types::I8X16,
-                Some(builder.ins().vconst(ir::types::I8X16, constant_handle)),
-                false,
-            )
-        }
         Ref(rt) => {
             let hty = environ.convert_heap_type(rt.heap_type())?;
             let (ty, needs_stack_map) = environ.reference_type(hty);
@@ -217,15 +620,80 @@ fn declare_locals(
         if needs_stack_map {
             builder.declare_var_needs_stack_map(local);
         }
+        let init = init.filter(|_| needs_init(*next_local));
         if let Some(init) = init {
             builder.def_var(local, init);
             builder.set_val_label(init, ValueLabel::new(*next_local));
         }
+        if environ.local_needs_shadow(wasm_type) {
+            environ.declare_shadow_local(builder, local, ty, init);
+        }
         *next_local += 1;
     }
     Ok(())
 }
 
+/// Returns, for each of the function's `num_locals` locals, whether the body
+/// in `reader` might read it before setting it.
+///
+/// This is a conservative pre-pass over the operators which follows the
+/// validation rules for non-nullable locals: a `local.set` or `local.tee`
+/// only counts as setting a local until the end of the control frame it's in,
+/// or until the `else` or `catch` which starts that frame's next arm. A read
+/// of a local which hasn't been set by then is assumed to see its initial
+/// value, whether or not the read is reachable. A body that fails to decode is
+/// reported as reading every local, leaving the error for the main
+/// translation loop to report.
+fn locals_read_before_set(reader: &BinaryReader, num_locals: usize) -> Vec<bool> {
+    let mut read_before_set = vec![false; num_locals];
+    let mut set = vec![false; num_locals];
+    // The locals set in the open control frames, with the position at which
+    // each frame's locals start.
+    let mut set_in_frames = Vec::new();
+    let mut frames = vec![0];
+
+    let mut reader = OperatorsReader::new(reader.clone());
+    while !reader.eof() {
+        let Ok(op) = reader.read() else {
+            return vec![true; num_locals];
+        };
+        match op {
+            Operator::LocalGet { local_index } => {
+                let local = local_index as usize;
+                if local < num_locals && !set[local] {
+                    read_before_set[local] = true;
+                }
+            }
+            Operator::LocalSet { local_index } | Operator::LocalTee { local_index } => {
+                let local = local_index as usize;
+                if local < num_locals && !set[local] {
+                    set[local] = true;
+                    set_in_frames.push(local);
+                }
+            }
+            Operator::Block { .. }
+            | Operator::Loop { .. }
+            | Operator::If { .. }
+            | Operator::Try { .. }
+            | Operator::TryTable { .. } => frames.push(set_in_frames.len()),
+            Operator::Else | Operator::Catch { .. } | Operator::CatchAll => {
+                let start = frames.last().copied().unwrap_or(0);
+                for local in set_in_frames.drain(start..) {
+                    set[local] = false;
+                }
+            }
+            Operator::End | Operator::Delegate { .. } => {
+                let start = frames.pop().unwrap_or(0);
+                for local in set_in_frames.drain(start..) {
+                    set[local] = false;
+                }
+            }
+            _ => {}
+        }
+    }
+    read_before_set
+}
+
 /// Parse the function body in `reader`.
 ///
 /// This assumes that the local variable declarations have already been parsed and function
@@ -236,11 +704,18 @@ fn parse_function_body(
     builder: &mut FunctionBuilder,
     stack: &mut FuncTranslationStacks,
     environ: &mut FuncEnvironment<'_>,
+    summary: &mut TranslationSummary,
 ) -> WasmResult<()> {
     // The control stack is initialized with a single block representing the whole function.
     debug_assert_eq!(stack.control_stack.len(), 1, "State not initialized");
 
-    environ.before_translate_function(builder, stack)?;
+    let start = reader.original_position();
+    environ
+        .before_translate_function(builder, stack)
+        .map_err(hook_error(builder, start))?;
+    summary.record_blocks(builder, |_| BlockOrigin::Internal {
+        offset: offset_of(start),
+    });
 
     let mut reader = OperatorsReader::new(reader);
     let mut operand_types = vec![];
@@ -250,16 +725,90 @@ fn parse_function_body(
         builder.set_srcloc(cur_srcloc(&reader.get_binary_reader()));
 
         let op = reader.read()?;
-        let operand_types =
-            validate_op_and_get_operand_types(validator, environ, &mut operand_types, &op, pos)?;
-
-        environ.before_translate_operator(&op, operand_types, builder, stack)?;
-        translate_operator(validator, &op, operand_types, builder, stack, environ)?;
-        environ.after_translate_operator(&op, operand_types, builder, stack)?;
+        translate_function_operator(
+            validator,
+            true,
+            &op,
+            pos,
+            &mut operand_types,
+            builder,
+            stack,
+            environ,
+            summary,
+        )?;
     }
-    environ.after_translate_function(builder, stack)?;
+    let end = reader.original_position();
     reader.finish()?;
 
+    finish_function_body(builder, stack, environ, end, summary)
+}
+
+/// Validate, if `checked` is set, and translate a single operator of a
+/// function body, surrounded by the environment's per-operator hooks.
+fn translate_function_operator(
+    validator: &mut FuncValidator<impl WasmModuleResources>,
+    checked: bool,
+    op: &Operator<'_>,
+    pos: usize,
+    operand_types: &mut Vec<wasmtime_environ::WasmValType>,
+    builder: &mut FunctionBuilder,
+    stack: &mut FuncTranslationStacks,
+    environ: &mut FuncEnvironment<'_>,
+    summary: &mut TranslationSummary,
+) -> WasmResult<()> {
+    summary.record_operator(op);
+    let operand_types = if checked {
+        validate_op_and_get_operand_types(validator, environ, operand_types, op, pos)?
+    } else if needs_operand_types(op) {
+        let error = wasm_unsupported!("`{op:?}` can only be translated with validation");
+        return Err(WasmError::in_function(&builder.func.name, pos, error));
+    } else {
+        None
+    };
+
+    let insts_before = builder.func.dfg.num_insts();
+    environ
+        .before_translate_operator(op, operand_types, builder, stack)
+        .map_err(hook_error(builder, pos))?;
+    debug_check_hook_emission(builder, stack, insts_before, op, "before");
+    let internal = |_| BlockOrigin::Internal {
+        offset: offset_of(pos),
+    };
+    summary.record_blocks(builder, internal);
+
+    // Without validation the operator doesn't look at its operand types,
+    // as those which would are rejected above.
+    let translate_types = if checked {
+        operand_types
+    } else {
+        Some(&[][..])
+    };
+    translate_operator(validator, op, pos, translate_types, builder, stack, environ)?;
+    summary.record_operator_blocks(op, pos, builder, stack);
+
+    let insts_before = builder.func.dfg.num_insts();
+    environ
+        .after_translate_operator(op, operand_types, builder, stack)
+        .map_err(hook_error(builder, pos))?;
+    debug_check_hook_emission(builder, stack, insts_before, op, "after");
+    summary.record_blocks(builder, internal);
+    Ok(())
+}
+
+/// Complete translation of a function once all of its operators, including
+/// the final `End`, have been translated. `end` is the offset just past the
+/// final `End`.
+fn finish_function_body(
+    builder: &mut FunctionBuilder,
+    stack: &mut FuncTranslationStacks,
+    environ: &mut FuncEnvironment<'_>,
+    end: usize,
+    summary: &mut TranslationSummary,
+) -> WasmResult<()> {
+    environ
+        .after_translate_function(builder, stack)
+        .map_err(hook_error(builder, end))?;
+
     // The final `End` operator left us in the exit block where we need to manually add a return
     // instruction.
     //
@@ -267,6 +816,9 @@ fn parse_function_body(
     // generate a return instruction that doesn't match the signature.
     if stack.reachable {
         if !builder.is_unreachable() {
+            environ
+                .translate_function_epilogue(builder)
+                .map_err(hook_error(builder, end))?;
             environ.handle_before_return(&stack.stack, builder);
             bitcast_wasm_returns(&mut stack.stack, builder);
             builder.ins().return_(&stack.stack);
@@ -277,14 +829,126 @@ fn parse_function_body(
     // or the end of the function is unreachable.
     stack.stack.clear();
 
+    summary.record_blocks(builder, |_| BlockOrigin::Internal {
+        offset: offset_of(end),
+    });
+
     Ok(())
 }
 
+/// Returns a function which attaches the name of the function being built by
+/// `builder` and the bytecode `offset` to an error raised by one of the
+/// environment's hooks.
+fn hook_error<'a>(
+    builder: &'a FunctionBuilder,
+    offset: usize,
+) -> impl FnOnce(WasmError) -> WasmError + 'a {
+    move |error| WasmError::in_function(&builder.func.name, offset, error)
+}
+
+/// Returns the index of `func` among the functions defined by the module
+/// being translated, if its name identifies one.
+fn defined_func_index(
+    func: &ir::Function,
+    environ: &FuncEnvironment<'_>,
+) -> Option<DefinedFuncIndex> {
+    match &func.name {
+        UserFuncName::User(name) if name.namespace == crate::NS_WASM_FUNC => environ
+            .module
+            .defined_func_index(FuncIndex::from_u32(name.index)),
+        _ => None,
+    }
+}
+
+/// Returns whether the function body in `reader` makes no calls to other
+/// WebAssembly functions.
+///
+/// This is a conservative pre-pass over the operators: a body that fails to
+/// decode is reported as not being a leaf and the error is left for the main
+/// translation loop to report.
+fn is_leaf_function(reader: &BinaryReader) -> bool {
+    let mut reader = OperatorsReader::new(reader.clone());
+    while !reader.eof() {
+        match reader.read() {
+            Ok(op) if !is_wasm_call(&op) => {}
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Returns whether translating `op` depends on the Wasm types of its
+/// operands, which are only known with validation.
+fn needs_operand_types(op: &Operator<'_>) -> bool {
+    matches!(
+        op,
+        Operator::RefIsNull
+            | Operator::RefAsNonNull
+            | Operator::RefEq
+            | Operator::BrOnNull { .. }
+            | Operator::BrOnNonNull { .. }
+            | Operator::RefTestNonNull { .. }
+            | Operator::RefTestNullable { .. }
+            | Operator::RefCastNonNull { .. }
+            | Operator::RefCastNullable { .. }
+            | Operator::BrOnCast { .. }
+            | Operator::BrOnCastFail { .. }
+    )
+}
+
+/// Returns whether `op` transfers control to another WebAssembly function.
+fn is_wasm_call(op: &Operator<'_>) -> bool {
+    matches!(
+        op,
+        Operator::Call { .. }
+            | Operator::CallIndirect { .. }
+            | Operator::CallRef { .. }
+            | Operator::ReturnCall { .. }
+            | Operator::ReturnCallIndirect { .. }
+            | Operator::ReturnCallRef { .. }
+            | Operator::Resume { .. }
+            | Operator::ResumeThrow { .. }
+            | Operator::Switch { .. }
+    )
+}
+
+/// Debug-mode check that an operator translation hook didn't emit any
+/// instructions while translation was in an unreachable region.
+///
+/// Such instructions would be appended to an already-terminated block (or use
+/// values from a truncated value stack) and fail CLIF verification much later
+/// with a far less helpful message. Hooks should use
+/// `FuncTranslationStacks::emit_if_reachable` to avoid this.
+fn debug_check_hook_emission(
+    builder: &FunctionBuilder,
+    stack: &FuncTranslationStacks,
+    insts_before: usize,
+    op: &Operator<'_>,
+    hook: &str,
+) {
+    if cfg!(debug_assertions) && !stack.reachable() {
+        debug_assert_eq!(
+            builder.func.dfg.num_insts(),
+            insts_before,
+            "the {hook}-operator translation hook for `{op:?}` emitted instructions in \
+             unreachable code (control depth {}, innermost frame {:?})",
+            stack.control_depth(),
+            stack.control_frame(0),
+        );
+    }
+}
+
+/// Validates `op` and returns the types of its operands, the last of which is
+/// the top of the operand stack.
+///
+/// Operand types are `None` in unreachable code, where an operator may pop
+/// more operands than its control frame holds, for operators whose arity
+/// can't be determined, and for invalid operators, which fail validation.
 fn validate_op_and_get_operand_types<'a>(
     validator: &mut FuncValidator<impl WasmModuleResources>,
     environ: &mut FuncEnvironment<'_>,
     operand_types: &'a mut Vec<wasmtime_environ::WasmValType>,
-    op: &wasmparser::Operator<'_>,
+    op: &Operator<'_>,
     pos: usize,
 ) -> WasmResult<Option<&'a [wasmtime_environ::WasmValType]>> {
     // Get the operand types for this operator.
@@ -297,9 +961,16 @@ fn validate_op_and_get_operand_types<'a>(
     // than actually exist on the stack (which is allowed in unreachable code)
     // so even if we can get arity, we are only guaranteed to have operand types
     // for ops that are not only valid but also reachable.
-    let arity = op.operator_arity(&*validator);
+    //
+    // Should the validator be unable to determine the arity, we determine it
+    // from the module's types. If that fails too the arity is unknown and no
+    // operand types are reported.
+    let operand_arity = op
+        .operator_arity(&*validator)
+        .or_else(|| operator_arity_from_types(validator.resources(), op))
+        .map(|(operand_arity, _result_arity)| operand_arity);
     operand_types.clear();
-    let operand_types = arity.and_then(|(operand_arity, _result_arity)| {
+    let operand_types = operand_arity.and_then(|operand_arity| {
         for i in (0..operand_arity).rev() {
             let i = usize::try_from(i).unwrap();
             let ty = validator.get_operand_type(i)??;
@@ -314,9 +985,1040 @@ fn validate_op_and_get_operand_types<'a>(
     Ok(operand_types)
 }
 
+/// Determines the arity of the operators which are defined in terms of one of
+/// the module's types from that type.
+fn operator_arity_from_types(
+    resources: &impl WasmModuleResources,
+    op: &Operator<'_>,
+) -> Option<(u32, u32)> {
+    use wasmparser::CompositeInnerType;
+
+    let inner = |index: u32| Some(&resources.sub_type_at(index)?.composite_type.inner);
+    let func_params = |index: u32| match inner(index)? {
+        CompositeInnerType::Func(ty) => Some(u32::try_from(ty.params().len()).unwrap()),
+        _ => None,
+    };
+    let func_results = |index: u32| match inner(index)? {
+        CompositeInnerType::Func(ty) => Some(u32::try_from(ty.results().len()).unwrap()),
+        _ => None,
+    };
+    match *op {
+        Operator::StructNew { struct_type_index } => match inner(struct_type_index)? {
+            CompositeInnerType::Struct(ty) => Some((u32::try_from(ty.fields.len()).unwrap(), 1)),
+            _ => None,
+        },
+        Operator::ArrayNewFixed {
+            array_type_index,
+            array_size,
+        } => match inner(array_type_index)? {
+            CompositeInnerType::Array(_) => Some((array_size, 1)),
+            _ => None,
+        },
+        Operator::CallRef { type_index } | Operator::CallIndirect { type_index, .. } => {
+            Some((func_params(type_index)? + 1, func_results(type_index)?))
+        }
+        Operator::ReturnCallRef { type_index }
+        | Operator::ReturnCallIndirect { type_index, .. } => {
+            Some((func_params(type_index)? + 1, 0))
+        }
+        _ => None,
+    }
+}
+
 /// Get the current source location from a reader.
 fn cur_srcloc(reader: &BinaryReader) -> ir::SourceLoc {
+    srcloc_at(reader.original_position())
+}
+
+/// Get the source location for the byte code offset `pos`.
+fn srcloc_at(pos: usize) -> ir::SourceLoc {
+    ir::SourceLoc::new(offset_of(pos))
+}
+
+/// Get the byte code offset `pos` as recorded in source locations and block
+/// origins.
+fn offset_of(pos: usize) -> u32 {
     // We record source locations as byte code offsets relative to the beginning of the file.
     // This will panic if bytecode is larger than 4 GB.
-    ir::SourceLoc::new(reader.original_position().try_into().unwrap())
+    pos.try_into().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BlockOrigin, ControlBlockPart, FuncTranslator, TranslationSummary, locals_read_before_set,
+        validate_op_and_get_operand_types,
+    };
+    use crate::builder::LinkOptions;
+    use crate::compiler::Compiler;
+    use crate::func_environ::{FuncEnvironment, FunctionHooks};
+    use crate::translate::ControlFrameKind;
+    use crate::wasm_call_signature;
+    use cranelift_codegen::data_value::DataValue;
+    use cranelift_codegen::ir::{self, InstBuilder, LibCall, UserFuncName};
+    use cranelift_codegen::isa::CallConv;
+    use cranelift_codegen::settings;
+    use cranelift_frontend::FunctionBuilder;
+    use cranelift_interpreter::environment::FunctionStore;
+    use cranelift_interpreter::interpreter::{Interpreter, InterpreterState, LibCallValues};
+    use cranelift_interpreter::step::{ControlFlow, CraneliftTrap};
+    use std::cell::{Cell, RefCell};
+    use wasmparser::{BinaryReader, FuncValidator, FunctionBody, ValidatorResources};
+    use wasmtime_environ::{
+        FunctionBodyData, ModuleEnvironment, ModuleTypesBuilder, Tunables, WasmError, WasmResult,
+        WasmValType,
+    };
+
+    /// A module with an add function and a function with control flow.
+    const MODULE: &str = r#"
+        (func (param i32 i32) (result i32)
+          local.get 0
+          local.get 1
+          i32.add)
+        (func (param i32) (result i32)
+          (local i32 i64)
+          block (result i32)
+            local.get 0
+            if (result i32)
+              local.get 0
+              i32.const 1
+              i32.sub
+              local.set 1
+              local.get 1
+            else
+              i32.const 42
+              br 1
+            end
+          end)
+    "#;
+
+    /// `MODULE` with the first function replaced by one which fails validation
+    /// with a `block` and its result still open.
+    const INVALID_FIRST_MODULE: &str = r#"
+        (func (param i32 i32) (result i32)
+          block (result i32)
+            i64.const 0
+          end)
+        (func (param i32) (result i32)
+          (local i32 i64)
+          block (result i32)
+            local.get 0
+            if (result i32)
+              local.get 0
+              i32.const 1
+              i32.sub
+              local.set 1
+              local.get 1
+            else
+              i32.const 42
+              br 1
+            end
+          end)
+    "#;
+
+    /// A module with a function which needs a deep operand stack, and one
+    /// with an unreachable region, a `loop` and a call.
+    const STACK_DEPTH_MODULE: &str = r#"
+        (func $deep (result i32)
+          i32.const 1
+          i32.const 2
+          i32.const 3
+          i32.const 4
+          i32.add
+          i32.add
+          i32.add)
+        (func (result i32)
+          block (result i32)
+            i32.const 7
+            br 0
+            i32.const 1
+            i32.const 2
+            i32.const 3
+            i32.const 4
+            i32.const 5
+            drop
+            drop
+            drop
+            drop
+          end
+          loop
+          end
+          call $deep
+          i32.add)
+    "#;
+
+    /// A module with a recursive function, a recursive function which traps
+    /// once its recursive call returns, and a function which tail-calls the
+    /// first.
+    const RECURSIVE_MODULE: &str = r#"
+        (func $fact (param i32) (result i32)
+          local.get 0
+          i32.eqz
+          if (result i32)
+            i32.const 1
+            return
+          else
+            local.get 0
+            local.get 0
+            i32.const 1
+            i32.sub
+            call $fact
+            i32.mul
+          end)
+        (func $trapping (param i32) (result i32)
+          local.get 0
+          if
+            local.get 0
+            i32.const 1
+            i32.sub
+            call $trapping
+            drop
+            unreachable
+          end
+          i32.const 0)
+        (func (param i32) (result i32)
+          local.get 0
+          return_call $fact)
+    "#;
+
+    /// A module with a function whose first `br_if` is hinted as unlikely,
+    /// whose second `br_if` is hinted as likely, whose first `if` is hinted as
+    /// unlikely, and whose second `if` isn't hinted.
+    const BRANCH_HINT_MODULE: &str = r#"
+        (func (param i32) (result i32)
+          block
+            local.get 0
+            (@metadata.code.branch_hint "\00")
+            br_if 0
+            local.get 0
+            (@metadata.code.branch_hint "\01")
+            br_if 0
+            local.get 0
+            (@metadata.code.branch_hint "\00")
+            if
+            end
+            local.get 0
+            if
+            end
+          end
+          i32.const 0)
+    "#;
+
+    /// A module with a function nesting each kind of control structure, with
+    /// the offset of each opening operator and `br_table`.
+    const NESTED_CONTROL_MODULE: &str = r#"
+        (func (param i32) (result i32)
+          block (result i32)          ;; 25
+            loop                      ;; 27
+              local.get 0
+              if                      ;; 31
+                local.get 0
+                i32.const 1
+                i32.sub
+                local.set 0
+                br 1
+              else
+              end
+            end
+            local.get 0
+            if (result i32)           ;; 47
+              i32.const 1
+            else
+              i32.const 2
+            end
+            local.get 0
+            br_table 0 1 0            ;; 57
+          end)
+    "#;
+
+    /// A module with functions using GC operators whose arity depends on the
+    /// module's types.
+    const GC_MODULE: &str = r#"
+        (type $point (struct (field i32) (field i64)))
+        (type $f (func (param i32) (result i32)))
+        (type $array (array (mut i32)))
+        (func $id (type $f)
+          local.get 0)
+        (func (type $f)
+          local.get 0
+          i64.const 7
+          struct.new $point
+          drop
+          local.get 0
+          i32.const 1
+          array.new_fixed $array 2
+          drop
+          local.get 0
+          ref.func $id
+          call_ref $f)
+        (elem declare func $id)
+    "#;
+
+    /// A module with a function using relaxed-SIMD operators.
+    const RELAXED_SIMD_MODULE: &str = r#"
+        (func (param v128 v128 v128) (result v128)
+          local.get 0
+          local.get 1
+          local.get 2
+          f32x4.relaxed_madd
+          local.get 1
+          i8x16.relaxed_swizzle
+          i32x4.relaxed_trunc_f32x4_s)
+    "#;
+
+    /// A module with a function whose `funcref` parameter and local are
+    /// mirrored by the shadow stack when it's enabled.
+    #[cfg(feature = "shadow-stack")]
+    const SHADOW_STACK_MODULE: &str = r#"
+        (func (param funcref) (result funcref)
+          (local funcref)
+          (local.set 1 (local.get 0))
+          (local.get 1))
+    "#;
+
+    /// What the tests translate functions from.
+    #[derive(Clone, Copy, Debug)]
+    enum Source {
+        /// The function body's bytes, with `translate_body`.
+        Bytes,
+        /// The pre-decoded locals and operators, with `translate_operators`.
+        Operators,
+        /// The pre-decoded locals and operators, with
+        /// `translate_operators_unchecked`.
+        Unchecked,
+    }
+    use Source::*;
+
+    /// Translates every function in the module `wat` to CLIF from `source`,
+    /// and returns the CLIF text with source locations stripped along with
+    /// the translation summary.
+    fn translate(wat: &str, source: Source) -> Vec<(String, TranslationSummary)> {
+        try_translate(wat, source, None).unwrap()
+    }
+
+    /// Function hooks which fail when the `FuncEnvironment` method they're
+    /// translated by, named by the string, is invoked.
+    struct FailingHooks(&'static str);
+
+    impl FailingHooks {
+        fn invoke(&self, method: &str) -> WasmResult<()> {
+            if self.0 == method {
+                return Err(WasmError::User(format!("{method} failed")));
+            }
+            Ok(())
+        }
+    }
+
+    impl FunctionHooks for FailingHooks {
+        fn prologue(&mut self, _builder: &mut FunctionBuilder) -> WasmResult<()> {
+            self.invoke("translate_function_prologue")
+        }
+
+        fn epilogue(&mut self, _builder: &mut FunctionBuilder) -> WasmResult<()> {
+            self.invoke("translate_function_epilogue")
+        }
+
+        fn before_body(&mut self, _builder: &mut FunctionBuilder) -> WasmResult<()> {
+            self.invoke("before_translate_function")
+        }
+
+        fn after_body(&mut self, _builder: &mut FunctionBuilder) -> WasmResult<()> {
+            self.invoke("after_translate_function")
+        }
+    }
+
+    /// Like `translate`, but makes the function hook translated by the
+    /// `FuncEnvironment` method `failing_hook` fail and returns the first
+    /// translation error.
+    fn try_translate(
+        wat: &str,
+        source: Source,
+        failing_hook: Option<&'static str>,
+    ) -> WasmResult<Vec<(String, TranslationSummary)>> {
+        let funcs = translate_functions(wat, source, |environ| {
+            if let Some(hook) = failing_hook {
+                environ.function_hooks = Some(Box::new(FailingHooks(hook)));
+            }
+        })?;
+        Ok(funcs
+            .into_iter()
+            .map(|(mut func, summary)| {
+                func.srclocs.clear();
+                (func.display().to_string(), summary)
+            })
+            .collect())
+    }
+
+    /// Translates every function in the module `wat` with an environment set
+    /// up by `configure`, returning the translated functions.
+    fn translate_functions(
+        wat: &str,
+        source: Source,
+        configure: impl Fn(&mut FuncEnvironment<'_>),
+    ) -> WasmResult<Vec<(ir::Function, TranslationSummary)>> {
+        translate_each(wat, source, configure).into_iter().collect()
+    }
+
+    /// Like `translate_functions`, but keeps translating with the same
+    /// translator after a function fails, returning each function's result.
+    fn translate_each(
+        wat: &str,
+        source: Source,
+        configure: impl Fn(&mut FuncEnvironment<'_>),
+    ) -> Vec<WasmResult<(ir::Function, TranslationSummary)>> {
+        let mut translator = FuncTranslator::new();
+        each_function(
+            wat,
+            Tunables::default_host(),
+            |mut func, environ, validator, body| {
+                configure(environ);
+                let result = if let Bytes = source {
+                    translator.translate_body(validator, body, &mut func, environ)
+                } else {
+                    let mut locals_reader = body.get_locals_reader().unwrap();
+                    let locals = (0..locals_reader.get_count())
+                        .map(|_| locals_reader.read().unwrap())
+                        .collect::<Vec<_>>();
+                    let mut ops_reader = body.get_operators_reader().unwrap();
+                    let mut ops = vec![];
+                    while !ops_reader.eof() {
+                        ops.push(ops_reader.read_with_offset().unwrap());
+                    }
+                    match source {
+                        Unchecked => translator.translate_operators_unchecked(
+                            validator, &locals, ops, &mut func, environ,
+                        ),
+                        _ => translator
+                            .translate_operators(validator, &locals, ops, &mut func, environ),
+                    }
+                };
+                result.map(|summary| (func, summary))
+            },
+        )
+    }
+
+    /// Validates the module `wat` and calls `f` for each of its functions
+    /// with an empty CLIF function of the right signature, an environment
+    /// and a validator for the function, and its body, returning the results.
+    fn each_function<T>(
+        wat: &str,
+        tunables: Tunables,
+        mut f: impl FnMut(
+            ir::Function,
+            &mut FuncEnvironment<'_>,
+            &mut FuncValidator<ValidatorResources>,
+            FunctionBody<'_>,
+        ) -> T,
+    ) -> Vec<T> {
+        let wasm = wat::parse_str(wat).unwrap();
+        let isa = cranelift_native::builder()
+            .unwrap()
+            .finish(settings::Flags::new(settings::builder()))
+            .unwrap();
+        let compiler = Compiler::new(
+            tunables,
+            isa,
+            None,
+            LinkOptions::default(),
+            None,
+            false,
+            Vec::new(),
+            None,
+        );
+
+        let mut validator = wasmparser::Validator::new();
+        let mut types = ModuleTypesBuilder::new(&validator);
+        let mut translation =
+            ModuleEnvironment::new(compiler.tunables(), &mut validator, &mut types)
+                .translate(wasmparser::Parser::new(0), &wasm)
+                .unwrap();
+        let inputs = std::mem::take(&mut translation.function_body_inputs);
+
+        let mut results = vec![];
+        for (index, FunctionBodyData { validator, body }) in inputs {
+            let func_index = translation.module.func_index(index);
+            let sig = translation.module.functions[func_index]
+                .signature
+                .unwrap_module_type_index();
+            let wasm_func_ty = types[sig].unwrap_func();
+            let func = ir::Function::with_name_signature(
+                UserFuncName::user(0, func_index.as_u32()),
+                wasm_call_signature(compiler.isa(), wasm_func_ty, compiler.tunables()),
+            );
+            let mut environ = FuncEnvironment::new(&compiler, &translation, &types, wasm_func_ty);
+            environ.set_branch_hints(func_index, body.range().start);
+            let mut validator = validator.into_validator(Default::default());
+            results.push(f(func, &mut environ, &mut validator, body));
+        }
+        results
+    }
+
+    /// Strips the source locations of a translated function and renders it
+    /// as CLIF text.
+    fn display(
+        result: WasmResult<(ir::Function, TranslationSummary)>,
+    ) -> (String, TranslationSummary) {
+        let (mut func, summary) = result.unwrap();
+        func.srclocs.clear();
+        (func.display().to_string(), summary)
+    }
+
+    #[test]
+    fn reuse_after_validation_error() {
+        let expected = translate(MODULE, Bytes).pop().unwrap();
+        for source in [Bytes, Operators] {
+            let mut results = translate_each(INVALID_FIRST_MODULE, source, |_| {});
+            assert_eq!(results.len(), 2);
+            let second = results.pop().unwrap();
+            let err = results.pop().unwrap().unwrap_err();
+            assert!(
+                matches!(err.root(), WasmError::InvalidWebAssembly { .. }),
+                "{err:?}"
+            );
+            assert_eq!(display(second), expected);
+        }
+    }
+
+    #[test]
+    fn reuse_after_hook_error() {
+        let expected = translate(MODULE, Bytes);
+        for hook in [
+            "translate_function_prologue",
+            "before_translate_function",
+            "after_translate_function",
+            "translate_function_epilogue",
+        ] {
+            for source in [Bytes, Operators, Unchecked] {
+                // Only the first function's hook fails.
+                let translated = Cell::new(0);
+                let mut results = translate_each(MODULE, source, |environ| {
+                    if translated.replace(translated.get() + 1) == 0 {
+                        environ.function_hooks = Some(Box::new(FailingHooks(hook)));
+                    }
+                });
+                assert_eq!(results.len(), 2);
+                let second = results.pop().unwrap();
+                assert!(results.pop().unwrap().is_err());
+                assert_eq!(display(second), expected[1], "{hook}");
+            }
+        }
+    }
+
+    #[test]
+    fn operators_translate_like_bytes() {
+        let from_bytes = translate(MODULE, Bytes);
+        assert_eq!(from_bytes.len(), 2);
+        assert_eq!(from_bytes, translate(MODULE, Operators));
+        assert_eq!(from_bytes, translate(MODULE, Unchecked));
+    }
+
+    #[test]
+    fn unchecked_operators_need_no_operand_types() {
+        // Operators which need the types of their operands are rejected.
+        let wat = r#"
+            (func (param funcref) (result i32)
+              local.get 0
+              ref.is_null)
+        "#;
+        let err = translate_functions(wat, Unchecked, |_| {}).unwrap_err();
+        assert!(
+            matches!(err.root(), WasmError::Unsupported(msg) if msg.contains("RefIsNull")),
+            "{err:?}"
+        );
+        translate_functions(wat, Operators, |_| {}).unwrap();
+    }
+
+    #[test]
+    fn summary_reports_stack_high_water_marks() {
+        let control = |kind, part, offset| BlockOrigin::Control { kind, part, offset };
+        let results = translate(STACK_DEPTH_MODULE, Bytes);
+        assert_eq!(
+            results[0].1,
+            TranslationSummary {
+                max_stack_depth: 4,
+                max_control_depth: 1,
+                num_operators: 8,
+                has_loops: false,
+                has_calls: false,
+                accesses_memory: false,
+                block_origins: vec![BlockOrigin::FunctionEntry, BlockOrigin::FunctionExit],
+            }
+        );
+
+        // Operators in unreachable code are counted but push nothing, and the
+        // result of the `block` is pushed when its `end` is translated. The
+        // second function's `block` is at offset 39 and its `loop` at 60.
+        assert_eq!(
+            results[1].1,
+            TranslationSummary {
+                max_stack_depth: 2,
+                max_control_depth: 2,
+                num_operators: 18,
+                has_loops: true,
+                has_calls: true,
+                accesses_memory: false,
+                block_origins: vec![
+                    BlockOrigin::FunctionEntry,
+                    BlockOrigin::FunctionExit,
+                    control(ControlFrameKind::Block, ControlBlockPart::End, 39),
+                    control(ControlFrameKind::Loop, ControlBlockPart::LoopHeader, 60),
+                    control(ControlFrameKind::Loop, ControlBlockPart::End, 60),
+                ],
+            }
+        );
+        assert_eq!(results, translate(STACK_DEPTH_MODULE, Operators));
+    }
+
+    #[test]
+    fn hook_errors_identify_the_function() {
+        // The first function's operators span offsets 32 to 38 of `MODULE`.
+        for (hook, offset) in [
+            ("before_translate_function", 32),
+            ("after_translate_function", 38),
+            ("translate_function_epilogue", 38),
+        ] {
+            for source in [Bytes, Operators] {
+                let err = try_translate(MODULE, source, Some(hook)).unwrap_err();
+                let expected = format!("{hook} failed");
+                assert!(
+                    matches!(err.root(), WasmError::User(msg) if *msg == expected),
+                    "{err:?}"
+                );
+                let rendered = format!("{:?}", anyhow::Error::from(err));
+                assert!(
+                    rendered.contains(&format!(
+                        "failed to translate function u0:0 at offset {offset}"
+                    )),
+                    "{rendered}"
+                );
+                assert!(rendered.contains(&expected), "{rendered}");
+            }
+        }
+    }
+
+    /// Instruments each entry to a function with a call to the `ceilf32`
+    /// libcall with `1.0`, and each exit with a call with `-1.0`, which
+    /// `record_depth_change` records when the function is interpreted.
+    struct DepthHooks;
+
+    impl DepthHooks {
+        fn change_depth(builder: &mut FunctionBuilder, delta: f32) -> WasmResult<()> {
+            let mut sig = ir::Signature::new(CallConv::SystemV);
+            sig.params.push(ir::AbiParam::new(ir::types::F32));
+            sig.returns.push(ir::AbiParam::new(ir::types::F32));
+            let signature = builder.import_signature(sig);
+            let func = builder.import_function(ir::ExtFuncData {
+                name: ir::ExternalName::LibCall(LibCall::CeilF32),
+                signature,
+                colocated: false,
+            });
+            let delta = builder.ins().f32const(delta);
+            builder.ins().call(func, &[delta]);
+            Ok(())
+        }
+    }
+
+    impl FunctionHooks for DepthHooks {
+        fn prologue(&mut self, builder: &mut FunctionBuilder) -> WasmResult<()> {
+            Self::change_depth(builder, 1.0)
+        }
+
+        fn epilogue(&mut self, builder: &mut FunctionBuilder) -> WasmResult<()> {
+            Self::change_depth(builder, -1.0)
+        }
+    }
+
+    thread_local! {
+        static DEPTH_CHANGES: RefCell<Vec<i32>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn record_depth_change(
+        libcall: LibCall,
+        args: LibCallValues,
+    ) -> Result<LibCallValues, CraneliftTrap> {
+        assert_eq!(libcall, LibCall::CeilF32);
+        let DataValue::F32(delta) = args[0] else {
+            unreachable!()
+        };
+        DEPTH_CHANGES.with(|changes| changes.borrow_mut().push(delta.as_f32() as i32));
+        Ok(args)
+    }
+
+    #[test]
+    fn entry_and_exit_hooks_are_balanced() {
+        let funcs = translate_functions(RECURSIVE_MODULE, Bytes, |environ| {
+            environ.function_hooks = Some(Box::new(DepthHooks));
+        })
+        .unwrap();
+        let mut store = FunctionStore::default();
+        for (func, _) in &funcs {
+            store.add(func.name.to_string(), func);
+        }
+
+        // Calls the function at `index` with `arg`, returning whether it
+        // returned rather than trapped along with the depth changes recorded
+        // by the hooks.
+        let run = |index: usize, arg: i32| {
+            DEPTH_CHANGES.with(|changes| changes.borrow_mut().clear());
+            let state = InterpreterState::default()
+                .with_function_store(store.clone())
+                .with_libcall_handler(record_depth_change);
+            let args = [DataValue::I64(0), DataValue::I64(0), DataValue::I32(arg)];
+            let returned = match Interpreter::new(state)
+                .call_by_name(&funcs[index].0.name.to_string(), &args)
+                .unwrap()
+            {
+                ControlFlow::Return(_) => true,
+                ControlFlow::Trap(CraneliftTrap::User(_)) => false,
+                _ => unreachable!(),
+            };
+            (returned, DEPTH_CHANGES.with(|changes| changes.take()))
+        };
+
+        // Every entry to `$fact` is matched by an exit, from both its
+        // explicit and its implicit return.
+        assert_eq!(run(0, 2), (true, vec![1, 1, 1, -1, -1, -1]));
+
+        // `$trapping` traps after its innermost call has returned, leaving
+        // the two frames which were unwound unbalanced.
+        assert_eq!(run(1, 0), (true, vec![1, -1]));
+        assert_eq!(run(1, 2), (false, vec![1, 1, 1, -1]));
+
+        // A tail call exits the caller before the callee is entered.
+        assert_eq!(run(2, 1), (true, vec![1, -1, 1, 1, -1, -1]));
+    }
+
+    #[test]
+    fn prologue_errors_identify_the_function() {
+        // The first function's body starts with its locals at offset 31 of
+        // `MODULE`, and its operators at offset 32.
+        for (source, offset) in [(Bytes, 31), (Operators, 32)] {
+            let err =
+                try_translate(MODULE, source, Some("translate_function_prologue")).unwrap_err();
+            let rendered = format!("{:?}", anyhow::Error::from(err));
+            assert!(
+                rendered.contains(&format!(
+                    "failed to translate function u0:0 at offset {offset}"
+                )),
+                "{rendered}"
+            );
+        }
+    }
+
+    /// Returns which locals of the only function in `wat` are read before
+    /// being set.
+    fn read_before_set(wat: &str) -> Vec<bool> {
+        each_function(wat, Tunables::default_host(), |_, _, validator, body| {
+            let mut reader = body.get_binary_reader();
+            for _ in 0..reader.read_var_u32().unwrap() {
+                let pos = reader.original_position();
+                let count = reader.read_var_u32().unwrap();
+                validator
+                    .define_locals(pos, count, reader.read().unwrap())
+                    .unwrap();
+            }
+            locals_read_before_set(&reader, validator.len_locals() as usize)
+        })
+        .pop()
+        .unwrap()
+    }
+
+    #[test]
+    fn locals_set_in_one_branch_are_read_before_set() {
+        let read = read_before_set(
+            r#"
+            (module
+              (func (param i32) (local i32 i32 i32)
+                i32.const 3
+                local.set 3
+                local.get 0
+                if
+                  i32.const 1
+                  local.set 1
+                  local.get 1
+                  local.get 3
+                  drop
+                  drop
+                else
+                  local.get 1
+                  local.get 3
+                  drop
+                  drop
+                end
+                local.get 0
+                if
+                  i32.const 2
+                  local.set 2
+                end
+                local.get 2
+                drop))
+            "#,
+        );
+        assert_eq!(read, [true, true, true, false]);
+    }
+
+    #[test]
+    fn locals_carried_around_loops_are_read_before_set() {
+        let read = read_before_set(
+            r#"
+            (module
+              (func (param i32) (local i32 i32)
+                loop
+                  i32.const 1
+                  local.set 1
+                  local.get 2
+                  local.get 1
+                  i32.add
+                  local.set 2
+                  local.get 0
+                  br_if 0
+                end))
+            "#,
+        );
+        assert_eq!(read, [true, false, true]);
+    }
+
+    #[test]
+    fn locals_set_by_tee_are_set() {
+        let read = read_before_set(
+            r#"
+            (module
+              (func (local i32 i32)
+                i32.const 5
+                local.tee 0
+                local.set 1
+                local.get 0
+                local.get 1
+                i32.add
+                drop))
+            "#,
+        );
+        assert_eq!(read, [false, false]);
+
+        // A body that fails to decode, here an `i32.const` without its
+        // immediate, reads everything.
+        let truncated = BinaryReader::new(&[0x41], 0);
+        assert_eq!(locals_read_before_set(&truncated, 2), [true, true]);
+    }
+
+    #[test]
+    fn branch_hints_mark_cold_blocks() {
+        // Each hinted branch moves exactly one block out of line: the taken
+        // side of the unlikely `br_if`, the fallthrough of the likely one and
+        // the `then` side of the unlikely `if`.
+        for source in [Bytes, Operators] {
+            let results = translate(BRANCH_HINT_MODULE, source);
+            assert_eq!(
+                results[0].0.matches(" cold:").count(),
+                3,
+                "{}",
+                results[0].0
+            );
+        }
+
+        // Without the hints nothing is cold.
+        let unhinted = BRANCH_HINT_MODULE
+            .lines()
+            .filter(|line| !line.contains("@metadata.code.branch_hint"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let results = translate(&unhinted, Bytes);
+        assert!(!results[0].0.contains(" cold"), "{}", results[0].0);
+    }
+
+    #[test]
+    fn block_origins_map_blocks_to_control_structures() {
+        use BlockOrigin::*;
+        use ControlBlockPart::*;
+        let control = |kind, part, offset| Control { kind, part, offset };
+
+        for source in [Bytes, Operators] {
+            let funcs = translate_functions(NESTED_CONTROL_MODULE, source, |_| {}).unwrap();
+            let (func, summary) = &funcs[0];
+            assert_eq!(summary.block_origins.len(), func.dfg.num_blocks());
+            assert_eq!(
+                summary.block_origins,
+                [
+                    FunctionEntry,
+                    FunctionExit,
+                    control(ControlFrameKind::Block, End, 25),
+                    control(ControlFrameKind::Loop, LoopHeader, 27),
+                    control(ControlFrameKind::Loop, End, 27),
+                    control(ControlFrameKind::If, Then, 31),
+                    control(ControlFrameKind::If, End, 31),
+                    // Allocated when the `else` is reached.
+                    control(ControlFrameKind::If, Else, 31),
+                    control(ControlFrameKind::If, Then, 47),
+                    control(ControlFrameKind::If, End, 47),
+                    // Allocated eagerly since the `if` has results.
+                    control(ControlFrameKind::If, Else, 47),
+                    BranchTableEdge { offset: 57 },
+                    BranchTableEdge { offset: 57 },
+                ]
+            );
+        }
+
+        // Blocks created while translating other operators are internal, and
+        // every block is covered.
+        for wat in [
+            MODULE,
+            STACK_DEPTH_MODULE,
+            RECURSIVE_MODULE,
+            BRANCH_HINT_MODULE,
+        ] {
+            for (func, summary) in translate_functions(wat, Bytes, |_| {}).unwrap() {
+                assert_eq!(summary.block_origins.len(), func.dfg.num_blocks());
+                assert_eq!(summary.block_origins[0], FunctionEntry);
+                assert_eq!(summary.block_origins[1], FunctionExit);
+            }
+        }
+        let funcs = translate_functions(BRANCH_HINT_MODULE, Bytes, |_| {}).unwrap();
+        assert!(
+            funcs[0]
+                .1
+                .block_origins
+                .iter()
+                .any(|origin| matches!(origin, Internal { .. }))
+        );
+    }
+
+    /// Validates every function in the module `wat` and returns the operand
+    /// types that the operator hooks would receive for each of its operators.
+    fn hook_operand_types(wat: &str) -> Vec<Vec<Option<Vec<WasmValType>>>> {
+        each_function(
+            wat,
+            Tunables::default_host(),
+            |_, environ, validator, body| {
+                let mut locals_reader = body.get_locals_reader().unwrap();
+                for _ in 0..locals_reader.get_count() {
+                    let offset = locals_reader.original_position();
+                    let (count, ty) = locals_reader.read().unwrap();
+                    validator.define_locals(offset, count, ty).unwrap();
+                }
+                let mut ops_reader = body.get_operators_reader().unwrap();
+                let mut operand_types = vec![];
+                let mut func_operand_types = vec![];
+                while !ops_reader.eof() {
+                    let (op, pos) = ops_reader.read_with_offset().unwrap();
+                    let types = validate_op_and_get_operand_types(
+                        validator,
+                        environ,
+                        &mut operand_types,
+                        &op,
+                        pos,
+                    )
+                    .unwrap();
+                    func_operand_types.push(types.map(<[_]>::to_vec));
+                }
+                func_operand_types
+            },
+        )
+    }
+
+    #[test]
+    fn hooks_receive_operand_types_of_gc_operators() {
+        use WasmValType::{I32, I64, Ref};
+
+        let results = hook_operand_types(GC_MODULE);
+        let types: Vec<_> = results[1]
+            .iter()
+            .map(|types| types.as_deref().expect("reachable operators have types"))
+            .collect();
+        assert_eq!(types.len(), 12);
+        assert_eq!(types[2], [I32, I64], "struct.new");
+        assert!(matches!(types[3], [Ref(_)]), "drop of a struct");
+        assert_eq!(types[6], [I32, I32], "array.new_fixed");
+        assert!(matches!(types[10], [I32, Ref(_)]), "call_ref");
+        assert_eq!(types[11], [I32], "end");
+    }
+
+    #[test]
+    fn hooks_receive_operand_types_of_relaxed_simd_operators() {
+        use WasmValType::V128;
+
+        let results = hook_operand_types(RELAXED_SIMD_MODULE);
+        assert_eq!(
+            results[0],
+            [
+                Some(vec![]),
+                Some(vec![]),
+                Some(vec![]),
+                Some(vec![V128, V128, V128]),
+                Some(vec![]),
+                Some(vec![V128, V128]),
+                Some(vec![V128]),
+                Some(vec![V128]),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "shadow-stack")]
+    fn shadow_stack_detects_corrupted_entries() {
+        use cranelift_codegen::cursor::{Cursor, FuncCursor};
+        use cranelift_codegen::ir::InstructionData;
+        use wasmtime_environ::ShadowStackCheck;
+
+        // Runs `func` with a `funcref` argument and returns its result, or
+        // the trap it raised.
+        let run = |func: &ir::Function| {
+            let state = InterpreterState::default().with_function_store(func.into());
+            let args = [DataValue::I64(0), DataValue::I64(0), DataValue::I64(0x1234)];
+            match Interpreter::new(state)
+                .call_by_name(&func.name.to_string(), &args)
+                .unwrap()
+            {
+                ControlFlow::Return(results) => Ok(results.into_vec()),
+                ControlFlow::Trap(trap) => Err(trap),
+                _ => unreachable!(),
+            }
+        };
+
+        // Overwrites the shadow copy of the parameter right after it's
+        // initialized.
+        let corrupt = |func: &ir::Function| {
+            let mut func = func.clone();
+            let entry = func.layout.entry_block().unwrap();
+            let mut pos = FuncCursor::new(&mut func).at_top(entry);
+            let slot = loop {
+                let inst = pos.next_inst().unwrap();
+                if let InstructionData::StackStore { stack_slot, .. } = pos.func.dfg.insts[inst] {
+                    break stack_slot;
+                }
+            };
+            pos.next_inst();
+            let bad = pos.ins().iconst(ir::types::I64, 0x5678);
+            pos.ins().stack_store(bad, slot, 0);
+            func
+        };
+
+        for (check, detected) in [
+            (
+                ShadowStackCheck::Trap,
+                Err(CraneliftTrap::User(crate::TRAP_INTERNAL_ASSERT)),
+            ),
+            (ShadowStackCheck::Breakpoint, Err(CraneliftTrap::Debug)),
+            (
+                ShadowStackCheck::Unchecked,
+                Ok(vec![DataValue::I64(0x1234)]),
+            ),
+        ] {
+            let mut tunables = Tunables::default_host();
+            tunables.shadow_stack = Some(check);
+            let func = each_function(
+                SHADOW_STACK_MODULE,
+                tunables,
+                |mut func, environ, validator, body| {
+                    FuncTranslator::new()
+                        .translate_body(validator, body, &mut func, environ)
+                        .unwrap();
+                    func
+                },
+            )
+            .remove(0);
+
+            assert_eq!(run(&func), Ok(vec![DataValue::I64(0x1234)]), "{check:?}");
+            assert_eq!(run(&corrupt(&func)), detected, "{check:?}");
+        }
+    }
 }
@@ -19,8 +19,10 @@ mod table;
 mod translation_utils;
 
 pub use self::environ::{GlobalVariable, StructFieldsVec, TargetEnvironment};
-pub use self::func_translator::FuncTranslator;
+pub use self::func_translator::{
+    BlockOrigin, ControlBlockPart, FuncTranslator, TranslationSummary,
+};
 pub use self::heap::{Heap, HeapData};
-pub use self::stack::FuncTranslationStacks;
+pub use self::stack::{ControlFrameInfo, ControlFrameKind, FuncTranslationStacks};
 pub use self::table::{TableData, TableSize};
 pub use self::translation_utils::*;
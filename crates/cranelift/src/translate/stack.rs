@@ -5,6 +5,7 @@
 //! a single function.
 
 use cranelift_codegen::ir::{self, Block, Inst, Value};
+use cranelift_frontend::FunctionBuilder;
 use std::vec::Vec;
 
 /// Information about the presence of an associated `else` for an `if`, or the
@@ -23,6 +24,10 @@ pub enum ElseData {
 
         /// The placeholder block we're replacing.
         placeholder: Block,
+
+        /// Whether a branch hint said that the `if` is likely taken, in which
+        /// case the `else` block is cold if we end up allocating one.
+        else_is_cold: bool,
     },
 
     /// We have already allocated an `else` block.
@@ -148,6 +153,22 @@ impl ControlStackFrame {
         }
     }
 
+    /// Returns the read-only view of this frame handed out to translation
+    /// hooks.
+    pub fn info(&self) -> ControlFrameInfo {
+        let kind = match self {
+            Self::If { .. } => ControlFrameKind::If,
+            Self::Block { .. } => ControlFrameKind::Block,
+            Self::Loop { .. } => ControlFrameKind::Loop,
+        };
+        ControlFrameInfo {
+            kind,
+            num_param_values: self.num_param_values(),
+            num_return_values: self.num_return_values(),
+            exit_is_branched_to: self.exit_is_branched_to(),
+        }
+    }
+
     pub fn is_loop(&self) -> bool {
         match *self {
             Self::If { .. } | Self::Block { .. } => false,
@@ -211,6 +232,32 @@ impl ControlStackFrame {
     }
 }
 
+/// The kind of Wasm control structure that a [`ControlFrameInfo`] describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlFrameKind {
+    /// A `block ... end`, or the implicit block of the whole function body.
+    Block,
+    /// A `loop ... end`.
+    Loop,
+    /// An `if ... end` or `if ... else ... end`.
+    If,
+}
+
+/// A read-only snapshot of a control stack frame, suitable for inspection by
+/// translation hooks such as `before_translate_operator`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControlFrameInfo {
+    /// What kind of control structure this frame is.
+    pub kind: ControlFrameKind,
+    /// The number of parameters the control structure takes.
+    pub num_param_values: usize,
+    /// The number of results the control structure produces.
+    pub num_return_values: usize,
+    /// Whether any branch targeting the exit of this frame has been translated
+    /// so far. Always `false` for loops, whose branches target the header.
+    pub exit_is_branched_to: bool,
+}
+
 /// Keeps track of Wasm's operand and control stacks, as well as reachability
 /// for each control frame.
 pub struct FuncTranslationStacks {
@@ -222,6 +269,10 @@ pub struct FuncTranslationStacks {
     /// Is the current translation state still reachable? This is false when translating operators
     /// like End, Return, or Unreachable.
     pub(crate) reachable: bool,
+    /// The largest size `stack` has reached since `initialize`.
+    pub(crate) max_stack_depth: usize,
+    /// The largest size `control_stack` has reached since `initialize`.
+    pub(crate) max_control_depth: usize,
 }
 
 // Public methods that are exposed to non- API consumers.
@@ -231,6 +282,44 @@ impl FuncTranslationStacks {
     pub fn reachable(&self) -> bool {
         self.reachable
     }
+
+    /// The number of control frames currently active, including the implicit
+    /// frame for the function body itself.
+    #[inline]
+    pub fn control_depth(&self) -> usize {
+        self.control_stack.len()
+    }
+
+    /// Returns information about the control frame `relative_depth` frames out
+    /// from the innermost one, using the same numbering as the label operand
+    /// of `br`. Returns `None` if there is no such frame.
+    pub fn control_frame(&self, relative_depth: u32) -> Option<ControlFrameInfo> {
+        let relative_depth = usize::try_from(relative_depth).ok()?;
+        let index = self.control_stack.len().checked_sub(relative_depth + 1)?;
+        Some(self.control_stack[index].info())
+    }
+
+    /// Runs `f` to emit instructions only if the current position of
+    /// translation is reachable, returning `None` otherwise.
+    ///
+    /// After operators such as `br`, `br_table`, `return`, or `unreachable`
+    /// the builder is left positioned at the end of a terminated block and the
+    /// value stack may have been truncated. Instructions emitted there would
+    /// be appended after a terminator and fail verification, so translation
+    /// hooks that inject code after arbitrary operators should do so through
+    /// this helper. Code emitted while translation is unreachable is flagged
+    /// by a debug assertion in the translator.
+    pub fn emit_if_reachable<R>(
+        &self,
+        builder: &mut FunctionBuilder,
+        f: impl FnOnce(&mut FunctionBuilder) -> R,
+    ) -> Option<R> {
+        if self.reachable && !builder.is_unreachable() {
+            Some(f(builder))
+        } else {
+            None
+        }
+    }
 }
 
 impl FuncTranslationStacks {
@@ -240,13 +329,35 @@ impl FuncTranslationStacks {
             stack: Vec::new(),
             control_stack: Vec::new(),
             reachable: true,
+            max_stack_depth: 0,
+            max_control_depth: 0,
         }
     }
 
-    fn clear(&mut self) {
-        debug_assert!(self.stack.is_empty());
-        debug_assert!(self.control_stack.is_empty());
-        self.reachable = true;
+    /// Reset every field to its initial state, keeping the allocations of the
+    /// stacks. Anything left over from a translation which failed midway is
+    /// discarded.
+    pub(crate) fn clear(&mut self) {
+        let FuncTranslationStacks {
+            stack,
+            control_stack,
+            reachable,
+            max_stack_depth,
+            max_control_depth,
+        } = self;
+        stack.clear();
+        control_stack.clear();
+        *reachable = true;
+        *max_stack_depth = 0;
+        *max_control_depth = 0;
+    }
+
+    /// Record the current depths of the value and control stacks in their
+    /// high-water marks. Called everywhere the stacks grow.
+    #[inline]
+    fn record_depths(&mut self) {
+        self.max_stack_depth = self.max_stack_depth.max(self.stack.len());
+        self.max_control_depth = self.max_control_depth.max(self.control_stack.len());
     }
 
     /// Initialize the state for compiling a function with the given signature.
@@ -268,17 +379,20 @@ impl FuncTranslationStacks {
     /// Push a value.
     pub(crate) fn push1(&mut self, val: Value) {
         self.stack.push(val);
+        self.record_depths();
     }
 
     /// Push two values.
     pub(crate) fn push2(&mut self, val1: Value, val2: Value) {
         self.stack.push(val1);
         self.stack.push(val2);
+        self.record_depths();
     }
 
     /// Push multiple values.
     pub(crate) fn pushn(&mut self, vals: &[Value]) {
         self.stack.extend_from_slice(vals);
+        self.record_depths();
     }
 
     /// Pop one value.
@@ -379,6 +493,7 @@ impl FuncTranslationStacks {
             num_return_values: num_result_types,
             exit_is_branched_to: false,
         });
+        self.record_depths();
     }
 
     /// Push a loop on the control stack.
@@ -397,6 +512,7 @@ impl FuncTranslationStacks {
             num_param_values: num_param_types,
             num_return_values: num_result_types,
         });
+        self.record_depths();
     }
 
     /// Push an if on the control stack.
@@ -432,5 +548,6 @@ impl FuncTranslationStacks {
             consequent_ends_reachable: None,
             blocktype,
         });
+        self.record_depths();
     }
 }
@@ -239,6 +239,14 @@ macro_rules! foreach_builtin_function {
             // the Option<VMContObj>, as in previous libcall.
             #[cfg(feature = "stack-switching")]
             table_fill_cont_obj(vmctx: vmctx, table: u32, dst: u64, value_contref: pointer, value_revision: u64, len: u64) -> bool;
+
+            // Invoked before a linear memory access when memory accesses are
+            // instrumented with a host callback.
+            memory_access(vmctx: vmctx, memory: u32, addr: u64, size: u32, is_store: u32) -> bool;
+
+            // Invoked before an unconditional trap when traps are lowered to
+            // notify the host, see `UnreachableLowering`.
+            trap_with_context(vmctx: vmctx, func_index: u32, wasm_offset: u32, code: u8);
         }
     };
 }
@@ -0,0 +1,64 @@
+//! Configurable gas costs for WebAssembly operators.
+
+use crate::prelude::*;
+use core::fmt;
+use wasmparser::Operator;
+
+/// Assigns the gas charged for each WebAssembly operator, configured with
+/// [`CompilerBuilder::gas_policy`](crate::CompilerBuilder::gas_policy).
+///
+/// Gas is charged through fuel: a policy replaces the default cost of one
+/// unit of fuel for most operators, so the remaining gas is read and set
+/// like fuel and running out of gas is handled like running out of fuel.
+///
+/// Charges are accumulated while a basic block is translated and added to
+/// the fuel counter once at the end of the block, so the gas charged for a
+/// block is exactly the sum of the charges of its operators. Operators in
+/// unreachable code are never executed and never charged, and `else` and
+/// `end` are charged when control falls through them but not when a branch
+/// targets the end of their block.
+pub trait GasPolicy: Send + Sync + fmt::Debug {
+    /// Returns the gas charged each time `op` is executed.
+    fn charge(&mut self, op: &Operator<'_>) -> u64;
+
+    /// Returns the gas charged each time the header of a loop is reached,
+    /// on entry to the loop as well as on each back edge.
+    ///
+    /// The `loop` operator itself is only charged on entry, so this is the
+    /// place for a per-iteration cost. Defaults to no gas.
+    fn charge_loop_iteration(&mut self) -> u64 {
+        0
+    }
+
+    /// Returns whether the remaining gas is checked right before each call,
+    /// in addition to the checks placed according to the
+    /// [`InterruptCheckPlacement`](crate::InterruptCheckPlacement).
+    ///
+    /// The gas charged up to and including a call is always settled before
+    /// the call so the callee, including host functions, observes it. With
+    /// this check a call made once gas has run out doesn't reach the
+    /// callee, which matters for host functions as they don't check gas
+    /// themselves. Calls through `call_ref` aren't checked. Defaults to
+    /// `false`.
+    fn check_before_calls(&self) -> bool {
+        false
+    }
+
+    /// Returns a string identifying this policy, including any configuration
+    /// which affects its charges.
+    ///
+    /// Compiled artifacts record it, and cache keys include it, so that code
+    /// compiled with one policy isn't loaded into an engine configured with
+    /// another. Defaults to the policy's `Debug` representation, which
+    /// suffices for policies whose charges are determined by their fields.
+    fn identity(&self) -> String {
+        format!("{self:?}")
+    }
+
+    /// Returns the policy used to translate a single function.
+    ///
+    /// Functions may be translated in parallel, each with its own policy
+    /// created by this method, so policies which don't carry state across
+    /// operators typically return a clone of themselves.
+    fn for_function(&self) -> Box<dyn GasPolicy>;
+}
@@ -17,6 +17,7 @@ use std::path;
 use std::sync::Arc;
 
 mod address_map;
+mod gas;
 mod module_artifacts;
 mod module_environ;
 mod module_types;
@@ -24,6 +25,7 @@ mod stack_maps;
 mod trap_encoding;
 
 pub use self::address_map::*;
+pub use self::gas::*;
 pub use self::module_artifacts::*;
 pub use self::module_environ::*;
 pub use self::module_types::*;
@@ -81,6 +83,47 @@ pub enum RelocationTarget {
     Builtin(BuiltinFunctionIndex),
     /// A pulley->host call from the interpreter.
     PulleyHostcall(u32),
+    /// This is a reference to a constant shared by the module's functions.
+    ModuleConstant(ModuleConstant),
+}
+
+/// A constant which is emitted once into the text section of a compiled
+/// module and referenced by address from each function that uses it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ModuleConstant {
+    /// Sixteen zero bytes, the all-zero `v128`.
+    V128Zero,
+}
+
+impl ModuleConstant {
+    /// Returns the constant with the index `index`, if any.
+    pub fn from_u32(index: u32) -> Option<ModuleConstant> {
+        match index {
+            0 => Some(ModuleConstant::V128Zero),
+            _ => None,
+        }
+    }
+
+    /// Returns the index of this constant, the inverse of `from_u32`.
+    pub fn index(&self) -> u32 {
+        match self {
+            ModuleConstant::V128Zero => 0,
+        }
+    }
+
+    /// Returns the bytes of this constant.
+    pub fn data(&self) -> &'static [u8] {
+        match self {
+            ModuleConstant::V128Zero => &[0; 16],
+        }
+    }
+
+    /// Returns the required alignment of this constant, in bytes.
+    pub fn align(&self) -> u32 {
+        match self {
+            ModuleConstant::V128Zero => 16,
+        }
+    }
 }
 
 /// Implementation of an incremental compilation's key/value cache store.
@@ -147,6 +190,59 @@ pub trait CompilerBuilder: Send + Sync + fmt::Debug {
 
     /// Enables or disables wmemcheck during runtime according to the wmemcheck CLI flag.
     fn wmemcheck(&mut self, _enable: bool) {}
+
+    /// Configures rules interposing on direct calls to imported functions,
+    /// applied in order with the first rule matching an import taking effect.
+    ///
+    /// This will return an error if the compiler does not support import call
+    /// interposition.
+    fn import_call_interpositions(&mut self, _rules: &[ImportCallInterposition]) -> Result<()> {
+        anyhow::bail!("import call interposition not supported");
+    }
+
+    /// Configures the [`GasPolicy`] assigning the fuel consumed by each
+    /// WebAssembly operator, replacing the default costs.
+    ///
+    /// This will return an error if the compiler does not support gas
+    /// policies.
+    fn gas_policy(&mut self, _policy: Arc<dyn GasPolicy>) -> Result<()> {
+        anyhow::bail!("gas policies not supported");
+    }
+}
+
+/// A rule changing how direct calls to an imported function are compiled.
+///
+/// Imports are identified by their module and field names and a rule only
+/// applies to modules which import a function under those names.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImportCallInterposition {
+    /// The module name of the imported function whose calls are interposed.
+    pub module: String,
+    /// The field name of the imported function whose calls are interposed.
+    pub name: String,
+    /// How calls to the imported function are compiled.
+    pub action: ImportCallAction,
+}
+
+/// How direct calls to an imported function are compiled, see
+/// [`ImportCallInterposition`].
+///
+/// Every function named here is identified by its `(module, name)` and must
+/// also be imported by any module calling the interposed import.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImportCallAction {
+    /// Call this imported function, which must have the same type as the
+    /// interposed import, instead.
+    Redirect(String, String),
+    /// Call `pre`, if any, with the call's arguments before the call and
+    /// `post`, if any, with its results after it. Neither function may return
+    /// any results.
+    Wrap {
+        /// The function called before the interposed import.
+        pre: Option<(String, String)>,
+        /// The function called after the interposed import.
+        post: Option<(String, String)>,
+    },
 }
 
 /// Description of compiler settings returned by [`CompilerBuilder::settings`].
@@ -3,9 +3,9 @@
 
 use crate::prelude::*;
 use crate::{
-    CompiledFunctionInfo, CompiledModuleInfo, DebugInfoData, DefinedFuncIndex, FunctionLoc,
-    FunctionName, MemoryInitialization, Metadata, ModuleInternedTypeIndex, ModuleTranslation,
-    PrimaryMap, Tunables, obj,
+    BoundsCheckStrategy, CompiledFunctionInfo, CompiledModuleInfo, DebugInfoData, DefinedFuncIndex,
+    FunctionLoc, FunctionName, MemoryInitialization, Metadata, ModuleInternedTypeIndex,
+    ModuleTranslation, PrimaryMap, Tunables, obj,
 };
 use anyhow::{Result, bail};
 use object::SectionKind;
@@ -24,6 +24,9 @@ pub struct ObjectBuilder<'a> {
     /// General compilation configuration.
     tunables: &'a Tunables,
 
+    /// Log2 of the page size that the compilation target is assumed to have.
+    page_size_log2: u8,
+
     /// The section identifier for "rodata" which is where wasm data segments
     /// will go.
     data: SectionId,
@@ -43,7 +46,11 @@ pub struct ObjectBuilder<'a> {
 
 impl<'a> ObjectBuilder<'a> {
     /// Creates a new builder for the `obj` specified.
-    pub fn new(mut obj: Object<'a>, tunables: &'a Tunables) -> ObjectBuilder<'a> {
+    pub fn new(
+        mut obj: Object<'a>,
+        tunables: &'a Tunables,
+        page_size_log2: u8,
+    ) -> ObjectBuilder<'a> {
         let data = obj.add_section(
             obj.segment_name(StandardSegment::Data).to_vec(),
             obj::ELF_WASM_DATA.as_bytes().to_vec(),
@@ -52,6 +59,7 @@ impl<'a> ObjectBuilder<'a> {
         ObjectBuilder {
             obj,
             tunables,
+            page_size_log2,
             data,
             names: None,
             dwarf: None,
@@ -217,6 +225,14 @@ impl<'a> ObjectBuilder<'a> {
             self.push_debuginfo(&mut dwarf, &debuginfo);
         }
 
+        let memory_bounds_checks = module
+            .memories
+            .values()
+            .map(|memory| {
+                BoundsCheckStrategy::for_memory(memory, self.tunables, self.page_size_log2)
+            })
+            .collect();
+
         Ok(CompiledModuleInfo {
             module,
             funcs,
@@ -227,6 +243,7 @@ impl<'a> ObjectBuilder<'a> {
                 code_section_offset: debuginfo.wasm_file.code_section_offset,
                 has_wasm_debuginfo: self.tunables.parse_wasm_debuginfo,
                 dwarf,
+                memory_bounds_checks,
             },
         })
     }
@@ -19,9 +19,9 @@ use std::mem;
 use std::path::PathBuf;
 use std::sync::Arc;
 use wasmparser::{
-    CustomSectionReader, DataKind, ElementItems, ElementKind, Encoding, ExternalKind,
-    FuncToValidate, FunctionBody, KnownCustom, NameSectionReader, Naming, Parser, Payload, TypeRef,
-    Validator, ValidatorResources, types::Types,
+    BranchHintSectionReader, CustomSectionReader, DataKind, ElementItems, ElementKind, Encoding,
+    ExternalKind, FuncToValidate, FunctionBody, KnownCustom, NameSectionReader, Naming, Parser,
+    Payload, TypeRef, Validator, ValidatorResources, types::Types,
 };
 
 /// Object containing the standalone environment information.
@@ -62,6 +62,21 @@ pub struct ModuleTranslation<'data> {
     pub known_imported_functions:
         SecondaryMap<FuncIndex, Option<(StaticModuleIndex, DefinedFuncIndex)>>,
 
+    /// For each defined function, the index of its first `call_indirect`
+    /// inline cache within the `VMContext`'s call-indirect cache array. A
+    /// function's caches are numbered consecutively from this index in the
+    /// order its `call_indirect` instructions appear.
+    ///
+    /// This is empty unless `Tunables::call_indirect_inline_caches` is
+    /// enabled.
+    pub call_indirect_cache_starts: PrimaryMap<DefinedFuncIndex, u32>,
+
+    /// Branch hints from the `metadata.code.branch_hint` custom section, keyed
+    /// by function and then by the offset of the hinted `if` or `br_if` from
+    /// the start of the function's body. A hint of `true` means that the
+    /// branch is likely taken.
+    pub branch_hints: HashMap<FuncIndex, HashMap<u32, bool>>,
+
     /// A list of type signatures which are considered exported from this
     /// module, or those that can possibly be called. This list is sorted, and
     /// trampolines for each of these signatures are required.
@@ -565,6 +580,18 @@ impl<'a, 'data> ModuleEnvironment<'a, 'data> {
                             params: sig.params().into(),
                         });
                 }
+                if self.tunables.call_indirect_inline_caches {
+                    let start = u32::try_from(self.result.module.num_call_indirect_caches).unwrap();
+                    self.result.call_indirect_cache_starts.push(start);
+                    let mut ops = body.get_operators_reader()?;
+                    while !ops.eof() {
+                        if let wasmparser::Operator::CallIndirect { .. }
+                        | wasmparser::Operator::ReturnCallIndirect { .. } = ops.read()?
+                        {
+                            self.result.module.num_call_indirect_caches += 1;
+                        }
+                    }
+                }
                 self.result
                     .function_body_inputs
                     .push(FunctionBodyData { validator, body });
@@ -687,6 +714,12 @@ and for re-adding support for interface types you can see this issue:
                     log::warn!("failed to parse name section {e:?}");
                 }
             }
+            KnownCustom::BranchHints(hints) => {
+                let result = self.branch_hint_section(hints);
+                if let Err(e) = result {
+                    log::warn!("failed to parse branch hint section {e:?}");
+                }
+            }
             _ => {
                 let name = section.name().trim_end_matches(".dwo");
                 if name.starts_with(".debug_") {
@@ -798,6 +831,24 @@ and for re-adding support for interface types you can see this issue:
         self.result.module.num_escaped_funcs += 1;
     }
 
+    /// Parses the `metadata.code.branch_hint` custom section of the wasm
+    /// module into `branch_hints`.
+    fn branch_hint_section(&mut self, section: BranchHintSectionReader<'data>) -> WasmResult<()> {
+        for func in section {
+            let func = func?;
+            let hints = self
+                .result
+                .branch_hints
+                .entry(FuncIndex::from_u32(func.func))
+                .or_default();
+            for hint in func.hints {
+                let hint = hint?;
+                hints.insert(hint.func_offset, hint.taken);
+            }
+        }
+        Ok(())
+    }
+
     /// Parses the Name section of the wasm module.
     fn name_section(&mut self, names: NameSectionReader<'data>) -> WasmResult<()> {
         for subsection in names {
@@ -1,3 +1,4 @@
+use alloc::boxed::Box;
 use alloc::string::{String, ToString};
 use core::fmt;
 use core::num::TryFromIntError;
@@ -34,6 +35,46 @@ pub enum WasmError {
 
     /// Any user-defined error.
     User(String),
+
+    /// An error raised while translating a particular function.
+    ///
+    /// This adds context to an `error` raised by the translation environment
+    /// without replacing it; use [`WasmError::root`] to match on the
+    /// underlying error.
+    InFunction {
+        /// The name of the function that was being translated.
+        func: String,
+        /// The bytecode offset at which translation was when the error occurred.
+        offset: usize,
+        /// The underlying error.
+        error: Box<WasmError>,
+    },
+}
+
+impl WasmError {
+    /// Wrap `error` with the identity of the function `func` that was being
+    /// translated at `offset` when it occurred.
+    ///
+    /// An error which already identifies its function is returned unchanged.
+    pub fn in_function(func: impl fmt::Display, offset: usize, error: WasmError) -> WasmError {
+        match error {
+            WasmError::InFunction { .. } => error,
+            error => WasmError::InFunction {
+                func: func.to_string(),
+                offset,
+                error: Box::new(error),
+            },
+        }
+    }
+
+    /// Returns the underlying error, looking through any context added by
+    /// [`WasmError::InFunction`].
+    pub fn root(&self) -> &WasmError {
+        match self {
+            WasmError::InFunction { error, .. } => error.root(),
+            _ => self,
+        }
+    }
 }
 
 /// Return an `Err(WasmError::Unsupported(msg))` where `msg` the string built by calling `format!`
@@ -86,8 +127,18 @@ impl fmt::Display for WasmError {
             WasmError::User(s) => {
                 write!(f, "User error: {s}")
             }
+            WasmError::InFunction { func, offset, .. } => {
+                write!(f, "failed to translate function {func} at offset {offset}")
+            }
         }
     }
 }
 
-impl core::error::Error for WasmError {}
+impl core::error::Error for WasmError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            WasmError::InFunction { error, .. } => Some(&**error),
+            _ => None,
+        }
+    }
+}
@@ -346,6 +346,11 @@ pub struct Module {
     /// an `func_ref` index (and is the maximum func_ref index).
     pub num_escaped_funcs: usize,
 
+    /// Number of `call_indirect` inline caches reserved in this module's
+    /// `VMContext`, one per `call_indirect` or `return_call_indirect`
+    /// instruction when `Tunables::call_indirect_inline_caches` is enabled.
+    pub num_call_indirect_caches: usize,
+
     /// Types of functions, imported and local.
     pub functions: PrimaryMap<FuncIndex, FunctionType>,
 
@@ -632,6 +637,7 @@ impl TypeTrace for Module {
             num_imported_globals: _,
             num_imported_tags: _,
             num_escaped_funcs: _,
+            num_call_indirect_caches: _,
             needs_gc_heap: _,
             functions,
             tables,
@@ -682,6 +688,7 @@ impl TypeTrace for Module {
             num_imported_globals: _,
             num_imported_tags: _,
             num_escaped_funcs: _,
+            num_call_indirect_caches: _,
             needs_gc_heap: _,
             functions,
             tables,
@@ -2,7 +2,10 @@
 //! with `bincode` as part of a module's compilation process.
 
 use crate::prelude::*;
-use crate::{DefinedFuncIndex, FilePos, FuncIndex, Module, ModuleInternedTypeIndex, PrimaryMap};
+use crate::{
+    DefinedFuncIndex, FilePos, FuncIndex, Memory, MemoryIndex, Module, ModuleInternedTypeIndex,
+    PrimaryMap, Tunables,
+};
 use core::fmt;
 use core::ops::Range;
 use core::str;
@@ -87,6 +90,33 @@ pub struct Metadata {
     /// Dwarf sections and the offsets at which they're stored in the
     /// ELF_WASMTIME_DWARF
     pub dwarf: Vec<(u8, Range<u64>)>,
+
+    /// How compiled code bounds checks accesses to each linear memory.
+    pub memory_bounds_checks: PrimaryMap<MemoryIndex, BoundsCheckStrategy>,
+}
+
+/// The strategy used by compiled code to bounds check accesses to a linear
+/// memory.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BoundsCheckStrategy {
+    /// Every access is checked explicitly against the memory's bound, and
+    /// out-of-bounds accesses never touch memory.
+    Explicit,
+    /// Some checks may be elided or weakened, relying on guard pages and
+    /// signals-based traps to catch out-of-bounds accesses.
+    GuardPages,
+}
+
+impl BoundsCheckStrategy {
+    /// Returns the strategy that code compiled with `tunables` uses for
+    /// `memory` on a target with the given page size.
+    pub fn for_memory(memory: &Memory, tunables: &Tunables, page_size_log2: u8) -> Self {
+        if memory.can_use_virtual_memory(tunables, page_size_log2) {
+            BoundsCheckStrategy::GuardPages
+        } else {
+            BoundsCheckStrategy::Explicit
+        }
+    }
 }
 
 /// Value of a configured setting for a [`Compiler`](crate::Compiler)
@@ -136,6 +136,58 @@ define_tunables! {
         /// The general size threshold for the sum of the caller's and callee's
         /// sizes, past which we will generally not inline calls anymore.
         pub inlining_sum_size_threshold: u32,
+
+        /// How all-zero `v128` values, such as the initial value of `v128`
+        /// locals, are materialized in compiled code.
+        pub v128_zero: V128Zero,
+
+        /// Whether every reference-typed local is mirrored into a shadow stack
+        /// slot that is written on each definition, and how the local's value
+        /// is checked against its slot on each read, or `None` to not mirror
+        /// locals at all.
+        pub shadow_stack: Option<ShadowStackCheck>,
+
+        /// Where fuel and epoch-interruption checks are placed in compiled
+        /// code.
+        pub interrupt_check_placement: InterruptCheckPlacement,
+
+        /// The classes of floating-point operators whose results have their
+        /// NaNs canonicalized during translation, or `None` to leave NaN
+        /// canonicalization entirely to Cranelift's
+        /// `enable_nan_canonicalization` setting.
+        pub nan_canonicalization: Option<NanCanonicalizationClasses>,
+
+        /// Whether each `call_indirect` site is given a slot in the
+        /// `VMContext` caching the last `VMFuncRef` that passed its signature
+        /// check, allowing later calls through the same entry to skip the
+        /// check.
+        pub call_indirect_inline_caches: bool,
+
+        /// Whether the trapping float-to-int truncation instructions are
+        /// translated exactly like their saturating counterparts, a deviation
+        /// from the WebAssembly specification for legacy modules.
+        pub nontrapping_float_to_int_override: bool,
+
+        /// Whether locals which are always assigned before being read skip
+        /// their initialization to zero at function entry.
+        pub lazy_local_init: bool,
+
+        /// The length, in bytes, below which `memory.copy` and `memory.fill`
+        /// with a constant length are translated to inline loads and stores
+        /// rather than calls to builtins, or 0 to always call the builtins.
+        pub bulk_memory_inline_threshold: u32,
+
+        /// How loads and stores of linear memories are instrumented, or `None`
+        /// to leave them uninstrumented.
+        pub memory_access_instrumentation: Option<MemoryAccessInstrumentation>,
+
+        /// The linear memories whose loads and stores are instrumented as
+        /// configured by `memory_access_instrumentation`.
+        pub instrumented_memories: InstrumentedMemories,
+
+        /// How the `unreachable` operator, and optionally other traps which
+        /// are raised unconditionally, are lowered.
+        pub unreachable_lowering: UnreachableLowering,
     }
 
     pub struct ConfigTunables {
@@ -210,6 +262,17 @@ impl Tunables {
             inlining_intra_module: IntraModuleInlining::WhenUsingGc,
             inlining_small_callee_size: 50,
             inlining_sum_size_threshold: 2000,
+            v128_zero: V128Zero::FunctionConstant,
+            shadow_stack: None,
+            interrupt_check_placement: InterruptCheckPlacement::PerLoop,
+            nan_canonicalization: None,
+            call_indirect_inline_caches: false,
+            nontrapping_float_to_int_override: false,
+            lazy_local_init: false,
+            bulk_memory_inline_threshold: 0,
+            memory_access_instrumentation: None,
+            instrumented_memories: InstrumentedMemories::all(),
+            unreachable_lowering: UnreachableLowering::Trap,
         }
     }
 
@@ -287,6 +350,331 @@ impl fmt::Display for Collector {
     }
 }
 
+/// Where fuel and epoch-interruption checks are placed in compiled code.
+///
+/// This only affects where the fuel and epoch counters are compared against
+/// their limits; the amount of fuel charged for executing code is the same for
+/// every placement.
+#[derive(Clone, Copy, Hash, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum InterruptCheckPlacement {
+    /// Check only on entry to each function.
+    ///
+    /// Loops are not checked, so a guest stuck in a loop that makes no calls
+    /// can never be interrupted.
+    PerFunction,
+
+    /// Check on entry to each function and at the header of each loop.
+    ///
+    /// This is the default and bounds the time between checks by the length of
+    /// the longest straight-line code in any function.
+    PerLoop,
+
+    /// The same as `PerLoop`, plus an additional check after every `n`
+    /// reachable operators within a function.
+    ///
+    /// This bounds the time between checks for guests with long straight-line
+    /// code at the cost of more checks.
+    PerNOperators(u32),
+
+    /// The same as `PerLoop`, except that functions which make no calls to
+    /// other WebAssembly functions skip the check on entry.
+    ///
+    /// The caller of such a function has already checked, and the function
+    /// can only run for as long as its straight-line code and loops allow, so
+    /// loops are still checked.
+    NoneForLeafFunctions,
+}
+
+impl FromStr for InterruptCheckPlacement {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "function" => Ok(Self::PerFunction),
+            "loop" => Ok(Self::PerLoop),
+            "leaf" => Ok(Self::NoneForLeafFunctions),
+            _ => match s.strip_prefix("ops:").map(|n| n.parse()) {
+                Some(Ok(n)) if n > 0 => Ok(Self::PerNOperators(n)),
+                _ => bail!(
+                    "invalid interrupt check placement: `{s}`, \
+                     only function,loop,leaf,ops:<n> accepted"
+                ),
+            },
+        }
+    }
+}
+
+impl fmt::Display for InterruptCheckPlacement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PerFunction => write!(f, "function"),
+            Self::PerLoop => write!(f, "loop"),
+            Self::PerNOperators(n) => write!(f, "ops:{n}"),
+            Self::NoneForLeafFunctions => write!(f, "leaf"),
+        }
+    }
+}
+
+/// How compiled code materializes all-zero `v128` values.
+#[derive(Clone, Copy, Hash, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum V128Zero {
+    /// Load the zero from the constant pool of each function which needs it.
+    FunctionConstant,
+
+    /// Synthesize the zero inline by splatting an integer zero, without any
+    /// constant at all.
+    Splat,
+
+    /// Load the zero by address from a single constant shared by all of a
+    /// module's functions. Targets which can't reference data by address fall
+    /// back to `FunctionConstant`.
+    ModuleConstant,
+}
+
+impl FromStr for V128Zero {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "function" => Ok(Self::FunctionConstant),
+            "splat" => Ok(Self::Splat),
+            "module" => Ok(Self::ModuleConstant),
+            _ => bail!(
+                "invalid v128 zero materialization: `{s}`, \
+                 only function,splat,module accepted"
+            ),
+        }
+    }
+}
+
+impl fmt::Display for V128Zero {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FunctionConstant => write!(f, "function"),
+            Self::Splat => write!(f, "splat"),
+            Self::ModuleConstant => write!(f, "module"),
+        }
+    }
+}
+
+/// How a read of a local mirrored by the shadow stack is checked against the
+/// local's shadow copy.
+#[derive(Clone, Copy, Hash, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum ShadowStackCheck {
+    /// Trap if the local's value differs from its shadow copy.
+    Trap,
+
+    /// Execute a debugger breakpoint if the local's value differs from its
+    /// shadow copy, then continue with the local's value.
+    Breakpoint,
+
+    /// Don't check reads at all, only keep the shadow copies up to date for
+    /// tools which inspect them.
+    Unchecked,
+}
+
+impl FromStr for ShadowStackCheck {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trap" => Ok(Self::Trap),
+            "breakpoint" => Ok(Self::Breakpoint),
+            "unchecked" => Ok(Self::Unchecked),
+            _ => bail!(
+                "invalid shadow stack check: `{s}`, \
+                 only trap,breakpoint,unchecked accepted"
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ShadowStackCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Trap => write!(f, "trap"),
+            Self::Breakpoint => write!(f, "breakpoint"),
+            Self::Unchecked => write!(f, "unchecked"),
+        }
+    }
+}
+
+/// How compiled code instruments loads and stores of linear memories.
+#[derive(Clone, Copy, Hash, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum MemoryAccessInstrumentation {
+    /// Increment the store's memory access counter before each access.
+    Count,
+
+    /// Call into the host before each access, passing it the memory, the
+    /// effective address, the size of the access, and whether it's a store.
+    Callback,
+}
+
+/// A set of linear memories, by index within their module, used to select
+/// which memories' accesses are instrumented.
+///
+/// Memories can be selected individually only if their index is below 64.
+#[derive(Clone, Copy, Hash, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct InstrumentedMemories(Option<u64>);
+
+impl InstrumentedMemories {
+    /// Every memory.
+    pub const fn all() -> Self {
+        Self(None)
+    }
+
+    /// The empty set.
+    pub const fn empty() -> Self {
+        Self(Some(0))
+    }
+
+    /// Returns `self` with the memory at `index` added.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is 64 or more.
+    pub const fn with(self, index: u32) -> Self {
+        assert!(index < 64, "only memories below index 64 can be selected");
+        match self.0 {
+            Some(bits) => Self(Some(bits | 1 << index)),
+            None => self,
+        }
+    }
+
+    /// Returns whether the memory at `index` is in this set.
+    pub const fn contains(self, index: u32) -> bool {
+        match self.0 {
+            Some(bits) => index < 64 && bits & 1 << index != 0,
+            None => true,
+        }
+    }
+}
+
+/// How compiled code lowers the `unreachable` operator and other traps which
+/// are raised unconditionally.
+#[derive(Clone, Copy, Hash, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum UnreachableLowering {
+    /// Lower to a plain trap.
+    Trap,
+
+    /// Call into the host before `unreachable` traps, passing it the index of
+    /// the trapping function and the offset of the operator within the
+    /// module.
+    NotifyHost,
+
+    /// Like `NotifyHost`, but additionally call into the host before traps
+    /// which translation inserts unconditionally, such as statically
+    /// out-of-bounds memory accesses and `call_indirect`s whose signature
+    /// can never match.
+    NotifyHostOnAllUnconditionalTraps,
+}
+
+/// A set of classes of WebAssembly floating-point operators, used to select
+/// which operators' results have their NaNs canonicalized.
+#[derive(Clone, Copy, Hash, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct NanCanonicalizationClasses(u8);
+
+impl NanCanonicalizationClasses {
+    /// Arithmetic and rounding operators, such as `f32.add`, `f64.sqrt`,
+    /// `f32.nearest`, and their `f32x4`/`f64x2` counterparts.
+    pub const ARITHMETIC: Self = Self(1 << 0);
+
+    /// Fused multiply-add operators, such as `f32x4.relaxed_madd`.
+    pub const FMA: Self = Self(1 << 1);
+
+    /// Conversions between floating-point formats, such as `f32.demote_f64`
+    /// and `f64x2.promote_low_f32x4`.
+    pub const CONVERSION: Self = Self(1 << 2);
+
+    /// Scalar floating-point loads, `f32.load` and `f64.load`.
+    pub const LOAD: Self = Self(1 << 3);
+
+    /// Vector lane operators producing floating-point values, such as
+    /// `f32x4.extract_lane`.
+    pub const LANE: Self = Self(1 << 4);
+
+    /// The empty set.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// The set of all classes.
+    pub const fn all() -> Self {
+        Self(Self::ARITHMETIC.0 | Self::FMA.0 | Self::CONVERSION.0 | Self::LOAD.0 | Self::LANE.0)
+    }
+
+    /// Returns the union of `self` and `other`.
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Returns `self` without the classes in `other`.
+    pub const fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    /// Returns whether all classes in `other` are in `self`.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns whether this set is empty.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl FromStr for NanCanonicalizationClasses {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut classes = Self::empty();
+        for class in s.split('+') {
+            classes = classes.union(match class {
+                "arithmetic" => Self::ARITHMETIC,
+                "fma" => Self::FMA,
+                "conversion" => Self::CONVERSION,
+                "load" => Self::LOAD,
+                "lane" => Self::LANE,
+                "all" => Self::all(),
+                _ => bail!(
+                    "invalid NaN canonicalization class: `{class}`, \
+                     only arithmetic,fma,conversion,load,lane,all accepted"
+                ),
+            });
+        }
+        Ok(classes)
+    }
+}
+
+impl fmt::Display for NanCanonicalizationClasses {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = [
+            (Self::ARITHMETIC, "arithmetic"),
+            (Self::FMA, "fma"),
+            (Self::CONVERSION, "conversion"),
+            (Self::LOAD, "load"),
+            (Self::LANE, "lane"),
+        ];
+        let mut sep = "";
+        for (class, name) in names {
+            if self.contains(class) {
+                write!(f, "{sep}{name}")?;
+                sep = "+";
+            }
+        }
+        Ok(())
+    }
+}
+
+impl core::ops::BitOr for NanCanonicalizationClasses {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        self.union(other)
+    }
+}
+
 /// Whether to inline function calls within the same module.
 #[derive(Clone, Copy, Hash, Serialize, Deserialize, Debug, PartialEq, Eq)]
 #[expect(missing_docs, reason = "self-describing variants")]
@@ -31,6 +31,7 @@
 //      globals: [VMGlobalDefinition; module.num_defined_globals],
 //      tags: [VMTagDefinition; module.num_defined_tags],
 //      func_refs: [VMFuncRef; module.num_escaped_funcs],
+//      call_indirect_caches: [*const VMFuncRef; module.num_call_indirect_caches],
 // }
 
 use crate::{
@@ -83,6 +84,8 @@ pub struct VMOffsets<P> {
     /// The number of escaped functions in the module, the size of the func_refs
     /// array.
     pub num_escaped_funcs: u32,
+    /// The number of `call_indirect` inline caches in the module.
+    pub num_call_indirect_caches: u32,
 
     // precalculated offsets of various member fields
     imported_functions: u32,
@@ -96,6 +99,7 @@ pub struct VMOffsets<P> {
     defined_globals: u32,
     defined_tags: u32,
     defined_func_refs: u32,
+    call_indirect_caches: u32,
     size: u32,
 }
 
@@ -221,6 +225,13 @@ pub trait PtrSize {
         self.vmstore_context_last_wasm_entry_fp() + self.size()
     }
 
+    /// Return the offset of the `memory_accesses` field of `VMStoreContext`.
+    fn vmstore_context_memory_accesses(&self) -> u8 {
+        let offset = self.vmstore_context_stack_chain() + self.size_of_vmstack_chain();
+        debug_assert_eq!(offset % 8, 0);
+        offset
+    }
+
     // Offsets within `VMMemoryDefinition`
 
     /// The offset of the `base` field.
@@ -472,6 +483,8 @@ pub struct VMOffsetsFields<P> {
     /// The number of escaped functions in the module, the size of the function
     /// references array.
     pub num_escaped_funcs: u32,
+    /// The number of `call_indirect` inline caches in the module.
+    pub num_call_indirect_caches: u32,
 }
 
 impl<P: PtrSize> VMOffsets<P> {
@@ -498,6 +511,7 @@ impl<P: PtrSize> VMOffsets<P> {
             num_defined_globals: cast_to_u32(module.globals.len() - module.num_imported_globals),
             num_defined_tags: cast_to_u32(module.tags.len() - module.num_imported_tags),
             num_escaped_funcs: cast_to_u32(module.num_escaped_funcs),
+            num_call_indirect_caches: cast_to_u32(module.num_call_indirect_caches),
         })
     }
 
@@ -529,6 +543,7 @@ impl<P: PtrSize> VMOffsets<P> {
                     num_defined_tags: _,
                     num_owned_memories: _,
                     num_escaped_funcs: _,
+                    num_call_indirect_caches: _,
 
                     // used as the initial size below
                     size,
@@ -557,6 +572,7 @@ impl<P: PtrSize> VMOffsets<P> {
         }
 
         calculate_sizes! {
+            call_indirect_caches: "call_indirect inline caches",
             defined_func_refs: "module functions",
             defined_tags: "defined tags",
             defined_globals: "defined globals",
@@ -587,6 +603,7 @@ impl<P: PtrSize> From<VMOffsetsFields<P>> for VMOffsets<P> {
             num_defined_globals: fields.num_defined_globals,
             num_defined_tags: fields.num_defined_tags,
             num_escaped_funcs: fields.num_escaped_funcs,
+            num_call_indirect_caches: fields.num_call_indirect_caches,
             imported_functions: 0,
             imported_tables: 0,
             imported_memories: 0,
@@ -598,6 +615,7 @@ impl<P: PtrSize> From<VMOffsetsFields<P>> for VMOffsets<P> {
             defined_globals: 0,
             defined_tags: 0,
             defined_func_refs: 0,
+            call_indirect_caches: 0,
             size: 0,
         };
 
@@ -656,6 +674,11 @@ impl<P: PtrSize> From<VMOffsetsFields<P>> for VMOffsets<P> {
                 ret.num_escaped_funcs,
                 ret.ptr.size_of_vm_func_ref(),
             ),
+            align(u32::from(ret.ptr.size())),
+            size(call_indirect_caches) = cmul(
+                ret.num_call_indirect_caches,
+                ret.ptr.size(),
+            ),
         }
 
         ret.size = next_field_offset;
@@ -886,6 +909,12 @@ impl<P: PtrSize> VMOffsets<P> {
         self.defined_func_refs
     }
 
+    /// The offset of the `call_indirect_caches` array.
+    #[inline]
+    pub fn vmctx_call_indirect_caches_begin(&self) -> u32 {
+        self.call_indirect_caches
+    }
+
     /// Return the size of the `VMContext` allocation.
     #[inline]
     pub fn size_of_vmctx(&self) -> u32 {
@@ -978,6 +1007,13 @@ impl<P: PtrSize> VMOffsets<P> {
         self.vmctx_func_refs_begin() + index.as_u32() * u32::from(self.ptr.size_of_vm_func_ref())
     }
 
+    /// Return the offset to the `index`th `call_indirect` inline cache.
+    #[inline]
+    pub fn vmctx_call_indirect_cache(&self, index: u32) -> u32 {
+        assert!(index < self.num_call_indirect_caches);
+        self.vmctx_call_indirect_caches_begin() + index * u32::from(self.ptr.size())
+    }
+
     /// Return the offset to the `wasm_call` field in `*const VMFunctionBody` index `index`.
     #[inline]
     pub fn vmctx_vmfunction_import_wasm_call(&self, index: FuncIndex) -> u32 {
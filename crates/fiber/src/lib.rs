@@ -41,6 +41,18 @@ cfg_if::cfg_if! {
 #[cfg(any(unix, not(feature = "std")))]
 pub(crate) mod stackswitch;
 
+/// Whether fibers can be created on this host.
+///
+/// Outside of Windows and MIRI fibers are implemented with stack-switching
+/// routines written for each supported architecture, and creating a fiber on
+/// any other architecture returns an error.
+#[cfg(any(not(feature = "std"), all(unix, not(miri))))]
+pub const SUPPORTED: bool = stackswitch::SUPPORTED_ARCH;
+
+/// Whether fibers can be created on this host.
+#[cfg(not(any(not(feature = "std"), all(unix, not(miri)))))]
+pub const SUPPORTED: bool = true;
+
 /// Represents an execution stack to use for a fiber.
 pub struct FiberStack(imp::FiberStack);
 
@@ -427,6 +427,8 @@ mod asan {
             bottom_old: &mut *const u8,
             size_old: &mut usize,
         );
+        fn __asan_poison_memory_region(addr: *const u8, size: usize);
+        fn __asan_unpoison_memory_region(addr: *const u8, size: usize);
     }
 
     /// This static is a workaround for llvm/llvm-project#53891, notably this is
@@ -456,8 +458,14 @@ mod asan {
 
         let stack = match stacks.iter().position(|i| needed_size <= i.mapping_len) {
             // If an appropriately sized stack was already allocated, then use
-            // that one.
-            Some(i) => stacks.remove(i),
+            // that one, unpoisoning it as it's about to be used again.
+            Some(i) => {
+                let stack = stacks.remove(i);
+                unsafe {
+                    __asan_unpoison_memory_region(stack.mapping_base, stack.mapping_len);
+                }
+                stack
+            }
             // ... otherwise allocate a brand new stack.
             None => MmapFiberStack::new(size)?,
         };
@@ -497,7 +505,14 @@ mod asan {
     impl Drop for AsanFiberStack {
         fn drop(&mut self) {
             let stack = unsafe { ManuallyDrop::take(&mut self.mmap) };
-            FIBER_STACKS.lock().unwrap().push(stack);
+            let mut stacks = FIBER_STACKS.lock().unwrap();
+            // Nothing may use this stack while it's cached, so poison it to
+            // catch host code holding on to pointers into a dead fiber. As in
+            // `new_fiber_stack` this happens while the cache is locked.
+            unsafe {
+                __asan_poison_memory_region(stack.mapping_base, stack.mapping_len);
+            }
+            stacks.push(stack);
         }
     }
 }
@@ -0,0 +1,14 @@
+use test_programs::wasi::cli::stdin;
+use test_programs::wasi::io::streams::StreamError;
+use test_programs::wasi_io_extensions::wasmtime::wasi_io::error_code;
+
+fn main() {
+    let stdin = stdin::get_stdin();
+    match stdin.blocking_read(100) {
+        Err(StreamError::LastOperationFailed(err)) => {
+            assert_eq!(error_code::code(&err), Some(3));
+            assert_eq!(err.to_debug_string(), "3: peer reset the connection");
+        }
+        other => panic!("expected the read to fail, got {other:?}"),
+    }
+}
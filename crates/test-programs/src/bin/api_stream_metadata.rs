@@ -0,0 +1,18 @@
+use test_programs::wasi::cli::stdin;
+use test_programs::wasi_io_extensions::wasmtime::wasi_io::streams_metadata;
+
+fn main() {
+    let stdin = stdin::get_stdin();
+    let metadata = streams_metadata::get_metadata(&stdin);
+    assert_eq!(
+        metadata,
+        [
+            ("content-length".to_string(), "13".to_string()),
+            ("name".to_string(), "greeting.txt".to_string()),
+        ]
+    );
+
+    // The stream itself is unaffected by reading its metadata.
+    let contents = stdin.blocking_read(100).unwrap();
+    assert_eq!(contents, b"Hello, world!");
+}
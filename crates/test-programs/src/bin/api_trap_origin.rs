@@ -0,0 +1,16 @@
+use test_programs::wasi::cli::{stdin, stdout};
+
+fn main() {
+    match std::env::var("TRAP_ORIGIN").as_deref() {
+        // Blocking writes are limited to 4096 bytes, so this is a guest trap.
+        Ok("guest") => {
+            let _ = stdout::get_stdout().blocking_write_and_flush(&[0; 5000]);
+        }
+        // The host's stdin reports a bug in its implementation.
+        Ok("host") => {
+            let _ = stdin::get_stdin().blocking_read(1);
+        }
+        other => panic!("unexpected TRAP_ORIGIN: {other:?}"),
+    }
+    unreachable!("the host should have trapped");
+}
@@ -51,6 +51,18 @@ pub mod proxy {
     });
 }
 
+pub mod wasi_io_extensions {
+    wit_bindgen::generate!({
+        path: "../wasi-io/wit",
+        world: "wasmtime:wasi-io/bindings",
+        with: {
+            "wasi:io/error@0.2.6": crate::wasi::io::error,
+            "wasi:io/poll@0.2.6": crate::wasi::io::poll,
+            "wasi:io/streams@0.2.6": crate::wasi::io::streams,
+        },
+    });
+}
+
 impl std::fmt::Display for wasi::io::error::Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(&self.to_debug_string())
@@ -0,0 +1,232 @@
+//! Accounting for host memory which guests fill through output streams.
+//!
+//! Bytes written to an [`OutputStream`](crate::streams::OutputStream) are
+//! often held in host memory until they're written to the underlying sink,
+//! which isn't visible to a store's resource limiter. A [`MemoryAccountant`]
+//! configured with [`IoLinkOptions::memory_accountant`] bounds this memory:
+//! streams which buffer accepted bytes charge the accountant for them when
+//! they're written and credit it once they've been flushed out of host
+//! memory. Writes which would exceed the budget fail with
+//! [`StreamError::LastOperationFailed`], which the guest sees as
+//! backpressure.
+//!
+//! [`IoLinkOptions::memory_accountant`]: crate::IoLinkOptions::memory_accountant
+
+use crate::streams::{StreamError, StreamResult};
+use alloc::sync::Arc;
+use anyhow::anyhow;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// A budget for host memory held by output streams, shared between all of
+/// the streams which are charged against it.
+#[derive(Clone, Debug)]
+pub struct MemoryAccountant(Arc<Budget>);
+
+#[derive(Debug)]
+struct Budget {
+    limit: usize,
+    used: AtomicUsize,
+}
+
+impl MemoryAccountant {
+    /// Creates an accountant which allows at most `limit` bytes to be
+    /// charged at once.
+    pub fn new(limit: usize) -> MemoryAccountant {
+        MemoryAccountant(Arc::new(Budget {
+            limit,
+            used: AtomicUsize::new(0),
+        }))
+    }
+
+    /// Returns the maximum number of bytes which may be charged at once.
+    pub fn limit(&self) -> usize {
+        self.0.limit
+    }
+
+    /// Returns the number of bytes currently charged.
+    pub fn used(&self) -> usize {
+        self.0.used.load(Ordering::Relaxed)
+    }
+
+    /// Charges `bytes` against this budget.
+    ///
+    /// Returns [`StreamError::LastOperationFailed`], and charges nothing, if
+    /// this would exceed the budget.
+    pub fn charge(&self, bytes: usize) -> StreamResult<()> {
+        let limit = self.0.limit;
+        self.0
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                used.checked_add(bytes).filter(|n| *n <= limit)
+            })
+            .map_err(|used| {
+                StreamError::LastOperationFailed(anyhow!(
+                    "writing {bytes} bytes would exceed the host memory budget of \
+                     {limit} bytes for output streams ({used} bytes in use)"
+                ))
+            })?;
+        Ok(())
+    }
+
+    /// Returns `bytes` previously charged with [`MemoryAccountant::charge`]
+    /// to this budget.
+    pub fn credit(&self, bytes: usize) {
+        let prev = self.0.used.fetch_sub(bytes, Ordering::Relaxed);
+        debug_assert!(prev >= bytes);
+    }
+}
+
+/// The bytes a single stream has charged to a [`MemoryAccountant`].
+///
+/// This is a helper for implementations of
+/// [`OutputStream::set_memory_accountant`](crate::streams::OutputStream::set_memory_accountant):
+/// streams call [`MemoryCharge::charge`] for bytes they accept and
+/// [`MemoryCharge::on_flushed`] once those bytes have left host memory. Any
+/// bytes still charged are credited back when this is dropped.
+#[derive(Debug, Default)]
+pub struct MemoryCharge {
+    accountant: Option<MemoryAccountant>,
+    outstanding: usize,
+}
+
+impl MemoryCharge {
+    /// Creates a charge which isn't associated with an accountant yet, and so
+    /// accepts all bytes.
+    pub const fn new() -> MemoryCharge {
+        MemoryCharge {
+            accountant: None,
+            outstanding: 0,
+        }
+    }
+
+    /// Associates this charge with `accountant`, if it isn't associated with
+    /// one already.
+    ///
+    /// Bytes charged before this call aren't accounted for.
+    pub fn set_accountant(&mut self, accountant: &MemoryAccountant) {
+        if self.accountant.is_none() {
+            self.accountant = Some(accountant.clone());
+        }
+    }
+
+    /// Charges `bytes` accepted by a stream, failing if the accountant's
+    /// budget would be exceeded.
+    pub fn charge(&mut self, bytes: usize) -> StreamResult<()> {
+        if let Some(accountant) = &self.accountant {
+            accountant.charge(bytes)?;
+            self.outstanding += bytes;
+        }
+        Ok(())
+    }
+
+    /// Credits `bytes` which have been flushed out of host memory.
+    ///
+    /// Bytes which were accepted before an accountant was set are ignored.
+    pub fn on_flushed(&mut self, bytes: usize) {
+        if let Some(accountant) = &self.accountant {
+            let bytes = bytes.min(self.outstanding);
+            accountant.credit(bytes);
+            self.outstanding -= bytes;
+        }
+    }
+}
+
+impl Drop for MemoryCharge {
+    fn drop(&mut self) {
+        self.on_flushed(self.outstanding);
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::bindings::wasi::io::streams::HostOutputStream;
+    use crate::poll::Pollable;
+    use crate::streams::{DynOutputStream, OutputStream};
+    use crate::{IoImpl, IoLinkOptions};
+    use alloc::boxed::Box;
+    use bytes::Bytes;
+    use wasmtime::component::ResourceTable;
+
+    /// A sink which accepts any number of bytes but never drains them.
+    struct NeverDrains {
+        charge: MemoryCharge,
+    }
+
+    #[async_trait::async_trait]
+    impl Pollable for NeverDrains {
+        async fn ready(&mut self) {}
+    }
+
+    #[async_trait::async_trait]
+    impl OutputStream for NeverDrains {
+        fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+            self.charge.charge(bytes.len())
+        }
+
+        fn flush(&mut self) -> StreamResult<()> {
+            Ok(())
+        }
+
+        fn check_write(&mut self) -> StreamResult<usize> {
+            Ok(usize::MAX)
+        }
+
+        fn set_memory_accountant(&mut self, accountant: &MemoryAccountant) {
+            self.charge.set_accountant(accountant);
+        }
+    }
+
+    fn stream(table: &mut ResourceTable) -> wasmtime::component::Resource<DynOutputStream> {
+        let stream: DynOutputStream = Box::new(NeverDrains {
+            charge: MemoryCharge::new(),
+        });
+        table.push(stream).unwrap()
+    }
+
+    #[test]
+    fn writes_fail_at_budget() -> anyhow::Result<()> {
+        let accountant = MemoryAccountant::new(10);
+        let mut options = IoLinkOptions::new();
+        options.memory_accountant(accountant.clone());
+        let mut table = ResourceTable::new();
+        let a = stream(&mut table);
+        let b = stream(&mut table);
+        let mut io = IoImpl::new(&mut table, &options);
+
+        let borrow = |s: &wasmtime::component::Resource<DynOutputStream>| {
+            wasmtime::component::Resource::new_borrow(s.rep())
+        };
+        io.check_write(borrow(&a))?;
+        io.check_write(borrow(&b))?;
+        io.write(borrow(&a), vec![0; 4])?;
+        io.write_zeroes(borrow(&b), 4)?;
+        assert_eq!(accountant.used(), 8);
+
+        // The budget is shared between both streams.
+        let err = io.write(borrow(&a), vec![0; 3]).unwrap_err();
+        assert!(
+            matches!(err, StreamError::LastOperationFailed(_)),
+            "{err:?}"
+        );
+        assert_eq!(accountant.used(), 8);
+        io.write(borrow(&b), vec![0; 2])?;
+        assert!(io.write_zeroes(borrow(&a), 1).is_err());
+
+        // Dropping a stream returns the bytes it still held.
+        drop(table.delete(a)?);
+        assert_eq!(accountant.used(), 6);
+        Ok(())
+    }
+
+    #[test]
+    fn unaccounted_without_options() -> anyhow::Result<()> {
+        let mut table = ResourceTable::new();
+        let a = stream(&mut table);
+        let options = IoLinkOptions::new();
+        let mut io = IoImpl::new(&mut table, &options);
+        io.check_write(wasmtime::component::Resource::new_borrow(a.rep()))?;
+        io.write(a, vec![0; 1 << 20])?;
+        Ok(())
+    }
+}
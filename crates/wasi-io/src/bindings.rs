@@ -18,9 +18,53 @@ wasmtime::component::bindgen!({
         "wasi:io/streams/[method]output-stream.blocking-write-and-flush": async | trappable,
         "wasi:io/streams/[method]output-stream.blocking-write-zeroes-and-flush": async | trappable,
         "wasi:io/streams/[drop]output-stream": async | trappable,
+        "wasmtime:wasi-io/streams-timeout/blocking-read-timeout": async | trappable,
+        "wasmtime:wasi-io/streams-read-vectored/blocking-read-vectored": async | trappable,
         default: trappable,
     },
     trappable_error_type: {
         "wasi:io/streams/stream-error" => crate::streams::StreamError,
     }
 });
+
+/// Bindings for the `wasi:io/imports` world in which the functions which
+/// wait are concurrent, as added by
+/// [`add_to_linker_concurrent`](crate::add_to_linker_concurrent).
+///
+/// These are generated from the same WIT as the bindings above, with the
+/// same resource types and the same [`StreamError`](crate::streams::StreamError)
+/// for the `stream-error` type, so host streams and pollables work with
+/// either. Waiting functions take an
+/// [`Accessor`](wasmtime::component::Accessor) instead of exclusive access to
+/// the store, so other guest tasks and host calls may run while they wait,
+/// and every function is traced with `tracing`.
+#[cfg(feature = "concurrent")]
+pub mod concurrent {
+    wasmtime::component::bindgen!({
+        path: "wit",
+        world: "wasi:io/imports",
+        with: {
+            "wasi:io/poll/pollable": crate::poll::DynPollable,
+            "wasi:io/streams/input-stream": crate::streams::DynInputStream,
+            "wasi:io/streams/output-stream": crate::streams::DynOutputStream,
+            "wasi:io/error/error": crate::streams::Error,
+        },
+        imports: {
+            "wasi:io/poll/poll": async | store | tracing | trappable,
+            "wasi:io/poll/[method]pollable.block": async | store | tracing | trappable,
+            "wasi:io/streams/[method]input-stream.blocking-read": async | store | tracing | trappable,
+            "wasi:io/streams/[method]input-stream.blocking-skip": async | store | tracing | trappable,
+            "wasi:io/streams/[drop]input-stream": async | store | tracing | trappable,
+            "wasi:io/streams/[method]output-stream.blocking-splice": async | store | tracing | trappable,
+            "wasi:io/streams/[method]output-stream.blocking-flush": async | store | tracing | trappable,
+            "wasi:io/streams/[method]output-stream.blocking-write-and-flush": async | store | tracing | trappable,
+            "wasi:io/streams/[method]output-stream.blocking-write-zeroes-and-flush": async | store | tracing | trappable,
+            "wasi:io/streams/[drop]output-stream": async | store | tracing | trappable,
+            default: tracing | trappable,
+        },
+        trappable_error_type: {
+            "wasi:io/streams/stream-error" => crate::streams::StreamError,
+        },
+        require_store_data_send: true,
+    });
+}
@@ -0,0 +1,273 @@
+//! Resources which are derived from, and refer to, another resource.
+//!
+//! A `pollable` refers to the stream it was subscribed to by its index in the
+//! [`ResourceTable`], and so must not outlive it. [`child_resource`]
+//! generalizes this for other resources derived from a table entry, such as
+//! statistics about a stream: the new resource is recorded as a child of its
+//! parent in the table, which then refuses to delete the parent while the
+//! child is alive, and is handed a [`ParentLink`] with which it refers to its
+//! parent.
+//!
+//! Host code which needs to delete a parent regardless of its children uses
+//! [`delete_parent`]. The children are dropped with it, and each is replaced
+//! by an [`Orphaned`] placeholder until its handle is deleted, so the guest's
+//! handles to them are never reused for unrelated resources.
+
+use alloc::vec::Vec;
+use anyhow::Result;
+use core::any::Any;
+use wasmtime::component::{Resource, ResourceTable, ResourceTableError};
+
+/// A child resource's reference to its parent, created by
+/// [`child_resource`].
+#[derive(Clone, Copy, Debug)]
+pub struct ParentLink {
+    index: u32,
+    pub(crate) remove_parent_on_delete: Option<fn(&mut ResourceTable, u32) -> Result<()>>,
+}
+
+impl ParentLink {
+    /// Returns the table index of the parent.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns whether the child owns its parent, that is whether it was
+    /// created from an owned handle to the parent and the parent should be
+    /// deleted along with the child.
+    pub fn owns_parent(&self) -> bool {
+        self.remove_parent_on_delete.is_some()
+    }
+
+    /// Deletes the parent if the child owns it.
+    ///
+    /// This is called after the child has been deleted. A parent which is
+    /// already gone, for example because it was deleted by
+    /// [`delete_parent`], is ignored.
+    pub fn release(self, table: &mut ResourceTable) -> Result<()> {
+        let Some(remove) = self.remove_parent_on_delete else {
+            return Ok(());
+        };
+        match remove(table, self.index) {
+            Err(e)
+                if matches!(
+                    e.downcast_ref::<ResourceTableError>(),
+                    Some(ResourceTableError::NotPresent)
+                ) =>
+            {
+                log::debug!("parent {} of deleted child was already deleted", self.index);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+}
+
+/// Pushes the resource created by `make` into `table` as a child of
+/// `parent`.
+///
+/// `make` is given the [`ParentLink`] through which the child refers to
+/// `parent`. If `parent` is an owned handle then the child owns it, and
+/// [`ParentLink::release`] deletes the parent once the child is deleted.
+///
+/// This is how [`subscribe`](crate::poll::subscribe) creates pollables.
+///
+/// # Example
+///
+/// ```
+/// use wasmtime::component::{Resource, ResourceTable};
+/// use wasmtime_wasi_io::child::ParentLink;
+/// use wasmtime_wasi_io::child_resource;
+///
+/// struct Stream;
+///
+/// struct StreamStats {
+///     stream: ParentLink,
+///     bytes_read: u64,
+/// }
+///
+/// let mut table = ResourceTable::new();
+/// let stream = table.push(Stream).unwrap();
+/// let borrow = Resource::<Stream>::new_borrow(stream.rep());
+/// let stats = child_resource(&mut table, borrow, |stream| StreamStats {
+///     stream,
+///     bytes_read: 0,
+/// })
+/// .unwrap();
+///
+/// // The stream can't be deleted until its statistics are.
+/// assert!(table.delete(Resource::<Stream>::new_own(stream.rep())).is_err());
+/// let stats = table.delete(stats).unwrap();
+/// stats.stream.release(&mut table).unwrap();
+/// table.delete(stream).unwrap();
+/// ```
+pub fn child_resource<P, T>(
+    table: &mut ResourceTable,
+    parent: Resource<P>,
+    make: impl FnOnce(ParentLink) -> T,
+) -> Result<Resource<T>, ResourceTableError>
+where
+    P: Any + Send,
+    T: Send + 'static,
+{
+    let link = ParentLink {
+        index: parent.rep(),
+        remove_parent_on_delete: if parent.owned() {
+            Some(remove_index::<P>)
+        } else {
+            None
+        },
+    };
+    table.push_child(make(link), &parent)
+}
+
+/// Deletes `parent` from `table` along with all of its descendants.
+///
+/// Each descendant is replaced by an [`Orphaned`] placeholder, which
+/// operations on the descendant's handle then find instead of the
+/// descendant, until the handle is deleted with [`delete_child`].
+pub fn delete_parent<P: Any>(
+    table: &mut ResourceTable,
+    parent: Resource<P>,
+) -> Result<P, ResourceTableError> {
+    // Children are deleted before their own parents, so descendants are
+    // visited deepest first.
+    let mut descendants = Vec::new();
+    let mut next = 0;
+    descendants.push(parent.rep());
+    while next < descendants.len() {
+        let index = descendants[next];
+        descendants.extend(
+            table
+                .iter()
+                .filter(|(_, p, _)| *p == Some(index))
+                .map(|(child, _, _)| child),
+        );
+        next += 1;
+    }
+    for &index in descendants[1..].iter().rev() {
+        table.delete_any(index)?;
+        table.insert_at(index, Orphaned, None)?;
+        log::debug!("child {index} orphaned by deletion of its parent");
+    }
+    table.delete(parent)
+}
+
+/// Deletes the child resource `child` from `table`.
+///
+/// Returns `None` if the child was already dropped by [`delete_parent`], in
+/// which case only its [`Orphaned`] placeholder is deleted.
+pub fn delete_child<T: Any>(
+    table: &mut ResourceTable,
+    child: Resource<T>,
+) -> Result<Option<T>, ResourceTableError> {
+    if table.get_any_mut(child.rep())?.is::<Orphaned>() {
+        table.delete(Resource::<Orphaned>::new_own(child.rep()))?;
+        return Ok(None);
+    }
+    table.delete(child).map(Some)
+}
+
+/// The placeholder for a child resource which was dropped by
+/// [`delete_parent`].
+///
+/// Operations on the child's handle fail with
+/// [`ResourceTableError::WrongType`] since they find this placeholder instead
+/// of the child.
+#[derive(Debug)]
+pub struct Orphaned;
+
+pub(crate) fn remove_index<T: Any>(table: &mut ResourceTable, idx: u32) -> Result<()> {
+    let resource = Resource::<T>::new_own(idx);
+    table.delete(resource)?;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    struct Stream;
+
+    struct Stats {
+        stream: ParentLink,
+    }
+
+    fn stats(table: &mut ResourceTable, owned: bool) -> (u32, Resource<Stats>) {
+        let stream = table.push(Stream).unwrap();
+        let rep = stream.rep();
+        let parent = if owned {
+            stream
+        } else {
+            Resource::new_borrow(rep)
+        };
+        let stats = child_resource(table, parent, |stream| Stats { stream }).unwrap();
+        (rep, stats)
+    }
+
+    #[test]
+    fn parent_deleted_after_child() -> Result<()> {
+        let mut table = ResourceTable::new();
+        let (stream, child) = stats(&mut table, false);
+        assert!(matches!(
+            table.delete(Resource::<Stream>::new_own(stream)),
+            Err(ResourceTableError::HasChildren)
+        ));
+
+        let child = delete_child(&mut table, child)?.unwrap();
+        assert_eq!(child.stream.index(), stream);
+        assert!(!child.stream.owns_parent());
+        child.stream.release(&mut table)?;
+        table.delete(Resource::<Stream>::new_own(stream))?;
+        assert_eq!(table.iter().count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn owned_parent_deleted_with_child() -> Result<()> {
+        let mut table = ResourceTable::new();
+        let (stream, child) = stats(&mut table, true);
+        let child = delete_child(&mut table, child)?.unwrap();
+        assert!(child.stream.owns_parent());
+        child.stream.release(&mut table)?;
+        assert!(table.get_any_mut(stream).is_err());
+
+        // Releasing a parent which is already gone is harmless.
+        child.stream.release(&mut table)?;
+        Ok(())
+    }
+
+    #[test]
+    fn parent_deleted_before_child() -> Result<()> {
+        let mut table = ResourceTable::new();
+        let (stream, child) = stats(&mut table, true);
+        let grandchild = child_resource(
+            &mut table,
+            Resource::<Stats>::new_borrow(child.rep()),
+            |_| (),
+        )?;
+        delete_parent(&mut table, Resource::<Stream>::new_own(stream))?;
+
+        // The children's handles are still reserved, but no longer refer to
+        // the children.
+        let unrelated = table.push(())?;
+        assert_ne!(unrelated.rep(), child.rep());
+        assert_ne!(unrelated.rep(), grandchild.rep());
+        assert!(matches!(
+            table.get(&child),
+            Err(ResourceTableError::WrongType)
+        ));
+
+        assert!(delete_child(&mut table, grandchild)?.is_none());
+        let rep = child.rep();
+        assert!(delete_child(&mut table, child)?.is_none());
+
+        // Deleting the child again is an error rather than deleting whatever
+        // reuses its index.
+        assert!(matches!(
+            delete_child(&mut table, Resource::<Stats>::new_own(rep)),
+            Err(ResourceTableError::NotPresent)
+        ));
+        Ok(())
+    }
+}
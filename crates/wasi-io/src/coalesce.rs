@@ -0,0 +1,409 @@
+//! Coalescing of small writes to output streams.
+//!
+//! Guests such as line-oriented loggers often issue many tiny `write` calls
+//! between flushes, each of which reaches the host's [`OutputStream`]
+//! separately. When [`IoLinkOptions::coalesce_writes`] is configured, the
+//! first `check-write` or `write` on an output stream wraps it in a
+//! [`CoalescingOutputStream`], which stages small writes and forwards them to
+//! the wrapped stream as a single write once enough bytes have accumulated.
+//!
+//! Staged bytes are forwarded, in order, when the staging buffer reaches its
+//! threshold, when the stream is flushed, when the wrapped stream's permit
+//! can no longer cover them in `check_write`, before zeroes are written,
+//! before bytes are spliced into the wrapped stream by
+//! [`OutputStream::splice_from`], and when the stream is dropped. Zeroes
+//! aren't staged, so that streams which write them sparsely still see them
+//! as a single run. The permits reported to the guest by `check_write`
+//! account for staged bytes, so coalescing isn't otherwise visible to the
+//! guest.
+//!
+//! [`IoLinkOptions::coalesce_writes`]: crate::IoLinkOptions::coalesce_writes
+
+use crate::accounting::MemoryAccountant;
+use crate::poll::{DynFuture, PendingHint, Pollable, ReadinessKind};
+use crate::snapshot::SnapshotableStream;
+use crate::streams::{DynOutputStream, InputStream, OutputStream, StreamResult};
+use alloc::boxed::Box;
+use alloc::string::String;
+use bytes::{Bytes, BytesMut};
+use core::any::Any;
+
+/// An [`OutputStream`] which stages writes smaller than a threshold and
+/// forwards them to the stream it wraps in larger chunks.
+pub struct CoalescingOutputStream {
+    inner: DynOutputStream,
+    staged: BytesMut,
+    threshold: usize,
+}
+
+impl CoalescingOutputStream {
+    /// Wraps `inner`, forwarding writes to it once at least `threshold`
+    /// bytes have been staged.
+    pub fn new(inner: DynOutputStream, threshold: usize) -> CoalescingOutputStream {
+        CoalescingOutputStream {
+            inner,
+            staged: BytesMut::new(),
+            threshold,
+        }
+    }
+
+    /// Returns the number of bytes which have been written but not yet
+    /// forwarded to the wrapped stream.
+    pub fn staged(&self) -> usize {
+        self.staged.len()
+    }
+
+    /// Writes up to `max` staged bytes to the wrapped stream.
+    fn forward(&mut self, max: usize) -> StreamResult<()> {
+        let len = self.staged.len().min(max);
+        if len > 0 {
+            let bytes = self.staged.split_to(len).freeze();
+            self.inner.write(bytes)?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for CoalescingOutputStream {
+    async fn ready(&mut self) {
+        self.inner.ready().await
+    }
+
+    fn ready_owned(&self) -> Option<DynFuture<'static>> {
+        self.inner.ready_owned()
+    }
+
+    fn pending_hint(&self) -> Option<&PendingHint> {
+        self.inner.pending_hint()
+    }
+
+    fn readiness_kind(&self) -> ReadinessKind {
+        self.inner.readiness_kind()
+    }
+
+    fn debug_name(&self) -> Option<String> {
+        self.inner.debug_name()
+    }
+}
+
+#[async_trait::async_trait]
+impl OutputStream for CoalescingOutputStream {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        // Large writes don't benefit from staging, so they're forwarded
+        // directly unless that would reorder them with staged bytes.
+        if self.staged.is_empty() && bytes.len() >= self.threshold {
+            return self.inner.write(bytes);
+        }
+        self.staged.extend_from_slice(&bytes);
+        if self.staged.len() >= self.threshold {
+            self.forward(usize::MAX)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        self.forward(usize::MAX)?;
+        self.inner.flush()
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        // The guest's permit was at most the wrapped stream's permit less
+        // the bytes staged at the time, so staged bytes always fit into the
+        // wrapped stream's permit. Once they don't leave room for any more
+        // they're forwarded so the guest can make progress.
+        let permit = self.inner.check_write()?;
+        if permit > self.staged.len() {
+            return Ok(permit - self.staged.len());
+        }
+        self.forward(permit)?;
+        if !self.staged.is_empty() {
+            return Ok(0);
+        }
+        self.inner.check_write()
+    }
+
+    async fn blocking_write_and_flush(&mut self, bytes: Bytes) -> StreamResult<()> {
+        let bytes = if self.staged.is_empty() {
+            bytes
+        } else {
+            self.staged.extend_from_slice(&bytes);
+            self.staged.split().freeze()
+        };
+        self.inner.blocking_write_and_flush(bytes).await
+    }
+
+    fn write_zeroes(&mut self, nelem: usize) -> StreamResult<()> {
+        // Zeroes aren't staged, so that the wrapped stream sees them as a
+        // single run.
+        self.forward(usize::MAX)?;
+        self.inner.write_zeroes(nelem)
+    }
+
+    async fn blocking_write_zeroes_and_flush(&mut self, nelem: usize) -> StreamResult<()> {
+        self.forward(usize::MAX)?;
+        self.inner.blocking_write_zeroes_and_flush(nelem).await
+    }
+
+    async fn cancel(&mut self) {
+        // Bytes accepted by `write` are handed to the wrapped stream just as
+        // they would have been without coalescing. As with any other write,
+        // errors are only reported to a guest which is no longer listening.
+        let _ = self.forward(usize::MAX);
+        self.inner.cancel().await
+    }
+
+    fn as_snapshotable(&self) -> Option<&dyn SnapshotableStream> {
+        // Staged bytes aren't part of the wrapped stream's snapshot.
+        if self.staged.is_empty() {
+            self.inner.as_snapshotable()
+        } else {
+            None
+        }
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        // Staged bytes haven't reached the wrapped stream yet.
+        if self.staged.is_empty() {
+            self.inner.as_any()
+        } else {
+            None
+        }
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        if self.staged.is_empty() {
+            self.inner.as_any_mut()
+        } else {
+            None
+        }
+    }
+
+    fn set_memory_accountant(&mut self, accountant: &MemoryAccountant) {
+        self.inner.set_memory_accountant(accountant)
+    }
+
+    fn buffered_bytes(&self) -> Option<usize> {
+        // Staged bytes are always known, even if the wrapped stream doesn't
+        // report the bytes it holds.
+        Some(self.staged() + self.inner.buffered_bytes().unwrap_or(0))
+    }
+
+    fn blocking_write_limit(&self) -> usize {
+        self.inner.blocking_write_limit()
+    }
+
+    fn is_coalescing(&self) -> bool {
+        true
+    }
+
+    fn is_deterministic(&self) -> bool {
+        self.inner.is_deterministic()
+    }
+
+    fn enforces_write_permits(&self) -> bool {
+        self.inner.enforces_write_permits()
+    }
+
+    fn splice_from(
+        &mut self,
+        src: &mut dyn InputStream,
+        len: usize,
+    ) -> Option<StreamResult<usize>> {
+        // Staged bytes were written before the spliced ones, so they're
+        // forwarded first. They fit into the wrapped stream's permit, which
+        // the splice is then responsible for honoring as usual.
+        if let Err(e) = self.forward(usize::MAX) {
+            return Some(Err(e));
+        }
+        self.inner.splice_from(src, len)
+    }
+}
+
+/// Wraps `stream` in a [`CoalescingOutputStream`] unless it already
+/// coalesces writes.
+pub(crate) fn coalesce(stream: &mut DynOutputStream, threshold: usize) {
+    if stream.is_coalescing() {
+        return;
+    }
+    let placeholder: DynOutputStream = Box::new(crate::snapshot::ClosedStream);
+    let inner = core::mem::replace(stream, placeholder);
+    *stream = Box::new(CoalescingOutputStream::new(inner, threshold));
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::bindings::wasi::io::streams::HostOutputStream;
+    use crate::{IoImpl, IoLinkOptions};
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+    use std::sync::Mutex;
+    use wasmtime::component::{Resource, ResourceTable};
+
+    /// The calls a [`CountingSink`] received, in order.
+    #[derive(Debug, PartialEq)]
+    enum Call {
+        Write(Vec<u8>),
+        Flush,
+        Splice(usize),
+    }
+
+    /// A sink which records every call made to it.
+    struct CountingSink(Arc<Mutex<Vec<Call>>>, PendingHint);
+
+    #[async_trait::async_trait]
+    impl Pollable for CountingSink {
+        async fn ready(&mut self) {}
+
+        fn pending_hint(&self) -> Option<&PendingHint> {
+            Some(&self.1)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl OutputStream for CountingSink {
+        fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+            self.0.lock().unwrap().push(Call::Write(bytes.to_vec()));
+            Ok(())
+        }
+
+        fn flush(&mut self) -> StreamResult<()> {
+            self.0.lock().unwrap().push(Call::Flush);
+            Ok(())
+        }
+
+        fn check_write(&mut self) -> StreamResult<usize> {
+            Ok(64)
+        }
+
+        fn enforces_write_permits(&self) -> bool {
+            true
+        }
+
+        fn splice_from(
+            &mut self,
+            _src: &mut dyn InputStream,
+            len: usize,
+        ) -> Option<StreamResult<usize>> {
+            self.0.lock().unwrap().push(Call::Splice(len));
+            Some(Ok(len))
+        }
+    }
+
+    fn sink(table: &mut ResourceTable) -> (Resource<DynOutputStream>, Arc<Mutex<Vec<Call>>>) {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let stream: DynOutputStream = Box::new(CountingSink(calls.clone(), PendingHint::new()));
+        (table.push(stream).unwrap(), calls)
+    }
+
+    fn borrow(s: &Resource<DynOutputStream>) -> Resource<DynOutputStream> {
+        Resource::new_borrow(s.rep())
+    }
+
+    #[test]
+    fn small_writes_are_coalesced() -> anyhow::Result<()> {
+        let mut options = IoLinkOptions::new();
+        options.coalesce_writes(16);
+        let mut table = ResourceTable::new();
+        let (stream, calls) = sink(&mut table);
+        let mut io = IoImpl::new(&mut table, &options);
+
+        // A line-oriented logger writing one short line at a time.
+        let mut expected = Vec::new();
+        for i in 0..100u8 {
+            let line = [b'a' + i % 26, b'\n'];
+            assert!(io.check_write(borrow(&stream))? >= 2);
+            io.write(borrow(&stream), line.to_vec())?;
+            expected.extend_from_slice(&line);
+        }
+        io.flush(borrow(&stream))?;
+
+        let calls = calls.lock().unwrap();
+        let (flush, writes) = calls.split_last().unwrap();
+        assert_eq!(*flush, Call::Flush);
+        // Every 8 lines fill the staging buffer, and the rest are forwarded
+        // by the flush.
+        assert_eq!(writes.len(), 13);
+        let written: Vec<u8> = writes
+            .iter()
+            .flat_map(|call| match call {
+                Call::Write(bytes) => bytes.clone(),
+                call => panic!("unexpected {call:?}"),
+            })
+            .collect();
+        assert_eq!(written, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn permits_account_for_staged_bytes() -> anyhow::Result<()> {
+        static SIGNAL: crate::WakeSignal = crate::WakeSignal::new();
+        let mut options = IoLinkOptions::new();
+        options.coalesce_writes(1024);
+        let mut table = ResourceTable::new();
+        let (stream, calls) = sink(&mut table);
+        let mut io = IoImpl::new(&mut table, &options);
+
+        assert_eq!(io.check_write(borrow(&stream))?, 64);
+        io.write(borrow(&stream), vec![1; 10])?;
+        assert_eq!(io.check_write(borrow(&stream))?, 54);
+        io.write(borrow(&stream), vec![2; 54])?;
+        assert!(calls.lock().unwrap().is_empty());
+
+        // The staged bytes use up the sink's whole permit, so they're
+        // forwarded to make room for more.
+        assert_eq!(io.check_write(borrow(&stream))?, 64);
+        io.write(borrow(&stream), vec![3; 1])?;
+        io.write(borrow(&stream), vec![4; 1])?;
+
+        // Dropping the stream forwards what's left.
+        crate::block_on(&SIGNAL, |_| {}, io.drop(stream))?;
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(
+            calls[0],
+            Call::Write([[1; 10].as_slice(), &[2; 54]].concat())
+        );
+        assert_eq!(calls[1], Call::Write(vec![3, 4]));
+        Ok(())
+    }
+
+    #[test]
+    fn uncoalesced_without_options() -> anyhow::Result<()> {
+        let options = IoLinkOptions::new();
+        let mut table = ResourceTable::new();
+        let (stream, calls) = sink(&mut table);
+        let mut io = IoImpl::new(&mut table, &options);
+        io.check_write(borrow(&stream))?;
+        io.write(borrow(&stream), vec![1])?;
+        io.write(borrow(&stream), vec![2])?;
+        assert_eq!(calls.lock().unwrap().len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn forwards_hints_permits_and_splices() -> anyhow::Result<()> {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let hint = PendingHint::new();
+        let sink = CountingSink(calls.clone(), hint.clone());
+        let mut stream = CoalescingOutputStream::new(Box::new(sink), 16);
+
+        // The wrapper shares the sink's hint and its enforcement of permits.
+        hint.set_pending();
+        assert!(stream.pending_hint().unwrap().is_pending());
+        assert!(stream.enforces_write_permits());
+
+        // Staged bytes reach the sink before the spliced ones.
+        stream.write(Bytes::from_static(b"ab"))?;
+        assert_eq!(stream.staged(), 2);
+        let mut src = crate::snapshot::ClosedStream;
+        assert_eq!(stream.splice_from(&mut src, 5).unwrap()?, 5);
+        assert_eq!(stream.staged(), 0);
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [Call::Write(b"ab".to_vec()), Call::Splice(5)]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,437 @@
+//! The host implementation of the concurrent `wasi:io` bindings added by
+//! [`add_to_linker_concurrent`](crate::add_to_linker_concurrent).
+//!
+//! Functions which don't wait forward to the implementation used by
+//! [`add_to_linker_async`](crate::add_to_linker_async), so both apply the
+//! same [`IoLinkOptions`]. Functions which wait are built from those same
+//! non-waiting operations and only access the store while they make
+//! progress: in between, they wait for readiness without holding the store.
+//! Pollees which provide a [`Pollable::ready_owned`] future are waited on
+//! with that future, which is kept alive until the wait ends. For the
+//! others, a [`Pollable::ready`] future is created anew, in the store, each
+//! time the task is woken, so as with `poll` it may be dropped before it
+//! completes.
+
+use crate::bindings::concurrent::wasi::io::{error, poll, streams};
+use crate::bindings::wasi::io::{
+    error as async_error, poll as async_poll, streams as async_streams,
+};
+use crate::impls::{
+    PollList, Pollee, owned_pollee_future, owned_pollee_futures, pollee_future, pollee_futures,
+    pollees,
+};
+use crate::poll::{DynFuture, DynPollable, Pollable};
+use crate::streams::{DynInputStream, DynOutputStream, StreamError, StreamResult};
+use crate::{IoImpl, IoLinkOptions, WasiIo};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use anyhow::{Result, anyhow};
+use core::future::{Future, poll_fn};
+use core::task::{Context, Poll};
+use wasmtime::component::{Accessor, Resource, ResourceTableError};
+
+fn borrow<T: 'static>(resource: &Resource<T>) -> Resource<T> {
+    Resource::new_borrow(resource.rep())
+}
+
+/// Waits for `resource` to become ready.
+async fn ready<T, P: Pollable>(
+    store: &Accessor<T, WasiIo>,
+    resource: &Resource<P>,
+) -> Result<(), ResourceTableError> {
+    let owned = store.with(|mut view| {
+        Ok::<_, ResourceTableError>(view.get().table.get(resource)?.ready_owned())
+    })?;
+    if let Some(ready) = owned {
+        ready.await;
+        return Ok(());
+    }
+    poll_fn(|cx| {
+        store
+            .with(|mut view| {
+                let pollee = view.get().table.get_mut(resource)?;
+                Ok(pollee.ready().as_mut().poll(cx))
+            })
+            .map_or_else(|e| Poll::Ready(Err(e)), |ready| ready.map(Ok))
+    })
+    .await
+}
+
+/// Waits for `stream` to be ready to accept a write, returning how many
+/// bytes it accepts.
+async fn write_ready<T>(
+    store: &Accessor<T, WasiIo>,
+    stream: &Resource<DynOutputStream>,
+) -> StreamResult<u64> {
+    loop {
+        ready(store, stream).await?;
+        let permit = store.with(|mut view| {
+            async_streams::HostOutputStream::check_write(&mut view.get(), borrow(stream))
+        })?;
+        if permit > 0 {
+            return Ok(permit);
+        }
+    }
+}
+
+/// Returns the largest buffer the guest may pass to the blocking writes of
+/// `stream`, see `OutputStream::blocking_write_limit`.
+fn blocking_write_limit<T>(
+    store: &Accessor<T, WasiIo>,
+    stream: &Resource<DynOutputStream>,
+) -> StreamResult<usize> {
+    store.with(|mut view| Ok(view.get().table.get(stream)?.blocking_write_limit()))
+}
+
+/// Flushes `stream` and waits for the flush to complete, ignoring the
+/// stream having been closed as `OutputStream::blocking_write_and_flush`
+/// does.
+async fn flush_and_wait<T>(
+    store: &Accessor<T, WasiIo>,
+    stream: &Resource<DynOutputStream>,
+) -> StreamResult<()> {
+    match store
+        .with(|mut view| async_streams::HostOutputStream::flush(&mut view.get(), borrow(stream)))
+    {
+        Ok(()) | Err(StreamError::Closed) => {}
+        Err(e) => return Err(e),
+    }
+    match write_ready(store, stream).await {
+        Ok(_) | Err(StreamError::Closed) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Deletes a stream dropped by the guest and waits for its cancellation, or
+/// detaches it, without holding the store.
+async fn drop_stream<T, S: Pollable>(
+    store: &Accessor<T, WasiIo>,
+    stream: Resource<S>,
+    cancel: impl FnOnce(S) -> DynFuture<'static>,
+) -> Result<()> {
+    let (stream, options): (S, IoLinkOptions) = store.with(|mut view| {
+        let io = view.get();
+        anyhow::Ok((io.table.delete(stream)?, io.options.clone()))
+    })?;
+    options.cancel_dropped(cancel(stream)).await;
+    Ok(())
+}
+
+impl poll::HostWithStore for WasiIo {
+    async fn poll<T>(
+        store: &Accessor<T, Self>,
+        pollables: Vec<Resource<DynPollable>>,
+    ) -> Result<Vec<u32>> {
+        if pollables.is_empty() {
+            return Err(anyhow!("empty poll list"));
+        }
+        let (pollees, mut owned, deterministic) = store.with(|mut view| {
+            let io = view.get();
+            let pollees = pollees(io.table, &pollables)?;
+            let owned = owned_pollee_futures(io.table, &pollees);
+            anyhow::Ok((pollees, owned, io.options.deterministic))
+        })?;
+        // The futures of the other pollees borrow them, so they're created
+        // in the store each time the task is woken.
+        let borrowed: BTreeMap<u32, Pollee> = pollees
+            .iter()
+            .filter(|(index, _)| !owned.contains_key(index))
+            .map(|(index, pollee)| (*index, pollee.clone()))
+            .collect();
+        let mut ready = poll_fn(|cx: &mut Context<'_>| {
+            let mut ready = Vec::new();
+            for (index, future) in owned.iter_mut() {
+                pollees[index].poll_readiness(future, cx, &mut ready);
+            }
+            if !borrowed.is_empty() {
+                let borrowed = store.with(|mut view| {
+                    let futures = pollee_futures(view.get().table, borrowed.clone())?;
+                    anyhow::Ok(PollList { futures }.poll_futures(cx))
+                });
+                match borrowed {
+                    Ok(borrowed) => ready.extend(borrowed),
+                    Err(e) => return Poll::Ready(Err(e)),
+                }
+            }
+            if ready.is_empty() {
+                Poll::Pending
+            } else {
+                Poll::Ready(Ok(ready))
+            }
+        })
+        .await?;
+        if deterministic {
+            ready.sort_unstable();
+        }
+        Ok(ready)
+    }
+}
+
+impl poll::HostPollableWithStore for WasiIo {
+    async fn block<T>(store: &Accessor<T, Self>, pollable: Resource<DynPollable>) -> Result<()> {
+        let owned = store.with(|mut view| {
+            let table = view.get().table;
+            let pollable = table.get(&pollable)?;
+            let (index, make_owned_future) = (pollable.index, pollable.make_owned_future);
+            let debounce = pollable.debounce.clone();
+            let ready = owned_pollee_future(table, index, make_owned_future, debounce);
+            anyhow::Ok(ready)
+        })?;
+        if let Some(ready) = owned {
+            ready.await;
+            return Ok(());
+        }
+        poll_fn(|cx| {
+            store
+                .with(|mut view| {
+                    let table = view.get().table;
+                    let pollable = table.get(&pollable)?;
+                    let (index, make_future) = (pollable.index, pollable.make_future);
+                    let debounce = pollable.debounce.clone();
+                    let entry = table.get_any_mut(index);
+                    let mut ready = pollee_future(index, entry, make_future, debounce)?;
+                    anyhow::Ok(ready.as_mut().poll(cx))
+                })
+                .map_or_else(|e| Poll::Ready(Err(e)), |ready| ready.map(Ok))
+        })
+        .await
+    }
+}
+
+impl poll::Host for IoImpl<'_> {}
+
+impl poll::HostPollable for IoImpl<'_> {
+    fn ready(&mut self, pollable: Resource<DynPollable>) -> Result<bool> {
+        let pollable = self.table.get(&pollable)?;
+        let (index, make_future) = (pollable.index, pollable.make_future);
+        let debounce = pollable.debounce.clone();
+        let entry = self.table.get_any_mut(index);
+        let mut ready = pollee_future(index, entry, make_future, debounce)?;
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        Ok(ready.as_mut().poll(&mut cx).is_ready())
+    }
+
+    fn drop(&mut self, pollable: Resource<DynPollable>) -> Result<()> {
+        <IoImpl as async_poll::HostPollable>::drop(self, pollable)
+    }
+}
+
+impl error::Host for IoImpl<'_> {}
+
+impl error::HostError for IoImpl<'_> {
+    fn drop(&mut self, err: Resource<streams::Error>) -> Result<()> {
+        <IoImpl as async_error::HostError>::drop(self, err)
+    }
+
+    fn to_debug_string(&mut self, err: Resource<streams::Error>) -> Result<String> {
+        <IoImpl as async_error::HostError>::to_debug_string(self, err)
+    }
+}
+
+impl streams::Host for IoImpl<'_> {
+    fn convert_stream_error(&mut self, err: StreamError) -> Result<streams::StreamError> {
+        Ok(
+            match <IoImpl as async_streams::Host>::convert_stream_error(self, err)? {
+                async_streams::StreamError::LastOperationFailed(e) => {
+                    streams::StreamError::LastOperationFailed(e)
+                }
+                async_streams::StreamError::Closed => streams::StreamError::Closed,
+            },
+        )
+    }
+}
+
+impl streams::HostInputStreamWithStore for WasiIo {
+    async fn drop<T>(store: &Accessor<T, Self>, stream: Resource<DynInputStream>) -> Result<()> {
+        drop_stream(store, stream, |mut stream| {
+            Box::pin(async move { stream.cancel().await })
+        })
+        .await
+    }
+
+    async fn blocking_read<T>(
+        store: &Accessor<T, Self>,
+        stream: Resource<DynInputStream>,
+        len: u64,
+    ) -> StreamResult<Vec<u8>> {
+        loop {
+            ready(store, &stream).await?;
+            let bytes = store.with(|mut view| {
+                async_streams::HostInputStream::read(&mut view.get(), borrow(&stream), len)
+            })?;
+            if !bytes.is_empty() || len == 0 {
+                return Ok(bytes);
+            }
+        }
+    }
+
+    async fn blocking_skip<T>(
+        store: &Accessor<T, Self>,
+        stream: Resource<DynInputStream>,
+        len: u64,
+    ) -> StreamResult<u64> {
+        loop {
+            ready(store, &stream).await?;
+            let skipped = store.with(|mut view| {
+                async_streams::HostInputStream::skip(&mut view.get(), borrow(&stream), len)
+            })?;
+            if skipped > 0 || len == 0 {
+                return Ok(skipped);
+            }
+        }
+    }
+}
+
+impl streams::HostInputStream for IoImpl<'_> {
+    fn read(&mut self, stream: Resource<DynInputStream>, len: u64) -> StreamResult<Vec<u8>> {
+        async_streams::HostInputStream::read(self, stream, len)
+    }
+
+    fn skip(&mut self, stream: Resource<DynInputStream>, len: u64) -> StreamResult<u64> {
+        async_streams::HostInputStream::skip(self, stream, len)
+    }
+
+    fn subscribe(&mut self, stream: Resource<DynInputStream>) -> Result<Resource<DynPollable>> {
+        async_streams::HostInputStream::subscribe(self, stream)
+    }
+}
+
+impl streams::HostOutputStreamWithStore for WasiIo {
+    async fn drop<T>(store: &Accessor<T, Self>, stream: Resource<DynOutputStream>) -> Result<()> {
+        drop_stream(store, stream, |mut stream| {
+            Box::pin(async move { stream.cancel().await })
+        })
+        .await
+    }
+
+    async fn blocking_write_and_flush<T>(
+        store: &Accessor<T, Self>,
+        stream: Resource<DynOutputStream>,
+        bytes: Vec<u8>,
+    ) -> StreamResult<()> {
+        let limit = blocking_write_limit(store, &stream)?;
+        if bytes.len() > limit {
+            return Err(StreamError::guest_trap(&alloc::format!(
+                "Buffer too large for blocking-write-and-flush (expected at most {limit})"
+            )));
+        }
+        let mut bytes = &bytes[..];
+        while !bytes.is_empty() {
+            let permit = write_ready(store, &stream).await?;
+            let (chunk, rest) = bytes.split_at(bytes.len().min(permit as usize));
+            store.with(|mut view| {
+                async_streams::HostOutputStream::write(
+                    &mut view.get(),
+                    borrow(&stream),
+                    chunk.to_vec(),
+                )
+            })?;
+            bytes = rest;
+        }
+        flush_and_wait(store, &stream).await
+    }
+
+    async fn blocking_write_zeroes_and_flush<T>(
+        store: &Accessor<T, Self>,
+        stream: Resource<DynOutputStream>,
+        mut len: u64,
+    ) -> StreamResult<()> {
+        let limit = blocking_write_limit(store, &stream)?;
+        if len > limit as u64 {
+            return Err(StreamError::guest_trap(&alloc::format!(
+                "Buffer too large for blocking-write-zeroes-and-flush (expected at most {limit})"
+            )));
+        }
+        while len > 0 {
+            let chunk = write_ready(store, &stream).await?.min(len);
+            store.with(|mut view| {
+                async_streams::HostOutputStream::write_zeroes(
+                    &mut view.get(),
+                    borrow(&stream),
+                    chunk,
+                )
+            })?;
+            len -= chunk;
+        }
+        flush_and_wait(store, &stream).await
+    }
+
+    async fn blocking_flush<T>(
+        store: &Accessor<T, Self>,
+        stream: Resource<DynOutputStream>,
+    ) -> StreamResult<()> {
+        store.with(|mut view| {
+            async_streams::HostOutputStream::flush(&mut view.get(), borrow(&stream))
+        })?;
+        write_ready(store, &stream).await?;
+        Ok(())
+    }
+
+    async fn blocking_splice<T>(
+        store: &Accessor<T, Self>,
+        dest: Resource<DynOutputStream>,
+        src: Resource<DynInputStream>,
+        len: u64,
+    ) -> StreamResult<u64> {
+        if len == 0 {
+            // This doesn't wait, but still reports closed streams.
+            return store.with(|mut view| {
+                async_streams::HostOutputStream::splice(
+                    &mut view.get(),
+                    borrow(&dest),
+                    borrow(&src),
+                    len,
+                )
+            });
+        }
+        let len = len.min(write_ready(store, &dest).await?);
+        loop {
+            ready(store, &src).await?;
+            let spliced = store.with(|mut view| {
+                async_streams::HostOutputStream::splice(
+                    &mut view.get(),
+                    borrow(&dest),
+                    borrow(&src),
+                    len,
+                )
+            })?;
+            if spliced > 0 {
+                flush_and_wait(store, &dest).await?;
+                return Ok(spliced);
+            }
+        }
+    }
+}
+
+impl streams::HostOutputStream for IoImpl<'_> {
+    fn check_write(&mut self, stream: Resource<DynOutputStream>) -> StreamResult<u64> {
+        async_streams::HostOutputStream::check_write(self, stream)
+    }
+
+    fn write(&mut self, stream: Resource<DynOutputStream>, bytes: Vec<u8>) -> StreamResult<()> {
+        async_streams::HostOutputStream::write(self, stream, bytes)
+    }
+
+    fn flush(&mut self, stream: Resource<DynOutputStream>) -> StreamResult<()> {
+        async_streams::HostOutputStream::flush(self, stream)
+    }
+
+    fn subscribe(&mut self, stream: Resource<DynOutputStream>) -> Result<Resource<DynPollable>> {
+        async_streams::HostOutputStream::subscribe(self, stream)
+    }
+
+    fn write_zeroes(&mut self, stream: Resource<DynOutputStream>, len: u64) -> StreamResult<()> {
+        async_streams::HostOutputStream::write_zeroes(self, stream, len)
+    }
+
+    fn splice(
+        &mut self,
+        dest: Resource<DynOutputStream>,
+        src: Resource<DynInputStream>,
+        len: u64,
+    ) -> StreamResult<u64> {
+        async_streams::HostOutputStream::splice(self, dest, src, len)
+    }
+}
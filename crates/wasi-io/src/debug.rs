@@ -0,0 +1,268 @@
+//! Diagnostics for guests stuck waiting on pollables.
+//!
+//! [`poll_snapshot`] reports which of the pollables in a [`ResourceTable`]
+//! are ready and what each of them waits on, so host code such as a watchdog
+//! can log what a guest blocked in `poll` is waiting for.
+
+use crate::poll::{DynPollable, ReadinessKind};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::task::{Context, Poll, Waker};
+use wasmtime::component::ResourceTable;
+
+/// The readiness of a pollable found by [`poll_snapshot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollableState {
+    /// The pollable is ready, so `poll` would return it.
+    Ready,
+    /// The pollable is pending.
+    Pending,
+    /// The pollable's readiness wasn't checked, since its pollee is
+    /// edge-triggered.
+    Unchecked,
+}
+
+/// A pollable in a [`ResourceTable`] as found by [`poll_snapshot`].
+///
+/// The `Display` implementation renders it on one line, for logs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PollableDebugInfo {
+    /// The index of the pollable in the table.
+    pub pollable: u32,
+    /// The index of the resource the pollable waits on.
+    pub pollee: u32,
+    /// The Rust type name of the pollee, or `None` if it was deleted while
+    /// the pollable was alive.
+    pub pollee_type: Option<&'static str>,
+    /// The pollee's [`debug_name`](crate::poll::Pollable::debug_name), if it
+    /// has one.
+    pub pollee_name: Option<String>,
+    /// Whether the pollable is ready.
+    pub state: PollableState,
+}
+
+impl fmt::Display for PollableDebugInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pollable {} on ", self.pollable)?;
+        match self.pollee_type {
+            Some(ty) => write!(f, "{ty} {}", self.pollee)?,
+            None => write!(f, "deleted resource {}", self.pollee)?,
+        }
+        if let Some(name) = &self.pollee_name {
+            write!(f, " ({name})")?;
+        }
+        let state = match self.state {
+            PollableState::Ready => "ready",
+            PollableState::Pending => "pending",
+            PollableState::Unchecked => "unchecked (edge-triggered)",
+        };
+        write!(f, ": {state}")
+    }
+}
+
+/// Returns the state of every pollable in `table`, in index order.
+///
+/// This is a diagnostic, and it isn't free of side effects: each pollee's
+/// readiness is checked as `poll` checks it, by creating its future and
+/// polling it once without waiting. The pollee may start work which it would
+/// otherwise have started the next time the guest polled it, and checking an
+/// edge-triggered pollee may consume the readiness the guest is waiting for.
+/// Pollees whose [`readiness_kind`](crate::poll::Pollable::readiness_kind)
+/// is [`ReadinessKind::Edge`] are therefore reported as
+/// [`PollableState::Unchecked`] rather than checked.
+///
+/// Debouncing applied with
+/// [`DebouncedPollable`](crate::poll::DebouncedPollable) is ignored, so a
+/// debounced pollable is reported ready as soon as its source is.
+pub fn poll_snapshot(table: &mut ResourceTable) -> Vec<PollableDebugInfo> {
+    let pollables: Vec<_> = table
+        .iter()
+        .filter_map(|(index, _, entry)| {
+            let pollable = entry.downcast_ref::<DynPollable>()?;
+            Some((
+                index,
+                pollable.index,
+                pollable.make_future,
+                pollable.describe,
+            ))
+        })
+        .collect();
+
+    let mut cx = Context::from_waker(Waker::noop());
+    pollables
+        .into_iter()
+        .map(|(pollable, pollee, make_future, describe)| {
+            let Ok(entry) = table.get_any_mut(pollee) else {
+                // As for `poll`, a pollable whose pollee is gone is ready.
+                return PollableDebugInfo {
+                    pollable,
+                    pollee,
+                    pollee_type: None,
+                    pollee_name: None,
+                    state: PollableState::Ready,
+                };
+            };
+            let description = describe(&*entry);
+            let state = match description.readiness_kind {
+                ReadinessKind::Edge => PollableState::Unchecked,
+                ReadinessKind::Level => match make_future(entry).as_mut().poll(&mut cx) {
+                    Poll::Ready(()) => PollableState::Ready,
+                    Poll::Pending => PollableState::Pending,
+                },
+            };
+            PollableDebugInfo {
+                pollable,
+                pollee,
+                pollee_type: Some(description.type_name),
+                pollee_name: description.debug_name,
+                state,
+            }
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::poll::{Notifier, Pollable, subscribe};
+    use crate::streams::{DynInputStream, InputStream, ReadAheadInputStream, StreamResult};
+    use alloc::boxed::Box;
+    use alloc::string::ToString;
+    use bytes::Bytes;
+    use wasmtime::component::Resource;
+
+    /// A socket which never has data to read.
+    struct Socket;
+
+    #[async_trait::async_trait]
+    impl Pollable for Socket {
+        async fn ready(&mut self) {
+            core::future::pending().await
+        }
+
+        fn debug_name(&self) -> Option<String> {
+            Some("peer 10.0.0.1:80".to_string())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl InputStream for Socket {
+        fn read(&mut self, _size: usize) -> StreamResult<Bytes> {
+            Ok(Bytes::new())
+        }
+    }
+
+    /// An edge-triggered pollee which panics if its readiness is checked.
+    struct Edge;
+
+    #[async_trait::async_trait]
+    impl Pollable for Edge {
+        async fn ready(&mut self) {
+            panic!("edge-triggered pollee was checked")
+        }
+
+        fn readiness_kind(&self) -> ReadinessKind {
+            ReadinessKind::Edge
+        }
+    }
+
+    /// An edge-triggered input stream.
+    struct EdgeStream;
+
+    #[async_trait::async_trait]
+    impl Pollable for EdgeStream {
+        async fn ready(&mut self) {
+            panic!("edge-triggered stream was checked")
+        }
+
+        fn readiness_kind(&self) -> ReadinessKind {
+            ReadinessKind::Edge
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl InputStream for EdgeStream {
+        fn read(&mut self, _size: usize) -> StreamResult<Bytes> {
+            Ok(Bytes::new())
+        }
+    }
+
+    #[test]
+    fn snapshot_reports_ready_and_pending_pollables() -> anyhow::Result<()> {
+        let mut table = ResourceTable::new();
+        let ready = Notifier::new();
+        ready.notify_one();
+        let ready = ready.pollable(&mut table)?;
+        let idle = Notifier::new().pollable(&mut table)?;
+        let socket = table.push(Box::new(Socket) as DynInputStream)?;
+        let reading = subscribe(
+            &mut table,
+            Resource::<DynInputStream>::new_borrow(socket.rep()),
+        )?;
+        let edge = table.push(Edge)?;
+        let edge = subscribe(&mut table, edge)?;
+
+        let snapshot = poll_snapshot(&mut table);
+        let states: Vec<_> = snapshot.iter().map(|p| (p.pollable, p.state)).collect();
+        assert_eq!(
+            states,
+            [
+                (ready.rep(), PollableState::Ready),
+                (idle.rep(), PollableState::Pending),
+                (reading.rep(), PollableState::Pending),
+                (edge.rep(), PollableState::Unchecked),
+            ]
+        );
+
+        let reading = &snapshot[2];
+        assert_eq!(reading.pollee, socket.rep());
+        assert_eq!(reading.pollee_name.as_deref(), Some("peer 10.0.0.1:80"));
+        assert_eq!(
+            reading.to_string(),
+            format!(
+                "pollable {} on {} {} (peer 10.0.0.1:80): pending",
+                reading.pollable,
+                core::any::type_name::<Socket>(),
+                reading.pollee
+            )
+        );
+        assert_eq!(
+            snapshot[0].to_string(),
+            format!(
+                "pollable {} on wasmtime_wasi_io::poll::Notifier {}: ready",
+                snapshot[0].pollable, snapshot[0].pollee
+            )
+        );
+
+        // Checking a level-triggered pollable doesn't consume its readiness.
+        assert_eq!(poll_snapshot(&mut table), snapshot);
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_describes_wrapped_streams() -> anyhow::Result<()> {
+        let mut table = ResourceTable::new();
+        let stream = ReadAheadInputStream::new(Box::new(Socket), 16);
+        let stream = table.push(Box::new(stream) as DynInputStream)?;
+        subscribe(
+            &mut table,
+            Resource::<DynInputStream>::new_borrow(stream.rep()),
+        )?;
+        let edge = ReadAheadInputStream::new(Box::new(EdgeStream), 16);
+        let edge = table.push(Box::new(edge) as DynInputStream)?;
+        subscribe(
+            &mut table,
+            Resource::<DynInputStream>::new_borrow(edge.rep()),
+        )?;
+
+        let snapshot = poll_snapshot(&mut table);
+        assert_eq!(
+            snapshot[0].pollee_type,
+            Some(core::any::type_name::<ReadAheadInputStream>())
+        );
+        assert_eq!(snapshot[0].pollee_name.as_deref(), Some("peer 10.0.0.1:80"));
+        assert_eq!(snapshot[1].state, PollableState::Unchecked);
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+//! Deterministic execution of wasi-io.
+//!
+//! Embedders which replay executions, or run them on several hosts which
+//! must agree on the outcome, need a guest's view of its streams to depend
+//! only on what the guest does. When [`IoLinkOptions::deterministic`] is
+//! enabled the host implementation added by
+//! [`add_to_linker_async`](crate::add_to_linker_async) guarantees that as
+//! follows:
+//!
+//! * Every stream operation, including `subscribe`, traps unless the stream
+//!   returns `true` from [`InputStream::is_deterministic`] or
+//!   [`OutputStream::is_deterministic`].
+//! * `poll` returns the indices of the ready pollables in ascending order,
+//!   rather than in the order in which their pollees are stored.
+//! * `read` returns exactly `min(len, available)` bytes, where `available` is
+//!   what the stream can produce without waiting: the stream is read
+//!   repeatedly, and the results concatenated, until `len` bytes have been
+//!   read or a read returns no bytes. `blocking-read` first waits for at
+//!   least one byte and then reads the same way. If the stream reports that
+//!   it's closed after some bytes were read, those bytes are returned and
+//!   the closure is reported by the next read. Other operations, including
+//!   `skip` and `splice`, perform a single read of the stream.
+//! * `error.to-debug-string` describes the error and its causes on one line,
+//!   separated by `: `, without a backtrace and with every hexadecimal
+//!   number, such as a pointer, replaced by `<addr>`.
+//!
+//! [`IoLinkOptions::deterministic`]: crate::IoLinkOptions::deterministic
+//! [`InputStream::is_deterministic`]: crate::streams::InputStream::is_deterministic
+//! [`OutputStream::is_deterministic`]: crate::streams::OutputStream::is_deterministic
+
+use crate::streams::{DynInputStream, StreamError, StreamResult};
+use alloc::string::String;
+use bytes::{Bytes, BytesMut};
+
+/// The error with which operations on a nondeterministic stream trap.
+pub(crate) fn nondeterministic(kind: &str, index: u32) -> StreamError {
+    StreamError::Trap(anyhow::anyhow!(
+        "{kind} stream {index} isn't deterministic but deterministic mode is enabled"
+    ))
+}
+
+/// Reads from `stream` until `len` bytes, including those already in
+/// `read`, have been read or no more are available.
+pub(crate) fn fill(stream: &mut DynInputStream, len: usize, read: Bytes) -> StreamResult<Bytes> {
+    if read.len() >= len {
+        return Ok(read);
+    }
+    let mut buf = BytesMut::from(&read[..]);
+    while buf.len() < len {
+        match stream.read(len - buf.len()) {
+            Ok(bytes) if bytes.is_empty() => break,
+            Ok(bytes) => buf.extend_from_slice(&bytes),
+            // Streams keep reporting their closure, so it's left for the
+            // next read.
+            Err(StreamError::Closed) if !buf.is_empty() => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(buf.freeze())
+}
+
+/// Formats `err` and its causes without addresses or a backtrace.
+pub(crate) fn stable_debug_string(err: &anyhow::Error) -> String {
+    let message = alloc::format!("{err:#}");
+    let mut stable = String::with_capacity(message.len());
+    let mut rest = message.as_str();
+    while let Some(start) = rest.find("0x") {
+        let digits = rest[start + 2..]
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .unwrap_or(rest.len() - start - 2);
+        stable.push_str(&rest[..start]);
+        if digits == 0 {
+            stable.push_str("0x");
+        } else {
+            stable.push_str("<addr>");
+        }
+        rest = &rest[start + 2 + digits..];
+    }
+    stable.push_str(rest);
+    stable
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::bindings::wasi::io::poll::Host as _;
+    use crate::bindings::wasi::io::streams::{HostInputStream, HostOutputStream};
+    use crate::executor::{WakeSignal, block_on};
+    use crate::poll::Pollable;
+    use crate::snapshot::ClosedStream;
+    use crate::streams::{DynOutputStream, InputStream};
+    use crate::test_streams::Chunks;
+    use crate::{IoImpl, IoLinkOptions};
+    use alloc::boxed::Box;
+    use wasmtime::component::{Resource, ResourceTable};
+
+    fn chunks(table: &mut ResourceTable, chunks: &[&'static str]) -> Resource<DynInputStream> {
+        let stream: DynInputStream = Box::new(Chunks::new(chunks.iter().copied()));
+        table.push(stream).unwrap()
+    }
+
+    fn borrow<T: 'static>(r: &Resource<T>) -> Resource<T> {
+        Resource::new_borrow(r.rep())
+    }
+
+    #[test]
+    fn reads_are_filled() -> anyhow::Result<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let mut options = IoLinkOptions::new();
+        options.deterministic(true);
+        let mut table = ResourceTable::new();
+        let stream = chunks(&mut table, &["ab", "", "cd", "efg"]);
+        let mut io = IoImpl::new(&mut table, &options);
+
+        // The empty chunk ends the first read.
+        assert_eq!(io.read(borrow(&stream), 3)?, b"ab");
+        assert_eq!(io.read(borrow(&stream), 3)?, b"cde");
+        let read = block_on(&SIGNAL, |_| {}, io.blocking_read(borrow(&stream), 8))?;
+        assert_eq!(read, b"fg");
+        assert!(matches!(
+            io.read(borrow(&stream), 1),
+            Err(StreamError::Closed)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn nondeterministic_streams_trap() -> anyhow::Result<()> {
+        struct Opaque;
+
+        #[async_trait::async_trait]
+        impl Pollable for Opaque {
+            async fn ready(&mut self) {}
+        }
+
+        #[async_trait::async_trait]
+        impl InputStream for Opaque {
+            fn read(&mut self, _size: usize) -> StreamResult<Bytes> {
+                Ok(Bytes::new())
+            }
+        }
+
+        let mut options = IoLinkOptions::new();
+        options.deterministic(true);
+        let mut table = ResourceTable::new();
+        let opaque = table.push(Box::new(Opaque) as DynInputStream)?;
+        let closed = table.push(Box::new(ClosedStream) as DynOutputStream)?;
+        let mut io = IoImpl::new(&mut table, &options);
+
+        assert!(matches!(
+            io.read(borrow(&opaque), 1),
+            Err(StreamError::Trap(_))
+        ));
+        assert!(HostInputStream::subscribe(&mut io, borrow(&opaque)).is_err());
+        assert!(matches!(
+            io.check_write(borrow(&closed)),
+            Err(StreamError::Closed)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn ready_pollables_are_sorted() -> anyhow::Result<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let mut options = IoLinkOptions::new();
+        options.deterministic(true);
+        let mut table = ResourceTable::new();
+        let first = chunks(&mut table, &[]);
+        let second = chunks(&mut table, &[]);
+        let mut io = IoImpl::new(&mut table, &options);
+        let first = HostInputStream::subscribe(&mut io, first)?;
+        let second = HostInputStream::subscribe(&mut io, second)?;
+
+        // Both pollables are ready, and the one listed first is subscribed
+        // to the stream with the higher table index.
+        let ready = block_on(&SIGNAL, |_| {}, io.poll(vec![second, first]))?;
+        assert_eq!(ready, [0, 1]);
+        Ok(())
+    }
+
+    #[test]
+    fn debug_strings_are_stable() {
+        let err = anyhow::anyhow!("buffer at 0x7ffd4a2c10 overflowed").context("write failed");
+        assert_eq!(
+            stable_debug_string(&err),
+            "write failed: buffer at <addr> overflowed"
+        );
+        let err = anyhow::anyhow!("0x is not an address, 0xAB is");
+        assert_eq!(stable_debug_string(&err), "0x is not an address, <addr> is");
+    }
+}
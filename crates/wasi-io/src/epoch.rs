@@ -0,0 +1,96 @@
+//! Pollables which become ready when an [`Engine`](wasmtime::Engine)'s epoch
+//! advances.
+//!
+//! Embedders which tick epochs, for example to schedule guests fairly, can
+//! let guests wait for a tick through the standard `wasi:io/poll` interface
+//! with [`epoch_pollable`]. The engine itself can't report epoch changes,
+//! since [`Engine::increment_epoch`](wasmtime::Engine::increment_epoch) must
+//! remain signal-safe, so the embedder provides a [`Notifier`] and calls
+//! [`Notifier::notify_waiters`] after each tick. Waiting pollables are woken
+//! by it and check the epoch again; nothing polls the epoch in a loop.
+//!
+//! # Interaction with epoch deadlines
+//!
+//! A store's epoch deadline is only checked while Wasm code runs. A guest
+//! blocked in `poll` is inside a host call, so its deadline neither traps
+//! nor, with
+//! [`Store::epoch_deadline_async_yield_and_update`](wasmtime::Store::epoch_deadline_async_yield_and_update),
+//! makes it yield while it's blocked, however many ticks pass. When `poll`
+//! returns, a deadline which passed in the meantime takes effect at the
+//! guest's next epoch check: the guest yields there, and the deadline is
+//! then advanced by the configured delta from the current epoch. An epoch
+//! pollable with a target at or before the store's deadline therefore wakes
+//! the guest no later than the deadline would have interrupted it.
+
+use crate::poll::{DynFuture, DynPollable, Notifier, Pollable, subscribe};
+use alloc::boxed::Box;
+use anyhow::Result;
+use wasmtime::EngineWeak;
+use wasmtime::component::{Resource, ResourceTable};
+
+/// A [`Pollable`] which is ready once the epoch of an engine has reached a
+/// target, see [`epoch_pollable`].
+#[derive(Clone)]
+pub struct EpochPollable {
+    engine: EngineWeak,
+    target: u64,
+    ticks: Notifier,
+}
+
+impl EpochPollable {
+    /// Creates a pollable which is ready once the epoch of `engine` is at
+    /// least `target_epoch`, and which checks the epoch whenever `ticks` is
+    /// notified.
+    ///
+    /// The pollable is also ready once the engine has been dropped, since
+    /// its epoch can't advance any further.
+    pub fn new(engine: EngineWeak, target_epoch: u64, ticks: &Notifier) -> EpochPollable {
+        EpochPollable {
+            engine,
+            target: target_epoch,
+            ticks: ticks.clone(),
+        }
+    }
+
+    fn reached(&self) -> bool {
+        match self.engine.upgrade() {
+            Some(engine) => engine.current_epoch() >= self.target,
+            None => true,
+        }
+    }
+
+    /// Waits until the target epoch is reached.
+    async fn wait(&self) {
+        let mut waiter = core::pin::pin!(self.ticks.waiter());
+        core::future::poll_fn(|cx| waiter.as_mut().poll_until(cx, || self.reached())).await
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for EpochPollable {
+    async fn ready(&mut self) {
+        self.wait().await
+    }
+
+    fn ready_owned(&self) -> Option<DynFuture<'static>> {
+        let pollable = self.clone();
+        Some(Box::pin(async move { pollable.wait().await }))
+    }
+}
+
+/// Creates a `wasi:io/poll.pollable` resource in `table` which is ready once
+/// the epoch of `engine` is at least `target_epoch`.
+///
+/// The embedder must call [`Notifier::notify_waiters`] on `ticks` after each
+/// call to [`Engine::increment_epoch`](wasmtime::Engine::increment_epoch)
+/// for waiting guests to observe the new epoch. See the
+/// [module documentation](self) for how this interacts with epoch deadlines.
+pub fn epoch_pollable(
+    table: &mut ResourceTable,
+    engine: EngineWeak,
+    target_epoch: u64,
+    ticks: &Notifier,
+) -> Result<Resource<DynPollable>> {
+    let resource = table.push(EpochPollable::new(engine, target_epoch, ticks))?;
+    subscribe(table, resource)
+}
@@ -0,0 +1,117 @@
+//! Errors with a machine-readable code.
+//!
+//! Streams report failures to the guest as `wasi:io/error.error` resources,
+//! which plain guests can only turn into a string with `to-debug-string`.
+//! Host code which wants guests to be able to branch on why an operation
+//! failed builds an [`IoError`] instead of an arbitrary error:
+//!
+//! ```
+//! use wasmtime_wasi_io::error::{ErrorCode, IoError};
+//! use wasmtime_wasi_io::streams::StreamError;
+//!
+//! fn connection_reset() -> StreamError {
+//!     IoError::new(ErrorCode::CONNECTION_RESET, "peer reset the connection").into()
+//! }
+//! ```
+//!
+//! The error's code is then available to guests through the
+//! `wasmtime:wasi-io/error-code` extension interface added by
+//! [`add_error_code_extension_to_linker`](crate::add_error_code_extension_to_linker),
+//! and `to-debug-string` renders it as `code: message`.
+
+use crate::streams::StreamError;
+use alloc::string::{String, ToString};
+use core::fmt;
+
+/// A machine-readable code describing why an operation failed.
+///
+/// Codes are opaque numbers to wasi-io, so embedders may define their own
+/// alongside the well-known ones defined here.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ErrorCode(pub u32);
+
+impl ErrorCode {
+    /// The operation failed for a reason not covered by a more specific
+    /// code.
+    pub const OTHER: ErrorCode = ErrorCode(0);
+    /// The operation isn't permitted.
+    pub const PERMISSION_DENIED: ErrorCode = ErrorCode(1);
+    /// The operation didn't complete in time.
+    pub const TIMED_OUT: ErrorCode = ErrorCode(2);
+    /// The peer reset the connection.
+    pub const CONNECTION_RESET: ErrorCode = ErrorCode(3);
+    /// The data read or written was invalid.
+    pub const INVALID_DATA: ErrorCode = ErrorCode(4);
+    /// A resource limit, such as a memory budget, was exhausted.
+    pub const LIMIT_EXCEEDED: ErrorCode = ErrorCode(5);
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// An error with an [`ErrorCode`], stored as the concrete type behind
+/// `wasi:io/error.error` resources created from it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IoError {
+    code: ErrorCode,
+    message: String,
+}
+
+impl IoError {
+    /// Creates an error with `code`, described by `message`.
+    pub fn new(code: ErrorCode, message: impl fmt::Display) -> IoError {
+        IoError {
+            code,
+            message: message.to_string(),
+        }
+    }
+
+    /// Returns this error's code.
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    /// Returns the description of this error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the code of the first [`IoError`] in `err`'s chain of causes,
+    /// if any.
+    pub fn code_of(err: &anyhow::Error) -> Option<ErrorCode> {
+        err.chain()
+            .find_map(|e| e.downcast_ref::<IoError>())
+            .map(IoError::code)
+    }
+}
+
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl core::error::Error for IoError {}
+
+impl From<IoError> for StreamError {
+    fn from(err: IoError) -> StreamError {
+        StreamError::LastOperationFailed(anyhow::Error::new(err))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_survives_context() {
+        let err = anyhow::Error::new(IoError::new(ErrorCode::TIMED_OUT, "no response"));
+        assert_eq!(err.to_string(), "2: no response");
+        let err = err.context("reading from socket");
+        assert_eq!(IoError::code_of(&err), Some(ErrorCode::TIMED_OUT));
+        assert_eq!(IoError::code_of(&anyhow::anyhow!("plain")), None);
+    }
+}
@@ -0,0 +1,607 @@
+//! A feed of the events the host implementation observes, for dashboards
+//! and debugging.
+//!
+//! [`subscribe`] registers a subscriber with an [`IoLinkOptions`] and
+//! returns an [`IoEvents`] stream, into which the host implementation added
+//! by [`add_to_linker_async`](crate::add_to_linker_async) publishes an
+//! [`IoEvent`] as the guest:
+//!
+//! * receives a stream, and drops it,
+//! * observes a pollable to be ready with `poll` or `block`,
+//! * starts and finishes a blocking operation, such as `poll` or
+//!   `blocking-read`,
+//! * and receives a stream error, or traps because of one.
+//!
+//! Streams the host pushed with [`IoImpl::push_input_stream`] or
+//! [`IoImpl::push_output_stream`] are reported when they're pushed. Other
+//! streams, such as those pushed into the table directly by other host
+//! interfaces, are reported when the guest first uses them, or when it drops
+//! them if it never used them, so every stream's creation is reported
+//! before its drop.
+//!
+//! Publishing never waits for a subscriber: each buffers up to its capacity
+//! of events, after which the oldest are dropped and counted by
+//! [`IoEvents::dropped`]. Without subscribers no events are created at all.
+//!
+//! Subscribers must be registered before the options are cloned into the
+//! stores which use them, since clones made earlier don't publish to them.
+//! Clones track which streams they have reported separately, so each store
+//! should use its own clone.
+//! The waits of the bindings added by
+//! [`add_to_linker_concurrent`](crate::add_to_linker_concurrent) aren't
+//! reported as blocking operations.
+//!
+//! [`IoImpl::push_input_stream`]: crate::IoImpl::push_input_stream
+//! [`IoImpl::push_output_stream`]: crate::IoImpl::push_output_stream
+
+use crate::IoLinkOptions;
+use crate::poll::SpinLock;
+use alloc::collections::{BTreeSet, VecDeque};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::future::Future;
+use core::mem;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll};
+use core::time::Duration;
+use futures::Stream;
+use futures::task::AtomicWaker;
+
+/// The number of events a subscriber buffers by default, see
+/// [`subscribe_with_capacity`].
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// Something the host implementation observed, as published to the
+/// subscribers created by [`subscribe`].
+///
+/// Resources are identified by the
+/// [`rep`](wasmtime::component::Resource::rep) of their handles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IoEvent {
+    /// The host pushed a stream for the guest.
+    StreamCreated {
+        /// The handle of the stream.
+        stream: u32,
+        /// Whether it's an input or an output stream.
+        kind: StreamKind,
+    },
+    /// The guest dropped a stream.
+    StreamDropped {
+        /// The handle of the stream.
+        stream: u32,
+        /// Whether it was an input or an output stream.
+        kind: StreamKind,
+    },
+    /// `poll` or `block` observed a pollable to be ready.
+    PollableReady {
+        /// The handle of the pollable.
+        pollable: u32,
+        /// The handle of the resource the pollable is subscribed to, such as
+        /// a stream which became readable.
+        pollee: u32,
+    },
+    /// The guest started an operation which waits.
+    BlockingStarted(BlockingOp),
+    /// An operation which waits finished, whether or not it succeeded.
+    BlockingFinished {
+        /// The operation.
+        op: BlockingOp,
+        /// How long the operation took, which is only measured with the
+        /// `std` feature of this crate.
+        elapsed: Option<Duration>,
+    },
+    /// A stream operation failed and its error was converted for the guest.
+    ErrorConverted(ConvertedError),
+}
+
+/// The kind of a stream in an [`IoEvent`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamKind {
+    /// An `input-stream`.
+    Input,
+    /// An `output-stream`.
+    Output,
+}
+
+/// An operation which waits, in an [`IoEvent`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BlockingOp {
+    /// `poll`, with this many pollables.
+    Poll {
+        /// The number of pollables passed to `poll`.
+        pollables: usize,
+    },
+    /// `pollable.block`.
+    Block {
+        /// The handle of the pollable.
+        pollable: u32,
+    },
+    /// `input-stream.blocking-read`.
+    Read {
+        /// The handle of the stream.
+        stream: u32,
+    },
+    /// `blocking-read-vectored` from the extension interface added by
+    /// [`add_read_vectored_extension_to_linker`](crate::add_read_vectored_extension_to_linker).
+    ReadVectored {
+        /// The handle of the stream.
+        stream: u32,
+    },
+    /// `blocking-read-timeout` from the extension interface added by
+    /// [`add_timeout_extension_to_linker`](crate::add_timeout_extension_to_linker).
+    ReadTimeout {
+        /// The handle of the stream.
+        stream: u32,
+    },
+    /// `input-stream.blocking-skip`.
+    Skip {
+        /// The handle of the stream.
+        stream: u32,
+    },
+    /// `output-stream.blocking-write-and-flush`.
+    WriteAndFlush {
+        /// The handle of the stream.
+        stream: u32,
+    },
+    /// `output-stream.blocking-write-zeroes-and-flush`.
+    WriteZeroesAndFlush {
+        /// The handle of the stream.
+        stream: u32,
+    },
+    /// `output-stream.blocking-flush`.
+    Flush {
+        /// The handle of the stream.
+        stream: u32,
+    },
+    /// `output-stream.blocking-splice`.
+    Splice {
+        /// The handle of the input stream spliced from.
+        src: u32,
+        /// The handle of the output stream spliced to.
+        dest: u32,
+    },
+}
+
+/// How a stream error was converted for the guest, in an [`IoEvent`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConvertedError {
+    /// The guest was told the stream is `closed`.
+    Closed,
+    /// The guest was told the operation failed and handed an error.
+    LastOperationFailed {
+        /// The handle of the `error` resource handed to the guest.
+        error: u32,
+    },
+    /// The guest trapped.
+    Trap,
+}
+
+/// Registers a new subscriber with `options`, buffering up to
+/// [`DEFAULT_CAPACITY`] events.
+///
+/// # Example
+///
+/// ```
+/// use futures::StreamExt;
+/// use wasmtime_wasi_io::events::{self, IoEvent};
+/// use wasmtime_wasi_io::{IoLinkOptions, WakeSignal, block_on};
+///
+/// let mut options = IoLinkOptions::new();
+/// let mut feed = events::subscribe(&mut options);
+///
+/// // ... hand `options` to the store and run the guest ...
+///
+/// drop(options);
+/// static SIGNAL: WakeSignal = WakeSignal::new();
+/// let events: Vec<IoEvent> = block_on(&SIGNAL, |_| {}, feed.by_ref().collect());
+/// assert!(events.is_empty());
+/// assert_eq!(feed.dropped(), 0);
+/// ```
+pub fn subscribe(options: &mut IoLinkOptions) -> IoEvents {
+    subscribe_with_capacity(options, DEFAULT_CAPACITY)
+}
+
+/// Like [`subscribe`], but buffers up to `capacity` events, which is raised
+/// to 1 if it's 0.
+pub fn subscribe_with_capacity(options: &mut IoLinkOptions, capacity: usize) -> IoEvents {
+    let queue = Arc::new(Queue {
+        capacity: capacity.max(1),
+        state: SpinLock::new(QueueState::default()),
+        waker: Arc::new(AtomicWaker::new()),
+        closed: AtomicBool::new(false),
+    });
+    options.events.queues.push(queue.clone());
+    IoEvents(queue)
+}
+
+/// The events published to a subscriber, created by [`subscribe`].
+///
+/// The stream ends once the [`IoLinkOptions`] it was registered with, and
+/// every clone of them, have been dropped.
+pub struct IoEvents(Arc<Queue>);
+
+impl IoEvents {
+    /// Returns the number of events which were dropped because this
+    /// subscriber's buffer was full.
+    pub fn dropped(&self) -> u64 {
+        self.0.state.with(|state| state.dropped)
+    }
+}
+
+impl Stream for IoEvents {
+    type Item = IoEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<IoEvent>> {
+        let queue = &self.0;
+        queue.waker.register(cx.waker());
+        if let Some(event) = queue.state.with(|state| state.events.pop_front()) {
+            return Poll::Ready(Some(event));
+        }
+        // Publishers hold the only other references to the queue.
+        if Arc::strong_count(queue) == 1 {
+            return Poll::Ready(None);
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for IoEvents {
+    fn drop(&mut self) {
+        self.0.closed.store(true, Ordering::Release);
+    }
+}
+
+impl fmt::Debug for IoEvents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IoEvents")
+            .field("dropped", &self.dropped())
+            .finish_non_exhaustive()
+    }
+}
+
+struct Queue {
+    capacity: usize,
+    state: SpinLock<QueueState>,
+    waker: Arc<AtomicWaker>,
+    /// Set once the subscriber has been dropped, after which nothing is
+    /// published to it.
+    closed: AtomicBool,
+}
+
+#[derive(Default)]
+struct QueueState {
+    events: VecDeque<IoEvent>,
+    dropped: u64,
+}
+
+impl Queue {
+    fn push(&self, event: IoEvent) {
+        self.state.with(|state| {
+            if state.events.len() == self.capacity {
+                state.events.pop_front();
+                state.dropped += 1;
+            }
+            state.events.push_back(event);
+        });
+        self.waker.wake();
+    }
+}
+
+/// The subscribers of an [`IoLinkOptions`], into which events are
+/// published.
+///
+/// Clones share the subscribers, but start without any reported streams.
+pub(crate) struct Publisher {
+    queues: Vec<Arc<Queue>>,
+    /// The streams whose creation has been reported and which haven't been
+    /// dropped since.
+    streams: SpinLock<BTreeSet<u32>>,
+}
+
+impl Publisher {
+    pub(crate) const fn new() -> Publisher {
+        Publisher {
+            queues: Vec::new(),
+            streams: SpinLock::new(BTreeSet::new()),
+        }
+    }
+
+    /// Returns whether there are any subscribers, without which publishing
+    /// does nothing.
+    pub(crate) fn is_enabled(&self) -> bool {
+        !self.queues.is_empty()
+    }
+
+    /// Publishes the event created by `event`, which is only called if there
+    /// are subscribers.
+    pub(crate) fn publish(&self, event: impl FnOnce() -> IoEvent) {
+        if !self.is_enabled() {
+            return;
+        }
+        let event = event();
+        for queue in &self.queues {
+            if !queue.closed.load(Ordering::Acquire) {
+                queue.push(event);
+            }
+        }
+    }
+
+    /// Reports the creation of `stream`, unless it has already been
+    /// reported.
+    pub(crate) fn stream_created(&self, stream: u32, kind: StreamKind) {
+        if self.is_enabled() && self.streams.with(|streams| streams.insert(stream)) {
+            self.publish(|| IoEvent::StreamCreated { stream, kind });
+        }
+    }
+
+    /// Reports the drop of `stream`, preceded by its creation if that
+    /// hasn't been reported.
+    pub(crate) fn stream_dropped(&self, stream: u32, kind: StreamKind) {
+        if !self.is_enabled() {
+            return;
+        }
+        if !self.streams.with(|streams| streams.remove(&stream)) {
+            self.publish(|| IoEvent::StreamCreated { stream, kind });
+        }
+        self.publish(|| IoEvent::StreamDropped { stream, kind });
+    }
+
+    /// Waits for `future`, the blocking operation `op`, publishing its start
+    /// and finish.
+    pub(crate) async fn blocking<F: Future>(&self, op: BlockingOp, future: F) -> F::Output {
+        if !self.is_enabled() {
+            return future.await;
+        }
+        self.publish(|| IoEvent::BlockingStarted(op));
+        #[cfg(feature = "std")]
+        let start = std::time::Instant::now();
+        let output = future.await;
+        #[cfg(feature = "std")]
+        let elapsed = Some(start.elapsed());
+        #[cfg(not(feature = "std"))]
+        let elapsed = None;
+        self.publish(|| IoEvent::BlockingFinished { op, elapsed });
+        output
+    }
+}
+
+impl Clone for Publisher {
+    fn clone(&self) -> Publisher {
+        Publisher {
+            queues: self.queues.clone(),
+            streams: SpinLock::default(),
+        }
+    }
+}
+
+impl Drop for Publisher {
+    fn drop(&mut self) {
+        // Subscribers are woken once the reference is gone, so they can
+        // tell whether this was the last publisher. The waker is shared
+        // separately from the queue so that it's still available then.
+        for queue in mem::take(&mut self.queues) {
+            let waker = queue.waker.clone();
+            drop(queue);
+            waker.wake();
+        }
+    }
+}
+
+impl fmt::Debug for Publisher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Publisher")
+            .field("subscribers", &self.queues.len())
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::bindings::wasi::io::poll::{self, Host as _};
+    use crate::bindings::wasi::io::streams::{
+        self, Host as _, HostInputStream as _, HostOutputStream as _,
+    };
+    use crate::executor::{WakeSignal, block_on};
+    use crate::streams::{DynInputStream, DynOutputStream, StreamError};
+    use crate::test_streams::{Chunks, Discard};
+    use crate::{IoImpl, IoLinkOptions};
+    use alloc::boxed::Box;
+    use futures::StreamExt;
+    use wasmtime::component::{Resource, ResourceTable};
+
+    fn borrow<T: 'static>(r: &Resource<T>) -> Resource<T> {
+        Resource::new_borrow(r.rep())
+    }
+
+    /// Collects the events of `feed`, whose options must have been dropped,
+    /// checking that blocking operations were timed and then clearing their
+    /// durations.
+    fn collect(signal: &'static WakeSignal, feed: &mut IoEvents) -> Vec<IoEvent> {
+        let mut events: Vec<IoEvent> = block_on(signal, |_| {}, feed.by_ref().collect());
+        for event in &mut events {
+            if let IoEvent::BlockingFinished { elapsed, .. } = event {
+                assert!(elapsed.take().is_some());
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn scripted_guest() -> anyhow::Result<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let mut options = IoLinkOptions::new();
+        let mut feed = subscribe(&mut options);
+        let mut table = ResourceTable::new();
+        let mut io = IoImpl::new(&mut table, &options);
+
+        let input = io.push_input_stream(Box::new(Chunks::new(["hi"])))?;
+        let output = io.push_output_stream(Box::new(Discard))?;
+        let (i, o) = (input.rep(), output.rep());
+        let pollable = streams::HostInputStream::subscribe(&mut io, borrow(&input))?;
+        let p = pollable.rep();
+        assert_eq!(
+            block_on(&SIGNAL, |_| {}, io.poll(vec![borrow(&pollable)]))?,
+            [0]
+        );
+        let read = block_on(&SIGNAL, |_| {}, io.blocking_read(borrow(&input), 10))?;
+        assert_eq!(read, b"hi");
+        let closed = io.read(borrow(&input), 10).unwrap_err();
+        assert!(matches!(
+            io.convert_stream_error(closed)?,
+            streams::StreamError::Closed
+        ));
+        let failed = StreamError::LastOperationFailed(anyhow::anyhow!("boom"));
+        let streams::StreamError::LastOperationFailed(error) = io.convert_stream_error(failed)?
+        else {
+            panic!("error wasn't converted to last-operation-failed");
+        };
+        assert!(io.convert_stream_error(StreamError::trap("bug")).is_err());
+        block_on(&SIGNAL, |_| {}, io.blocking_flush(borrow(&output)))?;
+        poll::HostPollable::drop(&mut io, pollable)?;
+        block_on(
+            &SIGNAL,
+            |_| {},
+            streams::HostInputStream::drop(&mut io, input),
+        )?;
+        block_on(
+            &SIGNAL,
+            |_| {},
+            streams::HostOutputStream::drop(&mut io, output),
+        )?;
+        drop(io);
+        drop(options);
+
+        let poll = BlockingOp::Poll { pollables: 1 };
+        let read = BlockingOp::Read { stream: i };
+        let flush = BlockingOp::Flush { stream: o };
+        let finished = |op| IoEvent::BlockingFinished { op, elapsed: None };
+        assert_eq!(
+            collect(&SIGNAL, &mut feed),
+            [
+                IoEvent::StreamCreated {
+                    stream: i,
+                    kind: StreamKind::Input
+                },
+                IoEvent::StreamCreated {
+                    stream: o,
+                    kind: StreamKind::Output
+                },
+                IoEvent::BlockingStarted(poll),
+                finished(poll),
+                IoEvent::PollableReady {
+                    pollable: p,
+                    pollee: i
+                },
+                IoEvent::BlockingStarted(read),
+                finished(read),
+                IoEvent::ErrorConverted(ConvertedError::Closed),
+                IoEvent::ErrorConverted(ConvertedError::LastOperationFailed { error: error.rep() }),
+                IoEvent::ErrorConverted(ConvertedError::Trap),
+                IoEvent::BlockingStarted(flush),
+                finished(flush),
+                IoEvent::StreamDropped {
+                    stream: i,
+                    kind: StreamKind::Input
+                },
+                IoEvent::StreamDropped {
+                    stream: o,
+                    kind: StreamKind::Output
+                },
+            ]
+        );
+        assert_eq!(feed.dropped(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn streams_pushed_directly_are_reported() -> anyhow::Result<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let mut options = IoLinkOptions::new();
+        let mut feed = subscribe(&mut options);
+        let mut table = ResourceTable::new();
+        let used: Resource<DynInputStream> = table.push(Box::new(Chunks::new(["hi"])))?;
+        let unused: Resource<DynOutputStream> = table.push(Box::new(Discard))?;
+        let (u, n) = (used.rep(), unused.rep());
+        let mut io = IoImpl::new(&mut table, &options);
+
+        // The first use reports the stream, and later ones don't.
+        assert_eq!(io.read(borrow(&used), 10)?, b"hi");
+        assert!(io.read(borrow(&used), 10).is_err());
+        block_on(
+            &SIGNAL,
+            |_| {},
+            streams::HostInputStream::drop(&mut io, used),
+        )?;
+        block_on(
+            &SIGNAL,
+            |_| {},
+            streams::HostOutputStream::drop(&mut io, unused),
+        )?;
+        drop(io);
+        drop(options);
+
+        assert_eq!(
+            collect(&SIGNAL, &mut feed),
+            [
+                IoEvent::StreamCreated {
+                    stream: u,
+                    kind: StreamKind::Input
+                },
+                IoEvent::StreamDropped {
+                    stream: u,
+                    kind: StreamKind::Input
+                },
+                IoEvent::StreamCreated {
+                    stream: n,
+                    kind: StreamKind::Output
+                },
+                IoEvent::StreamDropped {
+                    stream: n,
+                    kind: StreamKind::Output
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn overflow_drops_oldest() -> anyhow::Result<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let mut options = IoLinkOptions::new();
+        let mut small = subscribe_with_capacity(&mut options, 2);
+        let mut large = subscribe(&mut options);
+        let mut table = ResourceTable::new();
+        let mut io = IoImpl::new(&mut table, &options);
+        let reps = (0..5)
+            .map(|_| Ok(io.push_output_stream(Box::new(Discard))?.rep()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        drop(io);
+
+        let created = |stream| IoEvent::StreamCreated {
+            stream,
+            kind: StreamKind::Output,
+        };
+        drop(options);
+        assert_eq!(
+            collect(&SIGNAL, &mut small),
+            [created(reps[3]), created(reps[4])]
+        );
+        assert_eq!(small.dropped(), 3);
+        assert_eq!(collect(&SIGNAL, &mut large).len(), 5);
+        assert_eq!(large.dropped(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn nothing_published_without_subscribers() {
+        let options = IoLinkOptions::new();
+        options
+            .events
+            .publish(|| panic!("event created without subscribers"));
+    }
+}
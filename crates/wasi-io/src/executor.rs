@@ -0,0 +1,243 @@
+//! A minimal single-future executor for driving wasi-io from bare-metal
+//! embeddings.
+//!
+//! Guests which call `wasi:io/poll.block` leave a Wasmtime future pending
+//! until some [`Pollable`](crate::poll::Pollable) becomes ready, so an async
+//! executor is required to run them. Embeddings without an operating system
+//! typically have no such executor, and so [`block_on`] provides one which
+//! only needs two primitives from the platform:
+//!
+//! * A [`WakeSignal`], which wakers set and which is safe to set from an
+//!   interrupt handler with [`WakeSignal::wake_from_irq`].
+//! * A `wait` function, passed to [`block_on`], which idles the processor
+//!   until an interrupt may have set the signal, for example with `wfi` or
+//!   `hlt`.
+//!
+//! See the [`min-platform` example] for a timer-interrupt-driven embedding.
+//!
+//! [`min-platform` example]: https://github.com/bytecodealliance/wasmtime/tree/main/examples/min-platform
+
+use core::future::Future;
+use core::pin::pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+/// A flag which records that the future passed to [`block_on`] should be
+/// polled again.
+///
+/// Wakers created by [`block_on`] set this flag, as does
+/// [`WakeSignal::wake_from_irq`]. Setting it is a single atomic store, so it
+/// may happen concurrently with [`block_on`] from interrupt context or from
+/// another thread.
+pub struct WakeSignal {
+    woken: AtomicBool,
+    running: AtomicBool,
+}
+
+impl WakeSignal {
+    /// Creates a new, unset, signal.
+    pub const fn new() -> WakeSignal {
+        WakeSignal {
+            woken: AtomicBool::new(false),
+            running: AtomicBool::new(false),
+        }
+    }
+
+    /// Requests that the future being driven with this signal is polled
+    /// again.
+    ///
+    /// This only performs an atomic store and is safe to call from an
+    /// interrupt handler, including one which interrupted [`block_on`].
+    pub fn wake_from_irq(&self) {
+        self.woken.store(true, Ordering::Release);
+    }
+
+    /// Returns whether this signal is set, without clearing it.
+    ///
+    /// This is intended for `wait` functions which, for example, disable
+    /// interrupts, check this, and only then idle the processor, to avoid
+    /// missing a wakeup which happened just before idling.
+    pub fn is_woken(&self) -> bool {
+        self.woken.load(Ordering::Acquire)
+    }
+
+    fn take(&self) -> bool {
+        self.woken.swap(false, Ordering::Acquire)
+    }
+
+    fn waker(&'static self) -> Waker {
+        const VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake, drop);
+
+        unsafe fn clone(data: *const ()) -> RawWaker {
+            RawWaker::new(data, &VTABLE)
+        }
+        unsafe fn wake(data: *const ()) {
+            // SAFETY: `data` is always a `&'static WakeSignal`, see below.
+            unsafe { (*data.cast::<WakeSignal>()).wake_from_irq() }
+        }
+        unsafe fn drop(_data: *const ()) {}
+
+        let raw = RawWaker::new((self as *const WakeSignal).cast(), &VTABLE);
+        // SAFETY: the vtable functions above uphold the `RawWaker` contract
+        // since the signal they point to lives forever and is `Sync`.
+        unsafe { Waker::from_raw(raw) }
+    }
+}
+
+impl Default for WakeSignal {
+    fn default() -> WakeSignal {
+        WakeSignal::new()
+    }
+}
+
+/// Runs `future` to completion on the current thread.
+///
+/// The future is polled once immediately, and afterwards only when `signal`
+/// has been set, either by a waker handed to the future or by
+/// [`WakeSignal::wake_from_irq`]. In between polls `wait` is called to idle
+/// until an interrupt may have set `signal`.
+///
+/// `wait` is allowed to return spuriously, in which case it's simply called
+/// again. It must however eventually return once `signal` is set, so it
+/// should not idle if [`WakeSignal::is_woken`] is already `true`.
+///
+/// # Panics
+///
+/// Panics if `signal` is already being used by another, possibly nested,
+/// call to `block_on`.
+pub fn block_on<F: Future>(
+    signal: &'static WakeSignal,
+    mut wait: impl FnMut(&WakeSignal),
+    future: F,
+) -> F::Output {
+    if signal.running.swap(true, Ordering::Acquire) {
+        panic!("`WakeSignal` is already in use by another `block_on`");
+    }
+    struct Running(&'static WakeSignal);
+    impl Drop for Running {
+        fn drop(&mut self) {
+            self.0.running.store(false, Ordering::Release);
+        }
+    }
+    let _running = Running(signal);
+
+    let waker = signal.waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        // Clear the signal before polling so that wakeups which happen while
+        // the future is being polled cause it to be polled again.
+        signal.take();
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        while !signal.is_woken() {
+            wait(signal);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use core::sync::atomic::AtomicUsize;
+
+    /// A future which becomes ready after it has been woken `n` times,
+    /// recording the number of polls.
+    struct WakeCount<'a> {
+        remaining: usize,
+        polls: &'a AtomicUsize,
+        waker: &'a std::sync::Mutex<Option<Waker>>,
+    }
+
+    impl Future for WakeCount<'_> {
+        type Output = ();
+        fn poll(mut self: core::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            self.polls.fetch_add(1, Ordering::SeqCst);
+            if self.remaining == 0 {
+                return Poll::Ready(());
+            }
+            self.remaining -= 1;
+            *self.waker.lock().unwrap() = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn ready_future_needs_no_wait() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let result = block_on(&SIGNAL, |_| panic!("should not wait"), async { 42 });
+        assert_eq!(result, 42);
+    }
+
+    #[test]
+    fn interrupt_driven_wakeups() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let polls = AtomicUsize::new(0);
+        let waker = std::sync::Mutex::new(None);
+        let mut waits = 0;
+
+        // Each call to `wait` simulates idling until the "timer interrupt"
+        // fires: the first two calls return spuriously without anything
+        // happening, and the third wakes the future through its waker as an
+        // interrupt handler would.
+        block_on(
+            &SIGNAL,
+            |signal| {
+                waits += 1;
+                if waits % 3 == 0 {
+                    waker.lock().unwrap().take().unwrap().wake();
+                    assert!(signal.is_woken());
+                }
+            },
+            WakeCount {
+                remaining: 3,
+                polls: &polls,
+                waker: &waker,
+            },
+        );
+        assert_eq!(polls.load(Ordering::SeqCst), 4);
+        assert_eq!(waits, 9);
+    }
+
+    #[test]
+    fn wakeups_from_another_thread() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let polls = AtomicUsize::new(0);
+        let waker = std::sync::Mutex::new(None);
+        let done = AtomicBool::new(false);
+
+        std::thread::scope(|s| {
+            // An "interrupt" which fires asynchronously with respect to the
+            // executor whenever the future is waiting on it.
+            s.spawn(|| {
+                while !done.load(Ordering::SeqCst) {
+                    if let Some(waker) = waker.lock().unwrap().take() {
+                        waker.wake();
+                    }
+                    std::thread::yield_now();
+                }
+            });
+            block_on(
+                &SIGNAL,
+                |_| std::thread::yield_now(),
+                WakeCount {
+                    remaining: 5,
+                    polls: &polls,
+                    waker: &waker,
+                },
+            );
+            done.store(true, Ordering::SeqCst);
+        });
+        assert_eq!(polls.load(Ordering::SeqCst), 6);
+    }
+
+    #[test]
+    #[should_panic(expected = "already in use")]
+    fn nested_use_of_signal_panics() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        block_on(&SIGNAL, |_| {}, async {
+            block_on(&SIGNAL, |_| {}, async {});
+        });
+    }
+}
@@ -0,0 +1,169 @@
+//! Reading streams held by the guest from the host.
+//!
+//! A guest can hand an `input-stream` to the host, for example as the result
+//! of one of its exports, but the stream stays in the store's
+//! [`ResourceTable`] and the guest may still hold handles to it. With
+//! [`host_view_input_stream`] the host takes the stream out of its table
+//! entry and reads it directly, without going through the bindings, until it
+//! [releases](HostInputStreamHandle::release) it again.
+//!
+//! While the host holds the stream, the table entry refers to a placeholder
+//! on which the guest's reads fail, rather than reads from the host and the
+//! guest interleaving. If the guest drops its handle in the meantime, the
+//! stream is cancelled and the host's reads report it as closed.
+
+use crate::poll::Pollable;
+use crate::streams::{DynInputStream, InputStream, StreamError, StreamResult};
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use bytes::Bytes;
+use core::any::Any;
+use core::sync::atomic::{AtomicU8, Ordering};
+use wasmtime::component::{Resource, ResourceTable, ResourceTableError};
+
+/// The stream is held by the host.
+const LENT: u8 = 0;
+/// The host released the stream back into the table.
+const RELEASED: u8 = 1;
+/// The host dropped its handle without releasing the stream.
+const ABANDONED: u8 = 2;
+/// The guest dropped the stream's resource while the host held the stream.
+const DROPPED: u8 = 3;
+
+/// Takes the input stream `stream` out of `table` for the host to read with
+/// the returned handle.
+///
+/// `stream` may be a borrow of a resource owned by the host or by a guest.
+/// Until the handle is released, the guest's reads of the stream fail with
+/// [`StreamError::LastOperationFailed`], and once it's dropped without being
+/// released they report the stream as closed.
+///
+/// A stream which the host is already reading through another handle is
+/// reported as [`ResourceTableError::WrongType`], since its table entry no
+/// longer holds it.
+pub fn host_view_input_stream(
+    table: &mut ResourceTable,
+    stream: Resource<DynInputStream>,
+) -> Result<HostInputStreamHandle, ResourceTableError> {
+    let entry = table.get_mut(&stream)?;
+    if entry.as_any_mut().is_some_and(|s| s.is::<Lent>()) {
+        return Err(ResourceTableError::WrongType);
+    }
+    let state = Arc::new(AtomicU8::new(LENT));
+    let inner = core::mem::replace(entry, Box::new(Lent(state.clone())));
+    Ok(HostInputStreamHandle {
+        resource: stream,
+        stream: Some(inner),
+        state,
+    })
+}
+
+/// An input stream taken out of a [`ResourceTable`] by
+/// [`host_view_input_stream`].
+pub struct HostInputStreamHandle {
+    resource: Resource<DynInputStream>,
+    /// The stream, until the guest drops its resource and the stream is
+    /// cancelled.
+    stream: Option<DynInputStream>,
+    state: Arc<AtomicU8>,
+}
+
+impl HostInputStreamHandle {
+    /// Returns the stream, cancelling it first if the guest dropped its
+    /// resource.
+    async fn stream(&mut self) -> Option<&mut DynInputStream> {
+        if self.state.load(Ordering::Acquire) == DROPPED {
+            if let Some(mut stream) = self.stream.take() {
+                stream.cancel().await;
+            }
+        }
+        self.stream.as_mut()
+    }
+
+    /// Waits for the stream to be ready to read, as a guest's pollable for
+    /// it does.
+    ///
+    /// Returns immediately once the guest has dropped the stream.
+    pub async fn ready(&mut self) {
+        if let Some(stream) = self.stream().await {
+            stream.ready().await;
+        }
+    }
+
+    /// Reads up to `len` bytes from the stream, waiting for at least one to
+    /// be available as the guest's `blocking-read` does.
+    ///
+    /// The stream is reported as [`StreamError::Closed`] once the guest has
+    /// dropped it.
+    pub async fn read(&mut self, len: usize) -> StreamResult<Bytes> {
+        match self.stream().await {
+            Some(stream) => stream.blocking_read(len).await,
+            None => Err(StreamError::Closed),
+        }
+    }
+
+    /// Puts the stream back into its entry in `table`, where the guest can
+    /// read it again.
+    ///
+    /// Returns whether the stream was put back, which it isn't if the guest
+    /// dropped it in the meantime, in which case it's cancelled instead.
+    pub async fn release(mut self, table: &mut ResourceTable) -> Result<bool, ResourceTableError> {
+        let Some(mut stream) = self.stream.take() else {
+            return Ok(false);
+        };
+        if self
+            .state
+            .compare_exchange(LENT, RELEASED, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            stream.cancel().await;
+            return Ok(false);
+        }
+        *table.get_mut(&self.resource)? = stream;
+        Ok(true)
+    }
+}
+
+impl Drop for HostInputStreamHandle {
+    fn drop(&mut self) {
+        let _ = self
+            .state
+            .compare_exchange(LENT, ABANDONED, Ordering::AcqRel, Ordering::Acquire);
+    }
+}
+
+/// The placeholder left in the table while the host holds a stream.
+struct Lent(Arc<AtomicU8>);
+
+#[async_trait::async_trait]
+impl Pollable for Lent {
+    async fn ready(&mut self) {}
+}
+
+#[async_trait::async_trait]
+impl InputStream for Lent {
+    fn read(&mut self, _size: usize) -> StreamResult<Bytes> {
+        match self.0.load(Ordering::Acquire) {
+            LENT => Err(StreamError::LastOperationFailed(anyhow::anyhow!(
+                "the stream is being read by the host"
+            ))),
+            _ => Err(StreamError::Closed),
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.0.load(Ordering::Acquire) != LENT
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        Some(self)
+    }
+}
+
+impl Drop for Lent {
+    fn drop(&mut self) {
+        let _ = self
+            .0
+            .compare_exchange(LENT, DROPPED, Ordering::AcqRel, Ordering::Acquire);
+    }
+}
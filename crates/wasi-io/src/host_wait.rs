@@ -0,0 +1,226 @@
+//! Instrumentation of the time the guest spends waiting on the host.
+//!
+//! Wasmtime's [`Store::call_hook`] reports when the guest calls into the host
+//! and when the host returns, but a host function such as `blocking-read`
+//! may return immediately or only after waiting on a stream for a long time,
+//! and the call hook can't tell these apart. A [`HostWaitHook`] configured
+//! with [`IoLinkOptions::host_wait_hook`] is told when one of the blocking
+//! operations of wasi-io, such as `poll` or `blocking-read`, actually
+//! suspends, and when it finishes.
+//!
+//! # Correlating with the call hook
+//!
+//! Blocking operations are host functions, so every
+//! [`HostWaitHook::on_host_wait_begin`] falls between the
+//! [`CallHook::CallingHost`] and [`CallHook::ReturningFromHost`] of the
+//! call which performs the operation, and is followed by its
+//! [`HostWaitHook::on_host_wait_end`] before the host returns. A call hook
+//! which records spans can therefore attribute the time between the two to
+//! waiting in the innermost open host call of the same store. While waiting
+//! the store's future returns [`Poll::Pending`] to its executor, so the
+//! calls are made from whichever thread polls the store.
+//!
+//! Operations which complete without suspending, for example a
+//! `blocking-read` of a stream which already has data, aren't reported. An
+//! operation which is cancelled after suspending, because the store's
+//! future was dropped, still reports its end. Without a hook blocking
+//! operations are awaited directly, with no overhead.
+//!
+//! [`Store::call_hook`]: wasmtime::Store::call_hook
+//! [`CallHook::CallingHost`]: wasmtime::CallHook::CallingHost
+//! [`CallHook::ReturningFromHost`]: wasmtime::CallHook::ReturningFromHost
+//! [`IoLinkOptions::host_wait_hook`]: crate::IoLinkOptions::host_wait_hook
+//! [`Poll::Pending`]: core::task::Poll::Pending
+
+use crate::events::BlockingOp;
+use alloc::sync::Arc;
+use core::fmt;
+use core::future::{Future, poll_fn};
+use core::pin::pin;
+
+/// Receives the waits of blocking operations, configured with
+/// [`IoLinkOptions::host_wait_hook`](crate::IoLinkOptions::host_wait_hook).
+///
+/// Hooks aren't handed the guest's table and are called from the future of
+/// the host function, never from within the methods of a stream, so they
+/// may take locks which the embedder's streams also take. They should
+/// return quickly, as the store can't make progress until they do.
+pub trait HostWaitHook: Send + Sync {
+    /// Called once the blocking operation `kind` first suspends.
+    fn on_host_wait_begin(&self, kind: BlockingOp);
+
+    /// Called once the blocking operation `kind`, which was reported to
+    /// [`HostWaitHook::on_host_wait_begin`], finishes or is cancelled.
+    fn on_host_wait_end(&self, kind: BlockingOp);
+}
+
+/// A [`HostWaitHook`] configured with an
+/// [`IoLinkOptions`](crate::IoLinkOptions).
+#[derive(Clone)]
+pub(crate) struct WaitHook(Arc<dyn HostWaitHook>);
+
+impl WaitHook {
+    pub(crate) fn new(hook: Arc<dyn HostWaitHook>) -> WaitHook {
+        WaitHook(hook)
+    }
+}
+
+impl fmt::Debug for WaitHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WaitHook").finish_non_exhaustive()
+    }
+}
+
+/// Waits for `future`, the blocking operation `kind`, reporting to `hook`
+/// if it suspends.
+pub(crate) async fn bracket<F: Future>(
+    hook: Option<&WaitHook>,
+    kind: BlockingOp,
+    future: F,
+) -> F::Output {
+    let Some(hook) = hook else {
+        return future.await;
+    };
+
+    /// Reports the end of the wait when dropped, so that a cancelled
+    /// operation still closes its bracket.
+    struct Waiting<'a>(&'a dyn HostWaitHook, BlockingOp);
+
+    impl Drop for Waiting<'_> {
+        fn drop(&mut self) {
+            self.0.on_host_wait_end(self.1);
+        }
+    }
+
+    let mut future = pin!(future);
+    let mut waiting = None;
+    poll_fn(|cx| {
+        let output = future.as_mut().poll(cx);
+        if output.is_pending() && waiting.is_none() {
+            hook.0.on_host_wait_begin(kind);
+            waiting = Some(Waiting(&*hook.0, kind));
+        }
+        output
+    })
+    .await
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::bindings::wasi::io::streams::HostInputStream as _;
+    use crate::executor::{WakeSignal, block_on};
+    use crate::poll::{Pollable, SpinLock};
+    use crate::streams::{InputStream, StreamResult};
+    use crate::{IoImpl, IoLinkOptions};
+    use alloc::boxed::Box;
+    use alloc::vec::Vec;
+    use bytes::Bytes;
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use core::task::Poll;
+    use futures::FutureExt;
+    use wasmtime::component::{Resource, ResourceTable};
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Entry {
+        Suspended,
+        Read,
+        Begin(BlockingOp),
+        End(BlockingOp),
+    }
+
+    type Log = Arc<SpinLock<Vec<Entry>>>;
+
+    struct Recorder(Log);
+
+    impl HostWaitHook for Recorder {
+        fn on_host_wait_begin(&self, kind: BlockingOp) {
+            self.0.with(|log| log.push(Entry::Begin(kind)));
+        }
+
+        fn on_host_wait_end(&self, kind: BlockingOp) {
+            self.0.with(|log| log.push(Entry::End(kind)));
+        }
+    }
+
+    /// An input stream which, while `pending` is set, isn't ready the first
+    /// time it's polled.
+    struct Suspending {
+        pending: Arc<AtomicBool>,
+        log: Log,
+    }
+
+    #[async_trait::async_trait]
+    impl Pollable for Suspending {
+        async fn ready(&mut self) {
+            poll_fn(|cx| {
+                if self.pending.swap(false, Ordering::Relaxed) {
+                    self.log.with(|log| log.push(Entry::Suspended));
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+                Poll::Ready(())
+            })
+            .await
+        }
+    }
+
+    impl InputStream for Suspending {
+        fn read(&mut self, _size: usize) -> StreamResult<Bytes> {
+            self.log.with(|log| log.push(Entry::Read));
+            Ok(Bytes::from_static(b"hi"))
+        }
+    }
+
+    fn borrow<T: 'static>(r: &Resource<T>) -> Resource<T> {
+        Resource::new_borrow(r.rep())
+    }
+
+    #[test]
+    fn brackets_suspended_reads() -> anyhow::Result<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let log = Log::default();
+        let mut options = IoLinkOptions::new();
+        options.host_wait_hook(Arc::new(Recorder(log.clone())));
+        let mut table = ResourceTable::new();
+        let mut io = IoImpl::new(&mut table, &options);
+        let pending = Arc::new(AtomicBool::new(true));
+        let stream = io.push_input_stream(Box::new(Suspending {
+            pending: pending.clone(),
+            log: log.clone(),
+        }))?;
+        let read = BlockingOp::Read {
+            stream: stream.rep(),
+        };
+        let take = || log.with(core::mem::take);
+
+        let bytes = block_on(&SIGNAL, |_| {}, io.blocking_read(borrow(&stream), 10))?;
+        assert_eq!(bytes, b"hi");
+        assert_eq!(
+            take(),
+            [
+                Entry::Suspended,
+                Entry::Begin(read),
+                Entry::Read,
+                Entry::End(read)
+            ]
+        );
+
+        // A read which doesn't suspend isn't reported.
+        block_on(&SIGNAL, |_| {}, io.blocking_read(borrow(&stream), 10))?;
+        assert_eq!(take(), [Entry::Read]);
+
+        // A read which is cancelled after suspending still reports its end.
+        pending.store(true, Ordering::Relaxed);
+        assert!(
+            io.blocking_read(borrow(&stream), 10)
+                .now_or_never()
+                .is_none()
+        );
+        assert_eq!(
+            take(),
+            [Entry::Suspended, Entry::Begin(read), Entry::End(read)]
+        );
+        Ok(())
+    }
+}
@@ -1,81 +1,271 @@
 use crate::bindings::wasi::io::{error, poll, streams};
-use crate::poll::{DynFuture, DynPollable, MakeFuture, subscribe};
-use crate::streams::{DynInputStream, DynOutputStream, StreamError, StreamResult};
+use crate::bindings::wasmtime::wasi_io::{
+    error_code, streams_metadata, streams_preferred_read_size, streams_read_vectored,
+    streams_subscribe_batch, streams_timeout,
+};
+use crate::child::delete_child;
+use crate::deterministic;
+use crate::error::IoError;
+use crate::events::{BlockingOp, ConvertedError, IoEvent, StreamKind};
+use crate::poll::{
+    DebouncedPollable, DynFuture, DynPollable, MakeFuture, MakeOwnedFuture, PendingHint, subscribe,
+    subscribe_batch, with_entries,
+};
+use crate::streams::{
+    DynInputStream, DynOutputStream, StreamError, StreamResult, TrapOrigin, split_vectored,
+    vectored_total,
+};
+use crate::{DEFAULT_OPTIONS, IoImpl, IoLinkOptions};
+use alloc::boxed::Box;
 use alloc::collections::BTreeMap;
 use alloc::string::String;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use anyhow::{Result, anyhow};
+use bytes::Bytes;
+use core::any::Any;
 use core::future::Future;
 use core::pin::Pin;
 use core::task::{Context, Poll};
-use wasmtime::component::{Resource, ResourceTable};
+use core::time::Duration;
+use futures::future::Either;
+use wasmtime::component::{Resource, ResourceTable, ResourceTableError};
 
 impl poll::Host for ResourceTable {
     async fn poll(&mut self, pollables: Vec<Resource<DynPollable>>) -> Result<Vec<u32>> {
-        type ReadylistIndex = u32;
-
         if pollables.is_empty() {
             return Err(anyhow!("empty poll list"));
         }
 
-        let mut table_futures: BTreeMap<u32, (MakeFuture, Vec<ReadylistIndex>)> = BTreeMap::new();
-
-        for (ix, p) in pollables.iter().enumerate() {
-            let ix: u32 = ix.try_into()?;
+        let pollees = pollees(self, &pollables)?;
 
-            let pollable = self.get(p)?;
-            let (_, list) = table_futures
-                .entry(pollable.index)
-                .or_insert((pollable.make_future, Vec::new()));
-            list.push(ix);
+        // Pollees which were pending the last time they were checked are
+        // likely still pending, so first check whether any of the others are
+        // ready without creating futures for those which are hinted.
+        let unhinted: BTreeMap<u32, Pollee> = pollees
+            .iter()
+            .filter(|(_, pollee)| !pollee.hinted_pending())
+            .map(|(index, pollee)| (*index, pollee.clone()))
+            .collect();
+        if !unhinted.is_empty() && unhinted.len() < pollees.len() {
+            let futures = pollee_futures(self, unhinted)?;
+            let ready = PollList { futures }.poll_once().await;
+            if !ready.is_empty() {
+                return Ok(ready);
+            }
         }
 
-        let mut futures: Vec<(DynFuture<'_>, Vec<ReadylistIndex>)> = Vec::new();
-        for (entry, (make_future, readylist_indices)) in self.iter_entries(table_futures) {
-            let entry = entry?;
-            futures.push((make_future(entry), readylist_indices));
+        // Nothing was known to be ready, so every pollee is checked before
+        // suspending.
+        let futures = pollee_futures(self, pollees)?;
+        Ok(PollList { futures }.await)
+    }
+}
+
+type ReadylistIndex = u32;
+
+/// The pollables of a `poll` call subscribed to the same pollee.
+#[derive(Clone)]
+pub(crate) struct Pollee {
+    make_future: MakeFuture,
+    make_owned_future: MakeOwnedFuture,
+    hint: Option<PendingHint>,
+    debounce: Option<Arc<DebouncedPollable>>,
+    readylist_indices: Vec<ReadylistIndex>,
+}
+
+impl Pollee {
+    fn hinted_pending(&self) -> bool {
+        self.hint.as_ref().is_some_and(|hint| hint.is_pending())
+    }
+
+    /// Polls `future`, this pollee's readiness, adding the readylist
+    /// indices of its pollables to `results` if it's ready.
+    pub(crate) fn poll_readiness(
+        &self,
+        future: &mut DynFuture<'_>,
+        cx: &mut Context<'_>,
+        results: &mut Vec<u32>,
+    ) {
+        // The hint is set before polling so a notification racing with this
+        // poll clears it again.
+        if let Some(hint) = &self.hint {
+            hint.set_pending();
         }
+        if future.as_mut().poll(cx).is_ready() {
+            if let Some(hint) = &self.hint {
+                hint.mark_maybe_ready();
+            }
+            results.extend_from_slice(&self.readylist_indices);
+        }
+    }
+}
 
-        struct PollList<'a> {
-            futures: Vec<(DynFuture<'a>, Vec<ReadylistIndex>)>,
+/// Groups `pollables` by the table index of their pollee.
+pub(crate) fn pollees(
+    table: &ResourceTable,
+    pollables: &[Resource<DynPollable>],
+) -> Result<BTreeMap<u32, Pollee>> {
+    let mut pollees: BTreeMap<u32, Pollee> = BTreeMap::new();
+
+    for (ix, p) in pollables.iter().enumerate() {
+        let ix: u32 = ix.try_into()?;
+
+        let pollable = table.get(p)?;
+        let pollee = pollees.entry(pollable.index).or_insert_with(|| Pollee {
+            make_future: pollable.make_future,
+            make_owned_future: pollable.make_owned_future,
+            hint: pollable.last_known_pending.clone(),
+            debounce: pollable.debounce.clone(),
+            readylist_indices: Vec::new(),
+        });
+        // The pollee is only debounced if all of its pollables share the
+        // same debouncing, since any other pollable wakes the guest anyway.
+        if !Option::zip(pollee.debounce.as_ref(), pollable.debounce.as_ref())
+            .is_some_and(|(a, b)| Arc::ptr_eq(a, b))
+        {
+            pollee.debounce = None;
         }
-        impl<'a> Future for PollList<'a> {
-            type Output = Vec<u32>;
+        pollee.readylist_indices.push(ix);
+    }
+    Ok(pollees)
+}
 
-            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-                let mut any_ready = false;
-                let mut results = Vec::new();
-                for (fut, readylist_indicies) in self.futures.iter_mut() {
-                    match fut.as_mut().poll(cx) {
-                        Poll::Ready(()) => {
-                            results.extend_from_slice(readylist_indicies);
-                            any_ready = true;
-                        }
-                        Poll::Pending => {}
-                    }
-                }
-                if any_ready {
-                    Poll::Ready(results)
-                } else {
-                    Poll::Pending
-                }
-            }
+/// Creates the readiness futures of `pollees`.
+pub(crate) fn pollee_futures(
+    table: &mut ResourceTable,
+    pollees: BTreeMap<u32, Pollee>,
+) -> Result<Vec<(DynFuture<'_>, Pollee)>> {
+    let indices: Vec<u32> = pollees.keys().copied().collect();
+    with_entries(table, pollees, |entries| {
+        indices
+            .into_iter()
+            .zip(entries)
+            .map(|(index, (entry, pollee))| {
+                let future =
+                    pollee_future(index, entry, pollee.make_future, pollee.debounce.clone())?;
+                Ok((future, pollee))
+            })
+            .collect()
+    })
+}
+
+/// Creates the readiness futures of those of `pollees` which provide one
+/// that doesn't borrow them, see [`Pollable::ready_owned`].
+///
+/// [`Pollable::ready_owned`]: crate::poll::Pollable::ready_owned
+pub(crate) fn owned_pollee_futures(
+    table: &mut ResourceTable,
+    pollees: &BTreeMap<u32, Pollee>,
+) -> BTreeMap<u32, DynFuture<'static>> {
+    pollees
+        .iter()
+        .filter_map(|(index, pollee)| {
+            let future = owned_pollee_future(
+                table,
+                *index,
+                pollee.make_owned_future,
+                pollee.debounce.clone(),
+            )?;
+            Some((*index, future))
+        })
+        .collect()
+}
+
+/// Creates the readiness future of the pollee at `index` of the table which
+/// doesn't borrow it, if it provides one, debounced by `debounce` if given.
+///
+/// Pollees which are no longer present don't provide one, so
+/// [`pollee_future`] reports them as ready.
+pub(crate) fn owned_pollee_future(
+    table: &mut ResourceTable,
+    index: u32,
+    make_owned_future: MakeOwnedFuture,
+    debounce: Option<Arc<DebouncedPollable>>,
+) -> Option<DynFuture<'static>> {
+    let ready = make_owned_future(table.get_any_mut(index).ok()?)?;
+    Some(match debounce {
+        Some(debounce) => debounce.debounce(ready),
+        None => ready,
+    })
+}
+
+pub(crate) struct PollList<'a> {
+    pub(crate) futures: Vec<(DynFuture<'a>, Pollee)>,
+}
+
+impl PollList<'_> {
+    /// Polls every future once, returning the readylist indices of those
+    /// which are ready.
+    async fn poll_once(&mut self) -> Vec<u32> {
+        core::future::poll_fn(|cx| Poll::Ready(self.poll_futures(cx))).await
+    }
+
+    pub(crate) fn poll_futures(&mut self, cx: &mut Context<'_>) -> Vec<u32> {
+        let mut results = Vec::new();
+        for (fut, pollee) in self.futures.iter_mut() {
+            pollee.poll_readiness(fut, cx, &mut results);
         }
+        results
+    }
+}
 
-        Ok(PollList { futures }.await)
+impl<'a> Future for PollList<'a> {
+    type Output = Vec<u32>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let results = self.poll_futures(cx);
+        if results.is_empty() {
+            Poll::Pending
+        } else {
+            Poll::Ready(results)
+        }
     }
 }
 
-impl crate::bindings::wasi::io::poll::HostPollable for ResourceTable {
+/// Creates the future for the readiness of the pollee at `index` of the
+/// table.
+///
+/// If the pollee is no longer present, for example because host code deleted
+/// it while the guest still held a pollable for it, then the pollable is
+/// considered ready so the guest goes on to observe that the resource is
+/// gone, e.g. a `closed` stream, instead of trapping.
+///
+/// The readiness is debounced by `debounce`, if given.
+pub(crate) fn pollee_future<'a>(
+    index: u32,
+    entry: Result<&'a mut dyn Any, ResourceTableError>,
+    make_future: MakeFuture,
+    debounce: Option<Arc<DebouncedPollable>>,
+) -> Result<DynFuture<'a>> {
+    let ready = match entry {
+        Ok(entry) => make_future(entry),
+        Err(ResourceTableError::NotPresent) => {
+            log::debug!("pollable subscribed to missing resource {index} is ready");
+            Box::pin(async {})
+        }
+        Err(e) => return Err(e.into()),
+    };
+    Ok(match debounce {
+        Some(debounce) => debounce.debounce(ready),
+        None => ready,
+    })
+}
+
+impl poll::HostPollable for ResourceTable {
     async fn block(&mut self, pollable: Resource<DynPollable>) -> Result<()> {
         let pollable = self.get(&pollable)?;
-        let ready = (pollable.make_future)(self.get_any_mut(pollable.index)?);
+        let (index, make_future) = (pollable.index, pollable.make_future);
+        let debounce = pollable.debounce.clone();
+        let ready = pollee_future(index, self.get_any_mut(index), make_future, debounce)?;
         ready.await;
         Ok(())
     }
     async fn ready(&mut self, pollable: Resource<DynPollable>) -> Result<bool> {
         let pollable = self.get(&pollable)?;
-        let ready = (pollable.make_future)(self.get_any_mut(pollable.index)?);
+        let (index, make_future) = (pollable.index, pollable.make_future);
+        let debounce = pollable.debounce.clone();
+        let ready = pollee_future(index, self.get_any_mut(index), make_future, debounce)?;
         futures::pin_mut!(ready);
         Ok(matches!(
             futures::future::poll_immediate(ready).await,
@@ -83,9 +273,34 @@ impl crate::bindings::wasi::io::poll::HostPollable for ResourceTable {
         ))
     }
     fn drop(&mut self, pollable: Resource<DynPollable>) -> Result<()> {
-        let pollable = self.delete(pollable)?;
+        // Pollables whose stream was deleted by `delete_parent` are already
+        // gone.
+        let Some(pollable) = delete_child(self, pollable)? else {
+            return Ok(());
+        };
         if let Some(delete) = pollable.remove_index_on_delete {
-            delete(self, pollable.index)?;
+            // A pollee owned by siblings created with `DynPollable::duplicate`
+            // is deleted along with the last of them.
+            let shared = pollable
+                .owners
+                .is_some_and(|o| Arc::into_inner(o).is_none());
+            if shared {
+                return Ok(());
+            }
+            // An owned pollee which is already gone was deleted by host code,
+            // see `pollee_future`, so there's nothing left to clean up. Any
+            // other failure to delete it is still a bug.
+            if let Err(e) = delete(self, pollable.index) {
+                match e.downcast_ref::<ResourceTableError>() {
+                    Some(ResourceTableError::NotPresent) => {
+                        log::debug!(
+                            "pollee {} of dropped pollable was already deleted",
+                            pollable.index
+                        );
+                    }
+                    _ => return Err(e),
+                }
+            }
         }
         Ok(())
     }
@@ -97,10 +312,12 @@ impl streams::Host for ResourceTable {
     fn convert_stream_error(&mut self, err: StreamError) -> Result<streams::StreamError> {
         match err {
             StreamError::Closed => Ok(streams::StreamError::Closed),
+            // The error is stored as is, so an `IoError` behind it can still
+            // be found by `error-code.code`.
             StreamError::LastOperationFailed(e) => {
                 Ok(streams::StreamError::LastOperationFailed(self.push(e)?))
             }
-            StreamError::Trap(e) => Err(e),
+            StreamError::Trap(e) => Err(TrapOrigin::attach(e)),
         }
     }
 }
@@ -112,7 +329,17 @@ impl error::HostError for ResourceTable {
     }
 
     fn to_debug_string(&mut self, err: Resource<streams::Error>) -> Result<String> {
-        Ok(alloc::format!("{:?}", self.get(&err)?))
+        let err = self.get(&err)?;
+        if let Some(err) = err.downcast_ref::<IoError>() {
+            return Ok(alloc::format!("{err}"));
+        }
+        Ok(alloc::format!("{err:?}"))
+    }
+}
+
+impl error_code::Host for ResourceTable {
+    fn code(&mut self, err: Resource<streams::Error>) -> Result<Option<u32>> {
+        Ok(IoError::code_of(self.get(&err)?).map(|code| code.0))
     }
 }
 
@@ -123,11 +350,13 @@ impl streams::HostOutputStream for ResourceTable {
     }
 
     fn check_write(&mut self, stream: Resource<DynOutputStream>) -> StreamResult<u64> {
-        let bytes = self.get_mut(&stream)?.check_write()?;
-        Ok(bytes as u64)
+        check_write(self, stream, &DEFAULT_OPTIONS)
     }
 
     fn write(&mut self, stream: Resource<DynOutputStream>, bytes: Vec<u8>) -> StreamResult<()> {
+        if bytes.is_empty() {
+            return check_output_open(self, &stream);
+        }
         self.get_mut(&stream)?.write(bytes.into())?;
         Ok(())
     }
@@ -141,15 +370,18 @@ impl streams::HostOutputStream for ResourceTable {
         stream: Resource<DynOutputStream>,
         bytes: Vec<u8>,
     ) -> StreamResult<()> {
-        if bytes.len() > 4096 {
-            return Err(StreamError::trap(
-                "Buffer too large for blocking-write-and-flush (expected at most 4096)",
-            ));
+        let s = self.get_mut(&stream)?;
+        if bytes.is_empty() {
+            return blocking_flush(s).await;
+        }
+        let limit = s.blocking_write_limit();
+        if bytes.len() > limit {
+            return Err(StreamError::guest_trap(&alloc::format!(
+                "Buffer too large for blocking-write-and-flush (expected at most {limit})"
+            )));
         }
 
-        self.get_mut(&stream)?
-            .blocking_write_and_flush(bytes.into())
-            .await
+        blocking_write_and_flush_chunked(s, bytes.into()).await
     }
 
     async fn blocking_write_zeroes_and_flush(
@@ -157,20 +389,22 @@ impl streams::HostOutputStream for ResourceTable {
         stream: Resource<DynOutputStream>,
         len: u64,
     ) -> StreamResult<()> {
-        if len > 4096 {
-            return Err(StreamError::trap(
-                "Buffer too large for blocking-write-zeroes-and-flush (expected at most 4096)",
-            ));
+        let s = self.get_mut(&stream)?;
+        if len == 0 {
+            return blocking_flush(s).await;
+        }
+        let limit = s.blocking_write_limit();
+        if len > limit as u64 {
+            return Err(StreamError::guest_trap(&alloc::format!(
+                "Buffer too large for blocking-write-zeroes-and-flush (expected at most {limit})"
+            )));
         }
 
-        self.get_mut(&stream)?
-            .blocking_write_zeroes_and_flush(len as usize)
-            .await
+        blocking_write_zeroes_and_flush_chunked(s, len as usize).await
     }
 
     fn write_zeroes(&mut self, stream: Resource<DynOutputStream>, len: u64) -> StreamResult<()> {
-        self.get_mut(&stream)?.write_zeroes(len as usize)?;
-        Ok(())
+        write_zeroes(self, stream, len, &DEFAULT_OPTIONS)
     }
 
     fn flush(&mut self, stream: Resource<DynOutputStream>) -> StreamResult<()> {
@@ -179,10 +413,7 @@ impl streams::HostOutputStream for ResourceTable {
     }
 
     async fn blocking_flush(&mut self, stream: Resource<DynOutputStream>) -> StreamResult<()> {
-        let s = self.get_mut(&stream)?;
-        s.flush()?;
-        s.write_ready().await?;
-        Ok(())
+        blocking_flush(self.get_mut(&stream)?).await
     }
 
     fn splice(
@@ -191,27 +422,7 @@ impl streams::HostOutputStream for ResourceTable {
         src: Resource<DynInputStream>,
         len: u64,
     ) -> StreamResult<u64> {
-        let len = len.try_into().unwrap_or(usize::MAX);
-
-        let permit = {
-            let output = self.get_mut(&dest)?;
-            output.check_write()?
-        };
-        let len = len.min(permit);
-        if len == 0 {
-            return Ok(0);
-        }
-
-        let contents = self.get_mut(&src)?.read(len)?;
-
-        let len = contents.len();
-        if len == 0 {
-            return Ok(0);
-        }
-
-        let output = self.get_mut(&dest)?;
-        output.write(contents)?;
-        Ok(len.try_into().expect("usize can fit in u64"))
+        splice(self, dest, src, len, &DEFAULT_OPTIONS)
     }
 
     async fn blocking_splice(
@@ -220,27 +431,7 @@ impl streams::HostOutputStream for ResourceTable {
         src: Resource<DynInputStream>,
         len: u64,
     ) -> StreamResult<u64> {
-        let len = len.try_into().unwrap_or(usize::MAX);
-
-        let permit = {
-            let output = self.get_mut(&dest)?;
-            output.write_ready().await?
-        };
-        let len = len.min(permit);
-        if len == 0 {
-            return Ok(0);
-        }
-
-        let contents = self.get_mut(&src)?.blocking_read(len).await?;
-
-        let len = contents.len();
-        if len == 0 {
-            return Ok(0);
-        }
-
-        let output = self.get_mut(&dest)?;
-        output.blocking_write_and_flush(contents).await?;
-        Ok(len.try_into().expect("usize can fit in u64"))
+        blocking_splice(self, dest, src, len, &DEFAULT_OPTIONS).await
     }
 }
 
@@ -251,10 +442,7 @@ impl streams::HostInputStream for ResourceTable {
     }
 
     fn read(&mut self, stream: Resource<DynInputStream>, len: u64) -> StreamResult<Vec<u8>> {
-        let len = len.try_into().unwrap_or(usize::MAX);
-        let bytes = self.get_mut(&stream)?.read(len)?;
-        debug_assert!(bytes.len() <= len);
-        Ok(bytes.into())
+        read(self, stream, len, &DEFAULT_OPTIONS)
     }
 
     async fn blocking_read(
@@ -262,16 +450,11 @@ impl streams::HostInputStream for ResourceTable {
         stream: Resource<DynInputStream>,
         len: u64,
     ) -> StreamResult<Vec<u8>> {
-        let len = len.try_into().unwrap_or(usize::MAX);
-        let bytes = self.get_mut(&stream)?.blocking_read(len).await?;
-        debug_assert!(bytes.len() <= len);
-        Ok(bytes.into())
+        blocking_read(self, stream, len, &DEFAULT_OPTIONS).await
     }
 
     fn skip(&mut self, stream: Resource<DynInputStream>, len: u64) -> StreamResult<u64> {
-        let len = len.try_into().unwrap_or(usize::MAX);
-        let written = self.get_mut(&stream)?.skip(len)?;
-        Ok(written.try_into().expect("usize always fits in u64"))
+        skip(self, stream, len, &DEFAULT_OPTIONS)
     }
 
     async fn blocking_skip(
@@ -279,12 +462,1683 @@ impl streams::HostInputStream for ResourceTable {
         stream: Resource<DynInputStream>,
         len: u64,
     ) -> StreamResult<u64> {
-        let len = len.try_into().unwrap_or(usize::MAX);
-        let written = self.get_mut(&stream)?.blocking_skip(len).await?;
-        Ok(written.try_into().expect("usize always fits in u64"))
+        blocking_skip(self, stream, len, &DEFAULT_OPTIONS).await
     }
 
     fn subscribe(&mut self, stream: Resource<DynInputStream>) -> Result<Resource<DynPollable>> {
         crate::poll::subscribe(self, stream)
     }
 }
+
+impl streams_metadata::Host for ResourceTable {
+    fn get_metadata(&mut self, stream: Resource<DynInputStream>) -> Result<Vec<(String, String)>> {
+        let metadata = self.get(&stream)?.metadata();
+        Ok(metadata
+            .into_iter()
+            .flatten()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect())
+    }
+}
+
+impl streams_preferred_read_size::Host for ResourceTable {
+    fn preferred_read_size(&mut self, stream: Resource<DynInputStream>) -> Result<Option<u64>> {
+        let size = self.get(&stream)?.preferred_read_size();
+        Ok(size.map(|size| size as u64))
+    }
+}
+
+impl streams_subscribe_batch::Host for ResourceTable {
+    fn subscribe_batch(
+        &mut self,
+        streams: Vec<Resource<DynInputStream>>,
+    ) -> Result<Vec<Resource<DynPollable>>> {
+        subscribe_batch(self, streams)
+    }
+}
+
+// The operations which take lengths from the guest, shared by the
+// implementations for `ResourceTable` and `IoImpl`. Lengths are clamped with
+// `IoLinkOptions::clamp_len` before they reach a stream.
+//
+// Operations of zero bytes never read from or write to a stream: once the
+// guest's handles have been checked they return an empty result straight
+// away, so streams needn't handle zero lengths. As `wasi:io/streams`
+// requires, they still fail with `closed` if a stream they name is closed,
+// which is learned from `InputStream::is_closed` for input streams and by
+// probing `OutputStream::check_write` for output streams.
+// `blocking-write-and-flush` and `blocking-write-zeroes-and-flush` of zero
+// bytes still flush, as `blocking-flush` does.
+
+/// Completes an operation of zero bytes on `stream`, failing with `closed`
+/// if the stream is closed.
+fn check_input_open(table: &ResourceTable, stream: &Resource<DynInputStream>) -> StreamResult<()> {
+    if table.get(stream)?.is_closed() {
+        return Err(StreamError::Closed);
+    }
+    Ok(())
+}
+
+/// Like [`check_input_open`], for output streams.
+fn check_output_open(
+    table: &mut ResourceTable,
+    stream: &Resource<DynOutputStream>,
+) -> StreamResult<()> {
+    table.get_mut(stream)?.check_write()?;
+    Ok(())
+}
+
+fn check_write(
+    table: &mut ResourceTable,
+    stream: Resource<DynOutputStream>,
+    options: &IoLinkOptions,
+) -> StreamResult<u64> {
+    let bytes = table.get_mut(&stream)?.check_write()?;
+    Ok(bytes.min(options.max_len) as u64)
+}
+
+/// Flushes `stream` and waits for the flush to complete.
+async fn blocking_flush(stream: &mut DynOutputStream) -> StreamResult<()> {
+    stream.flush()?;
+    stream.write_ready().await?;
+    Ok(())
+}
+
+/// Writes `bytes` to `stream` and flushes it for `blocking-write-and-flush`,
+/// in chunks no larger than the permit the stream most recently granted.
+///
+/// Each chunk is passed to [`OutputStream::blocking_write_and_flush`] once
+/// the stream is ready for it, so that streams never receive more bytes than
+/// they've advertised, however large the guest's buffer. Should the stream
+/// close or fail after some chunks have been written, the error is returned
+/// rather than reporting success for part of the buffer.
+///
+/// [`OutputStream::blocking_write_and_flush`]: crate::streams::OutputStream::blocking_write_and_flush
+async fn blocking_write_and_flush_chunked(
+    stream: &mut DynOutputStream,
+    mut bytes: Bytes,
+) -> StreamResult<()> {
+    loop {
+        let permit = stream.write_ready().await?;
+        let chunk = bytes.split_to(bytes.len().min(permit));
+        stream.blocking_write_and_flush(chunk).await?;
+        if bytes.is_empty() {
+            return Ok(());
+        }
+    }
+}
+
+/// Like [`blocking_write_and_flush_chunked`], for
+/// `blocking-write-zeroes-and-flush`.
+async fn blocking_write_zeroes_and_flush_chunked(
+    stream: &mut DynOutputStream,
+    mut len: usize,
+) -> StreamResult<()> {
+    loop {
+        let chunk = len.min(stream.write_ready().await?);
+        stream.blocking_write_zeroes_and_flush(chunk).await?;
+        len -= chunk;
+        if len == 0 {
+            return Ok(());
+        }
+    }
+}
+
+fn write_zeroes(
+    table: &mut ResourceTable,
+    stream: Resource<DynOutputStream>,
+    len: u64,
+    options: &IoLinkOptions,
+) -> StreamResult<()> {
+    if len == 0 {
+        return check_output_open(table, &stream);
+    }
+    // Permits never exceed the limit, so longer writes are always
+    // permit violations.
+    let clamped = options.clamp_len(len);
+    if clamped as u64 != len {
+        return Err(StreamError::guest_trap("write-zeroes exceeded permit"));
+    }
+    table.get_mut(&stream)?.write_zeroes(clamped)?;
+    Ok(())
+}
+
+/// Offers the splice of `len` bytes from `src` to `dest` to
+/// `OutputStream::splice_from`, returning `None` if `dest` declines it.
+fn splice_fast_path(
+    table: &mut ResourceTable,
+    dest: &Resource<DynOutputStream>,
+    src: &Resource<DynInputStream>,
+    len: usize,
+) -> StreamResult<Option<u64>> {
+    // A stream can't be spliced into itself, which the generic path reports.
+    if dest.rep() == src.rep() {
+        return Ok(None);
+    }
+    let indices = BTreeMap::from([(dest.rep(), true), (src.rep(), false)]);
+    let (mut output, mut input) = (None, None);
+    for (entry, is_dest) in table.iter_entries(indices) {
+        let entry = entry?;
+        if is_dest {
+            output = entry.downcast_mut::<DynOutputStream>();
+        } else {
+            input = entry.downcast_mut::<DynInputStream>();
+        }
+    }
+    let (Some(output), Some(input)) = (output, input) else {
+        return Err(ResourceTableError::WrongType.into());
+    };
+    match output.splice_from(&mut **input, len) {
+        Some(spliced) => {
+            let spliced = spliced?;
+            debug_assert!(spliced <= len);
+            Ok(Some(spliced as u64))
+        }
+        None => Ok(None),
+    }
+}
+
+fn splice(
+    table: &mut ResourceTable,
+    dest: Resource<DynOutputStream>,
+    src: Resource<DynInputStream>,
+    len: u64,
+    options: &IoLinkOptions,
+) -> StreamResult<u64> {
+    if len == 0 {
+        check_output_open(table, &dest)?;
+        check_input_open(table, &src)?;
+        return Ok(0);
+    }
+    let len = options.clamp_len(len);
+    if let Some(spliced) = splice_fast_path(table, &dest, &src, len)? {
+        return Ok(spliced);
+    }
+
+    let permit = {
+        let output = table.get_mut(&dest)?;
+        output.check_write()?
+    };
+    let len = len.min(permit);
+    if len == 0 {
+        return Ok(0);
+    }
+
+    let contents = table.get_mut(&src)?.read(len)?;
+
+    let len = contents.len();
+    if len == 0 {
+        return Ok(0);
+    }
+
+    let output = table.get_mut(&dest)?;
+    output.write(contents)?;
+    Ok(len as u64)
+}
+
+async fn blocking_splice(
+    table: &mut ResourceTable,
+    dest: Resource<DynOutputStream>,
+    src: Resource<DynInputStream>,
+    len: u64,
+    options: &IoLinkOptions,
+) -> StreamResult<u64> {
+    if len == 0 {
+        check_output_open(table, &dest)?;
+        check_input_open(table, &src)?;
+        return Ok(0);
+    }
+    let len = options.clamp_len(len);
+    if let Some(spliced) = splice_fast_path(table, &dest, &src, len)? {
+        return Ok(spliced);
+    }
+
+    let permit = {
+        let output = table.get_mut(&dest)?;
+        output.write_ready().await?
+    };
+    let len = len.min(permit);
+    if len == 0 {
+        return Ok(0);
+    }
+
+    let contents = table.get_mut(&src)?.blocking_read(len).await?;
+
+    let len = contents.len();
+    if len == 0 {
+        return Ok(0);
+    }
+
+    let output = table.get_mut(&dest)?;
+    output.blocking_write_and_flush(contents).await?;
+    Ok(len as u64)
+}
+
+fn read(
+    table: &mut ResourceTable,
+    stream: Resource<DynInputStream>,
+    len: u64,
+    options: &IoLinkOptions,
+) -> StreamResult<Vec<u8>> {
+    if len == 0 {
+        check_input_open(table, &stream)?;
+        return Ok(Vec::new());
+    }
+    let len = options.clamp_len(len);
+    let bytes = table.get_mut(&stream)?.read(len)?;
+    debug_assert!(bytes.len() <= len);
+    Ok(bytes.into())
+}
+
+async fn blocking_read(
+    table: &mut ResourceTable,
+    stream: Resource<DynInputStream>,
+    len: u64,
+    options: &IoLinkOptions,
+) -> StreamResult<Vec<u8>> {
+    if len == 0 {
+        check_input_open(table, &stream)?;
+        return Ok(Vec::new());
+    }
+    let len = options.clamp_len(len);
+    let bytes = table.get_mut(&stream)?.blocking_read(len).await?;
+    debug_assert!(bytes.len() <= len);
+    Ok(bytes.into())
+}
+
+fn skip(
+    table: &mut ResourceTable,
+    stream: Resource<DynInputStream>,
+    len: u64,
+    options: &IoLinkOptions,
+) -> StreamResult<u64> {
+    if len == 0 {
+        check_input_open(table, &stream)?;
+        return Ok(0);
+    }
+    let len = options.clamp_len(len);
+    let skipped = table.get_mut(&stream)?.skip(len)?;
+    Ok(skipped as u64)
+}
+
+async fn blocking_skip(
+    table: &mut ResourceTable,
+    stream: Resource<DynInputStream>,
+    len: u64,
+    options: &IoLinkOptions,
+) -> StreamResult<u64> {
+    if len == 0 {
+        check_input_open(table, &stream)?;
+        return Ok(0);
+    }
+    let len = options.clamp_len(len);
+    let skipped = table.get_mut(&stream)?.blocking_skip(len).await?;
+    Ok(skipped as u64)
+}
+
+// The implementation used by `add_to_linker_async`, which forwards to the
+// implementation for `ResourceTable` above after applying `IoLinkOptions`.
+
+impl IoImpl<'_> {
+    /// Hands the configured `MemoryAccountant`, if any, to `stream` before
+    /// it's written to.
+    fn prepare_write(&mut self, stream: &Resource<DynOutputStream>) -> StreamResult<()> {
+        if let Some(accountant) = &self.options.memory_accountant {
+            self.table
+                .get_mut(stream)?
+                .set_memory_accountant(accountant);
+        }
+        Ok(())
+    }
+
+    /// Wraps `stream` in the streams configured by the options before the
+    /// guest checks or writes to it: one which coalesces writes, and then one
+    /// which checks writes against the permits the guest has been granted.
+    fn wrap_output(&mut self, stream: &Resource<DynOutputStream>) -> StreamResult<()> {
+        let s = self.table.get_mut(stream)?;
+        if let Some(threshold) = self.options.coalesce_writes {
+            crate::coalesce::coalesce(s, threshold);
+        }
+        crate::permits::check_permits(s, self.options);
+        Ok(())
+    }
+
+    /// Reports `stream` to event subscribers if it's new to them, and in
+    /// deterministic mode traps unless it's deterministic.
+    fn check_input(&self, stream: &Resource<DynInputStream>) -> StreamResult<()> {
+        self.stream_used(stream, StreamKind::Input);
+        if self.options.deterministic && !self.table.get(stream)?.is_deterministic() {
+            return Err(deterministic::nondeterministic("input", stream.rep()));
+        }
+        Ok(())
+    }
+
+    /// Reports `spliced` bytes spliced between the `(src, dest)` pair of
+    /// streams `reps` to the configured [`SpliceObserver`](crate::SpliceObserver).
+    fn observe_splice(&mut self, (src, dest): (u32, u32), spliced: u64) {
+        if let Some(tracker) = &self.options.splice_tracker {
+            tracker.spliced(self.table, src, dest, spliced);
+        }
+    }
+
+    /// Forgets the splices of the stream `rep`, which the guest is dropping.
+    fn forget_splices(&self, rep: u32) {
+        if let Some(tracker) = &self.options.splice_tracker {
+            tracker.forget(rep);
+        }
+    }
+
+    /// Reports to event subscribers that the guest used `stream`, which
+    /// reports its creation unless they have already been told about it.
+    fn stream_used<T: 'static>(&self, stream: &Resource<T>, kind: StreamKind) {
+        let events = &self.options.events;
+        if events.is_enabled() && self.table.get(stream).is_ok() {
+            events.stream_created(stream.rep(), kind);
+        }
+    }
+
+    /// Reports to event subscribers that the guest dropped the stream `rep`.
+    fn stream_dropped(&self, rep: u32, kind: StreamKind) {
+        self.options.events.stream_dropped(rep, kind);
+    }
+
+    /// Reports to event subscribers that the pollable `rep` was observed to
+    /// be ready.
+    fn pollable_ready(&self, rep: u32) {
+        self.options.events.publish(|| {
+            let pollee = self
+                .table
+                .get(&Resource::<DynPollable>::new_borrow(rep))
+                .map_or(rep, |pollable| pollable.index);
+            IoEvent::PollableReady {
+                pollable: rep,
+                pollee,
+            }
+        });
+    }
+
+    /// Reports `stream` to event subscribers if it's new to them, and in
+    /// deterministic mode traps unless it's deterministic.
+    fn check_output(&self, stream: &Resource<DynOutputStream>) -> StreamResult<()> {
+        self.stream_used(stream, StreamKind::Output);
+        if self.options.deterministic && !self.table.get(stream)?.is_deterministic() {
+            return Err(deterministic::nondeterministic("output", stream.rep()));
+        }
+        Ok(())
+    }
+}
+
+impl poll::Host for IoImpl<'_> {
+    async fn poll(&mut self, pollables: Vec<Resource<DynPollable>>) -> Result<Vec<u32>> {
+        // The handles are only needed to report which pollables are ready.
+        let reps: Vec<u32> = if self.options.events.is_enabled() {
+            pollables.iter().map(|p| p.rep()).collect()
+        } else {
+            Vec::new()
+        };
+        let op = BlockingOp::Poll {
+            pollables: pollables.len(),
+        };
+        let poll = <ResourceTable as poll::Host>::poll(self.table, pollables);
+        let mut ready = self.options.blocking(op, poll).await?;
+        if self.options.deterministic {
+            ready.sort_unstable();
+        }
+        if !reps.is_empty() {
+            for i in &ready {
+                self.pollable_ready(reps[*i as usize]);
+            }
+        }
+        Ok(ready)
+    }
+}
+
+impl poll::HostPollable for IoImpl<'_> {
+    async fn block(&mut self, pollable: Resource<DynPollable>) -> Result<()> {
+        let rep = pollable.rep();
+        let op = BlockingOp::Block { pollable: rep };
+        let block = <ResourceTable as poll::HostPollable>::block(self.table, pollable);
+        self.options.blocking(op, block).await?;
+        self.pollable_ready(rep);
+        Ok(())
+    }
+    async fn ready(&mut self, pollable: Resource<DynPollable>) -> Result<bool> {
+        <ResourceTable as poll::HostPollable>::ready(self.table, pollable).await
+    }
+    fn drop(&mut self, pollable: Resource<DynPollable>) -> Result<()> {
+        <ResourceTable as poll::HostPollable>::drop(self.table, pollable)
+    }
+}
+
+impl error::Host for IoImpl<'_> {}
+
+impl error::HostError for IoImpl<'_> {
+    fn drop(&mut self, err: Resource<streams::Error>) -> Result<()> {
+        <ResourceTable as error::HostError>::drop(self.table, err)
+    }
+
+    fn to_debug_string(&mut self, err: Resource<streams::Error>) -> Result<String> {
+        if self.options.deterministic {
+            return Ok(deterministic::stable_debug_string(self.table.get(&err)?));
+        }
+        <ResourceTable as error::HostError>::to_debug_string(self.table, err)
+    }
+}
+
+impl error_code::Host for IoImpl<'_> {
+    fn code(&mut self, err: Resource<streams::Error>) -> Result<Option<u32>> {
+        <ResourceTable as error_code::Host>::code(self.table, err)
+    }
+}
+
+impl streams::Host for IoImpl<'_> {
+    fn convert_stream_error(&mut self, err: StreamError) -> Result<streams::StreamError> {
+        let converted = <ResourceTable as streams::Host>::convert_stream_error(self.table, err);
+        self.options.events.publish(|| {
+            IoEvent::ErrorConverted(match &converted {
+                Ok(streams::StreamError::Closed) => ConvertedError::Closed,
+                Ok(streams::StreamError::LastOperationFailed(e)) => {
+                    ConvertedError::LastOperationFailed { error: e.rep() }
+                }
+                Err(_) => ConvertedError::Trap,
+            })
+        });
+        converted
+    }
+}
+
+impl streams::HostOutputStream for IoImpl<'_> {
+    async fn drop(&mut self, stream: Resource<DynOutputStream>) -> Result<()> {
+        self.forget_splices(stream.rep());
+        self.stream_dropped(stream.rep(), StreamKind::Output);
+        let mut stream = self.table.delete(stream)?;
+        self.options
+            .cancel_dropped(Box::pin(async move { stream.cancel().await }))
+            .await;
+        Ok(())
+    }
+
+    fn check_write(&mut self, stream: Resource<DynOutputStream>) -> StreamResult<u64> {
+        self.check_output(&stream)?;
+        self.wrap_output(&stream)?;
+        check_write(self.table, stream, self.options)
+    }
+
+    fn write(&mut self, stream: Resource<DynOutputStream>, bytes: Vec<u8>) -> StreamResult<()> {
+        self.check_output(&stream)?;
+        self.wrap_output(&stream)?;
+        self.prepare_write(&stream)?;
+        <ResourceTable as streams::HostOutputStream>::write(self.table, stream, bytes)
+    }
+
+    fn subscribe(&mut self, stream: Resource<DynOutputStream>) -> Result<Resource<DynPollable>> {
+        self.check_output(&stream)?;
+        <ResourceTable as streams::HostOutputStream>::subscribe(self.table, stream)
+    }
+
+    async fn blocking_write_and_flush(
+        &mut self,
+        stream: Resource<DynOutputStream>,
+        bytes: Vec<u8>,
+    ) -> StreamResult<()> {
+        self.check_output(&stream)?;
+        self.prepare_write(&stream)?;
+        let op = BlockingOp::WriteAndFlush {
+            stream: stream.rep(),
+        };
+        let write = <ResourceTable as streams::HostOutputStream>::blocking_write_and_flush(
+            self.table, stream, bytes,
+        );
+        self.options.blocking(op, write).await
+    }
+
+    async fn blocking_write_zeroes_and_flush(
+        &mut self,
+        stream: Resource<DynOutputStream>,
+        len: u64,
+    ) -> StreamResult<()> {
+        self.check_output(&stream)?;
+        self.prepare_write(&stream)?;
+        let op = BlockingOp::WriteZeroesAndFlush {
+            stream: stream.rep(),
+        };
+        let write = <ResourceTable as streams::HostOutputStream>::blocking_write_zeroes_and_flush(
+            self.table, stream, len,
+        );
+        self.options.blocking(op, write).await
+    }
+
+    fn write_zeroes(&mut self, stream: Resource<DynOutputStream>, len: u64) -> StreamResult<()> {
+        self.check_output(&stream)?;
+        self.wrap_output(&stream)?;
+        self.prepare_write(&stream)?;
+        write_zeroes(self.table, stream, len, self.options)
+    }
+
+    fn flush(&mut self, stream: Resource<DynOutputStream>) -> StreamResult<()> {
+        self.check_output(&stream)?;
+        <ResourceTable as streams::HostOutputStream>::flush(self.table, stream)
+    }
+
+    async fn blocking_flush(&mut self, stream: Resource<DynOutputStream>) -> StreamResult<()> {
+        self.check_output(&stream)?;
+        let op = BlockingOp::Flush {
+            stream: stream.rep(),
+        };
+        let flush =
+            <ResourceTable as streams::HostOutputStream>::blocking_flush(self.table, stream);
+        self.options.blocking(op, flush).await
+    }
+
+    fn splice(
+        &mut self,
+        dest: Resource<DynOutputStream>,
+        src: Resource<DynInputStream>,
+        len: u64,
+    ) -> StreamResult<u64> {
+        self.check_output(&dest)?;
+        self.check_input(&src)?;
+        self.prepare_write(&dest)?;
+        let reps = (src.rep(), dest.rep());
+        let spliced = splice(self.table, dest, src, len, self.options)?;
+        self.observe_splice(reps, spliced);
+        Ok(spliced)
+    }
+
+    async fn blocking_splice(
+        &mut self,
+        dest: Resource<DynOutputStream>,
+        src: Resource<DynInputStream>,
+        len: u64,
+    ) -> StreamResult<u64> {
+        self.check_output(&dest)?;
+        self.check_input(&src)?;
+        self.prepare_write(&dest)?;
+        if len == 0 {
+            return splice(self.table, dest, src, len, self.options);
+        }
+        let reps = (src.rep(), dest.rep());
+        let op = BlockingOp::Splice {
+            src: reps.0,
+            dest: reps.1,
+        };
+        let splice = blocking_splice(self.table, dest, src, len, self.options);
+        let spliced = self.options.blocking(op, splice).await?;
+        self.observe_splice(reps, spliced);
+        Ok(spliced)
+    }
+}
+
+impl streams_metadata::Host for IoImpl<'_> {
+    fn get_metadata(&mut self, stream: Resource<DynInputStream>) -> Result<Vec<(String, String)>> {
+        <ResourceTable as streams_metadata::Host>::get_metadata(self.table, stream)
+    }
+}
+
+impl streams_preferred_read_size::Host for IoImpl<'_> {
+    fn preferred_read_size(&mut self, stream: Resource<DynInputStream>) -> Result<Option<u64>> {
+        // Reads are clamped to `max_len`, so larger sizes can't be honored.
+        let size = self.table.get(&stream)?.preferred_read_size();
+        Ok(size.map(|size| size.min(self.options.max_len) as u64))
+    }
+}
+
+impl streams_subscribe_batch::Host for IoImpl<'_> {
+    fn subscribe_batch(
+        &mut self,
+        streams: Vec<Resource<DynInputStream>>,
+    ) -> Result<Vec<Resource<DynPollable>>> {
+        for stream in &streams {
+            self.check_input(stream)?;
+        }
+        <ResourceTable as streams_subscribe_batch::Host>::subscribe_batch(self.table, streams)
+    }
+}
+
+impl streams_timeout::Host for IoImpl<'_> {
+    async fn blocking_read_timeout(
+        &mut self,
+        stream: Resource<DynInputStream>,
+        len: u64,
+        timeout_ns: u64,
+    ) -> StreamResult<Vec<u8>> {
+        self.stream_used(&stream, StreamKind::Input);
+        let Some(timer) = &self.options.timer else {
+            return Err(StreamError::trap(
+                "blocking-read-timeout requires a timer provider",
+            ));
+        };
+        if self.options.deterministic {
+            return Err(StreamError::trap(
+                "blocking-read-timeout isn't available in deterministic mode",
+            ));
+        }
+        if len == 0 {
+            return read(self.table, stream, len, self.options);
+        }
+        let len = self.options.clamp_len(len);
+        let mut deadline = timer.0.sleep(Duration::from_nanos(timeout_ns));
+        let op = BlockingOp::ReadTimeout {
+            stream: stream.rep(),
+        };
+        let s = self.table.get_mut(&stream)?;
+
+        // This waits the way `InputStream::blocking_read` does by default,
+        // racing only the stream's readiness against the deadline: `read`
+        // itself doesn't wait, so no data is lost when the deadline wins.
+        let read = async move {
+            loop {
+                let timed_out = matches!(
+                    futures::future::select(s.ready(), &mut deadline).await,
+                    Either::Right(_)
+                );
+                if timed_out {
+                    return Ok(Vec::new());
+                }
+                let bytes = s.read(len)?;
+                if !bytes.is_empty() {
+                    return Ok(bytes.into());
+                }
+            }
+        };
+        self.options.blocking(op, read).await
+    }
+}
+
+impl streams_read_vectored::Host for IoImpl<'_> {
+    fn read_vectored(
+        &mut self,
+        stream: Resource<DynInputStream>,
+        lens: Vec<u64>,
+    ) -> StreamResult<Vec<Vec<u8>>> {
+        self.check_input(&stream)?;
+        let lens = self.options.clamp_vectored_lens(&lens);
+        if vectored_total(&lens) == 0 {
+            check_input_open(self.table, &stream)?;
+            return Ok(lens.iter().map(|_| Vec::new()).collect());
+        }
+        let s = self.table.get_mut(&stream)?;
+        let bufs = if self.options.deterministic {
+            let bytes = deterministic::fill(s, vectored_total(&lens), Bytes::new())?;
+            split_vectored(bytes, &lens)
+        } else {
+            s.read_vectored(&lens)?
+        };
+        Ok(vectored_result(bufs, &lens))
+    }
+
+    async fn blocking_read_vectored(
+        &mut self,
+        stream: Resource<DynInputStream>,
+        lens: Vec<u64>,
+    ) -> StreamResult<Vec<Vec<u8>>> {
+        self.check_input(&stream)?;
+        let lens = self.options.clamp_vectored_lens(&lens);
+        if vectored_total(&lens) == 0 {
+            check_input_open(self.table, &stream)?;
+            return Ok(lens.iter().map(|_| Vec::new()).collect());
+        }
+        let is_deterministic = self.options.deterministic;
+        let op = BlockingOp::ReadVectored {
+            stream: stream.rep(),
+        };
+        let s = self.table.get_mut(&stream)?;
+        let read = async {
+            if !is_deterministic {
+                return s.blocking_read_vectored(&lens).await;
+            }
+            let total = vectored_total(&lens);
+            let first = s.blocking_read(total).await?;
+            Ok(split_vectored(deterministic::fill(s, total, first)?, &lens))
+        };
+        let bufs = self.options.blocking(op, read).await?;
+        Ok(vectored_result(bufs, &lens))
+    }
+}
+
+/// Converts the buffers returned by a vectored read of `lens` to the lists
+/// returned to the guest.
+fn vectored_result(bufs: Vec<Bytes>, lens: &[usize]) -> Vec<Vec<u8>> {
+    debug_assert_eq!(bufs.len(), lens.len());
+    debug_assert!(bufs.iter().zip(lens).all(|(buf, len)| buf.len() <= *len));
+    bufs.into_iter().map(Vec::from).collect()
+}
+
+impl streams::HostInputStream for IoImpl<'_> {
+    async fn drop(&mut self, stream: Resource<DynInputStream>) -> Result<()> {
+        self.forget_splices(stream.rep());
+        self.stream_dropped(stream.rep(), StreamKind::Input);
+        let mut stream = self.table.delete(stream)?;
+        self.options
+            .cancel_dropped(Box::pin(async move { stream.cancel().await }))
+            .await;
+        Ok(())
+    }
+
+    fn read(&mut self, stream: Resource<DynInputStream>, len: u64) -> StreamResult<Vec<u8>> {
+        self.check_input(&stream)?;
+        if !self.options.deterministic || len == 0 {
+            return read(self.table, stream, len, self.options);
+        }
+        let len = self.options.clamp_len(len);
+        let bytes = deterministic::fill(self.table.get_mut(&stream)?, len, Bytes::new())?;
+        Ok(bytes.into())
+    }
+
+    async fn blocking_read(
+        &mut self,
+        stream: Resource<DynInputStream>,
+        len: u64,
+    ) -> StreamResult<Vec<u8>> {
+        self.check_input(&stream)?;
+        // Operations of zero bytes don't wait, so aren't reported as blocking.
+        if len == 0 {
+            return read(self.table, stream, len, self.options);
+        }
+        let op = BlockingOp::Read {
+            stream: stream.rep(),
+        };
+        let (table, options) = (&mut *self.table, self.options);
+        let read = async move {
+            if !options.deterministic {
+                return blocking_read(table, stream, len, options).await;
+            }
+            let len = options.clamp_len(len);
+            let s = table.get_mut(&stream)?;
+            let first = s.blocking_read(len).await?;
+            Ok(deterministic::fill(s, len, first)?.into())
+        };
+        options.blocking(op, read).await
+    }
+
+    fn skip(&mut self, stream: Resource<DynInputStream>, len: u64) -> StreamResult<u64> {
+        self.check_input(&stream)?;
+        skip(self.table, stream, len, self.options)
+    }
+
+    async fn blocking_skip(
+        &mut self,
+        stream: Resource<DynInputStream>,
+        len: u64,
+    ) -> StreamResult<u64> {
+        self.check_input(&stream)?;
+        if len == 0 {
+            return skip(self.table, stream, len, self.options);
+        }
+        let op = BlockingOp::Skip {
+            stream: stream.rep(),
+        };
+        let skip = blocking_skip(self.table, stream, len, self.options);
+        self.options.blocking(op, skip).await
+    }
+
+    fn subscribe(&mut self, stream: Resource<DynInputStream>) -> Result<Resource<DynPollable>> {
+        self.check_input(&stream)?;
+        <ResourceTable as streams::HostInputStream>::subscribe(self.table, stream)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::child::remove_index;
+    use crate::executor::{WakeSignal, block_on};
+    use crate::poll::{Notifier, Pollable, describe_pollee, make_future, make_owned_future};
+    use crate::streams::{InputStream, OutputStream};
+    use crate::test_streams::Stalled;
+    use crate::time::MockTimerProvider;
+    use crate::{DropPolicy, Spawn};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use poll::{Host as _, HostPollable as _};
+    use std::sync::Mutex;
+
+    /// Creates a pending stream and a pollable for it which, unlike those
+    /// created by `subscribe`, isn't a child of the stream, as some host code
+    /// might do.
+    fn detached_pollable(
+        table: &mut ResourceTable,
+    ) -> (Resource<DynInputStream>, Resource<DynPollable>) {
+        let stream = table
+            .push(Box::new(Stalled::default()) as DynInputStream)
+            .unwrap();
+        let pollable = table
+            .push(DynPollable {
+                index: stream.rep(),
+                make_future: make_future::<DynInputStream>,
+                make_owned_future: make_owned_future::<DynInputStream>,
+                remove_index_on_delete: Some(remove_index::<DynInputStream>),
+                last_known_pending: None,
+                owners: None,
+                debounce: None,
+                describe: describe_pollee::<DynInputStream>,
+            })
+            .unwrap();
+        (stream, pollable)
+    }
+
+    fn borrow<T: 'static>(r: &Resource<T>) -> Resource<T> {
+        Resource::new_borrow(r.rep())
+    }
+
+    #[test]
+    fn deleted_pollee_is_ready() -> Result<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let mut table = ResourceTable::new();
+        let (pending_stream, pending) = detached_pollable(&mut table);
+        let (stream, pollable) = detached_pollable(&mut table);
+        assert!(!block_on(&SIGNAL, |_| {}, table.ready(borrow(&pollable)))?);
+
+        table.delete(stream)?;
+        let ready = block_on(
+            &SIGNAL,
+            |_| {},
+            table.poll(vec![borrow(&pending), borrow(&pollable)]),
+        )?;
+        assert_eq!(ready, [1]);
+        assert!(block_on(&SIGNAL, |_| {}, table.ready(borrow(&pollable)))?);
+        block_on(&SIGNAL, |_| {}, table.block(borrow(&pollable)))?;
+
+        // Dropping the pollable doesn't fail even though its pollee is gone,
+        // and the pending one still cleans up its stream.
+        poll::HostPollable::drop(&mut table, pollable)?;
+        poll::HostPollable::drop(&mut table, pending)?;
+        assert!(table.get(&pending_stream).is_err());
+        Ok(())
+    }
+
+    /// A pollee which counts how often it's been dropped.
+    struct Dropped(Arc<AtomicUsize>);
+
+    #[async_trait::async_trait]
+    impl Pollable for Dropped {
+        async fn ready(&mut self) {}
+    }
+
+    impl Drop for Dropped {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn duplicates_share_their_pollee() -> Result<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        for reverse in [false, true] {
+            let mut table = ResourceTable::new();
+            let drops = Arc::new(AtomicUsize::new(0));
+            let pollee = table.push(Dropped(drops.clone()))?;
+            let pollee_rep = pollee.rep();
+            let original = subscribe(&mut table, pollee)?;
+            let sibling = DynPollable::duplicate(&mut table, &original)?;
+
+            // Both refer to the same readiness source.
+            let ready = block_on(
+                &SIGNAL,
+                |_| {},
+                table.poll(vec![borrow(&original), borrow(&sibling)]),
+            )?;
+            assert_eq!(ready, [0, 1]);
+
+            let (first, second) = if reverse {
+                (sibling, original)
+            } else {
+                (original, sibling)
+            };
+            poll::HostPollable::drop(&mut table, first)?;
+            assert_eq!(drops.load(Ordering::SeqCst), 0);
+            assert!(table.get_any_mut(pollee_rep).is_ok());
+
+            poll::HostPollable::drop(&mut table, second)?;
+            assert_eq!(drops.load(Ordering::SeqCst), 1);
+            assert!(table.get_any_mut(pollee_rep).is_err());
+        }
+        Ok(())
+    }
+
+    /// A pollee with a pending hint which counts how often its readiness is
+    /// checked, and which doesn't clear its hint when made ready.
+    struct Hinted {
+        hint: PendingHint,
+        ready: bool,
+        checks: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl Pollable for Hinted {
+        async fn ready(&mut self) {
+            self.checks.fetch_add(1, Ordering::SeqCst);
+            if !self.ready {
+                core::future::pending().await
+            }
+        }
+
+        fn pending_hint(&self) -> Option<&PendingHint> {
+            Some(&self.hint)
+        }
+    }
+
+    #[test]
+    fn hinted_pollees_are_checked_last() -> Result<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let mut table = ResourceTable::new();
+        let checks = Arc::new(AtomicUsize::new(0));
+        let hinted = table.push(Hinted {
+            hint: PendingHint::new(),
+            ready: false,
+            checks: checks.clone(),
+        })?;
+        let idle = subscribe(&mut table, borrow(&hinted))?;
+        let notifier = Notifier::new();
+        let active = notifier.pollable(&mut table)?;
+        notifier.notify_waiters();
+
+        // Nothing is hinted yet, so both pollees are checked.
+        let list = || vec![borrow(&idle), borrow(&active)];
+        assert_eq!(block_on(&SIGNAL, |_| {}, table.poll(list()))?, [1]);
+        assert_eq!(checks.load(Ordering::SeqCst), 1);
+
+        // The idle pollee was pending, so it isn't checked while the other
+        // one is ready.
+        assert_eq!(block_on(&SIGNAL, |_| {}, table.poll(list()))?, [1]);
+        assert_eq!(checks.load(Ordering::SeqCst), 1);
+
+        // A stale hint only delays observing readiness until nothing else
+        // is ready.
+        table.get_mut(&hinted)?.ready = true;
+        notifier.reset();
+        assert_eq!(block_on(&SIGNAL, |_| {}, table.poll(list()))?, [0]);
+        assert_eq!(checks.load(Ordering::SeqCst), 2);
+
+        // Once the hint is cleared the pollee is checked with the others.
+        notifier.notify_waiters();
+        table.get(&hinted)?.mark_maybe_ready();
+        assert_eq!(block_on(&SIGNAL, |_| {}, table.poll(list()))?, [0, 1]);
+        assert_eq!(checks.load(Ordering::SeqCst), 3);
+        Ok(())
+    }
+
+    /// An output stream whose cancellation waits for `gate` to be notified.
+    struct SlowCancel {
+        gate: Notifier,
+        cancelled: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl Pollable for SlowCancel {
+        async fn ready(&mut self) {}
+    }
+
+    #[async_trait::async_trait]
+    impl OutputStream for SlowCancel {
+        fn write(&mut self, _bytes: Bytes) -> StreamResult<()> {
+            Ok(())
+        }
+        fn flush(&mut self) -> StreamResult<()> {
+            Ok(())
+        }
+        fn check_write(&mut self) -> StreamResult<usize> {
+            Ok(0)
+        }
+        async fn cancel(&mut self) {
+            self.gate.ready().await;
+            self.cancelled.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// A spawner which holds on to futures until the test runs them.
+    #[derive(Clone, Default)]
+    struct Deferred(Arc<Mutex<Vec<DynFuture<'static>>>>);
+
+    impl Spawn for Deferred {
+        fn spawn(&self, future: DynFuture<'static>) {
+            self.0.lock().unwrap().push(future);
+        }
+    }
+
+    impl Deferred {
+        fn run_all(&self) {
+            static SIGNAL: WakeSignal = WakeSignal::new();
+            let futures = core::mem::take(&mut *self.0.lock().unwrap());
+            for future in futures {
+                block_on(&SIGNAL, |_| {}, future);
+            }
+        }
+    }
+
+    fn slow_streams(
+        table: &mut ResourceTable,
+        gate: &Notifier,
+        cancelled: &Arc<AtomicUsize>,
+    ) -> Vec<Resource<DynOutputStream>> {
+        (0..100)
+            .map(|_| {
+                let stream: DynOutputStream = Box::new(SlowCancel {
+                    gate: gate.clone(),
+                    cancelled: cancelled.clone(),
+                });
+                table.push(stream).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detached_drops_return_immediately() -> Result<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let spawner = Deferred::default();
+        let mut options = IoLinkOptions::new();
+        options
+            .drop_policy(DropPolicy::Detach)
+            .spawner(spawner.clone());
+        let mut table = ResourceTable::new();
+        let gate = Notifier::new();
+        let cancelled = Arc::new(AtomicUsize::new(0));
+        let handles = slow_streams(&mut table, &gate, &cancelled);
+        let mut io = IoImpl::new(&mut table, &options);
+
+        for stream in handles {
+            let drop = streams::HostOutputStream::drop(&mut io, stream);
+            block_on(&SIGNAL, |_| panic!("drop waited for cancel"), drop)?;
+        }
+        assert_eq!(cancelled.load(Ordering::SeqCst), 0);
+
+        gate.notify_waiters();
+        spawner.run_all();
+        assert_eq!(cancelled.load(Ordering::SeqCst), 100);
+        Ok(())
+    }
+
+    #[test]
+    fn bounded_detached_drops() -> Result<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let spawner = Deferred::default();
+        let mut options = IoLinkOptions::new();
+        options
+            .drop_policy(DropPolicy::DetachBounded(10))
+            .spawner(spawner.clone());
+        let mut table = ResourceTable::new();
+        let gate = Notifier::new();
+        let cancelled = Arc::new(AtomicUsize::new(0));
+        let handles = slow_streams(&mut table, &gate, &cancelled);
+        let mut io = IoImpl::new(&mut table, &options);
+
+        // Once 10 cancellations are in flight the rest are awaited, which
+        // needs the gate to open.
+        let mut waits = 0;
+        for stream in handles {
+            let drop = streams::HostOutputStream::drop(&mut io, stream);
+            block_on(
+                &SIGNAL,
+                |_| {
+                    waits += 1;
+                    gate.notify_waiters();
+                },
+                drop,
+            )?;
+            gate.reset();
+        }
+        assert_eq!(waits, 90);
+        assert_eq!(cancelled.load(Ordering::SeqCst), 90);
+        assert_eq!(spawner.0.lock().unwrap().len(), 10);
+
+        // Completed cancellations free up their slots.
+        gate.notify_waiters();
+        spawner.run_all();
+        assert_eq!(cancelled.load(Ordering::SeqCst), 100);
+        let stream = slow_streams(io.table, &gate, &cancelled).remove(0);
+        gate.reset();
+        let drop = streams::HostOutputStream::drop(&mut io, stream);
+        block_on(&SIGNAL, |_| panic!("drop waited for cancel"), drop)?;
+        assert_eq!(spawner.0.lock().unwrap().len(), 1);
+        Ok(())
+    }
+
+    /// An input stream which produces `result` once `gate` is notified.
+    struct Gated {
+        gate: Notifier,
+        result: Option<StreamResult<Bytes>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Pollable for Gated {
+        async fn ready(&mut self) {
+            self.gate.ready().await
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl InputStream for Gated {
+        fn read(&mut self, _size: usize) -> StreamResult<Bytes> {
+            if !self.gate.is_notified() {
+                return Ok(Bytes::new());
+            }
+            self.result.take().unwrap_or(Err(StreamError::Closed))
+        }
+    }
+
+    /// Reads from a [`Gated`] stream with a 1ms timeout, calling `on_wait`
+    /// with the stream's gate and the timers whenever the read waits.
+    fn read_with_timeout(
+        signal: &'static WakeSignal,
+        result: StreamResult<Bytes>,
+        mut on_wait: impl FnMut(&Notifier, &MockTimerProvider),
+    ) -> StreamResult<Vec<u8>> {
+        let gate = Notifier::new();
+        let timers = MockTimerProvider::new();
+        let mut options = IoLinkOptions::new();
+        options.timer_provider(timers.clone());
+        let mut table = ResourceTable::new();
+        let stream: DynInputStream = Box::new(Gated {
+            gate: gate.clone(),
+            result: Some(result),
+        });
+        let stream = table.push(stream).unwrap();
+        let mut io = IoImpl::new(&mut table, &options);
+        let read = streams_timeout::Host::blocking_read_timeout(&mut io, stream, 64, 1_000_000);
+        block_on(signal, |_| on_wait(&gate, &timers), read)
+    }
+
+    #[test]
+    fn data_before_timeout() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let read = read_with_timeout(&SIGNAL, Ok(Bytes::from_static(b"hello")), |gate, _| {
+            gate.notify_waiters()
+        });
+        assert_eq!(read.unwrap(), b"hello");
+    }
+
+    #[test]
+    fn timeout_before_data() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let read = read_with_timeout(&SIGNAL, Ok(Bytes::from_static(b"late")), |_, timers| {
+            timers.advance(Duration::from_millis(1))
+        });
+        assert!(read.unwrap().is_empty());
+    }
+
+    #[test]
+    fn closed_during_timed_wait() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let read = read_with_timeout(&SIGNAL, Err(StreamError::Closed), |gate, _| {
+            gate.notify_waiters()
+        });
+        assert!(matches!(read, Err(StreamError::Closed)));
+    }
+
+    #[test]
+    fn poll_yields_every_max_wait() -> Result<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let timers = MockTimerProvider::new();
+        let mut options = IoLinkOptions::new();
+        options.timer_provider(timers.clone());
+        options.max_poll_wait(Duration::from_millis(10));
+        let mut table = ResourceTable::new();
+        let (_stream, never) = detached_pollable(&mut table);
+        let gate = Notifier::new();
+        let gated = gate.pollable(&mut table)?;
+
+        // Each slice which expires wakes the task, which yields and starts
+        // the next slice without `poll` returning to the guest.
+        let mut io = IoImpl::new(&mut table, &options);
+        let poll = io.poll(vec![borrow(&never), borrow(&gated)]);
+        let mut slices = 0;
+        let ready = block_on(
+            &SIGNAL,
+            |signal| {
+                let deadline = (slices as u64 + 1) * 10_000_000;
+                assert_eq!(timers.pending_timers(), [deadline]);
+                if slices == 3 {
+                    gate.notify_waiters();
+                    return;
+                }
+                timers.advance(Duration::from_millis(10));
+                assert!(signal.is_woken());
+                slices += 1;
+            },
+            poll,
+        )?;
+        assert_eq!(ready, [1]);
+        assert_eq!(slices, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn blocking_read_yields_every_max_wait() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let timers = MockTimerProvider::new();
+        let gate = Notifier::new();
+        let mut options = IoLinkOptions::new();
+        options.timer_provider(timers.clone());
+        options.max_poll_wait(Duration::from_millis(10));
+        let mut table = ResourceTable::new();
+        let stream: DynInputStream = Box::new(Gated {
+            gate: gate.clone(),
+            result: Some(Ok(Bytes::from_static(b"hello"))),
+        });
+        let stream = table.push(stream).unwrap();
+        let mut io = IoImpl::new(&mut table, &options);
+        let read = streams::HostInputStream::blocking_read(&mut io, stream, 64);
+        let mut waits = 0;
+        let read = block_on(
+            &SIGNAL,
+            |_| {
+                // Each wait is one slice, with a timer of its own.
+                assert_eq!(timers.pending_timers().len(), 1);
+                waits += 1;
+                if waits <= 2 {
+                    timers.advance(Duration::from_millis(10));
+                } else {
+                    gate.notify_waiters();
+                }
+            },
+            read,
+        );
+        assert_eq!(read.unwrap(), b"hello");
+        assert_eq!(waits, 3);
+        assert!(timers.pending_timers().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "max-poll-wait requires a timer provider")]
+    fn max_poll_wait_requires_timers() {
+        IoLinkOptions::new().max_poll_wait(Duration::from_millis(10));
+    }
+
+    /// An input stream which produces as many bytes as are asked for, and an
+    /// output stream which accepts any number, recording the largest length
+    /// either was asked to handle.
+    #[derive(Clone, Default)]
+    struct Unbounded(Arc<AtomicUsize>);
+
+    impl Unbounded {
+        fn record(&self, len: usize) {
+            self.0.fetch_max(len, Ordering::SeqCst);
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Pollable for Unbounded {
+        async fn ready(&mut self) {}
+    }
+
+    #[async_trait::async_trait]
+    impl InputStream for Unbounded {
+        fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+            self.record(size);
+            Ok(vec![0; size].into())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl OutputStream for Unbounded {
+        fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+            self.record(bytes.len());
+            Ok(())
+        }
+        fn write_zeroes(&mut self, nelem: usize) -> StreamResult<()> {
+            self.record(nelem);
+            Ok(())
+        }
+        fn flush(&mut self) -> StreamResult<()> {
+            Ok(())
+        }
+        fn check_write(&mut self) -> StreamResult<usize> {
+            Ok(usize::MAX)
+        }
+    }
+
+    #[test]
+    fn guest_lengths_are_clamped() -> StreamResult<()> {
+        use streams::{HostInputStream as _, HostOutputStream as _};
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        // A limit far below `usize::MAX` stands in for a 32-bit host, on
+        // which `u64::MAX` doesn't fit in a `usize`.
+        const MAX: usize = 1024;
+        let mut options = IoLinkOptions::new();
+        options.max_len(MAX);
+        let largest = Unbounded::default();
+        let mut table = ResourceTable::new();
+        let input = table.push(Box::new(largest.clone()) as DynInputStream)?;
+        let output = table.push(Box::new(largest.clone()) as DynOutputStream)?;
+        let mut io = IoImpl::new(&mut table, &options);
+
+        assert_eq!(io.read(borrow(&input), u64::MAX)?.len(), MAX);
+        let read = io.blocking_read(borrow(&input), u64::MAX);
+        assert_eq!(block_on(&SIGNAL, |_| {}, read)?.len(), MAX);
+        assert_eq!(io.skip(borrow(&input), u64::MAX)?, MAX as u64);
+        let skip = io.blocking_skip(borrow(&input), u64::MAX);
+        assert_eq!(block_on(&SIGNAL, |_| {}, skip)?, MAX as u64);
+        assert_eq!(io.read(borrow(&input), 10)?.len(), 10);
+
+        assert_eq!(io.check_write(borrow(&output))?, MAX as u64);
+        let splice = io.splice(borrow(&output), borrow(&input), u64::MAX);
+        assert_eq!(splice?, MAX as u64);
+        let splice = io.blocking_splice(borrow(&output), borrow(&input), u64::MAX);
+        assert_eq!(block_on(&SIGNAL, |_| {}, splice)?, MAX as u64);
+        assert_eq!(io.check_write(borrow(&output))?, MAX as u64);
+        io.write_zeroes(borrow(&output), MAX as u64)?;
+        assert!(matches!(
+            io.write_zeroes(borrow(&output), u64::MAX),
+            Err(StreamError::Trap(_))
+        ));
+        assert_eq!(largest.0.load(Ordering::SeqCst), MAX);
+        Ok(())
+    }
+
+    /// Counts of the operations performed by a splice.
+    #[derive(Default, Debug, PartialEq)]
+    struct SpliceCounts {
+        reads: usize,
+        writes: usize,
+        fast_splices: usize,
+    }
+
+    /// An input stream of `data` which counts its reads.
+    struct SpliceSource {
+        data: Bytes,
+        counts: Arc<Mutex<SpliceCounts>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Pollable for SpliceSource {
+        async fn ready(&mut self) {}
+    }
+
+    #[async_trait::async_trait]
+    impl InputStream for SpliceSource {
+        fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+            self.counts.lock().unwrap().reads += 1;
+            Ok(self.data.split_to(size.min(self.data.len())))
+        }
+        fn as_any(&self) -> Option<&dyn Any> {
+            Some(self)
+        }
+        fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+            Some(self)
+        }
+    }
+
+    /// An output stream which collects the bytes written to it in
+    /// `written`, and takes them directly from a `SpliceSource` when `fast`
+    /// is set.
+    struct SpliceSink {
+        written: Arc<Mutex<Vec<u8>>>,
+        fast: bool,
+        counts: Arc<Mutex<SpliceCounts>>,
+    }
+
+    impl SpliceSink {
+        const PERMIT: usize = 4;
+    }
+
+    #[async_trait::async_trait]
+    impl Pollable for SpliceSink {
+        async fn ready(&mut self) {}
+    }
+
+    #[async_trait::async_trait]
+    impl OutputStream for SpliceSink {
+        fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+            self.counts.lock().unwrap().writes += 1;
+            self.written.lock().unwrap().extend_from_slice(&bytes);
+            Ok(())
+        }
+        fn flush(&mut self) -> StreamResult<()> {
+            Ok(())
+        }
+        fn check_write(&mut self) -> StreamResult<usize> {
+            Ok(Self::PERMIT)
+        }
+        fn splice_from(
+            &mut self,
+            src: &mut dyn InputStream,
+            len: usize,
+        ) -> Option<StreamResult<usize>> {
+            if !self.fast {
+                return None;
+            }
+            let src = src.as_any_mut()?.downcast_mut::<SpliceSource>()?;
+            self.counts.lock().unwrap().fast_splices += 1;
+            let len = len.min(Self::PERMIT).min(src.data.len());
+            let bytes = src.data.split_to(len);
+            self.written.lock().unwrap().extend_from_slice(&bytes);
+            Some(Ok(len))
+        }
+    }
+
+    /// Splices all of a `SpliceSource` into a `SpliceSink`, returning the
+    /// result of each splice, the bytes written and the operation counts.
+    fn splice_all(fast: bool) -> StreamResult<(Vec<u64>, Vec<u8>, SpliceCounts)> {
+        use streams::HostOutputStream as _;
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let counts = Arc::new(Mutex::new(SpliceCounts::default()));
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let mut table = ResourceTable::new();
+        let input = table.push(Box::new(SpliceSource {
+            data: Bytes::from_static(b"hello, world"),
+            counts: counts.clone(),
+        }) as DynInputStream)?;
+        let output = table.push(Box::new(SpliceSink {
+            written: written.clone(),
+            fast,
+            counts: counts.clone(),
+        }) as DynOutputStream)?;
+
+        let mut results = vec![table.splice(borrow(&output), borrow(&input), 5)?];
+        let splice = table.blocking_splice(borrow(&output), borrow(&input), 100);
+        results.push(block_on(&SIGNAL, |_| {}, splice)?);
+        results.push(table.splice(borrow(&output), borrow(&input), 100)?);
+        results.push(table.splice(borrow(&output), borrow(&input), 100)?);
+
+        let written = written.lock().unwrap().clone();
+        let counts = core::mem::take(&mut *counts.lock().unwrap());
+        Ok((results, written, counts))
+    }
+
+    #[test]
+    fn splice_fast_path_bypasses_generic_path() -> StreamResult<()> {
+        let (fast_results, fast_written, fast_counts) = splice_all(true)?;
+        let (results, written, counts) = splice_all(false)?;
+        assert_eq!(results, [4, 4, 4, 0]);
+        assert_eq!(written, b"hello, world");
+        assert_eq!(fast_results, results);
+        assert_eq!(fast_written, written);
+
+        assert_eq!(
+            fast_counts,
+            SpliceCounts {
+                reads: 0,
+                writes: 0,
+                fast_splices: 4,
+            }
+        );
+        assert_eq!(counts.fast_splices, 0);
+        assert!(counts.reads > 0 && counts.writes > 0, "{counts:?}");
+        Ok(())
+    }
+
+    /// A sink which permits [`SmallPermits::PERMIT`] bytes at a time, accepts
+    /// large buffers for blocking writes, and records the size of each
+    /// buffer handed to `blocking_write_and_flush`.
+    #[derive(Clone, Default)]
+    struct SmallPermits {
+        chunks: Arc<Mutex<Vec<usize>>>,
+        /// The number of bytes after which the sink closes, if any.
+        close_after: Option<usize>,
+    }
+
+    impl SmallPermits {
+        const PERMIT: usize = 1024;
+
+        fn written(&self) -> usize {
+            self.chunks.lock().unwrap().iter().sum()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Pollable for SmallPermits {
+        async fn ready(&mut self) {}
+    }
+
+    #[async_trait::async_trait]
+    impl OutputStream for SmallPermits {
+        fn write(&mut self, _bytes: Bytes) -> StreamResult<()> {
+            unreachable!("only blocking writes are used")
+        }
+
+        fn flush(&mut self) -> StreamResult<()> {
+            Ok(())
+        }
+
+        fn check_write(&mut self) -> StreamResult<usize> {
+            match self.close_after {
+                Some(n) if self.written() >= n => Err(StreamError::Closed),
+                _ => Ok(SmallPermits::PERMIT),
+            }
+        }
+
+        async fn blocking_write_and_flush(&mut self, bytes: Bytes) -> StreamResult<()> {
+            if bytes.len() > SmallPermits::PERMIT {
+                return Err(StreamError::host_bug("wrote more than permitted"));
+            }
+            self.chunks.lock().unwrap().push(bytes.len());
+            Ok(())
+        }
+
+        fn blocking_write_limit(&self) -> usize {
+            64 << 10
+        }
+    }
+
+    #[test]
+    fn blocking_writes_are_chunked_by_permit() -> StreamResult<()> {
+        use streams::HostOutputStream as _;
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let sink = SmallPermits::default();
+        let mut table = ResourceTable::new();
+        let output = table.push(Box::new(sink.clone()) as DynOutputStream)?;
+
+        let write = table.blocking_write_and_flush(borrow(&output), vec![1; 64 << 10]);
+        block_on(&SIGNAL, |_| {}, write)?;
+        assert_eq!(*sink.chunks.lock().unwrap(), [SmallPermits::PERMIT; 64]);
+
+        // Buffers beyond the stream's limit still trap.
+        let write = table.blocking_write_and_flush(borrow(&output), vec![1; (64 << 10) + 1]);
+        assert!(matches!(
+            block_on(&SIGNAL, |_| {}, write),
+            Err(StreamError::Trap(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn chunked_blocking_write_reports_closing() -> StreamResult<()> {
+        use streams::HostOutputStream as _;
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let sink = SmallPermits {
+            close_after: Some(2 * SmallPermits::PERMIT),
+            ..SmallPermits::default()
+        };
+        let mut table = ResourceTable::new();
+        let output = table.push(Box::new(sink.clone()) as DynOutputStream)?;
+
+        // Part of the buffer was written before the stream closed, which
+        // isn't reported as success.
+        let write = table.blocking_write_and_flush(borrow(&output), vec![1; 64 << 10]);
+        assert!(matches!(
+            block_on(&SIGNAL, |_| {}, write),
+            Err(StreamError::Closed)
+        ));
+        assert_eq!(*sink.chunks.lock().unwrap(), [SmallPermits::PERMIT; 2]);
+        Ok(())
+    }
+
+    /// A sink which permits [`ZeroRuns::PERMIT`] bytes at a time, only
+    /// accepts zeroes, and records the length of each run of zeroes handed to
+    /// `write_zeroes`.
+    #[derive(Clone, Default)]
+    struct ZeroRuns(Arc<Mutex<Vec<usize>>>);
+
+    impl ZeroRuns {
+        const PERMIT: usize = 1024;
+    }
+
+    #[async_trait::async_trait]
+    impl Pollable for ZeroRuns {
+        async fn ready(&mut self) {}
+    }
+
+    #[async_trait::async_trait]
+    impl OutputStream for ZeroRuns {
+        fn write(&mut self, _bytes: Bytes) -> StreamResult<()> {
+            Err(StreamError::host_bug("zeroes were converted into a buffer"))
+        }
+
+        fn write_zeroes(&mut self, nelem: usize) -> StreamResult<()> {
+            if nelem > ZeroRuns::PERMIT {
+                return Err(StreamError::host_bug("wrote more than permitted"));
+            }
+            self.0.lock().unwrap().push(nelem);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> StreamResult<()> {
+            Ok(())
+        }
+
+        fn check_write(&mut self) -> StreamResult<usize> {
+            Ok(ZeroRuns::PERMIT)
+        }
+
+        fn blocking_write_limit(&self) -> usize {
+            64 << 10
+        }
+    }
+
+    #[test]
+    fn zeroes_reach_streams_as_runs() -> StreamResult<()> {
+        use streams::HostOutputStream as _;
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let sink = ZeroRuns::default();
+        let mut table = ResourceTable::new();
+        let output = table.push(Box::new(sink.clone()) as DynOutputStream)?;
+
+        // Write coalescing doesn't turn zeroes into buffers either.
+        let mut options = IoLinkOptions::new();
+        options.coalesce_writes(4096);
+        let mut io = IoImpl::new(&mut table, &options);
+        assert_eq!(io.check_write(borrow(&output))?, ZeroRuns::PERMIT as u64);
+        io.write_zeroes(borrow(&output), 1000)?;
+        let write = io.blocking_write_zeroes_and_flush(borrow(&output), 3000);
+        block_on(&SIGNAL, |_| {}, write)?;
+        assert_eq!(*sink.0.lock().unwrap(), [1000, 1024, 1024, 952]);
+        sink.0.lock().unwrap().clear();
+
+        // Requests far above the permit are passed on in runs of the permit.
+        let write = table.blocking_write_zeroes_and_flush(borrow(&output), 64 << 10);
+        block_on(&SIGNAL, |_| {}, write)?;
+        assert_eq!(*sink.0.lock().unwrap(), [ZeroRuns::PERMIT; 64]);
+        Ok(())
+    }
+
+    #[test]
+    fn traps_carry_their_origin() {
+        use streams::Host as _;
+        let mut table = ResourceTable::new();
+        let origin = |table: &mut ResourceTable, trap: StreamError| {
+            let error = table.convert_stream_error(trap).unwrap_err();
+            *error.downcast_ref::<TrapOrigin>().unwrap()
+        };
+
+        let host = StreamError::host_bug("stream invariant broken");
+        assert_eq!(origin(&mut table, host), TrapOrigin::Host);
+        let guest = StreamError::guest_trap("write exceeded permit");
+        assert_eq!(origin(&mut table, guest), TrapOrigin::Guest);
+        // Traps created directly are attributed to the guest, as `trap` is.
+        let direct = StreamError::Trap(anyhow!("bad handle"));
+        assert_eq!(origin(&mut table, direct), TrapOrigin::Guest);
+        assert_eq!(
+            StreamError::host_bug("stream invariant broken").to_string(),
+            "trap: bug in the host's stream implementation: stream invariant broken"
+        );
+    }
+}
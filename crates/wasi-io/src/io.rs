@@ -0,0 +1,255 @@
+//! Synchronous access to the state of streams for host code.
+//!
+//! Host code outside of the bindings, such as metrics scrapers and admin
+//! endpoints, can inspect the output streams in a [`ResourceTable`] with
+//! these functions without constructing futures or going through the host
+//! traits of `wasi:io/streams`. They mirror the guest's non-blocking
+//! functions, without the limits [`IoLinkOptions`](crate::IoLinkOptions)
+//! applies to guests, and may be called while the guest holds handles to the
+//! same streams.
+//!
+//! Handles which aren't present, or which no longer refer to an output
+//! stream, for example because it was deleted with
+//! [`delete_parent`](crate::child::delete_parent), are reported as a
+//! [`ResourceTableError`]. Functions returning a [`StreamResult`] report it
+//! as a [`StreamError::Trap`], from which it can be downcast.
+//!
+//! Host code can also get back at the concrete type of a stream it pushed
+//! into the table with [`downcast_output_stream`] and
+//! [`downcast_input_stream`], for example to read the bytes captured by a
+//! buffer once the guest has finished, without keeping its own map of
+//! handles to streams.
+
+use crate::streams::{DynInputStream, DynOutputStream, StreamError, StreamResult};
+use core::any::Any;
+use wasmtime::component::{Resource, ResourceTable, ResourceTableError};
+
+/// Returns the number of bytes which may currently be written to `stream`,
+/// as `check-write` does for the guest.
+///
+/// As with any check of the stream, this refreshes the permit of a guest
+/// writing to it, see the [`permits`](crate::permits) module.
+pub fn check_write(
+    table: &mut ResourceTable,
+    stream: &Resource<DynOutputStream>,
+) -> StreamResult<usize> {
+    table.get_mut(stream)?.check_write()
+}
+
+/// Returns whether `stream` is closed, that is whether [`check_write`]
+/// reports [`StreamError::Closed`].
+///
+/// Other errors are returned as they are. A stream which reports an error
+/// only once won't report it to the guest as well.
+pub fn is_closed(
+    table: &mut ResourceTable,
+    stream: &Resource<DynOutputStream>,
+) -> StreamResult<bool> {
+    match check_write(table, stream) {
+        Ok(_) => Ok(false),
+        Err(StreamError::Closed) => Ok(true),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns the number of bytes written to `stream` which it still holds, or
+/// `None` if it doesn't keep track of them, see
+/// [`OutputStream::buffered_bytes`](crate::streams::OutputStream::buffered_bytes).
+pub fn buffered_bytes(
+    table: &mut ResourceTable,
+    stream: &Resource<DynOutputStream>,
+) -> Result<Option<usize>, ResourceTableError> {
+    Ok(table.get(stream)?.buffered_bytes())
+}
+
+/// Returns `stream` as the concrete output stream type `T`.
+///
+/// This relies on [`OutputStream::as_any_mut`], which streams opt into, and
+/// sees through the wrappers this crate puts around streams written to by
+/// the guest, except while a
+/// [`coalesce_writes`](crate::IoLinkOptions::coalesce_writes) wrapper holds
+/// bytes which haven't reached `T` yet. Streams which aren't a `T`, or which
+/// can't currently be downcast, are reported as
+/// [`ResourceTableError::WrongType`].
+///
+/// [`OutputStream::as_any_mut`]: crate::streams::OutputStream::as_any_mut
+pub fn downcast_output_stream<'a, T: Any>(
+    table: &'a mut ResourceTable,
+    stream: &Resource<DynOutputStream>,
+) -> Result<&'a mut T, ResourceTableError> {
+    table
+        .get_mut(stream)?
+        .as_any_mut()
+        .and_then(|s| s.downcast_mut())
+        .ok_or(ResourceTableError::WrongType)
+}
+
+/// Returns `stream` as the concrete input stream type `T`.
+///
+/// Like [`downcast_output_stream`], this relies on
+/// [`InputStream::as_any_mut`], and streams which aren't a `T` are reported
+/// as [`ResourceTableError::WrongType`].
+///
+/// [`InputStream::as_any_mut`]: crate::streams::InputStream::as_any_mut
+pub fn downcast_input_stream<'a, T: Any>(
+    table: &'a mut ResourceTable,
+    stream: &Resource<DynInputStream>,
+) -> Result<&'a mut T, ResourceTableError> {
+    table
+        .get_mut(stream)?
+        .as_any_mut()
+        .and_then(|s| s.downcast_mut())
+        .ok_or(ResourceTableError::WrongType)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::bindings::wasi::io::streams::HostOutputStream;
+    use crate::child::delete_parent;
+    use crate::poll::Pollable;
+    use crate::streams::OutputStream;
+    use crate::test_streams::Capture;
+    use crate::{IoImpl, IoLinkOptions};
+    use alloc::boxed::Box;
+    use bytes::Bytes;
+
+    /// A stream which holds up to [`CAPACITY`] written bytes until it's
+    /// flushed, and which closes once `flush` has been called twice.
+    #[derive(Default)]
+    struct Buffering {
+        held: usize,
+        flushes: usize,
+    }
+
+    const CAPACITY: usize = 8;
+
+    #[async_trait::async_trait]
+    impl Pollable for Buffering {
+        async fn ready(&mut self) {}
+    }
+
+    #[async_trait::async_trait]
+    impl OutputStream for Buffering {
+        fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+            self.held += bytes.len();
+            Ok(())
+        }
+
+        fn flush(&mut self) -> StreamResult<()> {
+            self.held = 0;
+            self.flushes += 1;
+            Ok(())
+        }
+
+        fn check_write(&mut self) -> StreamResult<usize> {
+            if self.flushes >= 2 {
+                return Err(StreamError::Closed);
+            }
+            Ok(CAPACITY - self.held)
+        }
+
+        fn buffered_bytes(&self) -> Option<usize> {
+            Some(self.held)
+        }
+    }
+
+    fn borrow(s: &Resource<DynOutputStream>) -> Resource<DynOutputStream> {
+        Resource::new_borrow(s.rep())
+    }
+
+    #[test]
+    fn observes_streams_used_by_the_guest() -> anyhow::Result<()> {
+        let options = IoLinkOptions::new();
+        let mut table = ResourceTable::new();
+        let stream = table.push(Box::new(Buffering::default()) as DynOutputStream)?;
+        // The guest holds its own handle and a pollable for the stream.
+        let guest = borrow(&stream);
+        let pollable = IoImpl::new(&mut table, &options).subscribe(borrow(&guest))?;
+
+        assert_eq!(check_write(&mut table, &stream)?, CAPACITY);
+        assert_eq!(buffered_bytes(&mut table, &stream)?, Some(0));
+
+        let mut io = IoImpl::new(&mut table, &options);
+        assert_eq!(io.check_write(borrow(&guest))?, CAPACITY as u64);
+        io.write(borrow(&guest), vec![0; 5])?;
+        assert_eq!(check_write(&mut table, &stream)?, CAPACITY - 5);
+        assert_eq!(buffered_bytes(&mut table, &stream)?, Some(5));
+        assert!(!is_closed(&mut table, &stream)?);
+
+        // The guest can go on writing after the host checked the stream.
+        let mut io = IoImpl::new(&mut table, &options);
+        io.write(borrow(&guest), vec![0; 3])?;
+        io.flush(borrow(&guest))?;
+        io.flush(borrow(&guest))?;
+        assert_eq!(buffered_bytes(&mut table, &stream)?, Some(0));
+        assert!(is_closed(&mut table, &stream)?);
+
+        table.delete(pollable)?;
+        table.delete(stream)?;
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_handles_are_errors() -> anyhow::Result<()> {
+        let mut table = ResourceTable::new();
+        let stream = table.push(Box::new(Buffering::default()) as DynOutputStream)?;
+        let missing = Resource::<DynOutputStream>::new_borrow(stream.rep() + 1);
+        let StreamError::Trap(e) = check_write(&mut table, &missing).unwrap_err() else {
+            panic!("expected a trap");
+        };
+        assert!(matches!(
+            e.downcast_ref::<ResourceTableError>(),
+            Some(ResourceTableError::NotPresent)
+        ));
+        assert!(is_closed(&mut table, &missing).is_err());
+
+        // A handle whose stream was deleted along with its children no
+        // longer refers to a stream.
+        let child = table.push_child(Box::new(Buffering::default()) as DynOutputStream, &stream)?;
+        delete_parent(&mut table, stream)?;
+        assert!(matches!(
+            buffered_bytes(&mut table, &child),
+            Err(ResourceTableError::WrongType)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn downcast_captured_output() -> anyhow::Result<()> {
+        let mut options = IoLinkOptions::new();
+        options.coalesce_writes(16);
+        let mut table = ResourceTable::new();
+        let capture = table.push(Box::new(Capture::new(1024)) as DynOutputStream)?;
+        let other = table.push(Box::new(Buffering::default()) as DynOutputStream)?;
+
+        let mut io = IoImpl::new(&mut table, &options);
+        io.check_write(borrow(&capture))?;
+        io.write(borrow(&capture), b"hello".to_vec())?;
+
+        // The written bytes are still staged by the coalescing wrapper.
+        assert!(matches!(
+            downcast_output_stream::<Capture>(&mut table, &capture),
+            Err(ResourceTableError::WrongType)
+        ));
+
+        let mut io = IoImpl::new(&mut table, &options);
+        io.flush(borrow(&capture))?;
+        io.write(borrow(&capture), b", world".to_vec())?;
+        io.flush(borrow(&capture))?;
+        let captured = downcast_output_stream::<Capture>(&mut table, &capture)?;
+        assert_eq!(captured.take(), b"hello, world");
+
+        // Streams which don't opt in, or which are of another type, can't be
+        // downcast.
+        assert!(matches!(
+            downcast_output_stream::<Buffering>(&mut table, &other),
+            Err(ResourceTableError::WrongType)
+        ));
+        assert!(matches!(
+            downcast_output_stream::<Buffering>(&mut table, &capture),
+            Err(ResourceTableError::WrongType)
+        ));
+        Ok(())
+    }
+}
@@ -24,10 +24,33 @@ extern crate alloc;
 #[macro_use]
 extern crate std;
 
+pub mod accounting;
 pub mod bindings;
+pub mod child;
+pub mod coalesce;
+#[cfg(feature = "concurrent")]
+mod concurrent;
+pub mod debug;
+pub mod deterministic;
+#[cfg(target_has_atomic = "64")]
+pub mod epoch;
+pub mod error;
+pub mod events;
+pub mod executor;
+pub mod host_view;
+pub mod host_wait;
 mod impls;
+pub mod io;
+pub mod permits;
 pub mod poll;
+pub mod scope;
+pub mod snapshot;
+pub mod splice;
 pub mod streams;
+#[cfg(all(test, feature = "std"))]
+mod test_streams;
+#[cfg(any(test, feature = "test-util"))]
+pub mod time;
 
 #[doc(no_inline)]
 pub use async_trait::async_trait;
@@ -35,8 +58,29 @@ pub use async_trait::async_trait;
 #[doc(no_inline)]
 pub use ::bytes;
 
+pub use accounting::MemoryAccountant;
+pub use child::child_resource;
+#[cfg(target_has_atomic = "64")]
+pub use epoch::epoch_pollable;
+pub use executor::{WakeSignal, block_on};
+pub use host_view::{HostInputStreamHandle, host_view_input_stream};
+pub use host_wait::HostWaitHook;
+pub use io::{downcast_input_stream, downcast_output_stream};
+pub use scope::IoScope;
+pub use snapshot::{IoSnapshotManifest, restore_io, snapshot_io};
+pub use splice::{SpliceObserver, SpliceProgress};
+
 use alloc::boxed::Box;
-use wasmtime::component::{HasData, ResourceTable};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::future::{Future, poll_fn};
+use core::pin::pin;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::Poll;
+use core::time::Duration;
+use poll::DynFuture;
+use wasmtime::component::{HasData, Resource, ResourceTable, ResourceTableError};
 
 /// A trait which provides access to the [`ResourceTable`] inside the
 /// embedder's `T` of [`Store<T>`][`Store`].
@@ -78,17 +122,484 @@ pub trait IoView {
     /// Embedders can add custom resources to this table as well to give
     /// resources to wasm as well.
     fn table(&mut self) -> &mut ResourceTable;
+
+    /// Yields the [`ResourceTable`] along with the [`IoLinkOptions`] which
+    /// the host implementation added by [`add_to_linker_async`] uses.
+    ///
+    /// By default this uses [`IoView::table`] and the default options.
+    /// Embedders which configure options store them alongside the table and
+    /// override this method.
+    fn io(&mut self) -> IoImpl<'_> {
+        IoImpl::new(self.table(), &DEFAULT_OPTIONS)
+    }
 }
 
 impl<T: ?Sized + IoView> IoView for &mut T {
     fn table(&mut self) -> &mut ResourceTable {
         T::table(self)
     }
+    fn io(&mut self) -> IoImpl<'_> {
+        T::io(self)
+    }
 }
 impl<T: ?Sized + IoView> IoView for Box<T> {
     fn table(&mut self) -> &mut ResourceTable {
         T::table(self)
     }
+    fn io(&mut self) -> IoImpl<'_> {
+        T::io(self)
+    }
+}
+
+/// Options for the host implementation of wasi-io added by
+/// [`add_to_linker_async`].
+///
+/// The host functions of a [`Linker`](wasmtime::component::Linker) can't
+/// capture state, so these options are provided to them through
+/// [`IoView::io`].
+///
+/// # Example
+///
+/// ```
+/// use wasmtime::component::ResourceTable;
+/// use wasmtime_wasi_io::{IoImpl, IoLinkOptions, IoView, MemoryAccountant};
+///
+/// struct MyState {
+///     table: ResourceTable,
+///     io_options: IoLinkOptions,
+/// }
+///
+/// impl MyState {
+///     fn new() -> MyState {
+///         let mut io_options = IoLinkOptions::new();
+///         io_options.memory_accountant(MemoryAccountant::new(1 << 20));
+///         MyState {
+///             table: ResourceTable::new(),
+///             io_options,
+///         }
+///     }
+/// }
+///
+/// impl IoView for MyState {
+///     fn table(&mut self) -> &mut ResourceTable { &mut self.table }
+///     fn io(&mut self) -> IoImpl<'_> {
+///         IoImpl::new(&mut self.table, &self.io_options)
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct IoLinkOptions {
+    memory_accountant: Option<MemoryAccountant>,
+    coalesce_writes: Option<usize>,
+    drop_policy: DropPolicy,
+    detacher: Option<Arc<Detacher>>,
+    deterministic: bool,
+    timer: Option<Timer>,
+    max_len: usize,
+    write_permit_policy: PermitPolicy,
+    splice_tracker: Option<splice::SpliceTracker>,
+    max_poll_wait: Option<Duration>,
+    events: events::Publisher,
+    wait_hook: Option<host_wait::WaitHook>,
+}
+
+/// The most bytes a single stream operation transfers by default, see
+/// [`IoLinkOptions::max_len`].
+const DEFAULT_MAX_LEN: usize = 64 << 20;
+
+static DEFAULT_OPTIONS: IoLinkOptions = IoLinkOptions::new();
+
+impl Default for IoLinkOptions {
+    fn default() -> IoLinkOptions {
+        IoLinkOptions::new()
+    }
+}
+
+impl IoLinkOptions {
+    /// Creates the default options.
+    pub const fn new() -> IoLinkOptions {
+        IoLinkOptions {
+            memory_accountant: None,
+            coalesce_writes: None,
+            drop_policy: DropPolicy::Await,
+            detacher: None,
+            deterministic: false,
+            timer: None,
+            max_len: DEFAULT_MAX_LEN,
+            write_permit_policy: PermitPolicy::Trap,
+            splice_tracker: None,
+            max_poll_wait: None,
+            events: events::Publisher::new(),
+            wait_hook: None,
+        }
+    }
+
+    /// Charges host memory held by output streams to `accountant`.
+    ///
+    /// Before bytes are written to an output stream it's handed `accountant`
+    /// with [`OutputStream::set_memory_accountant`], and writes which would
+    /// exceed its budget fail. See the [`accounting`] module for details.
+    ///
+    /// [`OutputStream::set_memory_accountant`]: streams::OutputStream::set_memory_accountant
+    pub fn memory_accountant(&mut self, accountant: MemoryAccountant) -> &mut Self {
+        self.memory_accountant = Some(accountant);
+        self
+    }
+
+    /// Coalesces writes to output streams until `threshold` bytes have
+    /// accumulated.
+    ///
+    /// Output streams are wrapped in a
+    /// [`CoalescingOutputStream`](coalesce::CoalescingOutputStream) when
+    /// they're first written to, so guests which issue many small writes
+    /// between flushes call into the underlying stream less often. See the
+    /// [`coalesce`] module for details.
+    pub fn coalesce_writes(&mut self, threshold: usize) -> &mut Self {
+        self.coalesce_writes = Some(threshold);
+        self
+    }
+
+    /// Configures how the cancellation of streams dropped by the guest is
+    /// waited for.
+    ///
+    /// Policies other than [`DropPolicy::Await`] require a spawner configured
+    /// with [`IoLinkOptions::spawner`], and without one cancellation is
+    /// awaited.
+    pub fn drop_policy(&mut self, policy: DropPolicy) -> &mut Self {
+        self.drop_policy = policy;
+        self
+    }
+
+    /// Configures the spawner onto which cancellation of dropped streams is
+    /// detached according to [`IoLinkOptions::drop_policy`].
+    pub fn spawner(&mut self, spawner: impl Spawn + 'static) -> &mut Self {
+        self.detacher = Some(Arc::new(Detacher {
+            spawner: Box::new(spawner),
+            in_flight: AtomicUsize::new(0),
+        }));
+        self
+    }
+
+    /// Makes the guest's view of its streams depend only on what the guest
+    /// does, for executions which are replayed or must agree across hosts.
+    ///
+    /// Streams which don't declare themselves deterministic trap when
+    /// used, `poll` results are sorted, reads return all available bytes up
+    /// to the requested length, and error descriptions omit addresses. See
+    /// the [`deterministic`] module for the exact rules.
+    pub fn deterministic(&mut self, enable: bool) -> &mut Self {
+        self.deterministic = enable;
+        self
+    }
+
+    /// Configures the timers used by operations which wait for a deadline,
+    /// such as `blocking-read-timeout` from the extension interface added by
+    /// [`add_timeout_extension_to_linker`].
+    pub fn timer_provider(&mut self, timers: impl TimerProvider + 'static) -> &mut Self {
+        self.timer = Some(Timer(Arc::new(timers)));
+        self
+    }
+
+    /// Limits the number of bytes a single stream operation transfers to
+    /// `max`, which defaults to 64 MiB.
+    ///
+    /// Guests pass lengths to operations such as `read`, `skip` and `splice`
+    /// as `u64`s, and may pass `u64::MAX` to mean "as much as possible".
+    /// Such lengths are clamped to `max` before they reach the stream, which
+    /// may use them to size buffers, so that no operation can ask for more
+    /// than `max` bytes of host memory, or for `usize::MAX` bytes on a
+    /// 32-bit host. Reads and skips then return at most `max` bytes, and
+    /// `check-write` permits at most `max` bytes, as the guest is always
+    /// prepared for. `max` is raised to 1 if it's 0.
+    pub fn max_len(&mut self, max: usize) -> &mut Self {
+        self.max_len = max.max(1);
+        self
+    }
+
+    /// Configures what happens when a guest writes more bytes to an output
+    /// stream than `check-write` permitted, which defaults to
+    /// [`PermitPolicy::Trap`].
+    ///
+    /// See the [`permits`] module for how permits are tracked.
+    pub fn write_permit_policy(&mut self, policy: PermitPolicy) -> &mut Self {
+        self.write_permit_policy = policy;
+        self
+    }
+
+    /// Reports the progress of splices between streams to `observer`.
+    ///
+    /// The number of bytes spliced between each pair of streams is tracked
+    /// until the guest drops either of them. Clones of these options share
+    /// `observer`, but track their own totals, so each store should use its
+    /// own clone. See the [`splice`] module for details.
+    pub fn splice_observer(&mut self, observer: Arc<dyn SpliceObserver>) -> &mut Self {
+        self.splice_tracker = Some(splice::SpliceTracker::new(observer));
+        self
+    }
+
+    /// Limits how long a single `poll`, `block` or blocking stream operation
+    /// of the guest waits before the store's future yields to its executor.
+    ///
+    /// Once `max` has elapsed while such an operation is still waiting, the
+    /// future running the guest is woken and returns [`Poll::Pending`], so
+    /// the executor can do other work, such as housekeeping between guest
+    /// calls, before it polls the future again and the operation goes on
+    /// waiting for another `max`. The guest doesn't observe this: `poll`
+    /// must return at least one ready pollable, so it's never cut short with
+    /// an empty list.
+    ///
+    /// Waits in the bindings added by [`add_to_linker_concurrent`] don't keep
+    /// the store suspended and aren't affected.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no timers have been configured with
+    /// [`IoLinkOptions::timer_provider`], which measure the waits.
+    pub fn max_poll_wait(&mut self, max: Duration) -> &mut Self {
+        assert!(
+            self.timer.is_some(),
+            "max-poll-wait requires a timer provider"
+        );
+        self.max_poll_wait = Some(max);
+        self
+    }
+
+    /// Tells `hook` when blocking operations of the guest, such as `poll`
+    /// and `blocking-read`, suspend and when they finish.
+    ///
+    /// This brackets the time a host call spends waiting, which
+    /// [`Store::call_hook`](wasmtime::Store::call_hook) alone can't tell
+    /// apart from the time it spends working. See the [`host_wait`] module
+    /// for how the two correlate.
+    pub fn host_wait_hook(&mut self, hook: Arc<dyn HostWaitHook>) -> &mut Self {
+        self.wait_hook = Some(host_wait::WaitHook::new(hook));
+        self
+    }
+
+    /// Converts a length passed by the guest to the number of bytes to
+    /// transfer, see [`IoLinkOptions::max_len`].
+    fn clamp_len(&self, len: u64) -> usize {
+        usize::try_from(len).unwrap_or(usize::MAX).min(self.max_len)
+    }
+
+    /// Converts the lengths of a vectored read passed by the guest to the
+    /// number of bytes to read into each buffer, so that the read transfers
+    /// at most [`IoLinkOptions::max_len`] bytes in total. Later buffers are
+    /// shortened first, as a short read would leave them.
+    fn clamp_vectored_lens(&self, lens: &[u64]) -> Vec<usize> {
+        let mut remaining = self.max_len;
+        lens.iter()
+            .map(|len| {
+                let len = self.clamp_len(*len).min(remaining);
+                remaining -= len;
+                len
+            })
+            .collect()
+    }
+
+    /// Waits for `future` on behalf of the guest, yielding to the executor
+    /// every [`IoLinkOptions::max_poll_wait`], if configured.
+    async fn time_sliced<F: Future>(&self, future: F) -> F::Output {
+        let Some(max) = self.max_poll_wait else {
+            return future.await;
+        };
+        let timer = self.timer.as_ref().unwrap();
+        let mut future = pin!(future);
+        let mut slice = timer.0.sleep(max);
+        poll_fn(|cx| {
+            if let Poll::Ready(output) = future.as_mut().poll(cx) {
+                return Poll::Ready(output);
+            }
+            if slice.as_mut().poll(cx).is_ready() {
+                // The next slice is only started once the executor polls
+                // again, so each slice yields exactly once.
+                slice = timer.0.sleep(max);
+                cx.waker().wake_by_ref();
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Waits for `future`, the blocking operation `op` of the guest,
+    /// reporting it to [`events`] subscribers and the
+    /// [`IoLinkOptions::host_wait_hook`], and yielding every
+    /// [`IoLinkOptions::max_poll_wait`].
+    async fn blocking<F: Future>(&self, op: events::BlockingOp, future: F) -> F::Output {
+        let wait = host_wait::bracket(self.wait_hook.as_ref(), op, self.time_sliced(future));
+        self.events.blocking(op, wait).await
+    }
+
+    /// Waits for `cancel`, the cancellation of a stream dropped by the
+    /// guest, or detaches it according to the configured [`DropPolicy`].
+    async fn cancel_dropped(&self, cancel: DynFuture<'static>) {
+        match self.detach() {
+            Some(slot) => slot.spawn(cancel),
+            None => cancel.await,
+        }
+    }
+
+    /// Returns the spawner onto which a dropped stream's cancellation should
+    /// be detached, if any, reserving one of its slots.
+    fn detach(&self) -> Option<DetachSlot> {
+        let detacher = self.detacher.as_ref()?;
+        let limit = match self.drop_policy {
+            DropPolicy::Await => return None,
+            DropPolicy::Detach => usize::MAX,
+            DropPolicy::DetachBounded(n) => n,
+        };
+        detacher
+            .in_flight
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                (n < limit).then_some(n + 1)
+            })
+            .ok()?;
+        Some(DetachSlot(detacher.clone()))
+    }
+}
+
+/// How the host implementation waits for streams dropped by the guest to
+/// be cancelled, configured with [`IoLinkOptions::drop_policy`].
+///
+/// Dropping an input or output stream calls its `cancel` method, which may
+/// perform slow host work, for example shutting down a network connection.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DropPolicy {
+    /// The guest's drop returns once cancellation has completed.
+    #[default]
+    Await,
+    /// Cancellation is spawned onto the configured [`Spawn`]er and the
+    /// guest's drop returns immediately.
+    Detach,
+    /// Like [`DropPolicy::Detach`], but with at most this many cancellations
+    /// detached at once. Once the limit is reached further cancellations
+    /// are awaited.
+    DetachBounded(usize),
+}
+
+/// What the host implementation does when a guest writes more bytes to an
+/// output stream than `check-write` permitted, configured with
+/// [`IoLinkOptions::write_permit_policy`].
+///
+/// Such a write violates the contract of `wasi:io/streams`, and host streams
+/// may not be prepared for it, for example because they've only reserved
+/// room for the permitted bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PermitPolicy {
+    /// The write traps without reaching the stream, as the specification
+    /// requires.
+    #[default]
+    Trap,
+    /// The permitted bytes are written, and the write then fails with
+    /// `last-operation-failed` for the rest.
+    Clamp,
+    /// The write is passed to the stream unchecked, which decides how to
+    /// handle it.
+    Forward,
+}
+
+/// A spawner for futures which run independently of the guest, such as the
+/// cancellation of streams detached according to [`DropPolicy`].
+///
+/// This is implemented by embedders on top of their executor, for example
+/// with `tokio::spawn`.
+pub trait Spawn: Send + Sync {
+    /// Runs `future` to completion in the background.
+    fn spawn(&self, future: DynFuture<'static>);
+}
+
+/// A source of timers for host operations which wait for a deadline,
+/// configured with [`IoLinkOptions::timer_provider`].
+///
+/// This is implemented by embedders on top of their executor, for example
+/// with `tokio::time::sleep`.
+pub trait TimerProvider: Send + Sync {
+    /// Returns a future which resolves once `duration` has elapsed.
+    fn sleep(&self, duration: Duration) -> DynFuture<'static>;
+}
+
+#[derive(Clone)]
+struct Timer(Arc<dyn TimerProvider>);
+
+impl fmt::Debug for Timer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Timer").finish_non_exhaustive()
+    }
+}
+
+struct Detacher {
+    spawner: Box<dyn Spawn>,
+    in_flight: AtomicUsize,
+}
+
+impl fmt::Debug for Detacher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Detacher")
+            .field("in_flight", &self.in_flight)
+            .finish_non_exhaustive()
+    }
+}
+
+/// One of the cancellations a [`Detacher`] has in flight, released when it
+/// completes.
+struct DetachSlot(Arc<Detacher>);
+
+impl DetachSlot {
+    fn spawn(self, cancel: DynFuture<'static>) {
+        let detacher = self.0.clone();
+        detacher.spawner.spawn(Box::pin(async move {
+            cancel.await;
+            drop(self);
+        }));
+    }
+}
+
+impl Drop for DetachSlot {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// The host implementation of wasi-io: a [`ResourceTable`] along with the
+/// [`IoLinkOptions`] to apply to it, as returned by [`IoView::io`].
+pub struct IoImpl<'a> {
+    /// The table which holds wasi-io's resources.
+    pub table: &'a mut ResourceTable,
+    /// The options used for operations on the resources in `table`.
+    pub options: &'a IoLinkOptions,
+}
+
+impl<'a> IoImpl<'a> {
+    /// Creates a new view of `table` using `options`.
+    pub fn new(table: &'a mut ResourceTable, options: &'a IoLinkOptions) -> IoImpl<'a> {
+        IoImpl { table, options }
+    }
+
+    /// Pushes `stream` into the table for the guest, reporting it to
+    /// [`events`] subscribers.
+    pub fn push_input_stream(
+        &mut self,
+        stream: streams::DynInputStream,
+    ) -> Result<Resource<streams::DynInputStream>, ResourceTableError> {
+        let stream = self.table.push(stream)?;
+        self.options
+            .events
+            .stream_created(stream.rep(), events::StreamKind::Input);
+        Ok(stream)
+    }
+
+    /// Pushes `stream` into the table for the guest, reporting it to
+    /// [`events`] subscribers.
+    pub fn push_output_stream(
+        &mut self,
+        stream: streams::DynOutputStream,
+    ) -> Result<Resource<streams::DynOutputStream>, ResourceTableError> {
+        let stream = self.table.push(stream)?;
+        self.options
+            .events
+            .stream_created(stream.rep(), events::StreamKind::Output);
+        Ok(stream)
+    }
 }
 
 /// Add the wasi-io host implementation from this crate into the `linker`
@@ -150,14 +661,154 @@ impl<T: ?Sized + IoView> IoView for Box<T> {
 pub fn add_to_linker_async<T: IoView + Send + 'static>(
     l: &mut wasmtime::component::Linker<T>,
 ) -> wasmtime::Result<()> {
-    crate::bindings::wasi::io::error::add_to_linker::<T, WasiIo>(l, T::table)?;
-    crate::bindings::wasi::io::poll::add_to_linker::<T, WasiIo>(l, T::table)?;
-    crate::bindings::wasi::io::streams::add_to_linker::<T, WasiIo>(l, T::table)?;
+    crate::bindings::wasi::io::error::add_to_linker::<T, WasiIo>(l, T::io)?;
+    crate::bindings::wasi::io::poll::add_to_linker::<T, WasiIo>(l, T::io)?;
+    crate::bindings::wasi::io::streams::add_to_linker::<T, WasiIo>(l, T::io)?;
+    Ok(())
+}
+
+/// Add the wasi-io host implementation from this crate into the `linker`
+/// provided, using the [concurrent bindings](crate::bindings::concurrent).
+///
+/// This adds the same interfaces as [`add_to_linker_async`], applying the
+/// same [`IoLinkOptions`], but the functions which wait, such as `poll` and
+/// `blocking-read`, don't hold exclusive access to the store while they do.
+/// Other guest tasks and host calls may therefore run concurrently with
+/// them, which requires [`Config::wasm_component_model_async(true)`][cm-async]
+/// and calling into the guest with
+/// [`Instance::run_concurrent`](wasmtime::component::Instance::run_concurrent).
+///
+/// This function is only available with the `concurrent` feature of this
+/// crate.
+///
+/// [cm-async]: wasmtime::Config::wasm_component_model_async
+#[cfg(feature = "concurrent")]
+pub fn add_to_linker_concurrent<T: IoView + Send + 'static>(
+    l: &mut wasmtime::component::Linker<T>,
+) -> wasmtime::Result<()> {
+    crate::bindings::concurrent::wasi::io::error::add_to_linker::<T, WasiIo>(l, T::io)?;
+    crate::bindings::concurrent::wasi::io::poll::add_to_linker::<T, WasiIo>(l, T::io)?;
+    crate::bindings::concurrent::wasi::io::streams::add_to_linker::<T, WasiIo>(l, T::io)?;
+    Ok(())
+}
+
+/// Add the `wasmtime:wasi-io/streams-metadata` extension interface to the
+/// `linker` provided.
+///
+/// This interface lets guests read the metadata which host streams provide
+/// with [`InputStream::metadata`](streams::InputStream::metadata). It isn't
+/// part of WASI and isn't added by [`add_to_linker_async`], so components
+/// which don't import it are unaffected by whether it's been added.
+pub fn add_metadata_extension_to_linker<T: IoView + Send + 'static>(
+    l: &mut wasmtime::component::Linker<T>,
+) -> wasmtime::Result<()> {
+    crate::bindings::wasmtime::wasi_io::streams_metadata::add_to_linker::<T, WasiIo>(l, T::io)?;
+    Ok(())
+}
+
+/// Add the `wasmtime:wasi-io/streams-timeout` extension interface to the
+/// `linker` provided.
+///
+/// This interface lets guests read from a stream while waiting at most a
+/// given time for data, using the timers configured with
+/// [`IoLinkOptions::timer_provider`]. Reads trap if no timer provider has
+/// been configured. Like [`add_metadata_extension_to_linker`] this isn't
+/// part of WASI and isn't added by [`add_to_linker_async`].
+pub fn add_timeout_extension_to_linker<T: IoView + Send + 'static>(
+    l: &mut wasmtime::component::Linker<T>,
+) -> wasmtime::Result<()> {
+    crate::bindings::wasmtime::wasi_io::streams_timeout::add_to_linker::<T, WasiIo>(l, T::io)?;
+    Ok(())
+}
+
+/// Add the `wasmtime:wasi-io/error-code` extension interface to the
+/// `linker` provided.
+///
+/// This interface lets guests read the [`ErrorCode`](error::ErrorCode) of
+/// errors which host streams report with an [`IoError`](error::IoError).
+/// Like [`add_metadata_extension_to_linker`] this isn't part of WASI and
+/// isn't added by [`add_to_linker_async`].
+pub fn add_error_code_extension_to_linker<T: IoView + Send + 'static>(
+    l: &mut wasmtime::component::Linker<T>,
+) -> wasmtime::Result<()> {
+    crate::bindings::wasmtime::wasi_io::error_code::add_to_linker::<T, WasiIo>(l, T::io)?;
+    Ok(())
+}
+
+/// Add the `wasmtime:wasi-io/streams-subscribe-batch` extension interface to
+/// the `linker` provided.
+///
+/// This interface lets guests create pollables for many input streams with
+/// a single call, which is cheaper than calling `subscribe` for each. Like
+/// [`add_metadata_extension_to_linker`] this isn't part of WASI and isn't
+/// added by [`add_to_linker_async`].
+pub fn add_subscribe_batch_extension_to_linker<T: IoView + Send + 'static>(
+    l: &mut wasmtime::component::Linker<T>,
+) -> wasmtime::Result<()> {
+    crate::bindings::wasmtime::wasi_io::streams_subscribe_batch::add_to_linker::<T, WasiIo>(
+        l,
+        T::io,
+    )?;
+    Ok(())
+}
+
+/// Add the `wasmtime:wasi-io/streams-read-vectored` extension interface to
+/// the `linker` provided.
+///
+/// This interface lets guests read from a stream into several buffers with
+/// a single call, with the semantics of `readv`. Streams implement it with
+/// [`InputStream::read_vectored`](streams::InputStream::read_vectored),
+/// which by default performs a single read and splits the result. Like
+/// [`add_metadata_extension_to_linker`] this isn't part of WASI and isn't
+/// added by [`add_to_linker_async`].
+pub fn add_read_vectored_extension_to_linker<T: IoView + Send + 'static>(
+    l: &mut wasmtime::component::Linker<T>,
+) -> wasmtime::Result<()> {
+    crate::bindings::wasmtime::wasi_io::streams_read_vectored::add_to_linker::<T, WasiIo>(
+        l,
+        T::io,
+    )?;
+    Ok(())
+}
+
+/// Add the `wasmtime:wasi-io/streams-preferred-read-size` extension
+/// interface to the `linker` provided.
+///
+/// This interface lets guests read the
+/// [`InputStream::preferred_read_size`](streams::InputStream::preferred_read_size)
+/// of host streams, clamped to [`IoLinkOptions::max_len`], to size their
+/// reads. Like [`add_metadata_extension_to_linker`] this isn't part of WASI
+/// and isn't added by [`add_to_linker_async`].
+pub fn add_preferred_read_size_extension_to_linker<T: IoView + Send + 'static>(
+    l: &mut wasmtime::component::Linker<T>,
+) -> wasmtime::Result<()> {
+    crate::bindings::wasmtime::wasi_io::streams_preferred_read_size::add_to_linker::<T, WasiIo>(
+        l,
+        T::io,
+    )?;
     Ok(())
 }
 
 struct WasiIo;
 
 impl HasData for WasiIo {
-    type Data<'a> = &'a mut ResourceTable;
+    type Data<'a> = IoImpl<'a>;
+}
+
+// `Send` is required of everything which guests can reach: the entries of a
+// `ResourceTable` are `Send`, and so are the futures of the async host
+// functions added by `add_to_linker_async`, which hold the store's data. No
+// `Sync` bound is required of any of them, though, since the store is only
+// accessed by one thread at a time.
+fn _assertions() {
+    fn _assert_send<T: Send>() {}
+
+    _assert_send::<streams::DynInputStream>();
+    _assert_send::<streams::DynOutputStream>();
+    _assert_send::<poll::DynPollable>();
+    _assert_send::<poll::DynFuture<'static>>();
+
+    // Streams are stored as boxed trait objects, without further indirection.
+    const _: () = assert!(size_of::<streams::DynInputStream>() == 2 * size_of::<usize>());
+    const _: () = assert!(size_of::<streams::DynOutputStream>() == 2 * size_of::<usize>());
 }
@@ -0,0 +1,268 @@
+//! Enforcement of the write permits which `check-write` grants.
+//!
+//! A guest may only `write` or `write-zeroes` as many bytes as the last
+//! `check-write` permitted, less what it has written since. Host streams
+//! aren't required to cope with guests which write more, so unless
+//! [`IoLinkOptions::write_permit_policy`] is [`PermitPolicy::Forward`], the
+//! first `check-write`, `write` or `write-zeroes` on an output stream wraps
+//! it in a [`PermitCheckedOutputStream`]. This keeps the stream's outstanding
+//! permit alongside it in its table entry, and applies the configured
+//! [`PermitPolicy`] to writes which exceed it.
+//!
+//! The permit is refreshed by every `check_write` and `write_ready` on the
+//! stream, including those the host makes itself, for example to splice
+//! into it, and is used up by the bytes written. Streams which enforce their
+//! permits themselves can opt out with
+//! [`OutputStream::enforces_write_permits`].
+//!
+//! [`IoLinkOptions::write_permit_policy`]: crate::IoLinkOptions::write_permit_policy
+
+use crate::accounting::MemoryAccountant;
+use crate::error::{ErrorCode, IoError};
+use crate::poll::{DynFuture, PendingHint, Pollable, ReadinessKind};
+use crate::snapshot::SnapshotableStream;
+use crate::streams::{DynOutputStream, InputStream, OutputStream, StreamError, StreamResult};
+use crate::{IoLinkOptions, PermitPolicy};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use bytes::Bytes;
+use core::any::Any;
+
+/// An [`OutputStream`] which tracks the permit granted by the stream it
+/// wraps and applies a [`PermitPolicy`] to writes which exceed it.
+pub struct PermitCheckedOutputStream {
+    inner: DynOutputStream,
+    permit: usize,
+    max_permit: usize,
+    policy: PermitPolicy,
+}
+
+impl PermitCheckedOutputStream {
+    /// Wraps `inner`, applying `policy` to writes which exceed its permit.
+    ///
+    /// No bytes are permitted until `check_write` or `write_ready` has been
+    /// called.
+    pub fn new(inner: DynOutputStream, policy: PermitPolicy) -> PermitCheckedOutputStream {
+        PermitCheckedOutputStream {
+            inner,
+            permit: 0,
+            max_permit: usize::MAX,
+            policy,
+        }
+    }
+
+    /// Returns the number of bytes which may still be written under the
+    /// last permit.
+    pub fn permit(&self) -> usize {
+        self.permit
+    }
+
+    /// Records the permit granted by the wrapped stream, which the guest
+    /// sees clamped to [`IoLinkOptions::max_len`](crate::IoLinkOptions::max_len).
+    fn record(&mut self, permit: StreamResult<usize>) -> StreamResult<usize> {
+        self.permit = permit.as_ref().map_or(0, |n| (*n).min(self.max_permit));
+        permit
+    }
+
+    /// Uses up the permit for a write of `len` bytes, returning how many of
+    /// them may be forwarded to the wrapped stream.
+    fn take(&mut self, len: usize) -> StreamResult<usize> {
+        let permit = self.permit;
+        if len <= permit {
+            self.permit -= len;
+            return Ok(len);
+        }
+        self.permit = 0;
+        match self.policy {
+            PermitPolicy::Trap => Err(StreamError::guest_trap(&overrun(len, permit))),
+            PermitPolicy::Clamp => Ok(permit),
+            PermitPolicy::Forward => Ok(len),
+        }
+    }
+}
+
+/// Describes a write of `len` bytes which exceeded a permit of `permit`.
+fn overrun(len: usize, permit: usize) -> String {
+    format!("write of {len} bytes exceeds the permit of {permit} bytes from check-write")
+}
+
+/// The error reported for a write which [`PermitPolicy::Clamp`] cut short.
+fn clamped(len: usize, permit: usize) -> StreamError {
+    IoError::new(ErrorCode::LIMIT_EXCEEDED, overrun(len, permit)).into()
+}
+
+#[async_trait::async_trait]
+impl Pollable for PermitCheckedOutputStream {
+    async fn ready(&mut self) {
+        self.inner.ready().await
+    }
+
+    fn ready_owned(&self) -> Option<DynFuture<'static>> {
+        self.inner.ready_owned()
+    }
+
+    fn pending_hint(&self) -> Option<&PendingHint> {
+        self.inner.pending_hint()
+    }
+
+    fn readiness_kind(&self) -> ReadinessKind {
+        self.inner.readiness_kind()
+    }
+
+    fn debug_name(&self) -> Option<String> {
+        self.inner.debug_name()
+    }
+}
+
+#[async_trait::async_trait]
+impl OutputStream for PermitCheckedOutputStream {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        let len = self.take(bytes.len())?;
+        if len == bytes.len() {
+            return self.inner.write(bytes);
+        }
+        if len > 0 {
+            self.inner.write(bytes.slice(..len))?;
+        }
+        Err(clamped(bytes.len(), len))
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        self.inner.flush()
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        let permit = self.inner.check_write();
+        self.record(permit)
+    }
+
+    async fn blocking_write_and_flush(&mut self, bytes: Bytes) -> StreamResult<()> {
+        self.inner.blocking_write_and_flush(bytes).await
+    }
+
+    fn write_zeroes(&mut self, nelem: usize) -> StreamResult<()> {
+        let len = self.take(nelem)?;
+        if len == nelem {
+            return self.inner.write_zeroes(nelem);
+        }
+        if len > 0 {
+            self.inner.write_zeroes(len)?;
+        }
+        Err(clamped(nelem, len))
+    }
+
+    async fn blocking_write_zeroes_and_flush(&mut self, nelem: usize) -> StreamResult<()> {
+        self.inner.blocking_write_zeroes_and_flush(nelem).await
+    }
+
+    async fn write_ready(&mut self) -> StreamResult<usize> {
+        let permit = self.inner.write_ready().await;
+        self.record(permit)
+    }
+
+    async fn cancel(&mut self) {
+        self.inner.cancel().await
+    }
+
+    fn as_snapshotable(&self) -> Option<&dyn SnapshotableStream> {
+        self.inner.as_snapshotable()
+    }
+
+    fn set_memory_accountant(&mut self, accountant: &MemoryAccountant) {
+        self.inner.set_memory_accountant(accountant)
+    }
+
+    fn buffered_bytes(&self) -> Option<usize> {
+        self.inner.buffered_bytes()
+    }
+
+    fn blocking_write_limit(&self) -> usize {
+        self.inner.blocking_write_limit()
+    }
+
+    fn is_coalescing(&self) -> bool {
+        self.inner.is_coalescing()
+    }
+
+    fn is_deterministic(&self) -> bool {
+        self.inner.is_deterministic()
+    }
+
+    fn enforces_write_permits(&self) -> bool {
+        true
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        self.inner.as_any()
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        self.inner.as_any_mut()
+    }
+
+    fn splice_from(
+        &mut self,
+        src: &mut dyn InputStream,
+        len: usize,
+    ) -> Option<StreamResult<usize>> {
+        let moved = self.inner.splice_from(src, len)?;
+        if let Ok(n) = &moved {
+            self.permit = self.permit.saturating_sub(*n);
+        }
+        Some(moved)
+    }
+}
+
+/// Wraps `stream` in a [`PermitCheckedOutputStream`] unless the configured
+/// policy is [`PermitPolicy::Forward`] or it already enforces its permits.
+pub(crate) fn check_permits(stream: &mut DynOutputStream, options: &IoLinkOptions) {
+    let policy = options.write_permit_policy;
+    if policy == PermitPolicy::Forward || stream.enforces_write_permits() {
+        return;
+    }
+    let placeholder: DynOutputStream = Box::new(crate::snapshot::ClosedStream);
+    let inner = core::mem::replace(stream, placeholder);
+    let mut checked = PermitCheckedOutputStream::new(inner, policy);
+    checked.max_permit = options.max_len;
+    *stream = Box::new(checked);
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::bindings::wasi::io::streams::HostOutputStream;
+    use crate::test_streams::Capture;
+    use crate::{IoImpl, IoLinkOptions};
+    use wasmtime::component::{Resource, ResourceTable};
+
+    fn borrow(s: &Resource<DynOutputStream>) -> Resource<DynOutputStream> {
+        Resource::new_borrow(s.rep())
+    }
+
+    #[test]
+    fn permits_are_limited_to_what_the_guest_sees() -> anyhow::Result<()> {
+        let mut options = IoLinkOptions::new();
+        options.max_len(16).write_permit_policy(PermitPolicy::Clamp);
+        let sink = Capture::new(usize::MAX);
+        let mut table = ResourceTable::new();
+        let stream = table.push(Box::new(sink.clone()) as DynOutputStream)?;
+        let mut io = IoImpl::new(&mut table, &options);
+
+        assert_eq!(io.check_write(borrow(&stream))?, 16);
+        io.write_zeroes(borrow(&stream), 10)?;
+        let err = io.write(borrow(&stream), vec![0; 10]).unwrap_err();
+        let StreamError::LastOperationFailed(e) = &err else {
+            panic!("expected the write to fail, got {err:?}");
+        };
+        assert_eq!(IoError::code_of(e), Some(ErrorCode::LIMIT_EXCEEDED));
+        assert_eq!(sink.written().len(), 16);
+
+        // Nothing is permitted until the guest checks again.
+        assert!(io.write(borrow(&stream), vec![0]).is_err());
+        assert_eq!(io.check_write(borrow(&stream))?, 16);
+        io.write(borrow(&stream), vec![0; 16])?;
+        assert_eq!(sink.written().len(), 32);
+        Ok(())
+    }
+}
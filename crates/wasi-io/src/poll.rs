@@ -1,12 +1,28 @@
+use crate::child::child_resource;
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
 use anyhow::Result;
 use core::any::Any;
+use core::cell::UnsafeCell;
+use core::fmt;
 use core::future::Future;
+use core::marker::PhantomPinned;
 use core::pin::Pin;
-use wasmtime::component::{Resource, ResourceTable};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+use wasmtime::component::{Resource, ResourceTable, ResourceTableError};
+
+mod debounce;
+pub use debounce::DebouncedPollable;
 
 pub type DynFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
 pub type MakeFuture = for<'a> fn(&'a mut dyn Any) -> DynFuture<'a>;
+pub(crate) type MakeOwnedFuture = fn(&dyn Any) -> Option<DynFuture<'static>>;
+pub(crate) type DescribePollee = fn(&dyn Any) -> PolleeDescription;
 
 /// The host representation of the `wasi:io/poll.pollable` resource.
 ///
@@ -17,7 +33,57 @@ pub type MakeFuture = for<'a> fn(&'a mut dyn Any) -> DynFuture<'a>;
 pub struct DynPollable {
     pub(crate) index: u32,
     pub(crate) make_future: MakeFuture,
+    /// Creates the pollee's readiness future which doesn't borrow it, if it
+    /// provides one, see [`Pollable::ready_owned`].
+    pub(crate) make_owned_future: MakeOwnedFuture,
     pub(crate) remove_index_on_delete: Option<fn(&mut ResourceTable, u32) -> Result<()>>,
+    /// The pollee's hint of whether it was last observed to be pending, if
+    /// it provides one.
+    pub(crate) last_known_pending: Option<PendingHint>,
+    /// For a pollable which owns its pollee, the ownership it shares with
+    /// the siblings created by [`DynPollable::duplicate`], if any. The
+    /// pollee is only deleted along with the last of them.
+    pub(crate) owners: Option<Arc<()>>,
+    /// The debouncing applied to the pollee's readiness, if any, see
+    /// [`DebouncedPollable`].
+    pub(crate) debounce: Option<Arc<DebouncedPollable>>,
+    /// Describes the pollee for diagnostics, see
+    /// [`poll_snapshot`](crate::debug::poll_snapshot).
+    pub(crate) describe: DescribePollee,
+}
+
+impl DynPollable {
+    /// Creates a sibling of `pollable` in `table`, another pollable for the
+    /// same readiness source.
+    ///
+    /// The sibling is ready whenever `pollable` is, and `poll` creates a
+    /// single future for the two of them when both are passed to it. It's a
+    /// child of the pollee, like `pollable`, but is dropped independently: if
+    /// `pollable` owns its pollee then so does the sibling, and the pollee is
+    /// only deleted once both of them, and any other siblings, have been
+    /// dropped.
+    pub fn duplicate(
+        table: &mut ResourceTable,
+        pollable: &Resource<DynPollable>,
+    ) -> Result<Resource<DynPollable>> {
+        let original = table.get_mut(pollable)?;
+        let owners = match original.remove_index_on_delete {
+            Some(_) => Some(original.owners.get_or_insert_with(Arc::default).clone()),
+            None => None,
+        };
+        let sibling = DynPollable {
+            index: original.index,
+            make_future: original.make_future,
+            make_owned_future: original.make_owned_future,
+            remove_index_on_delete: original.remove_index_on_delete,
+            last_known_pending: original.last_known_pending.clone(),
+            owners,
+            debounce: original.debounce.clone(),
+            describe: original.describe,
+        };
+        let pollee = Resource::<DynPollable>::new_borrow(sibling.index);
+        Ok(table.push_child(sibling, &pollee)?)
+    }
 }
 
 /// The trait used to implement [`DynPollable`] to create a `pollable`
@@ -32,6 +98,12 @@ pub struct DynPollable {
 /// This trait is used in conjunction with [`subscribe`] to create a `pollable`
 /// resource.
 ///
+/// Implementations must be `Send`, since they're stored in a
+/// [`ResourceTable`] and waited on by the futures of async host functions,
+/// both of which wasmtime requires to be `Send`. This holds for single-threaded
+/// embedders too: state shared through `Rc` has to be shared through `Arc`
+/// instead. They needn't be `Sync`.
+///
 /// # Example
 ///
 /// This is a simple example of creating a `Pollable` resource from a few
@@ -78,6 +150,126 @@ pub trait Pollable: Send + 'static {
     /// connected to. The call to `wasi:io/poll` itself does not return errors,
     /// only a list of ready objects.
     async fn ready(&mut self);
+
+    /// Returns a future which resolves when this object is ready, like
+    /// [`ready`](Pollable::ready), but which doesn't borrow this object.
+    ///
+    /// The concurrent bindings can't hold the store, and so this object,
+    /// while they wait, so they recreate the future returned by `ready` each
+    /// time they're woken. Objects whose futures register themselves with a
+    /// waker queue which forgets them when they're dropped, such as
+    /// [`Notifier`], should return a future here instead, which is kept
+    /// alive for the whole wait. The default implementation returns `None`.
+    fn ready_owned(&self) -> Option<DynFuture<'static>> {
+        None
+    }
+
+    /// Returns the hint shared between this object and its pollables of
+    /// whether it was pending the last time `poll` checked it.
+    ///
+    /// While the hint is set, `poll` only checks this object's readiness if
+    /// none of the other pollables in the call are ready, which saves
+    /// creating and polling a future for each idle object in a large poll
+    /// list. Objects which return a hint must call
+    /// [`mark_maybe_ready`](Pollable::mark_maybe_ready) whenever their
+    /// readiness may have changed. The default implementation returns
+    /// `None`, so this object is checked on every call to `poll`.
+    fn pending_hint(&self) -> Option<&PendingHint> {
+        None
+    }
+
+    /// Clears this object's [`pending_hint`](Pollable::pending_hint), if it
+    /// has one, so the next `poll` checks it along with the pollables which
+    /// aren't known to be pending.
+    fn mark_maybe_ready(&self) {
+        if let Some(hint) = self.pending_hint() {
+            hint.mark_maybe_ready();
+        }
+    }
+
+    /// Returns whether this object's readiness is level- or edge-triggered.
+    ///
+    /// Diagnostics such as [`poll_snapshot`](crate::debug::poll_snapshot)
+    /// only check the readiness of level-triggered objects, since checking
+    /// an edge-triggered one may consume the readiness the guest is waiting
+    /// for. The default implementation returns [`ReadinessKind::Level`].
+    fn readiness_kind(&self) -> ReadinessKind {
+        ReadinessKind::Level
+    }
+
+    /// Returns a description of this object for diagnostics, such as the
+    /// name of the file or the address of the peer it reads from. The
+    /// default implementation returns `None`.
+    fn debug_name(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns the name of this object's type for diagnostics.
+    ///
+    /// Implementations which forward to a boxed object, such as the
+    /// implementations for boxed streams, report the type of the object they
+    /// forward to. The default implementation returns the name of `Self`.
+    fn type_name(&self) -> &'static str {
+        core::any::type_name::<Self>()
+    }
+}
+
+/// How the readiness of a [`Pollable`] behaves, as returned by
+/// [`Pollable::readiness_kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadinessKind {
+    /// Once ready, the object stays ready until it's used, however often its
+    /// readiness is checked.
+    Level,
+    /// Checking the object's readiness may reset it, so it's only reported
+    /// ready to the first check after it became ready.
+    Edge,
+}
+
+/// What [`DescribePollee`] reports about a pollee.
+pub(crate) struct PolleeDescription {
+    pub(crate) type_name: &'static str,
+    pub(crate) readiness_kind: ReadinessKind,
+    pub(crate) debug_name: Option<String>,
+}
+
+/// Whether a [`Pollable`] was last observed to be pending by `poll`, as
+/// returned by [`Pollable::pending_hint`].
+///
+/// This is only a hint: `poll` never suspends without checking every
+/// pollable, so a hint which is set while its pollee is actually ready only
+/// delays observing that readiness until none of the other pollables are
+/// ready. Clones of a `PendingHint` share the same state.
+#[derive(Clone, Default)]
+pub struct PendingHint(Arc<AtomicBool>);
+
+impl PendingHint {
+    /// Creates a hint which isn't set.
+    pub fn new() -> PendingHint {
+        PendingHint::default()
+    }
+
+    /// Clears this hint, so pollables sharing it are checked by every call
+    /// to `poll` until one observes them to be pending.
+    pub fn mark_maybe_ready(&self) {
+        self.0.store(false, Ordering::Release);
+    }
+
+    pub(crate) fn set_pending(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub(crate) fn is_pending(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+impl fmt::Debug for PendingHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PendingHint")
+            .field(&self.is_pending())
+            .finish()
+    }
 }
 
 /// Creates a `wasi:io/poll/pollable` resource which is subscribed to the provided
@@ -94,26 +286,782 @@ pub fn subscribe<T>(
 where
     T: Pollable,
 {
-    fn make_future<'a, T>(stream: &'a mut dyn Any) -> DynFuture<'a>
-    where
-        T: Pollable,
-    {
-        stream.downcast_mut::<T>().unwrap().ready()
-    }
-
-    let pollable = DynPollable {
-        index: resource.rep(),
-        remove_index_on_delete: if resource.owned() {
-            Some(|table, idx| {
-                let resource = Resource::<T>::new_own(idx);
-                table.delete(resource)?;
-                Ok(())
-            })
-        } else {
-            None
-        },
+    let last_known_pending = table.get(&resource)?.pending_hint().cloned();
+    push_pollable(table, resource, last_known_pending)
+}
+
+/// Creates a `wasi:io/poll/pollable` resource for each of `resources`, as
+/// [`subscribe`] does, returning them in the same order.
+///
+/// Every resource is checked before any pollable is created, and the table
+/// is grown once for all of them. If any resource isn't present, or any
+/// pollable can't be inserted, then no pollables are left in the table and
+/// the error is returned.
+pub fn subscribe_batch<T>(
+    table: &mut ResourceTable,
+    resources: Vec<Resource<T>>,
+) -> Result<Vec<Resource<DynPollable>>>
+where
+    T: Pollable,
+{
+    let hints = resources
+        .iter()
+        .map(|resource| Ok(table.get(resource)?.pending_hint().cloned()))
+        .collect::<Result<Vec<_>>>()?;
+    table.reserve(resources.len());
+
+    let mut pollables = Vec::with_capacity(resources.len());
+    for (resource, last_known_pending) in resources.into_iter().zip(hints) {
+        match push_pollable(table, resource, last_known_pending) {
+            Ok(pollable) => pollables.push(pollable),
+            Err(e) => {
+                for pollable in pollables {
+                    table.delete(pollable)?;
+                }
+                return Err(e);
+            }
+        }
+    }
+    Ok(pollables)
+}
+
+fn push_pollable<T>(
+    table: &mut ResourceTable,
+    resource: Resource<T>,
+    last_known_pending: Option<PendingHint>,
+) -> Result<Resource<DynPollable>>
+where
+    T: Pollable,
+{
+    let pollable = child_resource(table, resource, |pollee| DynPollable {
+        index: pollee.index(),
+        remove_index_on_delete: pollee.remove_parent_on_delete,
         make_future: make_future::<T>,
-    };
+        make_owned_future: make_owned_future::<T>,
+        last_known_pending,
+        owners: None,
+        debounce: None,
+        describe: describe_pollee::<T>,
+    })?;
+    Ok(pollable)
+}
 
-    Ok(table.push_child(pollable, &resource)?)
+/// Calls `f` with simultaneous mutable access to several entries of `table`.
+///
+/// Each entry of `indices` is paired with the table entry at its key, in
+/// ascending index order. Since the keys of a [`BTreeMap`] are unique each
+/// table entry is borrowed at most once; callers which need the same entry
+/// for several purposes, such as `poll` with duplicate pollables, should group
+/// those purposes under a single key, for example with a `Vec` as the value.
+///
+/// An index which doesn't refer to a present entry is paired with
+/// [`ResourceTableError::NotPresent`] rather than failing the whole call, so
+/// `f` decides how to handle it. The entries are provided as `dyn Any` and
+/// can be downcast to their concrete types.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::BTreeMap;
+/// use wasmtime::component::ResourceTable;
+/// use wasmtime_wasi_io::poll::with_entries;
+///
+/// let mut table = ResourceTable::new();
+/// let a = table.push(1_u32).unwrap();
+/// let b = table.push(2_u32).unwrap();
+///
+/// let indices = BTreeMap::from([(a.rep(), 10), (b.rep(), 20)]);
+/// with_entries(&mut table, indices, |entries| {
+///     for (entry, add) in entries {
+///         *entry.unwrap().downcast_mut::<u32>().unwrap() += add;
+///     }
+/// });
+/// assert_eq!(*table.get(&a).unwrap(), 11);
+/// assert_eq!(*table.get(&b).unwrap(), 22);
+/// ```
+pub fn with_entries<'a, K, R>(
+    table: &'a mut ResourceTable,
+    indices: BTreeMap<u32, K>,
+    f: impl FnOnce(Vec<(Result<&'a mut dyn Any, ResourceTableError>, K)>) -> R,
+) -> R {
+    f(table.iter_entries(indices).collect())
+}
+
+/// A handle which host code, possibly on another thread, uses to make
+/// pollables ready.
+///
+/// Pollables created with [`Notifier::pollable`] are level-triggered: once
+/// [`Notifier::notify_one`] or [`Notifier::notify_waiters`] is called they're
+/// ready until [`Notifier::reset`] is called. Clones of a `Notifier` share the
+/// same state. Notifications clear the notifier's
+/// [`PendingHint`], so `poll` calls with many idle notifiers only check those
+/// which were notified, unless none of them were.
+///
+/// This only relies on atomics, so it's available without the `std`
+/// feature.
+#[derive(Clone, Default)]
+pub struct Notifier(Arc<NotifierState>);
+
+#[derive(Default)]
+struct NotifierState {
+    notified: AtomicBool,
+    waiters: WaiterLock,
+    hint: PendingHint,
+}
+
+impl Notifier {
+    /// Creates a new notifier which isn't notified.
+    pub fn new() -> Notifier {
+        Notifier::default()
+    }
+
+    /// Makes this notifier's pollables ready and wakes the task which has
+    /// been waiting on them the longest, if any.
+    ///
+    /// Other waiting tasks will observe readiness the next time they poll.
+    /// If the woken task drops its future before observing the
+    /// notification, the notification is passed on to the next waiting
+    /// task.
+    pub fn notify_one(&self) {
+        self.0.notified.store(true, Ordering::Release);
+        self.mark_maybe_ready();
+        if let Some(waker) = self.0.waiters.with(|queue| queue.notify_front()) {
+            waker.wake();
+        }
+    }
+
+    /// Makes this notifier's pollables ready and wakes all tasks waiting on
+    /// them.
+    pub fn notify_waiters(&self) {
+        self.0.notified.store(true, Ordering::Release);
+        self.mark_maybe_ready();
+        let mut wakers = Vec::new();
+        self.0.waiters.with(|queue| {
+            while let Some(waker) = queue.pop_front() {
+                wakers.push(waker);
+            }
+        });
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Makes this notifier's pollables pending again.
+    pub fn reset(&self) {
+        self.0.notified.store(false, Ordering::Release);
+    }
+
+    /// Returns whether this notifier has been notified since it was created
+    /// or last [`reset`](Notifier::reset).
+    pub fn is_notified(&self) -> bool {
+        self.0.notified.load(Ordering::Acquire)
+    }
+
+    /// Returns a place in this notifier's queue of waiters, which must be
+    /// pinned before it's polled with [`Waiter::poll_until`].
+    pub(crate) fn waiter(&self) -> Waiter<'_> {
+        Waiter {
+            notifier: self,
+            node: UnsafeCell::new(WaiterNode {
+                waker: None,
+                prev: ptr::null_mut(),
+                next: ptr::null_mut(),
+                queued: false,
+                notified: false,
+            }),
+            _pinned: PhantomPinned,
+        }
+    }
+
+    /// Waits until this notifier is notified.
+    async fn wait(&self) {
+        let mut waiter = core::pin::pin!(self.waiter());
+        core::future::poll_fn(|cx| waiter.as_mut().poll_until(cx, || self.is_notified())).await
+    }
+
+    /// Creates a `wasi:io/poll.pollable` resource in `table` which is ready
+    /// whenever this notifier is notified.
+    pub fn pollable(&self, table: &mut ResourceTable) -> Result<Resource<DynPollable>> {
+        let resource = table.push(self.clone())?;
+        subscribe(table, resource)
+    }
+}
+
+impl fmt::Debug for Notifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Notifier")
+            .field("notified", &self.is_notified())
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for Notifier {
+    async fn ready(&mut self) {
+        self.wait().await
+    }
+
+    fn ready_owned(&self) -> Option<DynFuture<'static>> {
+        let notifier = self.clone();
+        Some(Box::pin(async move { notifier.wait().await }))
+    }
+
+    fn pending_hint(&self) -> Option<&PendingHint> {
+        Some(&self.0.hint)
+    }
+}
+
+/// A value protected by a spin lock, which is only ever held briefly and
+/// never while calling out to other code, such as a waker.
+#[derive(Default)]
+pub(crate) struct SpinLock<T> {
+    locked: AtomicBool,
+    value: UnsafeCell<T>,
+}
+
+// SAFETY: `value` is only accessed while `locked` is held, see `with`.
+unsafe impl<T: Send> Sync for SpinLock<T> {}
+
+impl<T> SpinLock<T> {
+    pub(crate) const fn new(value: T) -> SpinLock<T> {
+        SpinLock {
+            locked: AtomicBool::new(false),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+        // SAFETY: the lock acquired above gives exclusive access.
+        let result = f(unsafe { &mut *self.value.get() });
+        self.locked.store(false, Ordering::Release);
+        result
+    }
+}
+
+/// A list of wakers to wake, see [`SpinLock`].
+pub(crate) type WakerList = SpinLock<Vec<Waker>>;
+
+impl WakerList {
+    /// Adds `waker` to the list, unless it wakes the same task as a waker
+    /// which is already in it.
+    pub(crate) fn register(&self, waker: &Waker) {
+        self.with(|list| {
+            if !list.iter().any(|w| w.will_wake(waker)) {
+                list.push(waker.clone());
+            }
+        });
+    }
+
+    /// Wakes, and removes, every waker in the list.
+    pub(crate) fn wake_all(&self) {
+        for waker in self.with(core::mem::take) {
+            waker.wake();
+        }
+    }
+}
+
+/// The lock protecting the [`WaiterQueue`] of a [`Notifier`]: a mutex with
+/// the `std` feature, and a [`SpinLock`] without it.
+#[derive(Default)]
+struct WaiterLock {
+    #[cfg(feature = "std")]
+    queue: std::sync::Mutex<WaiterQueue>,
+    #[cfg(not(feature = "std"))]
+    queue: SpinLock<WaiterQueue>,
+}
+
+impl WaiterLock {
+    fn with<R>(&self, f: impl FnOnce(&mut WaiterQueue) -> R) -> R {
+        #[cfg(feature = "std")]
+        {
+            // The queue is consistent between operations, so a panic while
+            // it was locked leaves nothing to recover.
+            f(&mut self.queue.lock().unwrap_or_else(|e| e.into_inner()))
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            self.queue.with(f)
+        }
+    }
+}
+
+/// A task waiting on a [`Notifier`], created by [`Notifier::waiter`].
+///
+/// Once registered its node is linked into the notifier's [`WaiterQueue`],
+/// so waiting doesn't allocate. The node is unlinked when a notification
+/// takes its waker or when the waiter is dropped, which pinning guarantees
+/// happens before its memory is reused.
+pub(crate) struct Waiter<'a> {
+    notifier: &'a Notifier,
+    node: UnsafeCell<WaiterNode>,
+    _pinned: PhantomPinned,
+}
+
+// SAFETY: `node` is only accessed while the notifier's queue is locked.
+unsafe impl Send for Waiter<'_> {}
+
+struct WaiterNode {
+    waker: Option<Waker>,
+    prev: *mut WaiterNode,
+    next: *mut WaiterNode,
+    queued: bool,
+    /// Whether [`Notifier::notify_one`] woke this waiter, which hasn't
+    /// observed the notification yet.
+    notified: bool,
+}
+
+impl Waiter<'_> {
+    /// Polls for `ready` to return true, registering `cx`'s waker to be
+    /// woken by the next notification if it doesn't.
+    pub(crate) fn poll_until(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        ready: impl Fn() -> bool,
+    ) -> Poll<()> {
+        if !ready() {
+            self.as_mut().register(cx.waker());
+            // Check again in case a notification raced with registering.
+            if !ready() {
+                return Poll::Pending;
+            }
+        }
+        let node = self.node.get();
+        // SAFETY: the queue is locked, and `self` is pinned so `node` is
+        // valid.
+        self.notifier
+            .0
+            .waiters
+            .with(|_| unsafe { (*node).notified = false });
+        Poll::Ready(())
+    }
+
+    /// Registers `waker` to be woken by the next notification, queueing
+    /// this waiter behind those which registered before it unless it's
+    /// already queued.
+    fn register(self: Pin<&mut Self>, waker: &Waker) {
+        let node = self.node.get();
+        let waker = waker.clone();
+        // The previous waker, if any, is dropped outside of the lock.
+        let _previous = self.notifier.0.waiters.with(|queue| {
+            // SAFETY: `self` is pinned, so `node` stays valid until it's
+            // unlinked by `drop`.
+            unsafe {
+                if !(*node).queued {
+                    queue.push_back(node);
+                }
+                // A notification which woke this waiter but didn't make it
+                // ready has been consumed by a reset.
+                (*node).notified = false;
+                (*node).waker.replace(waker)
+            }
+        });
+    }
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        let node = self.node.get();
+        let (_waker, next) = self.notifier.0.waiters.with(|queue| {
+            // SAFETY: the queue is locked and `node` is only linked into it
+            // while `queued` is set.
+            unsafe {
+                if (*node).queued {
+                    queue.remove(node);
+                }
+                // A notification from `notify_one` which this waiter didn't
+                // observe is passed on, so it isn't lost.
+                let next = if (*node).notified {
+                    queue.notify_front()
+                } else {
+                    None
+                };
+                ((*node).waker.take(), next)
+            }
+        });
+        if let Some(next) = next {
+            next.wake();
+        }
+    }
+}
+
+/// An intrusive, doubly-linked list of the [`Waiter`]s registered with a
+/// [`Notifier`], oldest first.
+struct WaiterQueue {
+    head: *mut WaiterNode,
+    tail: *mut WaiterNode,
+}
+
+// SAFETY: the nodes are only accessed while the queue is locked.
+unsafe impl Send for WaiterQueue {}
+
+impl Default for WaiterQueue {
+    fn default() -> WaiterQueue {
+        WaiterQueue {
+            head: ptr::null_mut(),
+            tail: ptr::null_mut(),
+        }
+    }
+}
+
+impl WaiterQueue {
+    /// # Safety
+    ///
+    /// `node` must be valid and not already queued.
+    unsafe fn push_back(&mut self, node: *mut WaiterNode) {
+        unsafe {
+            (*node).prev = self.tail;
+            (*node).next = ptr::null_mut();
+            (*node).queued = true;
+            match self.tail.as_mut() {
+                Some(tail) => tail.next = node,
+                None => self.head = node,
+            }
+        }
+        self.tail = node;
+    }
+
+    /// # Safety
+    ///
+    /// `node` must be queued in this queue.
+    unsafe fn remove(&mut self, node: *mut WaiterNode) {
+        unsafe {
+            let WaiterNode { prev, next, .. } = *node;
+            match prev.as_mut() {
+                Some(prev) => prev.next = next,
+                None => self.head = next,
+            }
+            match next.as_mut() {
+                Some(next) => next.prev = prev,
+                None => self.tail = prev,
+            }
+            (*node).prev = ptr::null_mut();
+            (*node).next = ptr::null_mut();
+            (*node).queued = false;
+        }
+    }
+
+    /// Unlinks the oldest waiter and marks it as woken by
+    /// [`Notifier::notify_one`], returning its waker.
+    fn notify_front(&mut self) -> Option<Waker> {
+        let node = self.head;
+        let waker = self.pop_front()?;
+        // SAFETY: `node` was queued, so it's still valid.
+        unsafe { (*node).notified = true };
+        Some(waker)
+    }
+
+    /// Unlinks the oldest waiter, returning its waker.
+    fn pop_front(&mut self) -> Option<Waker> {
+        let node = self.head;
+        if node.is_null() {
+            return None;
+        }
+        // SAFETY: queued nodes stay valid until they're unlinked, and a
+        // queued node always has a waker.
+        unsafe {
+            self.remove(node);
+            (*node).waker.take()
+        }
+    }
+}
+
+pub(crate) fn make_future<'a, T>(stream: &'a mut dyn Any) -> DynFuture<'a>
+where
+    T: Pollable,
+{
+    stream.downcast_mut::<T>().unwrap().ready()
+}
+
+pub(crate) fn make_owned_future<T>(pollee: &dyn Any) -> Option<DynFuture<'static>>
+where
+    T: Pollable,
+{
+    pollee.downcast_ref::<T>().unwrap().ready_owned()
+}
+
+pub(crate) fn describe_pollee<T>(pollee: &dyn Any) -> PolleeDescription
+where
+    T: Pollable,
+{
+    let pollee = pollee.downcast_ref::<T>().unwrap();
+    PolleeDescription {
+        type_name: pollee.type_name(),
+        readiness_kind: pollee.readiness_kind(),
+        debug_name: pollee.debug_name(),
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use alloc::string::{String, ToString};
+
+    #[test]
+    fn notifier_wakes_guest_blocked_in_poll() -> Result<()> {
+        use crate::bindings::wasi::io::poll::Host;
+        use crate::executor::{WakeSignal, block_on};
+
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let notifier = Notifier::new();
+        let mut table = ResourceTable::new();
+        let pending = Notifier::new().pollable(&mut table)?;
+        let pollable = notifier.pollable(&mut table)?;
+        let rep = pollable.rep();
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                notifier.notify_waiters();
+            });
+            let ready = block_on(
+                &SIGNAL,
+                |_| std::thread::yield_now(),
+                table.poll(vec![pending, pollable]),
+            )?;
+            assert_eq!(ready, [1]);
+            Ok::<_, anyhow::Error>(())
+        })?;
+
+        // Readiness is level-triggered until reset.
+        let pollable = Resource::<DynPollable>::new_borrow(rep);
+        assert!(notifier.is_notified());
+        let ready = block_on(&SIGNAL, |_| {}, table.poll(vec![pollable]))?;
+        assert_eq!(ready, [0]);
+        notifier.reset();
+        assert!(!notifier.is_notified());
+        Ok(())
+    }
+
+    /// A waker which records whether it was woken.
+    struct Flag(AtomicBool);
+
+    impl alloc::task::Wake for Flag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn notify_one_wakes_oldest_waiter() {
+        let notifier = Notifier::new();
+        let flags = [0, 1].map(|_| Arc::new(Flag(AtomicBool::new(false))));
+        let mut pollables = [notifier.clone(), notifier.clone()];
+        let mut futures: Vec<_> = pollables.iter_mut().map(|p| p.ready()).collect();
+        for (future, flag) in futures.iter_mut().zip(&flags) {
+            let waker = Waker::from(flag.clone());
+            let poll = future.as_mut().poll(&mut Context::from_waker(&waker));
+            assert!(poll.is_pending());
+        }
+
+        notifier.notify_one();
+        assert!(flags[0].0.load(Ordering::SeqCst));
+        assert!(!flags[1].0.load(Ordering::SeqCst));
+        notifier.notify_waiters();
+        assert!(flags[1].0.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn dropped_waiter_leaves_the_queue() {
+        let notifier = Notifier::new();
+        let flags = [0, 1, 2].map(|_| Arc::new(Flag(AtomicBool::new(false))));
+        let mut pollables = [notifier.clone(), notifier.clone(), notifier.clone()];
+        let mut futures: Vec<_> = pollables.iter_mut().map(|p| p.ready()).collect();
+        for (future, flag) in futures.iter_mut().zip(&flags) {
+            let waker = Waker::from(flag.clone());
+            let poll = future.as_mut().poll(&mut Context::from_waker(&waker));
+            assert!(poll.is_pending());
+        }
+
+        // Dropping the oldest and newest waiters unlinks them, so the one in
+        // the middle is next in line.
+        drop(futures.remove(2));
+        drop(futures.remove(0));
+        notifier.notify_one();
+        assert!(!flags[0].0.load(Ordering::SeqCst));
+        assert!(flags[1].0.load(Ordering::SeqCst));
+        assert!(!flags[2].0.load(Ordering::SeqCst));
+        notifier.0.waiters.with(|queue| {
+            assert!(queue.head.is_null());
+            assert!(queue.tail.is_null());
+        });
+    }
+
+    #[test]
+    fn dropped_notified_waiter_passes_notification_on() {
+        let notifier = Notifier::new();
+        let flags = [0, 1].map(|_| Arc::new(Flag(AtomicBool::new(false))));
+        let mut pollables = [notifier.clone(), notifier.clone()];
+        let mut futures: Vec<_> = pollables.iter_mut().map(|p| p.ready()).collect();
+        for (future, flag) in futures.iter_mut().zip(&flags) {
+            let waker = Waker::from(flag.clone());
+            let poll = future.as_mut().poll(&mut Context::from_waker(&waker));
+            assert!(poll.is_pending());
+        }
+
+        // The woken waiter is dropped without observing the notification,
+        // so the next one is woken instead.
+        notifier.notify_one();
+        assert!(flags[0].0.load(Ordering::SeqCst));
+        assert!(!flags[1].0.load(Ordering::SeqCst));
+        drop(futures.remove(0));
+        assert!(flags[1].0.load(Ordering::SeqCst));
+
+        // Once observed, the notification isn't passed on again.
+        let waker = Waker::from(flags[1].clone());
+        let poll = futures[0].as_mut().poll(&mut Context::from_waker(&waker));
+        assert!(poll.is_ready());
+        let flag = Arc::new(Flag(AtomicBool::new(false)));
+        let mut waiting = notifier.clone();
+        let mut future = waiting.ready();
+        notifier.reset();
+        let waker = Waker::from(flag.clone());
+        assert!(
+            future
+                .as_mut()
+                .poll(&mut Context::from_waker(&waker))
+                .is_pending()
+        );
+        drop(futures);
+        assert!(!flag.0.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn borrows_entries_mutably_at_once() {
+        let mut table = ResourceTable::new();
+        let a = table.push(1_u32).unwrap();
+        let b = table.push(String::from("b")).unwrap();
+
+        let indices = BTreeMap::from([(b.rep(), 'b'), (a.rep(), 'a')]);
+        with_entries(&mut table, indices, |entries| {
+            // Entries are provided in index order.
+            let keys: Vec<_> = entries.iter().map(|(_, k)| *k).collect();
+            assert_eq!(keys, ['a', 'b']);
+
+            let mut entries = entries.into_iter();
+            let a = entries.next().unwrap().0.unwrap();
+            let b = entries.next().unwrap().0.unwrap();
+            let a = a.downcast_mut::<u32>().unwrap();
+            let b = b.downcast_mut::<String>().unwrap();
+            *a += 1;
+            b.push_str(&a.to_string());
+        });
+        assert_eq!(*table.get(&a).unwrap(), 2);
+        assert_eq!(table.get(&b).unwrap(), "b2");
+    }
+
+    #[test]
+    fn duplicate_indices_are_grouped() {
+        let mut table = ResourceTable::new();
+        let a = table.push(0_u32).unwrap();
+
+        let mut indices = BTreeMap::<u32, Vec<usize>>::new();
+        for (i, rep) in [a.rep(), a.rep(), a.rep()].into_iter().enumerate() {
+            indices.entry(rep).or_default().push(i);
+        }
+        let n = with_entries(&mut table, indices, |entries| {
+            assert_eq!(entries.len(), 1);
+            let (entry, uses) = entries.into_iter().next().unwrap();
+            *entry.unwrap().downcast_mut::<u32>().unwrap() += uses.len() as u32;
+            uses
+        });
+        assert_eq!(n, [0, 1, 2]);
+        assert_eq!(*table.get(&a).unwrap(), 3);
+    }
+
+    #[test]
+    fn missing_indices_are_reported() {
+        let mut table = ResourceTable::new();
+        let a = table.push(0_u32).unwrap();
+        let gone = table.push(0_u32).unwrap();
+        let gone_rep = gone.rep();
+        table.delete(gone).unwrap();
+
+        let indices = BTreeMap::from([(a.rep(), ()), (gone_rep, ()), (100, ())]);
+        with_entries(&mut table, indices, |entries| {
+            let results: Vec<_> = entries
+                .into_iter()
+                .map(|(e, ())| match e {
+                    Ok(_) => None,
+                    Err(e) => Some(matches!(e, ResourceTableError::NotPresent)),
+                })
+                .collect();
+            assert_eq!(results, [None, Some(true), Some(true)]);
+        });
+    }
+
+    /// Returns each entry of `table` with its parent, and the pollee of
+    /// those which are pollables.
+    fn table_state(table: &ResourceTable) -> Vec<(u32, Option<u32>, Option<u32>)> {
+        table
+            .iter()
+            .map(|(index, parent, entry)| {
+                let pollee = entry.downcast_ref::<DynPollable>().map(|p| p.index);
+                (index, parent, pollee)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn batch_subscribe_matches_individual_subscribes() -> Result<()> {
+        let mut individual = ResourceTable::new();
+        let mut batch = ResourceTable::new();
+        let mut streams = Vec::new();
+        for _ in 0..5 {
+            streams.push(individual.push(Notifier::new())?.rep());
+            batch.push(Notifier::new())?;
+        }
+
+        let borrow = |rep: &u32| Resource::<Notifier>::new_borrow(*rep);
+        let one_by_one = streams
+            .iter()
+            .map(|rep| subscribe(&mut individual, borrow(rep)))
+            .collect::<Result<Vec<_>>>()?;
+        let batched = subscribe_batch(&mut batch, streams.iter().map(borrow).collect())?;
+
+        let reps = |pollables: &[Resource<DynPollable>]| {
+            pollables.iter().map(|p| p.rep()).collect::<Vec<_>>()
+        };
+        assert_eq!(reps(&one_by_one), reps(&batched));
+        assert_eq!(table_state(&individual), table_state(&batch));
+
+        // The pollables are children of their streams in both tables.
+        for rep in &streams {
+            let stream = Resource::<Notifier>::new_own(*rep);
+            assert!(individual.delete(stream).is_err());
+            let stream = Resource::<Notifier>::new_own(*rep);
+            assert!(batch.delete(stream).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn batch_subscribe_is_atomic() -> Result<()> {
+        let mut table = ResourceTable::new();
+        let a = table.push(Notifier::new())?;
+        let b = table.push(Notifier::new())?;
+        let gone = table.push(Notifier::new())?;
+        let gone_rep = gone.rep();
+        table.delete(gone)?;
+        let before = table_state(&table);
+
+        let streams = vec![
+            Resource::new_borrow(a.rep()),
+            Resource::new_borrow(gone_rep),
+            Resource::new_borrow(b.rep()),
+        ];
+        assert!(subscribe_batch::<Notifier>(&mut table, streams).is_err());
+        assert_eq!(table_state(&table), before);
+
+        // Nothing refers to the streams, so they can be deleted.
+        table.delete(a)?;
+        table.delete(b)?;
+        Ok(())
+    }
 }
@@ -0,0 +1,366 @@
+use super::{DynFuture, DynPollable, SpinLock, WakerList};
+use crate::TimerProvider;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use anyhow::Result;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+use wasmtime::component::{Resource, ResourceTable};
+
+/// The debouncing of a `pollable` whose readiness source flaps between ready
+/// and not ready, created by [`DebouncedPollable::wrap`].
+///
+/// Once the source is observed to be ready, a quiet period is started with a
+/// [`TimerProvider`], and the pollable only reports that it's ready once the
+/// quiet period has elapsed. The source isn't checked again in the meantime,
+/// so readiness accumulates over the quiet period: however often the source
+/// flaps during it, a guest waiting in `poll` is woken once, at its end. Once
+/// that readiness has been reported the next quiet period only starts when
+/// the source is observed to be ready again.
+///
+/// When a debounced pollable is passed to `poll` along with another pollable
+/// for the same source which doesn't share its debouncing, the source's
+/// readiness isn't debounced for that call, since the guest is woken by the
+/// other pollable regardless.
+pub struct DebouncedPollable {
+    quiet_period: Duration,
+    timers: Arc<dyn TimerProvider>,
+    /// The quiet period in progress, if the source has been observed to be
+    /// ready since readiness was last reported.
+    quiet: SpinLock<Option<Arc<QuietPeriod>>>,
+}
+
+/// A quiet period of a [`DebouncedPollable`], shared by all tasks waiting
+/// for it to elapse.
+struct QuietPeriod {
+    /// The period's timer, unless a task is polling it or it has elapsed.
+    timer: SpinLock<Option<DynFuture<'static>>>,
+    elapsed: AtomicBool,
+    /// The tasks waiting for the period to elapse, all of which are woken
+    /// when the timer wakes whichever task polled it last.
+    wakers: WakerList,
+}
+
+impl DebouncedPollable {
+    /// Debounces `inner` with a quiet period of `quiet_period`, as measured
+    /// by `timers`, returning the debounced pollable.
+    ///
+    /// The returned pollable takes the place of `inner`, keeping its handle,
+    /// its pollee and whether it owns the pollee, so a pollable which is only
+    /// handed out once can be debounced before the guest sees it. Pollables
+    /// created from it with [`DynPollable::duplicate`] share its debouncing.
+    /// Dropping it during a quiet period simply abandons the quiet period.
+    ///
+    /// A source which is deleted while the guest still holds a pollable for
+    /// it is ready, as for other pollables, and is reported as such once a
+    /// quiet period has elapsed.
+    pub fn wrap(
+        table: &mut ResourceTable,
+        inner: Resource<DynPollable>,
+        quiet_period: Duration,
+        timers: Arc<dyn TimerProvider>,
+    ) -> Result<Resource<DynPollable>> {
+        table.get_mut(&inner)?.debounce = Some(Arc::new(DebouncedPollable {
+            quiet_period,
+            timers,
+            quiet: SpinLock::new(None),
+        }));
+        Ok(inner)
+    }
+
+    /// Returns the quiet period of this pollable.
+    pub fn quiet_period(&self) -> Duration {
+        self.quiet_period
+    }
+
+    /// Debounces `ready`, the readiness of this pollable's source.
+    pub(crate) fn debounce<'a>(self: Arc<Self>, ready: DynFuture<'a>) -> DynFuture<'a> {
+        Box::pin(async move {
+            let period = match self.quiet.with(|quiet| quiet.clone()) {
+                Some(period) => period,
+                None => {
+                    ready.await;
+                    let period = Arc::new(QuietPeriod::new(self.timers.sleep(self.quiet_period)));
+                    // Another task may have started a quiet period while
+                    // this one waited for the source, in which case that
+                    // one is kept.
+                    let started = self.quiet.with(|quiet| match quiet {
+                        Some(started) => Some(started.clone()),
+                        None => {
+                            *quiet = Some(period.clone());
+                            None
+                        }
+                    });
+                    started.unwrap_or(period)
+                }
+            };
+            period.clone().elapsed().await;
+
+            // Readiness is now reported, so the next quiet period only starts
+            // once the source is observed to be ready again.
+            self.quiet.with(|quiet| {
+                if quiet.as_ref().is_some_and(|q| Arc::ptr_eq(q, &period)) {
+                    *quiet = None;
+                }
+            });
+        })
+    }
+
+    fn is_quiet(&self) -> bool {
+        self.quiet.with(|quiet| quiet.is_some())
+    }
+}
+
+impl QuietPeriod {
+    fn new(timer: DynFuture<'static>) -> QuietPeriod {
+        QuietPeriod {
+            timer: SpinLock::new(Some(timer)),
+            elapsed: AtomicBool::new(false),
+            wakers: WakerList::default(),
+        }
+    }
+
+    /// Waits for this quiet period to elapse.
+    async fn elapsed(self: Arc<Self>) {
+        core::future::poll_fn(|cx| {
+            if self.elapsed.load(Ordering::Acquire) {
+                return Poll::Ready(());
+            }
+            self.wakers.with(|list| {
+                if !list.iter().any(|w| w.will_wake(cx.waker())) {
+                    list.push(cx.waker().clone());
+                }
+            });
+
+            // The timer is polled outside of the lock, since it may wake
+            // other tasks. A task which finds it missing is woken, like any
+            // other, by the task polling it.
+            let Some(mut timer) = self.timer.with(Option::take) else {
+                return if self.elapsed.load(Ordering::Acquire) {
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                };
+            };
+            let waker = Waker::from(self.clone());
+            if timer
+                .as_mut()
+                .poll(&mut Context::from_waker(&waker))
+                .is_ready()
+            {
+                self.elapsed.store(true, Ordering::Release);
+                self.wake_all();
+                return Poll::Ready(());
+            }
+            self.timer.with(|slot| *slot = Some(timer));
+            Poll::Pending
+        })
+        .await
+    }
+
+    fn wake_all(&self) {
+        for waker in self.wakers.with(core::mem::take) {
+            waker.wake();
+        }
+    }
+}
+
+impl Wake for QuietPeriod {
+    fn wake(self: Arc<Self>) {
+        self.wake_all();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.wake_all();
+    }
+}
+
+impl fmt::Debug for DebouncedPollable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DebouncedPollable")
+            .field("quiet_period", &self.quiet_period)
+            .field("in_quiet_period", &self.is_quiet())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::bindings::wasi::io::poll::{Host as _, HostPollable};
+    use crate::executor::{WakeSignal, block_on};
+    use crate::poll::Notifier;
+    use crate::time::MockTimerProvider;
+
+    const QUIET: Duration = Duration::from_millis(10);
+    const TICK: Duration = Duration::from_millis(1);
+
+    fn debounced(
+        table: &mut ResourceTable,
+        source: &Notifier,
+        clock: &MockTimerProvider,
+    ) -> Result<Resource<DynPollable>> {
+        let inner = source.pollable(table)?;
+        DebouncedPollable::wrap(table, inner, QUIET, Arc::new(clock.clone()))
+    }
+
+    fn is_ready(table: &mut ResourceTable, pollable: &Resource<DynPollable>) -> Result<bool> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let pollable = Resource::new_borrow(pollable.rep());
+        block_on(&SIGNAL, |_| {}, HostPollable::ready(table, pollable))
+    }
+
+    #[test]
+    fn bursts_are_coalesced() -> Result<()> {
+        let clock = MockTimerProvider::new();
+        let source = Notifier::new();
+        let mut table = ResourceTable::new();
+        let pollable = debounced(&mut table, &source, &clock)?;
+
+        // A burst of flapping only starts a single quiet period.
+        for _ in 0..5 {
+            source.notify_waiters();
+            assert!(!is_ready(&mut table, &pollable)?);
+            source.reset();
+            clock.advance(TICK);
+        }
+        clock.advance(QUIET - 5 * TICK - TICK);
+        assert!(!is_ready(&mut table, &pollable)?);
+        clock.advance(TICK);
+        assert!(is_ready(&mut table, &pollable)?);
+
+        // The source has settled, so nothing more is reported until it's
+        // ready again.
+        assert!(!is_ready(&mut table, &pollable)?);
+        clock.advance(QUIET);
+        assert!(!is_ready(&mut table, &pollable)?);
+        Ok(())
+    }
+
+    #[test]
+    fn blocked_guest_is_woken_after_the_quiet_period() -> Result<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let clock = MockTimerProvider::new();
+        let source = Notifier::new();
+        let mut table = ResourceTable::new();
+        let pollable = debounced(&mut table, &source, &clock)?;
+
+        source.notify_waiters();
+        let mut ticks = 0;
+        let poll = table.poll(vec![pollable]);
+        let ready = block_on(
+            &SIGNAL,
+            |_| {
+                // The source keeps flapping while the guest waits.
+                ticks += 1;
+                source.reset();
+                source.notify_waiters();
+                clock.advance(TICK);
+            },
+            poll,
+        )?;
+        assert_eq!(ready, [0]);
+        assert_eq!(ticks * TICK, QUIET);
+        Ok(())
+    }
+
+    /// Timers which, when polled, poll the future in `during_poll`, as a
+    /// task on another thread might while the timer is being polled.
+    struct Reentrant {
+        clock: MockTimerProvider,
+        during_poll: Arc<std::sync::Mutex<Option<DynFuture<'static>>>>,
+        observed: Arc<std::sync::Mutex<Vec<bool>>>,
+    }
+
+    impl TimerProvider for Reentrant {
+        fn sleep(&self, duration: Duration) -> DynFuture<'static> {
+            let mut timer = self.clock.sleep(duration);
+            let during_poll = self.during_poll.clone();
+            let observed = self.observed.clone();
+            Box::pin(core::future::poll_fn(move |cx| {
+                // The lock is already held if this timer is being polled by
+                // the future in `during_poll` itself.
+                if let Ok(mut other) = during_poll.try_lock() {
+                    if let Some(other) = other.as_mut() {
+                        let ready = other.as_mut().poll(cx).is_ready();
+                        observed.lock().unwrap().push(ready);
+                    }
+                }
+                timer.as_mut().poll(cx)
+            }))
+        }
+    }
+
+    #[test]
+    fn concurrent_wait_keeps_the_quiet_period() -> Result<()> {
+        let clock = MockTimerProvider::new();
+        let during_poll = Arc::new(std::sync::Mutex::new(None));
+        let observed = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let timers = Arc::new(Reentrant {
+            clock: clock.clone(),
+            during_poll: during_poll.clone(),
+            observed: observed.clone(),
+        });
+        let source = Notifier::new();
+        let mut table = ResourceTable::new();
+        let inner = source.pollable(&mut table)?;
+        let pollable = DebouncedPollable::wrap(&mut table, inner, QUIET, timers)?;
+
+        source.notify_waiters();
+        assert!(!is_ready(&mut table, &pollable)?);
+
+        // Another wait for the same quiet period, which is polled whenever
+        // the period's timer is, doesn't see the period end early.
+        let debounce = table.get(&pollable)?.debounce.clone().unwrap();
+        let mut other = debounce.debounce(Box::pin(core::future::pending()));
+        let waker = futures::task::noop_waker_ref();
+        assert!(
+            other
+                .as_mut()
+                .poll(&mut Context::from_waker(waker))
+                .is_pending()
+        );
+        *during_poll.lock().unwrap() = Some(other);
+
+        clock.advance(TICK);
+        assert!(!is_ready(&mut table, &pollable)?);
+        assert_eq!(*observed.lock().unwrap(), [false]);
+
+        // Both waits see the period end once it has elapsed.
+        clock.advance(QUIET - TICK);
+        assert!(is_ready(&mut table, &pollable)?);
+        let mut other = during_poll.lock().unwrap().take().unwrap();
+        assert!(
+            other
+                .as_mut()
+                .poll(&mut Context::from_waker(waker))
+                .is_ready()
+        );
+        assert_eq!(*observed.lock().unwrap(), [false, false]);
+        Ok(())
+    }
+
+    #[test]
+    fn dropped_during_quiet_period() -> Result<()> {
+        let clock = MockTimerProvider::new();
+        let source = Notifier::new();
+        let mut table = ResourceTable::new();
+        let pollable = debounced(&mut table, &source, &clock)?;
+
+        source.notify_waiters();
+        assert!(!is_ready(&mut table, &pollable)?);
+        let debounce = table.get(&pollable)?.debounce.clone().unwrap();
+        assert!(debounce.is_quiet());
+
+        // The pollable owned its source, which is deleted with it, and the
+        // quiet period is abandoned.
+        HostPollable::drop(&mut table, pollable)?;
+        assert_eq!(table.iter().count(), 0);
+        assert_eq!(Arc::strong_count(&debounce), 1);
+        clock.advance(QUIET);
+        Ok(())
+    }
+}
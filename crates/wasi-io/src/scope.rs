@@ -0,0 +1,343 @@
+//! Tying the lifetime of host resources to a scope, such as a request.
+//!
+//! Embedders often create streams and pollables for the guest on behalf of
+//! some unit of work, for example one request handled by the guest, which
+//! must be torn down once that work ends even if the guest leaked the
+//! handles it was given. Resources pushed through an [`IoScope`] are tracked
+//! by it, and [`IoScope::close`] cancels and deletes those which the guest
+//! hasn't dropped yet.
+//!
+//! The guest's handles to closed resources stay valid: a closed stream is
+//! replaced by one on which every operation reports `closed`, and a closed
+//! pollable is always ready, so a guest which goes on using them observes
+//! the end of the stream rather than trapping. The placeholders are deleted
+//! once the guest drops its handles.
+//!
+//! Tracked resources are [children](crate::child) of an entry the scope
+//! owns in the [`ResourceTable`], which is how the scope knows which of them
+//! are still alive without being told about the guest's drops: a handle
+//! which the guest dropped and which now refers to an unrelated resource
+//! isn't a child of the scope's entry, and so isn't closed.
+
+use crate::poll::{DynFuture, DynPollable, PendingHint, Pollable, ReadinessKind, subscribe};
+use crate::snapshot::ClosedStream;
+use crate::streams::{DynInputStream, DynOutputStream};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use anyhow::Result;
+use core::mem;
+use wasmtime::component::{Resource, ResourceTable, ResourceTableError};
+
+/// A set of host resources which are closed together, see the [module
+/// documentation](self).
+///
+/// Dropping a scope without closing it leaves its resources, and its own
+/// entry, in the table.
+///
+/// # Example
+///
+/// ```
+/// use wasmtime::component::ResourceTable;
+/// use wasmtime_wasi_io::{IoScope, block_on, WakeSignal};
+/// # use wasmtime_wasi_io::{async_trait, bytes::Bytes, poll::Pollable};
+/// # use wasmtime_wasi_io::streams::{InputStream, StreamError, StreamResult};
+/// # struct Request;
+/// # #[async_trait]
+/// # impl Pollable for Request { async fn ready(&mut self) {} }
+/// # impl InputStream for Request {
+/// #     fn read(&mut self, _size: usize) -> StreamResult<Bytes> {
+/// #         Ok(Bytes::from_static(b"body"))
+/// #     }
+/// # }
+///
+/// let mut table = ResourceTable::new();
+/// let scope = IoScope::new(&mut table).unwrap();
+/// let body = scope.push_input_stream(&mut table, Box::new(Request)).unwrap();
+///
+/// // ... hand `body` to the guest, which may leak it ...
+///
+/// static SIGNAL: WakeSignal = WakeSignal::new();
+/// block_on(&SIGNAL, |_| {}, scope.close(&mut table)).unwrap();
+/// let body = table.get_mut(&body).unwrap();
+/// assert!(matches!(body.read(4), Err(StreamError::Closed)));
+/// ```
+#[derive(Debug)]
+pub struct IoScope {
+    anchor: u32,
+}
+
+/// The entry of an [`IoScope`] in the table, the parent of every resource
+/// it tracks.
+struct ScopeAnchor;
+
+/// The pollee of a pollable pushed with [`IoScope::push_pollable`], which is
+/// emptied, and then always ready, once the scope is closed.
+struct ScopedPollee(Option<Box<dyn Pollable>>);
+
+#[async_trait::async_trait]
+impl Pollable for ScopedPollee {
+    async fn ready(&mut self) {
+        if let Some(pollee) = &mut self.0 {
+            pollee.ready().await;
+        }
+    }
+
+    fn ready_owned(&self) -> Option<DynFuture<'static>> {
+        self.0.as_ref()?.ready_owned()
+    }
+
+    fn pending_hint(&self) -> Option<&PendingHint> {
+        self.0.as_ref()?.pending_hint()
+    }
+
+    fn readiness_kind(&self) -> ReadinessKind {
+        self.0
+            .as_ref()
+            .map_or(ReadinessKind::Level, |pollee| pollee.readiness_kind())
+    }
+
+    fn debug_name(&self) -> Option<String> {
+        self.0.as_ref()?.debug_name()
+    }
+
+    fn type_name(&self) -> &'static str {
+        match &self.0 {
+            Some(pollee) => pollee.type_name(),
+            None => core::any::type_name::<Self>(),
+        }
+    }
+}
+
+/// A resource taken out of the table by [`IoScope::close`].
+enum Closed {
+    Input(DynInputStream),
+    Output(DynOutputStream),
+    Pollee(Option<Box<dyn Pollable>>),
+}
+
+impl IoScope {
+    /// Creates a new scope with its entry in `table`.
+    pub fn new(table: &mut ResourceTable) -> Result<IoScope, ResourceTableError> {
+        let anchor = table.push(ScopeAnchor)?;
+        Ok(IoScope {
+            anchor: anchor.rep(),
+        })
+    }
+
+    /// Pushes `stream` into `table`, tracked by this scope.
+    pub fn push_input_stream(
+        &self,
+        table: &mut ResourceTable,
+        stream: DynInputStream,
+    ) -> Result<Resource<DynInputStream>, ResourceTableError> {
+        table.push_child(stream, &self.anchor())
+    }
+
+    /// Pushes `stream` into `table`, tracked by this scope.
+    pub fn push_output_stream(
+        &self,
+        table: &mut ResourceTable,
+        stream: DynOutputStream,
+    ) -> Result<Resource<DynOutputStream>, ResourceTableError> {
+        table.push_child(stream, &self.anchor())
+    }
+
+    /// Pushes `pollee` into `table`, tracked by this scope, and returns a
+    /// pollable subscribed to it.
+    ///
+    /// The pollable owns `pollee`, as with [`subscribe`], so `pollee` is
+    /// dropped along with it if the guest drops it before the scope is
+    /// closed.
+    pub fn push_pollable(
+        &self,
+        table: &mut ResourceTable,
+        pollee: impl Pollable,
+    ) -> Result<Resource<DynPollable>> {
+        let pollee = ScopedPollee(Some(Box::new(pollee)));
+        let pollee = table.push_child(pollee, &self.anchor())?;
+        subscribe(table, pollee)
+    }
+
+    /// Cancels and deletes every resource tracked by this scope which is
+    /// still in `table`.
+    ///
+    /// Each stream is replaced by a placeholder on which every operation
+    /// fails with [`StreamError::Closed`](crate::streams::StreamError::Closed)
+    /// and then cancelled, with cancellations awaited one after another.
+    /// Pollables pushed with [`IoScope::push_pollable`] drop their pollee and
+    /// are always ready from then on. Resources which the guest has already
+    /// dropped are skipped.
+    ///
+    /// The scope's own entry is deleted too, unless a closed
+    /// stream still has pollables, or a closed pollable is still held by the
+    /// guest: the placeholders of these remain children of the entry, which
+    /// then stays in `table` until the table is dropped.
+    pub async fn close(self, table: &mut ResourceTable) -> Result<()> {
+        let tracked: Vec<u32> = table
+            .iter()
+            .filter(|(_, parent, _)| *parent == Some(self.anchor))
+            .map(|(index, _, _)| index)
+            .collect();
+
+        let mut closed = Vec::with_capacity(tracked.len());
+        for index in tracked {
+            closed.push(close_entry(table, index)?);
+        }
+        match table.delete(Resource::<ScopeAnchor>::new_own(self.anchor)) {
+            Ok(ScopeAnchor) => {}
+            Err(ResourceTableError::HasChildren) => {
+                log::debug!("scope {} closed with placeholders in use", self.anchor);
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        for resource in closed {
+            match resource {
+                Closed::Input(mut stream) => stream.cancel().await,
+                Closed::Output(mut stream) => stream.cancel().await,
+                Closed::Pollee(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn anchor(&self) -> Resource<ScopeAnchor> {
+        Resource::new_borrow(self.anchor)
+    }
+}
+
+/// Takes the tracked resource at `index` out of `table`, leaving behind a
+/// placeholder which is no longer tracked if nothing refers to it.
+fn close_entry(table: &mut ResourceTable, index: u32) -> Result<Closed> {
+    let entry = table.get_any_mut(index)?;
+    let closed = if let Some(stream) = entry.downcast_mut::<DynInputStream>() {
+        let placeholder: DynInputStream = Box::new(ClosedStream);
+        Closed::Input(mem::replace(stream, placeholder))
+    } else if let Some(stream) = entry.downcast_mut::<DynOutputStream>() {
+        let placeholder: DynOutputStream = Box::new(ClosedStream);
+        Closed::Output(mem::replace(stream, placeholder))
+    } else if let Some(pollee) = entry.downcast_mut::<ScopedPollee>() {
+        let pollee = pollee.0.take();
+        // Pollables which last saw the pollee pending mustn't keep putting
+        // off checking it now that it's always ready.
+        if let Some(pollee) = &pollee {
+            pollee.mark_maybe_ready();
+        }
+        Closed::Pollee(pollee)
+    } else {
+        unreachable!("only streams and pollees are tracked by a scope");
+    };
+
+    // A placeholder which nothing refers to is re-inserted on its own, so it
+    // no longer keeps the scope's entry alive. Pollees are only reachable
+    // through their pollables and so don't need a placeholder at all.
+    match table.delete_any(index) {
+        Ok(_) => match &closed {
+            Closed::Input(_) => {
+                let placeholder: DynInputStream = Box::new(ClosedStream);
+                table.insert_at(index, placeholder, None)?;
+            }
+            Closed::Output(_) => {
+                let placeholder: DynOutputStream = Box::new(ClosedStream);
+                table.insert_at(index, placeholder, None)?;
+            }
+            Closed::Pollee(_) => {}
+        },
+        Err(ResourceTableError::HasChildren) => {}
+        Err(e) => return Err(e.into()),
+    }
+    Ok(closed)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::bindings::wasi::io::poll::HostPollable;
+    use crate::bindings::wasi::io::streams::{HostInputStream, HostOutputStream};
+    use crate::executor::{WakeSignal, block_on};
+    use crate::poll::Notifier;
+    use crate::streams::StreamError;
+    use crate::test_streams::Stalled;
+    use crate::{IoImpl, IoLinkOptions};
+
+    fn inp(rep: u32) -> Resource<DynInputStream> {
+        Resource::new_borrow(rep)
+    }
+
+    fn out(rep: u32) -> Resource<DynOutputStream> {
+        Resource::new_borrow(rep)
+    }
+
+    #[test]
+    fn close_leaked_resources() -> Result<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let stalled = Stalled::default();
+        let endless = || stalled.clone();
+        let options = IoLinkOptions::new();
+        let mut table = ResourceTable::new();
+        let scope = IoScope::new(&mut table)?;
+
+        let input = scope.push_input_stream(&mut table, Box::new(endless()))?;
+        let output = scope.push_output_stream(&mut table, Box::new(endless()))?;
+        let watched = scope.push_input_stream(&mut table, Box::new(endless()))?;
+        let dropped = scope.push_output_stream(&mut table, Box::new(endless()))?;
+        let notifier = Notifier::new();
+        let pollable = scope.push_pollable(&mut table, notifier.clone())?;
+        let (i, o, w, d) = (input.rep(), output.rep(), watched.rep(), dropped.rep());
+
+        // The guest reads and writes, subscribes to one stream, and drops
+        // another, whose handle is then reused by an unrelated stream.
+        let mut io = IoImpl::new(&mut table, &options);
+        assert_eq!(io.read(inp(i), 3)?, [1, 1, 1]);
+        assert_eq!(io.check_write(out(o))?, 1024);
+        let watcher = HostInputStream::subscribe(&mut io, inp(w))?;
+        block_on(&SIGNAL, |_| {}, HostOutputStream::drop(&mut io, dropped))?;
+        let unrelated = io.table.push(Box::new(endless()) as DynOutputStream)?;
+        assert_eq!(unrelated.rep(), d);
+        assert_eq!(stalled.cancellations(), 1);
+
+        block_on(&SIGNAL, |_| {}, scope.close(&mut table))?;
+        assert_eq!(stalled.cancellations(), 4);
+
+        // The guest's leaked handles now observe closure.
+        let mut io = IoImpl::new(&mut table, &options);
+        assert!(matches!(io.read(inp(i), 3), Err(StreamError::Closed)));
+        assert!(matches!(io.check_write(out(o)), Err(StreamError::Closed)));
+        let read = block_on(&SIGNAL, |_| {}, io.blocking_read(inp(w), 3));
+        assert!(matches!(read, Err(StreamError::Closed)));
+        let ready = Resource::new_borrow(watcher.rep());
+        block_on(&SIGNAL, |_| {}, HostPollable::block(&mut io, ready))?;
+        let ready = Resource::new_borrow(pollable.rep());
+        block_on(&SIGNAL, |_| {}, HostPollable::block(&mut io, ready))?;
+        assert!(!notifier.is_notified());
+        assert_eq!(io.check_write(out(d))?, 1024);
+
+        // Dropping the placeholders leaves only the unrelated stream and the
+        // scope's entry, which the guest's pollables kept alive.
+        block_on(&SIGNAL, |_| {}, HostInputStream::drop(&mut io, input))?;
+        block_on(&SIGNAL, |_| {}, HostOutputStream::drop(&mut io, output))?;
+        HostPollable::drop(&mut io, watcher)?;
+        block_on(&SIGNAL, |_| {}, HostInputStream::drop(&mut io, watched))?;
+        HostPollable::drop(&mut io, pollable)?;
+        assert_eq!(io.table.iter().count(), 2);
+        assert_eq!(stalled.cancellations(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn close_deletes_unused_scope() -> Result<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let stalled = Stalled::default();
+        let mut table = ResourceTable::new();
+        let scope = IoScope::new(&mut table)?;
+        let input = scope.push_input_stream(&mut table, Box::new(stalled.clone()))?;
+
+        block_on(&SIGNAL, |_| {}, scope.close(&mut table))?;
+        assert_eq!(stalled.cancellations(), 1);
+        assert_eq!(table.iter().count(), 1);
+        table.delete(input)?;
+        assert_eq!(table.iter().count(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,405 @@
+//! Saving and restoring the wasi-io resources of a [`ResourceTable`].
+//!
+//! Embedders which snapshot a running store, including the contents of its
+//! [`ResourceTable`], and restore it elsewhere can't serialize the streams and
+//! pollables in the table since they're opaque host objects. Instead
+//! [`snapshot_io`] records an [`IoSnapshotManifest`] describing all of the
+//! resources owned by wasi-io, and [`restore_io`] recreates them at the same
+//! indices of another table so that handles held by the guest stay valid.
+//!
+//! Streams take part by implementing [`SnapshotableStream`] and returning it
+//! from [`InputStream::as_snapshotable`] or
+//! [`OutputStream::as_snapshotable`]. They're recreated by a
+//! [`SnapshotResolver`] provided to [`restore_io`]. Streams which can't be
+//! saved or resolved are restored as closed streams, and pollables of
+//! resources which aren't wasi-io streams are restored as always ready.
+
+use crate::child::remove_index;
+use crate::poll::{
+    DynPollable, Pollable, PolleeDescription, ReadinessKind, describe_pollee, make_future,
+    make_owned_future,
+};
+use crate::streams::{
+    DynInputStream, DynOutputStream, Error, InputStream, OutputStream, StreamError, StreamResult,
+};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use anyhow::{Result, anyhow};
+use bytes::Bytes;
+use wasmtime::component::{ResourceTable, ResourceTableError};
+
+/// The saved state of a stream, as returned by [`SnapshotableStream::save`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_derive::Serialize, serde_derive::Deserialize)
+)]
+pub struct StreamSnapshot {
+    /// An identifier for the type of stream, used by a [`SnapshotResolver`]
+    /// to decide how to restore it.
+    pub kind: String,
+    /// The stream's state, in a format defined by its `kind`.
+    pub data: Vec<u8>,
+}
+
+/// A stream whose state can be saved by [`snapshot_io`].
+pub trait SnapshotableStream {
+    /// Saves the state of this stream, or returns `None` if it can't be saved
+    /// in its current state.
+    fn save(&self) -> Option<StreamSnapshot>;
+}
+
+/// Recreates streams saved with [`SnapshotableStream::save`] during
+/// [`restore_io`].
+///
+/// Returning `None` from either method restores the stream as a closed
+/// stream.
+pub trait SnapshotResolver {
+    /// Recreates an input stream from its snapshot.
+    fn restore_input(&mut self, snapshot: StreamSnapshot) -> Option<DynInputStream> {
+        let _ = snapshot;
+        None
+    }
+
+    /// Recreates an output stream from its snapshot.
+    fn restore_output(&mut self, snapshot: StreamSnapshot) -> Option<DynOutputStream> {
+        let _ = snapshot;
+        None
+    }
+}
+
+/// The wasi-io resources of a [`ResourceTable`], as recorded by
+/// [`snapshot_io`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_derive::Serialize, serde_derive::Deserialize)
+)]
+pub struct IoSnapshotManifest {
+    entries: Vec<IoSnapshotEntry>,
+}
+
+impl IoSnapshotManifest {
+    /// Returns the recorded resources, in index order.
+    pub fn entries(&self) -> &[IoSnapshotEntry] {
+        &self.entries
+    }
+}
+
+/// A single resource recorded in an [`IoSnapshotManifest`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_derive::Serialize, serde_derive::Deserialize)
+)]
+pub struct IoSnapshotEntry {
+    /// The index of this resource in the table.
+    pub index: u32,
+    /// The index of this resource's parent in the table, if it has one.
+    pub parent: Option<u32>,
+    /// The saved resource.
+    pub resource: IoResourceSnapshot,
+}
+
+/// The saved state of a wasi-io resource.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_derive::Serialize, serde_derive::Deserialize)
+)]
+pub enum IoResourceSnapshot {
+    /// An `input-stream`, and its saved state if it could be saved.
+    InputStream(Option<StreamSnapshot>),
+    /// An `output-stream`, and its saved state if it could be saved.
+    OutputStream(Option<StreamSnapshot>),
+    /// A `pollable` subscribed to the resource at index `pollee`.
+    Pollable {
+        /// The index of the resource this pollable is subscribed to.
+        pollee: u32,
+        /// Whether deleting the pollable also deletes `pollee`.
+        owns_pollee: bool,
+    },
+    /// An `error`, saved as its debug representation.
+    Error(String),
+}
+
+/// Records all wasi-io resources in `table`.
+///
+/// Resources which aren't owned by wasi-io are skipped and must be saved and
+/// restored by the embedder.
+pub fn snapshot_io(table: &ResourceTable) -> IoSnapshotManifest {
+    let entries = table
+        .iter()
+        .filter_map(|(index, parent, entry)| {
+            let resource = if let Some(stream) = entry.downcast_ref::<DynInputStream>() {
+                IoResourceSnapshot::InputStream(stream.as_snapshotable().and_then(|s| s.save()))
+            } else if let Some(stream) = entry.downcast_ref::<DynOutputStream>() {
+                IoResourceSnapshot::OutputStream(stream.as_snapshotable().and_then(|s| s.save()))
+            } else if let Some(pollable) = entry.downcast_ref::<DynPollable>() {
+                IoResourceSnapshot::Pollable {
+                    pollee: pollable.index,
+                    owns_pollee: pollable.remove_index_on_delete.is_some(),
+                }
+            } else if let Some(error) = entry.downcast_ref::<Error>() {
+                IoResourceSnapshot::Error(alloc::format!("{error:?}"))
+            } else {
+                return None;
+            };
+            Some(IoSnapshotEntry {
+                index,
+                parent,
+                resource,
+            })
+        })
+        .collect();
+    IoSnapshotManifest { entries }
+}
+
+/// Recreates the resources recorded in `manifest` at their original indices
+/// in `table`.
+///
+/// Parents of the recorded resources which aren't themselves recorded in
+/// `manifest`, such as the resources which non-stream pollables are
+/// subscribed to, must already have been restored in `table`. Returns an
+/// error if one is missing or if an index is already in use.
+pub fn restore_io(
+    table: &mut ResourceTable,
+    manifest: IoSnapshotManifest,
+    resolver: &mut dyn SnapshotResolver,
+) -> Result<()> {
+    // Pollables are recreated based on the type of stream they're subscribed
+    // to, recorded here as whether it's an input stream.
+    let mut streams = BTreeMap::new();
+    for entry in manifest.entries.iter() {
+        match entry.resource {
+            IoResourceSnapshot::InputStream(_) => {
+                streams.insert(entry.index, true);
+            }
+            IoResourceSnapshot::OutputStream(_) => {
+                streams.insert(entry.index, false);
+            }
+            _ => {}
+        }
+    }
+
+    // Entries may refer to parents at higher indices, so insert them in as
+    // many passes as it takes for every parent to be present.
+    let mut pending = manifest.entries;
+    let mut owners = BTreeMap::new();
+    while !pending.is_empty() {
+        let (ready, rest): (Vec<_>, Vec<_>) = pending.into_iter().partition(|e| match e.parent {
+            Some(parent) => table.get_any_mut(parent).is_ok(),
+            None => true,
+        });
+        if ready.is_empty() {
+            let missing = rest[0].parent.unwrap();
+            return Err(anyhow!(ResourceTableError::NotPresent)
+                .context(alloc::format!("parent {missing} of restored resource")));
+        }
+        for entry in ready {
+            restore_entry(table, entry, &streams, &mut owners, resolver)?;
+        }
+        pending = rest;
+    }
+    Ok(())
+}
+
+fn restore_entry(
+    table: &mut ResourceTable,
+    entry: IoSnapshotEntry,
+    streams: &BTreeMap<u32, bool>,
+    owners: &mut BTreeMap<u32, Arc<()>>,
+    resolver: &mut dyn SnapshotResolver,
+) -> Result<()> {
+    let IoSnapshotEntry {
+        index,
+        parent,
+        resource,
+    } = entry;
+    match resource {
+        IoResourceSnapshot::InputStream(snapshot) => {
+            let stream = snapshot
+                .and_then(|s| resolver.restore_input(s))
+                .unwrap_or_else(|| Box::new(ClosedStream));
+            table.insert_at(index, stream, parent)?;
+        }
+        IoResourceSnapshot::OutputStream(snapshot) => {
+            let stream = snapshot
+                .and_then(|s| resolver.restore_output(s))
+                .unwrap_or_else(|| Box::new(ClosedStream));
+            table.insert_at(index, stream, parent)?;
+        }
+        IoResourceSnapshot::Pollable {
+            pollee,
+            owns_pollee,
+        } => {
+            // Pollables which own the same pollee were duplicates of one
+            // another, and so share its ownership again.
+            let owners = owns_pollee.then(|| owners.entry(pollee).or_default().clone());
+            let pollable = match streams.get(&pollee) {
+                Some(true) => DynPollable {
+                    index: pollee,
+                    make_future: make_future::<DynInputStream>,
+                    make_owned_future: make_owned_future::<DynInputStream>,
+                    remove_index_on_delete: owns_pollee.then_some(remove_index::<DynInputStream>),
+                    last_known_pending: None,
+                    owners,
+                    debounce: None,
+                    describe: describe_pollee::<DynInputStream>,
+                },
+                Some(false) => DynPollable {
+                    index: pollee,
+                    make_future: make_future::<DynOutputStream>,
+                    make_owned_future: make_owned_future::<DynOutputStream>,
+                    remove_index_on_delete: owns_pollee.then_some(remove_index::<DynOutputStream>),
+                    last_known_pending: None,
+                    owners,
+                    debounce: None,
+                    describe: describe_pollee::<DynOutputStream>,
+                },
+                // The type of the pollee isn't known, so the pollable is made
+                // to refer to itself and is always ready.
+                _ => DynPollable {
+                    index,
+                    make_future: |_| Box::pin(async {}),
+                    make_owned_future: |_| None,
+                    remove_index_on_delete: None,
+                    last_known_pending: None,
+                    owners: None,
+                    debounce: None,
+                    describe: |_| PolleeDescription {
+                        type_name: "unknown",
+                        readiness_kind: ReadinessKind::Level,
+                        debug_name: None,
+                    },
+                },
+            };
+            table.insert_at(index, pollable, parent)?;
+        }
+        IoResourceSnapshot::Error(msg) => {
+            let error: Error = anyhow!(msg);
+            table.insert_at(index, error, parent)?;
+        }
+    }
+    Ok(())
+}
+
+/// The stream which takes the place of streams that couldn't be restored.
+pub(crate) struct ClosedStream;
+
+#[async_trait::async_trait]
+impl Pollable for ClosedStream {
+    async fn ready(&mut self) {}
+}
+
+#[async_trait::async_trait]
+impl InputStream for ClosedStream {
+    fn read(&mut self, _size: usize) -> StreamResult<Bytes> {
+        Err(StreamError::Closed)
+    }
+
+    fn is_closed(&self) -> bool {
+        true
+    }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
+}
+
+#[async_trait::async_trait]
+impl OutputStream for ClosedStream {
+    fn write(&mut self, _bytes: Bytes) -> StreamResult<()> {
+        Err(StreamError::Closed)
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        Err(StreamError::Closed)
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        Err(StreamError::Closed)
+    }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::poll::subscribe;
+    use wasmtime::component::Resource;
+
+    /// A stream which can't be saved.
+    struct Opaque;
+
+    #[async_trait::async_trait]
+    impl Pollable for Opaque {
+        async fn ready(&mut self) {}
+    }
+
+    #[async_trait::async_trait]
+    impl InputStream for Opaque {
+        fn read(&mut self, _size: usize) -> StreamResult<Bytes> {
+            Ok(Bytes::from_static(b"opaque"))
+        }
+    }
+
+    struct NoResolver;
+    impl SnapshotResolver for NoResolver {}
+
+    #[test]
+    fn unsaved_resources_become_placeholders() -> Result<()> {
+        let mut table = ResourceTable::new();
+        let other = table.push(())?;
+        let stream = table.push(Box::new(Opaque) as DynInputStream)?;
+        let stream_rep = stream.rep();
+        let pollable = subscribe(&mut table, stream)?;
+        let opaque = table.push(Opaque)?;
+        let opaque_rep = opaque.rep();
+        let unknown = subscribe(&mut table, opaque)?;
+        let unknown_rep = unknown.rep();
+        let error = table.push(anyhow!("boom"))?;
+
+        let manifest = snapshot_io(&table);
+        assert_eq!(manifest.entries().len(), 4);
+        assert!(manifest.entries().iter().all(|e| e.index != other.rep()));
+
+        // The resource which `unknown` is subscribed to isn't owned by
+        // wasi-io, so it must be restored first.
+        let mut restored = ResourceTable::new();
+        restored.insert_at(opaque_rep, Opaque, None)?;
+        restore_io(&mut restored, manifest, &mut NoResolver)?;
+
+        let stream = restored.get_mut(&Resource::<DynInputStream>::new_own(stream_rep))?;
+        assert!(matches!(stream.read(10), Err(StreamError::Closed)));
+
+        let pollable = restored.get(&Resource::<DynPollable>::new_own(pollable.rep()))?;
+        assert_eq!(pollable.index, stream_rep);
+        assert!(pollable.remove_index_on_delete.is_some());
+
+        let unknown = restored.get(&Resource::<DynPollable>::new_own(unknown_rep))?;
+        assert_eq!(unknown.index, unknown_rep);
+        assert!(unknown.remove_index_on_delete.is_none());
+
+        let error = restored.get(&Resource::<Error>::new_own(error.rep()))?;
+        assert!(alloc::format!("{error:?}").contains("boom"));
+        Ok(())
+    }
+
+    #[test]
+    fn missing_parent_is_an_error() -> Result<()> {
+        let mut table = ResourceTable::new();
+        let parent = table.push(())?;
+        table.push_child(Box::new(Opaque) as DynInputStream, &parent)?;
+        let manifest = snapshot_io(&table);
+        let err = restore_io(&mut ResourceTable::new(), manifest, &mut NoResolver).unwrap_err();
+        assert!(err.to_string().contains("parent 0"), "{err}");
+        Ok(())
+    }
+}
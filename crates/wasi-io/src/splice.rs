@@ -0,0 +1,262 @@
+//! Progress reporting for splices between streams.
+//!
+//! Guests copy between streams with loops of `splice` or `blocking-splice`
+//! calls, each of which transfers at most [`IoLinkOptions::max_len`] bytes,
+//! so the host only ever sees a single call at a time. A [`SpliceObserver`]
+//! configured with [`IoLinkOptions::splice_observer`] is told about every
+//! splice which transfers bytes, along with the total transferred between the
+//! same pair of streams so far, which is enough to report the progress of a
+//! long transfer.
+//!
+//! Totals are kept per pair of streams, identified by the
+//! [`rep`](wasmtime::component::Resource::rep) of their handles, and are
+//! forgotten once the guest drops either stream. Streams which are deleted
+//! by the host instead, for example with
+//! [`delete_parent`](crate::child::delete_parent), keep their totals until a
+//! stream reusing their handle is dropped.
+//!
+//! Handles are only unique within a store, so totals belong to the
+//! [`IoLinkOptions`](crate::IoLinkOptions) of a single store. Clones of the
+//! options start without any totals, and each store should use its own
+//! clone.
+//!
+//! [`IoLinkOptions::max_len`]: crate::IoLinkOptions::max_len
+//! [`IoLinkOptions::splice_observer`]: crate::IoLinkOptions::splice_observer
+
+use crate::poll::SpinLock;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use core::fmt;
+use wasmtime::component::ResourceTable;
+
+/// The bytes transferred by a single splice, passed to a [`SpliceObserver`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpliceProgress {
+    /// The handle of the input stream spliced from.
+    pub src_rep: u32,
+    /// The handle of the output stream spliced to.
+    pub dest_rep: u32,
+    /// The number of bytes transferred by this splice.
+    pub bytes: u64,
+    /// The number of bytes transferred from `src_rep` to `dest_rep` so far,
+    /// including those of this splice.
+    pub cumulative_bytes: u64,
+}
+
+/// Receives the progress of splices, configured with
+/// [`IoLinkOptions::splice_observer`](crate::IoLinkOptions::splice_observer).
+pub trait SpliceObserver: Send + Sync {
+    /// Called after a `splice` or `blocking-splice` has transferred bytes.
+    ///
+    /// The observer is handed the guest's `table` with no borrows of it
+    /// outstanding, so it may inspect the streams, for example with the
+    /// functions of the [`io`](crate::io) module. Splices which transfer no
+    /// bytes, or which fail, aren't reported.
+    fn spliced(&self, table: &mut ResourceTable, progress: SpliceProgress);
+}
+
+/// A [`SpliceObserver`] along with the totals of the pairs of streams it has
+/// been told about.
+///
+/// Clones share the observer, but start without any totals.
+pub(crate) struct SpliceTracker {
+    observer: Arc<dyn SpliceObserver>,
+    totals: Arc<SpinLock<BTreeMap<(u32, u32), u64>>>,
+}
+
+impl SpliceTracker {
+    pub(crate) fn new(observer: Arc<dyn SpliceObserver>) -> SpliceTracker {
+        SpliceTracker {
+            observer,
+            totals: Arc::new(SpinLock::new(BTreeMap::new())),
+        }
+    }
+
+    /// Records that `bytes` were spliced from `src_rep` to `dest_rep` and
+    /// tells the observer.
+    pub(crate) fn spliced(
+        &self,
+        table: &mut ResourceTable,
+        src_rep: u32,
+        dest_rep: u32,
+        bytes: u64,
+    ) {
+        if bytes == 0 {
+            return;
+        }
+        let cumulative_bytes = self.totals.with(|totals| {
+            let total = totals.entry((src_rep, dest_rep)).or_insert(0);
+            *total = total.saturating_add(bytes);
+            *total
+        });
+        self.observer.spliced(
+            table,
+            SpliceProgress {
+                src_rep,
+                dest_rep,
+                bytes,
+                cumulative_bytes,
+            },
+        );
+    }
+
+    /// Forgets the totals of the pairs of streams which include `rep`, which
+    /// the guest is dropping.
+    pub(crate) fn forget(&self, rep: u32) {
+        self.totals.with(|totals| {
+            totals.retain(|(src, dest), _| *src != rep && *dest != rep);
+        });
+    }
+
+    #[cfg(all(test, feature = "std"))]
+    fn tracked_pairs(&self) -> usize {
+        self.totals.with(|totals| totals.len())
+    }
+}
+
+impl Clone for SpliceTracker {
+    fn clone(&self) -> SpliceTracker {
+        SpliceTracker::new(self.observer.clone())
+    }
+}
+
+impl fmt::Debug for SpliceTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpliceTracker").finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::bindings::wasi::io::streams::{HostInputStream, HostOutputStream};
+    use crate::executor::{WakeSignal, block_on};
+    use crate::streams::{DynInputStream, DynOutputStream};
+    use crate::test_streams::{Discard, Zeroes};
+    use crate::{IoImpl, IoLinkOptions};
+    use alloc::boxed::Box;
+    use alloc::vec::Vec;
+    use std::sync::Mutex;
+    use wasmtime::component::Resource;
+
+    /// Records the progress it's told about, along with the permit of the
+    /// destination at that point.
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(SpliceProgress, usize)>>);
+
+    impl SpliceObserver for Recorder {
+        fn spliced(&self, table: &mut ResourceTable, progress: SpliceProgress) {
+            let dest = Resource::new_borrow(progress.dest_rep);
+            let permit = crate::io::check_write(table, &dest).unwrap();
+            self.0.lock().unwrap().push((progress, permit));
+        }
+    }
+
+    fn progress(src_rep: u32, dest_rep: u32, bytes: u64, cumulative_bytes: u64) -> SpliceProgress {
+        SpliceProgress {
+            src_rep,
+            dest_rep,
+            bytes,
+            cumulative_bytes,
+        }
+    }
+
+    fn inp(rep: u32) -> Resource<DynInputStream> {
+        Resource::new_borrow(rep)
+    }
+
+    fn out(rep: u32) -> Resource<DynOutputStream> {
+        Resource::new_borrow(rep)
+    }
+
+    #[test]
+    fn cumulative_progress_per_pair() -> anyhow::Result<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let recorder = Arc::new(Recorder::default());
+        let mut options = IoLinkOptions::new();
+        options.splice_observer(recorder.clone());
+        let mut table = ResourceTable::new();
+        let src = table.push(Box::new(Zeroes) as DynInputStream)?;
+        let dest = table.push(Box::new(Discard) as DynOutputStream)?;
+        let other = table.push(Box::new(Discard) as DynOutputStream)?;
+        let (s, d, o) = (src.rep(), dest.rep(), other.rep());
+
+        let mut io = IoImpl::new(&mut table, &options);
+        assert_eq!(io.splice(out(d), inp(s), 100)?, 100);
+        assert_eq!(io.splice(out(d), inp(s), 0)?, 0);
+        assert_eq!(
+            block_on(&SIGNAL, |_| {}, io.blocking_splice(out(d), inp(s), 5000))?,
+            1024
+        );
+        assert_eq!(io.splice(out(o), inp(s), 10)?, 10);
+        assert_eq!(io.splice(out(d), inp(s), 20)?, 20);
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [
+                (progress(s, d, 100, 100), 1024),
+                (progress(s, d, 1024, 1124), 1024),
+                (progress(s, o, 10, 10), 1024),
+                (progress(s, d, 20, 1144), 1024),
+            ]
+        );
+
+        // Dropping the destination forgets its pair only, and a stream
+        // which reuses its handle starts from zero.
+        let tracker = options.splice_tracker.as_ref().unwrap();
+        assert_eq!(tracker.tracked_pairs(), 2);
+        block_on(&SIGNAL, |_| {}, HostOutputStream::drop(&mut io, dest))?;
+        assert_eq!(tracker.tracked_pairs(), 1);
+        let dest = io.table.push(Box::new(Discard) as DynOutputStream)?;
+        assert_eq!(dest.rep(), d);
+        recorder.0.lock().unwrap().clear();
+        io.splice(out(d), inp(s), 7)?;
+        assert_eq!(*recorder.0.lock().unwrap(), [(progress(s, d, 7, 7), 1024)]);
+
+        // Dropping the source forgets every pair it's part of.
+        block_on(&SIGNAL, |_| {}, HostInputStream::drop(&mut io, src))?;
+        assert_eq!(tracker.tracked_pairs(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn stores_track_their_own_totals() -> anyhow::Result<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let recorder = Arc::new(Recorder::default());
+        let mut options = IoLinkOptions::new();
+        options.splice_observer(recorder.clone());
+
+        // Two stores whose streams have the same handles.
+        let stores = [options.clone(), options.clone()].map(|options| {
+            let mut table = ResourceTable::new();
+            let src = table.push(Box::new(Zeroes) as DynInputStream).unwrap();
+            let dest = table.push(Box::new(Discard) as DynOutputStream).unwrap();
+            (table, options, src, dest)
+        });
+        let [
+            (mut table_a, options_a, src_a, dest_a),
+            (mut table_b, options_b, src_b, _),
+        ] = stores;
+        let (s, d) = (src_a.rep(), dest_a.rep());
+        assert_eq!((src_b.rep(), d), (s, d));
+
+        IoImpl::new(&mut table_a, &options_a).splice(out(d), inp(s), 10)?;
+        IoImpl::new(&mut table_b, &options_b).splice(out(d), inp(s), 3)?;
+        IoImpl::new(&mut table_a, &options_a).splice(out(d), inp(s), 10)?;
+
+        // Dropping a stream in one store leaves the other's totals alone.
+        let mut io = IoImpl::new(&mut table_a, &options_a);
+        block_on(&SIGNAL, |_| {}, HostInputStream::drop(&mut io, src_a))?;
+        IoImpl::new(&mut table_b, &options_b).splice(out(d), inp(s), 4)?;
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [
+                (progress(s, d, 10, 10), 1024),
+                (progress(s, d, 3, 3), 1024),
+                (progress(s, d, 10, 20), 1024),
+                (progress(s, d, 4, 7), 1024),
+            ]
+        );
+        assert_eq!(options.splice_tracker.unwrap().tracked_pairs(), 0);
+        Ok(())
+    }
+}
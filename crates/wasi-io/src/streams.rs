@@ -1,7 +1,51 @@
-use crate::poll::Pollable;
+use crate::accounting::MemoryAccountant;
+use crate::poll::{DynFuture, PendingHint, Pollable, ReadinessKind};
+use crate::snapshot::SnapshotableStream;
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
 use anyhow::Result;
 use bytes::Bytes;
+use core::any::Any;
+
+mod buffer_pool;
+mod channel;
+#[cfg(feature = "std")]
+mod file;
+#[cfg(feature = "std")]
+mod flush_group;
+mod idle_timeout;
+#[cfg(feature = "std")]
+mod multiplex;
+mod priority;
+mod read_ahead;
+mod retry;
+#[cfg(feature = "std")]
+mod shared;
+mod transcode;
+mod watermarks;
+pub use buffer_pool::{BufferPool, BufferPoolStats, PooledBuffer};
+pub use channel::{
+    Framing, HostReceiver, HostSender, TryRecvError, TrySendError, channel, channel_with_framing,
+    output_channel, output_channel_with_framing,
+};
+#[cfg(feature = "std")]
+pub use file::{BlockInPlace, BlockingExecutor, FileInputStream, FileOutputStream, SyncPolicy};
+#[cfg(feature = "std")]
+pub use flush_group::{FlushGroup, FlushGroupStream};
+pub use idle_timeout::IdleTimeoutStream;
+#[cfg(feature = "std")]
+pub use multiplex::Multiplexer;
+pub use priority::{Evictions, Priority, PriorityOutputStream};
+pub use read_ahead::ReadAheadInputStream;
+pub use retry::{RetryPolicy, RetryingInputStream};
+#[cfg(feature = "std")]
+pub use shared::{SharedOutputHandle, SharedOutputStream};
+pub use transcode::{
+    Base64DecodeInputStream, Base64EncodeOutputStream, HexDecodeInputStream, HexEncodeOutputStream,
+};
+pub use watermarks::{SharedWatermarks, Tunable, WatermarkHandle, check_watermarks};
 
 /// `Pollable::ready()` for `InputStream` and `OutputStream` may return
 /// prematurely due to `io::ErrorKind::WouldBlock`.
@@ -14,6 +58,13 @@ const MAX_BLOCKING_ATTEMPTS: u8 = 10;
 
 /// Host trait for implementing the `wasi:io/streams.input-stream` resource: A
 /// bytestream which can be read from.
+///
+/// Guest reads and skips of zero bytes are completed by the host without
+/// reading from the stream, so implementations needn't handle a `size` or
+/// `nelem` of zero, or `lens` which add up to zero, on their behalf. Such
+/// operations only ask [`is_closed`](InputStream::is_closed) whether to fail
+/// with [`StreamError::Closed`]. Host code calling these methods directly may
+/// still pass zero.
 #[async_trait::async_trait]
 pub trait InputStream: Pollable {
     /// Reads up to `size` bytes, returning a buffer holding these bytes on
@@ -30,6 +81,18 @@ pub trait InputStream: Pollable {
     /// closed, when a read fails, or when a trap should be generated.
     fn read(&mut self, size: usize) -> StreamResult<Bytes>;
 
+    /// Returns whether this stream is closed, so that its next read would
+    /// fail with [`StreamError::Closed`] rather than return any more bytes.
+    ///
+    /// Guest reads and skips of zero bytes fail with `closed` if this returns
+    /// true, and otherwise succeed without reading. The default
+    /// implementation returns false, so a closed stream which doesn't
+    /// override it reports its closure on the guest's next non-empty read
+    /// instead.
+    fn is_closed(&self) -> bool {
+        false
+    }
+
     /// Similar to `read`, except that it blocks until at least one byte can be
     /// read.
     async fn blocking_read(&mut self, size: usize) -> StreamResult<Bytes> {
@@ -69,10 +132,107 @@ pub trait InputStream: Pollable {
         Ok(bs.len())
     }
 
+    /// Reads up to the sum of `lens` bytes, returning them split into one
+    /// buffer per element of `lens`.
+    ///
+    /// Like `readv`, a short read fills earlier buffers first, so every
+    /// buffer is as long as its length except for the one where the data ran
+    /// out and those after it, which are empty. The default implementation
+    /// issues a single [`read`](Self::read) for the total and splits the
+    /// result; streams which can fill separate buffers without copying
+    /// should override it.
+    fn read_vectored(&mut self, lens: &[usize]) -> StreamResult<Vec<Bytes>> {
+        let bytes = self.read(vectored_total(lens))?;
+        Ok(split_vectored(bytes, lens))
+    }
+
+    /// Similar to `read_vectored`, except that it blocks until at least one
+    /// byte can be read.
+    async fn blocking_read_vectored(&mut self, lens: &[usize]) -> StreamResult<Vec<Bytes>> {
+        let bytes = self.blocking_read(vectored_total(lens)).await?;
+        Ok(split_vectored(bytes, lens))
+    }
+
     /// Cancel any asynchronous work and wait for it to wrap up.
     async fn cancel(&mut self) {}
+
+    /// Returns this stream as a [`SnapshotableStream`], if it supports being
+    /// saved by [`snapshot_io`](crate::snapshot_io).
+    ///
+    /// Streams which return `None` here are restored as closed streams.
+    fn as_snapshotable(&self) -> Option<&dyn SnapshotableStream> {
+        None
+    }
+
+    /// Returns metadata describing this stream, such as a file name or a
+    /// content length, if it has any.
+    ///
+    /// Guests read this through the `wasmtime:wasi-io/streams-metadata`
+    /// extension interface added by
+    /// [`add_metadata_extension_to_linker`](crate::add_metadata_extension_to_linker).
+    fn metadata(&self) -> Option<&MetadataMap> {
+        None
+    }
+
+    /// Returns the number of bytes this stream reads most efficiently at a
+    /// time, if it has a preference.
+    ///
+    /// This is only a hint: reads of any size must still work. Guests read
+    /// it through the `wasmtime:wasi-io/streams-preferred-read-size`
+    /// extension interface added by
+    /// [`add_preferred_read_size_extension_to_linker`](crate::add_preferred_read_size_extension_to_linker),
+    /// and [`ReadAheadInputStream`] reads ahead in chunks of at least this
+    /// size.
+    fn preferred_read_size(&self) -> Option<usize> {
+        None
+    }
+
+    /// Returns whether this stream behaves deterministically.
+    ///
+    /// A deterministic stream's results depend only on the operations
+    /// performed on it, never on host timing or scheduling, so replaying the
+    /// same operations yields the same bytes and errors. Streams which return
+    /// `false` trap when used with
+    /// [`IoLinkOptions::deterministic`](crate::IoLinkOptions::deterministic)
+    /// enabled. See the [`deterministic`](crate::deterministic) module.
+    fn is_deterministic(&self) -> bool {
+        false
+    }
+
+    /// Returns this stream as [`Any`], so that an
+    /// [`OutputStream::splice_from`] implementation or host code with
+    /// [`downcast_input_stream`](crate::downcast_input_stream) can recognize
+    /// it.
+    ///
+    /// This is opt-in so that existing implementations keep compiling.
+    /// Implementations which want to take part in splice fast paths, or to
+    /// be downcast by the host, should return `Some(self)`.
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
+
+    /// Mutable version of [`as_any`](Self::as_any).
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        None
+    }
+}
+
+/// The number of bytes a vectored read of `lens` asks for.
+pub(crate) fn vectored_total(lens: &[usize]) -> usize {
+    lens.iter().fold(0, |total, len| total.saturating_add(*len))
+}
+
+/// Splits `bytes` into one buffer per element of `lens`, filling earlier
+/// buffers first and leaving later ones short or empty if `bytes` runs out.
+pub(crate) fn split_vectored(mut bytes: Bytes, lens: &[usize]) -> Vec<Bytes> {
+    lens.iter()
+        .map(|len| bytes.split_to((*len).min(bytes.len())))
+        .collect()
 }
 
+/// Metadata attached to an [`InputStream`], see [`InputStream::metadata`].
+pub type MetadataMap = BTreeMap<String, String>;
+
 /// Representation of the `error` resource type in the `wasi:io/error`
 /// interface.
 ///
@@ -90,8 +250,67 @@ pub enum StreamError {
 }
 
 impl StreamError {
+    /// Creates a trap caused by the guest, see [`StreamError::guest_trap`].
     pub fn trap(msg: &str) -> StreamError {
-        StreamError::Trap(anyhow::anyhow!("{msg}"))
+        StreamError::guest_trap(msg)
+    }
+
+    /// Creates a trap caused by the guest violating the interface's
+    /// contract, for example by writing more bytes than it was permitted to.
+    pub fn guest_trap(msg: &str) -> StreamError {
+        StreamError::Trap(anyhow::anyhow!("{msg}").context(TrapOrigin::Guest))
+    }
+
+    /// Creates a trap caused by a bug in the host, such as a stream
+    /// implementation breaking one of its own invariants.
+    pub fn host_bug(msg: &str) -> StreamError {
+        StreamError::Trap(anyhow::anyhow!("{msg}").context(TrapOrigin::Host))
+    }
+}
+
+/// Whether a [`StreamError::Trap`] was caused by the guest or by the host.
+///
+/// Traps created with [`StreamError::guest_trap`] or
+/// [`StreamError::host_bug`] carry their origin as context, and the host
+/// implementation of `wasi:io/streams` attaches [`TrapOrigin::Guest`] to
+/// other traps before they're propagated. Embedders can then tell the two
+/// apart in the error returned from a call into the guest:
+///
+/// ```
+/// use wasmtime_wasi_io::streams::TrapOrigin;
+///
+/// fn report(error: &anyhow::Error) {
+///     match error.downcast_ref::<TrapOrigin>() {
+///         Some(TrapOrigin::Host) => { /* crash reporting */ }
+///         Some(TrapOrigin::Guest) | None => { /* tenant logs */ }
+///     }
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrapOrigin {
+    /// The guest violated the interface's contract.
+    Guest,
+    /// The host hit a bug in its implementation.
+    Host,
+}
+
+impl TrapOrigin {
+    /// Attaches `TrapOrigin::Guest` to the error behind a trap unless it
+    /// already has an origin.
+    pub(crate) fn attach(error: anyhow::Error) -> anyhow::Error {
+        match error.downcast_ref::<TrapOrigin>() {
+            Some(_) => error,
+            None => error.context(TrapOrigin::Guest),
+        }
+    }
+}
+
+impl alloc::fmt::Display for TrapOrigin {
+    fn fmt(&self, f: &mut alloc::fmt::Formatter<'_>) -> alloc::fmt::Result {
+        match self {
+            TrapOrigin::Guest => write!(f, "invalid use of a stream by the guest"),
+            TrapOrigin::Host => write!(f, "bug in the host's stream implementation"),
+        }
     }
 }
 
@@ -100,7 +319,7 @@ impl alloc::fmt::Display for StreamError {
         match self {
             StreamError::Closed => write!(f, "closed"),
             StreamError::LastOperationFailed(e) => write!(f, "last operation failed: {e}"),
-            StreamError::Trap(e) => write!(f, "trap: {e}"),
+            StreamError::Trap(e) => write!(f, "trap: {e:#}"),
         }
     }
 }
@@ -122,6 +341,14 @@ impl From<wasmtime::component::ResourceTableError> for StreamError {
 
 /// Host trait for implementing the `wasi:io/streams.output-stream` resource:
 /// A bytestream which can be written to.
+///
+/// Guest writes, `write-zeroes` and splices of zero bytes are completed by
+/// the host without writing to the stream, so implementations needn't handle
+/// empty writes on their behalf. Such operations only call
+/// [`check_write`](OutputStream::check_write), and fail with the error it
+/// returns, such as [`StreamError::Closed`] for a closed stream.
+/// `blocking-write-and-flush` and `blocking-write-zeroes-and-flush` of zero
+/// bytes only flush the stream, as `blocking-flush` does.
 #[async_trait::async_trait]
 pub trait OutputStream: Pollable {
     /// Write bytes after obtaining a permit to write those bytes
@@ -232,6 +459,12 @@ pub trait OutputStream: Pollable {
     /// Important: this write must be non-blocking!
     /// Returning an Err which downcasts to a [`StreamError`] will be
     /// reported to Wasm as the empty error result. Otherwise, errors will trap.
+    ///
+    /// The host implementation of `write-zeroes` calls this directly with the
+    /// guest's length, rather than converting it into a [`write`](Self::write)
+    /// of a zeroed buffer, so implementations may complete it sparsely, for
+    /// example by extending a file with `ftruncate` or `fallocate`. The
+    /// default implementation writes a zeroed buffer.
     fn write_zeroes(&mut self, nelem: usize) -> StreamResult<()> {
         // TODO: We could optimize this to not allocate one big zeroed buffer, and instead write
         // repeatedly from a 'static buffer of zeros.
@@ -264,11 +497,32 @@ pub trait OutputStream: Pollable {
     /// // Check for any errors that arose during `flush`
     /// let _ = this.check-write();         // eliding error handling
     /// ```
-    async fn blocking_write_zeroes_and_flush(&mut self, nelem: usize) -> StreamResult<()> {
-        // TODO: We could optimize this to not allocate one big zeroed buffer, and instead write
-        // repeatedly from a 'static buffer of zeros.
-        let bs = Bytes::from_iter(core::iter::repeat(0).take(nelem));
-        self.blocking_write_and_flush(bs).await
+    ///
+    /// The default implementation passes the zeroes to
+    /// [`write_zeroes`](Self::write_zeroes) in runs no longer than the
+    /// stream's permits, so streams which complete `write_zeroes` sparsely
+    /// needn't implement this themselves.
+    async fn blocking_write_zeroes_and_flush(&mut self, mut nelem: usize) -> StreamResult<()> {
+        while nelem > 0 {
+            let len = nelem.min(self.write_ready().await?);
+            self.write_zeroes(len)?;
+            nelem -= len;
+        }
+
+        // As in `blocking_write_and_flush`, a stream which has become closed
+        // isn't an error here.
+        match self.flush() {
+            Ok(_) => {}
+            Err(StreamError::Closed) => {}
+            Err(e) => Err(e)?,
+        };
+        match self.write_ready().await {
+            Ok(_) => {}
+            Err(StreamError::Closed) => {}
+            Err(e) => Err(e)?,
+        };
+
+        Ok(())
     }
 
     /// Simultaneously waits for this stream to be writable and then returns how
@@ -291,6 +545,126 @@ pub trait OutputStream: Pollable {
 
     /// Cancel any asynchronous work and wait for it to wrap up.
     async fn cancel(&mut self) {}
+
+    /// Returns this stream as a [`SnapshotableStream`], if it supports being
+    /// saved by [`snapshot_io`](crate::snapshot_io).
+    ///
+    /// Streams which return `None` here are restored as closed streams.
+    fn as_snapshotable(&self) -> Option<&dyn SnapshotableStream> {
+        None
+    }
+
+    /// Provides the [`MemoryAccountant`] which this stream should charge for
+    /// bytes accepted by [`write`](Self::write) and
+    /// [`write_zeroes`](Self::write_zeroes) while they're held in host
+    /// memory.
+    ///
+    /// This is called before each write when an accountant is configured
+    /// with [`IoLinkOptions::memory_accountant`](crate::IoLinkOptions::memory_accountant).
+    /// Streams which buffer written bytes should record it, for example in a
+    /// [`MemoryCharge`](crate::accounting::MemoryCharge), and fail writes
+    /// which exceed its budget. The default implementation ignores it, which
+    /// is appropriate for streams which don't hold on to written bytes.
+    fn set_memory_accountant(&mut self, accountant: &MemoryAccountant) {
+        let _ = accountant;
+    }
+
+    /// Returns the number of bytes which have been written to this stream
+    /// but which it still holds, for example because they haven't been
+    /// flushed, or `None` if it doesn't keep track of them.
+    ///
+    /// This is only used by host code, see
+    /// [`io::buffered_bytes`](crate::io::buffered_bytes).
+    fn buffered_bytes(&self) -> Option<usize> {
+        None
+    }
+
+    /// Returns the largest buffer a guest may pass to
+    /// `blocking-write-and-flush` or `blocking-write-zeroes-and-flush` on
+    /// this stream, which `wasi:io/streams` fixes at 4096 bytes.
+    ///
+    /// Streams may raise this to accept larger buffers from guests which
+    /// know about it. The host implementation splits such buffers into
+    /// chunks no larger than the permits returned by
+    /// [`write_ready`](Self::write_ready), and passes each to
+    /// [`blocking_write_and_flush`](Self::blocking_write_and_flush) or
+    /// [`blocking_write_zeroes_and_flush`](Self::blocking_write_zeroes_and_flush)
+    /// separately, so the stream is never handed more than it advertised.
+    fn blocking_write_limit(&self) -> usize {
+        4096
+    }
+
+    /// Returns whether this stream already coalesces small writes itself.
+    ///
+    /// When [`IoLinkOptions::coalesce_writes`](crate::IoLinkOptions::coalesce_writes)
+    /// is configured, streams which return `false` are wrapped in a
+    /// [`CoalescingOutputStream`](crate::coalesce::CoalescingOutputStream)
+    /// when they're first checked or written to.
+    fn is_coalescing(&self) -> bool {
+        false
+    }
+
+    /// Returns whether this stream already applies a policy to writes which
+    /// exceed the permit granted by [`check_write`](Self::check_write).
+    ///
+    /// Unless [`IoLinkOptions::write_permit_policy`](crate::IoLinkOptions::write_permit_policy)
+    /// is [`PermitPolicy::Forward`](crate::PermitPolicy::Forward), streams
+    /// which return `false` are wrapped in a
+    /// [`PermitCheckedOutputStream`](crate::permits::PermitCheckedOutputStream)
+    /// when they're first checked or written to.
+    fn enforces_write_permits(&self) -> bool {
+        false
+    }
+
+    /// Returns whether this stream behaves deterministically.
+    ///
+    /// A deterministic stream's results depend only on the operations
+    /// performed on it, never on host timing or scheduling. Streams which
+    /// return `false` trap when used with
+    /// [`IoLinkOptions::deterministic`](crate::IoLinkOptions::deterministic)
+    /// enabled. See the [`deterministic`](crate::deterministic) module.
+    fn is_deterministic(&self) -> bool {
+        false
+    }
+
+    /// Returns this stream as [`Any`], so that other streams or host code
+    /// with [`downcast_output_stream`](crate::downcast_output_stream) can
+    /// recognize it.
+    ///
+    /// This is opt-in so that existing implementations keep compiling.
+    /// Implementations which want to take part in splice fast paths, or to
+    /// be downcast by the host, should return `Some(self)`.
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
+
+    /// Mutable version of [`as_any`](Self::as_any).
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        None
+    }
+
+    /// Moves up to `len` bytes from `src` into this stream without going
+    /// through the generic read and write loop, for example with
+    /// `sendfile`.
+    ///
+    /// This is offered first by both `splice` and `blocking_splice`. Returning
+    /// `None` declines, and the bytes are then moved by reading from `src`
+    /// and writing them to this stream. Returning `Some` skips the generic
+    /// path entirely, and the result is the number of bytes moved, which is
+    /// at most `len`, or the error to report.
+    ///
+    /// Implementations typically recognize `src` by downcasting
+    /// [`InputStream::as_any_mut`] to one of their own types. They're
+    /// responsible for honoring this stream's write permits and for making
+    /// the bytes visible as if they'd been written and flushed.
+    fn splice_from(
+        &mut self,
+        src: &mut dyn InputStream,
+        len: usize,
+    ) -> Option<StreamResult<usize>> {
+        let _ = (src, len);
+        None
+    }
 }
 
 #[async_trait::async_trait]
@@ -298,6 +672,26 @@ impl Pollable for Box<dyn OutputStream> {
     async fn ready(&mut self) {
         (**self).ready().await
     }
+
+    fn ready_owned(&self) -> Option<DynFuture<'static>> {
+        (**self).ready_owned()
+    }
+
+    fn pending_hint(&self) -> Option<&PendingHint> {
+        (**self).pending_hint()
+    }
+
+    fn readiness_kind(&self) -> ReadinessKind {
+        (**self).readiness_kind()
+    }
+
+    fn debug_name(&self) -> Option<String> {
+        (**self).debug_name()
+    }
+
+    fn type_name(&self) -> &'static str {
+        (**self).type_name()
+    }
 }
 
 #[async_trait::async_trait]
@@ -305,6 +699,26 @@ impl Pollable for Box<dyn InputStream> {
     async fn ready(&mut self) {
         (**self).ready().await
     }
+
+    fn ready_owned(&self) -> Option<DynFuture<'static>> {
+        (**self).ready_owned()
+    }
+
+    fn pending_hint(&self) -> Option<&PendingHint> {
+        (**self).pending_hint()
+    }
+
+    fn readiness_kind(&self) -> ReadinessKind {
+        (**self).readiness_kind()
+    }
+
+    fn debug_name(&self) -> Option<String> {
+        (**self).debug_name()
+    }
+
+    fn type_name(&self) -> &'static str {
+        (**self).type_name()
+    }
 }
 
 pub type DynInputStream = Box<dyn InputStream>;
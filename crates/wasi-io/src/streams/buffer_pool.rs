@@ -0,0 +1,258 @@
+#[cfg(not(feature = "std"))]
+use crate::poll::SpinLock;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bytes::{Bytes, BytesMut};
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// A pool of buffers for streams which read into freshly allocated memory
+/// and hand the result out as [`Bytes`].
+///
+/// Buffers are taken from the pool with [`BufferPool::get`], filled, and
+/// turned into [`Bytes`] with [`PooledBuffer::freeze`]. Dropping the
+/// [`PooledBuffer`] returns its allocation to the pool, and the next `get`
+/// reuses it if every [`Bytes`] frozen from it has been dropped by then;
+/// otherwise a new allocation is made. Streams whose readers consume data
+/// promptly therefore settle on a handful of allocations, however much data
+/// flows through them.
+///
+/// Pools are cheap to clone and clones share their buffers, so one pool may
+/// serve many streams. Streams which read concurrently then contend for the
+/// pool's lock, which is a mutex with the `std` feature and a spin lock
+/// without it, so a pool is best shared by streams which are read by the
+/// same task.
+#[derive(Clone)]
+pub struct BufferPool(Arc<Pool>);
+
+struct Pool {
+    buffer_size: usize,
+    max_free: usize,
+    free: FreeLock,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    outstanding: AtomicUsize,
+}
+
+/// The lock protecting the buffers waiting in a [`Pool`]: a mutex with the
+/// `std` feature, and a [`SpinLock`] without it.
+#[derive(Default)]
+struct FreeLock {
+    #[cfg(feature = "std")]
+    free: std::sync::Mutex<Vec<Free>>,
+    #[cfg(not(feature = "std"))]
+    free: SpinLock<Vec<Free>>,
+}
+
+impl FreeLock {
+    fn with<R>(&self, f: impl FnOnce(&mut Vec<Free>) -> R) -> R {
+        #[cfg(feature = "std")]
+        {
+            // The list is consistent between operations, so a panic while it
+            // was locked leaves nothing to recover.
+            f(&mut self.free.lock().unwrap_or_else(|e| e.into_inner()))
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            self.free.with(f)
+        }
+    }
+}
+
+/// A buffer waiting in the pool, along with the start of its allocation.
+struct Free {
+    buf: BytesMut,
+    base: usize,
+}
+
+/// Counters describing how well a [`BufferPool`] is reusing its buffers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BufferPoolStats {
+    /// The number of `get` calls which reused an allocation.
+    pub hits: usize,
+    /// The number of `get` calls which had to allocate, either because no
+    /// buffer was free, because the free buffer's data was still referenced,
+    /// or because the requested length exceeded the pool's buffer size.
+    pub misses: usize,
+    /// The number of buffers currently taken from the pool.
+    pub outstanding: usize,
+}
+
+impl BufferPool {
+    /// Creates a pool of buffers of `buffer_size` bytes, keeping at most
+    /// `max_free` of them around while they aren't in use.
+    pub fn new(buffer_size: usize, max_free: usize) -> BufferPool {
+        BufferPool(Arc::new(Pool {
+            buffer_size,
+            max_free,
+            free: FreeLock::default(),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+            outstanding: AtomicUsize::new(0),
+        }))
+    }
+
+    /// Returns the size of this pool's buffers.
+    pub fn buffer_size(&self) -> usize {
+        self.0.buffer_size
+    }
+
+    /// Returns an empty buffer with a capacity of at least `len` bytes.
+    ///
+    /// Requests larger than the pool's buffer size are served by a buffer
+    /// which isn't returned to the pool.
+    pub fn get(&self, len: usize) -> PooledBuffer {
+        let pool = &self.0;
+        if len > pool.buffer_size {
+            pool.misses.fetch_add(1, Ordering::Relaxed);
+            return PooledBuffer {
+                buf: BytesMut::with_capacity(len),
+                base: 0,
+                pool: None,
+            };
+        }
+        let (buf, base) = match pool.free.with(|free| free.pop()) {
+            Some(Free { mut buf, base }) => {
+                // `reserve` moves the buffer back to the start of its
+                // allocation if nothing else refers to it, and allocates
+                // otherwise.
+                let reused = buf.capacity() >= pool.buffer_size || {
+                    buf.reserve(pool.buffer_size);
+                    buf.as_ptr() as usize == base
+                };
+                if reused {
+                    pool.hits.fetch_add(1, Ordering::Relaxed);
+                    (buf, base)
+                } else {
+                    pool.misses.fetch_add(1, Ordering::Relaxed);
+                    let base = buf.as_ptr() as usize;
+                    (buf, base)
+                }
+            }
+            None => {
+                pool.misses.fetch_add(1, Ordering::Relaxed);
+                let buf = BytesMut::with_capacity(pool.buffer_size);
+                let base = buf.as_ptr() as usize;
+                (buf, base)
+            }
+        };
+        pool.outstanding.fetch_add(1, Ordering::Relaxed);
+        PooledBuffer {
+            buf,
+            base,
+            pool: Some(pool.clone()),
+        }
+    }
+
+    /// Returns this pool's counters.
+    pub fn stats(&self) -> BufferPoolStats {
+        BufferPoolStats {
+            hits: self.0.hits.load(Ordering::Relaxed),
+            misses: self.0.misses.load(Ordering::Relaxed),
+            outstanding: self.0.outstanding.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl core::fmt::Debug for BufferPool {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("BufferPool")
+            .field("buffer_size", &self.0.buffer_size)
+            .field("max_free", &self.0.max_free)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+/// A buffer taken from a [`BufferPool`], which returns to the pool when
+/// dropped.
+pub struct PooledBuffer {
+    buf: BytesMut,
+    base: usize,
+    pool: Option<Arc<Pool>>,
+}
+
+impl PooledBuffer {
+    /// Takes the contents of this buffer as [`Bytes`], leaving the buffer
+    /// empty.
+    pub fn freeze(mut self) -> Bytes {
+        self.buf.split().freeze()
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = BytesMut;
+
+    fn deref(&self) -> &BytesMut {
+        &self.buf
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut BytesMut {
+        &mut self.buf
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        let Some(pool) = self.pool.take() else {
+            return;
+        };
+        pool.outstanding.fetch_sub(1, Ordering::Relaxed);
+        let mut buf = core::mem::take(&mut self.buf);
+        buf.clear();
+        let buf = Free {
+            buf,
+            base: self.base,
+        };
+        // The buffer is dropped outside of the lock if the pool is full.
+        let _full = pool.free.with(|free| {
+            if free.len() < pool.max_free {
+                free.push(buf);
+                None
+            } else {
+                Some(buf)
+            }
+        });
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocations_are_reused_once_released() {
+        let pool = BufferPool::new(64, 2);
+
+        let mut buf = pool.get(16);
+        buf.extend_from_slice(b"hello");
+        let first = buf.freeze();
+        assert_eq!(first, b"hello"[..]);
+        assert_eq!(pool.stats().outstanding, 0);
+
+        // `first` still refers to the only pooled allocation.
+        let mut buf = pool.get(64);
+        buf.extend_from_slice(b"world");
+        let second = buf.freeze();
+        assert_eq!(first, b"hello"[..]);
+        drop((first, second));
+
+        let buf = pool.get(64);
+        assert_eq!(
+            pool.stats(),
+            BufferPoolStats {
+                hits: 1,
+                misses: 2,
+                outstanding: 1,
+            }
+        );
+        drop(buf);
+
+        // Oversized requests bypass the pool.
+        drop(pool.get(128));
+        assert_eq!(pool.stats().misses, 3);
+        assert_eq!(pool.stats().outstanding, 0);
+    }
+}
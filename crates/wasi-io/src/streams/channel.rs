@@ -0,0 +1,762 @@
+use crate::poll::{Pollable, SpinLock, WakerList};
+use crate::streams::{
+    DynInputStream, DynOutputStream, InputStream, OutputStream, StreamError, StreamResult,
+};
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use bytes::{Buf, Bytes, BytesMut};
+use core::fmt;
+use core::future::poll_fn;
+use core::task::{Poll, Waker};
+
+/// The length of the big-endian length which precedes each frame with
+/// [`Framing::LengthPrefixed`].
+const PREFIX_LEN: usize = 4;
+
+/// The most bytes the guest may write to an [`output_channel`] at once.
+const MAX_WRITE: usize = 64 * 1024;
+
+/// How frames appear in the byte stream seen by the guest.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Framing {
+    /// Frames are concatenated, so the guest sees the bytes of each frame
+    /// directly after those of the one before.
+    ///
+    /// For an [`output_channel`] every write of the guest is a frame.
+    #[default]
+    Concatenated,
+
+    /// Each frame is preceded by its length as a big-endian `u32`.
+    ///
+    /// For an [`output_channel`] the guest writes frames the same way, and
+    /// they're delivered to the host once complete, regardless of how they
+    /// were split into writes. Frames written by the guest may be at most
+    /// `frame_capacity * 64 KiB` long, and the guest's stream fails if it
+    /// declares a longer one.
+    LengthPrefixed,
+}
+
+/// Creates a channel through which host code sends frames to a guest, with
+/// room for `frame_capacity` frames which the guest hasn't started reading.
+///
+/// The guest reads the frames from the returned input stream, concatenated
+/// as with [`Framing::Concatenated`]. The stream is closed once every
+/// [`HostSender`] has been dropped and the guest has read all the frames.
+///
+/// The channel only relies on atomics, so it's available without the `std`
+/// feature, and the host side may be used from non-async code, including
+/// other threads.
+///
+/// # Panics
+///
+/// Panics if `frame_capacity` is zero.
+pub fn channel(frame_capacity: usize) -> (HostSender, DynInputStream) {
+    channel_with_framing(frame_capacity, Framing::Concatenated)
+}
+
+/// Same as [`channel`], but with the given `framing`.
+pub fn channel_with_framing(
+    frame_capacity: usize,
+    framing: Framing,
+) -> (HostSender, DynInputStream) {
+    let shared = Shared::new(frame_capacity, framing);
+    let stream = ChannelInputStream {
+        shared: shared.clone(),
+        reading: Bytes::new(),
+    };
+    (HostSender { shared }, Box::new(stream))
+}
+
+/// Creates a channel through which a guest sends frames to host code, with
+/// room for `frame_capacity` frames which the host hasn't received.
+///
+/// Each write of the guest to the returned output stream is a frame, as with
+/// [`Framing::Concatenated`]. The guest's permits are zero while the channel
+/// is full, and its stream becomes ready once [`HostReceiver::try_recv`] has
+/// made room. The channel is closed once the guest drops its stream and the
+/// host has received all the frames.
+///
+/// The channel only relies on atomics, so it's available without the `std`
+/// feature, and the host side may be used from non-async code, including
+/// other threads.
+///
+/// # Panics
+///
+/// Panics if `frame_capacity` is zero.
+pub fn output_channel(frame_capacity: usize) -> (DynOutputStream, HostReceiver) {
+    output_channel_with_framing(frame_capacity, Framing::Concatenated)
+}
+
+/// Same as [`output_channel`], but with the given `framing`.
+pub fn output_channel_with_framing(
+    frame_capacity: usize,
+    framing: Framing,
+) -> (DynOutputStream, HostReceiver) {
+    let shared = Shared::new(frame_capacity, framing);
+    let stream = ChannelOutputStream {
+        shared: shared.clone(),
+    };
+    (Box::new(stream), HostReceiver { shared })
+}
+
+/// The error returned by [`HostSender::try_send`], which hands the frame
+/// back.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TrySendError {
+    /// The channel is full, and the frame may be sent again once the guest
+    /// has made room.
+    Full(Bytes),
+    /// The guest dropped its stream, so the frame can never be read.
+    Closed(Bytes),
+}
+
+/// The error returned by [`HostReceiver::try_recv`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// No frames are waiting to be received.
+    Empty,
+    /// The guest dropped its stream, and all of its frames were received.
+    Closed,
+    /// The guest dropped its stream partway through a length-prefixed
+    /// frame, or declared a frame longer than the channel allows, and all
+    /// the frames before that one were received.
+    Truncated,
+}
+
+impl fmt::Display for TrySendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => write!(f, "channel is full"),
+            TrySendError::Closed(_) => write!(f, "channel is closed"),
+        }
+    }
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "channel is empty"),
+            TryRecvError::Closed => write!(f, "channel is closed"),
+            TryRecvError::Truncated => write!(f, "channel closed partway through a frame"),
+        }
+    }
+}
+
+impl core::error::Error for TrySendError {}
+impl core::error::Error for TryRecvError {}
+
+/// The sending half of a [`channel`], used by host code.
+///
+/// Senders may be cloned to send from several places, and the guest's
+/// stream is closed once all of them have been dropped.
+pub struct HostSender {
+    shared: Arc<Shared>,
+}
+
+impl HostSender {
+    /// Sends `frame` to the guest if the channel has room for it.
+    ///
+    /// Empty frames are accepted, but aren't visible to the guest with
+    /// [`Framing::Concatenated`].
+    ///
+    /// # Panics
+    ///
+    /// Panics with [`Framing::LengthPrefixed`] if `frame` is longer than
+    /// `u32::MAX` bytes.
+    pub fn try_send(&self, frame: Bytes) -> Result<(), TrySendError> {
+        if self.shared.framing == Framing::LengthPrefixed {
+            assert!(u32::try_from(frame.len()).is_ok(), "frame too large");
+        }
+        self.shared.state.with(|state| {
+            if state.guest_closed {
+                Err(TrySendError::Closed(frame))
+            } else if state.frames.len() >= self.shared.capacity {
+                Err(TrySendError::Full(frame))
+            } else {
+                state.frames.push_back(frame);
+                Ok(())
+            }
+        })?;
+        self.shared.guest_wakers.wake_all();
+        Ok(())
+    }
+
+    /// Registers `waker` to be woken once the guest makes room in the
+    /// channel, or drops its stream.
+    ///
+    /// Wakers are woken once, so a sender which finds the channel full
+    /// registers a waker, tries again in case room was made in the
+    /// meantime, and otherwise waits to be woken.
+    pub fn register_waker(&self, waker: &Waker) {
+        self.shared.host_wakers.register(waker);
+    }
+
+    /// Returns whether the guest dropped its stream.
+    pub fn is_closed(&self) -> bool {
+        self.shared.state.with(|state| state.guest_closed)
+    }
+}
+
+impl Clone for HostSender {
+    fn clone(&self) -> HostSender {
+        self.shared.state.with(|state| state.senders += 1);
+        HostSender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for HostSender {
+    fn drop(&mut self) {
+        let closed = self.shared.state.with(|state| {
+            state.senders -= 1;
+            if state.senders == 0 {
+                state.host_closed = true;
+            }
+            state.host_closed
+        });
+        if closed {
+            self.shared.guest_wakers.wake_all();
+        }
+    }
+}
+
+impl fmt::Debug for HostSender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostSender").finish_non_exhaustive()
+    }
+}
+
+/// The receiving half of an [`output_channel`], used by host code.
+pub struct HostReceiver {
+    shared: Arc<Shared>,
+}
+
+impl HostReceiver {
+    /// Receives the oldest frame the guest has sent, if any.
+    pub fn try_recv(&self) -> Result<Bytes, TryRecvError> {
+        let frame = self.shared.state.with(|state| {
+            let frame = state.frames.pop_front();
+            // Frames which didn't fit before may fit now.
+            state.take_prefixed_frames(self.shared.capacity, self.shared.max_frame());
+            match frame {
+                Some(frame) => Ok(frame),
+                // With room in `frames`, anything left in `written` is the
+                // start of a frame which the guest never finished.
+                None if state.guest_closed && (state.truncated || !state.written.is_empty()) => {
+                    Err(TryRecvError::Truncated)
+                }
+                None if state.guest_closed => Err(TryRecvError::Closed),
+                None => Err(TryRecvError::Empty),
+            }
+        })?;
+        self.shared.guest_wakers.wake_all();
+        Ok(frame)
+    }
+
+    /// Registers `waker` to be woken once the guest sends a frame, or drops
+    /// its stream.
+    ///
+    /// Wakers are woken once, so a receiver which finds the channel empty
+    /// registers a waker, tries again in case a frame was sent in the
+    /// meantime, and otherwise waits to be woken.
+    pub fn register_waker(&self, waker: &Waker) {
+        self.shared.host_wakers.register(waker);
+    }
+}
+
+impl Drop for HostReceiver {
+    fn drop(&mut self) {
+        self.shared.state.with(|state| state.host_closed = true);
+        self.shared.guest_wakers.wake_all();
+    }
+}
+
+impl fmt::Debug for HostReceiver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostReceiver").finish_non_exhaustive()
+    }
+}
+
+struct Shared {
+    state: SpinLock<State>,
+    capacity: usize,
+    framing: Framing,
+    /// Tasks waiting for the guest's stream to become ready.
+    guest_wakers: WakerList,
+    /// Wakers registered by host code.
+    host_wakers: WakerList,
+}
+
+struct State {
+    /// Frames which the guest hasn't started reading, or which the host
+    /// hasn't received.
+    frames: VecDeque<Bytes>,
+    /// With [`Framing::LengthPrefixed`], bytes written by the guest which
+    /// don't make up a whole frame, or which don't fit in `frames` yet.
+    written: BytesMut,
+    /// Whether the guest declared a frame longer than [`Shared::max_frame`],
+    /// after which `written` is discarded.
+    truncated: bool,
+    /// The number of [`HostSender`]s.
+    senders: usize,
+    /// Whether all senders, or the receiver, were dropped.
+    host_closed: bool,
+    /// Whether the guest dropped its stream.
+    guest_closed: bool,
+}
+
+impl Shared {
+    fn new(capacity: usize, framing: Framing) -> Arc<Shared> {
+        assert!(capacity > 0, "frame capacity must be non-zero");
+        Arc::new(Shared {
+            state: SpinLock::new(State {
+                frames: VecDeque::new(),
+                written: BytesMut::new(),
+                truncated: false,
+                senders: 1,
+                host_closed: false,
+                guest_closed: false,
+            }),
+            capacity,
+            framing,
+            guest_wakers: WakerList::default(),
+            host_wakers: WakerList::default(),
+        })
+    }
+
+    /// The longest length-prefixed frame the guest may write.
+    ///
+    /// This bounds how much `State::written` buffers, as the guest is only
+    /// granted permits for the rest of the frame it's writing.
+    fn max_frame(&self) -> usize {
+        self.capacity.saturating_mul(MAX_WRITE)
+    }
+
+    /// Waits until `ready` returns `true` for the state.
+    async fn wait(&self, ready: impl Fn(&State) -> bool) {
+        poll_fn(|cx| {
+            if self.state.with(|state| ready(state)) {
+                return Poll::Ready(());
+            }
+            self.guest_wakers.register(cx.waker());
+            // Check again in case the state changed while registering.
+            if self.state.with(|state| ready(state)) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+impl State {
+    /// Moves complete length-prefixed frames out of `written` while there's
+    /// room for them, and marks the channel as truncated if the next frame
+    /// is longer than `max_frame`.
+    fn take_prefixed_frames(&mut self, capacity: usize, max_frame: usize) {
+        while self.frames.len() < capacity && self.written.len() >= PREFIX_LEN {
+            let len = u32::from_be_bytes(self.written[..PREFIX_LEN].try_into().unwrap());
+            let len = usize::try_from(len).unwrap();
+            if len > max_frame {
+                self.truncated = true;
+                self.written = BytesMut::new();
+                break;
+            }
+            if self.written.len() - PREFIX_LEN < len {
+                break;
+            }
+            self.written.advance(PREFIX_LEN);
+            self.frames.push_back(self.written.split_to(len).freeze());
+        }
+    }
+}
+
+/// The guest's end of a [`channel`].
+struct ChannelInputStream {
+    shared: Arc<Shared>,
+    /// The rest of the frame being read, including its length prefix.
+    reading: Bytes,
+}
+
+#[async_trait::async_trait]
+impl Pollable for ChannelInputStream {
+    async fn ready(&mut self) {
+        if !self.reading.is_empty() {
+            return;
+        }
+        self.shared
+            .wait(|state| !state.frames.is_empty() || state.host_closed)
+            .await
+    }
+}
+
+impl InputStream for ChannelInputStream {
+    fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+        if self.reading.is_empty() {
+            let frame = self
+                .shared
+                .state
+                .with(|state| match state.frames.pop_front() {
+                    Some(frame) => Ok(Some(frame)),
+                    None if state.host_closed => Err(StreamError::Closed),
+                    None => Ok(None),
+                })?;
+            let Some(frame) = frame else {
+                return Ok(Bytes::new());
+            };
+            self.shared.host_wakers.wake_all();
+            self.reading = match self.shared.framing {
+                Framing::Concatenated => frame,
+                Framing::LengthPrefixed => {
+                    let mut prefixed = BytesMut::with_capacity(PREFIX_LEN + frame.len());
+                    prefixed.extend_from_slice(&u32::try_from(frame.len()).unwrap().to_be_bytes());
+                    prefixed.extend_from_slice(&frame);
+                    prefixed.freeze()
+                }
+            };
+        }
+        Ok(self.reading.split_to(size.min(self.reading.len())))
+    }
+
+    fn is_closed(&self) -> bool {
+        self.reading.is_empty()
+            && self
+                .shared
+                .state
+                .with(|state| state.frames.is_empty() && state.host_closed)
+    }
+}
+
+impl Drop for ChannelInputStream {
+    fn drop(&mut self) {
+        self.shared.state.with(|state| {
+            state.guest_closed = true;
+            state.frames.clear();
+        });
+        self.shared.host_wakers.wake_all();
+    }
+}
+
+/// The guest's end of an [`output_channel`].
+struct ChannelOutputStream {
+    shared: Arc<Shared>,
+}
+
+impl ChannelOutputStream {
+    fn permit(&self, state: &State) -> StreamResult<usize> {
+        if state.truncated {
+            Err(StreamError::LastOperationFailed(anyhow::anyhow!(
+                "frame longer than {} bytes",
+                self.shared.max_frame()
+            )))
+        } else if state.host_closed {
+            Err(StreamError::Closed)
+        } else if state.frames.len() >= self.shared.capacity {
+            Ok(0)
+        } else {
+            // Complete frames are moved out of `written` while there's room
+            // in `frames`, so it only holds the start of the frame being
+            // written, which may grow to its prefix and `max_frame` bytes.
+            let room = self.shared.max_frame().saturating_add(PREFIX_LEN) - state.written.len();
+            Ok(MAX_WRITE.min(room))
+        }
+    }
+
+    /// Writes `len` bytes with `append`, which adds them to the frames or to
+    /// the frame being written, waking the host if a frame was completed.
+    fn send(&self, len: usize, append: impl FnOnce(&mut State)) -> StreamResult<()> {
+        let sent = self.shared.state.with(|state| {
+            if len > self.permit(state)? {
+                return Err(StreamError::trap("write exceeded permit"));
+            }
+            let before = state.frames.len();
+            append(state);
+            Ok(state.frames.len() > before)
+        })?;
+        if sent {
+            self.shared.host_wakers.wake_all();
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for ChannelOutputStream {
+    async fn ready(&mut self) {
+        let capacity = self.shared.capacity;
+        self.shared
+            .wait(|state| state.frames.len() < capacity || state.host_closed || state.truncated)
+            .await
+    }
+}
+
+#[async_trait::async_trait]
+impl OutputStream for ChannelOutputStream {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        self.send(bytes.len(), |state| match self.shared.framing {
+            Framing::Concatenated if bytes.is_empty() => {}
+            Framing::Concatenated => state.frames.push_back(bytes),
+            Framing::LengthPrefixed => {
+                state.written.extend_from_slice(&bytes);
+                state.take_prefixed_frames(self.shared.capacity, self.shared.max_frame());
+            }
+        })
+    }
+
+    fn write_zeroes(&mut self, nelem: usize) -> StreamResult<()> {
+        // Length-prefixed frames are assembled in place, so the zeroes are
+        // appended to the frame being written without a buffer of their own.
+        self.send(nelem, |state| match self.shared.framing {
+            Framing::Concatenated if nelem == 0 => {}
+            Framing::Concatenated => state.frames.push_back(BytesMut::zeroed(nelem).freeze()),
+            Framing::LengthPrefixed => {
+                state.written.resize(state.written.len() + nelem, 0);
+                state.take_prefixed_frames(self.shared.capacity, self.shared.max_frame());
+            }
+        })
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        // Frames are visible to the host as soon as they're written.
+        self.shared.state.with(|state| self.permit(state))?;
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        self.shared.state.with(|state| self.permit(state))
+    }
+}
+
+impl Drop for ChannelOutputStream {
+    fn drop(&mut self) {
+        self.shared.state.with(|state| state.guest_closed = true);
+        self.shared.host_wakers.wake_all();
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::executor::{WakeSignal, block_on};
+    use alloc::task::Wake;
+    use alloc::vec::Vec;
+    use std::thread::{self, Thread};
+
+    /// Unparks a thread waiting on the host side of a channel.
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn frame(i: usize) -> Bytes {
+        Bytes::from(vec![i as u8; i % 7])
+    }
+
+    #[test]
+    fn host_thread_sends_to_polling_guest() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        const FRAMES: usize = 200;
+        let (sender, mut stream) = channel(3);
+
+        let producer = thread::spawn(move || {
+            let waker = Waker::from(Arc::new(Unpark(thread::current())));
+            for i in 0..FRAMES {
+                let mut frame = frame(i);
+                loop {
+                    match sender.try_send(frame) {
+                        Ok(()) => break,
+                        Err(TrySendError::Full(f)) => frame = f,
+                        Err(TrySendError::Closed(_)) => panic!("guest went away"),
+                    }
+                    sender.register_waker(&waker);
+                    match sender.try_send(frame) {
+                        Ok(()) => break,
+                        Err(TrySendError::Full(f)) => frame = f,
+                        Err(TrySendError::Closed(_)) => panic!("guest went away"),
+                    }
+                    thread::park();
+                }
+            }
+        });
+
+        // The guest is driven by the bare-metal executor, reading less than
+        // a frame at a time.
+        let received = block_on(&SIGNAL, |_| thread::yield_now(), async {
+            let mut received = Vec::new();
+            loop {
+                stream.ready().await;
+                match stream.read(2) {
+                    Ok(bytes) => received.extend_from_slice(&bytes),
+                    Err(StreamError::Closed) => break received,
+                    Err(e) => panic!("unexpected error: {e}"),
+                }
+            }
+        });
+        producer.join().unwrap();
+        let expected: Vec<u8> = (0..FRAMES).flat_map(|i| frame(i).to_vec()).collect();
+        assert_eq!(received, expected);
+    }
+
+    #[tokio::test]
+    async fn guest_sends_length_prefixed_frames_to_host_thread() {
+        const FRAMES: usize = 100;
+        let (mut stream, receiver) = output_channel_with_framing(2, Framing::LengthPrefixed);
+
+        let consumer = thread::spawn(move || {
+            let waker = Waker::from(Arc::new(Unpark(thread::current())));
+            let mut frames = Vec::new();
+            loop {
+                match receiver.try_recv() {
+                    Ok(frame) => frames.push(frame),
+                    Err(TryRecvError::Closed) => break frames,
+                    Err(TryRecvError::Empty) => {
+                        receiver.register_waker(&waker);
+                        match receiver.try_recv() {
+                            Ok(frame) => frames.push(frame),
+                            Err(TryRecvError::Closed) => break frames,
+                            Err(TryRecvError::Empty) => thread::park(),
+                        }
+                    }
+                }
+            }
+        });
+
+        // Frames are written in pieces which straddle frame boundaries.
+        let mut encoded = BytesMut::new();
+        for i in 0..FRAMES {
+            encoded.extend_from_slice(&u32::try_from(frame(i).len()).unwrap().to_be_bytes());
+            encoded.extend_from_slice(&frame(i));
+        }
+        let mut encoded = encoded.freeze();
+        while !encoded.is_empty() {
+            let permit = stream.write_ready().await.unwrap();
+            let len = permit.min(encoded.len()).min(5);
+            stream.write(encoded.split_to(len)).unwrap();
+        }
+        drop(stream);
+
+        let frames = consumer.join().unwrap();
+        assert_eq!(frames, (0..FRAMES).map(frame).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn backpressure_and_closing() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+
+        // Permits run out once the host has fallen `frame_capacity` frames
+        // behind, and come back as it receives them.
+        let (mut stream, receiver) = output_channel(2);
+        stream.write(Bytes::from_static(b"a")).unwrap();
+        stream.write(Bytes::from_static(b"bc")).unwrap();
+        assert_eq!(stream.check_write().unwrap(), 0);
+        assert!(stream.write(Bytes::from_static(b"d")).is_err());
+        assert_eq!(receiver.try_recv(), Ok(Bytes::from_static(b"a")));
+        block_on(&SIGNAL, |_| {}, stream.ready());
+        assert_eq!(stream.check_write().unwrap(), MAX_WRITE);
+        drop(receiver);
+        assert!(matches!(stream.check_write(), Err(StreamError::Closed)));
+
+        // The guest reads what was sent before the last sender was dropped,
+        // after which its stream is closed.
+        let (sender, mut stream) = channel_with_framing(1, Framing::LengthPrefixed);
+        let other = sender.clone();
+        sender.try_send(Bytes::from_static(b"hi")).unwrap();
+        assert_eq!(
+            other.try_send(Bytes::from_static(b"!")),
+            Err(TrySendError::Full(Bytes::from_static(b"!")))
+        );
+        drop(sender);
+        assert_eq!(stream.read(3).unwrap(), &[0, 0, 0][..]);
+        other.try_send(Bytes::from_static(b"!")).unwrap();
+        drop(other);
+        assert_eq!(stream.read(10).unwrap(), &[2, b'h', b'i'][..]);
+        assert_eq!(stream.read(10).unwrap(), &[0, 0, 0, 1, b'!'][..]);
+        block_on(&SIGNAL, |_| {}, stream.ready());
+        assert!(matches!(stream.read(10), Err(StreamError::Closed)));
+
+        // Frames sent after the guest went away are handed back.
+        let (sender, stream) = channel(1);
+        drop(stream);
+        assert!(sender.is_closed());
+        assert_eq!(
+            sender.try_send(Bytes::from_static(b"x")),
+            Err(TrySendError::Closed(Bytes::from_static(b"x")))
+        );
+    }
+}
+
+/// Tests which only rely on `core` and `alloc`, so they also run without the
+/// `std` feature.
+#[cfg(test)]
+mod no_std_tests {
+    use super::*;
+    use alloc::vec;
+
+    fn prefix(len: usize) -> Bytes {
+        Bytes::copy_from_slice(&u32::try_from(len).unwrap().to_be_bytes())
+    }
+
+    #[test]
+    fn prefixed_frames_are_bounded() {
+        let (mut stream, receiver) = output_channel_with_framing(1, Framing::LengthPrefixed);
+
+        // Permits cover the rest of the frame being written, and then stop
+        // while the channel is full.
+        stream.write(prefix(MAX_WRITE)).unwrap();
+        assert_eq!(stream.check_write().unwrap(), MAX_WRITE);
+        stream.write(Bytes::from(vec![1; MAX_WRITE - 1])).unwrap();
+        assert_eq!(stream.check_write().unwrap(), 1);
+        stream.write(Bytes::from_static(&[1])).unwrap();
+        assert_eq!(stream.check_write().unwrap(), 0);
+        assert_eq!(receiver.try_recv().unwrap().len(), MAX_WRITE);
+        assert_eq!(stream.check_write().unwrap(), MAX_WRITE);
+
+        // Declaring a frame longer than the channel allows fails the stream,
+        // without buffering it.
+        stream.write(prefix(MAX_WRITE + 1)).unwrap();
+        assert!(matches!(
+            stream.check_write(),
+            Err(StreamError::LastOperationFailed(_))
+        ));
+        assert!(stream.write(Bytes::from_static(&[1])).is_err());
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+        drop(stream);
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Truncated));
+    }
+
+    #[test]
+    fn partial_frames_on_close() {
+        // Complete frames which were waiting for room are still received
+        // after the guest goes away.
+        let (mut stream, receiver) = output_channel_with_framing(1, Framing::LengthPrefixed);
+        let mut frames = BytesMut::new();
+        for frame in [&b"ab"[..], b"c"] {
+            frames.extend_from_slice(&prefix(frame.len()));
+            frames.extend_from_slice(frame);
+        }
+        stream.write(frames.freeze()).unwrap();
+        drop(stream);
+        assert_eq!(receiver.try_recv(), Ok(Bytes::from_static(b"ab")));
+        assert_eq!(receiver.try_recv(), Ok(Bytes::from_static(b"c")));
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Closed));
+
+        // A frame the guest didn't finish is reported.
+        let (mut stream, receiver) = output_channel_with_framing(1, Framing::LengthPrefixed);
+        stream.write(prefix(3)).unwrap();
+        stream.write(Bytes::from_static(b"ab")).unwrap();
+        drop(stream);
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Truncated));
+
+        // As is a prefix the guest didn't finish.
+        let (mut stream, receiver) = output_channel_with_framing(1, Framing::LengthPrefixed);
+        stream.write(Bytes::from_static(&[0, 0])).unwrap();
+        drop(stream);
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Truncated));
+    }
+}
@@ -0,0 +1,579 @@
+use crate::poll::{DynFuture, Pollable};
+use crate::streams::{InputStream, OutputStream, StreamError, StreamResult};
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use bytes::Bytes;
+use core::future::Future;
+use core::pin::Pin;
+use futures::channel::oneshot;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// The number of bytes a [`FileInputStream`] reads at a time by default, and
+/// a [`FileOutputStream`] permits to be written at a time.
+const CHUNK_SIZE: usize = 64 << 10;
+
+/// Runs the file I/O of a [`FileInputStream`] or [`FileOutputStream`], which
+/// blocks the thread it runs on.
+///
+/// This is implemented by embedders on top of their executor, for example
+/// with `tokio::task::spawn_blocking`, to keep slow file systems from
+/// stalling the threads which run guests. The default is [`BlockInPlace`].
+pub trait BlockingExecutor: Send + Sync {
+    /// Runs `work` to completion, returning a future which resolves once it
+    /// has completed.
+    fn run_blocking(&self, work: Box<dyn FnOnce() + Send>) -> DynFuture<'static>;
+}
+
+/// The default [`BlockingExecutor`], which runs blocking work on the thread
+/// which waits for it.
+///
+/// With the `tokio` feature of this crate, work which is run from within a
+/// multi-threaded tokio runtime uses `tokio::task::block_in_place`, so that
+/// the runtime moves its other tasks off the blocked thread.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BlockInPlace;
+
+impl BlockingExecutor for BlockInPlace {
+    fn run_blocking(&self, work: Box<dyn FnOnce() + Send>) -> DynFuture<'static> {
+        Box::pin(async move {
+            #[cfg(feature = "tokio")]
+            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                if handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread {
+                    return tokio::task::block_in_place(work);
+                }
+            }
+            work()
+        })
+    }
+}
+
+/// File I/O handed to a [`BlockingExecutor`], resolving to its result.
+///
+/// Streams keep the operation they're waiting for across calls to `ready`,
+/// since `poll` drops the readiness futures of streams which aren't ready:
+/// an executor may already be running the operation, which must then be
+/// resumed rather than started again or forgotten.
+type InFlight<T> = Pin<Box<dyn Future<Output = StreamResult<T>> + Send>>;
+
+/// Starts running `f` on `executor`.
+fn start<T: Send + 'static>(
+    executor: &dyn BlockingExecutor,
+    f: impl FnOnce() -> T + Send + 'static,
+) -> InFlight<T> {
+    let (tx, rx) = oneshot::channel();
+    let work = executor.run_blocking(Box::new(move || {
+        let _ = tx.send(f());
+    }));
+    Box::pin(async move {
+        work.await;
+        rx.await
+            .map_err(|_| StreamError::host_bug("blocking executor dropped file I/O"))
+    })
+}
+
+/// Reads up to `len` bytes of `file` at `offset`, without moving its cursor
+/// where the platform allows.
+fn read_at(file: &File, len: usize, offset: u64) -> io::Result<Bytes> {
+    let mut buf = vec![0; len];
+    #[cfg(unix)]
+    let n = std::os::unix::fs::FileExt::read_at(file, &mut buf, offset)?;
+    #[cfg(windows)]
+    let n = std::os::windows::fs::FileExt::seek_read(file, &mut buf, offset)?;
+    #[cfg(not(any(unix, windows)))]
+    let n = {
+        use std::io::{Read, Seek, SeekFrom};
+        let mut file = file;
+        file.seek(SeekFrom::Start(offset))?;
+        file.read(&mut buf)?
+    };
+    buf.truncate(n);
+    Ok(buf.into())
+}
+
+/// Writes all of `bytes` to `file` at `offset`, or at its end if `offset` is
+/// `None`.
+fn write_at(file: &File, bytes: &[u8], offset: Option<u64>) -> io::Result<()> {
+    use std::io::Write;
+    let Some(offset) = offset else {
+        let mut file = file;
+        return file.write_all(bytes);
+    };
+    #[cfg(unix)]
+    return std::os::unix::fs::FileExt::write_all_at(file, bytes, offset);
+    #[cfg(not(unix))]
+    {
+        use std::io::{Seek, SeekFrom};
+        let mut file = file;
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(bytes)
+    }
+}
+
+fn io_error(e: io::Error) -> StreamError {
+    StreamError::LastOperationFailed(e.into())
+}
+
+/// An [`InputStream`] which reads a host file.
+///
+/// The file is read with positional reads starting at its beginning, each
+/// of up to 64 KiB, which are performed by a [`BlockingExecutor`] while the
+/// stream's readiness is awaited, so that `read` itself never blocks. The
+/// stream is [`StreamError::Closed`] once the end of the file is reached.
+pub struct FileInputStream {
+    file: Arc<File>,
+    offset: u64,
+    chunk_size: usize,
+    buffered: Bytes,
+    reading: Option<InFlight<io::Result<Bytes>>>,
+    error: Option<StreamError>,
+    executor: Arc<dyn BlockingExecutor>,
+}
+
+impl FileInputStream {
+    /// Opens the file at `path` for reading.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<FileInputStream> {
+        Ok(FileInputStream::from_file(File::open(path)?))
+    }
+
+    /// Reads `file` from its beginning, regardless of its cursor.
+    pub fn from_file(file: File) -> FileInputStream {
+        FileInputStream {
+            file: Arc::new(file),
+            offset: 0,
+            chunk_size: CHUNK_SIZE,
+            buffered: Bytes::new(),
+            reading: None,
+            error: None,
+            executor: Arc::new(BlockInPlace),
+        }
+    }
+
+    /// Performs reads with `executor` rather than [`BlockInPlace`].
+    pub fn with_executor(mut self, executor: Arc<dyn BlockingExecutor>) -> FileInputStream {
+        self.executor = executor;
+        self
+    }
+
+    /// Reads up to `chunk_size` bytes at a time, which is raised to 1 if
+    /// it's 0.
+    pub fn chunk_size(mut self, chunk_size: usize) -> FileInputStream {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Returns the offset in the file of the next byte to be read.
+    pub fn offset(&self) -> u64 {
+        self.offset - self.buffered.len() as u64
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for FileInputStream {
+    async fn ready(&mut self) {
+        if !self.buffered.is_empty() || self.error.is_some() {
+            return;
+        }
+        if self.reading.is_none() {
+            let file = self.file.clone();
+            let (len, offset) = (self.chunk_size, self.offset);
+            self.reading = Some(start(&*self.executor, move || read_at(&file, len, offset)));
+        }
+        let result = self.reading.as_mut().unwrap().await;
+        self.reading = None;
+        match result {
+            Ok(Ok(bytes)) if bytes.is_empty() => self.error = Some(StreamError::Closed),
+            Ok(Ok(bytes)) => {
+                self.offset += bytes.len() as u64;
+                self.buffered = bytes;
+            }
+            Ok(Err(e)) => self.error = Some(io_error(e)),
+            Err(e) => self.error = Some(e),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl InputStream for FileInputStream {
+    fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+        if !self.buffered.is_empty() {
+            return Ok(self.buffered.split_to(size.min(self.buffered.len())));
+        }
+        match self.error.take() {
+            // Once closed the stream stays closed, while other errors are
+            // reported once and leave the stream closed.
+            Some(e) => {
+                self.error = Some(StreamError::Closed);
+                Err(e)
+            }
+            None => Ok(Bytes::new()),
+        }
+    }
+
+    fn preferred_read_size(&self) -> Option<usize> {
+        Some(self.chunk_size)
+    }
+}
+
+/// When a [`FileOutputStream`] syncs the file to its storage device.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// A flush only waits for written bytes to be handed to the operating
+    /// system.
+    #[default]
+    Never,
+    /// A flush also syncs the file's contents with `File::sync_data`.
+    Data,
+    /// A flush also syncs the file's contents and metadata with
+    /// `File::sync_all`.
+    All,
+}
+
+/// An [`OutputStream`] which writes a host file.
+///
+/// Each write is performed by a [`BlockingExecutor`] while the stream's
+/// readiness is awaited, during which `check-write` permits no further
+/// writes, so that `write` itself never blocks. Writes to a file opened with
+/// [`FileOutputStream::create`] or [`FileOutputStream::from_file`] are
+/// positional, starting at its beginning, while writes to a file opened with
+/// [`FileOutputStream::append`] go to its end. A flush syncs the file
+/// according to the [`SyncPolicy`] configured with
+/// [`FileOutputStream::sync_on_flush`].
+pub struct FileOutputStream {
+    file: Arc<File>,
+    /// The offset of the next write, or `None` when appending.
+    offset: Option<u64>,
+    sync: SyncPolicy,
+    pending: Bytes,
+    sync_pending: bool,
+    /// The write or sync being performed, resolving to the number of bytes
+    /// written.
+    in_flight: Option<InFlight<io::Result<u64>>>,
+    error: Option<StreamError>,
+    executor: Arc<dyn BlockingExecutor>,
+}
+
+impl FileOutputStream {
+    /// Creates the file at `path`, truncating it if it exists.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<FileOutputStream> {
+        Ok(FileOutputStream::from_file(File::create(path)?))
+    }
+
+    /// Opens the file at `path` for appending, creating it if it doesn't
+    /// exist.
+    pub fn append(path: impl AsRef<Path>) -> io::Result<FileOutputStream> {
+        let file = File::options().append(true).create(true).open(path)?;
+        Ok(FileOutputStream {
+            offset: None,
+            ..FileOutputStream::from_file(file)
+        })
+    }
+
+    /// Writes `file` from its beginning, regardless of its cursor.
+    pub fn from_file(file: File) -> FileOutputStream {
+        FileOutputStream {
+            file: Arc::new(file),
+            offset: Some(0),
+            sync: SyncPolicy::Never,
+            pending: Bytes::new(),
+            sync_pending: false,
+            in_flight: None,
+            error: None,
+            executor: Arc::new(BlockInPlace),
+        }
+    }
+
+    /// Performs writes and syncs with `executor` rather than
+    /// [`BlockInPlace`].
+    pub fn with_executor(mut self, executor: Arc<dyn BlockingExecutor>) -> FileOutputStream {
+        self.executor = executor;
+        self
+    }
+
+    /// Makes flushes sync the file according to `policy`, which defaults to
+    /// [`SyncPolicy::Never`].
+    pub fn sync_on_flush(mut self, policy: SyncPolicy) -> FileOutputStream {
+        self.sync = policy;
+        self
+    }
+
+    fn is_busy(&self) -> bool {
+        !self.pending.is_empty() || self.sync_pending || self.in_flight.is_some()
+    }
+
+    /// Starts the pending write, or else the pending sync, if any.
+    fn start_next(&mut self) -> Option<InFlight<io::Result<u64>>> {
+        let file = self.file.clone();
+        if !self.pending.is_empty() {
+            let bytes = core::mem::take(&mut self.pending);
+            let offset = self.offset;
+            return Some(start(&*self.executor, move || -> io::Result<u64> {
+                write_at(&file, &bytes, offset)?;
+                Ok(bytes.len() as u64)
+            }));
+        }
+        if core::mem::take(&mut self.sync_pending) {
+            let sync = self.sync;
+            return Some(start(&*self.executor, move || -> io::Result<u64> {
+                match sync {
+                    SyncPolicy::Never => {}
+                    SyncPolicy::Data => file.sync_data()?,
+                    SyncPolicy::All => file.sync_all()?,
+                }
+                Ok(0)
+            }));
+        }
+        None
+    }
+
+    /// Returns the error of an earlier operation, which leaves the stream
+    /// closed.
+    fn check_error(&mut self) -> StreamResult<()> {
+        match self.error.take() {
+            Some(e) => {
+                self.error = Some(StreamError::Closed);
+                Err(e)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for FileOutputStream {
+    async fn ready(&mut self) {
+        while self.error.is_none() {
+            if self.in_flight.is_none() {
+                self.in_flight = self.start_next();
+            }
+            let Some(op) = &mut self.in_flight else {
+                return;
+            };
+            let result = op.await;
+            self.in_flight = None;
+            match result {
+                Ok(Ok(written)) => self.offset = self.offset.map(|offset| offset + written),
+                Ok(Err(e)) => self.error = Some(io_error(e)),
+                Err(e) => self.error = Some(e),
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl OutputStream for FileOutputStream {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        self.check_error()?;
+        if self.is_busy() || bytes.len() > CHUNK_SIZE {
+            return Err(StreamError::trap("write exceeded the permitted bytes"));
+        }
+        self.pending = bytes;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        self.check_error()?;
+        if self.sync != SyncPolicy::Never {
+            self.sync_pending = true;
+        }
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        self.check_error()?;
+        if self.is_busy() {
+            return Ok(0);
+        }
+        Ok(CHUNK_SIZE)
+    }
+
+    async fn cancel(&mut self) {
+        // Bytes which were written are handed to the operating system rather
+        // than lost.
+        self.ready().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::{WakeSignal, block_on};
+    use futures::FutureExt;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts the work it runs in place.
+    #[derive(Default)]
+    struct Counting(AtomicUsize);
+
+    impl BlockingExecutor for Counting {
+        fn run_blocking(&self, work: Box<dyn FnOnce() + Send>) -> DynFuture<'static> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            BlockInPlace.run_blocking(work)
+        }
+    }
+
+    /// Queues work until [`Deferred::run_all`] is called, like an executor
+    /// whose blocking threads are all busy.
+    #[derive(Default)]
+    struct Deferred(Mutex<Vec<Box<dyn FnOnce() + Send>>>);
+
+    impl Deferred {
+        /// Runs the queued work, returning how much there was.
+        fn run_all(&self) -> usize {
+            let work = core::mem::take(&mut *self.0.lock().unwrap());
+            let n = work.len();
+            for work in work {
+                work();
+            }
+            n
+        }
+    }
+
+    impl BlockingExecutor for Deferred {
+        fn run_blocking(&self, work: Box<dyn FnOnce() + Send>) -> DynFuture<'static> {
+            let (tx, rx) = oneshot::channel();
+            self.0.lock().unwrap().push(Box::new(move || {
+                work();
+                let _ = tx.send(());
+            }));
+            Box::pin(async move {
+                let _ = rx.await;
+            })
+        }
+    }
+
+    #[test]
+    fn write_then_read() -> anyhow::Result<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("file");
+        let executor = Arc::new(Counting::default());
+
+        let mut output = FileOutputStream::create(&path)?
+            .with_executor(executor.clone())
+            .sync_on_flush(SyncPolicy::Data);
+        assert_eq!(output.check_write()?, CHUNK_SIZE);
+        output.write(Bytes::from_static(b"hello, "))?;
+        assert_eq!(output.check_write()?, 0);
+        block_on(
+            &SIGNAL,
+            |_| {},
+            output.blocking_write_and_flush(Bytes::from_static(b"world")),
+        )?;
+        // Two writes and a sync.
+        assert_eq!(executor.0.load(Ordering::Relaxed), 3);
+
+        let mut input = FileInputStream::open(&path)?
+            .with_executor(executor.clone())
+            .chunk_size(4);
+        assert_eq!(input.read(10)?, b""[..]);
+        assert_eq!(
+            block_on(&SIGNAL, |_| {}, input.blocking_read(10))?,
+            b"hell"[..]
+        );
+        assert_eq!(input.offset(), 4);
+        assert_eq!(
+            block_on(&SIGNAL, |_| {}, input.blocking_read(2))?,
+            b"o,"[..]
+        );
+        assert_eq!(input.read(10)?, b" w"[..]);
+        assert_eq!(
+            block_on(&SIGNAL, |_| {}, input.blocking_read(10))?,
+            b"orld"[..]
+        );
+        assert!(matches!(
+            block_on(&SIGNAL, |_| {}, input.blocking_read(10)),
+            Err(StreamError::Closed)
+        ));
+        assert!(matches!(input.read(10), Err(StreamError::Closed)));
+        Ok(())
+    }
+
+    #[test]
+    fn append() -> anyhow::Result<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("file");
+        std::fs::write(&path, "abc")?;
+
+        let mut output = FileOutputStream::append(&path)?;
+        block_on(
+            &SIGNAL,
+            |_| {},
+            output.blocking_write_and_flush(Bytes::from_static(b"def")),
+        )?;
+        assert_eq!(std::fs::read(&path)?, b"abcdef");
+        Ok(())
+    }
+
+    #[test]
+    fn resumes_cancelled_io() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("file");
+        let executor = Arc::new(Deferred::default());
+
+        // `poll` drops the readiness futures of streams which aren't ready,
+        // after which the write that was started must be resumed.
+        let mut output = FileOutputStream::create(&path)?
+            .with_executor(executor.clone())
+            .sync_on_flush(SyncPolicy::Data);
+        output.write(Bytes::from_static(b"hello"))?;
+        assert!(output.ready().now_or_never().is_none());
+        assert_eq!(output.check_write()?, 0);
+        assert_eq!(executor.run_all(), 1);
+        assert!(output.ready().now_or_never().is_some());
+        assert_eq!(executor.run_all(), 0);
+
+        output.write(Bytes::from_static(b", world"))?;
+        output.flush()?;
+        assert!(output.ready().now_or_never().is_none());
+        // The write, and then the sync once the write has been resumed.
+        assert_eq!(executor.run_all(), 1);
+        assert!(output.ready().now_or_never().is_none());
+        assert_eq!(executor.run_all(), 1);
+        assert!(output.ready().now_or_never().is_some());
+        assert_eq!(output.check_write()?, CHUNK_SIZE);
+        assert_eq!(std::fs::read(&path)?, b"hello, world");
+
+        let mut input = FileInputStream::open(&path)?
+            .with_executor(executor.clone())
+            .chunk_size(5);
+        assert!(input.ready().now_or_never().is_none());
+        assert_eq!(executor.run_all(), 1);
+        assert!(input.ready().now_or_never().is_some());
+        assert_eq!(input.read(10)?, b"hello"[..]);
+        assert_eq!(input.offset(), 5);
+        Ok(())
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn block_in_place_within_tokio() -> anyhow::Result<()> {
+        use tokio::runtime::Builder;
+
+        let dir = tempfile::tempdir()?;
+        let path = Arc::new(dir.path().join("file"));
+        // Blocking in place is only possible on a multi-threaded runtime, on
+        // others the work runs directly.
+        let runtimes = [
+            Builder::new_current_thread().build()?,
+            Builder::new_multi_thread().worker_threads(1).build()?,
+        ];
+        for runtime in runtimes {
+            let path = path.clone();
+            let task = runtime.spawn(async move {
+                let mut output = FileOutputStream::create(&*path)?;
+                output
+                    .blocking_write_and_flush(Bytes::from_static(b"hi"))
+                    .await?;
+                let mut input = FileInputStream::open(&*path)?;
+                assert_eq!(input.blocking_read(10).await?, b"hi"[..]);
+                anyhow::Ok(())
+            });
+            runtime.block_on(task)??;
+        }
+        Ok(())
+    }
+}
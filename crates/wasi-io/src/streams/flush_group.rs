@@ -0,0 +1,351 @@
+use super::shared::{Guard, Shared};
+use crate::poll::{Pollable, ReadinessKind, SpinLock};
+use crate::streams::{DynOutputStream, OutputStream, StreamError, StreamResult};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bytes::Bytes;
+use core::future::poll_fn;
+
+/// A group of output streams whose flushes are ordered with respect to one
+/// another.
+///
+/// Guests which write related records to several streams, such as data and
+/// an index into it, may require that a record in one stream never becomes
+/// durable before a record it refers to in another. Flushing both streams
+/// after every record guarantees this, at the cost of a flush per stream per
+/// record. Instead, the streams can [`join`](FlushGroup::join) a
+/// `FlushGroup`, in the order in which their flushes must complete:
+///
+/// * A member's `flush` doesn't flush the stream it wraps, but queues a
+///   barrier for it.
+/// * Waiting for a flushed member to become ready, as `blocking-flush` does,
+///   flushes every member with a queued barrier in the order in which they
+///   joined the group, waiting for each flush to complete before starting
+///   the next. Each member is flushed once, however many barriers it queued.
+///
+/// A flush still means to the guest what it always does: once a flushed
+/// member becomes ready, the bytes written to it before the flush are
+/// flushed, and so are those written before a flush of any member which
+/// joined the group before it. Writes reach the wrapped streams when they're
+/// made; only flushes are deferred.
+///
+/// Like [`SharedOutputStream`](super::SharedOutputStream), this requires the
+/// `std` feature.
+#[derive(Clone, Default)]
+pub struct FlushGroup(Arc<SpinLock<Vec<Arc<Shared<Member>>>>>);
+
+/// A stream which joined a [`FlushGroup`].
+struct Member {
+    stream: DynOutputStream,
+    /// Whether the guest flushed this member since the group last flushed
+    /// it.
+    barrier: bool,
+    /// An error encountered while flushing this member on the group's
+    /// behalf, reported by the guest's next operation.
+    error: Option<StreamError>,
+}
+
+impl Member {
+    /// Flushes the wrapped stream and waits for the flush to complete.
+    async fn flush(&mut self) -> StreamResult<()> {
+        self.stream.flush()?;
+        while self.stream.check_write()? == 0 {
+            self.stream.ready().await;
+        }
+        Ok(())
+    }
+}
+
+impl FlushGroup {
+    /// Creates an empty group.
+    pub fn new() -> FlushGroup {
+        FlushGroup::default()
+    }
+
+    /// Adds `stream` to this group, after the streams which joined it
+    /// before, returning the stream to hand to the guest in its place.
+    pub fn join(&self, stream: DynOutputStream) -> FlushGroupStream {
+        let readiness_kind = stream.readiness_kind();
+        let debug_name = stream.debug_name();
+        let member = Arc::new(Shared::new(Member {
+            stream,
+            barrier: false,
+            error: None,
+        }));
+        self.0.with(|members| members.push(member.clone()));
+        FlushGroupStream {
+            group: self.clone(),
+            member,
+            flushing: false,
+            readiness_kind,
+            debug_name,
+        }
+    }
+
+    /// Flushes the members with a queued barrier, in order.
+    async fn flush(&self) {
+        let members = self.0.with(|members| members.clone());
+        for member in members {
+            let mut member = member.lock().await;
+            if !member.barrier {
+                continue;
+            }
+            if let Err(e) = member.flush().await {
+                member.error = Some(e);
+            }
+            // The barrier is only lifted once the flush has completed, so
+            // that if this future is dropped first, the next flush of the
+            // group still flushes this member before later ones.
+            member.barrier = false;
+        }
+    }
+}
+
+/// An [`OutputStream`] which is a member of a [`FlushGroup`], created with
+/// [`FlushGroup::join`].
+pub struct FlushGroupStream {
+    group: FlushGroup,
+    member: Arc<Shared<Member>>,
+    /// Whether the guest flushed this stream and hasn't yet waited for the
+    /// flush to complete.
+    flushing: bool,
+    /// The diagnostics of the wrapped stream, which may be locked by the
+    /// group when they're asked for.
+    readiness_kind: ReadinessKind,
+    debug_name: Option<String>,
+}
+
+impl FlushGroupStream {
+    /// Returns this member, reporting an error from flushing it on the
+    /// group's behalf if there was one.
+    fn member(&self) -> StreamResult<Guard<Member>> {
+        // The group is only flushed while the guest waits, so the member
+        // is never locked when the guest operates on it.
+        let mut member = self
+            .member
+            .try_lock()
+            .ok_or_else(|| StreamError::host_bug("flush group member is busy"))?;
+        match member.error.take() {
+            Some(e) => Err(e),
+            None => Ok(member),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for FlushGroupStream {
+    async fn ready(&mut self) {
+        if self.flushing {
+            self.group.flush().await;
+            self.flushing = false;
+            return;
+        }
+        // Another member flushing the group may need this one, in which case
+        // the wait ends early.
+        let mut member = self.member.lock().await;
+        let shared = self.member.clone();
+        let contended = poll_fn(move |cx| shared.poll_contended(cx));
+        futures::future::select(member.stream.ready(), contended).await;
+    }
+
+    fn readiness_kind(&self) -> ReadinessKind {
+        self.readiness_kind
+    }
+
+    fn debug_name(&self) -> Option<String> {
+        self.debug_name.clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl OutputStream for FlushGroupStream {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        self.member()?.stream.write(bytes)
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        self.member()?.barrier = true;
+        self.flushing = true;
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        if self.flushing {
+            return Ok(0);
+        }
+        self.member()?.stream.check_write()
+    }
+
+    fn write_zeroes(&mut self, nelem: usize) -> StreamResult<()> {
+        self.member()?.stream.write_zeroes(nelem)
+    }
+
+    async fn cancel(&mut self) {
+        self.group
+            .0
+            .with(|members| members.retain(|m| !Arc::ptr_eq(m, &self.member)));
+        self.member.lock().await.stream.cancel().await
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::executor::{WakeSignal, block_on};
+    use alloc::boxed::Box;
+    use alloc::string::String;
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use futures::FutureExt;
+    use std::sync::Mutex;
+
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    /// A sink whose flushes complete once it's waited on, unless `held` is
+    /// set, recording its writes and flushes in a log shared with other
+    /// sinks.
+    struct Recorder {
+        name: &'static str,
+        log: Log,
+        flushing: bool,
+        held: Arc<AtomicBool>,
+    }
+
+    impl Recorder {
+        fn join(group: &FlushGroup, name: &'static str, log: &Log) -> FlushGroupStream {
+            Recorder::join_held(group, name, log, &Arc::default())
+        }
+
+        fn join_held(
+            group: &FlushGroup,
+            name: &'static str,
+            log: &Log,
+            held: &Arc<AtomicBool>,
+        ) -> FlushGroupStream {
+            group.join(Box::new(Recorder {
+                name,
+                log: log.clone(),
+                flushing: false,
+                held: held.clone(),
+            }))
+        }
+
+        fn record(&self, event: &str) {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{} {event}", self.name));
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Pollable for Recorder {
+        async fn ready(&mut self) {
+            if self.held.load(Ordering::Relaxed) {
+                return futures::future::pending().await;
+            }
+            if self.flushing {
+                self.flushing = false;
+                self.record("durable");
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl OutputStream for Recorder {
+        fn write(&mut self, _bytes: Bytes) -> StreamResult<()> {
+            self.record("write");
+            Ok(())
+        }
+
+        fn flush(&mut self) -> StreamResult<()> {
+            self.record("flush");
+            self.flushing = true;
+            Ok(())
+        }
+
+        fn check_write(&mut self) -> StreamResult<usize> {
+            Ok(if self.flushing { 0 } else { 64 })
+        }
+    }
+
+    fn take(log: &Log) -> Vec<String> {
+        core::mem::take(&mut *log.lock().unwrap())
+    }
+
+    #[test]
+    fn flushes_follow_registration_order() -> StreamResult<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let log = Log::default();
+        let group = FlushGroup::new();
+        let mut data = Recorder::join(&group, "data", &log);
+        let mut index = Recorder::join(&group, "index", &log);
+
+        data.write(Bytes::from_static(b"record"))?;
+        data.flush()?;
+        index.write(Bytes::from_static(b"entry"))?;
+        index.flush()?;
+        data.flush()?;
+        assert_eq!(index.check_write()?, 0);
+        assert_eq!(take(&log), ["data write", "index write"]);
+
+        block_on(&SIGNAL, |_| {}, index.ready());
+        assert_eq!(
+            take(&log),
+            ["data flush", "data durable", "index flush", "index durable"]
+        );
+        assert_eq!(index.check_write()?, 64);
+
+        // The data stream's flush was completed on the index's behalf.
+        assert_eq!(data.check_write()?, 0);
+        block_on(&SIGNAL, |_| {}, data.ready());
+        assert_eq!(data.check_write()?, 64);
+        assert!(take(&log).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn unflushed_members_are_left_alone() -> StreamResult<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let log = Log::default();
+        let group = FlushGroup::new();
+        let mut data = Recorder::join(&group, "data", &log);
+        let mut index = Recorder::join(&group, "index", &log);
+
+        data.write(Bytes::from_static(b"record"))?;
+        index.write(Bytes::from_static(b"entry"))?;
+        index.flush()?;
+        block_on(&SIGNAL, |_| {}, index.ready());
+        assert_eq!(
+            take(&log),
+            ["data write", "index write", "index flush", "index durable"]
+        );
+        assert_eq!(data.check_write()?, 64);
+        Ok(())
+    }
+
+    #[test]
+    fn cancelled_flushes_keep_their_barriers() -> StreamResult<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let log = Log::default();
+        let group = FlushGroup::new();
+        let held = Arc::new(AtomicBool::new(true));
+        let mut data = Recorder::join_held(&group, "data", &log, &held);
+        let mut index = Recorder::join(&group, "index", &log);
+
+        data.flush()?;
+        index.flush()?;
+        // Waiting for the index is abandoned while the data is flushing.
+        assert!(index.ready().now_or_never().is_none());
+        assert_eq!(take(&log), ["data flush"]);
+
+        // The data is still flushed before the index when the wait resumes.
+        held.store(false, Ordering::Relaxed);
+        block_on(&SIGNAL, |_| {}, index.ready());
+        assert_eq!(
+            take(&log),
+            ["data flush", "data durable", "index flush", "index durable"]
+        );
+        Ok(())
+    }
+}
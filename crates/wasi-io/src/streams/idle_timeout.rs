@@ -0,0 +1,359 @@
+use crate::TimerProvider;
+use crate::error::{ErrorCode, IoError};
+use crate::poll::{DynFuture, Pollable, ReadinessKind};
+use crate::streams::{
+    DynInputStream, DynOutputStream, InputStream, OutputStream, StreamError, StreamResult,
+};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use bytes::Bytes;
+use core::task::{Context, Waker};
+use core::time::Duration;
+use futures::future::Either;
+
+/// A stream which is closed once it has been left idle for too long.
+///
+/// This wraps a [`DynInputStream`] or a [`DynOutputStream`], such as an
+/// accepted connection, which a guest may otherwise hold onto without ever
+/// using. Every operation on the stream, including waiting for it to become
+/// ready, restarts an idle timer taken from a [`TimerProvider`]. If the timer
+/// fires before the next operation the wrapped stream is cancelled and
+/// dropped, and operations return [`StreamError::Closed`], or an
+/// [`IoError`] with [`ErrorCode::TIMED_OUT`] once if
+/// [`IdleTimeoutStream::error_on_expiry`] was used.
+///
+/// The timer is only observed by operations on the stream and while a guest
+/// waits for it, for example in `poll`, in which case the wait ends as soon
+/// as the timer fires. Cancellation of the wrapped stream starts as soon as
+/// the timer is observed to have fired, and if it doesn't complete at once
+/// it's completed by the next wait for the stream or by
+/// [`cancel`](InputStream::cancel). The wrapped stream of a stream which is
+/// never touched again after its timer fires is released when the stream is
+/// dropped.
+pub struct IdleTimeoutStream<S> {
+    inner: Option<S>,
+    timers: Arc<dyn TimerProvider>,
+    idle: Duration,
+    timer: DynFuture<'static>,
+    /// The cancellation of the wrapped stream, once it has been started and
+    /// until it completes.
+    cancelling: Option<DynFuture<'static>>,
+    expired: bool,
+    error_on_expiry: bool,
+}
+
+impl<S> IdleTimeoutStream<S> {
+    /// Wraps `inner`, closing it if it's left idle for `idle`, as measured by
+    /// `timers`.
+    pub fn new(inner: S, idle: Duration, timers: Arc<dyn TimerProvider>) -> IdleTimeoutStream<S> {
+        IdleTimeoutStream {
+            inner: Some(inner),
+            timer: timers.sleep(idle),
+            timers,
+            idle,
+            cancelling: None,
+            expired: false,
+            error_on_expiry: false,
+        }
+    }
+
+    /// Makes the first operation after the idle timer fires fail with an
+    /// [`IoError`] describing the timeout, rather than report that the
+    /// stream is closed.
+    pub fn error_on_expiry(mut self) -> IdleTimeoutStream<S> {
+        self.error_on_expiry = true;
+        self
+    }
+
+    /// Returns whether the idle timer has fired.
+    pub fn is_expired(&self) -> bool {
+        self.expired
+    }
+
+    /// Restarts the idle timer, unless it fired since the last operation,
+    /// returning whether it did.
+    fn restart(&mut self) -> bool {
+        if !self.expired {
+            let mut cx = Context::from_waker(Waker::noop());
+            self.expired = self.timer.as_mut().poll(&mut cx).is_ready();
+        }
+        if !self.expired {
+            self.timer = self.timers.sleep(self.idle);
+        }
+        self.expired
+    }
+}
+
+/// Wrapped streams which can be cancelled once they've been taken out of an
+/// [`IdleTimeoutStream`].
+trait CancelOwned {
+    fn cancel_owned(self) -> DynFuture<'static>;
+}
+
+impl CancelOwned for DynInputStream {
+    fn cancel_owned(mut self) -> DynFuture<'static> {
+        Box::pin(async move { self.cancel().await })
+    }
+}
+
+impl CancelOwned for DynOutputStream {
+    fn cancel_owned(mut self) -> DynFuture<'static> {
+        Box::pin(async move { self.cancel().await })
+    }
+}
+
+impl<S: CancelOwned> IdleTimeoutStream<S> {
+    /// Starts cancelling the wrapped stream, unless that already happened,
+    /// and keeps the cancellation around if it doesn't complete at once.
+    fn start_cancel(&mut self) {
+        if let Some(inner) = self.inner.take() {
+            let mut cancel = inner.cancel_owned();
+            let mut cx = Context::from_waker(Waker::noop());
+            if cancel.as_mut().poll(&mut cx).is_pending() {
+                self.cancelling = Some(cancel);
+            }
+        }
+    }
+
+    /// Cancels the wrapped stream, or waits for its cancellation to
+    /// complete if it was already started.
+    async fn finish_cancel(&mut self) {
+        self.start_cancel();
+        if let Some(cancel) = &mut self.cancelling {
+            cancel.await;
+            self.cancelling = None;
+        }
+    }
+
+    /// Records an operation, returning the wrapped stream unless the idle
+    /// timer fired since the last one.
+    fn touch(&mut self) -> StreamResult<&mut S> {
+        if self.restart() {
+            self.start_cancel();
+            if self.error_on_expiry {
+                self.error_on_expiry = false;
+                let message = format!("stream was idle for {:?}", self.idle);
+                return Err(IoError::new(ErrorCode::TIMED_OUT, message).into());
+            }
+            return Err(StreamError::Closed);
+        }
+        Ok(self.inner.as_mut().unwrap())
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for IdleTimeoutStream<DynInputStream> {
+    async fn ready(&mut self) {
+        if !self.restart() {
+            let inner = self.inner.as_mut().unwrap();
+            let ready = futures::future::select(inner.ready(), self.timer.as_mut()).await;
+            self.expired = matches!(ready, Either::Right(_));
+        }
+        if self.expired {
+            self.finish_cancel().await;
+        }
+    }
+
+    fn readiness_kind(&self) -> ReadinessKind {
+        self.inner
+            .as_ref()
+            .map_or(ReadinessKind::Level, |inner| inner.readiness_kind())
+    }
+
+    fn debug_name(&self) -> Option<String> {
+        self.inner.as_ref()?.debug_name()
+    }
+}
+
+#[async_trait::async_trait]
+impl InputStream for IdleTimeoutStream<DynInputStream> {
+    fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+        self.touch()?.read(size)
+    }
+
+    fn skip(&mut self, nelem: usize) -> StreamResult<usize> {
+        self.touch()?.skip(nelem)
+    }
+
+    fn preferred_read_size(&self) -> Option<usize> {
+        self.inner.as_ref()?.preferred_read_size()
+    }
+
+    async fn cancel(&mut self) {
+        self.finish_cancel().await;
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for IdleTimeoutStream<DynOutputStream> {
+    async fn ready(&mut self) {
+        if !self.restart() {
+            let inner = self.inner.as_mut().unwrap();
+            let ready = futures::future::select(inner.ready(), self.timer.as_mut()).await;
+            self.expired = matches!(ready, Either::Right(_));
+        }
+        if self.expired {
+            self.finish_cancel().await;
+        }
+    }
+
+    fn readiness_kind(&self) -> ReadinessKind {
+        self.inner
+            .as_ref()
+            .map_or(ReadinessKind::Level, |inner| inner.readiness_kind())
+    }
+
+    fn debug_name(&self) -> Option<String> {
+        self.inner.as_ref()?.debug_name()
+    }
+}
+
+#[async_trait::async_trait]
+impl OutputStream for IdleTimeoutStream<DynOutputStream> {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        self.touch()?.write(bytes)
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        self.touch()?.flush()
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        self.touch()?.check_write()
+    }
+
+    fn write_zeroes(&mut self, nelem: usize) -> StreamResult<()> {
+        self.touch()?.write_zeroes(nelem)
+    }
+
+    async fn cancel(&mut self) {
+        self.finish_cancel().await;
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::executor::{WakeSignal, block_on};
+    use crate::test_streams::Stalled;
+    use crate::time::MockTimerProvider;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::task::Poll;
+
+    const IDLE: Duration = Duration::from_millis(10);
+
+    fn quiet_input(clock: &MockTimerProvider) -> (IdleTimeoutStream<DynInputStream>, Stalled) {
+        let stalled = Stalled::default();
+        let inner: DynInputStream = Box::new(stalled.clone());
+        let timers = Arc::new(clock.clone());
+        (IdleTimeoutStream::new(inner, IDLE, timers), stalled)
+    }
+
+    #[test]
+    fn activity_resets_the_timer() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let clock = MockTimerProvider::new();
+        let (mut stream, stalled) = quiet_input(&clock);
+
+        // Each read restarts the timer, so the stream outlives several idle
+        // periods in total.
+        for _ in 0..3 {
+            clock.advance(IDLE - Duration::from_millis(1));
+            assert_eq!(stream.read(1).unwrap(), [1][..]);
+        }
+        assert_eq!(stalled.cancellations(), 0);
+        clock.advance(IDLE);
+        assert!(matches!(stream.read(1), Err(StreamError::Closed)));
+        assert_eq!(stalled.cancellations(), 1);
+        assert!(matches!(stream.read(1), Err(StreamError::Closed)));
+        assert!(stream.is_expired());
+        block_on(&SIGNAL, |_| {}, InputStream::cancel(&mut stream));
+    }
+
+    /// A stream whose cancellation takes two polls, counting how often it
+    /// was started and how often it completed.
+    struct Lingering(Arc<[AtomicUsize; 2]>);
+
+    #[async_trait::async_trait]
+    impl Pollable for Lingering {
+        async fn ready(&mut self) {}
+    }
+
+    #[async_trait::async_trait]
+    impl InputStream for Lingering {
+        fn read(&mut self, _size: usize) -> StreamResult<Bytes> {
+            Ok(Bytes::from_static(b"x"))
+        }
+
+        async fn cancel(&mut self) {
+            self.0[0].fetch_add(1, Ordering::SeqCst);
+            let mut yielded = false;
+            core::future::poll_fn(|cx| {
+                if yielded {
+                    return Poll::Ready(());
+                }
+                yielded = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            })
+            .await;
+            self.0[1].fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn expiry_cancels_the_inner_stream_once() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let clock = MockTimerProvider::new();
+        let counts = Arc::new([AtomicUsize::new(0), AtomicUsize::new(0)]);
+        let inner: DynInputStream = Box::new(Lingering(counts.clone()));
+        let mut stream = IdleTimeoutStream::new(inner, IDLE, Arc::new(clock.clone()));
+        let seen = || counts.each_ref().map(|n| n.load(Ordering::SeqCst));
+
+        // Observing the expiry starts the cancellation, which the next wait
+        // completes without starting it again.
+        clock.advance(IDLE);
+        assert!(matches!(stream.read(1), Err(StreamError::Closed)));
+        assert_eq!(seen(), [1, 0]);
+        assert!(matches!(stream.read(1), Err(StreamError::Closed)));
+        block_on(&SIGNAL, |_| {}, stream.ready());
+        assert_eq!(seen(), [1, 1]);
+        block_on(&SIGNAL, |_| {}, InputStream::cancel(&mut stream));
+        assert_eq!(seen(), [1, 1]);
+    }
+
+    #[test]
+    fn expiry_wakes_a_waiting_guest() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let clock = MockTimerProvider::new();
+        let (mut stream, stalled) = quiet_input(&clock);
+
+        clock.advance(IDLE - Duration::from_millis(1));
+        // Waiting restarts the timer, which then fires during the wait.
+        block_on(&SIGNAL, |_| clock.advance(IDLE), stream.ready());
+        assert!(stream.is_expired());
+        assert_eq!(stalled.cancellations(), 1);
+        assert!(matches!(stream.read(1), Err(StreamError::Closed)));
+    }
+
+    #[test]
+    fn expiry_can_be_reported_as_an_error() {
+        let clock = MockTimerProvider::new();
+        let inner: DynOutputStream = Box::new(Stalled::default());
+        let mut stream =
+            IdleTimeoutStream::new(inner, IDLE, Arc::new(clock.clone())).error_on_expiry();
+
+        assert_eq!(stream.check_write().unwrap(), Stalled::PERMIT);
+        stream.write(Bytes::from_static(b"hello")).unwrap();
+        clock.advance(IDLE);
+        match stream.flush() {
+            Err(StreamError::LastOperationFailed(e)) => {
+                assert_eq!(IoError::code_of(&e), Some(ErrorCode::TIMED_OUT));
+            }
+            other => panic!("expected a timeout error, got {other:?}"),
+        }
+        assert!(matches!(stream.check_write(), Err(StreamError::Closed)));
+    }
+}
@@ -0,0 +1,923 @@
+use super::shared::Shared;
+use crate::error::{ErrorCode, IoError};
+use crate::poll::{Pollable, SpinLock, WakerList};
+use crate::streams::{
+    DynInputStream, DynOutputStream, InputStream, OutputStream, StreamError, StreamResult,
+};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bytes::{Bytes, BytesMut};
+use core::future::{pending, poll_fn};
+use core::task::Poll;
+use futures::future::Either;
+
+/// The length of a frame's header: a big-endian lane number, a kind, and a
+/// big-endian length.
+const HEADER_LEN: usize = 9;
+/// A frame carrying `length` bytes of a lane's data.
+const DATA: u8 = 0;
+/// A frame granting the receiver of the lane's data `length` more bytes of
+/// credit.
+const CREDIT: u8 = 1;
+/// A frame marking the end of a lane's data.
+const CLOSE: u8 = 2;
+
+/// The largest amount of data carried by a single frame, which bounds how
+/// long one lane can hold up the others.
+const MAX_FRAME: usize = 16 * 1024;
+/// The flow-control window used by [`Multiplexer::new`].
+const DEFAULT_WINDOW: usize = 64 * 1024;
+/// The most bytes read from the connection at once.
+const READ_SIZE: usize = 64 * 1024;
+/// Frames are only built while fewer than this many encoded bytes are
+/// waiting to be written to the connection.
+const OUTPUT_LIMIT: usize = 2 * MAX_FRAME;
+
+/// Many independent stream pairs, or lanes, carried over one connection.
+///
+/// A `Multiplexer` owns the input and output streams of a connection, such
+/// as a socket, and hands out lanes with [`Multiplexer::open_lane`]. Each
+/// lane is an input stream and an output stream which can be given to a
+/// guest like any other. The peer at the other end of the connection runs a
+/// `Multiplexer` as well, and the lanes each side opens are paired up in the
+/// order in which they're opened.
+///
+/// Lane data is carried in frames of at most 16 KiB, sent a frame per lane
+/// at a time so that busy lanes share the connection. Each lane has a
+/// flow-control window, which must be the same at both ends: a lane's output
+/// only accepts as many bytes as the peer's lane input has room for, and the
+/// room is granted again as the peer's guest reads them. A lane whose reader
+/// falls behind therefore never blocks the connection, or the other lanes.
+///
+/// Dropping a lane's output stream sends the end of its data once what was
+/// written before has been sent, after which the peer's lane input reports
+/// that it's closed, as do all lanes once the connection's input is closed.
+/// Malformed frames, such as frames of an unknown kind or frames exceeding a
+/// lane's window, poison the connection: every operation on every lane then
+/// fails with an [`IoError`] with [`ErrorCode::INVALID_DATA`] describing the
+/// problem.
+///
+/// The connection is serviced by the lanes' operations, including waits for
+/// lanes to become ready, so no task needs to be spawned for it.
+///
+/// Like [`SharedOutputStream`](super::SharedOutputStream), this requires the
+/// `std` feature.
+#[derive(Clone)]
+pub struct Multiplexer(Arc<Mux>);
+
+struct Mux {
+    conn: Arc<Shared<Conn>>,
+    state: SpinLock<State>,
+    /// Lanes waiting for another lane's operation to change their state.
+    waiters: WakerList,
+    window: usize,
+}
+
+/// The connection, which only the lane holding its lock reads or writes.
+struct Conn {
+    input: DynInputStream,
+    output: DynOutputStream,
+    input_closed: bool,
+    /// Bytes read which don't yet make up a whole frame.
+    received: BytesMut,
+    /// Frames waiting to be written.
+    sending: BytesMut,
+    /// Whether `output` is being flushed.
+    flushing: bool,
+}
+
+struct State {
+    lanes: BTreeMap<u32, Lane>,
+    next_lane: u32,
+    /// The lane to consider first when sending data.
+    rotation: u32,
+    /// Incremented whenever any lane's state changes, see [`Mux::wait`].
+    version: u64,
+    poison: Option<IoError>,
+    input_closed: bool,
+    output_closed: bool,
+}
+
+struct Lane {
+    /// Data received but not yet read.
+    received: BytesMut,
+    /// Bytes read or discarded which the peer hasn't been granted credit
+    /// for again.
+    unacked: usize,
+    /// Whether the peer's output has been closed.
+    remote_closed: bool,
+    /// Whether the guest dropped this lane's input.
+    input_dropped: bool,
+    /// The number of bytes the guest may still write.
+    credit: usize,
+    /// Data written but not yet framed.
+    staged: BytesMut,
+    flush: Flush,
+    /// Whether the guest dropped this lane's output.
+    output_dropped: bool,
+    close_sent: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Flush {
+    Idle,
+    /// The guest flushed the lane, but the connection hasn't been flushed
+    /// since its data was sent.
+    Requested,
+    /// The connection is being flushed with all of the lane's data.
+    InFlight,
+}
+
+impl Lane {
+    fn new(window: usize, remote_closed: bool) -> Lane {
+        Lane {
+            received: BytesMut::new(),
+            unacked: 0,
+            remote_closed,
+            input_dropped: false,
+            credit: window,
+            staged: BytesMut::new(),
+            flush: Flush::Idle,
+            output_dropped: false,
+            close_sent: false,
+        }
+    }
+}
+
+fn frame(out: &mut BytesMut, lane: u32, kind: u8, len: usize, payload: &[u8]) {
+    out.extend_from_slice(&lane.to_be_bytes());
+    out.extend_from_slice(&[kind]);
+    out.extend_from_slice(&(len as u32).to_be_bytes());
+    out.extend_from_slice(payload);
+}
+
+impl State {
+    fn lane(&mut self, id: u32) -> &mut Lane {
+        self.lanes
+            .get_mut(&id)
+            .expect("lanes outlive their streams")
+    }
+
+    fn changed(&mut self) {
+        self.version += 1;
+    }
+
+    fn fail(&mut self, error: IoError) {
+        if self.poison.is_none() {
+            self.poison = Some(error);
+            self.changed();
+        }
+    }
+
+    fn corrupt(&mut self, what: String) {
+        let message = format!("multiplexed connection is corrupt: {what}");
+        self.fail(IoError::new(ErrorCode::INVALID_DATA, message));
+    }
+
+    /// Returns the error every lane operation reports, if any.
+    fn check(&self) -> StreamResult<()> {
+        match &self.poison {
+            Some(e) => Err(e.clone().into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Applies the whole frames in `buf` to the lanes.
+    fn receive(&mut self, buf: &mut BytesMut, closed: bool, window: usize) {
+        while self.poison.is_none() && buf.len() >= HEADER_LEN {
+            let id = u32::from_be_bytes(buf[..4].try_into().unwrap());
+            let kind = buf[4];
+            let len = u32::from_be_bytes(buf[5..HEADER_LEN].try_into().unwrap()) as usize;
+            let payload = match kind {
+                DATA => len,
+                CREDIT | CLOSE => 0,
+                _ => return self.corrupt(format!("unknown frame kind {kind} on lane {id}")),
+            };
+            if payload > MAX_FRAME {
+                return self.corrupt(format!("frame of {len} bytes on lane {id} is too large"));
+            }
+            if buf.len() < HEADER_LEN + payload {
+                break;
+            }
+            let _ = buf.split_to(HEADER_LEN);
+            let data = buf.split_to(payload);
+            self.changed();
+            // Lanes which both sides are done with are forgotten.
+            if id < self.next_lane && !self.lanes.contains_key(&id) {
+                continue;
+            }
+            let input_closed = self.input_closed;
+            let lane = self
+                .lanes
+                .entry(id)
+                .or_insert_with(|| Lane::new(window, input_closed));
+            match kind {
+                DATA if lane.remote_closed => {
+                    return self.corrupt(format!("data after the end of lane {id}"));
+                }
+                DATA if lane.received.len() + lane.unacked + len > window => {
+                    return self.corrupt(format!("lane {id} exceeded its flow-control window"));
+                }
+                DATA if lane.input_dropped => lane.unacked += len,
+                DATA => lane.received.extend_from_slice(&data),
+                CREDIT if lane.credit + len > window => {
+                    return self.corrupt(format!("lane {id} was granted credit beyond its window"));
+                }
+                CREDIT => lane.credit += len,
+                _ => lane.remote_closed = true,
+            }
+        }
+        if closed && !self.input_closed && self.poison.is_none() {
+            if !buf.is_empty() {
+                return self.corrupt("the connection closed partway through a frame".into());
+            }
+            self.input_closed = true;
+            for lane in self.lanes.values_mut() {
+                lane.remote_closed = true;
+            }
+            self.changed();
+        }
+    }
+
+    /// Appends the frames ready to be sent to `out`.
+    fn build(&mut self, out: &mut BytesMut, window: usize) {
+        if self.poison.is_some() || self.output_closed {
+            return;
+        }
+        for (&id, lane) in self.lanes.iter_mut() {
+            if lane.unacked > 0 && lane.unacked >= window / 2 {
+                frame(out, id, CREDIT, lane.unacked, &[]);
+                lane.unacked = 0;
+            }
+        }
+        let ids: Vec<u32> = self
+            .lanes
+            .range(self.rotation..)
+            .chain(self.lanes.range(..self.rotation))
+            .map(|(id, _)| *id)
+            .collect();
+        'rounds: loop {
+            let mut sent = false;
+            for &id in &ids {
+                if out.len() >= OUTPUT_LIMIT {
+                    break 'rounds;
+                }
+                let lane = self.lane(id);
+                if lane.staged.is_empty() {
+                    continue;
+                }
+                let data = lane.staged.split_to(lane.staged.len().min(MAX_FRAME));
+                frame(out, id, DATA, data.len(), &data);
+                self.rotation = id.wrapping_add(1);
+                sent = true;
+            }
+            if !sent {
+                break;
+            }
+        }
+        for (&id, lane) in self.lanes.iter_mut() {
+            if lane.output_dropped && !lane.close_sent && lane.staged.is_empty() {
+                frame(out, id, CLOSE, 0, &[]);
+                lane.close_sent = true;
+            }
+        }
+        self.lanes
+            .retain(|_, lane| !(lane.input_dropped && lane.remote_closed && lane.close_sent));
+    }
+
+    /// Marks the lanes whose flushed data has all been sent as flushing
+    /// with the connection, returning whether there were any.
+    fn start_flush(&mut self) -> bool {
+        let mut any = false;
+        for lane in self.lanes.values_mut() {
+            if lane.flush == Flush::Requested && lane.staged.is_empty() {
+                lane.flush = Flush::InFlight;
+                any = true;
+            }
+        }
+        any
+    }
+
+    fn finish_flush(&mut self) {
+        for lane in self.lanes.values_mut() {
+            if lane.flush == Flush::InFlight {
+                lane.flush = Flush::Idle;
+            }
+        }
+        self.changed();
+    }
+}
+
+impl Mux {
+    fn version(&self) -> u64 {
+        self.state.with(|s| s.version)
+    }
+
+    /// Reports an error from the connection to every lane.
+    fn fail(&self, conn: &mut Conn, e: StreamError) {
+        conn.sending.clear();
+        self.state.with(|s| match e {
+            StreamError::Closed => {
+                s.output_closed = true;
+                s.changed();
+            }
+            StreamError::LastOperationFailed(e) | StreamError::Trap(e) => {
+                let message = format!("multiplexed connection failed: {e}");
+                s.fail(IoError::new(ErrorCode::OTHER, message));
+            }
+        });
+    }
+
+    /// Moves data between the connection and the lanes, without waiting,
+    /// and wakes the lanes waiting for their state to change.
+    fn pump(&self, conn: &mut Conn) {
+        let version = self.version();
+        while !conn.input_closed {
+            match conn.input.read(READ_SIZE) {
+                Ok(bytes) if bytes.is_empty() => break,
+                Ok(bytes) => conn.received.extend_from_slice(&bytes),
+                Err(StreamError::Closed) => conn.input_closed = true,
+                Err(e) => {
+                    conn.input_closed = true;
+                    self.fail(conn, e);
+                }
+            }
+        }
+        self.state
+            .with(|s| s.receive(&mut conn.received, conn.input_closed, self.window));
+        loop {
+            self.state.with(|s| s.build(&mut conn.sending, self.window));
+            if !self.send(conn) {
+                break;
+            }
+        }
+        self.flush(conn);
+        if self.version() != version {
+            self.wake();
+        }
+    }
+
+    /// Writes as many framed bytes as the connection accepts, returning
+    /// whether any were written.
+    fn send(&self, conn: &mut Conn) -> bool {
+        let mut sent = false;
+        while !conn.sending.is_empty() {
+            match conn.output.check_write() {
+                Ok(0) => break,
+                Ok(permit) => {
+                    let len = permit.min(conn.sending.len());
+                    let bytes = conn.sending.split_to(len).freeze();
+                    if let Err(e) = conn.output.write(bytes) {
+                        self.fail(conn, e);
+                        return false;
+                    }
+                    sent = true;
+                }
+                Err(e) => {
+                    self.fail(conn, e);
+                    return false;
+                }
+            }
+        }
+        sent
+    }
+
+    /// Flushes the connection on behalf of the lanes whose guests flushed
+    /// them, once their data has been written.
+    fn flush(&self, conn: &mut Conn) {
+        while conn.sending.is_empty() {
+            if conn.flushing {
+                match conn.output.check_write() {
+                    Ok(0) => return,
+                    Ok(_) => {
+                        conn.flushing = false;
+                        self.state.with(|s| s.finish_flush());
+                    }
+                    Err(e) => return self.fail(conn, e),
+                }
+            }
+            if !self.state.with(|s| s.start_flush()) {
+                return;
+            }
+            if let Err(e) = conn.output.flush() {
+                return self.fail(conn, e);
+            }
+            conn.flushing = true;
+        }
+    }
+
+    /// Services the connection unless another lane already is, in which case
+    /// that lane is told that this one's state changed.
+    fn try_pump(&self) {
+        match self.conn.try_lock() {
+            Some(mut conn) => self.pump(&mut conn),
+            None => self.wake(),
+        }
+    }
+
+    /// Records that a lane's state changed, and services the connection on
+    /// its behalf.
+    fn notify(&self) {
+        self.state.with(State::changed);
+        self.try_pump();
+    }
+
+    fn wake(&self) {
+        self.waiters.wake_all();
+    }
+
+    /// Resolves once some lane's state changes after `version`.
+    fn changed(&self, version: u64) -> impl Future<Output = ()> + Unpin + '_ {
+        poll_fn(move |cx| {
+            if self.version() == version {
+                self.waiters.register(cx.waker());
+            }
+            // The version may have changed before the waker was registered.
+            if self.version() == version {
+                Poll::Pending
+            } else {
+                Poll::Ready(())
+            }
+        })
+    }
+
+    /// Waits for some lane's state to change after `version`, servicing the
+    /// connection unless another lane already is.
+    async fn wait(&self, version: u64) {
+        let Some(mut conn) = self.conn.try_lock() else {
+            // The lane servicing the connection wakes the others when it
+            // changes their state, and one of them takes over once it stops.
+            let locked = self.conn.lock();
+            futures::future::select(self.changed(version), locked).await;
+            return;
+        };
+        self.pump(&mut conn);
+        if self.version() != version {
+            return;
+        }
+        let Conn {
+            input,
+            output,
+            input_closed,
+            sending,
+            flushing,
+            ..
+        } = &mut *conn;
+        let readable = match input_closed {
+            true => Either::Left(pending()),
+            false => Either::Right(input.ready()),
+        };
+        let writable = match !sending.is_empty() || *flushing {
+            true => Either::Left(output.ready()),
+            false => Either::Right(pending()),
+        };
+        // Other lanes' operations change their state without servicing the
+        // connection while it's locked here, so they end the wait.
+        let io = futures::future::select(readable, writable);
+        futures::future::select(io, self.changed(version)).await;
+    }
+}
+
+impl Multiplexer {
+    /// Multiplexes lanes over the connection made up of `input` and
+    /// `output`, with a flow-control window of 64 KiB per lane.
+    pub fn new(input: DynInputStream, output: DynOutputStream) -> Multiplexer {
+        Multiplexer::with_window(input, output, DEFAULT_WINDOW)
+    }
+
+    /// Multiplexes lanes over the connection made up of `input` and
+    /// `output`, with a flow-control window of `window` bytes per lane.
+    pub fn with_window(
+        input: DynInputStream,
+        output: DynOutputStream,
+        window: usize,
+    ) -> Multiplexer {
+        let conn = Conn {
+            input,
+            output,
+            input_closed: false,
+            received: BytesMut::new(),
+            sending: BytesMut::new(),
+            flushing: false,
+        };
+        Multiplexer(Arc::new(Mux {
+            conn: Arc::new(Shared::new(conn)),
+            state: SpinLock::new(State {
+                lanes: BTreeMap::new(),
+                next_lane: 0,
+                rotation: 0,
+                version: 0,
+                poison: None,
+                input_closed: false,
+                output_closed: false,
+            }),
+            waiters: WakerList::default(),
+            window: window.clamp(1, u32::MAX as usize),
+        }))
+    }
+
+    /// Opens the next lane, returning its input and output streams.
+    pub fn open_lane(&self) -> (DynInputStream, DynOutputStream) {
+        let mux = &self.0;
+        let id = mux.state.with(|s| {
+            let id = s.next_lane;
+            s.next_lane += 1;
+            let input_closed = s.input_closed;
+            s.lanes
+                .entry(id)
+                .or_insert_with(|| Lane::new(mux.window, input_closed));
+            id
+        });
+        let input = LaneInput {
+            mux: mux.clone(),
+            id,
+        };
+        let output = LaneOutput {
+            mux: mux.clone(),
+            id,
+        };
+        (Box::new(input), Box::new(output))
+    }
+}
+
+struct LaneInput {
+    mux: Arc<Mux>,
+    id: u32,
+}
+
+#[async_trait::async_trait]
+impl Pollable for LaneInput {
+    async fn ready(&mut self) {
+        loop {
+            let version = self.mux.version();
+            let ready = self.mux.state.with(|s| {
+                let lane = s.lane(self.id);
+                s.poison.is_some() || !lane.received.is_empty() || lane.remote_closed
+            });
+            if ready {
+                return;
+            }
+            self.mux.wait(version).await;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl InputStream for LaneInput {
+    fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+        self.mux.try_pump();
+        let read = self.mux.state.with(|s| {
+            s.check()?;
+            let lane = s.lane(self.id);
+            if lane.received.is_empty() && lane.remote_closed {
+                return Err(StreamError::Closed);
+            }
+            let len = size.min(lane.received.len());
+            lane.unacked += len;
+            Ok(lane.received.split_to(len).freeze())
+        })?;
+        // Reading may have freed enough of the window to grant the peer
+        // more credit.
+        if !read.is_empty() {
+            self.mux.notify();
+        }
+        Ok(read)
+    }
+
+    async fn cancel(&mut self) {
+        self.mux.state.with(|s| {
+            let lane = s.lane(self.id);
+            lane.input_dropped = true;
+            lane.unacked += lane.received.len();
+            lane.received.clear();
+        });
+        self.mux.notify();
+    }
+}
+
+struct LaneOutput {
+    mux: Arc<Mux>,
+    id: u32,
+}
+
+impl LaneOutput {
+    fn with_lane<R>(&self, f: impl FnOnce(&mut Lane) -> StreamResult<R>) -> StreamResult<R> {
+        self.mux.state.with(|s| {
+            s.check()?;
+            if s.output_closed {
+                return Err(StreamError::Closed);
+            }
+            f(s.lane(self.id))
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for LaneOutput {
+    async fn ready(&mut self) {
+        loop {
+            let version = self.mux.version();
+            let ready = self.mux.state.with(|s| {
+                let lane = s.lane(self.id);
+                let writable = lane.credit > 0 && lane.flush == Flush::Idle;
+                s.poison.is_some() || s.output_closed || writable
+            });
+            if ready {
+                return;
+            }
+            self.mux.wait(version).await;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl OutputStream for LaneOutput {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        self.with_lane(|lane| {
+            if bytes.len() > lane.credit || lane.flush != Flush::Idle {
+                return Err(StreamError::trap("write exceeded permit"));
+            }
+            lane.credit -= bytes.len();
+            lane.staged.extend_from_slice(&bytes);
+            Ok(())
+        })?;
+        self.mux.notify();
+        Ok(())
+    }
+
+    fn write_zeroes(&mut self, nelem: usize) -> StreamResult<()> {
+        self.with_lane(|lane| {
+            if nelem > lane.credit || lane.flush != Flush::Idle {
+                return Err(StreamError::trap("write-zeroes exceeded permit"));
+            }
+            lane.credit -= nelem;
+            lane.staged.resize(lane.staged.len() + nelem, 0);
+            Ok(())
+        })?;
+        self.mux.notify();
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        self.with_lane(|lane| {
+            if lane.flush == Flush::Idle {
+                lane.flush = Flush::Requested;
+            }
+            Ok(())
+        })?;
+        self.mux.notify();
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        self.mux.try_pump();
+        self.with_lane(|lane| match lane.flush {
+            Flush::Idle => Ok(lane.credit),
+            Flush::Requested | Flush::InFlight => Ok(0),
+        })
+    }
+
+    async fn cancel(&mut self) {
+        // Data already written is still sent, followed by the end of the
+        // lane.
+        self.mux
+            .state
+            .with(|s| s.lane(self.id).output_dropped = true);
+        self.mux.notify();
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::executor::{WakeSignal, block_on};
+    use alloc::collections::VecDeque;
+    use alloc::string::ToString;
+    use core::future::Future;
+    use core::pin::Pin;
+    use core::task::Waker;
+    use std::sync::Mutex;
+
+    const PIPE_CAPACITY: usize = 4096;
+
+    /// One direction of an in-memory connection with a bounded buffer.
+    #[derive(Clone, Default)]
+    struct Pipe(Arc<Mutex<PipeState>>);
+
+    #[derive(Default)]
+    struct PipeState {
+        buf: VecDeque<u8>,
+        closed: bool,
+        wakers: Vec<Waker>,
+    }
+
+    impl Pipe {
+        fn update<R>(&self, f: impl FnOnce(&mut PipeState) -> R) -> R {
+            let mut state = self.0.lock().unwrap();
+            let result = f(&mut state);
+            for waker in state.wakers.drain(..) {
+                waker.wake();
+            }
+            result
+        }
+
+        async fn wait(&self, ready: impl Fn(&PipeState) -> bool) {
+            poll_fn(|cx| {
+                let mut state = self.0.lock().unwrap();
+                if ready(&state) {
+                    return Poll::Ready(());
+                }
+                state.wakers.push(cx.waker().clone());
+                Poll::Pending
+            })
+            .await
+        }
+    }
+
+    struct PipeReader(Pipe);
+
+    #[async_trait::async_trait]
+    impl Pollable for PipeReader {
+        async fn ready(&mut self) {
+            self.0.wait(|s| !s.buf.is_empty() || s.closed).await
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl InputStream for PipeReader {
+        fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+            self.0.update(|s| {
+                if s.buf.is_empty() && s.closed {
+                    return Err(StreamError::Closed);
+                }
+                let len = size.min(s.buf.len());
+                Ok(s.buf.drain(..len).collect())
+            })
+        }
+    }
+
+    struct PipeWriter(Pipe);
+
+    #[async_trait::async_trait]
+    impl Pollable for PipeWriter {
+        async fn ready(&mut self) {
+            self.0.wait(|s| s.buf.len() < PIPE_CAPACITY).await
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl OutputStream for PipeWriter {
+        fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+            self.0.update(|s| s.buf.extend(&bytes[..]));
+            Ok(())
+        }
+
+        fn flush(&mut self) -> StreamResult<()> {
+            Ok(())
+        }
+
+        fn check_write(&mut self) -> StreamResult<usize> {
+            Ok(PIPE_CAPACITY - self.0.0.lock().unwrap().buf.len().min(PIPE_CAPACITY))
+        }
+    }
+
+    impl Drop for PipeWriter {
+        fn drop(&mut self) {
+            self.0.update(|s| s.closed = true);
+        }
+    }
+
+    fn connect() -> (Multiplexer, Multiplexer) {
+        let (there, back) = (Pipe::default(), Pipe::default());
+        let a = Multiplexer::new(
+            Box::new(PipeReader(back.clone())),
+            Box::new(PipeWriter(there.clone())),
+        );
+        let b = Multiplexer::new(Box::new(PipeReader(there)), Box::new(PipeWriter(back)));
+        (a, b)
+    }
+
+    fn payload(seed: usize, len: usize) -> Bytes {
+        (0..len).map(|i| (i * 31 + seed) as u8).collect()
+    }
+
+    /// Writes `data` to `output`, flushes it, and closes it.
+    async fn send(mut output: DynOutputStream, mut data: Bytes) {
+        while !data.is_empty() {
+            let permit = output.write_ready().await.unwrap();
+            output.write(data.split_to(permit.min(data.len()))).unwrap();
+        }
+        output.flush().unwrap();
+        output.write_ready().await.unwrap();
+        output.cancel().await;
+    }
+
+    /// Reads from `input` until it's closed.
+    async fn receive(mut input: DynInputStream) -> Vec<u8> {
+        let mut received = Vec::new();
+        loop {
+            match input.blocking_read(4096).await {
+                Ok(bytes) => received.extend_from_slice(&bytes),
+                Err(StreamError::Closed) => return received,
+                Err(e) => panic!("lane failed: {e}"),
+            }
+        }
+    }
+
+    type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+    #[test]
+    fn many_lanes_transfer_concurrently() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let (a, b) = connect();
+        let mut tasks: Vec<Task> = Vec::new();
+        for i in 0..24 {
+            let (a_in, a_out) = a.open_lane();
+            let (b_in, b_out) = b.open_lane();
+            let there = payload(i, 50_000 + i * 997);
+            let back = payload(i + 100, 20_000 + i * 31);
+            tasks.push(Box::pin(send(a_out, there.clone())));
+            tasks.push(Box::pin(
+                async move { assert_eq!(receive(b_in).await, there) },
+            ));
+            tasks.push(Box::pin(send(b_out, back.clone())));
+            tasks.push(Box::pin(
+                async move { assert_eq!(receive(a_in).await, back) },
+            ));
+        }
+        block_on(
+            &SIGNAL,
+            |_| std::thread::yield_now(),
+            futures::future::join_all(tasks),
+        );
+    }
+
+    #[test]
+    fn stalled_lanes_dont_starve_others() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let (a, b) = connect();
+        let (_, mut stalled_out) = a.open_lane();
+        let (mut stalled_in, _) = b.open_lane();
+        let (_, busy_out) = a.open_lane();
+        let (busy_in, _) = b.open_lane();
+
+        // Nothing reads the stalled lane, so its writer runs out of credit
+        // once it has filled the window.
+        let mut written = 0;
+        loop {
+            let permit = stalled_out.check_write().unwrap();
+            if permit == 0 {
+                break;
+            }
+            stalled_out.write(payload(0, permit)).unwrap();
+            written += permit;
+        }
+        assert_eq!(written, DEFAULT_WINDOW);
+
+        let data = payload(1, 1 << 20);
+        let transfer = futures::future::join(send(busy_out, data.clone()), receive(busy_in));
+        let ((), received) = block_on(&SIGNAL, |_| std::thread::yield_now(), transfer);
+        assert_eq!(received, data);
+        assert_eq!(stalled_out.check_write().unwrap(), 0);
+
+        // Reading the stalled lane grants its writer credit again.
+        let mut read = 0;
+        while read < DEFAULT_WINDOW {
+            let bytes = block_on(&SIGNAL, |_| {}, stalled_in.blocking_read(DEFAULT_WINDOW));
+            read += bytes.unwrap().len();
+        }
+        let permit = block_on(&SIGNAL, |_| {}, stalled_out.write_ready()).unwrap();
+        assert!(permit > 0);
+    }
+
+    #[test]
+    fn corrupt_frames_poison_every_lane() {
+        let input = Pipe::default();
+        input.update(|s| s.buf.extend([0, 0, 0, 1, 7, 0, 0, 0, 0]));
+        let mux = Multiplexer::new(
+            Box::new(PipeReader(input)),
+            Box::new(PipeWriter(Pipe::default())),
+        );
+        let (mut first, _) = mux.open_lane();
+        let (mut second, mut second_out) = mux.open_lane();
+
+        let message = "4: multiplexed connection is corrupt: unknown frame kind 7 on lane 1";
+        for result in [first.read(16).map(drop), second.read(16).map(drop)] {
+            match result {
+                Err(StreamError::LastOperationFailed(e)) => {
+                    assert_eq!(IoError::code_of(&e), Some(ErrorCode::INVALID_DATA));
+                    assert_eq!(e.to_string(), message);
+                }
+                other => panic!("expected the lane to be poisoned, got {other:?}"),
+            }
+        }
+        assert!(matches!(
+            second_out.check_write(),
+            Err(StreamError::LastOperationFailed(_))
+        ));
+    }
+}
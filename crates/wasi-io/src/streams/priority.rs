@@ -0,0 +1,516 @@
+use crate::poll::{Pollable, ReadinessKind};
+use crate::streams::{DynOutputStream, OutputStream, StreamError, StreamResult};
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::sync::Arc;
+use bytes::{Bytes, BytesMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The budget of each priority of a [`PriorityOutputStream`] unless
+/// configured otherwise.
+const DEFAULT_BUDGET: usize = 64 * 1024;
+
+/// The priority of a write to a [`PriorityOutputStream`], as assigned by its
+/// classifier.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Data which is the first to go under pressure, such as debug logs.
+    Low,
+    /// Data which is kept in favor of low-priority data.
+    Normal,
+    /// Data which is never evicted for lower-priority data, such as error
+    /// logs.
+    High,
+}
+
+impl Priority {
+    const ALL: [Priority; 3] = [Priority::Low, Priority::Normal, Priority::High];
+}
+
+/// An [`OutputStream`] which buffers writes by priority while the stream it
+/// wraps applies backpressure, evicting lower-priority data first once its
+/// budgets are exceeded.
+///
+/// Guests which log messages of every severity to one stream would rather
+/// lose debug messages than errors when the destination can't keep up. Each
+/// write is assigned a [`Priority`] by the classifier given to
+/// [`PriorityOutputStream::new`] and queued with other writes of that
+/// priority. Queued writes are passed on to the wrapped stream as its
+/// permits allow, highest priority first and in the order in which they
+/// were written within a priority, whenever the guest writes, flushes, or
+/// waits for the stream.
+///
+/// Each priority has a [`budget`](PriorityOutputStream::budget): the most
+/// bytes which may be queued in total, across all priorities, for data of
+/// that priority to be kept. When a write takes the queued bytes over the
+/// budget of its priority, queued writes of lower priority are evicted,
+/// lowest priority first and oldest first within a priority, until the queue
+/// fits again. If it still doesn't fit then the write itself is evicted,
+/// unless it is of high priority. Data of the same or a higher priority than
+/// a write is never evicted for it. Evicted writes are counted by the
+/// [`Evictions`] returned from [`PriorityOutputStream::evictions`].
+///
+/// The guest's permits are what the high-priority budget has left once the
+/// queued high-priority data is accounted for, since lower-priority data
+/// makes way for it. The guest is therefore never blocked solely because
+/// lower-priority data is queued.
+pub struct PriorityOutputStream {
+    inner: DynOutputStream,
+    classify: fn(&[u8]) -> Priority,
+    classes: [Class; 3],
+    /// The bytes queued across all priorities.
+    queued: usize,
+    /// Whether the guest flushed this stream and hasn't yet waited for the
+    /// flush to complete.
+    flushing: bool,
+    /// An error encountered while waiting for the wrapped stream, reported
+    /// by the guest's next operation.
+    error: Option<StreamError>,
+    evictions: Evictions,
+}
+
+/// The writes of one priority queued by a [`PriorityOutputStream`].
+struct Class {
+    budget: usize,
+    queue: VecDeque<Bytes>,
+    /// The bytes in `queue`.
+    queued: usize,
+    /// Whether part of the write at the front of `queue` has already been
+    /// passed on, in which case it's no longer evicted.
+    partial: bool,
+}
+
+/// Counters of the writes a [`PriorityOutputStream`] evicted, by priority.
+///
+/// Counters are cheap to clone and clones share their counts, so they may
+/// be kept by the host after the stream is handed to the guest.
+#[derive(Clone, Default)]
+pub struct Evictions(Arc<[EvictionCounter; 3]>);
+
+#[derive(Default)]
+struct EvictionCounter {
+    writes: AtomicUsize,
+    bytes: AtomicUsize,
+}
+
+impl Evictions {
+    /// Returns the number of writes of `priority` which were evicted.
+    pub fn writes(&self, priority: Priority) -> usize {
+        self.0[priority as usize].writes.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes of `priority` which were evicted.
+    pub fn bytes(&self, priority: Priority) -> usize {
+        self.0[priority as usize].bytes.load(Ordering::Relaxed)
+    }
+
+    fn record(&self, priority: Priority, bytes: usize) {
+        let counter = &self.0[priority as usize];
+        counter.writes.fetch_add(1, Ordering::Relaxed);
+        counter.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+impl PriorityOutputStream {
+    /// Wraps `inner`, assigning each write the priority returned by
+    /// `classify` for its bytes.
+    ///
+    /// By default each priority has a budget of 64 KiB.
+    pub fn new(inner: DynOutputStream, classify: fn(&[u8]) -> Priority) -> PriorityOutputStream {
+        PriorityOutputStream {
+            inner,
+            classify,
+            classes: Priority::ALL.map(|_| Class {
+                budget: DEFAULT_BUDGET,
+                queue: VecDeque::new(),
+                queued: 0,
+                partial: false,
+            }),
+            queued: 0,
+            flushing: false,
+            error: None,
+            evictions: Evictions::default(),
+        }
+    }
+
+    /// Sets the most bytes which may be queued in total for data of
+    /// `priority` to be kept.
+    pub fn budget(mut self, priority: Priority, bytes: usize) -> PriorityOutputStream {
+        self.classes[priority as usize].budget = bytes;
+        self
+    }
+
+    /// Returns the counters of the writes this stream evicted.
+    pub fn evictions(&self) -> Evictions {
+        self.evictions.clone()
+    }
+
+    /// Returns the bytes the guest may write, which is what the
+    /// high-priority budget has left.
+    fn permit(&self) -> usize {
+        let high = &self.classes[Priority::High as usize];
+        high.budget.saturating_sub(high.queued)
+    }
+
+    /// Passes queued writes on to the wrapped stream, highest priority
+    /// first, until its permits run out.
+    fn drain(&mut self) -> StreamResult<()> {
+        // A write which was partly passed on is finished before anything
+        // else so that writes aren't interleaved.
+        if let Some(i) = self.classes.iter().position(|c| c.partial) {
+            if !self.pass_on_front(i)? {
+                return Ok(());
+            }
+        }
+        for i in (0..self.classes.len()).rev() {
+            while !self.classes[i].queue.is_empty() {
+                if !self.pass_on_front(i)? {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Passes as much of the oldest write queued in `self.classes[i]` on to
+    /// the wrapped stream as it permits, returning whether all of it was.
+    fn pass_on_front(&mut self, i: usize) -> StreamResult<bool> {
+        let permit = self.inner.check_write()?;
+        if permit == 0 {
+            return Ok(false);
+        }
+        let class = &mut self.classes[i];
+        let front = class.queue.front_mut().unwrap();
+        let bytes = if front.len() <= permit {
+            class.partial = false;
+            class.queue.pop_front().unwrap()
+        } else {
+            class.partial = true;
+            front.split_to(permit)
+        };
+        class.queued -= bytes.len();
+        self.queued -= bytes.len();
+        let done = !class.partial;
+        self.inner.write(bytes)?;
+        Ok(done)
+    }
+
+    /// Evicts queued writes of lower priority than `priority`, lowest
+    /// priority and oldest first, until the queue fits the budget of
+    /// `priority`, and then the write of `priority` just queued if it still
+    /// doesn't fit.
+    fn evict(&mut self, priority: Priority) {
+        let budget = self.classes[priority as usize].budget;
+        for p in Priority::ALL.into_iter().take_while(|p| *p < priority) {
+            let class = &mut self.classes[p as usize];
+            while self.queued > budget {
+                let Some(bytes) = class.queue.remove(usize::from(class.partial)) else {
+                    break;
+                };
+                class.queued -= bytes.len();
+                self.queued -= bytes.len();
+                self.evictions.record(p, bytes.len());
+            }
+        }
+
+        // High-priority writes always fit their budget once lower-priority
+        // data is gone, as that's what the guest's permit allows, but for a
+        // partly passed on write at the front of a lower priority. That
+        // remainder is bounded by the wrapped stream's permit, so the write
+        // is kept.
+        if self.queued <= budget || priority == Priority::High {
+            return;
+        }
+        let class = &mut self.classes[priority as usize];
+        if class.partial && class.queue.len() == 1 {
+            return;
+        }
+        let bytes = class.queue.pop_back().unwrap();
+        class.queued -= bytes.len();
+        self.queued -= bytes.len();
+        self.evictions.record(priority, bytes.len());
+    }
+
+    fn take_error(&mut self) -> StreamResult<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Flushes the wrapped stream and waits for the flush to complete.
+    async fn flush_inner(&mut self) -> StreamResult<()> {
+        self.inner.flush()?;
+        while self.inner.check_write()? == 0 {
+            self.inner.ready().await;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for PriorityOutputStream {
+    async fn ready(&mut self) {
+        loop {
+            if let Err(e) = self.drain() {
+                self.error = Some(e);
+                return;
+            }
+            if self.queued == 0 && self.flushing {
+                if let Err(e) = self.flush_inner().await {
+                    self.error = Some(e);
+                }
+                self.flushing = false;
+                return;
+            }
+            if !self.flushing && self.permit() > 0 {
+                return;
+            }
+            self.inner.ready().await;
+        }
+    }
+
+    fn readiness_kind(&self) -> ReadinessKind {
+        self.inner.readiness_kind()
+    }
+
+    fn debug_name(&self) -> Option<String> {
+        self.inner.debug_name()
+    }
+}
+
+#[async_trait::async_trait]
+impl OutputStream for PriorityOutputStream {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        self.take_error()?;
+        if self.flushing || bytes.len() > self.permit() {
+            return Err(StreamError::trap("write exceeded permit"));
+        }
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        let priority = (self.classify)(&bytes);
+        let class = &mut self.classes[priority as usize];
+        class.queued += bytes.len();
+        self.queued += bytes.len();
+        class.queue.push_back(bytes);
+        self.drain()?;
+        self.evict(priority);
+        Ok(())
+    }
+
+    fn write_zeroes(&mut self, nelem: usize) -> StreamResult<()> {
+        self.take_error()?;
+        if self.flushing || nelem > self.permit() {
+            return Err(StreamError::trap("write-zeroes exceeded permit"));
+        }
+        // Zeroes are passed on as a run if nothing is queued ahead of them
+        // and the wrapped stream permits all of them, and are otherwise
+        // queued like any other write.
+        self.drain()?;
+        if self.queued == 0 && self.inner.check_write()? >= nelem {
+            return self.inner.write_zeroes(nelem);
+        }
+        self.write(BytesMut::zeroed(nelem).freeze())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        self.take_error()?;
+        self.drain()?;
+        self.flushing = true;
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        self.take_error()?;
+        self.drain()?;
+        Ok(if self.flushing { 0 } else { self.permit() })
+    }
+
+    async fn cancel(&mut self) {
+        self.inner.cancel().await
+    }
+
+    fn buffered_bytes(&self) -> Option<usize> {
+        Some(self.queued)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::executor::{WakeSignal, block_on};
+    use alloc::boxed::Box;
+    use alloc::vec::Vec;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct SinkState {
+        permit: usize,
+        written: Vec<u8>,
+        zero_runs: Vec<usize>,
+        flushes: usize,
+    }
+
+    /// A sink whose permit is controlled by the test, recording the bytes
+    /// written to it.
+    struct Sink(Arc<Mutex<SinkState>>);
+
+    #[async_trait::async_trait]
+    impl Pollable for Sink {
+        async fn ready(&mut self) {}
+    }
+
+    #[async_trait::async_trait]
+    impl OutputStream for Sink {
+        fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+            let mut state = self.0.lock().unwrap();
+            assert!(bytes.len() <= state.permit, "write exceeded permit");
+            state.permit -= bytes.len();
+            state.written.extend_from_slice(&bytes);
+            Ok(())
+        }
+
+        fn write_zeroes(&mut self, nelem: usize) -> StreamResult<()> {
+            let mut state = self.0.lock().unwrap();
+            assert!(nelem <= state.permit, "write-zeroes exceeded permit");
+            state.permit -= nelem;
+            state.zero_runs.push(nelem);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> StreamResult<()> {
+            self.0.lock().unwrap().flushes += 1;
+            Ok(())
+        }
+
+        fn check_write(&mut self) -> StreamResult<usize> {
+            Ok(self.0.lock().unwrap().permit)
+        }
+    }
+
+    fn classify(bytes: &[u8]) -> Priority {
+        match bytes.first() {
+            Some(b'E') => Priority::High,
+            Some(b'D') => Priority::Low,
+            _ => Priority::Normal,
+        }
+    }
+
+    fn stream() -> (PriorityOutputStream, Arc<Mutex<SinkState>>) {
+        let state = Arc::new(Mutex::new(SinkState::default()));
+        let stream = PriorityOutputStream::new(Box::new(Sink(state.clone())), classify);
+        (stream, state)
+    }
+
+    fn write(stream: &mut PriorityOutputStream, line: &'static str) -> StreamResult<()> {
+        stream.write(Bytes::from_static(line.as_bytes()))
+    }
+
+    #[test]
+    fn zeroes_are_passed_on_as_runs_unless_queued() -> StreamResult<()> {
+        let (mut stream, sink) = stream();
+        sink.lock().unwrap().permit = 8;
+        stream.write_zeroes(6)?;
+        assert_eq!(sink.lock().unwrap().zero_runs, [6]);
+
+        // Zeroes which the wrapped stream can't take yet are queued behind
+        // the write waiting for it, and passed on as bytes.
+        write(&mut stream, "ERR1")?;
+        stream.write_zeroes(2)?;
+        sink.lock().unwrap().permit = 8;
+        stream.check_write()?;
+        let sink = sink.lock().unwrap();
+        assert_eq!(sink.zero_runs, [6]);
+        assert_eq!(sink.written, b"ERR1\0\0");
+        Ok(())
+    }
+
+    #[test]
+    fn saturated_sink_evicts_low_priority() -> StreamResult<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let (stream, sink) = stream();
+        let mut stream = stream.budget(Priority::Low, 8).budget(Priority::High, 16);
+        let evictions = stream.evictions();
+
+        write(&mut stream, "DBG1")?;
+        write(&mut stream, "DBG2")?;
+        write(&mut stream, "DBG3")?;
+        assert_eq!(evictions.writes(Priority::Low), 1);
+
+        // Debug lines make way for errors, and don't reduce the permit.
+        assert_eq!(stream.check_write()?, 16);
+        write(&mut stream, "ERR1")?;
+        write(&mut stream, "ERR2")?;
+        write(&mut stream, "ERR3")?;
+        assert_eq!(stream.check_write()?, 4);
+        write(&mut stream, "ERR4")?;
+        assert_eq!(stream.check_write()?, 0);
+        assert!(write(&mut stream, "DBG4").is_err());
+        assert_eq!(stream.buffered_bytes(), Some(16));
+
+        assert_eq!(evictions.writes(Priority::Low), 3);
+        assert_eq!(evictions.bytes(Priority::Low), 12);
+        assert_eq!(evictions.writes(Priority::High), 0);
+        assert!(sink.lock().unwrap().written.is_empty());
+
+        sink.lock().unwrap().permit = 64;
+        stream.flush()?;
+        block_on(&SIGNAL, |_| {}, stream.ready());
+        let sink = sink.lock().unwrap();
+        assert_eq!(sink.written, b"ERR1ERR2ERR3ERR4");
+        assert_eq!(sink.flushes, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn partial_low_priority_write_does_not_evict_high_priority() -> StreamResult<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let (stream, sink) = stream();
+        let mut stream = stream.budget(Priority::High, 8);
+        let evictions = stream.evictions();
+
+        // Half of the debug line is passed on, so the rest can't be evicted
+        // and the queue stays over the high-priority budget.
+        sink.lock().unwrap().permit = 2;
+        write(&mut stream, "DBG1")?;
+        write(&mut stream, "ERR1")?;
+        write(&mut stream, "ERR2")?;
+        assert_eq!(stream.buffered_bytes(), Some(10));
+        assert_eq!(evictions.writes(Priority::Low), 0);
+        assert_eq!(evictions.writes(Priority::High), 0);
+
+        sink.lock().unwrap().permit = 64;
+        stream.flush()?;
+        block_on(&SIGNAL, |_| {}, stream.ready());
+        assert_eq!(sink.lock().unwrap().written, b"DBG1ERR1ERR2");
+        Ok(())
+    }
+
+    #[test]
+    fn flush_drains_in_priority_order() -> StreamResult<()> {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let (mut stream, sink) = stream();
+        for line in ["DBG1", "INF1", "ERR1", "DBG2", "INF2", "ERR2"] {
+            write(&mut stream, line)?;
+        }
+        assert_eq!(stream.buffered_bytes(), Some(24));
+
+        // A write split by the sink's permit is finished before anything of
+        // a higher priority is passed on.
+        sink.lock().unwrap().permit = 18;
+        assert_eq!(stream.check_write()?, DEFAULT_BUDGET);
+        assert_eq!(sink.lock().unwrap().written, b"ERR1ERR2INF1INF2DB");
+        write(&mut stream, "ERR3")?;
+
+        sink.lock().unwrap().permit = 64;
+        stream.flush()?;
+        assert_eq!(stream.check_write()?, 0);
+        block_on(&SIGNAL, |_| {}, stream.ready());
+        assert_eq!(stream.check_write()?, DEFAULT_BUDGET);
+        let sink = sink.lock().unwrap();
+        assert_eq!(sink.written, b"ERR1ERR2INF1INF2DBG1ERR3DBG2");
+        assert_eq!(sink.flushes, 1);
+        Ok(())
+    }
+}
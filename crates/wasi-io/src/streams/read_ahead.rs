@@ -0,0 +1,487 @@
+use crate::Spawn;
+use crate::poll::{Pollable, ReadinessKind};
+use crate::snapshot::SnapshotableStream;
+use crate::streams::{DynInputStream, InputStream, MetadataMap, StreamError, StreamResult};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use bytes::Bytes;
+use futures::channel::oneshot;
+
+/// An [`InputStream`] which reads ahead from the stream it wraps.
+///
+/// Guests typically read in a loop of `subscribe`, `poll`, and `read`, which
+/// costs a full round trip to a high-latency stream per chunk. This stream
+/// performs a `blocking_read` of up to `buffer_size` bytes from the wrapped
+/// stream in its [`Pollable::ready`], so that being ready means data is
+/// already buffered, and `read` serves buffered data before touching the
+/// wrapped stream.
+///
+/// Once the buffer has been drained the next chunk is prefetched lazily by
+/// the next `ready`, or in the background when a spawner was provided with
+/// [`ReadAheadInputStream::with_spawner`], which lets the guest process one
+/// chunk while the next is read.
+///
+/// Errors, including [`StreamError::Closed`], which are encountered while
+/// prefetching are reported by the first `read` after the data which was
+/// buffered before them.
+///
+/// If the wrapped stream has a
+/// [`preferred_read_size`](InputStream::preferred_read_size) larger than the
+/// buffer size it's read in chunks of that size instead, and this stream
+/// reports the size of its chunks as its own preferred read size.
+pub struct ReadAheadInputStream {
+    state: State,
+    buffer_size: usize,
+    buffered: Bytes,
+    error: Option<StreamError>,
+    spawner: Option<Arc<dyn Spawn>>,
+    metadata: Option<MetadataMap>,
+    /// The diagnostics of the wrapped stream, which is owned by a background
+    /// task while it's being prefetched from.
+    readiness_kind: ReadinessKind,
+    debug_name: Option<String>,
+}
+
+enum State {
+    /// The wrapped stream isn't being read from.
+    Idle(DynInputStream),
+    /// A background task owns the wrapped stream and sends it back along
+    /// with the result of its read.
+    Prefetching(oneshot::Receiver<(DynInputStream, StreamResult<Bytes>)>),
+    /// The background task was dropped without finishing, losing the wrapped
+    /// stream.
+    Lost,
+}
+
+impl ReadAheadInputStream {
+    /// Wraps `inner`, reading up to `buffer_size` bytes from it at a time
+    /// when this stream's readiness is awaited, or up to its preferred read
+    /// size if that's larger.
+    pub fn new(inner: DynInputStream, buffer_size: usize) -> ReadAheadInputStream {
+        let buffer_size = inner
+            .preferred_read_size()
+            .map_or(buffer_size, |preferred| preferred.max(buffer_size));
+        ReadAheadInputStream {
+            metadata: inner.metadata().cloned(),
+            readiness_kind: inner.readiness_kind(),
+            debug_name: inner.debug_name(),
+            state: State::Idle(inner),
+            buffer_size,
+            buffered: Bytes::new(),
+            error: None,
+            spawner: None,
+        }
+    }
+
+    /// Like [`ReadAheadInputStream::new`], but once the buffer has been
+    /// drained the next chunk is read on a task spawned onto `spawner`.
+    pub fn with_spawner(
+        inner: DynInputStream,
+        buffer_size: usize,
+        spawner: Arc<dyn Spawn>,
+    ) -> ReadAheadInputStream {
+        ReadAheadInputStream {
+            spawner: Some(spawner),
+            ..ReadAheadInputStream::new(inner, buffer_size)
+        }
+    }
+
+    /// Returns the number of bytes which have been read ahead but not yet
+    /// read from this stream.
+    pub fn buffered(&self) -> usize {
+        self.buffered.len()
+    }
+
+    fn has_result(&self) -> bool {
+        !self.buffered.is_empty() || self.error.is_some()
+    }
+
+    fn store(&mut self, result: StreamResult<Bytes>) {
+        match result {
+            Ok(bytes) => self.buffered = bytes,
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    fn finish_prefetch(
+        &mut self,
+        result: Result<(DynInputStream, StreamResult<Bytes>), oneshot::Canceled>,
+    ) {
+        match result {
+            Ok((inner, result)) => {
+                self.state = State::Idle(inner);
+                self.store(result);
+            }
+            Err(oneshot::Canceled) => {
+                self.state = State::Lost;
+                self.error = Some(StreamError::host_bug("read-ahead task was dropped"));
+            }
+        }
+    }
+
+    /// Starts reading the next chunk in the background, if a spawner was
+    /// provided.
+    fn prefetch(&mut self) {
+        let Some(spawner) = &self.spawner else {
+            return;
+        };
+        let mut inner = match core::mem::replace(&mut self.state, State::Lost) {
+            State::Idle(inner) => inner,
+            state => {
+                self.state = state;
+                return;
+            }
+        };
+        let (tx, rx) = oneshot::channel();
+        let size = self.buffer_size;
+        spawner.spawn(Box::pin(async move {
+            let result = inner.blocking_read(size).await;
+            let _ = tx.send((inner, result));
+        }));
+        self.state = State::Prefetching(rx);
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for ReadAheadInputStream {
+    async fn ready(&mut self) {
+        if self.has_result() {
+            return;
+        }
+        match &mut self.state {
+            State::Idle(inner) => {
+                let result = inner.blocking_read(self.buffer_size).await;
+                self.store(result);
+            }
+            State::Prefetching(rx) => {
+                let result = rx.await;
+                self.finish_prefetch(result);
+            }
+            State::Lost => {}
+        }
+    }
+
+    fn readiness_kind(&self) -> ReadinessKind {
+        self.readiness_kind
+    }
+
+    fn debug_name(&self) -> Option<String> {
+        self.debug_name.clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl InputStream for ReadAheadInputStream {
+    fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+        if !self.buffered.is_empty() {
+            let bytes = self.buffered.split_to(size.min(self.buffered.len()));
+            if !self.has_result() {
+                self.prefetch();
+            }
+            return Ok(bytes);
+        }
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        match &mut self.state {
+            State::Idle(inner) => inner.read(size),
+            State::Prefetching(rx) => match rx.try_recv() {
+                Ok(None) => Ok(Bytes::new()),
+                result => {
+                    let result = result.map(|r| r.unwrap());
+                    self.finish_prefetch(result);
+                    self.read(size)
+                }
+            },
+            State::Lost => Err(StreamError::Closed),
+        }
+    }
+
+    async fn cancel(&mut self) {
+        // A background read can't be interrupted, so it's left to finish on
+        // its own and the wrapped stream is dropped along with its result.
+        if let State::Idle(inner) = &mut self.state {
+            inner.cancel().await;
+        }
+    }
+
+    fn metadata(&self) -> Option<&MetadataMap> {
+        // The wrapped stream isn't available while it's prefetching, so its
+        // metadata is captured up front.
+        self.metadata.as_ref()
+    }
+
+    fn preferred_read_size(&self) -> Option<usize> {
+        Some(self.buffer_size)
+    }
+
+    fn as_snapshotable(&self) -> Option<&dyn SnapshotableStream> {
+        // Buffered data isn't part of the wrapped stream's snapshot.
+        match &self.state {
+            State::Idle(inner) if !self.has_result() => inner.as_snapshotable(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::TimerProvider;
+    use crate::executor::{WakeSignal, block_on};
+    use crate::poll::DynFuture;
+    use crate::time::MockTimerProvider;
+    use alloc::collections::VecDeque;
+    use alloc::vec::Vec;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::task::Context;
+    use core::time::Duration;
+
+    /// A stream which produces the given results, each after it's been
+    /// awaited for `latency` on a virtual clock.
+    struct Latent {
+        results: VecDeque<StreamResult<Bytes>>,
+        latency: Duration,
+        clock: MockTimerProvider,
+        arrived: bool,
+        reads: Arc<AtomicUsize>,
+    }
+
+    impl Latent {
+        fn new(
+            results: impl IntoIterator<Item = StreamResult<Bytes>>,
+            latency: Duration,
+            clock: &MockTimerProvider,
+        ) -> Latent {
+            Latent {
+                results: results.into_iter().collect(),
+                latency,
+                clock: clock.clone(),
+                arrived: false,
+                reads: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Pollable for Latent {
+        async fn ready(&mut self) {
+            if self.arrived || self.results.is_empty() {
+                return;
+            }
+            self.clock.sleep(self.latency).await;
+            self.arrived = true;
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl InputStream for Latent {
+        fn read(&mut self, _size: usize) -> StreamResult<Bytes> {
+            if self.results.is_empty() {
+                return Err(StreamError::Closed);
+            }
+            if !core::mem::take(&mut self.arrived) {
+                return Ok(Bytes::new());
+            }
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            self.results.pop_front().unwrap()
+        }
+    }
+
+    /// A stream which prefers large reads, recording the size of each read.
+    struct Chunked {
+        preferred: usize,
+        sizes: Arc<std::sync::Mutex<Vec<usize>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Pollable for Chunked {
+        async fn ready(&mut self) {}
+    }
+
+    #[async_trait::async_trait]
+    impl InputStream for Chunked {
+        fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+            self.sizes.lock().unwrap().push(size);
+            Ok(Bytes::from(vec![0; size.min(self.preferred)]))
+        }
+
+        fn preferred_read_size(&self) -> Option<usize> {
+            Some(self.preferred)
+        }
+    }
+
+    /// Queues spawned futures until [`Tasks::run`] polls them, standing in
+    /// for tasks which run alongside the guest.
+    #[derive(Clone, Default)]
+    struct Tasks(Arc<std::sync::Mutex<Vec<DynFuture<'static>>>>);
+
+    impl Spawn for Tasks {
+        fn spawn(&self, future: DynFuture<'static>) {
+            self.0.lock().unwrap().push(future);
+        }
+    }
+
+    impl Tasks {
+        /// Polls each queued task once, dropping those which finish.
+        fn run(&self) {
+            let mut tasks = core::mem::take(&mut *self.0.lock().unwrap());
+            let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+            tasks.retain_mut(|task| task.as_mut().poll(&mut cx).is_pending());
+            self.0.lock().unwrap().extend(tasks);
+        }
+
+        /// A `wait` function for `block_on` which lets the queued tasks make
+        /// progress before moving `clock` to the next pending timer.
+        fn wait<'a>(&'a self, clock: &'a MockTimerProvider) -> impl FnMut(&WakeSignal) + 'a {
+            move |signal| {
+                self.run();
+                clock.wait(signal);
+            }
+        }
+    }
+
+    /// A virtual clock which jumps to the next timer whenever everything is
+    /// idle.
+    fn clock() -> MockTimerProvider {
+        let clock = MockTimerProvider::new();
+        clock.set_auto_advance(true);
+        clock
+    }
+
+    /// Reads `stream` to the end as a guest would, spending `compute` on
+    /// each chunk it reads while `tasks` keep running.
+    fn guest_read_all(
+        signal: &'static WakeSignal,
+        stream: &mut dyn InputStream,
+        clock: &MockTimerProvider,
+        tasks: &Tasks,
+        compute: Duration,
+    ) -> StreamResult<Vec<u8>> {
+        let mut contents = Vec::new();
+        loop {
+            block_on(signal, tasks.wait(clock), stream.ready());
+            match stream.read(4096) {
+                Ok(bytes) => {
+                    contents.extend_from_slice(&bytes);
+                    tasks.run();
+                    clock.advance(compute);
+                    tasks.run();
+                }
+                Err(StreamError::Closed) => return Ok(contents),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn chunks(n: u8) -> impl Iterator<Item = StreamResult<Bytes>> {
+        (0..n).map(|i| Ok(Bytes::from(vec![i; 100])))
+    }
+
+    #[test]
+    fn background_prefetch_overlaps_compute() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let latency = Duration::from_millis(100);
+        let clock = clock();
+        let tasks = Tasks::default();
+        let inner = Latent::new(chunks(4), latency, &clock);
+        let mut stream =
+            ReadAheadInputStream::with_spawner(Box::new(inner), 4096, Arc::new(tasks.clone()));
+
+        let contents = guest_read_all(&SIGNAL, &mut stream, &clock, &tasks, latency).unwrap();
+
+        let expected: Vec<u8> = (0..4).flat_map(|i| [i; 100]).collect();
+        assert_eq!(contents, expected);
+        // Reading and processing each chunk in turn would take 800ms, whereas
+        // only the first read isn't overlapped with processing a chunk.
+        assert_eq!(clock.now(), latency + 4 * latency);
+    }
+
+    #[test]
+    fn lazy_prefetch_reads_in_ready() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let clock = clock();
+        let inner = Latent::new(chunks(3), Duration::ZERO, &clock);
+        let reads = inner.reads.clone();
+        let mut stream = ReadAheadInputStream::new(Box::new(inner), 4096);
+
+        // Nothing is read until the guest waits for readiness, at which point
+        // a whole chunk is buffered.
+        assert_eq!(stream.read(10).unwrap().len(), 0);
+        assert_eq!(reads.load(Ordering::SeqCst), 0);
+        block_on(&SIGNAL, |_| {}, stream.ready());
+        assert_eq!(reads.load(Ordering::SeqCst), 1);
+        assert_eq!(stream.buffered(), 100);
+
+        assert_eq!(stream.read(10).unwrap(), [0; 10].as_slice());
+        assert_eq!(stream.skip(50).unwrap(), 50);
+        assert_eq!(stream.buffered(), 40);
+        block_on(&SIGNAL, |_| panic!("data is buffered"), stream.ready());
+        assert_eq!(stream.read(100).unwrap().len(), 40);
+        assert_eq!(reads.load(Ordering::SeqCst), 1);
+
+        let tasks = Tasks::default();
+        let rest = guest_read_all(&SIGNAL, &mut stream, &clock, &tasks, Duration::ZERO).unwrap();
+        assert_eq!(rest.len(), 200);
+    }
+
+    #[test]
+    fn prefetch_errors_are_delivered() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let clock = clock();
+        let tasks = Tasks::default();
+        let results = chunks(1).chain([Err(StreamError::LastOperationFailed(anyhow::anyhow!(
+            "disk on fire"
+        )))]);
+        let inner = Latent::new(results, Duration::from_millis(1), &clock);
+        let mut stream =
+            ReadAheadInputStream::with_spawner(Box::new(inner), 4096, Arc::new(tasks.clone()));
+
+        block_on(&SIGNAL, tasks.wait(&clock), stream.ready());
+        assert_eq!(stream.read(4096).unwrap().len(), 100);
+
+        // The failed read happens in the background and is reported once the
+        // guest next reads, followed by the end of the stream.
+        block_on(&SIGNAL, tasks.wait(&clock), stream.ready());
+        match stream.read(4096) {
+            Err(StreamError::LastOperationFailed(e)) => {
+                assert_eq!(e.to_string(), "disk on fire");
+            }
+            result => panic!("unexpected result {result:?}"),
+        }
+        block_on(&SIGNAL, tasks.wait(&clock), stream.ready());
+        assert!(matches!(stream.read(4096), Err(StreamError::Closed)));
+    }
+
+    #[test]
+    fn reads_ahead_in_preferred_chunks() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let sizes = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let inner = Chunked {
+            preferred: 1 << 20,
+            sizes: sizes.clone(),
+        };
+        let mut stream = ReadAheadInputStream::new(Box::new(inner), 4096);
+        assert_eq!(stream.preferred_read_size(), Some(1 << 20));
+
+        // The guest reads in small chunks, but the wrapped stream is read in
+        // the chunks it prefers.
+        block_on(&SIGNAL, |_| {}, stream.ready());
+        assert_eq!(stream.read(4096).unwrap().len(), 4096);
+        assert_eq!(stream.buffered(), (1 << 20) - 4096);
+        assert_eq!(*sizes.lock().unwrap(), [1 << 20]);
+
+        // A buffer size which is already larger is kept, and streams without
+        // a preference are read as before.
+        let inner = Chunked {
+            preferred: 100,
+            sizes: Arc::default(),
+        };
+        let stream = ReadAheadInputStream::new(Box::new(inner), 4096);
+        assert_eq!(stream.preferred_read_size(), Some(4096));
+        let inner = Latent::new(chunks(1), Duration::ZERO, &clock());
+        let stream = ReadAheadInputStream::new(Box::new(inner), 4096);
+        assert_eq!(stream.preferred_read_size(), Some(4096));
+    }
+}
@@ -0,0 +1,471 @@
+use crate::TimerProvider;
+use crate::error::{ErrorCode, IoError};
+use crate::poll::{DynFuture, Pollable, ReadinessKind};
+use crate::streams::{DynInputStream, InputStream, StreamError, StreamResult};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bytes::Bytes;
+use core::fmt;
+use core::task::{Context, Waker};
+use core::time::Duration;
+
+/// When and how often a [`RetryingInputStream`] retries failed operations.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    timers: Arc<dyn TimerProvider>,
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    retryable: Vec<ErrorCode>,
+}
+
+impl RetryPolicy {
+    /// Creates a policy which waits between attempts with timers from
+    /// `timers`.
+    ///
+    /// By default an operation is attempted up to 3 times, waiting 100ms
+    /// after the first failure and twice as long after each further one, up
+    /// to 5s. No errors are retried until some are made retryable with
+    /// [`RetryPolicy::retry_on`].
+    pub fn new(timers: Arc<dyn TimerProvider>) -> RetryPolicy {
+        RetryPolicy {
+            timers,
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            retryable: Vec::new(),
+        }
+    }
+
+    /// Sets how many times in a row an operation is attempted before its
+    /// error is reported, including the first attempt.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `attempts` is zero.
+    pub fn max_attempts(mut self, attempts: u32) -> RetryPolicy {
+        assert!(attempts > 0, "at least one attempt is required");
+        self.max_attempts = attempts;
+        self
+    }
+
+    /// Sets the wait after the first failure, which doubles after each
+    /// further failure in a row up to `max`.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> RetryPolicy {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Makes failures with an [`IoError`] whose code is `code` retryable.
+    pub fn retry_on(mut self, code: ErrorCode) -> RetryPolicy {
+        self.retryable.push(code);
+        self
+    }
+
+    /// Returns whether `err` may be retried.
+    fn is_retryable(&self, err: &anyhow::Error) -> bool {
+        IoError::code_of(err).is_some_and(|code| self.retryable.contains(&code))
+    }
+
+    /// Returns the wait after the `failures`th failure in a row.
+    fn delay(&self, failures: u32) -> Duration {
+        let factor = 1u32.checked_shl(failures - 1).unwrap_or(u32::MAX);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("retryable", &self.retryable)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An input stream which transparently retries transient failures of the
+/// stream it wraps.
+///
+/// The wrapped stream is created by a factory which is passed the offset,
+/// in bytes, at which the stream must start, so that it can be recreated
+/// after a failure, for example by re-requesting an object with a range.
+/// The offset is the number of bytes read or skipped through this stream so
+/// far, starting at 0 for the first stream, which is only created once the
+/// stream is first used.
+///
+/// When a read or skip fails with [`StreamError::LastOperationFailed`] and
+/// an error made retryable by the [`RetryPolicy`], the wrapped stream is
+/// dropped and recreated after a backoff, during which operations return no
+/// data and the stream isn't ready. Failures of the factory are handled the
+/// same way. Once the policy's attempts run out, and for any other error,
+/// the error is returned unchanged. Attempts are counted from the last
+/// operation which made progress.
+pub struct RetryingInputStream {
+    factory: Box<dyn FnMut(u64) -> StreamResult<DynInputStream> + Send>,
+    policy: RetryPolicy,
+    inner: Option<DynInputStream>,
+    offset: u64,
+    /// The number of failed attempts since the last progress.
+    failures: u32,
+    /// The wait before the next attempt, if one is scheduled.
+    backoff: Option<DynFuture<'static>>,
+    /// A failure to recreate the stream noticed while waiting for it to be
+    /// ready, reported by the next operation.
+    error: Option<StreamError>,
+}
+
+impl RetryingInputStream {
+    /// Creates a stream reading from the streams created by `factory`,
+    /// retrying their failures according to `policy`.
+    pub fn new(
+        factory: impl FnMut(u64) -> StreamResult<DynInputStream> + Send + 'static,
+        policy: RetryPolicy,
+    ) -> RetryingInputStream {
+        RetryingInputStream {
+            factory: Box::new(factory),
+            policy,
+            inner: None,
+            offset: 0,
+            failures: 0,
+            backoff: None,
+            error: None,
+        }
+    }
+
+    /// Returns the number of bytes read or skipped through this stream.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns whether a retry is scheduled and its backoff hasn't elapsed.
+    pub fn is_backing_off(&self) -> bool {
+        self.backoff.is_some()
+    }
+
+    /// Returns the wrapped stream, creating it if needed, or `None` if a
+    /// retry is pending.
+    fn connect(&mut self) -> StreamResult<Option<&mut DynInputStream>> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        if let Some(backoff) = &mut self.backoff {
+            let mut cx = Context::from_waker(Waker::noop());
+            if backoff.as_mut().poll(&mut cx).is_pending() {
+                return Ok(None);
+            }
+            self.backoff = None;
+        }
+        if self.inner.is_none() {
+            match (self.factory)(self.offset) {
+                Ok(inner) => self.inner = Some(inner),
+                Err(e) => {
+                    self.failed(e)?;
+                    return Ok(None);
+                }
+            }
+        }
+        Ok(self.inner.as_mut())
+    }
+
+    /// Handles the failure of an attempt, scheduling a retry if `err` may be
+    /// retried and returning it otherwise.
+    fn failed(&mut self, err: StreamError) -> StreamResult<()> {
+        let StreamError::LastOperationFailed(e) = &err else {
+            return Err(err);
+        };
+        self.failures += 1;
+        if self.failures >= self.policy.max_attempts || !self.policy.is_retryable(e) {
+            self.failures = 0;
+            return Err(err);
+        }
+        self.inner = None;
+        let delay = self.policy.delay(self.failures);
+        self.backoff = Some(self.policy.timers.sleep(delay));
+        Ok(())
+    }
+
+    /// Records that `len` bytes were read or skipped.
+    fn advance(&mut self, len: usize) {
+        if len > 0 {
+            self.offset += len as u64;
+            self.failures = 0;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for RetryingInputStream {
+    async fn ready(&mut self) {
+        loop {
+            if let Some(backoff) = &mut self.backoff {
+                backoff.await;
+                self.backoff = None;
+            }
+            match self.connect() {
+                Ok(Some(inner)) => return inner.ready().await,
+                // Recreating the stream failed, and a retry was scheduled.
+                Ok(None) => {}
+                Err(e) => {
+                    self.error = Some(e);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn readiness_kind(&self) -> ReadinessKind {
+        self.inner
+            .as_ref()
+            .map_or(ReadinessKind::Level, |inner| inner.readiness_kind())
+    }
+
+    fn debug_name(&self) -> Option<String> {
+        self.inner.as_ref()?.debug_name()
+    }
+}
+
+#[async_trait::async_trait]
+impl InputStream for RetryingInputStream {
+    fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+        let Some(inner) = self.connect()? else {
+            return Ok(Bytes::new());
+        };
+        match inner.read(size) {
+            Ok(bytes) => {
+                self.advance(bytes.len());
+                Ok(bytes)
+            }
+            Err(e) => {
+                self.failed(e)?;
+                Ok(Bytes::new())
+            }
+        }
+    }
+
+    fn skip(&mut self, nelem: usize) -> StreamResult<usize> {
+        let Some(inner) = self.connect()? else {
+            return Ok(0);
+        };
+        match inner.skip(nelem) {
+            Ok(skipped) => {
+                self.advance(skipped);
+                Ok(skipped)
+            }
+            Err(e) => {
+                self.failed(e)?;
+                Ok(0)
+            }
+        }
+    }
+
+    async fn cancel(&mut self) {
+        self.backoff = None;
+        if let Some(mut inner) = self.inner.take() {
+            inner.cancel().await;
+        }
+    }
+}
+
+impl fmt::Debug for RetryingInputStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryingInputStream")
+            .field("policy", &self.policy)
+            .field("offset", &self.offset)
+            .field("failures", &self.failures)
+            .field("backing_off", &self.is_backing_off())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::executor::{WakeSignal, block_on};
+    use crate::time::MockTimerProvider;
+    use alloc::collections::VecDeque;
+    use core::pin::pin;
+    use std::sync::Mutex;
+
+    const DATA: &[u8] = b"the quick brown fox";
+    const BACKOFF: Duration = Duration::from_millis(10);
+    const TICK: Duration = Duration::from_millis(1);
+
+    /// What the factory does when it's next called.
+    enum Attempt {
+        /// Fails to create a stream.
+        Refuse(ErrorCode),
+        /// Creates a stream which fails with the code after serving the
+        /// given number of bytes, if any.
+        Serve(Option<(usize, ErrorCode)>),
+    }
+
+    /// A stream serving `DATA` from an offset, which may fail partway.
+    struct Flaky {
+        data: Bytes,
+        fail: Option<(usize, ErrorCode)>,
+    }
+
+    #[async_trait::async_trait]
+    impl Pollable for Flaky {
+        async fn ready(&mut self) {}
+    }
+
+    impl InputStream for Flaky {
+        fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+            let mut size = size.min(self.data.len());
+            if let Some((left, code)) = &mut self.fail {
+                if *left == 0 {
+                    return Err(IoError::new(*code, "flaky").into());
+                }
+                size = size.min(*left);
+                *left -= size;
+            }
+            if self.data.is_empty() {
+                return Err(StreamError::Closed);
+            }
+            Ok(self.data.split_to(size))
+        }
+    }
+
+    /// Returns a factory following `script`, along with the offsets it's
+    /// called with.
+    fn scripted(
+        script: impl IntoIterator<Item = Attempt>,
+    ) -> (
+        impl FnMut(u64) -> StreamResult<DynInputStream> + Send + 'static,
+        Arc<Mutex<Vec<u64>>>,
+    ) {
+        let mut script: VecDeque<_> = script.into_iter().collect();
+        let offsets = Arc::new(Mutex::new(Vec::new()));
+        let calls = offsets.clone();
+        let factory = move |offset: u64| {
+            calls.lock().unwrap().push(offset);
+            match script.pop_front().expect("factory called too often") {
+                Attempt::Refuse(code) => Err(IoError::new(code, "refused").into()),
+                Attempt::Serve(fail) => {
+                    let data = Bytes::from_static(DATA).slice(offset as usize..);
+                    Ok(Box::new(Flaky { data, fail }) as DynInputStream)
+                }
+            }
+        };
+        (factory, offsets)
+    }
+
+    fn policy(clock: &MockTimerProvider) -> RetryPolicy {
+        RetryPolicy::new(Arc::new(clock.clone()))
+            .max_attempts(3)
+            .backoff(BACKOFF, Duration::from_secs(1))
+            .retry_on(ErrorCode::TIMED_OUT)
+            .retry_on(ErrorCode::CONNECTION_RESET)
+    }
+
+    /// Reads `stream` to its end or first error, advancing `clock` whenever
+    /// the stream isn't ready.
+    fn read_all(
+        stream: &mut RetryingInputStream,
+        clock: &MockTimerProvider,
+    ) -> (Vec<u8>, StreamError) {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        block_on(&SIGNAL, |_| clock.advance(TICK), async {
+            let mut read = Vec::new();
+            loop {
+                stream.ready().await;
+                match stream.read(4) {
+                    Ok(bytes) => read.extend_from_slice(&bytes),
+                    Err(e) => return (read, e),
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn succeeds_after_retries_at_the_right_offset() {
+        let clock = MockTimerProvider::new();
+        let (factory, offsets) = scripted([
+            Attempt::Serve(Some((6, ErrorCode::TIMED_OUT))),
+            Attempt::Refuse(ErrorCode::CONNECTION_RESET),
+            Attempt::Serve(Some((5, ErrorCode::TIMED_OUT))),
+            Attempt::Serve(None),
+        ]);
+        let mut stream = RetryingInputStream::new(factory, policy(&clock));
+
+        let (read, err) = read_all(&mut stream, &clock);
+        assert_eq!(read, DATA);
+        assert!(matches!(err, StreamError::Closed));
+        assert_eq!(stream.offset(), DATA.len() as u64);
+        assert_eq!(*offsets.lock().unwrap(), [0, 6, 6, 11]);
+
+        // The backoff doubles while failures follow each other, and starts
+        // over once a stream made progress.
+        assert_eq!(clock.now(), BACKOFF + 2 * BACKOFF + BACKOFF);
+    }
+
+    #[test]
+    fn exhausted_attempts_report_the_error() {
+        let clock = MockTimerProvider::new();
+        let (factory, offsets) = scripted([
+            Attempt::Serve(Some((2, ErrorCode::TIMED_OUT))),
+            Attempt::Serve(Some((0, ErrorCode::TIMED_OUT))),
+            Attempt::Refuse(ErrorCode::TIMED_OUT),
+        ]);
+        let mut stream = RetryingInputStream::new(factory, policy(&clock));
+
+        let (read, err) = read_all(&mut stream, &clock);
+        assert_eq!(read, &DATA[..2]);
+        let StreamError::LastOperationFailed(e) = err else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!(IoError::code_of(&e), Some(ErrorCode::TIMED_OUT));
+        assert_eq!(*offsets.lock().unwrap(), [0, 2, 2]);
+        assert_eq!(clock.now(), BACKOFF + 2 * BACKOFF);
+    }
+
+    #[test]
+    fn other_errors_pass_through() {
+        let clock = MockTimerProvider::new();
+        let (factory, offsets) =
+            scripted([Attempt::Serve(Some((3, ErrorCode::PERMISSION_DENIED)))]);
+        let mut stream = RetryingInputStream::new(factory, policy(&clock));
+
+        let (read, err) = read_all(&mut stream, &clock);
+        assert_eq!(read, &DATA[..3]);
+        let StreamError::LastOperationFailed(e) = err else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!(IoError::code_of(&e), Some(ErrorCode::PERMISSION_DENIED));
+        assert_eq!(clock.now(), Duration::ZERO);
+
+        // The failed stream is kept, so reading it again doesn't recreate
+        // it.
+        assert!(stream.read(1).is_err());
+        assert_eq!(*offsets.lock().unwrap(), [0]);
+    }
+
+    #[test]
+    fn not_ready_during_backoff() {
+        let clock = MockTimerProvider::new();
+        let (factory, _) = scripted([
+            Attempt::Serve(Some((4, ErrorCode::CONNECTION_RESET))),
+            Attempt::Serve(None),
+        ]);
+        let mut stream = RetryingInputStream::new(factory, policy(&clock));
+        assert_eq!(stream.read(10).unwrap(), &DATA[..4]);
+        assert_eq!(stream.read(10).unwrap(), &b""[..]);
+        assert!(stream.is_backing_off());
+
+        let mut cx = Context::from_waker(Waker::noop());
+        clock.advance(BACKOFF - TICK);
+        assert!(pin!(stream.ready()).poll(&mut cx).is_pending());
+        assert_eq!(stream.read(10).unwrap(), &b""[..]);
+        clock.advance(TICK);
+        assert!(pin!(stream.ready()).poll(&mut cx).is_ready());
+        assert_eq!(stream.read(10).unwrap(), &DATA[4..14]);
+    }
+}
@@ -0,0 +1,605 @@
+use crate::poll::{Pollable, ReadinessKind, WakerList};
+use crate::streams::{OutputStream, StreamError, StreamResult};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use bytes::{Bytes, BytesMut};
+use core::future::{Future, poll_fn};
+use core::mem;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Context, Poll, ready};
+use futures::FutureExt;
+use futures::future::Either;
+use futures::lock::{Mutex, OwnedMutexGuard, OwnedMutexLockFuture};
+
+/// The number of bytes the guest may write to a [`SharedOutputStream`]
+/// before they must be forwarded to the wrapped stream.
+const STAGING_CAPACITY: usize = 4096;
+
+/// An [`OutputStream`] which the guest shares with host code.
+///
+/// The `OutputStream` trait relies on the [`ResourceTable`] for exclusive
+/// access to a stream, so host code which also writes to the underlying
+/// connection, for example from a background task, would otherwise
+/// interleave its writes with the guest's. A `SharedOutputStream` puts the
+/// wrapped stream behind an async-aware lock, and host code writes to it
+/// through a [`SharedOutputHandle`] created with
+/// [`SharedOutputStream::handle`]. Each write, from either side, reaches the
+/// wrapped stream in one piece or, if it doesn't fit in the wrapped stream's
+/// permit, in pieces written while the lock is held throughout:
+///
+/// * The guest's writes are staged and forwarded as soon as the lock is free
+///   and the wrapped stream can take them. Until then they're forwarded by
+///   the stream's readiness, as awaited by `subscribe`, and `check_write`
+///   reports no permit once part of them has been written.
+/// * The handle holds the lock for each item it's sent until the whole item
+///   has been written, waiting for the wrapped stream's permits as needed.
+///
+/// Waiting for the guest's stream to become ready otherwise doesn't hold up
+/// the host: the lock is given up as soon as the handle asks for it, and
+/// acquired again once the handle is done.
+///
+/// The lock is a [`futures::lock::Mutex`], so this requires the `std`
+/// feature.
+///
+/// [`ResourceTable`]: wasmtime::component::ResourceTable
+pub struct SharedOutputStream<T> {
+    shared: Arc<Shared<T>>,
+    staged: BytesMut,
+    /// Whether part of `staged` has already been written, in which case the
+    /// rest must be written before the lock is given up.
+    partial: bool,
+    /// The lock, kept while the rest of a partially written chunk is waiting
+    /// for a permit.
+    held: Option<Guard<T>>,
+    /// Whether the guest flushed the stream before its staged bytes were
+    /// written.
+    flush_staged: bool,
+    /// Whether the wrapped stream is being flushed on the guest's behalf.
+    flushing: bool,
+    /// An error encountered while forwarding staged bytes, reported by the
+    /// guest's next operation.
+    error: Option<StreamError>,
+    /// The diagnostics of the wrapped stream, which may be locked by host
+    /// code when they're asked for.
+    readiness_kind: ReadinessKind,
+    debug_name: Option<String>,
+}
+
+impl<T: OutputStream> SharedOutputStream<T> {
+    /// Wraps `stream` so that it can be shared with host code through
+    /// [`SharedOutputStream::handle`].
+    pub fn new(stream: T) -> SharedOutputStream<T> {
+        SharedOutputStream {
+            readiness_kind: stream.readiness_kind(),
+            debug_name: stream.debug_name(),
+            shared: Arc::new(Shared::new(stream)),
+            staged: BytesMut::new(),
+            partial: false,
+            held: None,
+            flush_staged: false,
+            flushing: false,
+            error: None,
+        }
+    }
+
+    /// Returns a handle through which host code writes to the wrapped stream
+    /// without its writes being interleaved with the guest's.
+    pub fn handle(&self) -> SharedOutputHandle<T> {
+        SharedOutputHandle {
+            shared: self.shared.clone(),
+            in_flight: None,
+            flushing: false,
+        }
+    }
+
+    fn permit(&self) -> usize {
+        if self.partial || self.flush_staged || self.flushing {
+            0
+        } else {
+            STAGING_CAPACITY - self.staged.len()
+        }
+    }
+
+    /// Records `error` to be reported by the guest's next operation,
+    /// discarding anything staged.
+    fn fail(&mut self, error: StreamError) {
+        self.staged.clear();
+        self.partial = false;
+        self.held = None;
+        self.flush_staged = false;
+        self.flushing = false;
+        self.error = Some(error);
+    }
+
+    /// Forwards staged bytes, and a staged flush, if the lock is free and the
+    /// wrapped stream can take them without waiting.
+    fn forward_now(&mut self) -> StreamResult<()> {
+        if self.staged.is_empty() && !self.flush_staged && !self.flushing {
+            return Ok(());
+        }
+        let mut stream = match self.held.take() {
+            Some(stream) => stream,
+            None => match self.shared.try_lock() {
+                Some(stream) => stream,
+                None => return Ok(()),
+            },
+        };
+        if !self.staged.is_empty() {
+            // Unless part of the staged bytes has already been written
+            // they're only written if they fit in one go, so the lock isn't
+            // kept while the guest is busy elsewhere.
+            let permit = stream.check_write()?;
+            if self.partial || permit >= self.staged.len() {
+                let len = permit.min(self.staged.len());
+                stream.write(self.staged.split_to(len).freeze())?;
+                self.partial = !self.staged.is_empty();
+            }
+            if self.partial {
+                self.held = Some(stream);
+                return Ok(());
+            }
+            if !self.staged.is_empty() {
+                return Ok(());
+            }
+        }
+        if self.flush_staged {
+            self.flush_staged = false;
+            stream.flush()?;
+            self.flushing = true;
+        }
+        if self.flushing && stream.check_write()? > 0 {
+            self.flushing = false;
+        }
+        Ok(())
+    }
+
+    /// Writes the staged bytes, and performs a staged flush, waiting for the
+    /// lock and the wrapped stream's permits as needed.
+    async fn drain(&mut self) -> StreamResult<()> {
+        if self.staged.is_empty() && !self.flush_staged {
+            return Ok(());
+        }
+        let mut stream = match self.held.take() {
+            Some(stream) => stream,
+            None => self.shared.lock().await,
+        };
+        while !self.staged.is_empty() {
+            let permit = stream.check_write()?;
+            if permit == 0 {
+                // If this future is dropped while waiting, the lock is only
+                // kept when the staged bytes have been partially written.
+                if self.partial {
+                    self.held.insert(stream).ready().await;
+                    stream = self.held.take().unwrap();
+                    continue;
+                }
+                // Nothing has been written yet, so the lock is given up
+                // while waiting if another task asks for it.
+                let shared = self.shared.clone();
+                let contended = poll_fn(move |cx| shared.poll_contended(cx));
+                let contended = matches!(
+                    futures::future::select(stream.ready(), contended).await,
+                    Either::Right(_)
+                );
+                if contended {
+                    drop(stream);
+                    poll_fn(|cx| self.shared.poll_uncontended(cx)).await;
+                    stream = self.shared.lock().await;
+                }
+                continue;
+            }
+            let len = permit.min(self.staged.len());
+            stream.write(self.staged.split_to(len).freeze())?;
+            self.partial = !self.staged.is_empty();
+        }
+        if self.flush_staged {
+            self.flush_staged = false;
+            stream.flush()?;
+            self.flushing = true;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: OutputStream> Pollable for SharedOutputStream<T> {
+    async fn ready(&mut self) {
+        if let Err(e) = self.drain().await {
+            self.fail(e);
+            return;
+        }
+        while self.flushing {
+            poll_fn(|cx| self.shared.poll_uncontended(cx)).await;
+            let mut stream = self.shared.lock().await;
+            match stream.check_write() {
+                Ok(0) => {}
+                Ok(_) => {
+                    self.flushing = false;
+                    return;
+                }
+                Err(e) => {
+                    self.fail(e);
+                    return;
+                }
+            }
+            let shared = self.shared.clone();
+            let contended = poll_fn(move |cx| shared.poll_contended(cx));
+            if let Either::Left(_) = futures::future::select(stream.ready(), contended).await {
+                return;
+            }
+        }
+    }
+
+    fn readiness_kind(&self) -> ReadinessKind {
+        self.readiness_kind
+    }
+
+    fn debug_name(&self) -> Option<String> {
+        self.debug_name.clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: OutputStream> OutputStream for SharedOutputStream<T> {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        if bytes.len() > self.permit() {
+            return Err(StreamError::trap("write exceeded permit"));
+        }
+        self.staged.extend_from_slice(&bytes);
+        if let Err(e) = self.forward_now() {
+            self.fail(e);
+        }
+        Ok(())
+    }
+
+    fn write_zeroes(&mut self, nelem: usize) -> StreamResult<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        if nelem > self.permit() {
+            return Err(StreamError::trap("write-zeroes exceeded permit"));
+        }
+        // Zeroes are passed on as a run if nothing is staged ahead of them
+        // and the wrapped stream permits all of them without waiting, and
+        // are otherwise staged like any other write.
+        if self.staged.is_empty() {
+            if let Some(mut stream) = self.shared.try_lock() {
+                let result = match stream.check_write() {
+                    Ok(permit) if permit >= nelem => stream.write_zeroes(nelem).map(|()| true),
+                    Ok(_) => Ok(false),
+                    Err(e) => Err(e),
+                };
+                drop(stream);
+                match result {
+                    Ok(true) => return Ok(()),
+                    Ok(false) => {}
+                    Err(e) => {
+                        self.fail(e);
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        self.staged.resize(self.staged.len() + nelem, 0);
+        if let Err(e) = self.forward_now() {
+            self.fail(e);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        self.flush_staged = true;
+        if let Err(e) = self.forward_now() {
+            self.fail(e);
+        }
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        if let Err(e) = self.forward_now() {
+            self.fail(e);
+        }
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        Ok(self.permit())
+    }
+
+    async fn cancel(&mut self) {
+        // The wrapped stream belongs to the host as well, so it's left open,
+        // but bytes accepted by `write` still reach it. As with any other
+        // write, errors are only reported to a guest which is no longer
+        // listening.
+        let _ = self.drain().await;
+    }
+}
+
+/// Host code's handle to a [`SharedOutputStream`], created with
+/// [`SharedOutputStream::handle`].
+///
+/// Each item sent through this [`Sink`](futures::Sink) is written to the
+/// wrapped stream while holding its lock, so that the guest's writes aren't
+/// interleaved with it, and flushing the sink flushes the wrapped stream.
+pub struct SharedOutputHandle<T> {
+    shared: Arc<Shared<T>>,
+    in_flight: Option<Pin<Box<dyn Future<Output = StreamResult<()>> + Send>>>,
+    flushing: bool,
+}
+
+impl<T> SharedOutputHandle<T> {
+    fn poll_in_flight(&mut self, cx: &mut Context<'_>) -> Poll<StreamResult<()>> {
+        let Some(in_flight) = &mut self.in_flight else {
+            return Poll::Ready(Ok(()));
+        };
+        let result = ready!(in_flight.as_mut().poll(cx));
+        self.in_flight = None;
+        Poll::Ready(result)
+    }
+}
+
+impl<T: OutputStream> futures::Sink<Bytes> for SharedOutputHandle<T> {
+    type Error = StreamError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<StreamResult<()>> {
+        self.get_mut().poll_in_flight(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, mut item: Bytes) -> StreamResult<()> {
+        let this = self.get_mut();
+        debug_assert!(this.in_flight.is_none(), "`start_send` before `poll_ready`");
+        let shared = this.shared.clone();
+        this.in_flight = Some(Box::pin(async move {
+            let mut stream = shared.lock().await;
+            while !item.is_empty() {
+                let permit = stream.write_ready().await?;
+                stream.write(item.split_to(permit.min(item.len())))?;
+            }
+            Ok(())
+        }));
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<StreamResult<()>> {
+        let this = self.get_mut();
+        if !this.flushing {
+            ready!(this.poll_in_flight(cx))?;
+            this.flushing = true;
+            let shared = this.shared.clone();
+            this.in_flight = Some(Box::pin(async move {
+                let mut stream = shared.lock().await;
+                stream.flush()?;
+                stream.write_ready().await?;
+                Ok(())
+            }));
+        }
+        let result = ready!(this.poll_in_flight(cx));
+        this.flushing = false;
+        Poll::Ready(result)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<StreamResult<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+/// The lock shared by a [`SharedOutputStream`] and its handles, and by the
+/// members of a [`FlushGroup`](super::FlushGroup), which also keeps track
+/// of whether other tasks are waiting for it.
+pub(super) struct Shared<T> {
+    stream: Arc<Mutex<T>>,
+    /// The number of [`Lock`] futures waiting for the lock.
+    contenders: AtomicUsize,
+    /// Tasks to wake when the last contender acquires the lock or gives up.
+    uncontended: WakerList,
+    /// Tasks to wake when a contender starts waiting for the lock.
+    contended: WakerList,
+}
+
+/// Exclusive access to the stream behind a [`Shared`] lock.
+pub(super) type Guard<T> = OwnedMutexGuard<T>;
+
+impl<T> Shared<T> {
+    pub(super) fn new(stream: T) -> Shared<T> {
+        Shared {
+            stream: Arc::new(Mutex::new(stream)),
+            contenders: AtomicUsize::new(0),
+            uncontended: WakerList::default(),
+            contended: WakerList::default(),
+        }
+    }
+
+    pub(super) fn try_lock(&self) -> Option<Guard<T>> {
+        self.stream.try_lock_owned()
+    }
+
+    pub(super) fn lock(self: &Arc<Self>) -> Lock<T> {
+        Lock {
+            shared: self.clone(),
+            lock: self.stream.clone().lock_owned(),
+            waiting: false,
+        }
+    }
+
+    /// Resolves once another task is waiting for the lock.
+    pub(super) fn poll_contended(&self, cx: &mut Context<'_>) -> Poll<()> {
+        self.poll_contenders(&self.contended, cx, |n| n > 0)
+    }
+
+    /// Resolves once no task is waiting for the lock.
+    fn poll_uncontended(&self, cx: &mut Context<'_>) -> Poll<()> {
+        self.poll_contenders(&self.uncontended, cx, |n| n == 0)
+    }
+
+    fn poll_contenders(
+        &self,
+        wakers: &WakerList,
+        cx: &mut Context<'_>,
+        done: impl Fn(usize) -> bool,
+    ) -> Poll<()> {
+        if done(self.contenders.load(Ordering::Acquire)) {
+            return Poll::Ready(());
+        }
+        wakers.register(cx.waker());
+        // The number of contenders may have changed before the waker was
+        // registered.
+        if done(self.contenders.load(Ordering::Acquire)) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// A future which resolves once it has acquired a [`Shared`] lock.
+pub(super) struct Lock<T> {
+    shared: Arc<Shared<T>>,
+    lock: OwnedMutexLockFuture<T>,
+    waiting: bool,
+}
+
+impl<T> Lock<T> {
+    /// Stops counting this future as a contender for the lock.
+    fn stop_waiting(&mut self) {
+        if mem::take(&mut self.waiting)
+            && self.shared.contenders.fetch_sub(1, Ordering::AcqRel) == 1
+        {
+            // Tasks which were letting the contenders go first can proceed.
+            self.shared.uncontended.wake_all();
+        }
+    }
+}
+
+impl<T> Future for Lock<T> {
+    type Output = Guard<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Guard<T>> {
+        if let Poll::Ready(guard) = self.lock.poll_unpin(cx) {
+            self.stop_waiting();
+            return Poll::Ready(guard);
+        }
+        if !self.waiting {
+            self.waiting = true;
+            self.shared.contenders.fetch_add(1, Ordering::AcqRel);
+            self.shared.contended.wake_all();
+        }
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Lock<T> {
+    fn drop(&mut self) {
+        self.stop_waiting();
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::bindings::wasi::io::streams::HostOutputStream;
+    use crate::executor::{WakeSignal, block_on};
+    use crate::streams::DynOutputStream;
+    use crate::{IoImpl, IoLinkOptions};
+    use alloc::vec::Vec;
+    use futures::SinkExt;
+    use std::sync::Mutex;
+    use wasmtime::component::{Resource, ResourceTable};
+
+    const FRAMES: usize = 500;
+    const PERMIT: usize = 16;
+
+    /// A connection which records what's written to it, in chunks of at
+    /// most [`PERMIT`] bytes.
+    struct Connection(Arc<Mutex<Vec<u8>>>);
+
+    #[async_trait::async_trait]
+    impl Pollable for Connection {
+        async fn ready(&mut self) {}
+    }
+
+    #[async_trait::async_trait]
+    impl OutputStream for Connection {
+        fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+            assert!(bytes.len() <= PERMIT);
+            self.0.lock().unwrap().extend_from_slice(&bytes);
+            // Give the other side a chance to interleave its writes.
+            std::thread::yield_now();
+            Ok(())
+        }
+
+        fn flush(&mut self) -> StreamResult<()> {
+            Ok(())
+        }
+
+        fn check_write(&mut self) -> StreamResult<usize> {
+            Ok(PERMIT)
+        }
+    }
+
+    /// The guest's frames fit in one write to the connection.
+    fn guest_frame(i: usize) -> Vec<u8> {
+        format!("g{i:04}\n").into_bytes()
+    }
+
+    /// The host's frames take several writes to the connection.
+    fn host_frame(i: usize) -> Vec<u8> {
+        format!("h{i:04}{}\n", "x".repeat(34)).into_bytes()
+    }
+
+    #[test]
+    fn concurrent_writes_keep_framing() {
+        static GUEST: WakeSignal = WakeSignal::new();
+        static HOST: WakeSignal = WakeSignal::new();
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let stream = SharedOutputStream::new(Connection(written.clone()));
+        let mut handle = stream.handle();
+        let mut table = ResourceTable::new();
+        let stream = table.push(Box::new(stream) as DynOutputStream).unwrap();
+        let options = IoLinkOptions::new();
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let mut io = IoImpl::new(&mut table, &options);
+                block_on(&GUEST, |_| std::thread::yield_now(), async {
+                    for i in 0..FRAMES {
+                        let stream = Resource::new_borrow(stream.rep());
+                        io.blocking_write_and_flush(stream, guest_frame(i))
+                            .await
+                            .unwrap();
+                    }
+                });
+            });
+            s.spawn(|| {
+                block_on(&HOST, |_| std::thread::yield_now(), async {
+                    for i in 0..FRAMES {
+                        handle.send(host_frame(i).into()).await.unwrap();
+                    }
+                });
+            });
+        });
+
+        // Every frame arrived in one piece, and in order for each side.
+        let written = written.lock().unwrap();
+        let mut rest = &written[..];
+        let (mut guest, mut host) = (0, 0);
+        while !rest.is_empty() {
+            let frame = if rest[0] == b'g' {
+                guest += 1;
+                guest_frame(guest - 1)
+            } else {
+                host += 1;
+                host_frame(host - 1)
+            };
+            assert_eq!(&rest[..frame.len()], frame);
+            rest = &rest[frame.len()..];
+        }
+        assert_eq!((guest, host), (FRAMES, FRAMES));
+    }
+}
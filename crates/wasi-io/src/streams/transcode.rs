@@ -0,0 +1,684 @@
+use crate::error::{ErrorCode, IoError};
+use crate::poll::{Pollable, ReadinessKind};
+use crate::streams::{
+    DynInputStream, DynOutputStream, InputStream, OutputStream, StreamError, StreamResult,
+};
+use alloc::format;
+use alloc::string::String;
+use bytes::{Bytes, BytesMut};
+use core::marker::PhantomData;
+
+/// A text encoding of bytes, which encodes groups of `BYTES` bytes as
+/// `CHARS` ASCII characters.
+trait Codec: Send + Sync + 'static {
+    /// The name of the encoding, used in error messages.
+    const NAME: &'static str;
+    /// The number of bytes in a group.
+    const BYTES: usize;
+    /// The number of characters encoding a group.
+    const CHARS: usize;
+
+    /// Appends the encoding of `group`, which holds `BYTES` bytes, or fewer
+    /// at the end of the data.
+    fn encode(group: &[u8], out: &mut BytesMut);
+
+    /// Appends the bytes encoded by `group`, which holds `CHARS` characters,
+    /// returning whether the group ends the encoded data or the index of the
+    /// first invalid character.
+    fn decode(group: &[u8], out: &mut BytesMut) -> Result<bool, usize>;
+}
+
+struct Base64;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+impl Codec for Base64 {
+    const NAME: &'static str = "base64";
+    const BYTES: usize = 3;
+    const CHARS: usize = 4;
+
+    fn encode(group: &[u8], out: &mut BytesMut) {
+        let byte = |i: usize| u32::from(group.get(i).copied().unwrap_or(0));
+        let n = (byte(0) << 16) | (byte(1) << 8) | byte(2);
+        let mut chars = [b'='; 4];
+        for (i, c) in chars.iter_mut().take(group.len() + 1).enumerate() {
+            *c = BASE64_ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize];
+        }
+        out.extend_from_slice(&chars);
+    }
+
+    fn decode(group: &[u8], out: &mut BytesMut) -> Result<bool, usize> {
+        let mut n = 0;
+        let mut padding = 0;
+        for (i, &c) in group.iter().enumerate() {
+            // Padding may only replace the last one or two characters, and
+            // nothing but padding may follow it.
+            let sextet = match c {
+                b'=' if i >= 2 => {
+                    padding += 1;
+                    0
+                }
+                _ if padding > 0 => return Err(i),
+                b'A'..=b'Z' => c - b'A',
+                b'a'..=b'z' => c - b'a' + 26,
+                b'0'..=b'9' => c - b'0' + 52,
+                b'+' => 62,
+                b'/' => 63,
+                _ => return Err(i),
+            };
+            n = (n << 6) | u32::from(sextet);
+        }
+        let bytes = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        out.extend_from_slice(&bytes[..3 - padding]);
+        Ok(padding > 0)
+    }
+}
+
+struct Hex;
+
+impl Codec for Hex {
+    const NAME: &'static str = "hex";
+    const BYTES: usize = 1;
+    const CHARS: usize = 2;
+
+    fn encode(group: &[u8], out: &mut BytesMut) {
+        const DIGITS: &[u8; 16] = b"0123456789abcdef";
+        out.extend_from_slice(&[
+            DIGITS[usize::from(group[0] >> 4)],
+            DIGITS[usize::from(group[0] & 0xf)],
+        ]);
+    }
+
+    fn decode(group: &[u8], out: &mut BytesMut) -> Result<bool, usize> {
+        let nibble = |i: usize| match group[i] {
+            c @ b'0'..=b'9' => Ok(c - b'0'),
+            c @ b'a'..=b'f' => Ok(c - b'a' + 10),
+            c @ b'A'..=b'F' => Ok(c - b'A' + 10),
+            _ => Err(i),
+        };
+        out.extend_from_slice(&[(nibble(0)? << 4) | nibble(1)?]);
+        Ok(false)
+    }
+}
+
+/// The state shared by the encoding output streams.
+struct Encoder<C> {
+    inner: DynOutputStream,
+    /// Bytes written which don't yet make up a whole group.
+    partial: BytesMut,
+    codec: PhantomData<C>,
+}
+
+impl<C: Codec> Encoder<C> {
+    fn new(inner: DynOutputStream) -> Encoder<C> {
+        Encoder {
+            inner,
+            partial: BytesMut::with_capacity(C::BYTES),
+            codec: PhantomData,
+        }
+    }
+
+    /// Returns a buffer for the encoding of `len` more bytes.
+    fn output(&self, len: usize) -> BytesMut {
+        BytesMut::with_capacity((self.partial.len() + len) / C::BYTES * C::CHARS)
+    }
+
+    /// Encodes the whole groups of `bytes`, which follow the bytes already
+    /// written, into `out`.
+    fn encode(&mut self, bytes: &[u8], out: &mut BytesMut) {
+        let mut rest = bytes;
+        if !self.partial.is_empty() {
+            let len = rest.len().min(C::BYTES - self.partial.len());
+            self.partial.extend_from_slice(&rest[..len]);
+            rest = &rest[len..];
+            if self.partial.len() < C::BYTES {
+                return;
+            }
+            C::encode(&self.partial, out);
+            self.partial.clear();
+        }
+        let whole = rest.len() / C::BYTES * C::BYTES;
+        for group in rest[..whole].chunks_exact(C::BYTES) {
+            C::encode(group, out);
+        }
+        self.partial.extend_from_slice(&rest[whole..]);
+    }
+
+    fn write_encoded(&mut self, out: BytesMut) -> StreamResult<()> {
+        if out.is_empty() {
+            return Ok(());
+        }
+        self.inner.write(out.freeze())
+    }
+
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        let mut out = self.output(bytes.len());
+        self.encode(&bytes, &mut out);
+        self.write_encoded(out)
+    }
+
+    fn write_zeroes(&mut self, nelem: usize) -> StreamResult<()> {
+        // The encoding of zeroes isn't zeroes, so they're encoded like any
+        // other bytes, but a chunk at a time rather than from a buffer of
+        // `nelem` zeroes.
+        const ZEROES: [u8; 64] = [0; 64];
+        let mut out = self.output(nelem);
+        let mut rest = nelem;
+        while rest > 0 {
+            let len = rest.min(ZEROES.len());
+            self.encode(&ZEROES[..len], &mut out);
+            rest -= len;
+        }
+        self.write_encoded(out)
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        // Only whole groups are written to the wrapped stream, so the guest
+        // may write up to one byte short of a group more than the wrapped
+        // stream's permit covers, less the bytes it already left over.
+        let groups = self.inner.check_write()? / C::CHARS;
+        Ok((groups * C::BYTES + C::BYTES - 1).saturating_sub(self.partial.len()))
+    }
+
+    async fn cancel(&mut self) {
+        // The stream's end is the only point at which a partial group may
+        // be encoded, with padding. As with any other write, errors are only
+        // reported to a guest which is no longer listening.
+        if !self.partial.is_empty() {
+            let mut out = BytesMut::with_capacity(C::CHARS);
+            C::encode(&self.partial, &mut out);
+            self.partial.clear();
+            let _ = self.inner.blocking_write_and_flush(out.freeze()).await;
+        }
+        self.inner.cancel().await
+    }
+}
+
+/// The state shared by the decoding input streams.
+struct Decoder<C> {
+    inner: DynInputStream,
+    /// Characters read which don't yet make up a whole group.
+    partial: BytesMut,
+    /// Bytes decoded but not yet returned by `read`.
+    decoded: Bytes,
+    /// The offset in the wrapped stream of the next character read.
+    offset: u64,
+    /// Whether a group ending the encoded data has been decoded.
+    ended: bool,
+    /// An error to return once `decoded` has been returned.
+    error: Option<StreamError>,
+    closed: bool,
+    codec: PhantomData<C>,
+}
+
+impl<C: Codec> Decoder<C> {
+    fn new(inner: DynInputStream) -> Decoder<C> {
+        Decoder {
+            inner,
+            partial: BytesMut::with_capacity(C::CHARS),
+            decoded: Bytes::new(),
+            offset: 0,
+            ended: false,
+            error: None,
+            closed: false,
+            codec: PhantomData,
+        }
+    }
+
+    fn malformed(&self, offset: u64, what: &str) -> StreamError {
+        IoError::new(
+            ErrorCode::INVALID_DATA,
+            format!("{what} in {} input at offset {offset}", C::NAME),
+        )
+        .into()
+    }
+
+    /// Decodes `chars`, which follow the characters already read.
+    fn decode(&mut self, chars: &[u8]) {
+        let mut out =
+            BytesMut::with_capacity((self.partial.len() + chars.len()) / C::CHARS * C::BYTES);
+        let mut rest = chars;
+        while !rest.is_empty() {
+            if self.ended {
+                self.error = Some(self.malformed(self.offset, "data after padding"));
+                break;
+            }
+            // Groups split across reads are reassembled in `partial`, while
+            // whole groups are decoded in place.
+            let len = rest.len().min(C::CHARS - self.partial.len());
+            let group = if self.partial.is_empty() && len == C::CHARS {
+                &rest[..len]
+            } else {
+                self.partial.extend_from_slice(&rest[..len]);
+                if self.partial.len() < C::CHARS {
+                    self.offset += len as u64;
+                    break;
+                }
+                &self.partial[..]
+            };
+            let start = self.offset + len as u64 - C::CHARS as u64;
+            match C::decode(group, &mut out) {
+                Ok(ended) => self.ended = ended,
+                Err(i) => {
+                    let c = group[i];
+                    let offset = start + i as u64;
+                    self.error =
+                        Some(self.malformed(offset, &format!("invalid character {c:#04x}")));
+                    break;
+                }
+            }
+            self.partial.clear();
+            self.offset += len as u64;
+            rest = &rest[len..];
+        }
+        self.decoded = out.freeze();
+    }
+
+    fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+        if self.decoded.is_empty() && self.error.is_none() && !self.closed {
+            let groups = size.div_ceil(C::BYTES).max(1);
+            match self.inner.read(groups * C::CHARS - self.partial.len()) {
+                Ok(chars) => self.decode(&chars),
+                Err(StreamError::Closed) if !self.partial.is_empty() => {
+                    let offset = self.offset - self.partial.len() as u64;
+                    self.error = Some(self.malformed(offset, "truncated group"));
+                }
+                Err(e) => self.error = Some(e),
+            }
+        }
+        if self.decoded.is_empty() {
+            if let Some(e) = self.error.take() {
+                self.closed = true;
+                return Err(e);
+            }
+            if self.closed {
+                return Err(StreamError::Closed);
+            }
+        }
+        let len = size.min(self.decoded.len());
+        Ok(self.decoded.split_to(len))
+    }
+
+    async fn ready(&mut self) {
+        if self.decoded.is_empty() && self.error.is_none() && !self.closed {
+            self.inner.ready().await
+        }
+    }
+}
+
+macro_rules! encode_output_stream {
+    ($(#[$attr:meta])* $name:ident, $codec:ty) => {
+        $(#[$attr])*
+        pub struct $name(Encoder<$codec>);
+
+        impl $name {
+            /// Wraps `inner`, writing the encoding of the bytes written to
+            /// this stream to it.
+            pub fn new(inner: DynOutputStream) -> $name {
+                $name(Encoder::new(inner))
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl Pollable for $name {
+            async fn ready(&mut self) {
+                self.0.inner.ready().await
+            }
+
+            fn readiness_kind(&self) -> ReadinessKind {
+                self.0.inner.readiness_kind()
+            }
+
+            fn debug_name(&self) -> Option<String> {
+                self.0.inner.debug_name()
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl OutputStream for $name {
+            fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+                self.0.write(bytes)
+            }
+
+            fn flush(&mut self) -> StreamResult<()> {
+                self.0.inner.flush()
+            }
+
+            fn check_write(&mut self) -> StreamResult<usize> {
+                self.0.check_write()
+            }
+
+            fn write_zeroes(&mut self, nelem: usize) -> StreamResult<()> {
+                self.0.write_zeroes(nelem)
+            }
+
+            async fn cancel(&mut self) {
+                self.0.cancel().await
+            }
+
+            fn is_deterministic(&self) -> bool {
+                self.0.inner.is_deterministic()
+            }
+        }
+    };
+}
+
+macro_rules! decode_input_stream {
+    ($(#[$attr:meta])* $name:ident, $codec:ty) => {
+        $(#[$attr])*
+        pub struct $name(Decoder<$codec>);
+
+        impl $name {
+            /// Wraps `inner`, decoding the text read from it.
+            pub fn new(inner: DynInputStream) -> $name {
+                $name(Decoder::new(inner))
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl Pollable for $name {
+            async fn ready(&mut self) {
+                self.0.ready().await
+            }
+
+            fn readiness_kind(&self) -> ReadinessKind {
+                self.0.inner.readiness_kind()
+            }
+
+            fn debug_name(&self) -> Option<String> {
+                self.0.inner.debug_name()
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl InputStream for $name {
+            fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+                self.0.read(size)
+            }
+
+            async fn cancel(&mut self) {
+                self.0.inner.cancel().await
+            }
+
+            fn is_deterministic(&self) -> bool {
+                self.0.inner.is_deterministic()
+            }
+        }
+    };
+}
+
+encode_output_stream! {
+    /// An [`OutputStream`] which base64-encodes the bytes written to it,
+    /// using the standard alphabet, and writes the encoding to the stream it
+    /// wraps.
+    ///
+    /// Bytes are encoded in groups of three, and a write which ends partway
+    /// through a group leaves the rest of the group for the next write, so
+    /// writes may be split anywhere. `check_write` accounts for the 4/3
+    /// expansion, so the permits it reports are never more than the wrapped
+    /// stream can accept. A final partial group is encoded, with padding,
+    /// when the stream is dropped; flushing doesn't pad, since padding may
+    /// only appear at the end of the data.
+    Base64EncodeOutputStream,
+    Base64
+}
+
+decode_input_stream! {
+    /// An [`InputStream`] which decodes base64 text, using the standard
+    /// alphabet, read from the stream it wraps.
+    ///
+    /// Groups may be split across reads of the wrapped stream. The encoded
+    /// data must be padded to a whole number of groups, and padding ends it.
+    /// Malformed input, including characters outside the alphabet,
+    /// whitespace, data after padding, and a truncated final group, fails the
+    /// read with an [`IoError`] whose code is [`ErrorCode::INVALID_DATA`] and
+    /// whose message gives the offset of the offending character in the
+    /// wrapped stream. Bytes decoded before the offending group are returned
+    /// first.
+    Base64DecodeInputStream,
+    Base64
+}
+
+encode_output_stream! {
+    /// An [`OutputStream`] which hex-encodes the bytes written to it, using
+    /// lowercase digits, and writes the encoding to the stream it wraps.
+    ///
+    /// `check_write` accounts for each byte being encoded as two digits, so
+    /// the permits it reports are never more than the wrapped stream can
+    /// accept.
+    HexEncodeOutputStream,
+    Hex
+}
+
+decode_input_stream! {
+    /// An [`InputStream`] which decodes hex text, in either case, read from
+    /// the stream it wraps.
+    ///
+    /// Malformed input, including a final digit without a partner, fails the
+    /// read as with [`Base64DecodeInputStream`].
+    HexDecodeInputStream,
+    Hex
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::executor::{WakeSignal, block_on};
+    use crate::test_streams::{Capture, Chunks};
+    use alloc::boxed::Box;
+    use alloc::collections::VecDeque;
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+
+    /// A xorshift generator, so that failures are reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    /// Splits `data` into chunks of random lengths, including empty ones.
+    fn split(rng: &mut Rng, data: &[u8]) -> VecDeque<Bytes> {
+        let mut chunks = VecDeque::new();
+        let mut rest = data;
+        while !rest.is_empty() {
+            let len = rng.below(rest.len().min(9) + 1);
+            chunks.push_back(Bytes::copy_from_slice(&rest[..len]));
+            rest = &rest[len..];
+        }
+        chunks
+    }
+
+    /// Writes `data` through `encoder` in `chunks`, respecting its permits,
+    /// and drops it, returning what it wrote to `written`.
+    fn encode(
+        signal: &'static WakeSignal,
+        mut encoder: impl OutputStream,
+        mut chunks: VecDeque<Bytes>,
+        written: &Capture,
+    ) -> Vec<u8> {
+        while let Some(mut chunk) = chunks.pop_front() {
+            let permit = encoder.check_write().unwrap();
+            let rest = chunk.split_off(chunk.len().min(permit));
+            encoder.write(chunk).unwrap();
+            if !rest.is_empty() {
+                chunks.push_front(rest);
+            }
+        }
+        block_on(signal, |_| {}, encoder.cancel());
+        written.take()
+    }
+
+    /// Reads everything from `decoder` in reads of random sizes.
+    fn decode(rng: &mut Rng, mut decoder: impl InputStream) -> StreamResult<Vec<u8>> {
+        let mut decoded = Vec::new();
+        loop {
+            match decoder.read(rng.below(16)) {
+                Ok(bytes) => decoded.extend_from_slice(&bytes),
+                Err(StreamError::Closed) => return Ok(decoded),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn sink(permit: usize) -> (DynOutputStream, Capture) {
+        let sink = Capture::new(permit);
+        (Box::new(sink.clone()), sink)
+    }
+
+    fn chunks(chunks: VecDeque<Bytes>) -> DynInputStream {
+        Box::new(Chunks::new(chunks))
+    }
+
+    fn decode_error(decoder: impl InputStream) -> String {
+        match decode(&mut Rng(1), decoder) {
+            Err(StreamError::LastOperationFailed(e)) => {
+                assert_eq!(IoError::code_of(&e), Some(ErrorCode::INVALID_DATA));
+                e.to_string()
+            }
+            other => panic!("expected a decoding error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        for (data, text) in [
+            (&b""[..], &b""[..]),
+            (b"f", b"Zg=="),
+            (b"fo", b"Zm8="),
+            (b"foo", b"Zm9v"),
+            (b"foob", b"Zm9vYg=="),
+            (b"\xfb\xff", b"+/8="),
+        ] {
+            let (inner, written) = sink(64);
+            let writes = data.iter().map(|b| Bytes::copy_from_slice(&[*b])).collect();
+            let encoded = encode(
+                &SIGNAL,
+                Base64EncodeOutputStream::new(inner),
+                writes,
+                &written,
+            );
+            assert_eq!(encoded, text);
+
+            let text = Bytes::from_static(text);
+            let decoder = Base64DecodeInputStream::new(chunks([text].into()));
+            assert_eq!(decode(&mut Rng(1), decoder).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let (inner, written) = sink(64);
+        let writes = [Bytes::from_static(b"\x00\xab"), Bytes::from_static(b"\xff")].into();
+        let encoded = encode(&SIGNAL, HexEncodeOutputStream::new(inner), writes, &written);
+        assert_eq!(encoded, b"00abff");
+
+        let text = [Bytes::from_static(b"00A"), Bytes::from_static(b"bff")].into();
+        let decoder = HexDecodeInputStream::new(chunks(text));
+        assert_eq!(decode(&mut Rng(1), decoder).unwrap(), b"\x00\xab\xff");
+    }
+
+    #[test]
+    fn zeroes_are_encoded() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let (inner, written) = sink(256);
+        let mut encoder = Base64EncodeOutputStream::new(inner);
+        encoder.write(Bytes::from_static(b"f")).unwrap();
+        encoder.write_zeroes(100).unwrap();
+        block_on(&SIGNAL, |_| {}, encoder.cancel());
+
+        let mut data = b"f".to_vec();
+        data.resize(101, 0);
+        let (inner, expected) = sink(256);
+        let writes = [Bytes::from(data)].into();
+        let expected = encode(
+            &SIGNAL,
+            Base64EncodeOutputStream::new(inner),
+            writes,
+            &expected,
+        );
+        assert_eq!(written.written(), expected);
+    }
+
+    #[test]
+    fn permits_account_for_expansion() {
+        let (inner, written) = sink(10);
+        let mut encoder = Base64EncodeOutputStream::new(inner);
+        // Two whole groups fit into ten characters, along with two bytes
+        // which are held back.
+        assert_eq!(encoder.check_write().unwrap(), 8);
+        encoder.write(Bytes::from_static(b"abcdefgh")).unwrap();
+        assert_eq!(written.written().len(), 8);
+        assert_eq!(encoder.check_write().unwrap(), 6);
+
+        let (inner, _) = sink(9);
+        let mut encoder = HexEncodeOutputStream::new(inner);
+        assert_eq!(encoder.check_write().unwrap(), 4);
+    }
+
+    #[test]
+    fn malformed_input_reports_offset() {
+        let text = |t: &'static [u8]| chunks(split(&mut Rng(7), t));
+        assert_eq!(
+            decode_error(Base64DecodeInputStream::new(text(b"Zm9vYm!y"))),
+            "4: invalid character 0x21 in base64 input at offset 6"
+        );
+        assert_eq!(
+            decode_error(Base64DecodeInputStream::new(text(b"Zg==Zg=="))),
+            "4: data after padding in base64 input at offset 4"
+        );
+        assert_eq!(
+            decode_error(Base64DecodeInputStream::new(text(b"Zm9vYg"))),
+            "4: truncated group in base64 input at offset 4"
+        );
+        assert_eq!(
+            decode_error(Base64DecodeInputStream::new(text(b"Z=9v"))),
+            "4: invalid character 0x3d in base64 input at offset 1"
+        );
+        assert_eq!(
+            decode_error(HexDecodeInputStream::new(text(b"0g"))),
+            "4: invalid character 0x67 in hex input at offset 1"
+        );
+
+        // Bytes decoded before the malformed group are still returned.
+        let mut decoder = Base64DecodeInputStream::new(text(b"Zm9v!"));
+        let mut decoded = Vec::new();
+        while let Ok(bytes) = decoder.read(16) {
+            decoded.extend_from_slice(&bytes);
+        }
+        assert_eq!(decoded, b"foo");
+    }
+
+    #[test]
+    fn random_chunk_splits_round_trip() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..200 {
+            let len = rng.below(64);
+            let data: Vec<u8> = (0..len).map(|_| rng.below(256) as u8).collect();
+            let permit = 4 + rng.below(16);
+
+            let (inner, written) = sink(permit);
+            let encoder = Base64EncodeOutputStream::new(inner);
+            let text = encode(&SIGNAL, encoder, split(&mut rng, &data), &written);
+            assert_eq!(text.len(), len.div_ceil(3) * 4);
+            let decoder = Base64DecodeInputStream::new(chunks(split(&mut rng, &text)));
+            assert_eq!(decode(&mut rng, decoder).unwrap(), data);
+
+            let (inner, written) = sink(permit);
+            let encoder = HexEncodeOutputStream::new(inner);
+            let text = encode(&SIGNAL, encoder, split(&mut rng, &data), &written);
+            assert_eq!(text.len(), len * 2);
+            let decoder = HexDecodeInputStream::new(chunks(split(&mut rng, &text)));
+            assert_eq!(decode(&mut rng, decoder).unwrap(), data);
+        }
+    }
+}
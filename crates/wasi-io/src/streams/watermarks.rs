@@ -0,0 +1,86 @@
+use alloc::sync::Arc;
+use anyhow::{Result, bail};
+
+/// A stream whose buffering is bounded by a pair of watermarks which can be
+/// adjusted while it's in use.
+///
+/// The high watermark bounds how many bytes the stream buffers: writes are
+/// only permitted while fewer bytes than that are buffered. Once the high
+/// watermark has been reached the stream applies backpressure until it has
+/// drained to the low watermark.
+///
+/// Lowering the high watermark below the number of bytes currently buffered
+/// never discards any of them; the new limit takes effect as they drain.
+pub trait Tunable {
+    /// Sets the low and high watermarks of this stream.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `high` is zero or `low` exceeds `high`, see
+    /// [`check_watermarks`].
+    fn set_watermarks(&mut self, low: usize, high: usize) -> Result<()>;
+
+    /// Returns the low and high watermarks of this stream.
+    fn watermarks(&self) -> (usize, usize);
+}
+
+/// Checks that `low` and `high` are valid arguments to
+/// [`Tunable::set_watermarks`].
+pub fn check_watermarks(low: usize, high: usize) -> Result<()> {
+    if high == 0 {
+        bail!("the high watermark must be non-zero");
+    }
+    if low > high {
+        bail!("the low watermark {low} exceeds the high watermark {high}");
+    }
+    Ok(())
+}
+
+/// The watermarks which a [`Tunable`] stream shares with the
+/// [`WatermarkHandle`]s it hands out.
+///
+/// Implementations wake any task waiting for the stream to become writable
+/// when the watermarks change.
+pub trait SharedWatermarks: Send + Sync {
+    /// See [`Tunable::set_watermarks`].
+    fn set_watermarks(&self, low: usize, high: usize) -> Result<()>;
+
+    /// See [`Tunable::watermarks`].
+    fn watermarks(&self) -> (usize, usize);
+}
+
+/// A handle through which the host adjusts the watermarks of a [`Tunable`]
+/// stream without access to the stream itself, for example once it's been
+/// pushed into a `ResourceTable`.
+///
+/// Streams return a handle when they're constructed. Handles are cheap to
+/// clone and all clones adjust the same stream.
+#[derive(Clone)]
+pub struct WatermarkHandle(Arc<dyn SharedWatermarks>);
+
+impl WatermarkHandle {
+    /// Creates a handle which adjusts the watermarks in `shared`.
+    pub fn new(shared: Arc<dyn SharedWatermarks>) -> WatermarkHandle {
+        WatermarkHandle(shared)
+    }
+}
+
+impl Tunable for WatermarkHandle {
+    fn set_watermarks(&mut self, low: usize, high: usize) -> Result<()> {
+        self.0.set_watermarks(low, high)
+    }
+
+    fn watermarks(&self) -> (usize, usize) {
+        self.0.watermarks()
+    }
+}
+
+impl core::fmt::Debug for WatermarkHandle {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (low, high) = self.watermarks();
+        f.debug_struct("WatermarkHandle")
+            .field("low", &low)
+            .field("high", &high)
+            .finish()
+    }
+}
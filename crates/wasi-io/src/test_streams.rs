@@ -0,0 +1,196 @@
+//! Streams shared by the tests of this crate's modules.
+//!
+//! Tests whose streams only differ from these in details which don't matter
+//! to them should use these rather than defining their own.
+
+use crate::poll::Pollable;
+use crate::streams::{InputStream, OutputStream, StreamError, StreamResult};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bytes::Bytes;
+use core::any::Any;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// A sink which discards everything written to it.
+pub(crate) struct Discard;
+
+#[async_trait::async_trait]
+impl Pollable for Discard {
+    async fn ready(&mut self) {}
+}
+
+#[async_trait::async_trait]
+impl OutputStream for Discard {
+    fn write(&mut self, _bytes: Bytes) -> StreamResult<()> {
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        Ok(1024)
+    }
+}
+
+/// A sink with a fixed permit which captures the bytes written to it.
+/// Clones share the captured bytes.
+#[derive(Clone)]
+pub(crate) struct Capture {
+    permit: usize,
+    written: Arc<Mutex<Vec<u8>>>,
+}
+
+impl Capture {
+    /// Creates a sink which permits `permit` bytes at a time.
+    pub(crate) fn new(permit: usize) -> Capture {
+        Capture {
+            permit,
+            written: Arc::default(),
+        }
+    }
+
+    /// Returns the bytes written so far.
+    pub(crate) fn written(&self) -> Vec<u8> {
+        self.written.lock().unwrap().clone()
+    }
+
+    /// Returns the bytes written so far and forgets them.
+    pub(crate) fn take(&self) -> Vec<u8> {
+        core::mem::take(&mut *self.written.lock().unwrap())
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for Capture {
+    async fn ready(&mut self) {}
+}
+
+#[async_trait::async_trait]
+impl OutputStream for Capture {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        assert!(bytes.len() <= self.permit, "write exceeded permit");
+        self.written.lock().unwrap().extend_from_slice(&bytes);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        Ok(self.permit)
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        Some(self)
+    }
+}
+
+/// An input stream which returns the given chunks one read at a time and is
+/// then closed.
+pub(crate) struct Chunks(VecDeque<Bytes>);
+
+impl Chunks {
+    pub(crate) fn new<B: Into<Bytes>>(chunks: impl IntoIterator<Item = B>) -> Chunks {
+        Chunks(chunks.into_iter().map(Into::into).collect())
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for Chunks {
+    async fn ready(&mut self) {}
+}
+
+#[async_trait::async_trait]
+impl InputStream for Chunks {
+    fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+        let Some(mut chunk) = self.0.pop_front() else {
+            return Err(StreamError::Closed);
+        };
+        let read = chunk.split_to(size.min(chunk.len()));
+        if !chunk.is_empty() {
+            self.0.push_front(chunk);
+        }
+        Ok(read)
+    }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
+}
+
+/// An endless source of zeroes.
+pub(crate) struct Zeroes;
+
+#[async_trait::async_trait]
+impl Pollable for Zeroes {
+    async fn ready(&mut self) {}
+}
+
+#[async_trait::async_trait]
+impl InputStream for Zeroes {
+    fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+        Ok(Bytes::from(vec![0; size]))
+    }
+}
+
+/// A stream which never becomes ready on its own but always accepts
+/// operations, reading as many ones as are asked for, and which counts its
+/// cancellations. Clones share the count.
+#[derive(Clone, Default)]
+pub(crate) struct Stalled(Arc<AtomicUsize>);
+
+impl Stalled {
+    /// The permit reported by `check_write`.
+    pub(crate) const PERMIT: usize = 1024;
+
+    /// Returns how often this stream, or one of its clones, was cancelled.
+    pub(crate) fn cancellations(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for Stalled {
+    async fn ready(&mut self) {
+        core::future::pending().await
+    }
+}
+
+#[async_trait::async_trait]
+impl InputStream for Stalled {
+    fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+        Ok(Bytes::from(vec![1; size]))
+    }
+
+    async fn cancel(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[async_trait::async_trait]
+impl OutputStream for Stalled {
+    fn write(&mut self, _bytes: Bytes) -> StreamResult<()> {
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        Ok(Stalled::PERMIT)
+    }
+
+    async fn cancel(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,253 @@
+//! Virtual time for deterministic tests of timer-dependent code.
+//!
+//! Deadline pollables, idle timeouts, retries, and debouncing all wait on
+//! timers from a [`TimerProvider`]. Testing them against real time is slow
+//! and flaky, so [`MockTimerProvider`] provides timers on a virtual clock
+//! which only moves when the test says so.
+//!
+//! This module is only available with the `test-util` feature.
+
+use crate::TimerProvider;
+use crate::executor::WakeSignal;
+use crate::poll::{DynFuture, SpinLock};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+
+/// A [`TimerProvider`] whose timers run on a virtual clock.
+///
+/// The clock starts at zero and moves forward with
+/// [`advance`](MockTimerProvider::advance), firing every timer whose deadline
+/// it reaches or passes. Timers with the same deadline fire in the order in
+/// which they were started, and the tasks waiting on them are woken in that
+/// order.
+///
+/// Rather than advancing the clock explicitly, tests may turn on
+/// [auto-advance](MockTimerProvider::set_auto_advance) and pass
+/// [`wait`](MockTimerProvider::wait) to
+/// [`block_on`](crate::executor::block_on), which then jumps straight to the
+/// next pending timer whenever the future it's driving is idle.
+///
+/// Providers are cheap to clone and clones share their clock.
+#[derive(Clone, Default)]
+pub struct MockTimerProvider(Arc<SpinLock<Clock>>);
+
+#[derive(Default)]
+struct Clock {
+    now: Duration,
+    auto_advance: bool,
+    /// The id of the next timer started.
+    next_id: u64,
+    /// The timers which haven't fired yet, in firing order, along with the
+    /// waker of the task waiting on each if it has been polled.
+    pending: BTreeMap<(Duration, u64), Option<Waker>>,
+}
+
+impl MockTimerProvider {
+    /// Creates a provider whose clock starts at zero, without
+    /// auto-advance.
+    pub fn new() -> MockTimerProvider {
+        MockTimerProvider::default()
+    }
+
+    /// Returns the time elapsed on this provider's clock.
+    pub fn now(&self) -> Duration {
+        self.0.with(|clock| clock.now)
+    }
+
+    /// Moves the clock forward by `by`, firing every timer whose deadline is
+    /// reached.
+    pub fn advance(&self, by: Duration) {
+        let wakers = self.0.with(|clock| {
+            clock.now += by;
+            clock.fire()
+        });
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    /// Moves the clock forward to the deadline of the next pending timer,
+    /// firing it along with any others with the same deadline.
+    ///
+    /// Returns whether there was a pending timer. The clock never moves
+    /// backwards, so timers whose deadline has passed fire without moving
+    /// it.
+    pub fn advance_to_next(&self) -> bool {
+        let wakers = self.0.with(|clock| {
+            let &(deadline, _) = clock.pending.keys().next()?;
+            clock.now = clock.now.max(deadline);
+            Some(clock.fire())
+        });
+        match wakers {
+            Some(wakers) => {
+                wakers.into_iter().for_each(Waker::wake);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the deadline of each pending timer in nanoseconds since the
+    /// clock started, in the order in which they fire.
+    ///
+    /// Timers are pending from when they're started until they fire or the
+    /// future returned by [`sleep`](TimerProvider::sleep) is dropped.
+    pub fn pending_timers(&self) -> Vec<u64> {
+        self.0.with(|clock| {
+            clock
+                .pending
+                .keys()
+                .map(|(deadline, _)| u64::try_from(deadline.as_nanos()).unwrap_or(u64::MAX))
+                .collect()
+        })
+    }
+
+    /// Sets whether [`wait`](MockTimerProvider::wait) moves the clock to the
+    /// next pending timer.
+    pub fn set_auto_advance(&self, enabled: bool) {
+        self.0.with(|clock| clock.auto_advance = enabled);
+    }
+
+    /// A `wait` function for [`block_on`](crate::executor::block_on), which
+    /// calls it whenever the future it's driving is idle.
+    ///
+    /// With auto-advance on this moves the clock to the next pending timer
+    /// unless `signal` is already set. Otherwise it does nothing, leaving it
+    /// to the caller to make progress.
+    ///
+    /// # Panics
+    ///
+    /// Panics if auto-advance is on, the future is idle, and no timer is
+    /// pending, since nothing would ever wake the future.
+    pub fn wait(&self, signal: &WakeSignal) {
+        if signal.is_woken() || !self.0.with(|clock| clock.auto_advance) {
+            return;
+        }
+        assert!(
+            self.advance_to_next(),
+            "idle with no pending timers, the future would never complete"
+        );
+    }
+}
+
+impl Clock {
+    /// Removes the timers whose deadline has been reached, returning the
+    /// wakers of those which were waited on, in firing order.
+    fn fire(&mut self) -> Vec<Waker> {
+        let later = self.pending.split_off(&(self.now, u64::MAX));
+        let fired = core::mem::replace(&mut self.pending, later);
+        fired.into_values().flatten().collect()
+    }
+}
+
+impl TimerProvider for MockTimerProvider {
+    fn sleep(&self, duration: Duration) -> DynFuture<'static> {
+        let key = self.0.with(|clock| {
+            let key = (clock.now.saturating_add(duration), clock.next_id);
+            clock.next_id += 1;
+            clock.pending.insert(key, None);
+            key
+        });
+        Box::pin(Sleep {
+            clock: self.0.clone(),
+            key,
+        })
+    }
+}
+
+/// A timer started by [`MockTimerProvider::sleep`].
+struct Sleep {
+    clock: Arc<SpinLock<Clock>>,
+    key: (Duration, u64),
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.clock.with(|clock| {
+            if clock.now >= self.key.0 {
+                clock.pending.remove(&self.key);
+                return Poll::Ready(());
+            }
+            match clock.pending.get_mut(&self.key) {
+                Some(waker) => *waker = Some(cx.waker().clone()),
+                None => unreachable!("pending timer was removed"),
+            }
+            Poll::Pending
+        })
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        self.clock.with(|clock| clock.pending.remove(&self.key));
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::executor::block_on;
+    use std::sync::Mutex;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn timers_fire_in_deadline_then_start_order() {
+        static SIGNAL: WakeSignal = WakeSignal::new();
+        let clock = MockTimerProvider::new();
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let sleeps = [(3, 'a'), (1, 'b'), (3, 'c'), (2, 'd')].map(|(ms, name)| {
+            let sleep = clock.sleep(ms * MS);
+            let fired = fired.clone();
+            async move {
+                sleep.await;
+                fired.lock().unwrap().push(name);
+            }
+        });
+        assert_eq!(
+            clock.pending_timers(),
+            [1_000_000, 2_000_000, 3_000_000, 3_000_000]
+        );
+
+        clock.set_auto_advance(true);
+        block_on(
+            &SIGNAL,
+            |signal| clock.wait(signal),
+            futures::future::join_all(sleeps),
+        );
+        assert_eq!(*fired.lock().unwrap(), ['b', 'd', 'a', 'c']);
+        assert_eq!(clock.now(), 3 * MS);
+        assert!(clock.pending_timers().is_empty());
+    }
+
+    #[test]
+    fn advance_fires_reached_deadlines() {
+        let clock = MockTimerProvider::new();
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut short = clock.sleep(2 * MS);
+        let mut long = clock.sleep(5 * MS);
+        let dropped = clock.sleep(MS);
+        assert!(short.as_mut().poll(&mut cx).is_pending());
+        assert!(long.as_mut().poll(&mut cx).is_pending());
+
+        // Dropped timers are no longer pending.
+        drop(dropped);
+        assert_eq!(clock.pending_timers(), [2_000_000, 5_000_000]);
+
+        clock.advance(2 * MS);
+        assert!(short.as_mut().poll(&mut cx).is_ready());
+        assert!(long.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(clock.pending_timers(), [5_000_000]);
+
+        assert!(clock.advance_to_next());
+        assert!(long.as_mut().poll(&mut cx).is_ready());
+        assert_eq!(clock.now(), 5 * MS);
+        assert!(!clock.advance_to_next());
+    }
+}
@@ -0,0 +1,233 @@
+//! Runs guests against the host implementation added by
+//! `add_to_linker_concurrent`, with one guest blocked reading a pipe while
+//! another writes to it, and with guests blocked on a `Notifier`.
+
+use anyhow::{Result, bail};
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use wasmtime::component::{Component, Instance, Linker, Resource, ResourceTable, TypedFunc};
+use wasmtime::{Config, Engine, Store};
+use wasmtime_wasi_io::IoView;
+use wasmtime_wasi_io::bindings::concurrent::wasi::io::streams::StreamError;
+use wasmtime_wasi_io::poll::{DynFuture, DynPollable, Notifier, Pollable};
+use wasmtime_wasi_io::streams::{
+    self, DynInputStream, DynOutputStream, InputStream, OutputStream, StreamResult,
+};
+
+/// How many bytes the pipe buffers before its writer must wait.
+const CAPACITY: usize = 8;
+
+const DATA: &[u8] = b"the quick brown fox jumps over the lazy dog";
+
+#[derive(Default)]
+struct Pipe {
+    buffer: Mutex<VecDeque<u8>>,
+    readable: Notifier,
+    writable: Notifier,
+}
+
+impl Pipe {
+    /// Waits on `notifier` until `ready` returns true.
+    async fn wait(&self, notifier: &Notifier, ready: impl Fn(&VecDeque<u8>) -> bool) {
+        loop {
+            if ready(&self.buffer.lock().unwrap()) {
+                return;
+            }
+            notifier.reset();
+            if ready(&self.buffer.lock().unwrap()) {
+                return;
+            }
+            notifier.clone().ready().await;
+        }
+    }
+}
+
+struct PipeReader(Arc<Pipe>);
+
+#[async_trait::async_trait]
+impl Pollable for PipeReader {
+    async fn ready(&mut self) {
+        self.0.wait(&self.0.readable, |b| !b.is_empty()).await
+    }
+
+    fn ready_owned(&self) -> Option<DynFuture<'static>> {
+        let pipe = self.0.clone();
+        Some(Box::pin(async move {
+            pipe.wait(&pipe.readable, |b| !b.is_empty()).await
+        }))
+    }
+}
+
+#[async_trait::async_trait]
+impl InputStream for PipeReader {
+    fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+        let mut buffer = self.0.buffer.lock().unwrap();
+        let len = size.min(buffer.len());
+        let bytes: Vec<u8> = buffer.drain(..len).collect();
+        self.0.writable.notify_waiters();
+        Ok(bytes.into())
+    }
+}
+
+struct PipeWriter(Arc<Pipe>);
+
+#[async_trait::async_trait]
+impl Pollable for PipeWriter {
+    async fn ready(&mut self) {
+        self.0.wait(&self.0.writable, |b| b.len() < CAPACITY).await
+    }
+
+    fn ready_owned(&self) -> Option<DynFuture<'static>> {
+        let pipe = self.0.clone();
+        Some(Box::pin(async move {
+            pipe.wait(&pipe.writable, |b| b.len() < CAPACITY).await
+        }))
+    }
+}
+
+#[async_trait::async_trait]
+impl OutputStream for PipeWriter {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        let mut buffer = self.0.buffer.lock().unwrap();
+        if buffer.len() + bytes.len() > CAPACITY {
+            return Err(streams::StreamError::trap("write exceeded permit"));
+        }
+        buffer.extend(bytes);
+        self.0.readable.notify_waiters();
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        Ok(CAPACITY - self.0.buffer.lock().unwrap().len())
+    }
+}
+
+struct Host {
+    table: ResourceTable,
+}
+
+impl IoView for Host {
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+}
+
+fn borrow<T: 'static>(resource: &Resource<T>) -> Resource<T> {
+    Resource::new_borrow(resource.rep())
+}
+
+type BlockingRead = TypedFunc<(Resource<DynInputStream>, u64), (Result<Vec<u8>, StreamError>,)>;
+type BlockingWrite = TypedFunc<(Resource<DynOutputStream>, Vec<u8>), (Result<(), StreamError>,)>;
+type Block = TypedFunc<(Resource<DynPollable>,), ()>;
+type Poll = TypedFunc<(Vec<Resource<DynPollable>>,), (Vec<u32>,)>;
+
+/// Creates a store, and a linker with the concurrent bindings, for the
+/// conformance guest.
+fn setup() -> Result<(Store<Host>, Linker<Host>, Component)> {
+    let mut config = Config::new();
+    config.async_support(true);
+    config.wasm_component_model_async(true);
+    let engine = Engine::new(&config)?;
+    let component = Component::new(&engine, include_str!("conformance/guest.wat"))?;
+    let mut linker = Linker::new(&engine);
+    wasmtime_wasi_io::add_to_linker_concurrent(&mut linker)?;
+    let store = Store::new(
+        &engine,
+        Host {
+            table: ResourceTable::new(),
+        },
+    );
+    Ok((store, linker, component))
+}
+
+#[tokio::test]
+async fn guests_read_and_write_concurrently() -> Result<()> {
+    let (mut store, linker, component) = setup()?;
+
+    // A guest blocked in a call holds its instance, so the reader and the
+    // writer are separate instances sharing the store's table.
+    let reader: Instance = linker.instantiate_async(&mut store, &component).await?;
+    let writer: Instance = linker.instantiate_async(&mut store, &component).await?;
+    let blocking_read: BlockingRead = reader.get_typed_func(&mut store, "blocking-read")?;
+    let blocking_write: BlockingWrite =
+        writer.get_typed_func(&mut store, "blocking-write-and-flush")?;
+
+    let pipe = Arc::new(Pipe::default());
+    let input = store
+        .data_mut()
+        .table
+        .push(Box::new(PipeReader(pipe.clone())) as DynInputStream)?;
+    let output = store
+        .data_mut()
+        .table
+        .push(Box::new(PipeWriter(pipe)) as DynOutputStream)?;
+
+    let read = reader
+        .run_concurrent(&mut store, async |accessor| -> Result<Vec<u8>> {
+            let read = async {
+                let mut read = Vec::new();
+                while read.len() < DATA.len() {
+                    let params = (borrow(&input), 5);
+                    match blocking_read.call_concurrent(accessor, params).await? {
+                        (Ok(bytes),) => read.extend(bytes),
+                        (Err(_),) => bail!("read failed"),
+                    }
+                }
+                Ok(read)
+            };
+            let write = async {
+                for chunk in DATA.chunks(CAPACITY + 3) {
+                    let params = (borrow(&output), chunk.to_vec());
+                    if let (Err(_),) = blocking_write.call_concurrent(accessor, params).await? {
+                        bail!("write failed");
+                    }
+                }
+                Ok(())
+            };
+            let (read, write) = futures::future::join(read, write).await;
+            write?;
+            read
+        })
+        .await??;
+    assert_eq!(read, DATA);
+    Ok(())
+}
+
+#[tokio::test]
+async fn notifier_wakes_guests_blocked_concurrently() -> Result<()> {
+    let (mut store, linker, component) = setup()?;
+    let blocker: Instance = linker.instantiate_async(&mut store, &component).await?;
+    let poller: Instance = linker.instantiate_async(&mut store, &component).await?;
+    let block: Block = blocker.get_typed_func(&mut store, "block")?;
+    let poll: Poll = poller.get_typed_func(&mut store, "poll")?;
+
+    let notifier = Notifier::new();
+    let pollable = notifier.pollable(&mut store.data_mut().table)?;
+
+    // The notification comes from another thread once both guests have
+    // had time to park, so it's only observed if it wakes them.
+    let notify = std::thread::spawn({
+        let notifier = notifier.clone();
+        move || {
+            std::thread::sleep(Duration::from_millis(50));
+            notifier.notify_waiters();
+        }
+    });
+    let woken = blocker.run_concurrent(&mut store, async |accessor| -> Result<Vec<u32>> {
+        let block = block.call_concurrent(accessor, (borrow(&pollable),));
+        let poll = poll.call_concurrent(accessor, (vec![borrow(&pollable)],));
+        let (block, poll) = futures::future::join(block, poll).await;
+        block?;
+        Ok(poll?.0)
+    });
+    let ready = tokio::time::timeout(Duration::from_secs(10), woken).await???;
+    notify.join().unwrap();
+    assert_eq!(ready, [0]);
+    Ok(())
+}
@@ -0,0 +1,439 @@
+;; A guest which exports each function of `wasi:io`, and of the
+;; `wasmtime:wasi-io/streams-read-vectored` and
+;; `wasmtime:wasi-io/streams-preferred-read-size` extensions, under its own name,
+;; forwarding its arguments to the import and its results back to the caller.
+;; Calls go through the canonical ABI in both directions, so the host sees
+;; exactly what a real guest's calls would produce.
+;;
+;; Borrowed handles passed in by the host are dropped before each export
+;; returns, as the canonical ABI requires.
+(component $C
+  (import "wasi:io/error@0.2.6" (instance $error
+    (export "error" (type $e (sub resource)))
+    (export "[method]error.to-debug-string"
+      (func (param "self" (borrow $e)) (result string)))
+  ))
+  (alias export $error "error" (type $error-t))
+
+  (import "wasi:io/poll@0.2.6" (instance $poll
+    (export "pollable" (type $p (sub resource)))
+    (export "[method]pollable.ready" (func (param "self" (borrow $p)) (result bool)))
+    (export "[method]pollable.block" (func (param "self" (borrow $p))))
+    (export "poll" (func (param "in" (list (borrow $p))) (result (list u32))))
+  ))
+  (alias export $poll "pollable" (type $pollable-t))
+
+  (import "wasi:io/streams@0.2.6" (instance $streams
+    (alias outer $C $error-t (type $e0))
+    (export "error" (type $e (eq $e0)))
+    (alias outer $C $pollable-t (type $p0))
+    (export "pollable" (type $p (eq $p0)))
+    (type $se0 (variant (case "last-operation-failed" (own $e)) (case "closed")))
+    (export "stream-error" (type $se (eq $se0)))
+    (export "input-stream" (type $in (sub resource)))
+    (export "output-stream" (type $out (sub resource)))
+
+    (export "[method]input-stream.read"
+      (func (param "self" (borrow $in)) (param "len" u64)
+        (result (result (list u8) (error $se)))))
+    (export "[method]input-stream.blocking-read"
+      (func (param "self" (borrow $in)) (param "len" u64)
+        (result (result (list u8) (error $se)))))
+    (export "[method]input-stream.skip"
+      (func (param "self" (borrow $in)) (param "len" u64)
+        (result (result u64 (error $se)))))
+    (export "[method]input-stream.blocking-skip"
+      (func (param "self" (borrow $in)) (param "len" u64)
+        (result (result u64 (error $se)))))
+    (export "[method]input-stream.subscribe"
+      (func (param "self" (borrow $in)) (result (own $p))))
+
+    (export "[method]output-stream.check-write"
+      (func (param "self" (borrow $out)) (result (result u64 (error $se)))))
+    (export "[method]output-stream.write"
+      (func (param "self" (borrow $out)) (param "contents" (list u8))
+        (result (result (error $se)))))
+    (export "[method]output-stream.blocking-write-and-flush"
+      (func (param "self" (borrow $out)) (param "contents" (list u8))
+        (result (result (error $se)))))
+    (export "[method]output-stream.flush"
+      (func (param "self" (borrow $out)) (result (result (error $se)))))
+    (export "[method]output-stream.blocking-flush"
+      (func (param "self" (borrow $out)) (result (result (error $se)))))
+    (export "[method]output-stream.subscribe"
+      (func (param "self" (borrow $out)) (result (own $p))))
+    (export "[method]output-stream.write-zeroes"
+      (func (param "self" (borrow $out)) (param "len" u64)
+        (result (result (error $se)))))
+    (export "[method]output-stream.blocking-write-zeroes-and-flush"
+      (func (param "self" (borrow $out)) (param "len" u64)
+        (result (result (error $se)))))
+    (export "[method]output-stream.splice"
+      (func (param "self" (borrow $out)) (param "src" (borrow $in)) (param "len" u64)
+        (result (result u64 (error $se)))))
+    (export "[method]output-stream.blocking-splice"
+      (func (param "self" (borrow $out)) (param "src" (borrow $in)) (param "len" u64)
+        (result (result u64 (error $se)))))
+  ))
+  (alias export $streams "input-stream" (type $in))
+  (alias export $streams "output-stream" (type $out))
+  (alias export $streams "stream-error" (type $se))
+
+  (import "wasmtime:wasi-io/streams-read-vectored" (instance $read-vectored
+    (alias outer $C $in (type $in0))
+    (export "input-stream" (type $in (eq $in0)))
+    (alias outer $C $se (type $se0))
+    (export "stream-error" (type $se (eq $se0)))
+    (export "read-vectored"
+      (func (param "stream" (borrow $in)) (param "lens" (list u64))
+        (result (result (list (list u8)) (error $se)))))
+    (export "blocking-read-vectored"
+      (func (param "stream" (borrow $in)) (param "lens" (list u64))
+        (result (result (list (list u8)) (error $se)))))
+  ))
+
+  (import "wasmtime:wasi-io/streams-preferred-read-size" (instance $preferred-read-size
+    (alias outer $C $in (type $in0))
+    (export "input-stream" (type $in (eq $in0)))
+    (export "preferred-read-size"
+      (func (param "stream" (borrow $in)) (result (option u64))))
+  ))
+
+  ;; Memory, and a bump allocator which never frees, for lowered lists and
+  ;; strings.
+  (core module $Libc
+    (memory (export "memory") 1)
+    (global $heap (mut i32) (i32.const 1024))
+    (func (export "realloc") (param i32 i32) (param $align i32) (param $size i32) (result i32)
+      (local $ptr i32)
+      (local $end i32)
+      (local.set $ptr
+        (i32.and
+          (i32.add (global.get $heap) (i32.sub (local.get $align) (i32.const 1)))
+          (i32.sub (i32.const 0) (local.get $align))))
+      (local.set $end (i32.add (local.get $ptr) (local.get $size)))
+      (if (i32.gt_u (local.get $end) (i32.mul (memory.size) (i32.const 65536)))
+        (then
+          (if (i32.eq
+                (memory.grow
+                  (i32.add
+                    (i32.shr_u
+                      (i32.sub (local.get $end) (i32.mul (memory.size) (i32.const 65536)))
+                      (i32.const 16))
+                    (i32.const 1)))
+                (i32.const -1))
+            (then unreachable))))
+      (global.set $heap (local.get $end))
+      (local.get $ptr))
+  )
+  (core instance $libc (instantiate $Libc))
+
+  (core func $to-debug-string
+    (canon lower (func $error "[method]error.to-debug-string")
+      (memory $libc "memory") (realloc (func $libc "realloc"))))
+  (core func $ready (canon lower (func $poll "[method]pollable.ready")))
+  (core func $block (canon lower (func $poll "[method]pollable.block")))
+  (core func $poll
+    (canon lower (func $poll "poll")
+      (memory $libc "memory") (realloc (func $libc "realloc"))))
+  (core func $read
+    (canon lower (func $streams "[method]input-stream.read")
+      (memory $libc "memory") (realloc (func $libc "realloc"))))
+  (core func $blocking-read
+    (canon lower (func $streams "[method]input-stream.blocking-read")
+      (memory $libc "memory") (realloc (func $libc "realloc"))))
+  (core func $skip
+    (canon lower (func $streams "[method]input-stream.skip") (memory $libc "memory")))
+  (core func $blocking-skip
+    (canon lower (func $streams "[method]input-stream.blocking-skip") (memory $libc "memory")))
+  (core func $input-subscribe
+    (canon lower (func $streams "[method]input-stream.subscribe")))
+  (core func $check-write
+    (canon lower (func $streams "[method]output-stream.check-write") (memory $libc "memory")))
+  (core func $write
+    (canon lower (func $streams "[method]output-stream.write") (memory $libc "memory")))
+  (core func $blocking-write-and-flush
+    (canon lower (func $streams "[method]output-stream.blocking-write-and-flush")
+      (memory $libc "memory")))
+  (core func $flush
+    (canon lower (func $streams "[method]output-stream.flush") (memory $libc "memory")))
+  (core func $blocking-flush
+    (canon lower (func $streams "[method]output-stream.blocking-flush") (memory $libc "memory")))
+  (core func $output-subscribe
+    (canon lower (func $streams "[method]output-stream.subscribe")))
+  (core func $write-zeroes
+    (canon lower (func $streams "[method]output-stream.write-zeroes") (memory $libc "memory")))
+  (core func $blocking-write-zeroes-and-flush
+    (canon lower (func $streams "[method]output-stream.blocking-write-zeroes-and-flush")
+      (memory $libc "memory")))
+  (core func $splice
+    (canon lower (func $streams "[method]output-stream.splice") (memory $libc "memory")))
+  (core func $blocking-splice
+    (canon lower (func $streams "[method]output-stream.blocking-splice")
+      (memory $libc "memory")))
+  (core func $read-vectored
+    (canon lower (func $read-vectored "read-vectored")
+      (memory $libc "memory") (realloc (func $libc "realloc"))))
+  (core func $blocking-read-vectored
+    (canon lower (func $read-vectored "blocking-read-vectored")
+      (memory $libc "memory") (realloc (func $libc "realloc"))))
+  (core func $preferred-read-size
+    (canon lower (func $preferred-read-size "preferred-read-size") (memory $libc "memory")))
+  (core func $drop-error (canon resource.drop $error-t))
+  (core func $drop-pollable (canon resource.drop $pollable-t))
+  (core func $drop-input (canon resource.drop $in))
+  (core func $drop-output (canon resource.drop $out))
+
+  (core module $M
+    (import "libc" "memory" (memory 0))
+    (import "" "to-debug-string" (func $to-debug-string (param i32 i32)))
+    (import "" "ready" (func $ready (param i32) (result i32)))
+    (import "" "block" (func $block (param i32)))
+    (import "" "poll" (func $poll (param i32 i32 i32)))
+    (import "" "read" (func $read (param i32 i64 i32)))
+    (import "" "blocking-read" (func $blocking-read (param i32 i64 i32)))
+    (import "" "skip" (func $skip (param i32 i64 i32)))
+    (import "" "blocking-skip" (func $blocking-skip (param i32 i64 i32)))
+    (import "" "input-subscribe" (func $input-subscribe (param i32) (result i32)))
+    (import "" "check-write" (func $check-write (param i32 i32)))
+    (import "" "write" (func $write (param i32 i32 i32 i32)))
+    (import "" "blocking-write-and-flush" (func $blocking-write-and-flush (param i32 i32 i32 i32)))
+    (import "" "flush" (func $flush (param i32 i32)))
+    (import "" "blocking-flush" (func $blocking-flush (param i32 i32)))
+    (import "" "output-subscribe" (func $output-subscribe (param i32) (result i32)))
+    (import "" "write-zeroes" (func $write-zeroes (param i32 i64 i32)))
+    (import "" "blocking-write-zeroes-and-flush"
+      (func $blocking-write-zeroes-and-flush (param i32 i64 i32)))
+    (import "" "splice" (func $splice (param i32 i32 i64 i32)))
+    (import "" "blocking-splice" (func $blocking-splice (param i32 i32 i64 i32)))
+    (import "" "read-vectored" (func $read-vectored (param i32 i32 i32 i32)))
+    (import "" "blocking-read-vectored" (func $blocking-read-vectored (param i32 i32 i32 i32)))
+    (import "" "preferred-read-size" (func $preferred-read-size (param i32 i32)))
+    (import "" "drop-error" (func $drop-error (param i32)))
+    (import "" "drop-pollable" (func $drop-pollable (param i32)))
+    (import "" "drop-input" (func $drop-input (param i32)))
+    (import "" "drop-output" (func $drop-output (param i32)))
+
+    ;; Where lowered results are written, and lifted results read from.
+    (global $ret i32 (i32.const 16))
+
+    (func (export "to-debug-string") (param $e i32) (result i32)
+      (call $to-debug-string (local.get $e) (global.get $ret))
+      (call $drop-error (local.get $e))
+      (global.get $ret))
+
+    (func (export "ready") (param $p i32) (result i32)
+      (local $ready i32)
+      (local.set $ready (call $ready (local.get $p)))
+      (call $drop-pollable (local.get $p))
+      (local.get $ready))
+
+    (func (export "block") (param $p i32)
+      (call $block (local.get $p))
+      (call $drop-pollable (local.get $p)))
+
+    (func (export "poll") (param $ptr i32) (param $len i32) (result i32)
+      (local $i i32)
+      (call $poll (local.get $ptr) (local.get $len) (global.get $ret))
+      (block $done
+        (loop $next
+          (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+          (call $drop-pollable
+            (i32.load (i32.add (local.get $ptr) (i32.shl (local.get $i) (i32.const 2)))))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $next)))
+      (global.get $ret))
+
+    (func (export "read") (param $s i32) (param $len i64) (result i32)
+      (call $read (local.get $s) (local.get $len) (global.get $ret))
+      (call $drop-input (local.get $s))
+      (global.get $ret))
+
+    (func (export "blocking-read") (param $s i32) (param $len i64) (result i32)
+      (call $blocking-read (local.get $s) (local.get $len) (global.get $ret))
+      (call $drop-input (local.get $s))
+      (global.get $ret))
+
+    (func (export "skip") (param $s i32) (param $len i64) (result i32)
+      (call $skip (local.get $s) (local.get $len) (global.get $ret))
+      (call $drop-input (local.get $s))
+      (global.get $ret))
+
+    (func (export "blocking-skip") (param $s i32) (param $len i64) (result i32)
+      (call $blocking-skip (local.get $s) (local.get $len) (global.get $ret))
+      (call $drop-input (local.get $s))
+      (global.get $ret))
+
+    (func (export "input-subscribe") (param $s i32) (result i32)
+      (local $p i32)
+      (local.set $p (call $input-subscribe (local.get $s)))
+      (call $drop-input (local.get $s))
+      (local.get $p))
+
+    (func (export "check-write") (param $s i32) (result i32)
+      (call $check-write (local.get $s) (global.get $ret))
+      (call $drop-output (local.get $s))
+      (global.get $ret))
+
+    (func (export "write") (param $s i32) (param $ptr i32) (param $len i32) (result i32)
+      (call $write (local.get $s) (local.get $ptr) (local.get $len) (global.get $ret))
+      (call $drop-output (local.get $s))
+      (global.get $ret))
+
+    (func (export "blocking-write-and-flush")
+      (param $s i32) (param $ptr i32) (param $len i32) (result i32)
+      (call $blocking-write-and-flush
+        (local.get $s) (local.get $ptr) (local.get $len) (global.get $ret))
+      (call $drop-output (local.get $s))
+      (global.get $ret))
+
+    (func (export "flush") (param $s i32) (result i32)
+      (call $flush (local.get $s) (global.get $ret))
+      (call $drop-output (local.get $s))
+      (global.get $ret))
+
+    (func (export "blocking-flush") (param $s i32) (result i32)
+      (call $blocking-flush (local.get $s) (global.get $ret))
+      (call $drop-output (local.get $s))
+      (global.get $ret))
+
+    (func (export "output-subscribe") (param $s i32) (result i32)
+      (local $p i32)
+      (local.set $p (call $output-subscribe (local.get $s)))
+      (call $drop-output (local.get $s))
+      (local.get $p))
+
+    (func (export "write-zeroes") (param $s i32) (param $len i64) (result i32)
+      (call $write-zeroes (local.get $s) (local.get $len) (global.get $ret))
+      (call $drop-output (local.get $s))
+      (global.get $ret))
+
+    (func (export "blocking-write-zeroes-and-flush") (param $s i32) (param $len i64) (result i32)
+      (call $blocking-write-zeroes-and-flush (local.get $s) (local.get $len) (global.get $ret))
+      (call $drop-output (local.get $s))
+      (global.get $ret))
+
+    (func (export "splice") (param $s i32) (param $src i32) (param $len i64) (result i32)
+      (call $splice (local.get $s) (local.get $src) (local.get $len) (global.get $ret))
+      (call $drop-output (local.get $s))
+      (call $drop-input (local.get $src))
+      (global.get $ret))
+
+    (func (export "blocking-splice") (param $s i32) (param $src i32) (param $len i64) (result i32)
+      (call $blocking-splice (local.get $s) (local.get $src) (local.get $len) (global.get $ret))
+      (call $drop-output (local.get $s))
+      (call $drop-input (local.get $src))
+      (global.get $ret))
+
+    (func (export "read-vectored") (param $s i32) (param $ptr i32) (param $len i32) (result i32)
+      (call $read-vectored (local.get $s) (local.get $ptr) (local.get $len) (global.get $ret))
+      (call $drop-input (local.get $s))
+      (global.get $ret))
+
+    (func (export "blocking-read-vectored")
+      (param $s i32) (param $ptr i32) (param $len i32) (result i32)
+      (call $blocking-read-vectored
+        (local.get $s) (local.get $ptr) (local.get $len) (global.get $ret))
+      (call $drop-input (local.get $s))
+      (global.get $ret))
+
+    (func (export "preferred-read-size") (param $s i32) (result i32)
+      (call $preferred-read-size (local.get $s) (global.get $ret))
+      (call $drop-input (local.get $s))
+      (global.get $ret))
+  )
+  (core instance $m (instantiate $M
+    (with "libc" (instance $libc))
+    (with "" (instance
+      (export "to-debug-string" (func $to-debug-string))
+      (export "ready" (func $ready))
+      (export "block" (func $block))
+      (export "poll" (func $poll))
+      (export "read" (func $read))
+      (export "blocking-read" (func $blocking-read))
+      (export "skip" (func $skip))
+      (export "blocking-skip" (func $blocking-skip))
+      (export "input-subscribe" (func $input-subscribe))
+      (export "check-write" (func $check-write))
+      (export "write" (func $write))
+      (export "blocking-write-and-flush" (func $blocking-write-and-flush))
+      (export "flush" (func $flush))
+      (export "blocking-flush" (func $blocking-flush))
+      (export "output-subscribe" (func $output-subscribe))
+      (export "write-zeroes" (func $write-zeroes))
+      (export "blocking-write-zeroes-and-flush" (func $blocking-write-zeroes-and-flush))
+      (export "splice" (func $splice))
+      (export "blocking-splice" (func $blocking-splice))
+      (export "read-vectored" (func $read-vectored))
+      (export "blocking-read-vectored" (func $blocking-read-vectored))
+      (export "preferred-read-size" (func $preferred-read-size))
+      (export "drop-error" (func $drop-error))
+      (export "drop-pollable" (func $drop-pollable))
+      (export "drop-input" (func $drop-input))
+      (export "drop-output" (func $drop-output))
+    ))
+  ))
+
+  (func (export "to-debug-string") (param "self" (borrow $error-t)) (result string)
+    (canon lift (core func $m "to-debug-string") (memory $libc "memory")))
+  (func (export "ready") (param "self" (borrow $pollable-t)) (result bool)
+    (canon lift (core func $m "ready")))
+  (func (export "block") (param "self" (borrow $pollable-t))
+    (canon lift (core func $m "block")))
+  (func (export "poll") (param "in" (list (borrow $pollable-t))) (result (list u32))
+    (canon lift (core func $m "poll")
+      (memory $libc "memory") (realloc (func $libc "realloc"))))
+  (func (export "read") (param "self" (borrow $in)) (param "len" u64)
+    (result (result (list u8) (error $se)))
+    (canon lift (core func $m "read") (memory $libc "memory")))
+  (func (export "blocking-read") (param "self" (borrow $in)) (param "len" u64)
+    (result (result (list u8) (error $se)))
+    (canon lift (core func $m "blocking-read") (memory $libc "memory")))
+  (func (export "skip") (param "self" (borrow $in)) (param "len" u64)
+    (result (result u64 (error $se)))
+    (canon lift (core func $m "skip") (memory $libc "memory")))
+  (func (export "blocking-skip") (param "self" (borrow $in)) (param "len" u64)
+    (result (result u64 (error $se)))
+    (canon lift (core func $m "blocking-skip") (memory $libc "memory")))
+  (func (export "input-subscribe") (param "self" (borrow $in)) (result (own $pollable-t))
+    (canon lift (core func $m "input-subscribe")))
+  (func (export "check-write") (param "self" (borrow $out))
+    (result (result u64 (error $se)))
+    (canon lift (core func $m "check-write") (memory $libc "memory")))
+  (func (export "write") (param "self" (borrow $out)) (param "contents" (list u8))
+    (result (result (error $se)))
+    (canon lift (core func $m "write")
+      (memory $libc "memory") (realloc (func $libc "realloc"))))
+  (func (export "blocking-write-and-flush") (param "self" (borrow $out))
+    (param "contents" (list u8)) (result (result (error $se)))
+    (canon lift (core func $m "blocking-write-and-flush")
+      (memory $libc "memory") (realloc (func $libc "realloc"))))
+  (func (export "flush") (param "self" (borrow $out)) (result (result (error $se)))
+    (canon lift (core func $m "flush") (memory $libc "memory")))
+  (func (export "blocking-flush") (param "self" (borrow $out)) (result (result (error $se)))
+    (canon lift (core func $m "blocking-flush") (memory $libc "memory")))
+  (func (export "output-subscribe") (param "self" (borrow $out)) (result (own $pollable-t))
+    (canon lift (core func $m "output-subscribe")))
+  (func (export "write-zeroes") (param "self" (borrow $out)) (param "len" u64)
+    (result (result (error $se)))
+    (canon lift (core func $m "write-zeroes") (memory $libc "memory")))
+  (func (export "blocking-write-zeroes-and-flush") (param "self" (borrow $out)) (param "len" u64)
+    (result (result (error $se)))
+    (canon lift (core func $m "blocking-write-zeroes-and-flush") (memory $libc "memory")))
+  (func (export "splice") (param "self" (borrow $out)) (param "src" (borrow $in))
+    (param "len" u64) (result (result u64 (error $se)))
+    (canon lift (core func $m "splice") (memory $libc "memory")))
+  (func (export "blocking-splice") (param "self" (borrow $out)) (param "src" (borrow $in))
+    (param "len" u64) (result (result u64 (error $se)))
+    (canon lift (core func $m "blocking-splice") (memory $libc "memory")))
+  (func (export "read-vectored") (param "self" (borrow $in)) (param "lens" (list u64))
+    (result (result (list (list u8)) (error $se)))
+    (canon lift (core func $m "read-vectored")
+      (memory $libc "memory") (realloc (func $libc "realloc"))))
+  (func (export "blocking-read-vectored") (param "self" (borrow $in)) (param "lens" (list u64))
+    (result (result (list (list u8)) (error $se)))
+    (canon lift (core func $m "blocking-read-vectored")
+      (memory $libc "memory") (realloc (func $libc "realloc"))))
+  (func (export "preferred-read-size") (param "self" (borrow $in)) (result (option u64))
+    (canon lift (core func $m "preferred-read-size") (memory $libc "memory")))
+)
@@ -0,0 +1,687 @@
+//! A guest which forwards each `wasi:io` function to the host, and a suite
+//! of checks which drive it against any kind of stream.
+
+use anyhow::{Result, bail, ensure};
+use bytes::Bytes;
+use std::sync::{Arc, LazyLock, Mutex};
+use wasmtime::component::{
+    Component, ComponentNamedList, Instance, Lift, Linker, Lower, Resource, ResourceTable,
+};
+use wasmtime::{Config, Engine, Store};
+use wasmtime_wasi_io::bindings::wasi::io::streams::StreamError;
+use wasmtime_wasi_io::error::{ErrorCode, IoError};
+use wasmtime_wasi_io::poll::{DynPollable, Pollable};
+use wasmtime_wasi_io::streams::{
+    self, DynInputStream, DynOutputStream, InputStream, OutputStream, StreamResult,
+};
+use wasmtime_wasi_io::{IoImpl, IoLinkOptions, IoView};
+
+/// The kind of stream under test, built around the harness's in-memory
+/// streams.
+///
+/// Stream types which wrap another stream implement this to run the suite
+/// with their own streams between the guest and the in-memory ones.
+pub trait Streams {
+    fn input(&self, inner: DynInputStream) -> DynInputStream;
+    fn output(&self, inner: DynOutputStream) -> DynOutputStream;
+}
+
+/// The in-memory streams themselves.
+pub struct Memory;
+
+impl Streams for Memory {
+    fn input(&self, inner: DynInputStream) -> DynInputStream {
+        inner
+    }
+
+    fn output(&self, inner: DynOutputStream) -> DynOutputStream {
+        inner
+    }
+}
+
+/// An input stream which produces its contents and is then closed.
+pub struct MemoryInput(pub Bytes);
+
+#[async_trait::async_trait]
+impl Pollable for MemoryInput {
+    async fn ready(&mut self) {}
+}
+
+#[async_trait::async_trait]
+impl InputStream for MemoryInput {
+    fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+        if self.0.is_empty() {
+            return Err(streams::StreamError::Closed);
+        }
+        Ok(self.0.split_to(size.min(self.0.len())))
+    }
+
+    fn is_closed(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// An input stream whose reads fail with an [`IoError`].
+pub struct FailingInput;
+
+#[async_trait::async_trait]
+impl Pollable for FailingInput {
+    async fn ready(&mut self) {}
+}
+
+#[async_trait::async_trait]
+impl InputStream for FailingInput {
+    fn read(&mut self, _size: usize) -> StreamResult<Bytes> {
+        Err(IoError::new(ErrorCode::CONNECTION_RESET, "peer reset the connection").into())
+    }
+}
+
+/// An output stream which collects what's written to it, permitting
+/// [`MemoryOutput::PERMIT`] bytes per write.
+#[derive(Clone, Default)]
+pub struct MemoryOutput(Arc<Mutex<Vec<u8>>>);
+
+impl MemoryOutput {
+    pub const PERMIT: usize = 256;
+
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for MemoryOutput {
+    async fn ready(&mut self) {}
+}
+
+#[async_trait::async_trait]
+impl OutputStream for MemoryOutput {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        if bytes.len() > MemoryOutput::PERMIT {
+            return Err(streams::StreamError::trap("write exceeded permit"));
+        }
+        self.0.lock().unwrap().extend_from_slice(&bytes);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        Ok(MemoryOutput::PERMIT)
+    }
+}
+
+struct Host {
+    table: ResourceTable,
+    options: IoLinkOptions,
+}
+
+impl IoView for Host {
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+
+    fn io(&mut self) -> IoImpl<'_> {
+        IoImpl::new(&mut self.table, &self.options)
+    }
+}
+
+static GUEST: LazyLock<(Engine, Component)> = LazyLock::new(|| {
+    let mut config = Config::new();
+    config.async_support(true);
+    let engine = Engine::new(&config).unwrap();
+    let component = Component::new(&engine, include_str!("guest.wat")).unwrap();
+    (engine, component)
+});
+
+fn borrow<T: 'static>(resource: &Resource<T>) -> Resource<T> {
+    Resource::new_borrow(resource.rep())
+}
+
+/// An instance of the guest, whose exports are named after the `wasi:io`
+/// functions they call.
+pub struct Guest {
+    store: Store<Host>,
+    instance: Instance,
+}
+
+impl Guest {
+    pub async fn new() -> Result<Guest> {
+        Guest::with_options(IoLinkOptions::new()).await
+    }
+
+    pub async fn with_options(options: IoLinkOptions) -> Result<Guest> {
+        let (engine, component) = &*GUEST;
+        let mut linker = Linker::new(engine);
+        wasmtime_wasi_io::add_to_linker_async(&mut linker)?;
+        wasmtime_wasi_io::add_read_vectored_extension_to_linker(&mut linker)?;
+        wasmtime_wasi_io::add_preferred_read_size_extension_to_linker(&mut linker)?;
+        let mut store = Store::new(
+            engine,
+            Host {
+                table: ResourceTable::new(),
+                options,
+            },
+        );
+        let instance = linker.instantiate_async(&mut store, component).await?;
+        Ok(Guest { store, instance })
+    }
+
+    pub fn engine(&self) -> &Engine {
+        self.store.engine()
+    }
+
+    pub fn table(&mut self) -> &mut ResourceTable {
+        &mut self.store.data_mut().table
+    }
+
+    pub fn push_input(&mut self, stream: DynInputStream) -> Resource<DynInputStream> {
+        self.store.data_mut().table.push(stream).unwrap()
+    }
+
+    pub fn push_output(&mut self, stream: DynOutputStream) -> Resource<DynOutputStream> {
+        self.store.data_mut().table.push(stream).unwrap()
+    }
+
+    pub fn drop_pollable(&mut self, pollable: Resource<DynPollable>) {
+        self.store.data_mut().table.delete(pollable).unwrap();
+    }
+
+    async fn call<P, R>(&mut self, name: &str, params: P) -> Result<R>
+    where
+        P: ComponentNamedList + Lower + Send + Sync,
+        R: ComponentNamedList + Lift + Send + Sync + 'static,
+    {
+        let func = self
+            .instance
+            .get_typed_func::<P, R>(&mut self.store, name)?;
+        let results = func.call_async(&mut self.store, params).await?;
+        func.post_return_async(&mut self.store).await?;
+        Ok(results)
+    }
+
+    pub async fn to_debug_string(&mut self, error: &Resource<anyhow::Error>) -> Result<String> {
+        let (s,) = self.call("to-debug-string", (borrow(error),)).await?;
+        Ok(s)
+    }
+
+    pub async fn ready(&mut self, pollable: &Resource<DynPollable>) -> Result<bool> {
+        let (ready,) = self.call("ready", (borrow(pollable),)).await?;
+        Ok(ready)
+    }
+
+    pub async fn block(&mut self, pollable: &Resource<DynPollable>) -> Result<()> {
+        self.call::<_, ()>("block", (borrow(pollable),)).await
+    }
+
+    pub async fn poll(&mut self, pollables: &[&Resource<DynPollable>]) -> Result<Vec<u32>> {
+        let list: Vec<_> = pollables.iter().map(|p| borrow(p)).collect();
+        let (ready,) = self.call("poll", (list,)).await?;
+        Ok(ready)
+    }
+
+    pub async fn read(
+        &mut self,
+        stream: &Resource<DynInputStream>,
+        len: u64,
+    ) -> Result<Result<Vec<u8>, StreamError>> {
+        let (r,) = self.call("read", (borrow(stream), len)).await?;
+        Ok(r)
+    }
+
+    pub async fn blocking_read(
+        &mut self,
+        stream: &Resource<DynInputStream>,
+        len: u64,
+    ) -> Result<Result<Vec<u8>, StreamError>> {
+        let (r,) = self.call("blocking-read", (borrow(stream), len)).await?;
+        Ok(r)
+    }
+
+    pub async fn skip(
+        &mut self,
+        stream: &Resource<DynInputStream>,
+        len: u64,
+    ) -> Result<Result<u64, StreamError>> {
+        let (r,) = self.call("skip", (borrow(stream), len)).await?;
+        Ok(r)
+    }
+
+    pub async fn blocking_skip(
+        &mut self,
+        stream: &Resource<DynInputStream>,
+        len: u64,
+    ) -> Result<Result<u64, StreamError>> {
+        let (r,) = self.call("blocking-skip", (borrow(stream), len)).await?;
+        Ok(r)
+    }
+
+    pub async fn subscribe_input(
+        &mut self,
+        stream: &Resource<DynInputStream>,
+    ) -> Result<Resource<DynPollable>> {
+        let (p,) = self.call("input-subscribe", (borrow(stream),)).await?;
+        Ok(p)
+    }
+
+    pub async fn check_write(
+        &mut self,
+        stream: &Resource<DynOutputStream>,
+    ) -> Result<Result<u64, StreamError>> {
+        let (r,) = self.call("check-write", (borrow(stream),)).await?;
+        Ok(r)
+    }
+
+    pub async fn write(
+        &mut self,
+        stream: &Resource<DynOutputStream>,
+        contents: &[u8],
+    ) -> Result<Result<(), StreamError>> {
+        let (r,) = self
+            .call("write", (borrow(stream), contents.to_vec()))
+            .await?;
+        Ok(r)
+    }
+
+    pub async fn blocking_write_and_flush(
+        &mut self,
+        stream: &Resource<DynOutputStream>,
+        contents: &[u8],
+    ) -> Result<Result<(), StreamError>> {
+        let params = (borrow(stream), contents.to_vec());
+        let (r,) = self.call("blocking-write-and-flush", params).await?;
+        Ok(r)
+    }
+
+    pub async fn flush(
+        &mut self,
+        stream: &Resource<DynOutputStream>,
+    ) -> Result<Result<(), StreamError>> {
+        let (r,) = self.call("flush", (borrow(stream),)).await?;
+        Ok(r)
+    }
+
+    pub async fn blocking_flush(
+        &mut self,
+        stream: &Resource<DynOutputStream>,
+    ) -> Result<Result<(), StreamError>> {
+        let (r,) = self.call("blocking-flush", (borrow(stream),)).await?;
+        Ok(r)
+    }
+
+    pub async fn subscribe_output(
+        &mut self,
+        stream: &Resource<DynOutputStream>,
+    ) -> Result<Resource<DynPollable>> {
+        let (p,) = self.call("output-subscribe", (borrow(stream),)).await?;
+        Ok(p)
+    }
+
+    pub async fn write_zeroes(
+        &mut self,
+        stream: &Resource<DynOutputStream>,
+        len: u64,
+    ) -> Result<Result<(), StreamError>> {
+        let (r,) = self.call("write-zeroes", (borrow(stream), len)).await?;
+        Ok(r)
+    }
+
+    pub async fn blocking_write_zeroes_and_flush(
+        &mut self,
+        stream: &Resource<DynOutputStream>,
+        len: u64,
+    ) -> Result<Result<(), StreamError>> {
+        let params = (borrow(stream), len);
+        let (r,) = self.call("blocking-write-zeroes-and-flush", params).await?;
+        Ok(r)
+    }
+
+    pub async fn splice(
+        &mut self,
+        dest: &Resource<DynOutputStream>,
+        src: &Resource<DynInputStream>,
+        len: u64,
+    ) -> Result<Result<u64, StreamError>> {
+        let (r,) = self
+            .call("splice", (borrow(dest), borrow(src), len))
+            .await?;
+        Ok(r)
+    }
+
+    pub async fn blocking_splice(
+        &mut self,
+        dest: &Resource<DynOutputStream>,
+        src: &Resource<DynInputStream>,
+        len: u64,
+    ) -> Result<Result<u64, StreamError>> {
+        let params = (borrow(dest), borrow(src), len);
+        let (r,) = self.call("blocking-splice", params).await?;
+        Ok(r)
+    }
+
+    pub async fn read_vectored(
+        &mut self,
+        stream: &Resource<DynInputStream>,
+        lens: &[u64],
+    ) -> Result<Result<Vec<Vec<u8>>, StreamError>> {
+        let params = (borrow(stream), lens.to_vec());
+        let (r,) = self.call("read-vectored", params).await?;
+        Ok(r)
+    }
+
+    pub async fn blocking_read_vectored(
+        &mut self,
+        stream: &Resource<DynInputStream>,
+        lens: &[u64],
+    ) -> Result<Result<Vec<Vec<u8>>, StreamError>> {
+        let params = (borrow(stream), lens.to_vec());
+        let (r,) = self.call("blocking-read-vectored", params).await?;
+        Ok(r)
+    }
+
+    pub async fn preferred_read_size(
+        &mut self,
+        stream: &Resource<DynInputStream>,
+    ) -> Result<Option<u64>> {
+        let (size,) = self.call("preferred-read-size", (borrow(stream),)).await?;
+        Ok(size)
+    }
+
+    /// Returns the debug string of the error behind a failed operation.
+    async fn describe(&mut self, error: StreamError) -> Result<String> {
+        match error {
+            StreamError::LastOperationFailed(e) => {
+                let description = self.to_debug_string(&e).await?;
+                self.store.data_mut().table.delete(e)?;
+                Ok(description)
+            }
+            StreamError::Closed => bail!("expected an error, got closed"),
+        }
+    }
+}
+
+const DATA: &[u8] = b"the quick brown fox jumps over the lazy dog";
+
+/// Runs every check against `streams`.
+pub async fn run_suite(streams: &dyn Streams) -> Result<()> {
+    read_until_closed(streams).await?;
+    blocking_reads_and_skips(streams).await?;
+    nonblocking_reads_and_skips(streams).await?;
+    vectored_reads(streams).await?;
+    writes(streams).await?;
+    write_zeroes(streams).await?;
+    splices(streams).await?;
+    poll(streams).await?;
+    errors(streams).await?;
+    traps(streams).await?;
+    Ok(())
+}
+
+async fn read_until_closed(streams: &dyn Streams) -> Result<()> {
+    let mut guest = Guest::new().await?;
+    let input = guest.push_input(streams.input(Box::new(MemoryInput(DATA.into()))));
+    let mut read = Vec::new();
+    loop {
+        match guest.blocking_read(&input, 5).await? {
+            Ok(bytes) => {
+                ensure!(bytes.len() <= 5, "read more than requested");
+                read.extend(bytes);
+            }
+            Err(StreamError::Closed) => break,
+            Err(e) => bail!("read failed: {}", guest.describe(e).await?),
+        }
+    }
+    ensure!(read == DATA, "read {read:?}");
+    // A closed stream stays closed.
+    ensure!(matches!(
+        guest.read(&input, 5).await?,
+        Err(StreamError::Closed)
+    ));
+    Ok(())
+}
+
+async fn blocking_reads_and_skips(streams: &dyn Streams) -> Result<()> {
+    let mut guest = Guest::new().await?;
+    let input = guest.push_input(streams.input(Box::new(MemoryInput(DATA.into()))));
+    let skipped = match guest.blocking_skip(&input, 4).await? {
+        Ok(n) => n as usize,
+        Err(e) => bail!("skip failed: {}", guest.describe(e).await?),
+    };
+    ensure!((1..=4).contains(&skipped), "skipped {skipped} bytes");
+    let mut read = Vec::new();
+    while let Ok(bytes) = guest.blocking_read(&input, u64::MAX).await? {
+        read.extend(bytes);
+    }
+    ensure!(read == DATA[skipped..], "read {read:?} after skipping");
+    Ok(())
+}
+
+/// Checks that the buffers of a vectored read of `lens` have the shape of a
+/// `readv`: full buffers, then at most one partially filled one, then empty
+/// ones.
+pub fn check_vectored(bufs: &[Vec<u8>], lens: &[u64]) -> Result<()> {
+    ensure!(bufs.len() == lens.len(), "read {} buffers", bufs.len());
+    let mut short = false;
+    for (buf, len) in bufs.iter().zip(lens) {
+        ensure!(buf.len() as u64 <= *len, "read more than requested");
+        ensure!(
+            !short || buf.is_empty(),
+            "filled a buffer after a short one"
+        );
+        short = (buf.len() as u64) < *len;
+    }
+    Ok(())
+}
+
+async fn vectored_reads(streams: &dyn Streams) -> Result<()> {
+    const LENS: &[u64] = &[4, 0, 7, 16];
+    let mut guest = Guest::new().await?;
+    let input = guest.push_input(streams.input(Box::new(MemoryInput(DATA.into()))));
+    let mut read = Vec::new();
+    loop {
+        match guest.blocking_read_vectored(&input, LENS).await? {
+            Ok(bufs) => {
+                check_vectored(&bufs, LENS)?;
+                ensure!(!bufs.concat().is_empty(), "blocking read returned nothing");
+                read.extend(bufs.concat());
+            }
+            Err(StreamError::Closed) => break,
+            Err(e) => bail!("read failed: {}", guest.describe(e).await?),
+        }
+    }
+    ensure!(read == DATA, "read {read:?}");
+    ensure!(matches!(
+        guest.read_vectored(&input, LENS).await?,
+        Err(StreamError::Closed)
+    ));
+    Ok(())
+}
+
+async fn nonblocking_reads_and_skips(streams: &dyn Streams) -> Result<()> {
+    let mut guest = Guest::new().await?;
+    let input = guest.push_input(streams.input(Box::new(MemoryInput(DATA.into()))));
+    let pollable = guest.subscribe_input(&input).await?;
+    let mut read = Vec::new();
+    let mut skipped = 0;
+    loop {
+        // Skip a byte, then read a byte, waiting whenever neither is
+        // available.
+        let progress = match guest.skip(&input, 1).await? {
+            Ok(n) => {
+                skipped += n;
+                n
+            }
+            Err(StreamError::Closed) => break,
+            Err(e) => bail!("skip failed: {}", guest.describe(e).await?),
+        };
+        let progress = match guest.read(&input, 1).await? {
+            Ok(bytes) => {
+                read.extend(&bytes);
+                progress + bytes.len() as u64
+            }
+            Err(StreamError::Closed) => break,
+            Err(e) => bail!("read failed: {}", guest.describe(e).await?),
+        };
+        if progress == 0 {
+            guest.block(&pollable).await?;
+            ensure!(guest.ready(&pollable).await?, "not ready after blocking");
+        }
+    }
+    guest.drop_pollable(pollable);
+    ensure!(read.len() as u64 + skipped == DATA.len() as u64);
+    ensure!(read.iter().all(|b| DATA.contains(b)));
+    Ok(())
+}
+
+async fn writes(streams: &dyn Streams) -> Result<()> {
+    let mut guest = Guest::new().await?;
+    let sink = MemoryOutput::default();
+    let output = guest.push_output(streams.output(Box::new(sink.clone())));
+    let pollable = guest.subscribe_output(&output).await?;
+    let mut rest = DATA;
+    while !rest.is_empty() {
+        let permit = match guest.check_write(&output).await? {
+            Ok(permit) => permit as usize,
+            Err(e) => bail!("check-write failed: {}", guest.describe(e).await?),
+        };
+        if permit == 0 {
+            guest.block(&pollable).await?;
+            continue;
+        }
+        let (chunk, tail) = rest.split_at(permit.min(rest.len()).min(7));
+        if let Err(e) = guest.write(&output, chunk).await? {
+            bail!("write failed: {}", guest.describe(e).await?);
+        }
+        rest = tail;
+    }
+    if let Err(e) = guest.flush(&output).await? {
+        bail!("flush failed: {}", guest.describe(e).await?);
+    }
+    guest.block(&pollable).await?;
+    if let Err(e) = guest.blocking_flush(&output).await? {
+        bail!("blocking-flush failed: {}", guest.describe(e).await?);
+    }
+    ensure!(sink.contents() == DATA, "wrote {:?}", sink.contents());
+
+    if let Err(e) = guest.blocking_write_and_flush(&output, b"!").await? {
+        bail!(
+            "blocking-write-and-flush failed: {}",
+            guest.describe(e).await?
+        );
+    }
+    ensure!(sink.contents().ends_with(b"dog!"));
+    guest.drop_pollable(pollable);
+    Ok(())
+}
+
+async fn write_zeroes(streams: &dyn Streams) -> Result<()> {
+    let mut guest = Guest::new().await?;
+    let sink = MemoryOutput::default();
+    let output = guest.push_output(streams.output(Box::new(sink.clone())));
+    let permit = match guest.check_write(&output).await? {
+        Ok(permit) => permit,
+        Err(e) => bail!("check-write failed: {}", guest.describe(e).await?),
+    };
+    ensure!(permit > 0, "no permit to write");
+    if let Err(e) = guest.write_zeroes(&output, permit.min(3)).await? {
+        bail!("write-zeroes failed: {}", guest.describe(e).await?);
+    }
+    if let Err(e) = guest.blocking_write_zeroes_and_flush(&output, 5).await? {
+        bail!(
+            "blocking-write-zeroes-and-flush failed: {}",
+            guest.describe(e).await?
+        );
+    }
+    let expected = vec![0; permit.min(3) as usize + 5];
+    ensure!(sink.contents() == expected, "wrote {:?}", sink.contents());
+    Ok(())
+}
+
+async fn splices(streams: &dyn Streams) -> Result<()> {
+    let mut guest = Guest::new().await?;
+    let sink = MemoryOutput::default();
+    let input = guest.push_input(streams.input(Box::new(MemoryInput(DATA.into()))));
+    let output = guest.push_output(streams.output(Box::new(sink.clone())));
+    let pollable = guest.subscribe_input(&input).await?;
+    let mut spliced = 0;
+    loop {
+        match guest.splice(&output, &input, 10).await? {
+            Ok(0) => guest.block(&pollable).await?,
+            Ok(n) => {
+                ensure!(n <= 10, "spliced more than requested");
+                spliced += n;
+            }
+            Err(StreamError::Closed) => break,
+            Err(e) => bail!("splice failed: {}", guest.describe(e).await?),
+        }
+    }
+    guest.drop_pollable(pollable);
+    loop {
+        match guest.blocking_flush(&output).await? {
+            Ok(()) => break,
+            Err(e) => bail!("blocking-flush failed: {}", guest.describe(e).await?),
+        }
+    }
+    ensure!(spliced == DATA.len() as u64);
+    ensure!(sink.contents() == DATA, "spliced {:?}", sink.contents());
+
+    let input = guest.push_input(streams.input(Box::new(MemoryInput(DATA.into()))));
+    let mut spliced = 0;
+    loop {
+        match guest.blocking_splice(&output, &input, u64::MAX).await? {
+            Ok(n) => spliced += n,
+            Err(StreamError::Closed) => break,
+            Err(e) => bail!("blocking-splice failed: {}", guest.describe(e).await?),
+        }
+    }
+    ensure!(spliced == DATA.len() as u64);
+    ensure!(sink.contents() == [DATA, DATA].concat());
+    Ok(())
+}
+
+async fn poll(streams: &dyn Streams) -> Result<()> {
+    let mut guest = Guest::new().await?;
+    let input = guest.push_input(streams.input(Box::new(MemoryInput(DATA.into()))));
+    let output = guest.push_output(streams.output(Box::new(MemoryOutput::default())));
+    let readable = guest.subscribe_input(&input).await?;
+    let writable = guest.subscribe_output(&output).await?;
+    let ready = guest.poll(&[&readable, &writable, &readable]).await?;
+    ensure!(!ready.is_empty(), "poll returned nothing");
+    ensure!(ready.iter().all(|i| *i < 3), "poll returned {ready:?}");
+    guest.drop_pollable(readable);
+    guest.drop_pollable(writable);
+    Ok(())
+}
+
+async fn errors(streams: &dyn Streams) -> Result<()> {
+    let mut guest = Guest::new().await?;
+    let input = guest.push_input(streams.input(Box::new(FailingInput)));
+    match guest.blocking_read(&input, 5).await? {
+        Err(e @ StreamError::LastOperationFailed(_)) => {
+            let description = guest.describe(e).await?;
+            ensure!(
+                description == "3: peer reset the connection",
+                "{description}"
+            );
+        }
+        other => bail!("expected the read to fail, got {other:?}"),
+    }
+    Ok(())
+}
+
+async fn traps(streams: &dyn Streams) -> Result<()> {
+    // A trap leaves the instance unusable, so each gets its own.
+    let mut guest = Guest::new().await?;
+    let output = guest.push_output(streams.output(Box::new(MemoryOutput::default())));
+    let oversized = guest.blocking_write_and_flush(&output, &[0; 4097]).await;
+    ensure!(
+        oversized.is_err(),
+        "oversized blocking-write-and-flush didn't trap"
+    );
+
+    let mut guest = Guest::new().await?;
+    ensure!(guest.poll(&[]).await.is_err(), "empty poll didn't trap");
+    Ok(())
+}
@@ -0,0 +1,366 @@
+//! Runs a guest's calls to every `wasi:io` function against the host
+//! implementation, with the in-memory streams of the harness and with the
+//! crate's stream types wrapping them.
+
+mod harness;
+
+use bytes::Bytes;
+use harness::{Guest, Memory, MemoryInput, MemoryOutput, Streams, check_vectored, run_suite};
+use std::time::Duration;
+use wasmtime_wasi_io::bindings::wasi::io::streams::StreamError;
+use wasmtime_wasi_io::coalesce::CoalescingOutputStream;
+use wasmtime_wasi_io::epoch_pollable;
+use wasmtime_wasi_io::poll::{Notifier, Pollable};
+use wasmtime_wasi_io::streams::{
+    self, DynInputStream, DynOutputStream, FileInputStream, FileOutputStream, InputStream,
+    OutputStream, ReadAheadInputStream, StreamResult, SyncPolicy,
+};
+use wasmtime_wasi_io::{IoLinkOptions, PermitPolicy};
+
+#[tokio::test]
+async fn memory_streams() -> anyhow::Result<()> {
+    run_suite(&Memory).await
+}
+
+struct ReadAhead;
+
+impl Streams for ReadAhead {
+    fn input(&self, inner: DynInputStream) -> DynInputStream {
+        Box::new(ReadAheadInputStream::new(inner, 3))
+    }
+
+    fn output(&self, inner: DynOutputStream) -> DynOutputStream {
+        inner
+    }
+}
+
+#[tokio::test]
+async fn read_ahead_streams() -> anyhow::Result<()> {
+    run_suite(&ReadAhead).await
+}
+
+struct Coalescing;
+
+impl Streams for Coalescing {
+    fn input(&self, inner: DynInputStream) -> DynInputStream {
+        inner
+    }
+
+    fn output(&self, inner: DynOutputStream) -> DynOutputStream {
+        Box::new(CoalescingOutputStream::new(inner, 16))
+    }
+}
+
+#[tokio::test]
+async fn coalescing_streams() -> anyhow::Result<()> {
+    run_suite(&Coalescing).await
+}
+
+#[tokio::test]
+async fn poll_wakes_when_epoch_advances() -> anyhow::Result<()> {
+    let mut guest = Guest::new().await?;
+    let engine = guest.engine().clone();
+    let ticks = Notifier::new();
+    let target = engine.current_epoch() + 2;
+    let pollable = epoch_pollable(guest.table(), engine.weak(), target, &ticks)?;
+    assert!(!guest.ready(&pollable).await?);
+
+    let ticker = std::thread::spawn({
+        let engine = engine.weak();
+        let ticks = ticks.clone();
+        move || {
+            for _ in 0..2 {
+                std::thread::sleep(Duration::from_millis(10));
+                engine.upgrade().unwrap().increment_epoch();
+                ticks.notify_waiters();
+            }
+        }
+    });
+    // The guest blocks in `poll` until the second tick.
+    assert_eq!(guest.poll(&[&pollable]).await?, [0]);
+    assert!(engine.current_epoch() >= target);
+    ticker.join().unwrap();
+
+    assert!(guest.ready(&pollable).await?);
+    guest.drop_pollable(pollable);
+    Ok(())
+}
+
+#[tokio::test]
+async fn read_vectored_fills_earlier_buffers_first() -> anyhow::Result<()> {
+    let mut guest = Guest::new().await?;
+    let input = guest.push_input(Box::new(MemoryInput((&b"abcdefghij"[..]).into())));
+
+    // A full read fills every buffer.
+    let bufs = guest.read_vectored(&input, &[3, 0, 2]).await?.unwrap();
+    assert_eq!(bufs, [&b"abc"[..], b"", b"de"]);
+
+    // A short read fills the buffer where the data ran out partially, and
+    // leaves the later ones empty.
+    let lens = [2, 4, 3];
+    let bufs = guest.blocking_read_vectored(&input, &lens).await?.unwrap();
+    check_vectored(&bufs, &lens)?;
+    assert_eq!(bufs, [&b"fg"[..], b"hij", b""]);
+
+    // Once the stream is closed both variants report it.
+    assert!(matches!(
+        guest.read_vectored(&input, &lens).await?,
+        Err(StreamError::Closed)
+    ));
+    assert!(matches!(
+        guest.blocking_read_vectored(&input, &lens).await?,
+        Err(StreamError::Closed)
+    ));
+    Ok(())
+}
+
+/// An input stream which prefers to be read 1 MiB at a time.
+struct ObjectStore(MemoryInput);
+
+#[async_trait::async_trait]
+impl Pollable for ObjectStore {
+    async fn ready(&mut self) {}
+}
+
+#[async_trait::async_trait]
+impl InputStream for ObjectStore {
+    fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+        self.0.read(size)
+    }
+
+    fn preferred_read_size(&self) -> Option<usize> {
+        Some(1 << 20)
+    }
+}
+
+#[tokio::test]
+async fn guest_sees_preferred_read_size() -> anyhow::Result<()> {
+    let mut options = IoLinkOptions::new();
+    options.max_len(1 << 16);
+    let mut guest = Guest::with_options(options).await?;
+
+    let plain = guest.push_input(Box::new(MemoryInput(Bytes::new())));
+    assert_eq!(guest.preferred_read_size(&plain).await?, None);
+
+    // Sizes beyond the largest read the host permits are clamped.
+    let store = guest.push_input(Box::new(ObjectStore(MemoryInput(Bytes::new()))));
+    assert_eq!(guest.preferred_read_size(&store).await?, Some(1 << 16));
+
+    // A read-ahead stream reports the size of the chunks it reads ahead.
+    let ahead = Box::new(ReadAheadInputStream::new(
+        Box::new(MemoryInput(Bytes::new())),
+        4096,
+    ));
+    let ahead = guest.push_input(ahead);
+    assert_eq!(guest.preferred_read_size(&ahead).await?, Some(4096));
+    Ok(())
+}
+
+#[tokio::test]
+async fn file_streams() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("file");
+    let mut guest = Guest::new().await?;
+
+    let output = FileOutputStream::create(&path)?.sync_on_flush(SyncPolicy::All);
+    let output = guest.push_output(Box::new(output));
+    guest
+        .blocking_write_and_flush(&output, b"hello")
+        .await?
+        .unwrap();
+    let permit = guest.check_write(&output).await?.unwrap();
+    assert!(permit >= 6);
+    guest.write(&output, b", host").await?.unwrap();
+    guest.blocking_flush(&output).await?.unwrap();
+    assert_eq!(std::fs::read(&path)?, b"hello, host");
+
+    let append = guest.push_output(Box::new(FileOutputStream::append(&path)?));
+    guest
+        .blocking_write_and_flush(&append, b"!")
+        .await?
+        .unwrap();
+
+    let input = FileInputStream::open(&path)?.chunk_size(8);
+    let input = guest.push_input(Box::new(input));
+    assert_eq!(guest.preferred_read_size(&input).await?, Some(8));
+    // Nothing is read until the guest waits for the stream.
+    assert_eq!(guest.read(&input, 100).await?.unwrap(), b"");
+    assert_eq!(
+        guest.blocking_read(&input, 100).await?.unwrap(),
+        b"hello, h"
+    );
+    assert_eq!(guest.blocking_read(&input, 100).await?.unwrap(), b"ost!");
+    assert!(matches!(
+        guest.blocking_read(&input, 100).await?,
+        Err(StreamError::Closed)
+    ));
+    Ok(())
+}
+
+/// A stream which traps if it's read from, written to or flushed.
+struct Untouchable;
+
+impl Untouchable {
+    fn touched<T>() -> StreamResult<T> {
+        Err(streams::StreamError::trap("the stream was touched"))
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for Untouchable {
+    async fn ready(&mut self) {}
+}
+
+#[async_trait::async_trait]
+impl InputStream for Untouchable {
+    fn read(&mut self, _size: usize) -> StreamResult<Bytes> {
+        Untouchable::touched()
+    }
+}
+
+#[async_trait::async_trait]
+impl OutputStream for Untouchable {
+    fn write(&mut self, _bytes: Bytes) -> StreamResult<()> {
+        Untouchable::touched()
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        Untouchable::touched()
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        Ok(0)
+    }
+}
+
+/// An output stream which has been closed.
+struct ClosedOutput;
+
+#[async_trait::async_trait]
+impl Pollable for ClosedOutput {
+    async fn ready(&mut self) {}
+}
+
+#[async_trait::async_trait]
+impl OutputStream for ClosedOutput {
+    fn write(&mut self, _bytes: Bytes) -> StreamResult<()> {
+        Err(streams::StreamError::Closed)
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        Err(streams::StreamError::Closed)
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        Err(streams::StreamError::Closed)
+    }
+}
+
+#[tokio::test]
+async fn zero_length_operations_skip_the_stream() -> anyhow::Result<()> {
+    let mut guest = Guest::new().await?;
+    let input = guest.push_input(Box::new(Untouchable));
+    let output = guest.push_output(Box::new(Untouchable));
+
+    assert_eq!(guest.read(&input, 0).await?.unwrap(), b"");
+    assert_eq!(guest.blocking_read(&input, 0).await?.unwrap(), b"");
+    assert_eq!(guest.skip(&input, 0).await?.unwrap(), 0);
+    assert_eq!(guest.blocking_skip(&input, 0).await?.unwrap(), 0);
+    let bufs = guest.read_vectored(&input, &[0, 0]).await?.unwrap();
+    assert_eq!(bufs, [&b""[..]; 2]);
+    let bufs = guest.blocking_read_vectored(&input, &[]).await?.unwrap();
+    assert!(bufs.is_empty());
+    guest.write(&output, b"").await?.unwrap();
+    guest.write_zeroes(&output, 0).await?.unwrap();
+    assert_eq!(guest.splice(&output, &input, 0).await?.unwrap(), 0);
+    assert_eq!(guest.blocking_splice(&output, &input, 0).await?.unwrap(), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn zero_length_operations_on_closed_streams() -> anyhow::Result<()> {
+    let mut guest = Guest::new().await?;
+    let input = guest.push_input(Box::new(MemoryInput(Bytes::new())));
+    let output = guest.push_output(Box::new(ClosedOutput));
+    let open_input = guest.push_input(Box::new(Untouchable));
+    let open_output = guest.push_output(Box::new(Untouchable));
+
+    // Operations of zero bytes fail with `closed` if a stream they name is
+    // closed.
+    fn closed<T>(result: Result<T, StreamError>) -> bool {
+        matches!(result, Err(StreamError::Closed))
+    }
+    assert!(closed(guest.read(&input, 0).await?));
+    assert!(closed(guest.blocking_read(&input, 0).await?));
+    assert!(closed(guest.skip(&input, 0).await?));
+    assert!(closed(guest.blocking_skip(&input, 0).await?));
+    assert!(closed(guest.read_vectored(&input, &[0]).await?));
+    assert!(closed(guest.blocking_read_vectored(&input, &[0]).await?));
+    assert!(closed(guest.write(&output, b"").await?));
+    assert!(closed(guest.write_zeroes(&output, 0).await?));
+    assert!(closed(guest.splice(&output, &open_input, 0).await?));
+    assert!(closed(
+        guest.blocking_splice(&output, &open_input, 0).await?
+    ));
+    assert!(closed(guest.splice(&open_output, &input, 0).await?));
+    assert!(closed(
+        guest.blocking_splice(&open_output, &input, 0).await?
+    ));
+    assert!(closed(guest.blocking_write_and_flush(&output, b"").await?));
+    assert!(closed(
+        guest.blocking_write_zeroes_and_flush(&output, 0).await?
+    ));
+    Ok(())
+}
+
+/// Has the guest check for a permit of 256 bytes, write 200 bytes, and then
+/// write another 100 without checking again, overrunning the remaining
+/// permit of 56 bytes.
+async fn overrun_permit(
+    policy: PermitPolicy,
+) -> anyhow::Result<(Guest, MemoryOutput, anyhow::Result<Result<(), StreamError>>)> {
+    let mut options = IoLinkOptions::new();
+    options.write_permit_policy(policy);
+    let mut guest = Guest::with_options(options).await?;
+    let sink = MemoryOutput::default();
+    let output = guest.push_output(Box::new(sink.clone()));
+    let permit = guest.check_write(&output).await?.unwrap();
+    assert_eq!(permit, MemoryOutput::PERMIT as u64);
+    guest.write(&output, &[1; 200]).await?.unwrap();
+    let overrun = guest.write(&output, &[2; 100]).await;
+    Ok((guest, sink, overrun))
+}
+
+#[tokio::test]
+async fn permit_overrun_traps() -> anyhow::Result<()> {
+    let (_guest, sink, overrun) = overrun_permit(PermitPolicy::Trap).await?;
+    let err = overrun.unwrap_err();
+    assert!(
+        format!("{err:?}").contains("exceeds the permit of 56 bytes"),
+        "{err:?}"
+    );
+    assert_eq!(sink.contents(), [1; 200]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn permit_overrun_is_clamped() -> anyhow::Result<()> {
+    let (mut guest, sink, overrun) = overrun_permit(PermitPolicy::Clamp).await?;
+    let Err(StreamError::LastOperationFailed(err)) = overrun? else {
+        panic!("the overrunning write didn't fail");
+    };
+    let description = guest.to_debug_string(&err).await?;
+    assert!(description.contains("exceeds the permit"), "{description}");
+    let expected = [vec![1; 200], vec![2; 56]].concat();
+    assert_eq!(sink.contents(), expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn permit_overrun_is_forwarded() -> anyhow::Result<()> {
+    let (_guest, sink, overrun) = overrun_permit(PermitPolicy::Forward).await?;
+    overrun?.unwrap();
+    let expected = [vec![1; 200], vec![2; 100]].concat();
+    assert_eq!(sink.contents(), expected);
+    Ok(())
+}
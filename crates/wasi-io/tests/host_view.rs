@@ -0,0 +1,278 @@
+//! Has the host read an input stream which a guest component hands to it
+//! with `host_view_input_stream`, while the guest still holds handles to it.
+
+use anyhow::Result;
+use bytes::Bytes;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use wasmtime::component::{Component, Instance, Linker, Resource, ResourceTable};
+use wasmtime::{Config, Engine, Store};
+use wasmtime_wasi_io::bindings::wasi::io::streams::StreamError;
+use wasmtime_wasi_io::poll::Pollable;
+use wasmtime_wasi_io::streams::{self, DynInputStream, InputStream, StreamResult};
+use wasmtime_wasi_io::{IoView, host_view_input_stream};
+
+/// A guest which gets an input stream from the host's `open` import and
+/// exports it from `produce`, and which can skip bytes of the stream and
+/// drop it.
+const PRODUCER: &str = r#"
+(component $C
+  (import "wasi:io/error@0.2.6" (instance $error
+    (export "error" (type $e (sub resource)))
+  ))
+  (alias export $error "error" (type $error-t))
+  (import "wasi:io/streams@0.2.6" (instance $streams
+    (alias outer $C $error-t (type $e0))
+    (export "error" (type $e (eq $e0)))
+    (type $se0 (variant (case "last-operation-failed" (own $e)) (case "closed")))
+    (export "stream-error" (type $se (eq $se0)))
+    (export "input-stream" (type $in (sub resource)))
+    (export "[method]input-stream.skip"
+      (func (param "self" (borrow $in)) (param "len" u64)
+        (result (result u64 (error $se)))))
+  ))
+  (alias export $streams "input-stream" (type $in))
+  (alias export $streams "stream-error" (type $se))
+  (import "open" (func $open (result (own $in))))
+
+  (core module $Memory (memory (export "memory") 1))
+  (core instance $memory (instantiate $Memory))
+
+  (core func $open (canon lower (func $open)))
+  (core func $skip
+    (canon lower (func $streams "[method]input-stream.skip") (memory $memory "memory")))
+  (core func $drop-input (canon resource.drop $in))
+
+  (core module $M
+    (import "" "open" (func $open (result i32)))
+    (import "" "skip" (func $skip (param i32 i64 i32)))
+    (import "" "drop-input" (func $drop-input (param i32)))
+
+    (func (export "produce") (result i32)
+      (call $open))
+
+    (func (export "skip") (param $s i32) (param $len i64) (result i32)
+      (call $skip (local.get $s) (local.get $len) (i32.const 0))
+      (call $drop-input (local.get $s))
+      (i32.const 0))
+
+    (func (export "discard") (param $s i32)
+      (call $drop-input (local.get $s)))
+  )
+  (core instance $m (instantiate $M
+    (with "" (instance
+      (export "open" (func $open))
+      (export "skip" (func $skip))
+      (export "drop-input" (func $drop-input))
+    ))
+  ))
+
+  (func (export "produce") (result (own $in))
+    (canon lift (core func $m "produce")))
+  (func (export "skip") (param "self" (borrow $in)) (param "len" u64)
+    (result (result u64 (error $se)))
+    (canon lift (core func $m "skip") (memory $memory "memory")))
+  (func (export "discard") (param "self" (own $in))
+    (canon lift (core func $m "discard")))
+)
+"#;
+
+const DATA: &[u8] = b"the quick brown fox jumps over the lazy dog";
+
+/// A stream of [`DATA`] which records its cancellation.
+struct Source {
+    remaining: Bytes,
+    cancelled: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl Pollable for Source {
+    async fn ready(&mut self) {}
+}
+
+#[async_trait::async_trait]
+impl InputStream for Source {
+    fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+        if self.remaining.is_empty() {
+            return Err(streams::StreamError::Closed);
+        }
+        let len = size.min(self.remaining.len());
+        Ok(self.remaining.split_to(len))
+    }
+
+    async fn cancel(&mut self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+}
+
+struct Host {
+    table: ResourceTable,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl IoView for Host {
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+}
+
+fn borrow<T: 'static>(resource: &Resource<T>) -> Resource<T> {
+    Resource::new_borrow(resource.rep())
+}
+
+struct Producer {
+    store: Store<Host>,
+    instance: Instance,
+}
+
+impl Producer {
+    async fn new() -> Result<Producer> {
+        let mut config = Config::new();
+        config.async_support(true);
+        let engine = Engine::new(&config)?;
+        let component = Component::new(&engine, PRODUCER)?;
+        let mut linker = Linker::<Host>::new(&engine);
+        wasmtime_wasi_io::add_to_linker_async(&mut linker)?;
+        linker.root().func_wrap("open", |mut store, ()| {
+            let host: &mut Host = store.data_mut();
+            let source = Source {
+                remaining: Bytes::from_static(DATA),
+                cancelled: host.cancelled.clone(),
+            };
+            let stream = host.table.push(Box::new(source) as DynInputStream)?;
+            Ok((stream,))
+        })?;
+        let mut store = Store::new(
+            &engine,
+            Host {
+                table: ResourceTable::new(),
+                cancelled: Arc::default(),
+            },
+        );
+        let instance = linker.instantiate_async(&mut store, &component).await?;
+        Ok(Producer { store, instance })
+    }
+
+    async fn produce(&mut self) -> Result<Resource<DynInputStream>> {
+        let func = self
+            .instance
+            .get_typed_func::<(), (Resource<DynInputStream>,)>(&mut self.store, "produce")?;
+        let (stream,) = func.call_async(&mut self.store, ()).await?;
+        func.post_return_async(&mut self.store).await?;
+        Ok(stream)
+    }
+
+    async fn skip(
+        &mut self,
+        stream: &Resource<DynInputStream>,
+        len: u64,
+    ) -> Result<Result<u64, StreamError>> {
+        let func = self
+            .instance
+            .get_typed_func::<(Resource<DynInputStream>, u64), (Result<u64, StreamError>,)>(
+                &mut self.store,
+                "skip",
+            )?;
+        let (r,) = func
+            .call_async(&mut self.store, (borrow(stream), len))
+            .await?;
+        func.post_return_async(&mut self.store).await?;
+        Ok(r)
+    }
+
+    async fn discard(&mut self, stream: Resource<DynInputStream>) -> Result<()> {
+        let func = self
+            .instance
+            .get_typed_func::<(Resource<DynInputStream>,), ()>(&mut self.store, "discard")?;
+        func.call_async(&mut self.store, (stream,)).await?;
+        func.post_return_async(&mut self.store).await?;
+        Ok(())
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.store.data_mut().table
+    }
+}
+
+#[tokio::test]
+async fn host_drains_a_stream_produced_by_the_guest() -> Result<()> {
+    let mut producer = Producer::new().await?;
+    let stream = producer.produce().await?;
+    let mut handle = host_view_input_stream(producer.table(), borrow(&stream))?;
+
+    // The host can't take the stream a second time, and the guest can't read
+    // it while the host holds it.
+    assert!(host_view_input_stream(producer.table(), borrow(&stream)).is_err());
+    assert!(matches!(
+        producer.skip(&stream, 4).await?,
+        Err(StreamError::LastOperationFailed(_))
+    ));
+
+    let mut read = Vec::new();
+    loop {
+        handle.ready().await;
+        match handle.read(5).await {
+            Ok(bytes) => read.extend_from_slice(&bytes),
+            Err(streams::StreamError::Closed) => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    assert_eq!(read, DATA);
+
+    // Once released, the guest sees the stream the host drained.
+    assert!(handle.release(producer.table()).await?);
+    assert!(matches!(
+        producer.skip(&stream, 4).await?,
+        Err(StreamError::Closed)
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn guest_reads_after_release() -> Result<()> {
+    let mut producer = Producer::new().await?;
+    let stream = producer.produce().await?;
+    let mut handle = host_view_input_stream(producer.table(), borrow(&stream))?;
+    assert_eq!(&handle.read(4).await?[..], b"the ");
+    assert!(handle.release(producer.table()).await?);
+
+    let rest = (DATA.len() - 4) as u64;
+    assert_eq!(producer.skip(&stream, 100).await?.ok(), Some(rest));
+    Ok(())
+}
+
+#[tokio::test]
+async fn guest_drop_cancels_the_stream() -> Result<()> {
+    let mut producer = Producer::new().await?;
+    let stream = producer.produce().await?;
+    let mut handle = host_view_input_stream(producer.table(), borrow(&stream))?;
+
+    // The guest takes ownership of the stream and drops it.
+    producer.discard(stream).await?;
+    let cancelled = producer.store.data().cancelled.clone();
+    assert!(!cancelled.load(Ordering::SeqCst));
+
+    assert!(matches!(
+        handle.read(5).await,
+        Err(streams::StreamError::Closed)
+    ));
+    assert!(cancelled.load(Ordering::SeqCst));
+    assert!(!handle.release(producer.table()).await?);
+    Ok(())
+}
+
+#[tokio::test]
+async fn release_after_guest_drop_cancels_the_stream() -> Result<()> {
+    let mut producer = Producer::new().await?;
+    let stream = producer.produce().await?;
+    let handle = host_view_input_stream(producer.table(), borrow(&stream))?;
+
+    // The host releases the stream without reading it after the guest
+    // dropped it, so it's cancelled on release instead.
+    producer.discard(stream).await?;
+    let cancelled = producer.store.data().cancelled.clone();
+    assert!(!cancelled.load(Ordering::SeqCst));
+    assert!(!handle.release(producer.table()).await?);
+    assert!(cancelled.load(Ordering::SeqCst));
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! Measure allocator traffic when transferring 1 GiB through an
+//! `AsyncReadStream`, with and without reusing buffers from a `BufferPool`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+use wasmtime_wasi::p2::pipe::AsyncReadStream;
+use wasmtime_wasi::p2::{InputStream, Pollable, StreamError};
+use wasmtime_wasi_io::streams::BufferPool;
+
+const TRANSFER: u64 = 1 << 30;
+const READ_SIZE: usize = 64 * 1024;
+
+/// Counts allocations made through the global allocator.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn transfer(pool: BufferPool) -> (Duration, usize) {
+    wasmtime_wasi::runtime::in_tokio(async move {
+        let reader = tokio::io::repeat(0).take(TRANSFER);
+        let start = Instant::now();
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+        let mut stream = AsyncReadStream::with_pool(reader, pool);
+        let mut total = 0;
+        loop {
+            stream.ready().await;
+            match stream.read(READ_SIZE) {
+                Ok(bytes) => total += bytes.len() as u64,
+                Err(StreamError::Closed) => break,
+                Err(e) => panic!("transfer failed: {e:?}"),
+            }
+        }
+        assert_eq!(total, TRANSFER);
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+        (start.elapsed(), allocations)
+    })
+}
+
+fn main() {
+    // A pool which keeps no free buffers allocates for every read.
+    let (unpooled, unpooled_allocations) = transfer(BufferPool::new(4096, 0));
+    println!("unpooled: {unpooled:?}, {unpooled_allocations} allocations");
+
+    let pool = BufferPool::new(4096, 4);
+    let (pooled, pooled_allocations) = transfer(Some(pool.clone()));
+    println!("pooled:   {pooled:?}, {pooled_allocations} allocations");
+    println!("pool:     {:?}", pool.stats());
+
+    assert!(pooled_allocations < unpooled_allocations);
+}
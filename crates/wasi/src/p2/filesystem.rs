@@ -3,10 +3,11 @@ use crate::p2::{InputStream, OutputStream, Pollable, StreamError, StreamResult};
 use crate::runtime::{AbortOnDropJoinHandle, spawn_blocking};
 use crate::{DirPerms, FilePerms, OpenMode, TrappableError};
 use anyhow::anyhow;
-use bytes::{Bytes, BytesMut};
+use bytes::Bytes;
 use std::io;
 use std::mem;
 use std::sync::Arc;
+use wasmtime_wasi_io::streams::BufferPool;
 
 pub type FsResult<T> = Result<T, FsError>;
 
@@ -219,6 +220,7 @@ pub struct FileInputStream {
     file: File,
     position: u64,
     state: ReadState,
+    pool: BufferPool,
 }
 enum ReadState {
     Idle,
@@ -233,13 +235,20 @@ impl FileInputStream {
             file: file.clone(),
             position,
             state: ReadState::Idle,
+            pool: crate::p2::pipe::stream_buffer_pool(),
         }
     }
 
-    fn blocking_read(file: &cap_std::fs::File, offset: u64, size: usize) -> ReadState {
+    fn blocking_read(
+        file: &cap_std::fs::File,
+        pool: &BufferPool,
+        offset: u64,
+        size: usize,
+    ) -> ReadState {
         use system_interface::fs::FileIoExt;
 
-        let mut buf = BytesMut::zeroed(size);
+        let mut buf = pool.get(size);
+        buf.resize(size, 0);
         loop {
             match file.read_at(&mut buf, offset) {
                 Ok(0) => return ReadState::Closed,
@@ -275,9 +284,10 @@ impl InputStream for FileInputStream {
                 }
 
                 let p = self.position;
+                let pool = self.pool.clone();
                 self.state = ReadState::Waiting(
                     self.file
-                        .spawn_blocking(move |f| Self::blocking_read(f, p, size)),
+                        .spawn_blocking(move |f| Self::blocking_read(f, &pool, p, size)),
                 );
                 Ok(Bytes::new())
             }
@@ -306,9 +316,10 @@ impl InputStream for FileInputStream {
         // Before we defer to the regular `read`, make sure it has data ready to go:
         if let ReadState::Idle = self.state {
             let p = self.position;
+            let pool = self.pool.clone();
             self.state = self
                 .file
-                .run_blocking(move |f| Self::blocking_read(f, p, size))
+                .run_blocking(move |f| Self::blocking_read(f, &pool, p, size))
                 .await;
         }
 
@@ -342,9 +353,10 @@ impl Pollable for FileInputStream {
 
             const DEFAULT_READ_SIZE: usize = 4096;
             let p = self.position;
+            let pool = self.pool.clone();
             self.state = ReadState::Waiting(
                 self.file
-                    .spawn_blocking(move |f| Self::blocking_read(f, p, DEFAULT_READ_SIZE)),
+                    .spawn_blocking(move |f| Self::blocking_read(f, &pool, p, DEFAULT_READ_SIZE)),
             );
         }
 
@@ -487,6 +499,22 @@ impl OutputStream for FileOutputStream {
             }
         }
     }
+    /// Writes the zeroes with the specialized `blocking_write_and_flush`
+    /// rather than in runs of `write_zeroes`, each of which would spawn a
+    /// task. The zeroes are written in chunks of a static buffer, so large
+    /// runs aren't materialized.
+    async fn blocking_write_zeroes_and_flush(&mut self, mut nelem: usize) -> StreamResult<()> {
+        static ZEROES: [u8; 64 * 1024] = [0; 64 * 1024];
+        loop {
+            let len = nelem.min(ZEROES.len());
+            self.blocking_write_and_flush(Bytes::from_static(&ZEROES[..len]))
+                .await?;
+            nelem -= len;
+            if nelem == 0 {
+                return Ok(());
+            }
+        }
+    }
     fn flush(&mut self) -> Result<(), StreamError> {
         match self.state {
             // Only userland buffering of file writes is in the blocking task,
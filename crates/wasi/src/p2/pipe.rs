@@ -12,27 +12,73 @@ use bytes::Bytes;
 use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc;
 use wasmtime_wasi_io::{
+    accounting::{MemoryAccountant, MemoryCharge},
     poll::Pollable,
-    streams::{InputStream, OutputStream, StreamError},
+    snapshot::{SnapshotableStream, StreamSnapshot},
+    streams::{
+        BufferPool, InputStream, MetadataMap, OutputStream, SharedWatermarks, StreamError, Tunable,
+        WatermarkHandle, check_watermarks,
+    },
 };
 
 pub use crate::p2::write_stream::AsyncWriteStream;
 
+/// Creates the pool which a built-in stream reads into unless it's given
+/// another one.
+///
+/// Each stream gets a pool of its own, so that streams read by different
+/// threads don't contend for it. A stream only holds a few buffers at a
+/// time, so only a few are kept around.
+pub(crate) fn stream_buffer_pool() -> BufferPool {
+    BufferPool::new(4096, 4)
+}
+
 #[derive(Debug, Clone)]
 pub struct MemoryInputPipe {
     buffer: Arc<Mutex<Bytes>>,
+    metadata: Option<Arc<MetadataMap>>,
 }
 
 impl MemoryInputPipe {
     pub fn new(bytes: impl Into<Bytes>) -> Self {
         Self {
             buffer: Arc::new(Mutex::new(bytes.into())),
+            metadata: None,
         }
     }
 
+    /// Attaches `metadata` to this pipe, which guests can read with the
+    /// `wasmtime:wasi-io/streams-metadata` extension interface.
+    pub fn with_metadata(mut self, metadata: MetadataMap) -> Self {
+        self.metadata = Some(Arc::new(metadata));
+        self
+    }
+
     pub fn is_empty(&self) -> bool {
         self.buffer.lock().unwrap().is_empty()
     }
+
+    /// The [`StreamSnapshot::kind`] of snapshots of this pipe.
+    pub const SNAPSHOT_KIND: &'static str = "wasmtime-wasi:memory-input-pipe";
+
+    /// Recreates a pipe from a snapshot taken with
+    /// [`SnapshotableStream::save`], returning `None` if it's not a snapshot
+    /// of a `MemoryInputPipe`.
+    pub fn restore(snapshot: StreamSnapshot) -> Option<Self> {
+        if snapshot.kind != Self::SNAPSHOT_KIND {
+            return None;
+        }
+        Some(Self::new(snapshot.data))
+    }
+}
+
+impl SnapshotableStream for MemoryInputPipe {
+    fn save(&self) -> Option<StreamSnapshot> {
+        Some(StreamSnapshot {
+            kind: Self::SNAPSHOT_KIND.to_string(),
+            data: self.buffer.lock().unwrap().to_vec(),
+        })
+    }
 }
 
 #[async_trait::async_trait]
@@ -47,6 +93,22 @@ impl InputStream for MemoryInputPipe {
         let read = buffer.split_to(size);
         Ok(read)
     }
+
+    fn is_closed(&self) -> bool {
+        self.buffer.lock().unwrap().is_empty()
+    }
+
+    fn as_snapshotable(&self) -> Option<&dyn SnapshotableStream> {
+        Some(self)
+    }
+
+    fn metadata(&self) -> Option<&MetadataMap> {
+        self.metadata.as_deref()
+    }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
 }
 
 #[async_trait::async_trait]
@@ -54,17 +116,43 @@ impl Pollable for MemoryInputPipe {
     async fn ready(&mut self) {}
 }
 
+/// An [`OutputStream`] which collects everything written to it in memory.
+///
+/// The pipe accepts up to its capacity in bytes, which is its high
+/// watermark, see [`Tunable`]. Contents are never drained, so its low
+/// watermark has no effect. Clones of a pipe share its contents and its
+/// watermarks.
 #[derive(Debug, Clone)]
 pub struct MemoryOutputPipe {
-    capacity: usize,
+    watermarks: Arc<PipeWatermarks>,
     buffer: Arc<Mutex<bytes::BytesMut>>,
+    // Contents are never drained from the pipe, so they stay charged until
+    // the last clone of the pipe is dropped.
+    charge: Arc<Mutex<MemoryCharge>>,
+}
+
+/// The low and high watermarks of a [`MemoryOutputPipe`].
+#[derive(Debug)]
+struct PipeWatermarks(Mutex<(usize, usize)>);
+
+impl SharedWatermarks for PipeWatermarks {
+    fn set_watermarks(&self, low: usize, high: usize) -> anyhow::Result<()> {
+        check_watermarks(low, high)?;
+        *self.0.lock().unwrap() = (low, high);
+        Ok(())
+    }
+
+    fn watermarks(&self) -> (usize, usize) {
+        *self.0.lock().unwrap()
+    }
 }
 
 impl MemoryOutputPipe {
     pub fn new(capacity: usize) -> Self {
         MemoryOutputPipe {
-            capacity,
+            watermarks: Arc::new(PipeWatermarks(Mutex::new((capacity, capacity)))),
             buffer: std::sync::Arc::new(std::sync::Mutex::new(bytes::BytesMut::new())),
+            charge: Arc::new(Mutex::new(MemoryCharge::new())),
         }
     }
 
@@ -75,17 +163,56 @@ impl MemoryOutputPipe {
     pub fn try_into_inner(self) -> Option<bytes::BytesMut> {
         std::sync::Arc::into_inner(self.buffer).map(|m| m.into_inner().unwrap())
     }
+
+    /// Returns a handle through which the capacity of this pipe can be
+    /// adjusted.
+    pub fn watermark_handle(&self) -> WatermarkHandle {
+        WatermarkHandle::new(self.watermarks.clone())
+    }
+
+    fn capacity(&self) -> usize {
+        self.watermarks.watermarks().1
+    }
+
+    /// The [`StreamSnapshot::kind`] of snapshots of this pipe.
+    pub const SNAPSHOT_KIND: &'static str = "wasmtime-wasi:memory-output-pipe";
+
+    /// Recreates a pipe from a snapshot taken with
+    /// [`SnapshotableStream::save`], returning `None` if it's not a snapshot
+    /// of a `MemoryOutputPipe`.
+    pub fn restore(snapshot: StreamSnapshot) -> Option<Self> {
+        if snapshot.kind != Self::SNAPSHOT_KIND {
+            return None;
+        }
+        let (capacity, contents) = snapshot.data.split_first_chunk::<8>()?;
+        let pipe = Self::new(usize::try_from(u64::from_le_bytes(*capacity)).ok()?);
+        pipe.buffer.lock().unwrap().extend_from_slice(contents);
+        Some(pipe)
+    }
+}
+
+impl SnapshotableStream for MemoryOutputPipe {
+    fn save(&self) -> Option<StreamSnapshot> {
+        // The capacity is saved as a little-endian prefix of the contents.
+        let mut data = (self.capacity() as u64).to_le_bytes().to_vec();
+        data.extend_from_slice(&self.buffer.lock().unwrap());
+        Some(StreamSnapshot {
+            kind: Self::SNAPSHOT_KIND.to_string(),
+            data,
+        })
+    }
 }
 
 #[async_trait::async_trait]
 impl OutputStream for MemoryOutputPipe {
     fn write(&mut self, bytes: Bytes) -> Result<(), StreamError> {
         let mut buf = self.buffer.lock().unwrap();
-        if bytes.len() > self.capacity - buf.len() {
+        if bytes.len() > self.capacity().saturating_sub(buf.len()) {
             return Err(StreamError::Trap(anyhow!(
                 "write beyond capacity of MemoryOutputPipe"
             )));
         }
+        self.charge.lock().unwrap().charge(bytes.len())?;
         buf.extend_from_slice(bytes.as_ref());
         // Always ready for writing
         Ok(())
@@ -96,13 +223,36 @@ impl OutputStream for MemoryOutputPipe {
     }
     fn check_write(&mut self) -> Result<usize, StreamError> {
         let consumed = self.buffer.lock().unwrap().len();
-        if consumed < self.capacity {
-            Ok(self.capacity - consumed)
+        let capacity = self.capacity();
+        if consumed < capacity {
+            Ok(capacity - consumed)
         } else {
             // Since the buffer is full, no more bytes will ever be written
             Err(StreamError::Closed)
         }
     }
+
+    fn as_snapshotable(&self) -> Option<&dyn SnapshotableStream> {
+        Some(self)
+    }
+
+    fn set_memory_accountant(&mut self, accountant: &MemoryAccountant) {
+        self.charge.lock().unwrap().set_accountant(accountant);
+    }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
+}
+
+impl Tunable for MemoryOutputPipe {
+    fn set_watermarks(&mut self, low: usize, high: usize) -> anyhow::Result<()> {
+        self.watermarks.set_watermarks(low, high)
+    }
+
+    fn watermarks(&self) -> (usize, usize) {
+        self.watermarks.watermarks()
+    }
 }
 
 #[async_trait::async_trait]
@@ -111,25 +261,161 @@ impl Pollable for MemoryOutputPipe {
 }
 
 /// Provides a [`InputStream`] impl from a [`tokio::io::AsyncRead`] impl
+///
+/// The stream reads ahead of the guest up to its high watermark, see
+/// [`Tunable`]. Unless created with [`AsyncReadStream::with_watermarks`] its
+/// watermarks are unbounded and read-ahead is only limited by its buffers.
 pub struct AsyncReadStream {
     closed: bool,
     buffer: Option<Result<Bytes, StreamError>>,
     receiver: mpsc::Receiver<Result<Bytes, StreamError>>,
+    watermarks: Arc<ReadWatermarks>,
     join_handle: Option<crate::runtime::AbortOnDropJoinHandle<()>>,
 }
 
+/// The watermarks of an [`AsyncReadStream`] along with how many bytes it has
+/// read ahead of the guest.
+#[derive(Debug)]
+struct ReadWatermarks {
+    state: Mutex<ReadAhead>,
+    changed: tokio::sync::Notify,
+}
+
+#[derive(Debug)]
+struct ReadAhead {
+    low: usize,
+    high: usize,
+    /// The number of bytes read from the reader which the guest hasn't
+    /// read yet.
+    buffered: usize,
+    /// Whether `buffered` reached the high watermark and hasn't drained to
+    /// the low watermark since.
+    draining: bool,
+}
+
+impl ReadAhead {
+    fn update_draining(&mut self) {
+        if self.buffered >= self.high {
+            self.draining = true;
+        } else if self.buffered <= self.low {
+            self.draining = false;
+        }
+    }
+}
+
+impl ReadWatermarks {
+    fn new(low: usize, high: usize) -> Self {
+        ReadWatermarks {
+            state: Mutex::new(ReadAhead {
+                low,
+                high,
+                buffered: 0,
+                draining: false,
+            }),
+            changed: tokio::sync::Notify::new(),
+        }
+    }
+
+    /// Waits until more may be read ahead, returning how many bytes.
+    async fn read_budget(&self) -> usize {
+        loop {
+            {
+                let state = self.state.lock().unwrap();
+                if !state.draining {
+                    return state.high - state.buffered;
+                }
+            }
+            self.changed.notified().await;
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut ReadAhead)) {
+        let mut state = self.state.lock().unwrap();
+        f(&mut state);
+        state.update_draining();
+        drop(state);
+        self.changed.notify_one();
+    }
+}
+
+impl SharedWatermarks for ReadWatermarks {
+    fn set_watermarks(&self, low: usize, high: usize) -> anyhow::Result<()> {
+        check_watermarks(low, high)?;
+        self.update(|state| {
+            state.low = low;
+            state.high = high;
+        });
+        Ok(())
+    }
+
+    fn watermarks(&self) -> (usize, usize) {
+        let state = self.state.lock().unwrap();
+        (state.low, state.high)
+    }
+}
+
 impl AsyncReadStream {
     /// Create a [`AsyncReadStream`]. In order to use the [`InputStream`] impl
     /// provided by this struct, the argument must impl [`tokio::io::AsyncRead`].
-    pub fn new<T: tokio::io::AsyncRead + Send + Unpin + 'static>(mut reader: T) -> Self {
+    ///
+    /// Reads go into buffers taken from a pool of the stream's own. Use
+    /// [`AsyncReadStream::with_pool`] to share a pool between streams.
+    pub fn new<T: tokio::io::AsyncRead + Send + Unpin + 'static>(reader: T) -> Self {
+        Self::with_pool(reader, stream_buffer_pool())
+    }
+
+    /// Create a [`AsyncReadStream`] which reads into buffers taken from
+    /// `pool`, reusing their allocations once the guest has consumed them.
+    pub fn with_pool<T: tokio::io::AsyncRead + Send + Unpin + 'static>(
+        reader: T,
+        pool: BufferPool,
+    ) -> Self {
+        Self::spawn(reader, pool, ReadWatermarks::new(usize::MAX, usize::MAX))
+    }
+
+    /// Create a [`AsyncReadStream`] with the given watermarks, see
+    /// [`Tunable`], along with a handle through which they can be adjusted
+    /// after the stream has been handed off.
+    pub fn with_watermarks<T: tokio::io::AsyncRead + Send + Unpin + 'static>(
+        low: usize,
+        high: usize,
+        reader: T,
+    ) -> anyhow::Result<(Self, WatermarkHandle)> {
+        check_watermarks(low, high)?;
+        let stream = Self::spawn(reader, stream_buffer_pool(), ReadWatermarks::new(low, high));
+        let handle = stream.watermark_handle();
+        Ok((stream, handle))
+    }
+
+    /// Returns a handle through which the watermarks of this stream can be
+    /// adjusted.
+    pub fn watermark_handle(&self) -> WatermarkHandle {
+        WatermarkHandle::new(self.watermarks.clone())
+    }
+
+    fn spawn<T: tokio::io::AsyncRead + Send + Unpin + 'static>(
+        mut reader: T,
+        pool: BufferPool,
+        watermarks: ReadWatermarks,
+    ) -> Self {
+        let watermarks = Arc::new(watermarks);
+        let shared = watermarks.clone();
         let (sender, receiver) = mpsc::channel(1);
         let join_handle = crate::runtime::spawn(async move {
             loop {
                 use tokio::io::AsyncReadExt;
-                let mut buf = bytes::BytesMut::with_capacity(4096);
-                let sent = match reader.read_buf(&mut buf).await {
+                let budget = shared.read_budget().await;
+                let mut buf = pool.get(pool.buffer_size());
+                let read = (&mut reader)
+                    .take(u64::try_from(budget).unwrap_or(u64::MAX))
+                    .read_buf(&mut *buf)
+                    .await;
+                let sent = match read {
                     Ok(nbytes) if nbytes == 0 => sender.send(Err(StreamError::Closed)).await,
-                    Ok(_) => sender.send(Ok(buf.freeze())).await,
+                    Ok(nbytes) => {
+                        shared.update(|state| state.buffered += nbytes);
+                        sender.send(Ok(buf.freeze())).await
+                    }
                     Err(e) => {
                         sender
                             .send(Err(StreamError::LastOperationFailed(e.into())))
@@ -146,9 +432,18 @@ impl AsyncReadStream {
             closed: false,
             buffer: None,
             receiver,
+            watermarks,
             join_handle: Some(join_handle),
         }
     }
+
+    /// Records that `bytes` were handed to the guest.
+    fn consumed(&self, bytes: &Bytes) {
+        if !bytes.is_empty() {
+            self.watermarks
+                .update(|state| state.buffered -= bytes.len());
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -164,6 +459,7 @@ impl InputStream for AsyncReadStream {
                 if !rest.is_empty() {
                     self.buffer = Some(Ok(rest));
                 }
+                self.consumed(&bytes);
                 return Ok(bytes);
             }
             Some(Err(e)) => {
@@ -180,6 +476,7 @@ impl InputStream for AsyncReadStream {
                 if !rest.is_empty() {
                     self.buffer = Some(Ok(rest));
                 }
+                self.consumed(&bytes);
 
                 Ok(bytes)
             }
@@ -194,6 +491,12 @@ impl InputStream for AsyncReadStream {
         }
     }
 
+    fn is_closed(&self) -> bool {
+        // A closure received while waiting for readiness is reported by the
+        // next read.
+        self.closed || matches!(self.buffer, Some(Err(StreamError::Closed)))
+    }
+
     async fn cancel(&mut self) {
         match self.join_handle.take() {
             Some(task) => _ = task.cancel().await,
@@ -201,6 +504,16 @@ impl InputStream for AsyncReadStream {
         }
     }
 }
+impl Tunable for AsyncReadStream {
+    fn set_watermarks(&mut self, low: usize, high: usize) -> anyhow::Result<()> {
+        self.watermarks.set_watermarks(low, high)
+    }
+
+    fn watermarks(&self) -> (usize, usize) {
+        SharedWatermarks::watermarks(&*self.watermarks)
+    }
+}
+
 #[async_trait::async_trait]
 impl Pollable for AsyncReadStream {
     async fn ready(&mut self) {
@@ -234,6 +547,10 @@ impl OutputStream for SinkOutputStream {
         // This stream is always ready for writing.
         Ok(usize::MAX)
     }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
 }
 
 #[async_trait::async_trait]
@@ -250,6 +567,14 @@ impl InputStream for ClosedInputStream {
     fn read(&mut self, _size: usize) -> Result<Bytes, StreamError> {
         Err(StreamError::Closed)
     }
+
+    fn is_closed(&self) -> bool {
+        true
+    }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
 }
 
 #[async_trait::async_trait]
@@ -273,6 +598,10 @@ impl OutputStream for ClosedOutputStream {
     fn check_write(&mut self) -> Result<usize, StreamError> {
         Err(StreamError::Closed)
     }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
 }
 
 #[async_trait::async_trait]
@@ -766,6 +1095,153 @@ mod test {
         writer.write(chunk.clone()).expect("write does not trap");
     }
 
+    #[test_log::test(tokio::test(flavor = "multi_thread"))]
+    async fn accounted_write_stream() {
+        let accountant = MemoryAccountant::new(100);
+        // Nothing reads from the simplex until later, so after it has
+        // accepted 64 bytes the worker holds on to everything written.
+        let (mut reader, writer) = simplex(64);
+        let mut writer = AsyncWriteStream::new(4096, writer);
+        writer.set_memory_accountant(&accountant);
+
+        let until_used = |n: usize| {
+            let accountant = accountant.clone();
+            async move {
+                while accountant.used() != n {
+                    tokio::task::yield_now().await;
+                }
+            }
+        };
+
+        writer.write(Bytes::from_static(&[0; 64])).unwrap();
+        resolves_immediately(until_used(0)).await;
+
+        writer.write(Bytes::from_static(&[0; 64])).unwrap();
+        writer.write(Bytes::from_static(&[0; 32])).unwrap();
+        assert_eq!(accountant.used(), 96);
+        let err = writer.write(Bytes::from_static(&[0; 8])).unwrap_err();
+        assert!(
+            matches!(err, StreamError::LastOperationFailed(_)),
+            "{err:?}"
+        );
+
+        // Draining the sink credits the bytes which the worker then writes.
+        let mut buf = [0; 64];
+        reader.read_exact(&mut buf).await.unwrap();
+        resolves_immediately(until_used(32)).await;
+        writer.write(Bytes::from_static(&[0; 8])).unwrap();
+    }
+
+    #[test]
+    fn accounted_memory_output_pipe() {
+        let accountant = MemoryAccountant::new(10);
+        let mut pipe = MemoryOutputPipe::new(1024);
+        pipe.set_memory_accountant(&accountant);
+        pipe.write(Bytes::from_static(b"12345678")).unwrap();
+        assert!(pipe.write(Bytes::from_static(b"1234")).is_err());
+        assert_eq!(pipe.contents(), "12345678");
+        let clone = pipe.clone();
+        drop(pipe);
+        assert_eq!(accountant.used(), 8);
+        drop(clone);
+        assert_eq!(accountant.used(), 0);
+    }
+
+    #[test]
+    fn tuned_memory_output_pipe() {
+        let mut pipe = MemoryOutputPipe::new(16);
+        let mut handle = pipe.watermark_handle();
+        assert_eq!(handle.watermarks(), (16, 16));
+        pipe.write(Bytes::from_static(b"12345678")).unwrap();
+
+        // Shrinking the pipe below its contents keeps them but refuses more.
+        handle.set_watermarks(4, 4).unwrap();
+        assert!(matches!(pipe.check_write(), Err(StreamError::Closed)));
+        assert!(pipe.write(Bytes::from_static(b"9")).is_err());
+        assert_eq!(pipe.contents(), "12345678");
+
+        handle.set_watermarks(0, 10).unwrap();
+        assert_eq!(pipe.watermarks(), (0, 10));
+        assert_eq!(pipe.check_write().unwrap(), 2);
+        assert!(handle.set_watermarks(5, 4).is_err());
+        assert!(handle.set_watermarks(0, 0).is_err());
+        assert_eq!(pipe.watermarks(), (0, 10));
+    }
+
+    #[test_log::test(tokio::test(flavor = "multi_thread"))]
+    async fn shrink_write_stream_mid_transfer() {
+        // Nothing reads from the simplex until later, so once it has
+        // accepted the first chunk the worker holds on to everything written.
+        let (mut reader, writer) = simplex(64);
+        let (mut writer, mut handle) =
+            AsyncWriteStream::with_watermarks(1024, 1024, writer).unwrap();
+
+        // Flushing waits for the worker to hand the first chunk to the
+        // simplex, so the whole budget is available again afterwards.
+        let first = Bytes::from_static(&[1; 64]);
+        writer.write(first.clone()).unwrap();
+        writer.flush().unwrap();
+        let permit = resolves_immediately(writer.write_ready()).await.unwrap();
+        assert_eq!(permit, 1024);
+        let second = Bytes::from_static(&[2; 256]);
+        writer.write(second.clone()).unwrap();
+
+        // With 256 bytes buffered, shrinking to 128 applies backpressure
+        // straight away.
+        handle.set_watermarks(64, 128).unwrap();
+        assert_eq!(writer.watermarks(), (64, 128));
+        assert_eq!(writer.check_write().unwrap(), 0);
+        writer
+            .write(Bytes::from_static(&[3]))
+            .err()
+            .expect("unpermitted write does trap");
+        never_resolves(writer.write_ready()).await;
+
+        // Nothing buffered was dropped.
+        let mut buf = [0; 320];
+        reader.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf[..64], &first[..]);
+        assert_eq!(&buf[64..], &second[..]);
+
+        // Once drained, writes are permitted up to the new high watermark.
+        let permit = resolves_immediately(writer.write_ready()).await.unwrap();
+        assert_eq!(permit, 128);
+        writer
+            .write(Bytes::from_static(&[4; 129]))
+            .err()
+            .expect("write beyond the high watermark does trap");
+        writer.write(Bytes::from_static(&[4; 128])).unwrap();
+    }
+
+    #[test_log::test(tokio::test(flavor = "multi_thread"))]
+    async fn shrink_read_stream_mid_transfer() {
+        let contents = (0..4096).map(|i| i as u8).collect::<Vec<_>>();
+        let (r, mut w) = simplex(8192);
+        w.write_all(&contents).await.unwrap();
+        drop(w);
+        let (mut reader, mut handle) = AsyncReadStream::with_watermarks(0, 1024, r).unwrap();
+
+        // The reader stops once it's read ahead up to the high watermark.
+        resolves_immediately(reader.ready()).await;
+        handle.set_watermarks(0, 256).unwrap();
+        assert_eq!(reader.watermarks(), (0, 256));
+
+        // Shrinking didn't drop what was already read ahead.
+        let mut received = reader.read(4096).unwrap().to_vec();
+        assert_eq!(received.len(), 1024);
+
+        // From then on the reader only reads ahead up to the new watermark.
+        while received.len() < contents.len() {
+            resolves_immediately(reader.ready()).await;
+            let chunk = reader.read(4096).unwrap();
+            assert!(!chunk.is_empty() && chunk.len() <= 256, "{}", chunk.len());
+            received.extend_from_slice(&chunk);
+        }
+        assert_eq!(received, contents);
+        resolves_immediately(reader.ready()).await;
+        assert!(matches!(reader.read(1), Err(StreamError::Closed)));
+    }
+
     #[test_log::test(tokio::test(flavor = "multi_thread"))]
     async fn backpressure_write_stream_with_flush() {
         for n in 0..TEST_ITERATIONS {
@@ -837,4 +1313,125 @@ mod test {
             .expect("ready is ok");
         assert_eq!(permit, 1024);
     }
+
+    #[test]
+    fn memory_pipes_resume_after_restore() -> anyhow::Result<()> {
+        use wasmtime::component::{Resource, ResourceTable};
+        use wasmtime_wasi_io::snapshot::SnapshotResolver;
+        use wasmtime_wasi_io::streams::{DynInputStream, DynOutputStream};
+        use wasmtime_wasi_io::{restore_io, snapshot_io};
+
+        struct Pipes;
+        impl SnapshotResolver for Pipes {
+            fn restore_input(&mut self, snapshot: StreamSnapshot) -> Option<DynInputStream> {
+                Some(Box::new(MemoryInputPipe::restore(snapshot)?))
+            }
+            fn restore_output(&mut self, snapshot: StreamSnapshot) -> Option<DynOutputStream> {
+                Some(Box::new(MemoryOutputPipe::restore(snapshot)?))
+            }
+        }
+
+        let mut table = ResourceTable::new();
+        let input: Resource<DynInputStream> =
+            table.push(Box::new(MemoryInputPipe::new("hello, world")))?;
+        let output: Resource<DynOutputStream> = table.push(Box::new(MemoryOutputPipe::new(16)))?;
+        let pollable = wasmtime_wasi_io::poll::subscribe(
+            &mut table,
+            Resource::<DynInputStream>::new_borrow(input.rep()),
+        )?;
+
+        // Transfer part of the input to the output before the snapshot.
+        let chunk = table.get_mut(&input)?.read(7)?;
+        assert_eq!(chunk, "hello, ");
+        table.get_mut(&output)?.write(chunk)?;
+        let manifest = snapshot_io(&table);
+
+        let mut restored = ResourceTable::new();
+        restore_io(&mut restored, manifest, &mut Pipes)?;
+        assert!(restored.get(&pollable).is_ok());
+
+        let rest = restored.get_mut(&input)?.read(100)?;
+        assert_eq!(rest, "world");
+        assert!(matches!(
+            restored.get_mut(&input)?.read(100),
+            Err(StreamError::Closed)
+        ));
+        let output = restored.get_mut(&output)?;
+        assert_eq!(output.check_write()?, 9);
+        output.write(rest)?;
+        assert_eq!(output.check_write()?, 4);
+        let contents = output.as_snapshotable().unwrap().save().unwrap();
+        let pipe = MemoryOutputPipe::restore(contents).unwrap();
+        assert_eq!(pipe.contents(), "hello, world");
+        Ok(())
+    }
+
+    #[test]
+    fn deterministic_transcripts_match() -> anyhow::Result<()> {
+        use wasmtime::component::{Resource, ResourceTable};
+        use wasmtime_wasi_io::bindings::wasi::io::{
+            error::HostError, poll::Host as _, streams::HostInputStream, streams::HostOutputStream,
+        };
+        use wasmtime_wasi_io::streams::{DynInputStream, DynOutputStream};
+        use wasmtime_wasi_io::{IoImpl, IoLinkOptions, WakeSignal, block_on};
+
+        static SIGNAL: WakeSignal = WakeSignal::new();
+
+        fn borrow<T: 'static>(r: &Resource<T>) -> Resource<T> {
+            Resource::new_borrow(r.rep())
+        }
+
+        /// Runs a fixed guest trace against fresh pipes, recording the
+        /// result of every operation.
+        fn trace() -> anyhow::Result<Vec<String>> {
+            let mut options = IoLinkOptions::new();
+            options.deterministic(true);
+            let mut table = ResourceTable::new();
+            let input: Resource<DynInputStream> =
+                table.push(Box::new(MemoryInputPipe::new("the quick brown fox")))?;
+            let closed: Resource<DynInputStream> = table.push(Box::new(ClosedInputStream))?;
+            let output: Resource<DynOutputStream> =
+                table.push(Box::new(MemoryOutputPipe::new(16)))?;
+            let error = table.push(anyhow!("pipe at 0xdeadbeef failed"))?;
+            let mut io = IoImpl::new(&mut table, &options);
+
+            let mut transcript = Vec::new();
+            let output_ready = HostOutputStream::subscribe(&mut io, borrow(&output))?;
+            let input_ready = HostInputStream::subscribe(&mut io, borrow(&input))?;
+            let ready = block_on(&SIGNAL, |_| {}, io.poll(vec![input_ready, output_ready]))?;
+            transcript.push(format!("{ready:?}"));
+            for len in [4, 0, 6] {
+                transcript.push(format!("{:?}", io.read(borrow(&input), len)));
+            }
+            let read = block_on(&SIGNAL, |_| {}, io.blocking_read(borrow(&input), 100));
+            transcript.push(format!("{read:?}"));
+            transcript.push(format!("{:?}", io.read(borrow(&input), 1)));
+            transcript.push(format!("{:?}", io.read(borrow(&closed), 1)));
+            transcript.push(format!("{:?}", io.check_write(borrow(&output))));
+            transcript.push(format!("{:?}", io.write(borrow(&output), read?)));
+            transcript.push(format!("{:?}", io.check_write(borrow(&output))));
+            transcript.push(io.to_debug_string(error)?);
+            Ok(transcript)
+        }
+
+        let first = trace()?;
+        assert_eq!(first, trace()?);
+        assert_eq!(
+            first,
+            [
+                "[0, 1]",
+                "Ok([116, 104, 101, 32])",
+                "Ok([])",
+                "Ok([113, 117, 105, 99, 107, 32])",
+                "Ok([98, 114, 111, 119, 110, 32, 102, 111, 120])",
+                "Err(Closed)",
+                "Err(Closed)",
+                "Ok(16)",
+                "Ok(())",
+                "Ok(7)",
+                "pipe at <addr> failed",
+            ]
+        );
+        Ok(())
+    }
 }
@@ -9,6 +9,7 @@ use bytes::Bytes;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use wasmtime::component::Resource;
+use wasmtime_wasi_io::accounting::MemoryAccountant;
 use wasmtime_wasi_io::streams;
 
 /// A trait used to represent the standard input to a guest program.
@@ -76,6 +77,7 @@ impl StdinStream for InputFile {
     fn stream(&self) -> Box<dyn InputStream> {
         Box::new(InputFileStream {
             file: Arc::clone(&self.file),
+            pool: pipe::stream_buffer_pool(),
         })
     }
 }
@@ -88,6 +90,7 @@ impl IsTerminal for InputFile {
 
 struct InputFileStream {
     file: Arc<std::fs::File>,
+    pool: streams::BufferPool,
 }
 
 #[async_trait::async_trait]
@@ -99,7 +102,8 @@ impl InputStream for InputFileStream {
     fn read(&mut self, size: usize) -> StreamResult<Bytes> {
         use std::io::Read;
 
-        let mut buf = bytes::BytesMut::zeroed(size);
+        let mut buf = self.pool.get(size);
+        buf.resize(size, 0);
         let bytes_read = self
             .file
             .read(&mut buf)
@@ -108,7 +112,7 @@ impl InputStream for InputFileStream {
             return Err(StreamError::Closed);
         }
         buf.truncate(bytes_read);
-        StreamResult::Ok(buf.into())
+        StreamResult::Ok(buf.freeze())
     }
 }
 
@@ -454,6 +458,11 @@ impl OutputStream for AsyncStdoutStream {
             Err(_) => Err(StreamError::trap("concurrent flushes not supported yet")),
         }
     }
+    fn set_memory_accountant(&mut self, accountant: &MemoryAccountant) {
+        if let Ok(mut stream) = self.0.try_lock() {
+            stream.set_memory_accountant(accountant);
+        }
+    }
     async fn cancel(&mut self) {
         // Cancel the inner stream if we're the last reference to it:
         if let Some(mutex) = Arc::get_mut(&mut self.0) {
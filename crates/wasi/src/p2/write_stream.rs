@@ -2,14 +2,24 @@ use crate::p2::{OutputStream, Pollable, StreamError};
 use anyhow::anyhow;
 use bytes::Bytes;
 use std::sync::{Arc, Mutex};
+use wasmtime_wasi_io::accounting::{MemoryAccountant, MemoryCharge};
+use wasmtime_wasi_io::streams::{SharedWatermarks, Tunable, WatermarkHandle, check_watermarks};
 
 #[derive(Debug)]
 struct WorkerState {
     alive: bool,
     items: std::collections::VecDeque<Bytes>,
-    write_budget: usize,
+    /// The number of bytes written to the stream which the worker hasn't
+    /// written yet.
+    buffered: usize,
+    low_watermark: usize,
+    high_watermark: usize,
+    /// Whether `buffered` reached the high watermark and hasn't drained to
+    /// the low watermark since.
+    draining: bool,
     flush_pending: bool,
     error: Option<anyhow::Error>,
+    charge: MemoryCharge,
 }
 
 impl WorkerState {
@@ -22,6 +32,24 @@ impl WorkerState {
         }
         Ok(())
     }
+
+    /// The number of bytes which may currently be written.
+    fn write_budget(&self) -> usize {
+        if self.draining {
+            0
+        } else {
+            self.high_watermark.saturating_sub(self.buffered)
+        }
+    }
+
+    /// Updates `draining` after `buffered` or the watermarks changed.
+    fn update_draining(&mut self) {
+        if self.buffered >= self.high_watermark {
+            self.draining = true;
+        } else if self.buffered <= self.low_watermark {
+            self.draining = false;
+        }
+    }
 }
 
 struct Worker {
@@ -36,14 +64,18 @@ enum Job {
 }
 
 impl Worker {
-    fn new(write_budget: usize) -> Self {
+    fn new(low_watermark: usize, high_watermark: usize) -> Self {
         Self {
             state: Mutex::new(WorkerState {
                 alive: true,
                 items: std::collections::VecDeque::new(),
-                write_budget,
+                buffered: 0,
+                low_watermark,
+                high_watermark,
+                draining: false,
                 flush_pending: false,
                 error: None,
+                charge: MemoryCharge::new(),
             }),
             new_work: tokio::sync::Notify::new(),
             write_ready_changed: tokio::sync::Notify::new(),
@@ -55,7 +87,7 @@ impl Worker {
                 let state = self.state();
                 if state.error.is_some()
                     || !state.alive
-                    || (!state.flush_pending && state.write_budget > 0)
+                    || (!state.flush_pending && state.write_budget() > 0)
                 {
                     return;
                 }
@@ -69,11 +101,11 @@ impl Worker {
             return Err(e);
         }
 
-        if state.flush_pending || state.write_budget == 0 {
+        if state.flush_pending {
             return Ok(0);
         }
 
-        Ok(state.write_budget)
+        Ok(state.write_budget())
     }
     fn state(&self) -> std::sync::MutexGuard<'_, WorkerState> {
         self.state.lock().unwrap()
@@ -123,7 +155,10 @@ impl Worker {
                                 return;
                             }
                             Ok(_) => {
-                                self.state().write_budget += len;
+                                let mut state = self.state();
+                                state.buffered -= len;
+                                state.update_draining();
+                                state.charge.on_flushed(len);
                             }
                         }
                     }
@@ -136,7 +171,30 @@ impl Worker {
     }
 }
 
+impl SharedWatermarks for Worker {
+    fn set_watermarks(&self, low: usize, high: usize) -> anyhow::Result<()> {
+        check_watermarks(low, high)?;
+        {
+            let mut state = self.state();
+            state.low_watermark = low;
+            state.high_watermark = high;
+            state.update_draining();
+        }
+        self.write_ready_changed.notify_one();
+        Ok(())
+    }
+
+    fn watermarks(&self) -> (usize, usize) {
+        let state = self.state();
+        (state.low_watermark, state.high_watermark)
+    }
+}
+
 /// Provides a [`OutputStream`] impl from a [`tokio::io::AsyncWrite`] impl
+///
+/// The stream buffers up to its high watermark, initially the write budget
+/// it was created with, of bytes which haven't been written to the
+/// [`tokio::io::AsyncWrite`] yet. See [`Tunable`] for adjusting it.
 pub struct AsyncWriteStream {
     worker: Arc<Worker>,
     join_handle: Option<crate::runtime::AbortOnDropJoinHandle<()>>,
@@ -149,7 +207,31 @@ impl AsyncWriteStream {
         write_budget: usize,
         writer: T,
     ) -> Self {
-        let worker = Arc::new(Worker::new(write_budget));
+        Self::spawn(Worker::new(write_budget, write_budget), writer)
+    }
+
+    /// Create a [`AsyncWriteStream`] with the given watermarks, see
+    /// [`Tunable`], along with a handle through which they can be adjusted
+    /// after the stream has been handed off.
+    pub fn with_watermarks<T: tokio::io::AsyncWrite + Send + Unpin + 'static>(
+        low: usize,
+        high: usize,
+        writer: T,
+    ) -> anyhow::Result<(Self, WatermarkHandle)> {
+        check_watermarks(low, high)?;
+        let stream = Self::spawn(Worker::new(low, high), writer);
+        let handle = stream.watermark_handle();
+        Ok((stream, handle))
+    }
+
+    /// Returns a handle through which the watermarks of this stream can be
+    /// adjusted.
+    pub fn watermark_handle(&self) -> WatermarkHandle {
+        WatermarkHandle::new(self.worker.clone())
+    }
+
+    fn spawn<T: tokio::io::AsyncWrite + Send + Unpin + 'static>(worker: Worker, writer: T) -> Self {
+        let worker = Arc::new(worker);
 
         let w = Arc::clone(&worker);
         let join_handle = crate::runtime::spawn(async move { w.work(writer).await });
@@ -171,13 +253,13 @@ impl OutputStream for AsyncWriteStream {
                 "write not permitted while flush pending"
             )));
         }
-        match state.write_budget.checked_sub(bytes.len()) {
-            Some(remaining_budget) => {
-                state.write_budget = remaining_budget;
-                state.items.push_back(bytes);
-            }
-            None => return Err(StreamError::Trap(anyhow!("write exceeded budget"))),
+        if bytes.len() > state.write_budget() {
+            return Err(StreamError::Trap(anyhow!("write exceeded budget")));
         }
+        state.charge.charge(bytes.len())?;
+        state.buffered += bytes.len();
+        state.update_draining();
+        state.items.push_back(bytes);
         drop(state);
         self.worker.new_work.notify_one();
         Ok(())
@@ -202,7 +284,21 @@ impl OutputStream for AsyncWriteStream {
             None => {}
         }
     }
+
+    fn set_memory_accountant(&mut self, accountant: &MemoryAccountant) {
+        self.worker.state().charge.set_accountant(accountant);
+    }
+}
+impl Tunable for AsyncWriteStream {
+    fn set_watermarks(&mut self, low: usize, high: usize) -> anyhow::Result<()> {
+        self.worker.set_watermarks(low, high)
+    }
+
+    fn watermarks(&self) -> (usize, usize) {
+        SharedWatermarks::watermarks(&*self.worker)
+    }
 }
+
 #[async_trait::async_trait]
 impl Pollable for AsyncWriteStream {
     async fn ready(&mut self) {
@@ -5,6 +5,7 @@ use std::time::Duration;
 use wasmtime::Store;
 use wasmtime::component::{Component, Linker, ResourceTable};
 use wasmtime_wasi::p2::bindings::Command;
+use wasmtime_wasi::p2::pipe::MemoryInputPipe;
 use wasmtime_wasi::p2::{
     WasiCtx, WasiCtxBuilder, WasiCtxView, WasiView, add_to_linker_async,
     bindings::{clocks::wall_clock, filesystem::types as filesystem},
@@ -25,6 +26,14 @@ impl WasiView for CommandCtx {
     }
 }
 
+// Needed for `add_metadata_extension_to_linker` and
+// `add_error_code_extension_to_linker`.
+impl wasmtime_wasi_io::IoView for CommandCtx {
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+}
+
 use test_programs_artifacts::*;
 
 foreach_api!(assert_test_exists);
@@ -110,6 +119,145 @@ async fn api_read_only() -> Result<()> {
         .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))
 }
 
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+async fn api_stream_metadata() -> Result<()> {
+    let stdin = MemoryInputPipe::new("Hello, world!").with_metadata(
+        [("name", "greeting.txt"), ("content-length", "13")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+    );
+    let table = ResourceTable::new();
+    let wasi = WasiCtxBuilder::new().stdin(stdin).build();
+
+    let engine = test_programs_artifacts::engine(|config| {
+        config.async_support(true);
+    });
+    let mut linker = Linker::new(&engine);
+    add_to_linker_async(&mut linker)?;
+    wasmtime_wasi_io::add_metadata_extension_to_linker(&mut linker)?;
+
+    let mut store = Store::new(&engine, CommandCtx { table, wasi });
+    let component = Component::from_file(&engine, API_STREAM_METADATA_COMPONENT)?;
+    let command = Command::instantiate_async(&mut store, &component, &linker).await?;
+    command
+        .wasi_cli_run()
+        .call_run(&mut store)
+        .await?
+        .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))
+}
+
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+async fn api_error_code() -> Result<()> {
+    use wasmtime_wasi::cli::IsTerminal;
+    use wasmtime_wasi::p2::{InputStream, Pollable, StdinStream, StreamError, StreamResult};
+    use wasmtime_wasi_io::error::{ErrorCode, IoError};
+
+    /// A stdin whose reads fail as though its connection was reset.
+    struct ResetStdin;
+
+    #[async_trait::async_trait]
+    impl Pollable for ResetStdin {
+        async fn ready(&mut self) {}
+    }
+
+    #[async_trait::async_trait]
+    impl InputStream for ResetStdin {
+        fn read(&mut self, _size: usize) -> StreamResult<bytes::Bytes> {
+            Err(StreamError::from(IoError::new(
+                ErrorCode::CONNECTION_RESET,
+                "peer reset the connection",
+            )))
+        }
+    }
+
+    impl StdinStream for ResetStdin {
+        fn stream(&self) -> Box<dyn InputStream> {
+            Box::new(ResetStdin)
+        }
+    }
+
+    impl IsTerminal for ResetStdin {
+        fn is_terminal(&self) -> bool {
+            false
+        }
+    }
+
+    let table = ResourceTable::new();
+    let wasi = WasiCtxBuilder::new().stdin(ResetStdin).build();
+
+    let engine = test_programs_artifacts::engine(|config| {
+        config.async_support(true);
+    });
+    let mut linker = Linker::new(&engine);
+    add_to_linker_async(&mut linker)?;
+    wasmtime_wasi_io::add_error_code_extension_to_linker(&mut linker)?;
+
+    let mut store = Store::new(&engine, CommandCtx { table, wasi });
+    let component = Component::from_file(&engine, API_ERROR_CODE_COMPONENT)?;
+    let command = Command::instantiate_async(&mut store, &component, &linker).await?;
+    command
+        .wasi_cli_run()
+        .call_run(&mut store)
+        .await?
+        .map_err(|()| anyhow::anyhow!("command returned with failing exit status"))
+}
+
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+async fn api_trap_origin() -> Result<()> {
+    use wasmtime_wasi::cli::IsTerminal;
+    use wasmtime_wasi::p2::{InputStream, Pollable, StdinStream, StreamError, StreamResult};
+    use wasmtime_wasi_io::streams::TrapOrigin;
+
+    /// A stdin whose reads hit a bug in the host.
+    struct BuggyStdin;
+
+    #[async_trait::async_trait]
+    impl Pollable for BuggyStdin {
+        async fn ready(&mut self) {}
+    }
+
+    #[async_trait::async_trait]
+    impl InputStream for BuggyStdin {
+        fn read(&mut self, _size: usize) -> StreamResult<bytes::Bytes> {
+            Err(StreamError::host_bug("stdin lost track of its buffer"))
+        }
+    }
+
+    impl StdinStream for BuggyStdin {
+        fn stream(&self) -> Box<dyn InputStream> {
+            Box::new(BuggyStdin)
+        }
+    }
+
+    impl IsTerminal for BuggyStdin {
+        fn is_terminal(&self) -> bool {
+            false
+        }
+    }
+
+    for (origin, expected) in [("guest", TrapOrigin::Guest), ("host", TrapOrigin::Host)] {
+        let table = ResourceTable::new();
+        let wasi = WasiCtxBuilder::new()
+            .stdin(BuggyStdin)
+            .env("TRAP_ORIGIN", origin)
+            .build();
+        let (mut store, command) =
+            instantiate(API_TRAP_ORIGIN_COMPONENT, CommandCtx { table, wasi }).await?;
+        let error = command
+            .wasi_cli_run()
+            .call_run(&mut store)
+            .await
+            .expect_err("the host should have trapped");
+        assert_eq!(
+            error.downcast_ref::<TrapOrigin>(),
+            Some(&expected),
+            "{error:?}"
+        );
+    }
+    Ok(())
+}
+
 #[expect(
     dead_code,
     reason = "tested in the wasi-http crate, satisfying foreach_api! macro"
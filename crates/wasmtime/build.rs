@@ -23,14 +23,34 @@ fn main() {
         && has_host_compiler_backend;
     let has_virtual_memory = supported_os || cfg!(feature = "custom-virtual-memory");
 
+    // The `custom` platform in `runtime/vm/sys` is used whenever the host OS
+    // isn't natively supported or `std` is disabled, and it's only then that
+    // a `VirtualMemoryProvider` can be plugged in.
+    let has_custom_virtual_memory = cfg!(feature = "custom-virtual-memory")
+        && !miri
+        && (!supported_os || !cfg!(feature = "std"));
+
     custom_cfg("has_native_signals", has_native_signals);
     custom_cfg("has_virtual_memory", has_virtual_memory);
+    custom_cfg("has_custom_virtual_memory", has_custom_virtual_memory);
     custom_cfg("has_host_compiler_backend", has_host_compiler_backend);
 
     // If this OS isn't supported and no debug-builtins or if Cranelift doesn't support
     // the host or there's no need to build these helpers.
-    #[cfg(feature = "runtime")]
-    if has_host_compiler_backend && (supported_os || cfg!(feature = "debug-builtins")) {
+    //
+    // With `no-c-helpers` the helpers are instead implemented in Rust, which
+    // is only done for Unix on x86_64 and aarch64. Elsewhere the feature has
+    // no effect and `helpers.c` is still compiled.
+    let arch = std::env::var("CARGO_CFG_TARGET_ARCH").unwrap();
+    let has_rust_helpers = cfg!(feature = "no-c-helpers")
+        && unix
+        && matches!(arch.as_str(), "x86_64" | "aarch64");
+    custom_cfg("has_rust_helpers", has_rust_helpers);
+    let needs_helpers = cfg!(feature = "runtime")
+        && has_host_compiler_backend
+        && (supported_os || cfg!(feature = "debug-builtins"));
+    if needs_helpers && !has_rust_helpers {
+        #[cfg(feature = "runtime")]
         build_c_helpers();
     }
 
@@ -1258,6 +1258,9 @@ impl FunctionIndices {
                 RelocationTarget::PulleyHostcall(_) => {
                     unreachable!("relocation is resolved at runtime, not compile time");
                 }
+                RelocationTarget::ModuleConstant(_) => {
+                    unreachable!("relocation is resolved by the compiler's text section");
+                }
             },
         )?;
 
@@ -1276,7 +1279,8 @@ impl FunctionIndices {
             )?;
         }
 
-        let mut obj = wasmtime_environ::ObjectBuilder::new(obj, tunables);
+        let page_size_log2 = u8::try_from(compiler.page_size_align().trailing_zeros()).unwrap();
+        let mut obj = wasmtime_environ::ObjectBuilder::new(obj, tunables, page_size_log2);
         let mut artifacts = Artifacts::default();
 
         // Remove this as it's not needed by anything below and we'll debug
@@ -310,6 +310,12 @@ impl std::hash::Hash for HashedEngineCompileEnv<'_> {
         self.0.tunables().hash(hasher);
         self.0.features().hash(hasher);
         config.wmemcheck.hash(hasher);
+        config.import_call_interposition_ids().hash(hasher);
+        config.gas_policy_id().hash(hasher);
+
+        // Artifacts record the compile-time capabilities of the build which
+        // produced them, see `Engine::platform_capabilities`.
+        crate::PlatformCapabilities::host().hash(hasher);
 
         // Catch accidental bugs of reusing across crate versions.
         config.module_version.hash(hasher);
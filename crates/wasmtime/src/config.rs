@@ -1,4 +1,5 @@
 use crate::prelude::*;
+use crate::{ConfigError, HostIncompatibility, PlatformCapabilities};
 use alloc::sync::Arc;
 use bitflags::Flags;
 use core::fmt;
@@ -30,6 +31,12 @@ pub use crate::runtime::code_memory::CustomCodeMemory;
 pub use wasmtime_cache::{Cache, CacheConfig};
 #[cfg(all(feature = "incremental-cache", feature = "cranelift"))]
 pub use wasmtime_environ::CacheStore;
+#[cfg(any(feature = "cranelift", feature = "winch"))]
+pub use wasmtime_environ::GasPolicy;
+pub use wasmtime_environ::{
+    InstrumentedMemories, InterruptCheckPlacement, MemoryAccessInstrumentation,
+    NanCanonicalizationClasses, ShadowStackCheck, UnreachableLowering, V128Zero,
+};
 
 /// Represents the module instance allocation strategy to use.
 #[derive(Clone)]
@@ -121,7 +128,7 @@ impl core::hash::Hash for ModuleVersionStrategy {
 pub struct Config {
     #[cfg(any(feature = "cranelift", feature = "winch"))]
     compiler_config: CompilerConfig,
-    target: Option<target_lexicon::Triple>,
+    pub(crate) target: Option<target_lexicon::Triple>,
     #[cfg(feature = "gc")]
     collector: Collector,
     profiling_strategy: ProfilingStrategy,
@@ -162,6 +169,7 @@ pub struct Config {
     #[cfg(feature = "coredump")]
     pub(crate) coredump_on_trap: bool,
     pub(crate) macos_use_mach_ports: bool,
+    pub(crate) pulley_instruction_metering: bool,
     pub(crate) detect_host_feature: Option<fn(&str) -> Option<bool>>,
 }
 
@@ -176,6 +184,8 @@ struct CompilerConfig {
     cache_store: Option<Arc<dyn CacheStore>>,
     clif_dir: Option<std::path::PathBuf>,
     wmemcheck: bool,
+    import_call_interpositions: Vec<wasmtime_environ::ImportCallInterposition>,
+    gas_policy: Option<Arc<dyn GasPolicy>>,
 }
 
 #[cfg(any(feature = "cranelift", feature = "winch"))]
@@ -189,6 +199,8 @@ impl CompilerConfig {
             cache_store: None,
             clif_dir: None,
             wmemcheck: false,
+            import_call_interpositions: Vec::new(),
+            gas_policy: None,
         }
     }
 
@@ -267,6 +279,7 @@ impl Config {
             #[cfg(feature = "coredump")]
             coredump_on_trap: false,
             macos_use_mach_ports: !cfg!(miri),
+            pulley_instruction_metering: false,
             #[cfg(feature = "std")]
             detect_host_feature: Some(detect_host_feature),
             #[cfg(not(feature = "std"))]
@@ -310,6 +323,12 @@ impl Config {
     /// done with [`Config::cranelift_flag_set`] and
     /// [`Config::cranelift_flag_enable`].
     ///
+    /// When the `pulley` Cargo feature is enabled this can also be used to
+    /// select Pulley, Wasmtime's interpreter, at runtime with a target such as
+    /// `pulley64`, even on hosts which have a native Cranelift backend.
+    /// Modules compiled this way run on the host through the interpreter, see
+    /// [`Engine::is_pulley`](crate::Engine::is_pulley).
+    ///
     /// # Errors
     ///
     /// This method will error if the given target triple is not supported.
@@ -555,6 +574,37 @@ impl Config {
         self
     }
 
+    /// Configures whether the Pulley interpreter counts the instructions it
+    /// executes.
+    ///
+    /// This is an alternative to [`Config::consume_fuel`] for Pulley targets,
+    /// see [`Config::target`]. Instead of instrumenting the generated code,
+    /// the interpreter counts every instruction it executes, so the count only
+    /// depends on the bytecode being run and is the same on every host. The
+    /// count is available from [`Store::pulley_instructions_executed`], and
+    /// [`Store::set_pulley_instruction_limit`] makes execution trap with
+    /// [`Trap::InstructionLimitExceeded`] once a number of instructions have
+    /// been executed.
+    ///
+    /// The count is specific to Pulley bytecode, so it changes along with the
+    /// bytecode which Wasmtime generates for a module, for example between
+    /// releases or with different optimization levels.
+    ///
+    /// By default this option is `false`.
+    ///
+    /// **Note** Enabling this option requires the `pulley-metering` Cargo
+    /// feature of Wasmtime, which adds a small overhead to the interpreter even
+    /// when this option is disabled. Enabling this option when the target
+    /// isn't Pulley is an error.
+    ///
+    /// [`Store::pulley_instructions_executed`]: crate::Store::pulley_instructions_executed
+    /// [`Store::set_pulley_instruction_limit`]: crate::Store::set_pulley_instruction_limit
+    /// [`Trap::InstructionLimitExceeded`]: crate::Trap::InstructionLimitExceeded
+    pub fn pulley_instruction_metering(&mut self, enable: bool) -> &mut Self {
+        self.pulley_instruction_metering = enable;
+        self
+    }
+
     /// Enables epoch-based interruption.
     ///
     /// When executing code in async mode, we sometimes want to
@@ -669,6 +719,24 @@ impl Config {
         self
     }
 
+    /// Configures where compiled code checks for running out of fuel or for
+    /// reaching its epoch deadline.
+    ///
+    /// This only has an effect when [`Config::consume_fuel`] or
+    /// [`Config::epoch_interruption`] is enabled. Denser placements reduce the
+    /// latency between an epoch change, or fuel running out, and the guest
+    /// noticing it at the cost of executing more checks. The amount of fuel
+    /// consumed by a guest does not depend on this option. See
+    /// [`InterruptCheckPlacement`] for the available placements.
+    ///
+    /// **Note** This option is not supported by the Winch compiler.
+    ///
+    /// By default this is [`InterruptCheckPlacement::PerLoop`].
+    pub fn interrupt_check_placement(&mut self, placement: InterruptCheckPlacement) -> &mut Self {
+        self.tunables.interrupt_check_placement = Some(placement);
+        self
+    }
+
     /// Configures the maximum amount of stack space available for
     /// executing WebAssembly code.
     ///
@@ -1325,6 +1393,31 @@ impl Config {
         self
     }
 
+    /// Configures NaN canonicalization for only some classes of floating-point
+    /// operators.
+    ///
+    /// Unlike [`Config::cranelift_nan_canonicalization`], which canonicalizes
+    /// the result of every floating-point arithmetic operation, this option
+    /// canonicalizes NaNs only in the results of the WebAssembly operators in
+    /// the given `classes`. This allows, for example, canonicalizing after
+    /// arithmetic while leaving loaded values alone. The canonical NaN and the
+    /// instruction sequences used are the same as for
+    /// [`Config::cranelift_nan_canonicalization`], which cannot be enabled at
+    /// the same time as this option.
+    ///
+    /// This option is only supported by Cranelift.
+    ///
+    /// By default this option is not configured and NaN canonicalization is
+    /// controlled by [`Config::cranelift_nan_canonicalization`] alone.
+    #[cfg(any(feature = "cranelift", feature = "winch"))]
+    pub fn nan_canonicalization_classes(
+        &mut self,
+        classes: NanCanonicalizationClasses,
+    ) -> &mut Self {
+        self.tunables.nan_canonicalization = Some(Some(classes));
+        self
+    }
+
     /// Controls whether proof-carrying code (PCC) is used to validate
     /// lowering of Wasm sandbox checks.
     ///
@@ -1987,6 +2080,136 @@ impl Config {
         self
     }
 
+    /// Compiles direct calls to the imported function `module::name` as calls
+    /// to the imported function `to_module::to_name` instead.
+    ///
+    /// This applies to every module which imports a function named
+    /// `module::name`. Such modules must also import `to_module::to_name`
+    /// with the same type, otherwise their compilation fails. Calls through
+    /// tables or function references are unaffected, as are modules which
+    /// don't import `module::name`.
+    ///
+    /// Rules configured with this method and [`Config::wrap_import_calls`]
+    /// are applied in the order they were configured, and only the first
+    /// rule for an import takes effect.
+    ///
+    /// The configured rules are recorded in serialized modules, which can
+    /// then only be deserialized into engines configured with the same rules
+    /// in the same order. This option is only supported by Cranelift.
+    #[cfg(any(feature = "cranelift", feature = "winch"))]
+    pub fn redirect_import_calls(
+        &mut self,
+        module: &str,
+        name: &str,
+        to_module: &str,
+        to_name: &str,
+    ) -> &mut Self {
+        self.compiler_config.import_call_interpositions.push(
+            wasmtime_environ::ImportCallInterposition {
+                module: module.to_string(),
+                name: name.to_string(),
+                action: wasmtime_environ::ImportCallAction::Redirect(
+                    to_module.to_string(),
+                    to_name.to_string(),
+                ),
+            },
+        );
+        self
+    }
+
+    /// Compiles direct calls to the imported function `module::name` such
+    /// that the imported function `pre` is called with the call's arguments
+    /// just before it and the imported function `post` is called with its
+    /// results just after it.
+    ///
+    /// Both `pre` and `post` are given as `(module, name)` pairs and must be
+    /// imported, with no results, by every module importing `module::name`,
+    /// otherwise their compilation fails. Tail calls to `module::name` can't
+    /// be wrapped with a `post` function and fail to compile as well.
+    ///
+    /// See [`Config::redirect_import_calls`] for how rules are applied and
+    /// which calls are affected.
+    #[cfg(any(feature = "cranelift", feature = "winch"))]
+    pub fn wrap_import_calls(
+        &mut self,
+        module: &str,
+        name: &str,
+        pre: Option<(&str, &str)>,
+        post: Option<(&str, &str)>,
+    ) -> &mut Self {
+        let to_owned = |(m, n): (&str, &str)| (m.to_string(), n.to_string());
+        self.compiler_config.import_call_interpositions.push(
+            wasmtime_environ::ImportCallInterposition {
+                module: module.to_string(),
+                name: name.to_string(),
+                action: wasmtime_environ::ImportCallAction::Wrap {
+                    pre: pre.map(to_owned),
+                    post: post.map(to_owned),
+                },
+            },
+        );
+        self
+    }
+
+    /// Returns a description of each rule configured with
+    /// [`Config::redirect_import_calls`] and [`Config::wrap_import_calls`],
+    /// which compiled artifacts record and cache keys include since the rules
+    /// change the compiled code.
+    pub(crate) fn import_call_interposition_ids(&self) -> Vec<String> {
+        #[cfg(any(feature = "cranelift", feature = "winch"))]
+        return self
+            .compiler_config
+            .import_call_interpositions
+            .iter()
+            .map(|rule| format!("{rule:?}"))
+            .collect();
+        #[cfg(not(any(feature = "cranelift", feature = "winch")))]
+        return Vec::new();
+    }
+
+    /// Returns the [`GasPolicy::identity`] of the policy configured with
+    /// [`Config::gas_policy`], if any, which compiled artifacts record and
+    /// cache keys include since the policy changes the compiled code.
+    pub(crate) fn gas_policy_id(&self) -> Option<String> {
+        #[cfg(any(feature = "cranelift", feature = "winch"))]
+        return self
+            .compiler_config
+            .gas_policy
+            .as_ref()
+            .map(|policy| policy.identity());
+        #[cfg(not(any(feature = "cranelift", feature = "winch")))]
+        return None;
+    }
+
+    /// Configures the [`GasPolicy`] which assigns the fuel consumed by each
+    /// WebAssembly operator, replacing the default costs.
+    ///
+    /// The gas charged by `policy` is consumed as fuel, so it requires
+    /// [`Config::consume_fuel`] and is managed with [`Store::set_fuel`] and
+    /// [`Store::get_fuel`] like any other fuel. Charges are settled once per
+    /// basic block, with a single update of the fuel counter, so the fuel
+    /// consumed by a block is exactly the sum of the charges of the operators
+    /// it executes. Where running out is checked is still configured with
+    /// [`Config::interrupt_check_placement`], along with
+    /// [`GasPolicy::check_before_calls`].
+    ///
+    /// The policy sees operators as defined by the version of `wasmparser`
+    /// used by Wasmtime, which is re-exported with the `reexport-wasmparser`
+    /// feature.
+    ///
+    /// The policy's [`GasPolicy::identity`] is recorded in serialized
+    /// modules, which can then only be deserialized into engines configured
+    /// with a policy of the same identity. This option is only supported by
+    /// Cranelift.
+    ///
+    /// [`Store::set_fuel`]: crate::Store::set_fuel
+    /// [`Store::get_fuel`]: crate::Store::get_fuel
+    #[cfg(any(feature = "cranelift", feature = "winch"))]
+    pub fn gas_policy(&mut self, policy: impl GasPolicy + 'static) -> &mut Self {
+        self.compiler_config.gas_policy = Some(Arc::new(policy));
+        self
+    }
+
     /// Configures the "guaranteed dense image size" for copy-on-write
     /// initialized memories.
     ///
@@ -2046,6 +2269,218 @@ impl Config {
         self
     }
 
+    /// Configures how all-zero `v128` values, such as the initial value of
+    /// `v128` locals, are materialized in compiled code.
+    ///
+    /// * [`V128Zero::FunctionConstant`] places the zero in the constant pool
+    ///   of each function that needs it. Targets which can produce a zero
+    ///   vector without a load, such as x86-64, never emit the constant.
+    /// * [`V128Zero::Splat`] synthesizes the zero by splatting an integer
+    ///   zero, which requires no constant at all.
+    /// * [`V128Zero::ModuleConstant`] emits a single 16-byte zero into the
+    ///   module's text section which every function loads by address. This is
+    ///   currently only supported on x86-64; other targets fall back to
+    ///   [`V128Zero::FunctionConstant`].
+    ///
+    /// This only affects the size and shape of compiled code, never its
+    /// behavior. This option is only supported by Cranelift.
+    ///
+    /// By default this option is [`V128Zero::FunctionConstant`].
+    pub fn v128_zero(&mut self, zero: V128Zero) -> &mut Self {
+        self.tunables.v128_zero = Some(zero);
+        self
+    }
+
+    /// Configures shadow-stack instrumentation of reference-typed locals.
+    ///
+    /// When enabled, every local (including parameters) of a reference type is
+    /// mirrored into a dedicated stack slot. The slot is written whenever the
+    /// local is defined, and each `local.get` checks the local's value
+    /// against its shadow copy as configured by `check`:
+    ///
+    /// * [`ShadowStackCheck::Trap`] traps if they differ.
+    /// * [`ShadowStackCheck::Breakpoint`] executes a debugger breakpoint if
+    ///   they differ and then continues.
+    /// * [`ShadowStackCheck::Unchecked`] doesn't check at all, only keeping
+    ///   the shadow copies up to date for tools which inspect them.
+    ///
+    /// This is a debugging and research aid for detecting corruption of
+    /// compiled code's locals and comes with a significant performance cost.
+    /// Passing `None` disables the instrumentation.
+    ///
+    /// This option is only supported by Cranelift and requires the
+    /// `shadow-stack` feature.
+    ///
+    /// By default this option is `None`.
+    #[cfg(feature = "shadow-stack")]
+    pub fn shadow_stack(&mut self, check: Option<ShadowStackCheck>) -> &mut Self {
+        self.tunables.shadow_stack = Some(check);
+        self
+    }
+
+    /// Configures whether `call_indirect` sites cache the last function they
+    /// successfully called.
+    ///
+    /// When enabled each `call_indirect` instruction is given a slot in its
+    /// instance's `VMContext`. Every call compares the table entry it is about
+    /// to call against that slot and, if they are the same function
+    /// reference, skips the signature check since it already succeeded for
+    /// that function reference. Otherwise the full signature check is
+    /// performed and its result recorded in the slot for the next call.
+    ///
+    /// This trades a slightly larger `VMContext` and an extra load and
+    /// compare per call for cheaper monomorphic indirect calls. Semantics are
+    /// unchanged: calls through null or mismatched table entries trap exactly
+    /// as they would otherwise. This option is only supported by Cranelift.
+    ///
+    /// By default this option is `false`.
+    pub fn call_indirect_inline_caches(&mut self, enable: bool) -> &mut Self {
+        self.tunables.call_indirect_inline_caches = Some(enable);
+        self
+    }
+
+    /// Configures whether the trapping float-to-int truncation instructions
+    /// saturate instead of trapping.
+    ///
+    /// When enabled, instructions such as `i32.trunc_f32_s` are compiled
+    /// exactly like their `*_sat` counterparts from the nontrapping
+    /// float-to-int conversions proposal: NaN converts to 0 and out-of-range
+    /// inputs are clamped to the minimum or maximum value of the result type
+    /// instead of raising a trap.
+    ///
+    /// **This is a deviation from the WebAssembly specification.** It exists
+    /// solely for compatibility with legacy modules produced by toolchains
+    /// which assumed saturating semantics for the trapping instructions, and
+    /// should not be enabled otherwise. This option is only supported by
+    /// Cranelift.
+    ///
+    /// By default this option is `false`.
+    pub fn nontrapping_float_to_int_override(&mut self, enable: bool) -> &mut Self {
+        self.tunables.nontrapping_float_to_int_override = Some(enable);
+        self
+    }
+
+    /// Configures whether locals which are always assigned before being read
+    /// are initialized to zero on function entry.
+    ///
+    /// WebAssembly locals start out as zero, and by default compiled code
+    /// stores that zero into every local when a function is entered. Code
+    /// from some toolchains declares hundreds of locals, each of which is
+    /// assigned before any read, and the optimizer can't always remove the
+    /// resulting dead stores. When this option is enabled each function is
+    /// scanned before it's translated, and the initialization is skipped for
+    /// locals which the scan proves are assigned before every read. Locals of
+    /// reference types are always initialized.
+    ///
+    /// This only affects the size and shape of compiled code, never its
+    /// behavior. This option is only supported by Cranelift.
+    ///
+    /// By default this option is `false`.
+    pub fn lazy_local_init(&mut self, enable: bool) -> &mut Self {
+        self.tunables.lazy_local_init = Some(enable);
+        self
+    }
+
+    /// Configures the length, in bytes, below which `memory.copy` and
+    /// `memory.fill` are compiled inline.
+    ///
+    /// By default these instructions call into the runtime, which is costly
+    /// when the amount of memory they copy or fill is small. When an
+    /// instruction's length operand is a constant below this threshold, and
+    /// it operates on a single unshared 32-bit linear memory, it's instead
+    /// compiled to a sequence of loads and stores. Lengths of 256 bytes or
+    /// more are never compiled inline.
+    ///
+    /// This only affects the size and shape of compiled code, never its
+    /// behavior: out-of-bounds operations trap before accessing memory and
+    /// overlapping copies behave as `memmove` does. This option is only
+    /// supported by Cranelift.
+    ///
+    /// By default this option is `0`, which never compiles these instructions
+    /// inline.
+    pub fn bulk_memory_inline_threshold(&mut self, bytes: u32) -> &mut Self {
+        self.tunables.bulk_memory_inline_threshold = Some(bytes);
+        self
+    }
+
+    /// Configures how loads and stores of linear memories are instrumented.
+    ///
+    /// Instrumentation observes every load and store of a linear memory,
+    /// including atomic and SIMD accesses, immediately before memory is
+    /// accessed. This is useful for building watchpoints, software memory
+    /// protection, or profiles of a guest's memory traffic. Bulk memory
+    /// operators, such as `memory.copy`, aren't instrumented.
+    ///
+    /// Instrumentation is placed after any explicit bounds check of the
+    /// access. Accesses which rely on guard pages to be bounds checked, as
+    /// described in [`Config::memory_reservation`], may therefore be
+    /// observed just before they trap.
+    ///
+    /// * [`MemoryAccessInstrumentation::Count`] increments a counter in the
+    ///   store before each access, which is read with
+    ///   [`Store::memory_accesses`](crate::Store::memory_accesses). This is
+    ///   compiled inline and is relatively cheap.
+    /// * [`MemoryAccessInstrumentation::Callback`] calls the callback
+    ///   configured with
+    ///   [`Store::memory_access_callback`](crate::Store::memory_access_callback)
+    ///   before each access, which may reject the access with a trap. This is
+    ///   a call into the host for every access and is quite slow.
+    ///
+    /// This option is only supported by Cranelift.
+    ///
+    /// By default this option is `None` and memory accesses aren't
+    /// instrumented. Which memories are instrumented is configured with
+    /// [`Config::instrumented_memories`].
+    pub fn memory_access_instrumentation(
+        &mut self,
+        instrumentation: Option<MemoryAccessInstrumentation>,
+    ) -> &mut Self {
+        self.tunables.memory_access_instrumentation = Some(instrumentation);
+        self
+    }
+
+    /// Configures which linear memories have their accesses instrumented
+    /// when [`Config::memory_access_instrumentation`] is enabled.
+    ///
+    /// Memories are selected by their index within the module which defines
+    /// or imports them, and the selection applies to every module compiled
+    /// with this configuration. Accesses of the other memories are compiled
+    /// without any instrumentation.
+    ///
+    /// By default every memory is instrumented.
+    pub fn instrumented_memories(&mut self, memories: InstrumentedMemories) -> &mut Self {
+        self.tunables.instrumented_memories = Some(memories);
+        self
+    }
+
+    /// Configures how the `unreachable` operator, and optionally other traps
+    /// which are raised unconditionally, are compiled.
+    ///
+    /// * [`UnreachableLowering::Trap`] compiles them to a plain trap.
+    /// * [`UnreachableLowering::NotifyHost`] calls the callback configured
+    ///   with
+    ///   [`Store::trap_context_callback`](crate::Store::trap_context_callback)
+    ///   before `unreachable` traps, passing it the index of the trapping
+    ///   function and the offset of the operator within its module. This
+    ///   allows logging where a guest trapped without inspecting its
+    ///   backtrace or debug information.
+    /// * [`UnreachableLowering::NotifyHostOnAllUnconditionalTraps`]
+    ///   additionally calls the callback before traps which are known to
+    ///   happen at compile time, such as statically out-of-bounds memory
+    ///   accesses.
+    ///
+    /// In all cases the trap is still raised once the callback returns, so
+    /// `unreachable` still traps with
+    /// [`Trap::UnreachableCodeReached`](crate::Trap::UnreachableCodeReached).
+    ///
+    /// This option is only supported by Cranelift.
+    ///
+    /// By default this option is [`UnreachableLowering::Trap`].
+    pub fn unreachable_lowering(&mut self, lowering: UnreachableLowering) -> &mut Self {
+        self.tunables.unreachable_lowering = Some(lowering);
+        self
+    }
+
     /// Returns the set of features that the currently selected compiler backend
     /// does not support at all and may panic on.
     ///
@@ -2197,6 +2632,67 @@ impl Config {
         target_lexicon::Triple::host()
     }
 
+    /// Checks that this configuration can be used to execute WebAssembly on
+    /// the current host.
+    ///
+    /// Some options depend on what this build of Wasmtime supports on the
+    /// host, see [`Engine::platform_capabilities`], such as signals-based
+    /// traps which need native signal handlers, or guard pages which need
+    /// virtual memory. This method reports every option which the host can't
+    /// support at once, as a list of [`HostIncompatibility`] values, instead
+    /// of failing on the first one when it's used.
+    ///
+    /// [`Engine::new`] performs the same checks when no [`Config::target`] is
+    /// configured. When a target is configured the engine may only be used to
+    /// compile modules for another host, so these checks are instead deferred
+    /// until modules are loaded with it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConfigError::Invalid`] if this configuration is invalid on
+    /// every host, with the error [`Engine::new`] would return, and
+    /// [`ConfigError::Incompatible`] if it's valid but can't be used on this
+    /// host.
+    ///
+    /// [`Engine::new`]: crate::Engine::new
+    /// [`Engine::platform_capabilities`]: crate::Engine::platform_capabilities
+    pub fn validate_for_host(&self) -> Result<(), ConfigError> {
+        let (tunables, _) = self.validate().map_err(ConfigError::Invalid)?;
+        let incompatibilities = self.host_incompatibilities(&tunables);
+        if incompatibilities.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Incompatible(incompatibilities))
+        }
+    }
+
+    /// Returns the reasons that this configuration, resolved to `tunables`,
+    /// can't be used to execute WebAssembly on the current host.
+    pub(crate) fn host_incompatibilities(&self, tunables: &Tunables) -> Vec<HostIncompatibility> {
+        let caps = PlatformCapabilities::host();
+        let mut incompatibilities = Vec::new();
+        if !target_runs_on_host(&self.compiler_target()) {
+            incompatibilities.push(HostIncompatibility::Target);
+        }
+        if tunables.signals_based_traps && !caps.native_signals {
+            incompatibilities.push(HostIncompatibility::SignalsBasedTraps);
+        }
+        if tunables.memory_init_cow && !caps.virtual_memory {
+            incompatibilities.push(HostIncompatibility::MemoryInitCow);
+        }
+        if tunables.memory_guard_size > 0 && !caps.virtual_memory {
+            incompatibilities.push(HostIncompatibility::MemoryGuardPages);
+        }
+        #[cfg(feature = "async")]
+        if self.async_support && !wasmtime_fiber::SUPPORTED {
+            incompatibilities.push(HostIncompatibility::AsyncStacks);
+        }
+        if tunables.epoch_interruption && !cfg!(target_has_atomic = "64") {
+            incompatibilities.push(HostIncompatibility::EpochInterruption);
+        }
+        incompatibilities
+    }
+
     pub(crate) fn validate(&self) -> Result<(Tunables, WasmFeatures)> {
         let features = self.features();
 
@@ -2230,6 +2726,14 @@ impl Config {
         if self.wmemcheck {
             bail!("wmemcheck (memory checker) was requested but is not enabled in this build");
         }
+        if self.pulley_instruction_metering {
+            if !cfg!(feature = "pulley-metering") {
+                bail!("pulley instruction metering was requested but is not enabled in this build");
+            }
+            if !self.compiler_target().is_pulley() {
+                bail!("pulley instruction metering requires a pulley target");
+            }
+        }
 
         let mut tunables = Tunables::default_for_target(&self.compiler_target())?;
 
@@ -2248,10 +2752,31 @@ impl Config {
                 tunables.memory_guard_size = 0;
             }
 
+            // Similarly if the registered virtual memory provider can't
+            // create inaccessible guard regions then fall back to explicit
+            // bounds checks.
+            #[cfg(all(feature = "runtime", has_custom_virtual_memory))]
+            if !crate::runtime::vm::virtual_memory_supports_guard_pages() {
+                tunables.signals_based_traps = false;
+                tunables.memory_guard_size = 0;
+            }
+
+            // If the provider reports large pages then size reservations so
+            // that they can be backed entirely by them.
+            #[cfg(all(feature = "runtime", has_custom_virtual_memory))]
+            if let Some(size) = crate::runtime::vm::virtual_memory_large_page_size() {
+                let size = u64::try_from(size).unwrap();
+                tunables.memory_reservation = tunables.memory_reservation.next_multiple_of(size);
+                tunables.memory_reservation_for_growth = tunables
+                    .memory_reservation_for_growth
+                    .next_multiple_of(size);
+            }
+
             // When virtual memory is not available use slightly different
             // defaults for tunables to be more amenable to `MallocMemory`.
             // Note that these can still be overridden by config options.
             if !cfg!(has_virtual_memory) {
+                tunables.memory_guard_size = 0;
                 tunables.memory_reservation = 0;
                 tunables.memory_reservation_for_growth = 1 << 20; // 1MB
                 tunables.memory_init_cow = false;
@@ -2264,6 +2789,46 @@ impl Config {
         #[cfg(any(feature = "cranelift", feature = "winch"))]
         {
             tunables.winch_callable = self.compiler_config.strategy == Some(Strategy::Winch);
+            if tunables.shadow_stack.is_some() && tunables.winch_callable {
+                bail!("the shadow stack is not supported by Winch");
+            }
+            if tunables.call_indirect_inline_caches && tunables.winch_callable {
+                bail!("call_indirect inline caches are not supported by Winch");
+            }
+            if tunables.nontrapping_float_to_int_override && tunables.winch_callable {
+                bail!("the nontrapping float-to-int override is not supported by Winch");
+            }
+            if tunables.memory_access_instrumentation.is_some() && tunables.winch_callable {
+                bail!("memory access instrumentation is not supported by Winch");
+            }
+            if tunables.unreachable_lowering != UnreachableLowering::Trap && tunables.winch_callable
+            {
+                bail!("notifying the host of traps is not supported by Winch");
+            }
+            if tunables.nan_canonicalization.is_some() {
+                if tunables.winch_callable {
+                    bail!("per-operator NaN canonicalization is not supported by Winch");
+                }
+                if self
+                    .compiler_config
+                    .settings
+                    .get("enable_nan_canonicalization")
+                    .is_some_and(|v| v == "true")
+                {
+                    bail!(
+                        "per-operator NaN canonicalization cannot be combined with \
+                         `cranelift_nan_canonicalization`"
+                    );
+                }
+            }
+            if tunables.interrupt_check_placement != InterruptCheckPlacement::PerLoop
+                && tunables.winch_callable
+            {
+                bail!("custom interrupt check placement is not supported by Winch");
+            }
+        }
+        if tunables.interrupt_check_placement == InterruptCheckPlacement::PerNOperators(0) {
+            bail!("interrupt checks cannot be placed every 0 operators");
         }
 
         tunables.collector = if features.gc_types() {
@@ -2530,6 +3095,16 @@ impl Config {
         }
 
         compiler.wmemcheck(self.compiler_config.wmemcheck);
+        if !self.compiler_config.import_call_interpositions.is_empty() {
+            compiler
+                .import_call_interpositions(&self.compiler_config.import_call_interpositions)?;
+        }
+        if let Some(policy) = &self.compiler_config.gas_policy {
+            if !tunables.consume_fuel {
+                bail!("a gas policy requires fuel consumption to be enabled");
+            }
+            compiler.gas_policy(policy.clone())?;
+        }
 
         Ok((self, compiler.build()?))
     }
@@ -3543,6 +4118,23 @@ impl PoolingAllocationConfig {
     }
 }
 
+/// Returns whether code compiled for `target` can be executed on this host.
+pub(crate) fn target_runs_on_host(target: &target_lexicon::Triple) -> bool {
+    let host = target_lexicon::Triple::host();
+
+    // Pulley targets run anywhere Pulley is enabled, as long as they match
+    // the pointer width and endianness of the host.
+    if target.is_pulley() {
+        return cfg!(feature = "pulley")
+            && target.pointer_width() == host.pointer_width()
+            && target.endianness() == host.endianness();
+    }
+
+    // Native targets must match the host exactly, and can't run at all on a
+    // host which only executes Pulley.
+    *target == host && !PlatformCapabilities::host().pulley_by_default
+}
+
 #[cfg(feature = "std")]
 fn detect_host_feature(feature: &str) -> Option<bool> {
     #[cfg(target_arch = "aarch64")]
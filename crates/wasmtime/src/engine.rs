@@ -17,8 +17,11 @@ use std::{fs::File, path::Path};
 use wasmparser::WasmFeatures;
 use wasmtime_environ::{FlagValue, ObjectKind, TripleExt, Tunables};
 
+mod capabilities;
 mod serialization;
 
+pub use capabilities::{ConfigError, HostIncompatibility, PlatformCapabilities};
+
 /// An `Engine` which is a global context for compilation and management of wasm
 /// modules.
 ///
@@ -96,10 +99,23 @@ impl Engine {
     /// the compiler setting `enable_safepoints` and `unwind_info`
     /// to `true`, but explicitly disable these two compiler settings
     /// will cause errors.
+    ///
+    /// When no [`Config::target`] is configured this also fails with a
+    /// [`ConfigError::Incompatible`] if the host can't support the
+    /// configuration, see [`Config::validate_for_host`].
     pub fn new(config: &Config) -> Result<Engine> {
         let config = config.clone();
         let (tunables, features) = config.validate()?;
 
+        // Without an explicit target this engine executes code on this host,
+        // so report everything the host doesn't support up front.
+        if config.target.is_none() {
+            let incompatibilities = config.host_incompatibilities(&tunables);
+            if !incompatibilities.is_empty() {
+                return Err(ConfigError::Incompatible(incompatibilities).into());
+            }
+        }
+
         #[cfg(feature = "runtime")]
         if tunables.signals_based_traps {
             // Ensure that crate::runtime::vm's signal handlers are
@@ -304,34 +320,10 @@ impl Engine {
 
     #[cfg(any(feature = "cranelift", feature = "winch"))]
     fn _check_compatible_with_native_host(&self) -> Result<(), String> {
-        use target_lexicon::Triple;
-
         let compiler = self.compiler();
 
         let target = compiler.triple();
-        let host = Triple::host();
-        let target_matches_host = || {
-            // If the host target and target triple match, then it's valid
-            // to run results of compilation on this host.
-            if host == *target {
-                return true;
-            }
-
-            // If there's a mismatch and the target is a compatible pulley
-            // target, then that's also ok to run.
-            if cfg!(feature = "pulley")
-                && target.is_pulley()
-                && target.pointer_width() == host.pointer_width()
-                && target.endianness() == host.endianness()
-            {
-                return true;
-            }
-
-            // ... otherwise everything else is considered not a match.
-            false
-        };
-
-        if !target_matches_host() {
+        if !crate::config::target_runs_on_host(target) {
             return Err(format!(
                 "target '{target}' specified in the configuration does not match the host"
             ));
@@ -347,14 +339,9 @@ impl Engine {
 
         // Double-check that this configuration isn't requesting capabilities
         // that this build of Wasmtime doesn't support.
-        if !cfg!(has_native_signals) && self.tunables().signals_based_traps {
-            return Err("signals-based-traps disabled at compile time -- cannot be enabled".into());
-        }
-        if !cfg!(has_virtual_memory) && self.tunables().memory_init_cow {
-            return Err("virtual memory disabled at compile time -- cannot enable CoW".into());
-        }
-        if !cfg!(target_has_atomic = "64") && self.tunables().epoch_interruption {
-            return Err("epochs currently require 64-bit atomics".into());
+        let incompatibilities = self.config().host_incompatibilities(self.tunables());
+        if let Some(incompatibility) = incompatibilities.first() {
+            return Err(incompatibility.to_string());
         }
         Ok(())
     }
@@ -614,9 +601,31 @@ impl Engine {
     /// Cranelift backend to support them. For example at the time of this
     /// writing 32-bit x86 is not supported in Cranelift so the
     /// `i686-unknown-linux-gnu` target would by default return `true` here.
+    ///
+    /// Pulley can also be selected at runtime on hosts which do have a
+    /// Cranelift backend by configuring [`Config::target`] with a Pulley
+    /// target such as `pulley64`, when the `pulley` Cargo feature is enabled.
+    /// Engines with and without Pulley may be used side-by-side in the same
+    /// process, but modules can only be used with engines that execute the
+    /// same kind of code that they were compiled to.
+    ///
+    /// [`Config::target`]: crate::Config::target
     pub fn is_pulley(&self) -> bool {
         self.target().is_pulley()
     }
+
+    /// Returns the platform capabilities available to this [`Engine`].
+    ///
+    /// This reports which platform-dependent features this build of Wasmtime
+    /// supports on the current host, along with whether this engine was
+    /// configured to use them. Configurations which request something
+    /// unsupported here fail with an error when they're used.
+    pub fn platform_capabilities(&self) -> PlatformCapabilities {
+        PlatformCapabilities {
+            signals_based_traps: self.tunables().signals_based_traps,
+            ..PlatformCapabilities::host()
+        }
+    }
 }
 
 #[cfg(any(feature = "cranelift", feature = "winch"))]
@@ -751,8 +760,9 @@ impl Engine {
         &self.inner.epoch
     }
 
+    /// Returns the current epoch, see [`Engine::increment_epoch`].
     #[cfg(target_has_atomic = "64")]
-    pub(crate) fn current_epoch(&self) -> u64 {
+    pub fn current_epoch(&self) -> u64 {
         self.epoch_counter().load(Ordering::Relaxed)
     }
 
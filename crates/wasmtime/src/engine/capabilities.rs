@@ -0,0 +1,158 @@
+use crate::prelude::*;
+use core::fmt;
+
+/// A description of which platform-level features are available to an
+/// [`Engine`](crate::Engine).
+///
+/// Many of these are determined when Wasmtime itself is compiled, based on the
+/// target platform and enabled Cargo features, and they affect which
+/// [`Config`](crate::Config) options can be used. This is returned by
+/// [`Engine::platform_capabilities`](crate::Engine::platform_capabilities).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct PlatformCapabilities {
+    /// Whether this build of Wasmtime can catch faults in compiled code, for
+    /// example out-of-bounds memory accesses, with native signal handlers.
+    ///
+    /// This is required for [`Config::signals_based_traps`].
+    ///
+    /// [`Config::signals_based_traps`]: crate::Config::signals_based_traps
+    pub native_signals: bool,
+
+    /// Whether this build of Wasmtime can reserve, protect, and remap virtual
+    /// memory.
+    ///
+    /// This is required for guard pages, see [`Config::memory_guard_size`], as
+    /// well as for [`Config::memory_init_cow`].
+    ///
+    /// [`Config::memory_guard_size`]: crate::Config::memory_guard_size
+    /// [`Config::memory_init_cow`]: crate::Config::memory_init_cow
+    pub virtual_memory: bool,
+
+    /// Whether Cranelift can generate native code for the host architecture.
+    pub host_compiler_backend: bool,
+
+    /// Whether this build of Wasmtime supports shared memories, which the
+    /// WebAssembly threads proposal relies on.
+    ///
+    /// This is required for `Config::wasm_threads`, which is only available
+    /// when the `threads` Cargo feature is enabled.
+    pub threads: bool,
+
+    /// Whether Pulley, Wasmtime's interpreter, is used by default on this
+    /// host because native code can't be used.
+    pub pulley_by_default: bool,
+
+    /// Whether the engine is configured to rely on signals-based traps.
+    ///
+    /// Unlike the other fields this depends on the engine's configuration and
+    /// is only ever `true` when `native_signals` is also `true`.
+    pub signals_based_traps: bool,
+}
+
+impl PlatformCapabilities {
+    /// Returns the capabilities that this build of Wasmtime was compiled with.
+    pub(crate) const fn host() -> PlatformCapabilities {
+        PlatformCapabilities {
+            native_signals: cfg!(has_native_signals),
+            virtual_memory: cfg!(has_virtual_memory),
+            host_compiler_backend: cfg!(has_host_compiler_backend),
+            threads: cfg!(feature = "threads"),
+            pulley_by_default: cfg!(default_target_pulley),
+            signals_based_traps: false,
+        }
+    }
+}
+
+/// An error returned by [`Config::validate_for_host`].
+///
+/// [`Config::validate_for_host`]: crate::Config::validate_for_host
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// The configuration is invalid regardless of the host, for example
+    /// because it combines options which can't be used together. This is the
+    /// error that [`Engine::new`](crate::Engine::new) returns for it.
+    Invalid(anyhow::Error),
+
+    /// The configuration is valid, but can't be used to execute WebAssembly
+    /// on this host for every one of the listed reasons.
+    Incompatible(Vec<HostIncompatibility>),
+}
+
+/// A reason that a [`Config`](crate::Config) can't be used to execute
+/// WebAssembly on this host, see [`ConfigError::Incompatible`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum HostIncompatibility {
+    /// The configured [`Config::target`](crate::Config::target) doesn't
+    /// match the host, or is a native target on a host which can only
+    /// execute Pulley, see [`PlatformCapabilities::pulley_by_default`].
+    Target,
+
+    /// Signals-based traps are enabled but
+    /// [`PlatformCapabilities::native_signals`] is `false`.
+    SignalsBasedTraps,
+
+    /// Copy-on-write memory initialization is enabled but
+    /// [`PlatformCapabilities::virtual_memory`] is `false`.
+    MemoryInitCow,
+
+    /// Guard pages are configured but
+    /// [`PlatformCapabilities::virtual_memory`] is `false`.
+    MemoryGuardPages,
+
+    /// Async support is enabled but fibers, which async calls run on, can't
+    /// be created for this host architecture.
+    AsyncStacks,
+
+    /// Epoch-based interruption is enabled but this host lacks 64-bit
+    /// atomics.
+    EpochInterruption,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Invalid(e) => write!(f, "invalid configuration: {e}"),
+            ConfigError::Incompatible(incompatibilities) => {
+                write!(f, "configuration is not supported on this host:")?;
+                for incompatibility in incompatibilities {
+                    write!(f, "\n  * {incompatibility}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl core::error::Error for ConfigError {}
+
+impl fmt::Display for HostIncompatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HostIncompatibility::Target => {
+                "target specified in the configuration does not match the host"
+            }
+            HostIncompatibility::SignalsBasedTraps => {
+                "signals-based-traps disabled at compile time -- cannot be enabled \
+                 (native signals are unavailable on this platform, see \
+                 `Engine::platform_capabilities`)"
+            }
+            HostIncompatibility::MemoryInitCow => {
+                "virtual memory disabled at compile time -- cannot enable CoW \
+                 (see `Engine::platform_capabilities`)"
+            }
+            HostIncompatibility::MemoryGuardPages => {
+                "virtual memory disabled at compile time -- cannot use guard \
+                 pages, `Config::memory_guard_size` must be zero \
+                 (see `Engine::platform_capabilities`)"
+            }
+            HostIncompatibility::AsyncStacks => {
+                "async support requires fibers, which are unsupported on this \
+                 host architecture"
+            }
+            HostIncompatibility::EpochInterruption => "epochs currently require 64-bit atomics",
+        })
+    }
+}
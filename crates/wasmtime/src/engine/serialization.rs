@@ -21,6 +21,7 @@
 //! other random ELF files, as well as provide better error messages for
 //! using wasmtime artifacts across versions.
 
+use super::PlatformCapabilities;
 use crate::prelude::*;
 use crate::{Engine, ModuleVersionStrategy, Precompiled};
 use core::fmt;
@@ -31,7 +32,7 @@ use object::write::{Object, StandardSegment};
 use object::{FileFlags, Object as _, ObjectSection, read::elf::ElfFile64};
 use serde_derive::{Deserialize, Serialize};
 use wasmtime_environ::obj;
-use wasmtime_environ::{FlagValue, ObjectKind, Tunables};
+use wasmtime_environ::{FlagValue, ObjectKind, TripleExt, Tunables};
 
 const VERSION: u8 = 0;
 
@@ -44,6 +45,17 @@ const VERSION: u8 = 0;
 /// compiler options, etc. If a mismatch is found and the compilation metadata
 /// specified is incompatible then an error is returned.
 pub fn check_compatible(engine: &Engine, mmap: &[u8], expected: ObjectKind) -> Result<()> {
+    read_metadata(engine, mmap, expected)?.check_compatible(engine)
+}
+
+/// Reads the `Metadata` from the engine section of `mmap`, after checking
+/// that it's an artifact of the `expected` kind produced by a compatible
+/// version of Wasmtime.
+fn read_metadata<'a>(
+    engine: &Engine,
+    mmap: &'a [u8],
+    expected: ObjectKind,
+) -> Result<Metadata<'a>> {
     // Parse the input `mmap` as an ELF file and see if the header matches the
     // Wasmtime-generated header. This includes a Wasmtime-specific `os_abi` and
     // the `e_flags` field should indicate whether `expected` matches or not.
@@ -115,7 +127,7 @@ pub fn check_compatible(engine: &Engine, mmap: &[u8], expected: ObjectKind) -> R
         }
         ModuleVersionStrategy::None => { /* ignore the version info, accept all */ }
     }
-    postcard::from_bytes::<Metadata<'_>>(data)?.check_compatible(engine)
+    Ok(postcard::from_bytes::<Metadata<'_>>(data)?)
 }
 
 #[cfg(any(feature = "cranelift", feature = "winch"))]
@@ -181,6 +193,79 @@ pub struct Metadata<'a> {
     isa_flags: Vec<(&'a str, FlagValue<'a>)>,
     tunables: Tunables,
     features: u64,
+    capabilities: Capabilities,
+    required: RequiredCapabilities,
+    import_call_interpositions: Vec<String>,
+    gas_policy: Option<String>,
+}
+
+/// The compile-time capabilities of the build of Wasmtime which produced an
+/// artifact, a subset of [`PlatformCapabilities`].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+struct Capabilities {
+    host_compiler_backend: bool,
+    native_signals: bool,
+    virtual_memory: bool,
+    pulley_by_default: bool,
+}
+
+impl From<PlatformCapabilities> for Capabilities {
+    fn from(caps: PlatformCapabilities) -> Capabilities {
+        Capabilities {
+            host_compiler_backend: caps.host_compiler_backend,
+            native_signals: caps.native_signals,
+            virtual_memory: caps.virtual_memory,
+            pulley_by_default: caps.pulley_by_default,
+        }
+    }
+}
+
+/// The platform capabilities which an artifact needs in order to execute,
+/// derived from the settings it was compiled with.
+///
+/// This is a bitset rather than a structure so that a build of Wasmtime can
+/// still report that an artifact requires a capability it doesn't know of.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+struct RequiredCapabilities(u32);
+
+impl RequiredCapabilities {
+    /// The artifact contains native code rather than Pulley bytecode.
+    const NATIVE_CODE: u32 = 1 << 0;
+    /// The artifact relies on signal handlers to catch traps.
+    const SIGNALS_BASED_TRAPS: u32 = 1 << 1;
+    /// The artifact's linear memories rely on guard pages.
+    const GUARD_PAGES: u32 = 1 << 2;
+    /// The artifact was compiled with the threads proposal enabled, so it may
+    /// use shared memories.
+    const THREADS: u32 = 1 << 3;
+
+    const ALL: u32 =
+        Self::NATIVE_CODE | Self::SIGNALS_BASED_TRAPS | Self::GUARD_PAGES | Self::THREADS;
+
+    fn new(
+        is_pulley: bool,
+        tunables: &Tunables,
+        features: wasmparser::WasmFeatures,
+    ) -> RequiredCapabilities {
+        let mut bits = 0;
+        if !is_pulley {
+            bits |= Self::NATIVE_CODE;
+        }
+        if tunables.signals_based_traps {
+            bits |= Self::SIGNALS_BASED_TRAPS;
+        }
+        if tunables.memory_guard_size > 0 {
+            bits |= Self::GUARD_PAGES;
+        }
+        if features.contains(wasmparser::WasmFeatures::THREADS) {
+            bits |= Self::THREADS;
+        }
+        RequiredCapabilities(bits)
+    }
+
+    fn contains(self, capability: u32) -> bool {
+        self.0 & capability == capability
+    }
 }
 
 impl Metadata<'_> {
@@ -192,23 +277,115 @@ impl Metadata<'_> {
             isa_flags: engine.compiler().isa_flags(),
             tunables: engine.tunables().clone(),
             features: engine.features().bits(),
+            capabilities: PlatformCapabilities::host().into(),
+            required: RequiredCapabilities::new(
+                engine.is_pulley(),
+                engine.tunables(),
+                engine.features(),
+            ),
+            import_call_interpositions: engine.config().import_call_interposition_ids(),
+            gas_policy: engine.config().gas_policy_id(),
         }
     }
 
     fn check_compatible(mut self, engine: &Engine) -> Result<()> {
+        self.check_capabilities(engine.is_pulley(), engine.platform_capabilities())?;
         self.check_triple(engine)?;
         self.check_shared_flags(engine)?;
         self.check_isa_flags(engine)?;
         self.check_tunables(&engine.tunables())?;
         self.check_features(&engine.features())?;
+        self.check_import_call_interpositions(engine)?;
+        self.check_gas_policy(engine)?;
         Ok(())
     }
 
+    /// Checks that the module doesn't rely on capabilities which this build of
+    /// Wasmtime, described by `host`, lacks.
+    ///
+    /// The capabilities the module relies on were recorded when it was
+    /// compiled. All mismatches are reported at once since they typically
+    /// stem from the module being produced by a differently configured build
+    /// of Wasmtime.
+    fn check_capabilities(&self, engine_is_pulley: bool, host: PlatformCapabilities) -> Result<()> {
+        let module = self.capabilities;
+        let required = self.required;
+        let mut mismatches = Vec::new();
+
+        if required.contains(RequiredCapabilities::NATIVE_CODE) && !host.host_compiler_backend {
+            mismatches.push(
+                "the module contains native code but this build of Wasmtime has no \
+                 compiler backend for the host and can only execute Pulley bytecode",
+            );
+        }
+        if !required.contains(RequiredCapabilities::NATIVE_CODE)
+            && !engine_is_pulley
+            && module.pulley_by_default
+        {
+            mismatches.push(
+                "the module was produced by a build of Wasmtime which uses Pulley by \
+                 default but this build executes native code",
+            );
+        }
+        if required.contains(RequiredCapabilities::SIGNALS_BASED_TRAPS) && !host.native_signals {
+            mismatches.push(
+                "the module relies on signals-based traps but this build of Wasmtime \
+                 has no native signal support",
+            );
+        }
+        if required.contains(RequiredCapabilities::GUARD_PAGES) && !host.virtual_memory {
+            mismatches.push(
+                "the module relies on guard pages but this build of Wasmtime has no \
+                 virtual memory support",
+            );
+        }
+        if required.contains(RequiredCapabilities::THREADS) && !host.threads {
+            mismatches.push(
+                "the module was compiled with the threads proposal enabled but this \
+                 build of Wasmtime has no support for shared memories",
+            );
+        }
+        if required.0 & !RequiredCapabilities::ALL != 0 {
+            mismatches.push(
+                "the module relies on capabilities which this build of Wasmtime \
+                 doesn't know of",
+            );
+        }
+
+        if mismatches.is_empty() {
+            return Ok(());
+        }
+        let mut msg = String::from(
+            "Module is incompatible with the platform capabilities of this build of \
+             Wasmtime (see `Engine::platform_capabilities`):",
+        );
+        for mismatch in mismatches {
+            msg.push_str("\n  * ");
+            msg.push_str(mismatch);
+        }
+        bail!(msg)
+    }
+
     fn check_triple(&self, engine: &Engine) -> Result<()> {
         let engine_target = engine.target();
         let module_target =
             target_lexicon::Triple::from_str(&self.target).map_err(|e| anyhow!(e))?;
 
+        // Engines execute either Pulley bytecode or native code, and modules
+        // can't be moved between the two, so give a specific error for that.
+        match (module_target.is_pulley(), engine_target.is_pulley()) {
+            (true, false) => bail!(
+                "Module was compiled for Pulley ('{module_target}') but this engine \
+                 executes native code for '{engine_target}'; load it with an engine \
+                 configured with `Config::target(\"{module_target}\")`"
+            ),
+            (false, true) => bail!(
+                "Module was compiled to native code for '{module_target}' but this \
+                 engine executes Pulley bytecode for '{engine_target}'"
+            ),
+            _ => {}
+        }
+
         if module_target.architecture != engine_target.architecture {
             bail!(
                 "Module was compiled for architecture '{}'",
@@ -272,7 +449,46 @@ impl Metadata<'_> {
         );
     }
 
+    /// Checks that the module was compiled with the same rules configured by
+    /// `Config::redirect_import_calls` and `Config::wrap_import_calls` as
+    /// `engine`, since they change the compiled code.
+    fn check_import_call_interpositions(&self, engine: &Engine) -> Result<()> {
+        let host = engine.config().import_call_interposition_ids();
+        if self.import_call_interpositions == host {
+            return Ok(());
+        }
+        bail!(
+            "Module was compiled with the import call interpositions {:?} \
+             but the current engine is configured with {:?}",
+            self.import_call_interpositions,
+            host,
+        )
+    }
+
+    /// Checks that the module was compiled with a gas policy of the same
+    /// identity as the one `engine` is configured with, if any.
+    fn check_gas_policy(&self, engine: &Engine) -> Result<()> {
+        let host = engine.config().gas_policy_id();
+        match (&self.gas_policy, &host) {
+            (module, host) if module == host => Ok(()),
+            (Some(module), Some(host)) => bail!(
+                "Module was compiled with the gas policy `{module}` \
+                 but the current engine is configured with `{host}`"
+            ),
+            (Some(module), None) => bail!(
+                "Module was compiled with the gas policy `{module}` \
+                 but the current engine has no gas policy"
+            ),
+            (None, _) => bail!(
+                "Module was compiled without a gas policy \
+                 but the current engine has one"
+            ),
+        }
+    }
+
     fn check_tunables(&mut self, other: &Tunables) -> Result<()> {
+        Self::check_bounds_checking(&self.tunables, other)?;
+
         let Tunables {
             collector,
             memory_reservation,
@@ -292,6 +508,17 @@ impl Metadata<'_> {
             inlining_intra_module,
             inlining_small_callee_size,
             inlining_sum_size_threshold,
+            v128_zero,
+            shadow_stack,
+            interrupt_check_placement,
+            nan_canonicalization,
+            call_indirect_inline_caches,
+            nontrapping_float_to_int_override,
+            lazy_local_init,
+            bulk_memory_inline_threshold,
+            memory_access_instrumentation,
+            instrumented_memories,
+            unreachable_lowering,
 
             // This doesn't affect compilation, it's just a runtime setting.
             memory_reservation_for_growth: _,
@@ -372,6 +599,72 @@ impl Metadata<'_> {
             "function inlining sum-size threshold",
         )?;
         Self::check_intra_module_inlining(inlining_intra_module, other.inlining_intra_module)?;
+        if v128_zero != other.v128_zero {
+            bail!(
+                "module was compiled with `{v128_zero}` v128 zero materialization \
+                 however the host is configured with `{}`",
+                other.v128_zero,
+            );
+        }
+        if shadow_stack != other.shadow_stack {
+            bail!(
+                "module was compiled with a shadow stack of {shadow_stack:?} \
+                 however the host is configured with {:?}",
+                other.shadow_stack,
+            );
+        }
+        Self::check_interrupt_check_placement(
+            interrupt_check_placement,
+            other.interrupt_check_placement,
+        )?;
+        if nan_canonicalization != other.nan_canonicalization {
+            bail!(
+                "module was compiled with NaN canonicalization of {nan_canonicalization:?} \
+                 however the host is configured with {:?}",
+                other.nan_canonicalization,
+            );
+        }
+        Self::check_bool(
+            call_indirect_inline_caches,
+            other.call_indirect_inline_caches,
+            "call_indirect inline caches",
+        )?;
+        Self::check_bool(
+            nontrapping_float_to_int_override,
+            other.nontrapping_float_to_int_override,
+            "nontrapping float-to-int override",
+        )?;
+        Self::check_bool(
+            lazy_local_init,
+            other.lazy_local_init,
+            "lazy local initialization",
+        )?;
+        Self::check_int(
+            bulk_memory_inline_threshold,
+            other.bulk_memory_inline_threshold,
+            "bulk memory inline threshold",
+        )?;
+        if memory_access_instrumentation != other.memory_access_instrumentation {
+            bail!(
+                "module was compiled with memory access instrumentation of \
+                 {memory_access_instrumentation:?} however the host is configured with {:?}",
+                other.memory_access_instrumentation,
+            );
+        }
+        if instrumented_memories != other.instrumented_memories {
+            bail!(
+                "module was compiled with instrumented memories of \
+                 {instrumented_memories:?} however the host is configured with {:?}",
+                other.instrumented_memories,
+            );
+        }
+        if unreachable_lowering != other.unreachable_lowering {
+            bail!(
+                "module was compiled with unreachable lowering of \
+                 {unreachable_lowering:?} however the host is configured with {:?}",
+                other.unreachable_lowering,
+            );
+        }
 
         Ok(())
     }
@@ -459,6 +752,29 @@ impl Metadata<'_> {
         }
     }
 
+    /// Gives a descriptive error when a module's linear memories were
+    /// compiled with a different bounds-checking strategy than the host uses,
+    /// which would otherwise show up as a mismatch of one of the individual
+    /// settings checked in `check_tunables`.
+    fn check_bounds_checking(module: &Tunables, host: &Tunables) -> Result<()> {
+        let guard_pages = |t: &Tunables| t.signals_based_traps && t.memory_guard_size > 0;
+        match (guard_pages(module), guard_pages(host)) {
+            (true, false) => bail!(
+                "Module was compiled to rely on guard pages and signals-based traps \
+                 for memory bounds checks, but the host performs explicit bounds \
+                 checks; recompile the module with `Config::signals_based_traps(false)` \
+                 and `Config::memory_guard_size(0)`"
+            ),
+            (false, true) => bail!(
+                "Module was compiled with explicit memory bounds checks, but the host \
+                 relies on guard pages and signals-based traps; load the module with \
+                 an engine configured with `Config::signals_based_traps(false)` and \
+                 `Config::memory_guard_size(0)`"
+            ),
+            _ => Ok(()),
+        }
+    }
+
     fn check_intra_module_inlining(
         module: wasmtime_environ::IntraModuleInlining,
         host: wasmtime_environ::IntraModuleInlining,
@@ -480,6 +796,19 @@ impl Metadata<'_> {
 
         bail!("module was compiled {module} however the host is configured {host}")
     }
+
+    fn check_interrupt_check_placement(
+        module: wasmtime_environ::InterruptCheckPlacement,
+        host: wasmtime_environ::InterruptCheckPlacement,
+    ) -> Result<()> {
+        if module == host {
+            return Ok(());
+        }
+        bail!(
+            "module was compiled with `{module}` interrupt check placement \
+             however the host is configured with `{host}`"
+        )
+    }
 }
 
 #[cfg(test)]
@@ -509,6 +838,193 @@ mod test {
         Ok(())
     }
 
+    /// Capabilities of a hypothetical build of Wasmtime with native code and
+    /// full OS support.
+    fn full_capabilities() -> PlatformCapabilities {
+        PlatformCapabilities {
+            native_signals: true,
+            virtual_memory: true,
+            host_compiler_backend: true,
+            threads: true,
+            pulley_by_default: false,
+            signals_based_traps: false,
+        }
+    }
+
+    #[test]
+    fn test_capabilities_match() -> Result<()> {
+        let engine = Engine::default();
+        let metadata = Metadata::new(&engine);
+        metadata.check_capabilities(engine.is_pulley(), PlatformCapabilities::host())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_capabilities_native_code_without_backend() -> Result<()> {
+        let engine = Engine::default();
+        let mut metadata = Metadata::new(&engine);
+        metadata.required = RequiredCapabilities(RequiredCapabilities::NATIVE_CODE);
+
+        let host = PlatformCapabilities {
+            host_compiler_backend: false,
+            pulley_by_default: true,
+            ..full_capabilities()
+        };
+        let err = metadata.check_capabilities(true, host).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Module is incompatible with the platform capabilities of this build of \
+             Wasmtime (see `Engine::platform_capabilities`):\n  \
+             * the module contains native code but this build of Wasmtime has no \
+             compiler backend for the host and can only execute Pulley bytecode",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_capabilities_pulley_by_default() -> Result<()> {
+        let engine = Engine::default();
+        let mut metadata = Metadata::new(&engine);
+        metadata.required = RequiredCapabilities(0);
+        metadata.capabilities.pulley_by_default = true;
+
+        let err = metadata
+            .check_capabilities(false, full_capabilities())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("produced by a build of Wasmtime which uses Pulley by default"),
+            "bad error: {err}"
+        );
+
+        // An engine explicitly configured for Pulley can load the module.
+        metadata.check_capabilities(true, full_capabilities())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_capabilities_all_mismatches_reported() -> Result<()> {
+        let engine = Engine::default();
+        let mut metadata = Metadata::new(&engine);
+        metadata.required = RequiredCapabilities(
+            RequiredCapabilities::NATIVE_CODE
+                | RequiredCapabilities::SIGNALS_BASED_TRAPS
+                | RequiredCapabilities::GUARD_PAGES
+                | RequiredCapabilities::THREADS
+                | 1 << 31,
+        );
+
+        let host = PlatformCapabilities {
+            native_signals: false,
+            virtual_memory: false,
+            threads: false,
+            ..full_capabilities()
+        };
+        let err = metadata
+            .check_capabilities(false, host)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("* the module relies on signals-based traps but this build of Wasmtime has no native signal support"),
+            "bad error: {err}"
+        );
+        assert!(
+            err.contains("* the module relies on guard pages but this build of Wasmtime has no virtual memory support"),
+            "bad error: {err}"
+        );
+        assert!(
+            err.contains("* the module was compiled with the threads proposal enabled but this build of Wasmtime has no support for shared memories"),
+            "bad error: {err}"
+        );
+        assert!(
+            err.contains(
+                "* the module relies on capabilities which this build of Wasmtime doesn't know of"
+            ),
+            "bad error: {err}"
+        );
+        assert!(!err.contains("compiler backend"), "bad error: {err}");
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_required_capabilities_round_trip() -> Result<()> {
+        let engine = Engine::default();
+        let bytes = engine.precompile_module(b"(module (memory 1))")?;
+        let metadata = read_metadata(&engine, &bytes, ObjectKind::Module)?;
+        let required = metadata.required;
+        assert_eq!(
+            required,
+            RequiredCapabilities::new(engine.is_pulley(), engine.tunables(), engine.features())
+        );
+
+        // The engine which compiled the module has every capability it
+        // requires.
+        metadata.check_capabilities(engine.is_pulley(), engine.platform_capabilities())?;
+        unsafe { Module::deserialize(&engine, &bytes)? };
+
+        // A host lacking every capability reports exactly the ones which were
+        // recorded as required.
+        let host = PlatformCapabilities {
+            native_signals: false,
+            virtual_memory: false,
+            host_compiler_backend: false,
+            threads: false,
+            ..full_capabilities()
+        };
+        let err = match metadata.check_capabilities(true, host) {
+            Ok(()) => String::new(),
+            Err(e) => e.to_string(),
+        };
+        for (capability, description) in [
+            (RequiredCapabilities::NATIVE_CODE, "contains native code"),
+            (
+                RequiredCapabilities::SIGNALS_BASED_TRAPS,
+                "signals-based traps",
+            ),
+            (RequiredCapabilities::GUARD_PAGES, "guard pages"),
+            (RequiredCapabilities::THREADS, "threads proposal"),
+        ] {
+            assert_eq!(
+                err.contains(description),
+                required.contains(capability),
+                "bad error for {description:?}: {err}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    #[cfg(feature = "component-model")]
+    fn test_required_capabilities_of_explicit_bounds_checks() -> Result<()> {
+        let mut config = Config::new();
+        config.signals_based_traps(false).memory_guard_size(0);
+        let engine = Engine::new(&config)?;
+        let bytes = engine.precompile_component(b"(component)")?;
+        let metadata = read_metadata(&engine, &bytes, ObjectKind::Component)?;
+        assert!(
+            !metadata
+                .required
+                .contains(RequiredCapabilities::SIGNALS_BASED_TRAPS)
+        );
+        assert!(
+            !metadata
+                .required
+                .contains(RequiredCapabilities::GUARD_PAGES)
+        );
+
+        // Such a module can be loaded by builds without native signals or
+        // virtual memory.
+        let host = PlatformCapabilities {
+            native_signals: false,
+            virtual_memory: false,
+            ..full_capabilities()
+        };
+        metadata.check_capabilities(engine.is_pulley(), host)?;
+        Ok(())
+    }
+
     // Note that this test runs on a platform that is known to use Cranelift
     #[test]
     #[cfg(all(target_arch = "x86_64", not(miri)))]
@@ -786,6 +1302,37 @@ Caused by:
         assert_ne!(opt_none_hash, opt_speed_hash)
     }
 
+    #[test]
+    fn test_import_call_interpositions_mismatch() -> Result<()> {
+        let plain = Engine::default();
+        let mut config = Config::new();
+        config.redirect_import_calls("env", "f", "env", "g");
+        let redirecting = Engine::new(&config)?;
+
+        Metadata::new(&redirecting).check_compatible(&redirecting)?;
+        let err = Metadata::new(&plain)
+            .check_compatible(&redirecting)
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("Module was compiled with the import call interpositions []"),
+            "{err}"
+        );
+        assert!(
+            Metadata::new(&redirecting)
+                .check_compatible(&plain)
+                .is_err()
+        );
+
+        let hash = |engine: &Engine| {
+            let mut hasher = DefaultHasher::new();
+            engine.precompile_compatibility_hash().hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_ne!(hash(&plain), hash(&redirecting));
+        Ok(())
+    }
+
     #[test]
     fn precompile_compatibility_key_accounts_for_module_version_strategy() -> Result<()> {
         fn hash_for_config(cfg: &Config) -> u64 {
@@ -60,6 +60,8 @@ pub(crate) mod vm;
 
 #[cfg(feature = "component-model")]
 pub mod component;
+#[cfg(all(feature = "custom-native-signals", has_native_signals))]
+pub mod unwinding;
 
 cfg_if::cfg_if! {
     if #[cfg(miri)] {
@@ -89,7 +91,8 @@ pub use resources::*;
 #[cfg(all(feature = "async", feature = "call-hook"))]
 pub use store::CallHookHandler;
 pub use store::{
-    AsContext, AsContextMut, CallHook, Store, StoreContext, StoreContextMut, UpdateDeadline,
+    AsContext, AsContextMut, CallHook, MemoryAccess, Store, StoreContext, StoreContextMut,
+    TrapContext, UpdateDeadline,
 };
 pub use trap::*;
 pub use types::*;
@@ -101,6 +104,9 @@ pub(crate) use uninhabited::*;
 #[cfg(feature = "pooling-allocator")]
 pub use vm::PoolConcurrencyLimitError;
 
+#[cfg(has_custom_virtual_memory)]
+pub use vm::{MemoryProtection, VirtualMemoryProvider, set_virtual_memory_provider};
+
 #[cfg(feature = "profiling")]
 mod profiling;
 #[cfg(feature = "profiling")]
@@ -16,6 +16,8 @@ pub enum ResourceTableError {
     /// Resource cannot be deleted because child resources exist in the table. Consult wit docs for
     /// the particular resource to see which methods may return child resources.
     HasChildren,
+    /// Resource cannot be inserted at an index which is already in use.
+    Occupied,
 }
 
 impl fmt::Display for ResourceTableError {
@@ -25,6 +27,7 @@ impl fmt::Display for ResourceTableError {
             Self::NotPresent => write!(f, "resource not present"),
             Self::WrongType => write!(f, "resource is of another type"),
             Self::HasChildren => write!(f, "resource has children"),
+            Self::Occupied => write!(f, "resource index is already in use"),
         }
     }
 }
@@ -114,6 +117,13 @@ impl ResourceTable {
         }
     }
 
+    /// Reserves capacity for at least `additional` more entries to be
+    /// inserted without reallocating, for callers about to insert many
+    /// entries at once.
+    pub fn reserve(&mut self, additional: usize) {
+        self.entries.reserve(additional);
+    }
+
     /// Inserts a new value `T` into this table, returning a corresponding
     /// `Resource<T>` which can be used to refer to it after it was inserted.
     pub fn push<T>(&mut self, entry: T) -> Result<Resource<T>, ResourceTableError>
@@ -202,10 +212,9 @@ impl ResourceTable {
     /// lifetime of parent referent even after parent resource is destroyed,
     /// possibility for deadlocks.
     ///
-    /// Parent-child relationships may not be modified once created. There
-    /// is no way to observe these relationships through the [`ResourceTable`]
-    /// methods except for erroring on deletion, or the [`std::fmt::Debug`]
-    /// impl.
+    /// Parent-child relationships may not be modified once created. They
+    /// can be observed through [`ResourceTable::iter`], erroring on deletion,
+    /// or the [`std::fmt::Debug`] impl.
     pub fn push_child<T, U>(
         &mut self,
         entry: T,
@@ -222,6 +231,65 @@ impl ResourceTable {
         Ok(Resource::new_own(child))
     }
 
+    /// Inserts a new value `T` at the given `index` of this table, optionally
+    /// as a child of `parent`.
+    ///
+    /// This is intended for restoring a table's contents, for example from a
+    /// snapshot, such that previously handed out indices remain valid. The
+    /// table grows as needed to contain `index`, and the `parent`, if any,
+    /// must already be present. Returns [`ResourceTableError::Occupied`] if
+    /// `index` is already in use.
+    pub fn insert_at<T>(
+        &mut self,
+        index: u32,
+        entry: T,
+        parent: Option<u32>,
+    ) -> Result<Resource<T>, ResourceTableError>
+    where
+        T: Send + 'static,
+    {
+        if let Some(parent) = parent {
+            self.occupied(parent)?;
+        }
+        let ix = index as usize;
+        while self.entries.len() <= ix {
+            let free = self.entries.len();
+            self.entries.push(Entry::Free {
+                next: self.free_head,
+            });
+            self.free_head = Some(free);
+        }
+        let next = match &self.entries[ix] {
+            Entry::Free { next } => *next,
+            Entry::Occupied { .. } => return Err(ResourceTableError::Occupied),
+        };
+
+        // Unlink `ix` from the free list.
+        if self.free_head == Some(ix) {
+            self.free_head = next;
+        } else {
+            let mut cur = self.free_head;
+            while let Some(c) = cur {
+                match &mut self.entries[c] {
+                    Entry::Free { next: n } if *n == Some(ix) => {
+                        *n = next;
+                        break;
+                    }
+                    Entry::Free { next: n } => cur = *n,
+                    Entry::Occupied { .. } => unreachable!(),
+                }
+            }
+        }
+
+        self.entries[ix] = Entry::Occupied {
+            entry: TableEntry::new(Box::new(entry), parent),
+        };
+        if let Some(parent) = parent {
+            self.occupied_mut(parent)?.add_child(index);
+        }
+        Ok(Resource::new_own(index))
+    }
+
     /// Get an immutable reference to a resource of a given type at a given
     /// index.
     ///
@@ -267,6 +335,15 @@ impl ResourceTable {
         }
     }
 
+    /// Same as `delete`, but returns the resource at `index` without
+    /// checking its type.
+    ///
+    /// Like `delete`, this fails with [`ResourceTableError::HasChildren`] if
+    /// the resource still has children.
+    pub fn delete_any(&mut self, index: u32) -> Result<Box<dyn Any + Send>, ResourceTableError> {
+        Ok(self.delete_entry(index)?.entry)
+    }
+
     fn delete_entry(&mut self, key: u32) -> Result<TableEntry, ResourceTableError> {
         if !self.occupied(key)?.children.is_empty() {
             return Err(ResourceTableError::HasChildren);
@@ -300,6 +377,17 @@ impl ResourceTable {
         })
     }
 
+    /// Iterate over all resources in this table in index order.
+    ///
+    /// Each item is the index of a resource, the index of its parent if it
+    /// has one, and the resource itself.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, Option<u32>, &(dyn Any + Send))> {
+        self.entries.iter().enumerate().filter_map(|(ix, e)| {
+            let e = e.occupied()?;
+            Some((ix as u32, e.parent, e.entry.as_ref()))
+        })
+    }
+
     /// Iterate over all children belonging to the provided parent
     pub fn iter_children<T>(
         &self,
@@ -351,3 +439,34 @@ pub fn test_free_list() {
     let x = table.push(()).unwrap();
     assert_eq!(x.rep(), 2);
 }
+
+#[test]
+pub fn test_insert_at() {
+    let mut table = ResourceTable::new();
+
+    // Inserting past the end grows the table and leaves the gap free.
+    let x = table.insert_at(3, (), None).unwrap();
+    assert_eq!(x.rep(), 3);
+    let y = table.insert_at(1, (), Some(3)).unwrap();
+    assert_eq!(y.rep(), 1);
+    assert!(matches!(
+        table.insert_at(3, (), None),
+        Err(ResourceTableError::Occupied)
+    ));
+    assert!(matches!(
+        table.insert_at(4, (), Some(5)),
+        Err(ResourceTableError::NotPresent)
+    ));
+
+    let entries: Vec<_> = table.iter().map(|(ix, parent, _)| (ix, parent)).collect();
+    assert_eq!(entries, [(1, Some(3)), (3, None)]);
+    assert!(matches!(
+        table.delete(x),
+        Err(ResourceTableError::HasChildren)
+    ));
+
+    // The remaining free indices are still handed out by `push`.
+    let mut pushed: Vec<_> = (0..3).map(|_| table.push(()).unwrap().rep()).collect();
+    pushed.sort();
+    assert_eq!(pushed, [0, 2, 4]);
+}
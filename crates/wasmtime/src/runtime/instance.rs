@@ -268,6 +268,14 @@ impl Instance {
         imports: Imports<'_>,
     ) -> Result<(Instance, Option<FuncIndex>)> {
         if !Engine::same(store.engine(), module.engine()) {
+            if store.engine().is_pulley() != module.engine().is_pulley() {
+                bail!(
+                    "cannot instantiate a module compiled for '{}' in a store whose \
+                     engine executes code for '{}'",
+                    module.engine().target(),
+                    store.engine().target(),
+                );
+            }
             bail!("cross-`Engine` instantiation is not currently supported");
         }
         store.bump_resource_counts(module)?;
@@ -237,6 +237,9 @@ pub struct StoreInner<T: 'static> {
     #[cfg(target_has_atomic = "64")]
     epoch_deadline_behavior:
         Option<Box<dyn FnMut(StoreContextMut<T>) -> Result<UpdateDeadline> + Send + Sync>>,
+    memory_access_callback:
+        Option<Box<dyn FnMut(StoreContextMut<T>, MemoryAccess) -> Result<()> + Send + Sync>>,
+    trap_context_callback: Option<Box<dyn FnMut(StoreContextMut<T>, TrapContext) + Send + Sync>>,
     // for comments about `ManuallyDrop`, see `Store::into_data`
     data: ManuallyDrop<T>,
 }
@@ -287,6 +290,37 @@ pub enum UpdateDeadline {
     ),
 }
 
+/// A linear memory access reported to the callback configured with
+/// [`Store::memory_access_callback`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MemoryAccess {
+    /// The index of the accessed memory within the index space of the module
+    /// making the access.
+    pub memory: u32,
+    /// The effective address of the access, which is the sum of its address
+    /// operand and its static offset.
+    pub address: u64,
+    /// The size of the access, in bytes.
+    pub size: u32,
+    /// Whether the access writes to memory. Atomic read-modify-write and
+    /// compare-and-exchange operators are reported as stores.
+    pub is_store: bool,
+}
+
+/// A trap about to be raised by WebAssembly, reported to the callback
+/// configured with [`Store::trap_context_callback`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TrapContext {
+    /// The index of the trapping function within the index space of its
+    /// module.
+    pub func_index: u32,
+    /// The offset of the trapping operator within its module's binary, in the
+    /// same space as [`FrameInfo::module_offset`](crate::FrameInfo::module_offset).
+    pub wasm_offset: u32,
+    /// The trap about to be raised.
+    pub trap: Trap,
+}
+
 // Forward methods on `StoreOpaque` to also being on `StoreInner<T>`
 impl<T> Deref for StoreInner<T> {
     type Target = StoreOpaque;
@@ -435,6 +469,24 @@ impl Executor {
             Executor::Interpreter(Interpreter::new(engine))
         }
     }
+
+    #[cfg(feature = "pulley-metering")]
+    fn interpreter(&self) -> Option<&Interpreter> {
+        match self {
+            Executor::Interpreter(i) => Some(i),
+            #[cfg(has_host_compiler_backend)]
+            Executor::Native => None,
+        }
+    }
+
+    #[cfg(feature = "pulley-metering")]
+    fn interpreter_mut(&mut self) -> Option<&mut Interpreter> {
+        match self {
+            Executor::Interpreter(i) => Some(i),
+            #[cfg(has_host_compiler_backend)]
+            Executor::Native => None,
+        }
+    }
 }
 
 /// A borrowed reference to `Executor` above.
@@ -608,6 +660,8 @@ impl<T> Store<T> {
             call_hook: None,
             #[cfg(target_has_atomic = "64")]
             epoch_deadline_behavior: None,
+            memory_access_callback: None,
+            trap_context_callback: None,
             data: ManuallyDrop::new(data),
         });
 
@@ -861,6 +915,50 @@ impl<T> Store<T> {
         self.inner.set_fuel(fuel)
     }
 
+    /// Returns the number of Pulley instructions which WebAssembly has
+    /// executed in this [`Store`].
+    ///
+    /// The count starts at zero when the store is created and is never reset.
+    /// See [`Config::pulley_instruction_metering`](crate::Config::pulley_instruction_metering)
+    /// for more information.
+    ///
+    /// This method is only available when the `pulley-metering` Cargo feature
+    /// is enabled.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if instruction metering is not
+    /// enabled via
+    /// [`Config::pulley_instruction_metering`](crate::Config::pulley_instruction_metering).
+    #[cfg(feature = "pulley-metering")]
+    pub fn pulley_instructions_executed(&self) -> Result<u64> {
+        self.inner.pulley_instructions_executed()
+    }
+
+    /// Configures the number of Pulley instructions, as counted by
+    /// [`Store::pulley_instructions_executed`], after which WebAssembly
+    /// executing in this [`Store`] traps with
+    /// [`Trap::InstructionLimitExceeded`], or removes the limit with `None`.
+    ///
+    /// By default a [`Store`] has no limit. Note that the limit is compared
+    /// against the total count of the store, so to allow `n` more
+    /// instructions pass the current count plus `n`. Once the limit has been
+    /// reached every further call into WebAssembly traps until the limit is
+    /// raised.
+    ///
+    /// This method is only available when the `pulley-metering` Cargo feature
+    /// is enabled.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if instruction metering is not
+    /// enabled via
+    /// [`Config::pulley_instruction_metering`](crate::Config::pulley_instruction_metering).
+    #[cfg(feature = "pulley-metering")]
+    pub fn set_pulley_instruction_limit(&mut self, limit: Option<u64>) -> Result<()> {
+        self.inner.set_pulley_instruction_limit(limit)
+    }
+
     /// Configures a [`Store`] to yield execution of async WebAssembly code
     /// periodically.
     ///
@@ -989,6 +1087,53 @@ impl<T> Store<T> {
     ) {
         self.inner.epoch_deadline_callback(Box::new(callback));
     }
+
+    /// Configures a callback invoked before each linear memory access made by
+    /// WebAssembly in this store.
+    ///
+    /// The callback is only invoked for modules compiled with
+    /// [`MemoryAccessInstrumentation::Callback`](crate::MemoryAccessInstrumentation::Callback),
+    /// and is given a description of the access before it's performed. If
+    /// the callback returns an error then the access isn't performed and
+    /// execution terminates with a trap.
+    ///
+    /// See [`Config::memory_access_instrumentation`](crate::Config::memory_access_instrumentation)
+    /// for more information.
+    pub fn memory_access_callback(
+        &mut self,
+        callback: impl FnMut(StoreContextMut<T>, MemoryAccess) -> Result<()> + Send + Sync + 'static,
+    ) {
+        self.inner.memory_access_callback = Some(Box::new(callback));
+    }
+
+    /// Returns the number of linear memory accesses made by WebAssembly in
+    /// this store.
+    ///
+    /// Only accesses made by modules compiled with
+    /// [`MemoryAccessInstrumentation::Count`](crate::MemoryAccessInstrumentation::Count)
+    /// are counted. See
+    /// [`Config::memory_access_instrumentation`](crate::Config::memory_access_instrumentation)
+    /// for more information.
+    pub fn memory_accesses(&self) -> u64 {
+        self.inner.memory_accesses()
+    }
+
+    /// Configures a callback invoked before WebAssembly in this store raises
+    /// a trap with `unreachable`.
+    ///
+    /// The callback is only invoked for modules compiled with an
+    /// [`UnreachableLowering`](crate::UnreachableLowering) which notifies the
+    /// host, and is given the function and offset of the trapping operator.
+    /// Once the callback returns the trap is raised as usual.
+    ///
+    /// See [`Config::unreachable_lowering`](crate::Config::unreachable_lowering)
+    /// for more information.
+    pub fn trap_context_callback(
+        &mut self,
+        callback: impl FnMut(StoreContextMut<T>, TrapContext) + Send + Sync + 'static,
+    ) {
+        self.inner.trap_context_callback = Some(Box::new(callback));
+    }
 }
 
 impl<'a, T> StoreContext<'a, T> {
@@ -1060,6 +1205,22 @@ impl<'a, T> StoreContextMut<'a, T> {
         self.0.set_fuel(fuel)
     }
 
+    /// Returns the number of Pulley instructions executed in this store.
+    ///
+    /// For more information see [`Store::pulley_instructions_executed`]
+    #[cfg(feature = "pulley-metering")]
+    pub fn pulley_instructions_executed(&self) -> Result<u64> {
+        self.0.pulley_instructions_executed()
+    }
+
+    /// Configures the Pulley instruction limit of this store.
+    ///
+    /// For more information see [`Store::set_pulley_instruction_limit`]
+    #[cfg(feature = "pulley-metering")]
+    pub fn set_pulley_instruction_limit(&mut self, limit: Option<u64>) -> Result<()> {
+        self.0.set_pulley_instruction_limit(limit)
+    }
+
     /// Configures this `Store` to periodically yield while executing futures.
     ///
     /// For more information see [`Store::fuel_async_yield_interval`]
@@ -1726,6 +1887,10 @@ impl StoreOpaque {
         self.gc_host_alloc_types.insert(ty);
     }
 
+    pub fn memory_accesses(&self) -> u64 {
+        unsafe { *self.vm_store_context.memory_accesses.get() }
+    }
+
     pub fn get_fuel(&self) -> Result<u64> {
         anyhow::ensure!(
             self.engine().tunables().consume_fuel,
@@ -1744,6 +1909,32 @@ impl StoreOpaque {
         )
     }
 
+    #[cfg(feature = "pulley-metering")]
+    pub fn pulley_instructions_executed(&self) -> Result<u64> {
+        anyhow::ensure!(
+            self.engine().config().pulley_instruction_metering,
+            "pulley instruction metering is not configured in this store"
+        );
+        let interpreter = self
+            .executor
+            .interpreter()
+            .expect("metering requires a pulley target");
+        Ok(interpreter.instructions_executed())
+    }
+
+    #[cfg(feature = "pulley-metering")]
+    pub fn set_pulley_instruction_limit(&mut self, limit: Option<u64>) -> Result<()> {
+        anyhow::ensure!(
+            self.engine().config().pulley_instruction_metering,
+            "pulley instruction metering is not configured in this store"
+        );
+        self.executor
+            .interpreter_mut()
+            .expect("metering requires a pulley target")
+            .set_instruction_limit(limit);
+        Ok(())
+    }
+
     pub fn set_fuel(&mut self, fuel: u64) -> Result<()> {
         anyhow::ensure!(
             self.engine().tunables().consume_fuel,
@@ -2022,6 +2213,13 @@ at https://bytecodealliance.org/security.
     #[cfg(feature = "async")]
     pub(crate) fn swap_executor(&mut self, executor: &mut Executor) {
         mem::swap(&mut self.executor, executor);
+
+        // Instructions are counted per store, not per fiber, so the executor
+        // taking over carries on from the count of the previous one.
+        #[cfg(feature = "pulley-metering")]
+        if let (Some(new), Some(old)) = (self.executor.interpreter_mut(), executor.interpreter()) {
+            new.copy_metering_from(old);
+        }
     }
 
     pub(crate) fn unwinder(&self) -> &'static dyn Unwind {
@@ -2246,6 +2444,28 @@ unsafe impl<T> vm::VMStore for StoreInner<T> {
         Ok(())
     }
 
+    fn memory_access(&mut self, access: MemoryAccess) -> Result<()> {
+        // Temporarily take the callback to avoid mutably borrowing multiple
+        // times.
+        let mut callback = self.memory_access_callback.take();
+        let result = match &mut callback {
+            Some(callback) => callback((&mut *self).as_context_mut(), access),
+            None => Ok(()),
+        };
+        self.memory_access_callback = callback;
+        result
+    }
+
+    fn trap_with_context(&mut self, context: TrapContext) {
+        // Temporarily take the callback to avoid mutably borrowing multiple
+        // times.
+        let mut callback = self.trap_context_callback.take();
+        if let Some(callback) = &mut callback {
+            callback((&mut *self).as_context_mut(), context);
+        }
+        self.trap_context_callback = callback;
+    }
+
     #[cfg(target_has_atomic = "64")]
     fn new_epoch(&mut self) -> Result<u64, anyhow::Error> {
         // Temporarily take the configured behavior to avoid mutably borrowing
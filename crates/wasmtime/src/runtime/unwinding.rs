@@ -0,0 +1,24 @@
+//! Delegating native faults to Wasmtime from embedder-owned fault handlers.
+//!
+//! With the `custom-native-signals` feature Wasmtime asks the platform to
+//! install a fault handler through `wasmtime_init_traps`. Platforms where the
+//! embedder already owns fault handling, such as unikernels, can instead
+//! forward faults from their own handler:
+//!
+//! * [`resolve_fault`] tests whether a fault is a WebAssembly trap, based on
+//!   the faulting instruction and Wasmtime's registry of loaded code.
+//! * [`ResolvedTrap::resume`] completes such a trap by unwinding back to the
+//!   entry of the WebAssembly call.
+//! * [`register_fault_resume`] customizes how that final transfer of control
+//!   happens.
+//!
+//! ```ignore
+//! unsafe fn on_fault(pc: usize, fp: usize, addr: Option<usize>) {
+//!     if let Some(trap) = wasmtime::unwinding::resolve_fault(pc, fp, addr) {
+//!         trap.resume();
+//!     }
+//!     // ... not a wasm trap, handle as usual ...
+//! }
+//! ```
+
+pub use crate::runtime::vm::{FaultResume, ResolvedTrap, register_fault_resume, resolve_fault};
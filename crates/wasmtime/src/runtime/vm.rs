@@ -51,6 +51,7 @@ use wasmtime_environ::{
 #[cfg(feature = "gc")]
 use wasmtime_environ::ModuleInternedTypeIndex;
 
+mod asan;
 #[cfg(feature = "component-model")]
 pub mod component;
 mod const_expr;
@@ -109,6 +110,11 @@ pub use crate::runtime::vm::memory::{
 };
 pub use crate::runtime::vm::mmap_vec::MmapVec;
 pub use crate::runtime::vm::provenance::*;
+#[cfg(has_custom_virtual_memory)]
+pub use crate::runtime::vm::provider::{
+    MemoryProtection, VirtualMemoryProvider, large_page_size as virtual_memory_large_page_size,
+    set_virtual_memory_provider, supports_guard_pages as virtual_memory_supports_guard_pages,
+};
 pub use crate::runtime::vm::stack_switching::*;
 pub use crate::runtime::vm::store_box::*;
 #[cfg(feature = "std")]
@@ -140,6 +146,11 @@ mod cow;
 mod cow_disabled;
 #[cfg(has_virtual_memory)]
 mod mmap;
+// Only the custom platform consults a registered provider, but the module is
+// built wherever there's virtual memory so its tests run on every host.
+#[cfg(has_virtual_memory)]
+#[cfg_attr(not(has_custom_virtual_memory), allow(dead_code))]
+mod provider;
 
 #[cfg(feature = "async")]
 mod async_yield;
@@ -223,6 +234,15 @@ pub unsafe trait VMStore: 'static {
     /// continue as normal.
     fn out_of_gas(&mut self) -> Result<(), Error>;
 
+    /// Callback invoked before a linear memory access made by WebAssembly
+    /// compiled with `MemoryAccessInstrumentation::Callback`. If an error is
+    /// returned that's raised as a trap.
+    fn memory_access(&mut self, access: crate::MemoryAccess) -> Result<(), Error>;
+
+    /// Callback invoked before WebAssembly raises a trap which is lowered to
+    /// notify the host, see `UnreachableLowering`.
+    fn trap_with_context(&mut self, context: crate::TrapContext);
+
     /// Callback invoked whenever an instance observes a new epoch
     /// number. Cannot fail; cooperative epoch-based yielding is
     /// completely semantically transparent. Returns the new deadline.
@@ -0,0 +1,47 @@
+//! Support for making AddressSanitizer aware of memory that Wasmtime recycles
+//! itself.
+//!
+//! Pooling allocator slots, for example, are reused without going through the
+//! system allocator, so ASan can't tell when host code accesses guest memory
+//! belonging to an instance which has already been deallocated. When the
+//! `asan` cfg is enabled the functions here poison and unpoison such regions
+//! so these accesses are reported. Otherwise they do nothing.
+//!
+//! Note that compiled WebAssembly isn't instrumented, so poisoning a region
+//! only affects accesses from host code.
+
+#[cfg(asan)]
+unsafe extern "C" {
+    fn __asan_poison_memory_region(addr: *const u8, size: usize);
+    fn __asan_unpoison_memory_region(addr: *const u8, size: usize);
+    #[cfg(test)]
+    fn __asan_address_is_poisoned(addr: *const u8) -> core::ffi::c_int;
+}
+
+/// Marks `len` bytes starting at `ptr` as inaccessible to instrumented code.
+#[inline]
+pub fn poison(ptr: *const u8, len: usize) {
+    #[cfg(asan)]
+    unsafe {
+        __asan_poison_memory_region(ptr, len)
+    }
+    #[cfg(not(asan))]
+    let _ = (ptr, len);
+}
+
+/// Marks `len` bytes starting at `ptr` as accessible to instrumented code.
+#[inline]
+pub fn unpoison(ptr: *const u8, len: usize) {
+    #[cfg(asan)]
+    unsafe {
+        __asan_unpoison_memory_region(ptr, len)
+    }
+    #[cfg(not(asan))]
+    let _ = (ptr, len);
+}
+
+/// Returns whether an access to `ptr` by instrumented code would be reported.
+#[cfg(all(test, asan))]
+pub fn is_poisoned(ptr: *const u8) -> bool {
+    unsafe { __asan_address_is_poisoned(ptr) != 0 }
+}
@@ -44,6 +44,7 @@ pub unsafe extern "C" fn set_vmctx_memory(vmctx_ptr: *mut VMContext) {
 /// `wasmtime_*` symbols defined in `helpers.c` to actually get exported. That
 /// means they need to be referenced for the linker to include them which is
 /// what this function does with trickery in C.
+#[cfg(not(has_rust_helpers))]
 pub fn init() {
     unsafe extern "C" {
         #[wasmtime_versioned_export_macros::versioned_link]
@@ -54,3 +55,28 @@ pub fn init() {
         wasmtime_debug_builtins_init();
     }
 }
+
+// Without `helpers.c` the public interface is defined here instead. Note that
+// `build.rs` only enables this on Unix, where Rust's exported symbols are
+// visible to debuggers.
+
+#[cfg(has_rust_helpers)]
+#[versioned_export]
+pub unsafe extern "C" fn wasmtime_resolve_vmctx_memory_ptr(p: *const u32) -> *const u8 {
+    unsafe { resolve_vmctx_memory_ptr(p) }
+}
+
+#[cfg(has_rust_helpers)]
+#[versioned_export]
+pub unsafe extern "C" fn wasmtime_set_vmctx_memory(vmctx_ptr: *mut VMContext) {
+    unsafe { set_vmctx_memory(vmctx_ptr) }
+}
+
+/// Forces the `wasmtime_*` symbols above to be retained by the linker.
+#[cfg(has_rust_helpers)]
+pub fn init() {
+    let f: unsafe extern "C" fn(*const u32) -> *const u8 = wasmtime_resolve_vmctx_memory_ptr;
+    core::hint::black_box(f);
+    let f: unsafe extern "C" fn(*mut VMContext) = wasmtime_set_vmctx_memory;
+    core::hint::black_box(f);
+}
@@ -1085,6 +1085,7 @@ mod tests {
             num_defined_globals: 0,
             num_defined_tags: 0,
             num_escaped_funcs: 0,
+            num_call_indirect_caches: 0,
         });
 
         assert_eq!(
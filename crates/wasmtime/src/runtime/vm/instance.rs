@@ -1470,6 +1470,21 @@ impl Instance {
         // to that element. In other words, there is no state needed to track
         // the lazy-init, so we don't need to initialize any state now.
 
+        // Initialize every `call_indirect` inline cache to null, which no
+        // table entry that passes a signature check can ever be equal to.
+        //
+        // SAFETY: the vmctx is safe to initialize during this function and
+        // the caches are plain pointers for which all-zeros is a valid value.
+        unsafe {
+            ptr::write_bytes(
+                self.vmctx_plus_offset_raw::<u8>(offsets.vmctx_call_indirect_caches_begin())
+                    .as_ptr(),
+                0,
+                usize::try_from(offsets.num_call_indirect_caches).unwrap()
+                    * usize::from(offsets.pointer_size()),
+            );
+        }
+
         // Initialize the defined tables
         //
         // SAFETY: it's safe to initialize these tables during initialization
@@ -57,7 +57,7 @@ use super::{
 use crate::prelude::*;
 use crate::runtime::vm::{
     CompiledModuleId, InstanceAllocationRequest, InstanceLimits, Memory, MemoryBase,
-    MemoryImageSlot, Mmap, MmapOffset, PoolingInstanceAllocatorConfig, mmap::AlignedLength,
+    MemoryImageSlot, Mmap, MmapOffset, PoolingInstanceAllocatorConfig, asan, mmap::AlignedLength,
 };
 use crate::{
     MpkEnabled,
@@ -383,8 +383,17 @@ impl MemoryPool {
             // mmap that would leave an open space for someone
             // else to come in and map something.
             let initial_size = usize::try_from(initial_size).unwrap();
+            let slot_ptr = base.as_mut_ptr();
+            asan::unpoison(slot_ptr, base_capacity.byte_count());
             slot.instantiate(initial_size, image, ty, tunables)?;
 
+            // Keep the part of the slot beyond the initial size of the memory
+            // poisoned until it grows, see `StaticMemory::set_byte_size`.
+            asan::poison(
+                slot_ptr.wrapping_add(initial_size),
+                base_capacity.byte_count() - initial_size,
+            );
+
             Memory::new_static(
                 ty,
                 tunables,
@@ -396,6 +405,10 @@ impl MemoryPool {
         })() {
             Ok(memory) => Ok((allocation_index, memory)),
             Err(e) => {
+                asan::poison(
+                    self.get_base(allocation_index).as_mut_ptr(),
+                    self.layout.max_memory_bytes.byte_count(),
+                );
                 self.stripes[stripe_index]
                     .allocator
                     .free(SlotId(striped_allocation_index.0));
@@ -421,6 +434,12 @@ impl MemoryPool {
     ) {
         self.return_memory_image_slot(allocation_index, image);
 
+        // Any further access to this slot from the host is a use-after-free.
+        asan::poison(
+            self.get_base(allocation_index).as_mut_ptr(),
+            self.layout.max_memory_bytes.byte_count(),
+        );
+
         let (stripe_index, striped_allocation_index) =
             StripedAllocationIndex::from_unstriped_slot_index(allocation_index, self.stripes.len());
         self.stripes[stripe_index]
@@ -524,6 +543,10 @@ impl Drop for MemoryPool {
                 slot.no_clear_on_drop();
             }
         }
+
+        // The address space is about to be unmapped and may be reused by
+        // anything, so don't leave it poisoned.
+        asan::unpoison(self.mapping.as_ptr(), self.mapping.len());
     }
 }
 
@@ -830,6 +853,42 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[cfg(asan)]
+    fn deallocated_slot_is_poisoned() -> Result<()> {
+        use crate::{
+            Config, Engine, Instance, InstanceAllocationStrategy, Module, PoolingAllocationConfig,
+            Store,
+        };
+
+        let mut pool = PoolingAllocationConfig::new();
+        pool.total_memories(1)
+            .max_memory_size(2 * WASM_PAGE_SIZE as usize);
+        let mut config = Config::new();
+        config.allocation_strategy(InstanceAllocationStrategy::Pooling(pool));
+        let engine = Engine::new(&config)?;
+        let module = Module::new(&engine, r#"(module (memory (export "m") 1 2))"#)?;
+
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let memory = instance.get_memory(&mut store, "m").unwrap();
+        let base = memory.data_ptr(&store);
+        let second_page = base.wrapping_add(WASM_PAGE_SIZE as usize);
+
+        // Only the accessible part of the slot may be touched by the host.
+        assert!(!asan::is_poisoned(base));
+        assert!(asan::is_poisoned(second_page));
+        memory.grow(&mut store, 1)?;
+        assert!(!asan::is_poisoned(second_page));
+
+        // Once the instance is gone a host read through `base` would be
+        // reported by ASan as a use-after-free.
+        drop(store);
+        assert!(asan::is_poisoned(base));
+        assert!(asan::is_poisoned(second_page));
+        Ok(())
+    }
+
     #[test]
     #[cfg_attr(miri, ignore)]
     fn test_pooling_allocator_striping() {
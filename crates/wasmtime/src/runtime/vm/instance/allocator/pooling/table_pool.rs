@@ -4,7 +4,7 @@ use super::{
 };
 use crate::runtime::vm::sys::vm::commit_pages;
 use crate::runtime::vm::{
-    InstanceAllocationRequest, Mmap, PoolingInstanceAllocatorConfig, SendSyncPtr, Table,
+    InstanceAllocationRequest, Mmap, PoolingInstanceAllocatorConfig, SendSyncPtr, Table, asan,
     mmap::AlignedLength,
 };
 use crate::{prelude::*, vm::HostAlignedByteCount};
@@ -150,6 +150,7 @@ impl TablePool {
             unsafe {
                 commit_pages(base, data_size)?;
             }
+            asan::unpoison(base, data_size);
 
             let ptr =
                 NonNull::new(std::ptr::slice_from_raw_parts_mut(base.cast(), data_size)).unwrap();
@@ -164,6 +165,7 @@ impl TablePool {
         })() {
             Ok(table) => Ok((allocation_index, table)),
             Err(e) => {
+                asan::poison(self.get(allocation_index), self.table_size.byte_count());
                 self.index_allocator.free(SlotId(allocation_index.0));
                 Err(e)
             }
@@ -183,6 +185,10 @@ impl TablePool {
     pub unsafe fn deallocate(&self, allocation_index: TableAllocationIndex, table: Table) {
         assert!(table.is_static());
         drop(table);
+
+        // Any further access to this slot from the host is a use-after-free.
+        asan::poison(self.get(allocation_index), self.table_size.byte_count());
+
         self.index_allocator.free(SlotId(allocation_index.0));
     }
 
@@ -231,6 +237,14 @@ impl TablePool {
     }
 }
 
+impl Drop for TablePool {
+    fn drop(&mut self) {
+        // The address space is about to be unmapped and may be reused by
+        // anything, so don't leave it poisoned.
+        asan::unpoison(self.mapping.as_ptr(), self.mapping.len());
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
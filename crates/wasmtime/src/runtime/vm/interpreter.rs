@@ -52,8 +52,15 @@ pub struct Interpreter {
 impl Interpreter {
     /// Creates a new interpreter ready to interpret code.
     pub fn new(engine: &Engine) -> Interpreter {
+        #[cfg_attr(
+            not(feature = "pulley-metering"),
+            expect(unused_mut, reason = "only configured with metering")
+        )]
+        let mut vm = Vm::with_stack(engine.config().max_wasm_stack);
+        #[cfg(feature = "pulley-metering")]
+        vm.set_instruction_metering(engine.config().pulley_instruction_metering);
         let ret = Interpreter {
-            pulley: StoreBox::new(Vm::with_stack(engine.config().max_wasm_stack)),
+            pulley: StoreBox::new(vm),
         };
         engine.profiler().register_interpreter(&ret);
         ret
@@ -76,6 +83,31 @@ impl Interpreter {
     pub fn unwinder(&self) -> &'static dyn Unwind {
         &UnwindPulley
     }
+
+    /// Returns the number of instructions executed by this interpreter, see
+    /// `Config::pulley_instruction_metering`.
+    #[cfg(feature = "pulley-metering")]
+    pub fn instructions_executed(&self) -> u64 {
+        self.pulley().instructions_executed()
+    }
+
+    /// Sets the instruction limit of this interpreter.
+    #[cfg(feature = "pulley-metering")]
+    pub fn set_instruction_limit(&mut self, limit: Option<u64>) {
+        // SAFETY: `&mut self` guarantees exclusive access to the `Vm`.
+        unsafe { self.pulley.get().as_mut().set_instruction_limit(limit) }
+    }
+
+    /// Carries the instruction count and limit of `other` over to this
+    /// interpreter, which is taking over execution from it.
+    #[cfg(feature = "pulley-metering")]
+    pub fn copy_metering_from(&mut self, other: &Interpreter) {
+        let other = other.pulley();
+        // SAFETY: `&mut self` guarantees exclusive access to the `Vm`.
+        let vm = unsafe { self.pulley.get().as_mut() };
+        vm.set_instructions_executed(other.instructions_executed());
+        vm.set_instruction_limit(other.instruction_limit());
+    }
 }
 
 /// Wrapper around `&mut pulley_interpreter::Vm` to enable compiling this to a
@@ -472,6 +504,7 @@ fn trap(vm: &mut Vm, pc: NonNull<u8>, kind: Option<TrapKind>, setjmp: Setjmp) {
                     TrapKind::MemoryOutOfBounds => Trap::MemoryOutOfBounds,
                     TrapKind::DisabledOpcode => Trap::DisabledOpcode,
                     TrapKind::StackOverflow => Trap::StackOverflow,
+                    TrapKind::InstructionLimitExceeded => Trap::InstructionLimitExceeded,
                 };
                 s.set_jit_trap(regs, None, trap);
             }
@@ -1128,6 +1128,38 @@ fn out_of_gas(store: &mut dyn VMStore, _instance: Pin<&mut Instance>) -> Result<
     store.out_of_gas()
 }
 
+// Hook for instrumented linear memory accesses.
+fn memory_access(
+    store: &mut dyn VMStore,
+    _instance: Pin<&mut Instance>,
+    memory: u32,
+    address: u64,
+    size: u32,
+    is_store: u32,
+) -> Result<()> {
+    store.memory_access(crate::MemoryAccess {
+        memory,
+        address,
+        size,
+        is_store: is_store != 0,
+    })
+}
+
+// Hook for traps lowered to notify the host before they're raised.
+fn trap_with_context(
+    store: &mut dyn VMStore,
+    _instance: Pin<&mut Instance>,
+    func_index: u32,
+    wasm_offset: u32,
+    code: u8,
+) {
+    store.trap_with_context(crate::TrapContext {
+        func_index,
+        wasm_offset,
+        trap: Trap::from_u8(code).unwrap(),
+    })
+}
+
 // Hook for when an instance observes that the epoch has changed.
 #[cfg(target_has_atomic = "64")]
 fn new_epoch(store: &mut dyn VMStore, _instance: Pin<&mut Instance>) -> Result<NextEpoch> {
@@ -3,6 +3,7 @@
 
 use crate::prelude::*;
 use crate::runtime::vm::MemoryBase;
+use crate::runtime::vm::asan;
 use crate::runtime::vm::memory::RuntimeLinearMemory;
 
 /// A "static" memory where the lifetime of the backing memory is managed
@@ -64,11 +65,17 @@ impl RuntimeLinearMemory for StaticMemory {
         assert!(new_byte_size <= self.capacity);
 
         // Update our accounting of the available size.
-        self.size = new_byte_size;
+        self.set_byte_size(new_byte_size);
         Ok(())
     }
 
     fn set_byte_size(&mut self, len: usize) {
+        // The pooling allocator poisons the unused tail of the slot, so make
+        // the newly accessible bytes visible to ASan.
+        if len > self.size {
+            let base = self.base.as_non_null().as_ptr();
+            asan::unpoison(base.wrapping_add(self.size), len - self.size);
+        }
         self.size = len;
     }
 
@@ -0,0 +1,436 @@
+//! Rust-level hooks for supplying virtual memory on custom platforms.
+//!
+//! By default the custom platform implements virtual memory in terms of the
+//! `wasmtime_mmap_*` functions in the `capi` module. Embedders which would
+//! rather describe their platform's memory primitives in Rust can instead
+//! register a [`VirtualMemoryProvider`] with [`set_virtual_memory_provider`],
+//! and all of Wasmtime's memory, table, and pooling allocations will be routed
+//! through it.
+
+use crate::prelude::*;
+use crate::sync::OnceLock;
+use core::ptr::NonNull;
+
+/// Access permissions which a [`VirtualMemoryProvider`] may be asked to apply
+/// to a region of memory.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MemoryProtection {
+    /// The region must not be accessible at all.
+    None,
+    /// The region is readable but not writable or executable.
+    Read,
+    /// The region is readable and writable.
+    ReadWrite,
+    /// The region is readable and executable.
+    ReadExecute,
+}
+
+/// A source of virtual memory for Wasmtime on platforms which aren't natively
+/// supported.
+///
+/// All pointers and lengths passed to the methods of this trait are aligned
+/// to [`VirtualMemoryProvider::page_size`], and all regions passed to methods
+/// other than `reserve` lie within a region previously returned by
+/// [`VirtualMemoryProvider::reserve`].
+///
+/// Providers are registered with [`set_virtual_memory_provider`].
+pub trait VirtualMemoryProvider: Send + Sync {
+    /// Returns the granularity, in bytes, of all regions managed by this
+    /// provider.
+    ///
+    /// This must be a power of two and must not change over the lifetime of
+    /// the program.
+    fn page_size(&self) -> usize;
+
+    /// Reserves `size` bytes of address space.
+    ///
+    /// The returned region should be inaccessible if
+    /// [`VirtualMemoryProvider::supports_guard_pages`] returns `true`.
+    /// Otherwise it may be accessible, but its contents must be zero.
+    fn reserve(&self, size: usize) -> Result<NonNull<u8>>;
+
+    /// Releases a region previously returned from
+    /// [`VirtualMemoryProvider::reserve`] in its entirety.
+    ///
+    /// # Safety
+    ///
+    /// The region must not be used after this call.
+    unsafe fn release(&self, ptr: NonNull<u8>, size: usize) -> Result<()>;
+
+    /// Makes the given region readable and writable, backing it with memory
+    /// if necessary.
+    ///
+    /// Committing a region which is already committed must leave its contents
+    /// intact.
+    ///
+    /// # Safety
+    ///
+    /// The region must lie within a reservation of this provider.
+    unsafe fn commit(&self, ptr: NonNull<u8>, size: usize) -> Result<()>;
+
+    /// Changes the access permissions of the given committed region.
+    ///
+    /// Providers which don't support guard pages may treat
+    /// [`MemoryProtection::None`] as a no-op.
+    ///
+    /// # Safety
+    ///
+    /// The region must lie within a reservation of this provider and the
+    /// new permissions must not invalidate any live Rust references.
+    unsafe fn protect(&self, ptr: NonNull<u8>, size: usize, prot: MemoryProtection) -> Result<()>;
+
+    /// Releases the memory backing the given region and makes it inaccessible.
+    ///
+    /// When the region is next committed its contents must be zero. This is
+    /// only called if [`VirtualMemoryProvider::supports_decommit`] returns
+    /// `true`.
+    ///
+    /// # Safety
+    ///
+    /// The region must lie within a reservation of this provider and must not
+    /// be in use.
+    unsafe fn decommit(&self, ptr: NonNull<u8>, size: usize) -> Result<()>;
+
+    /// Returns whether [`VirtualMemoryProvider::decommit`] is implemented.
+    ///
+    /// When this returns `false` Wasmtime resets memory for reuse, for example
+    /// in the pooling allocator, by writing zeros to it instead, which keeps
+    /// it resident.
+    fn supports_decommit(&self) -> bool;
+
+    /// Returns whether inaccessible regions fault when accessed.
+    ///
+    /// When this returns `false` Wasmtime defaults to not relying on guard
+    /// pages or signals-based traps and instead emits explicit bounds checks.
+    fn supports_guard_pages(&self) -> bool;
+
+    /// Returns the size, in bytes, of the large pages this provider can back
+    /// reservations with, or `None` if large pages aren't available.
+    ///
+    /// When large pages are available Wasmtime rounds its default linear
+    /// memory reservations up to a multiple of this size so they can be
+    /// backed entirely by large pages. This must be a power of two which is
+    /// no smaller than [`VirtualMemoryProvider::page_size`].
+    fn large_page_size(&self) -> Option<usize> {
+        None
+    }
+}
+
+static PROVIDER: OnceLock<&'static dyn VirtualMemoryProvider> = OnceLock::new();
+
+/// Registers `provider` as the source of all virtual memory used by Wasmtime.
+///
+/// This must be called before any `Engine` is created, and may only be
+/// called once. An error is returned if a provider is already registered.
+pub fn set_virtual_memory_provider(provider: &'static dyn VirtualMemoryProvider) -> Result<()> {
+    assert!(provider.page_size().is_power_of_two());
+    if let Some(size) = provider.large_page_size() {
+        assert!(size.is_power_of_two() && size >= provider.page_size());
+    }
+    let mut registered = false;
+    PROVIDER.get_or_init(|| {
+        registered = true;
+        provider
+    });
+    if !registered {
+        bail!("a virtual memory provider has already been registered");
+    }
+    Ok(())
+}
+
+/// Returns the registered provider, if any.
+#[inline]
+pub fn provider() -> Option<&'static dyn VirtualMemoryProvider> {
+    PROVIDER.get().copied()
+}
+
+/// Returns whether guard pages are available, either because no provider is
+/// registered or because the registered provider supports them.
+pub fn supports_guard_pages() -> bool {
+    provider().map_or(true, |p| p.supports_guard_pages())
+}
+
+/// Returns the large page size of the registered provider, if any.
+pub fn large_page_size() -> Option<usize> {
+    provider().and_then(|p| p.large_page_size())
+}
+
+/// Resets `len` bytes at `ptr` to zero while keeping them readable and
+/// writable, decommitting them if the provider supports that.
+pub(super) unsafe fn reset_to_zero(
+    provider: &dyn VirtualMemoryProvider,
+    ptr: NonNull<u8>,
+    len: usize,
+) -> Result<()> {
+    unsafe {
+        if provider.supports_decommit() {
+            provider.decommit(ptr, len)?;
+            provider.commit(ptr, len)
+        } else {
+            provider.commit(ptr, len)?;
+            ptr.as_ptr().write_bytes(0, len);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::UnsafeCell;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    const ARENA_PAGE_SIZE: usize = 0x1000;
+    const ARENA_LARGE_PAGE_SIZE: usize = 2 << 20;
+    const ARENA_SIZE: usize = 16 << 20;
+
+    #[repr(C, align(4096))]
+    struct Storage(UnsafeCell<[u8; ARENA_SIZE]>);
+
+    /// A provider which hands out bump-allocated chunks of a static arena
+    /// and supports neither guard pages nor decommit, similar to what's
+    /// available on an RTOS without an MMU.
+    struct ArenaProvider {
+        storage: Storage,
+        next: AtomicUsize,
+    }
+
+    unsafe impl Sync for ArenaProvider {}
+
+    static ARENA: ArenaProvider = ArenaProvider {
+        storage: Storage(UnsafeCell::new([0; ARENA_SIZE])),
+        next: AtomicUsize::new(0),
+    };
+
+    impl ArenaProvider {
+        fn contains(&self, ptr: *const u8) -> bool {
+            let base = self.storage.0.get().cast::<u8>() as usize;
+            (base..base + ARENA_SIZE).contains(&(ptr as usize))
+        }
+    }
+
+    impl VirtualMemoryProvider for ArenaProvider {
+        fn page_size(&self) -> usize {
+            ARENA_PAGE_SIZE
+        }
+
+        fn reserve(&self, size: usize) -> Result<NonNull<u8>> {
+            let Ok(offset) =
+                self.next
+                    .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |offset| {
+                        offset.checked_add(size).filter(|end| *end <= ARENA_SIZE)
+                    })
+            else {
+                bail!("static arena exhausted");
+            };
+            let base = self.storage.0.get().cast::<u8>();
+            Ok(NonNull::new(unsafe { base.add(offset) }).unwrap())
+        }
+
+        unsafe fn release(&self, _ptr: NonNull<u8>, _size: usize) -> Result<()> {
+            Ok(())
+        }
+
+        unsafe fn commit(&self, _ptr: NonNull<u8>, _size: usize) -> Result<()> {
+            Ok(())
+        }
+
+        unsafe fn protect(
+            &self,
+            _ptr: NonNull<u8>,
+            _size: usize,
+            _prot: MemoryProtection,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        unsafe fn decommit(&self, _ptr: NonNull<u8>, _size: usize) -> Result<()> {
+            unreachable!()
+        }
+
+        fn supports_decommit(&self) -> bool {
+            false
+        }
+
+        fn supports_guard_pages(&self) -> bool {
+            false
+        }
+
+        fn large_page_size(&self) -> Option<usize> {
+            Some(ARENA_LARGE_PAGE_SIZE)
+        }
+    }
+
+    /// A provider which records decommits, to check that they're used for
+    /// resets when supported.
+    struct DecommitProvider {
+        decommits: AtomicUsize,
+    }
+
+    impl VirtualMemoryProvider for DecommitProvider {
+        fn page_size(&self) -> usize {
+            ARENA_PAGE_SIZE
+        }
+
+        fn reserve(&self, _size: usize) -> Result<NonNull<u8>> {
+            unreachable!()
+        }
+
+        unsafe fn release(&self, _ptr: NonNull<u8>, _size: usize) -> Result<()> {
+            unreachable!()
+        }
+
+        unsafe fn commit(&self, _ptr: NonNull<u8>, _size: usize) -> Result<()> {
+            Ok(())
+        }
+
+        unsafe fn protect(
+            &self,
+            _ptr: NonNull<u8>,
+            _size: usize,
+            _prot: MemoryProtection,
+        ) -> Result<()> {
+            unreachable!()
+        }
+
+        unsafe fn decommit(&self, ptr: NonNull<u8>, size: usize) -> Result<()> {
+            unsafe { ptr.as_ptr().write_bytes(0, size) };
+            self.decommits.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+
+        fn supports_decommit(&self) -> bool {
+            true
+        }
+
+        fn supports_guard_pages(&self) -> bool {
+            true
+        }
+    }
+
+    fn register_arena() {
+        let _ = set_virtual_memory_provider(&ARENA);
+    }
+
+    #[test]
+    fn arena_is_registered() {
+        register_arena();
+        assert!(set_virtual_memory_provider(&ARENA).is_err());
+        assert!(!supports_guard_pages());
+        assert_eq!(large_page_size(), Some(ARENA_LARGE_PAGE_SIZE));
+    }
+
+    #[test]
+    fn arena_reservations_are_disjoint() -> Result<()> {
+        let a = ARENA.reserve(2 * ARENA_PAGE_SIZE)?;
+        let b = ARENA.reserve(ARENA_PAGE_SIZE)?;
+        assert!(ARENA.contains(a.as_ptr()) && ARENA.contains(b.as_ptr()));
+        assert!(a.as_ptr() as usize + 2 * ARENA_PAGE_SIZE <= b.as_ptr() as usize);
+        assert!(ARENA.reserve(ARENA_SIZE).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn reset_without_decommit_zeroes() -> Result<()> {
+        let ptr = ARENA.reserve(2 * ARENA_PAGE_SIZE)?;
+        unsafe {
+            ARENA.commit(ptr, 2 * ARENA_PAGE_SIZE)?;
+            ptr.as_ptr().write_bytes(0xff, 2 * ARENA_PAGE_SIZE);
+            reset_to_zero(&ARENA, ptr, 2 * ARENA_PAGE_SIZE)?;
+            let contents = core::slice::from_raw_parts(ptr.as_ptr(), 2 * ARENA_PAGE_SIZE);
+            assert!(contents.iter().all(|b| *b == 0));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn reset_with_decommit_decommits() -> Result<()> {
+        let provider = DecommitProvider {
+            decommits: AtomicUsize::new(0),
+        };
+        let ptr = ARENA.reserve(ARENA_PAGE_SIZE)?;
+        unsafe {
+            ptr.as_ptr().write_bytes(0x42, ARENA_PAGE_SIZE);
+            reset_to_zero(&provider, ptr, ARENA_PAGE_SIZE)?;
+            assert_eq!(*ptr.as_ptr(), 0);
+        }
+        assert_eq!(provider.decommits.load(Ordering::Relaxed), 1);
+        Ok(())
+    }
+
+    /// Tests of the custom platform's allocations, which are only routed
+    /// through the registered provider on that platform.
+    #[cfg(has_custom_virtual_memory)]
+    mod custom {
+        use super::*;
+        use crate::runtime::vm::HostAlignedByteCount;
+        use crate::runtime::vm::mmap::Mmap;
+        use crate::runtime::vm::sys::vm;
+
+        fn page_count(n: usize) -> HostAlignedByteCount {
+            HostAlignedByteCount::new(n * ARENA_PAGE_SIZE).unwrap()
+        }
+
+        #[test]
+        fn page_size_from_arena() {
+            register_arena();
+            assert_eq!(vm::get_page_size(), ARENA_PAGE_SIZE);
+        }
+
+        #[test]
+        fn mmap_from_arena() -> Result<()> {
+            register_arena();
+            let mmap = Mmap::with_at_least(2 * ARENA_PAGE_SIZE)?;
+            assert!(ARENA.contains(mmap.as_ptr()));
+            unsafe {
+                let ptr = mmap.as_mut_ptr();
+                ptr.write_bytes(0xaa, mmap.len());
+                assert_eq!(*ptr.add(mmap.len() - 1), 0xaa);
+            }
+            Ok(())
+        }
+
+        #[test]
+        fn reserved_memory_made_accessible() -> Result<()> {
+            register_arena();
+            let mmap = Mmap::accessible_reserved(page_count(1), page_count(4))?;
+            unsafe {
+                mmap.make_accessible(page_count(1), page_count(2))?;
+                let ptr = mmap.as_mut_ptr().add(ARENA_PAGE_SIZE);
+                ptr.write_bytes(0x11, 2 * ARENA_PAGE_SIZE);
+            }
+            Ok(())
+        }
+
+        #[test]
+        #[cfg(feature = "pooling-allocator")]
+        fn decommit_without_provider_support_zeroes() -> Result<()> {
+            register_arena();
+            let mmap = Mmap::with_at_least(2 * ARENA_PAGE_SIZE)?;
+            unsafe {
+                let ptr = mmap.as_mut_ptr();
+                ptr.write_bytes(0xff, mmap.len());
+                vm::decommit_pages(ptr, mmap.len())?;
+                let contents = core::slice::from_raw_parts(ptr, mmap.len());
+                assert!(contents.iter().all(|b| *b == 0));
+                vm::commit_pages(ptr, mmap.len())?;
+            }
+            Ok(())
+        }
+
+        #[test]
+        fn erased_mapping_is_zero() -> Result<()> {
+            register_arena();
+            let mmap = Mmap::with_at_least(ARENA_PAGE_SIZE)?;
+            unsafe {
+                let ptr = mmap.as_mut_ptr();
+                ptr.write_bytes(0x42, mmap.len());
+                vm::erase_existing_mapping(ptr, mmap.len())?;
+                vm::expose_existing_mapping(ptr, mmap.len())?;
+                let contents = core::slice::from_raw_parts(ptr, mmap.len());
+                assert!(contents.iter().all(|b| *b == 0));
+            }
+            Ok(())
+        }
+    }
+}
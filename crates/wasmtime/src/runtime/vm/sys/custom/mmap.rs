@@ -1,5 +1,6 @@
 use super::cvt;
 use crate::prelude::*;
+use crate::runtime::vm::provider::{self, MemoryProtection};
 use crate::runtime::vm::sys::{capi, vm::MemoryImageSource};
 use crate::runtime::vm::{HostAlignedByteCount, SendSyncPtr};
 use core::ops::Range;
@@ -25,6 +26,14 @@ impl Mmap {
     }
 
     pub fn new(size: HostAlignedByteCount) -> Result<Self> {
+        if let Some(provider) = provider::provider() {
+            let ptr = provider.reserve(size.byte_count())?;
+            let mmap = Mmap::from_provider(ptr, size);
+            unsafe {
+                provider.commit(ptr, size.byte_count())?;
+            }
+            return Ok(mmap);
+        }
         let mut ptr = ptr::null_mut();
         cvt(unsafe {
             capi::wasmtime_mmap_new(
@@ -39,6 +48,10 @@ impl Mmap {
     }
 
     pub fn reserve(size: HostAlignedByteCount) -> Result<Self> {
+        if let Some(provider) = provider::provider() {
+            let ptr = provider.reserve(size.byte_count())?;
+            return Ok(Mmap::from_provider(ptr, size));
+        }
         let mut ptr = ptr::null_mut();
         cvt(unsafe { capi::wasmtime_mmap_new(size.byte_count(), 0, &mut ptr) })?;
         let memory = ptr::slice_from_raw_parts_mut(ptr.cast(), size.byte_count());
@@ -46,6 +59,13 @@ impl Mmap {
         Ok(Mmap { memory })
     }
 
+    fn from_provider(ptr: NonNull<u8>, size: HostAlignedByteCount) -> Self {
+        let memory = NonNull::slice_from_raw_parts(ptr, size.byte_count());
+        Mmap {
+            memory: SendSyncPtr::new(memory),
+        }
+    }
+
     #[cfg(feature = "std")]
     pub fn from_file(_file: &File) -> Result<Self> {
         anyhow::bail!("not supported on this platform");
@@ -57,6 +77,12 @@ impl Mmap {
         len: HostAlignedByteCount,
     ) -> Result<()> {
         let ptr = self.memory.as_ptr();
+        if let Some(provider) = provider::provider() {
+            return unsafe {
+                let base = self.as_send_sync_ptr().as_non_null();
+                provider.commit(base.byte_add(start.byte_count()), len.byte_count())
+            };
+        }
         unsafe {
             cvt(capi::wasmtime_mprotect(
                 ptr.byte_add(start.byte_count()).cast(),
@@ -90,6 +116,15 @@ impl Mmap {
             // not mapped into the C API at this time.
             let _ = enable_branch_protection;
 
+            if let Some(provider) = provider::provider() {
+                let base = self.as_send_sync_ptr().as_non_null();
+                return provider.protect(
+                    base.byte_add(range.start),
+                    len,
+                    MemoryProtection::ReadExecute,
+                );
+            }
+
             cvt(capi::wasmtime_mprotect(
                 base,
                 len,
@@ -104,6 +139,11 @@ impl Mmap {
             let base = self.memory.as_ptr().byte_add(range.start).cast();
             let len = range.end - range.start;
 
+            if let Some(provider) = provider::provider() {
+                let base = self.as_send_sync_ptr().as_non_null();
+                return provider.protect(base.byte_add(range.start), len, MemoryProtection::Read);
+            }
+
             cvt(capi::wasmtime_mprotect(base, len, capi::PROT_READ))?;
         }
         Ok(())
@@ -140,6 +180,11 @@ impl Drop for Mmap {
             if len == 0 {
                 return;
             }
+            if let Some(provider) = provider::provider() {
+                let ptr = self.as_send_sync_ptr().as_non_null();
+                provider.release(ptr, len).unwrap();
+                return;
+            }
             cvt(capi::wasmtime_munmap(ptr, len)).unwrap();
         }
     }
@@ -5,6 +5,10 @@
 //! module and all other functionality here is implemented in terms of that
 //! module.
 //!
+//! When the `custom-virtual-memory` feature is enabled virtual memory may
+//! alternatively be supplied from Rust through the `runtime::vm::provider`
+//! module.
+//!
 //! For more information about this see `./examples/min-platform` as well as
 //! `./docs/examples-minimal.md`.
 
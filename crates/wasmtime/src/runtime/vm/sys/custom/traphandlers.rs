@@ -61,6 +61,13 @@ extern "C" fn handle_trap(pc: usize, fp: usize, has_faulting_addr: bool, faultin
         match test {
             TrapTest::NotWasm => {}
             TrapTest::HandledByEmbedder => unreachable!(),
+            // Embedders may customize how to jump back to the entry of wasm,
+            // see `wasmtime::unwinding::register_fault_resume`.
+            #[cfg(feature = "custom-native-signals")]
+            TrapTest::Trap { jmp_buf } => unsafe {
+                crate::runtime::vm::traphandlers::resume_at(jmp_buf)
+            },
+            #[cfg(not(feature = "custom-native-signals"))]
             TrapTest::Trap { jmp_buf } => unsafe { wasmtime_longjmp(jmp_buf) },
         }
     })
@@ -1,6 +1,7 @@
 use super::cvt;
 use crate::prelude::*;
 use crate::runtime::vm::SendSyncPtr;
+use crate::runtime::vm::provider::{self, MemoryProtection};
 use crate::runtime::vm::sys::capi;
 use crate::vm::sys::DecommitBehavior;
 use core::ptr::{self, NonNull};
@@ -8,6 +9,9 @@ use core::ptr::{self, NonNull};
 use std::{fs::File, sync::Arc};
 
 pub unsafe fn expose_existing_mapping(ptr: *mut u8, len: usize) -> Result<()> {
+    if let Some(provider) = provider::provider() {
+        return unsafe { provider.commit(NonNull::new(ptr).unwrap(), len) };
+    }
     unsafe {
         cvt(capi::wasmtime_mprotect(
             ptr.cast(),
@@ -18,15 +22,37 @@ pub unsafe fn expose_existing_mapping(ptr: *mut u8, len: usize) -> Result<()> {
 }
 
 pub unsafe fn hide_existing_mapping(ptr: *mut u8, len: usize) -> Result<()> {
+    if let Some(provider) = provider::provider() {
+        let ptr = NonNull::new(ptr).unwrap();
+        return unsafe { provider.protect(ptr, len, MemoryProtection::None) };
+    }
     unsafe { cvt(capi::wasmtime_mprotect(ptr.cast(), len, 0)) }
 }
 
 pub unsafe fn erase_existing_mapping(ptr: *mut u8, len: usize) -> Result<()> {
+    if let Some(provider) = provider::provider() {
+        let ptr = NonNull::new(ptr).unwrap();
+        return unsafe {
+            if provider.supports_decommit() {
+                provider.decommit(ptr, len)
+            } else {
+                provider::reset_to_zero(provider, ptr, len)?;
+                provider.protect(ptr, len, MemoryProtection::None)
+            }
+        };
+    }
     unsafe { cvt(capi::wasmtime_mmap_remap(ptr.cast(), len, 0)) }
 }
 
 #[cfg(feature = "pooling-allocator")]
-pub unsafe fn commit_pages(_addr: *mut u8, _len: usize) -> Result<()> {
+pub unsafe fn commit_pages(addr: *mut u8, len: usize) -> Result<()> {
+    if let Some(provider) = provider::provider() {
+        if len == 0 {
+            return Ok(());
+        }
+        return unsafe { provider.commit(NonNull::new(addr).unwrap(), len) };
+    }
+
     // Pages are always READ | WRITE so there's nothing that needs to be
     // done here.
     Ok(())
@@ -38,6 +64,12 @@ pub unsafe fn decommit_pages(addr: *mut u8, len: usize) -> Result<()> {
         return Ok(());
     }
 
+    // Providers without decommit support have their pages zeroed in place
+    // instead, which keeps them resident but still makes them reusable.
+    if let Some(provider) = provider::provider() {
+        return unsafe { provider::reset_to_zero(provider, NonNull::new(addr).unwrap(), len) };
+    }
+
     unsafe {
         cvt(capi::wasmtime_mmap_remap(
             addr,
@@ -48,6 +80,9 @@ pub unsafe fn decommit_pages(addr: *mut u8, len: usize) -> Result<()> {
 }
 
 pub fn get_page_size() -> usize {
+    if let Some(provider) = provider::provider() {
+        return provider.page_size();
+    }
     unsafe { capi::wasmtime_page_size() }
 }
 
@@ -67,6 +102,11 @@ impl MemoryImageSource {
     }
 
     pub fn from_data(data: &[u8]) -> Result<Option<MemoryImageSource>> {
+        // Copy-on-write images aren't part of the provider interface, so
+        // they're disabled when a provider is in use.
+        if provider::provider().is_some() {
+            return Ok(None);
+        }
         unsafe {
             let mut ptr = ptr::null_mut();
             cvt(capi::wasmtime_memory_image_new(
@@ -0,0 +1,250 @@
+//! Rust implementations of the functions otherwise provided by `helpers.c`.
+//!
+//! This is used when the `no-c-helpers` feature is enabled, for example when
+//! cross-compiling without a C toolchain for the target. It's only supported
+//! on x86_64 and aarch64; elsewhere `build.rs` compiles `helpers.c` anyway.
+//!
+//! Instead of `setjmp`, `wasmtime_setjmp` saves all callee-saved registers in
+//! its own stack frame and publishes the stack pointer to that frame as the
+//! "jmp_buf". `wasmtime_longjmp` then resets the stack pointer to that frame,
+//! restores the saved registers, and returns `false` from `wasmtime_setjmp`.
+//! This is all that's needed since Wasmtime only ever longjmps to a frame
+//! which is still on the stack, possibly from a signal handler.
+
+use crate::vm::VMContext;
+use core::ptr::NonNull;
+use wasmtime_asm_macros::asm_func;
+
+unsafe extern "C" {
+    #[wasmtime_versioned_export_macros::versioned_link]
+    pub fn wasmtime_setjmp(
+        jmp_buf: *mut *const u8,
+        callback: extern "C" fn(*mut u8, NonNull<VMContext>) -> bool,
+        payload: *mut u8,
+        callee: NonNull<VMContext>,
+    ) -> bool;
+
+    #[wasmtime_versioned_export_macros::versioned_link]
+    pub fn wasmtime_longjmp(jmp_buf: *const u8) -> !;
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(target_arch = "x86_64")] {
+        // fn(
+        //    jmp_buf(rdi): *mut *const u8,
+        //    callback(rsi): extern "C" fn(*mut u8, NonNull<VMContext>) -> bool,
+        //    payload(rdx): *mut u8,
+        //    callee(rcx): NonNull<VMContext>,
+        // ) -> bool
+        #[rustfmt::skip]
+        asm_func!(
+            wasmtime_versioned_export_macros::versioned_stringify_ident!(wasmtime_setjmp),
+            "
+                .cfi_startproc
+                push rbp
+                .cfi_def_cfa_offset 16
+                .cfi_offset rbp, -16
+                mov rbp, rsp
+                .cfi_def_cfa_register rbp
+                push rbx
+                .cfi_offset rbx, -24
+                push r12
+                .cfi_offset r12, -32
+                push r13
+                .cfi_offset r13, -40
+                push r14
+                .cfi_offset r14, -48
+                push r15
+                .cfi_offset r15, -56
+
+                // The saved registers are the jmp_buf, see `wasmtime_longjmp`.
+                mov [rdi], rsp
+
+                // Realign the stack to 16 bytes and invoke the callback.
+                sub rsp, 8
+                mov rax, rsi
+                mov rdi, rdx
+                mov rsi, rcx
+                call rax
+                add rsp, 8
+
+                pop r15
+                pop r14
+                pop r13
+                pop r12
+                pop rbx
+                pop rbp
+                .cfi_def_cfa rsp, 8
+                ret
+                .cfi_endproc
+            ",
+        );
+
+        // fn(jmp_buf(rdi): *const u8) -> !
+        asm_func!(
+            wasmtime_versioned_export_macros::versioned_stringify_ident!(wasmtime_longjmp),
+            "
+                mov rsp, rdi
+                pop r15
+                pop r14
+                pop r13
+                pop r12
+                pop rbx
+                pop rbp
+                xor eax, eax
+                ret
+            ",
+        );
+    } else if #[cfg(target_arch = "aarch64")] {
+        // fn(
+        //    jmp_buf(x0): *mut *const u8,
+        //    callback(x1): extern "C" fn(*mut u8, NonNull<VMContext>) -> bool,
+        //    payload(x2): *mut u8,
+        //    callee(x3): NonNull<VMContext>,
+        // ) -> bool
+        #[rustfmt::skip]
+        asm_func!(
+            wasmtime_versioned_export_macros::versioned_stringify_ident!(wasmtime_setjmp),
+            "
+                .cfi_startproc
+                stp x29, x30, [sp, #-16]!
+                .cfi_def_cfa_offset 16
+                .cfi_offset x29, -16
+                .cfi_offset x30, -8
+                mov x29, sp
+                .cfi_def_cfa_register x29
+                stp x19, x20, [sp, #-16]!
+                .cfi_offset x19, -32
+                .cfi_offset x20, -24
+                stp x21, x22, [sp, #-16]!
+                .cfi_offset x21, -48
+                .cfi_offset x22, -40
+                stp x23, x24, [sp, #-16]!
+                .cfi_offset x23, -64
+                .cfi_offset x24, -56
+                stp x25, x26, [sp, #-16]!
+                .cfi_offset x25, -80
+                .cfi_offset x26, -72
+                stp x27, x28, [sp, #-16]!
+                .cfi_offset x27, -96
+                .cfi_offset x28, -88
+                stp d8, d9, [sp, #-16]!
+                .cfi_offset d8, -112
+                .cfi_offset d9, -104
+                stp d10, d11, [sp, #-16]!
+                .cfi_offset d10, -128
+                .cfi_offset d11, -120
+                stp d12, d13, [sp, #-16]!
+                .cfi_offset d12, -144
+                .cfi_offset d13, -136
+                stp d14, d15, [sp, #-16]!
+                .cfi_offset d14, -160
+                .cfi_offset d15, -152
+
+                // The saved registers are the jmp_buf, see `wasmtime_longjmp`.
+                mov x9, sp
+                str x9, [x0]
+
+                mov x9, x1
+                mov x0, x2
+                mov x1, x3
+                blr x9
+
+                ldp d14, d15, [sp], #16
+                ldp d12, d13, [sp], #16
+                ldp d10, d11, [sp], #16
+                ldp d8, d9, [sp], #16
+                ldp x27, x28, [sp], #16
+                ldp x25, x26, [sp], #16
+                ldp x23, x24, [sp], #16
+                ldp x21, x22, [sp], #16
+                ldp x19, x20, [sp], #16
+                ldp x29, x30, [sp], #16
+                .cfi_def_cfa sp, 0
+                ret
+                .cfi_endproc
+            ",
+        );
+
+        // fn(jmp_buf(x0): *const u8) -> !
+        asm_func!(
+            wasmtime_versioned_export_macros::versioned_stringify_ident!(wasmtime_longjmp),
+            "
+                mov sp, x0
+                ldp d14, d15, [sp], #16
+                ldp d12, d13, [sp], #16
+                ldp d10, d11, [sp], #16
+                ldp d8, d9, [sp], #16
+                ldp x27, x28, [sp], #16
+                ldp x25, x26, [sp], #16
+                ldp x23, x24, [sp], #16
+                ldp x21, x22, [sp], #16
+                ldp x19, x20, [sp], #16
+                ldp x29, x30, [sp], #16
+                mov w0, #0
+                ret
+            ",
+        );
+    } else {
+        compile_error!("the `no-c-helpers` feature is not supported on this architecture");
+    }
+}
+
+/// Equivalent of `wasmtime_using_libunwind` in `helpers.c`, but looks up the
+/// symbol dynamically since weak symbols can't be used from Rust.
+pub fn using_libunwind() -> bool {
+    unsafe { !libc::dlsym(libc::RTLD_DEFAULT, c"__unw_add_dynamic_fde".as_ptr()).is_null() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    struct Payload {
+        jmp_buf: Cell<*const u8>,
+        longjmp: bool,
+    }
+
+    extern "C" fn callback(payload: *mut u8, _callee: NonNull<VMContext>) -> bool {
+        let payload = unsafe { &*payload.cast::<Payload>() };
+        if payload.longjmp {
+            unsafe { wasmtime_longjmp(payload.jmp_buf.get()) }
+        }
+        true
+    }
+
+    fn run(longjmp: bool) -> bool {
+        let payload = Payload {
+            jmp_buf: Cell::new(core::ptr::null()),
+            longjmp,
+        };
+        // Keep some state live in callee-saved registers across the call.
+        let a = core::hint::black_box(0x1234_u64);
+        let b = core::hint::black_box(1.5_f64);
+        let ret = unsafe {
+            wasmtime_setjmp(
+                payload.jmp_buf.as_ptr(),
+                callback,
+                (&raw const payload).cast_mut().cast(),
+                NonNull::dangling(),
+            )
+        };
+        assert!(!payload.jmp_buf.get().is_null());
+        assert_eq!(a, 0x1234);
+        assert_eq!(b, 1.5);
+        ret
+    }
+
+    #[test]
+    fn setjmp_returns_callback_result() {
+        assert!(run(false));
+    }
+
+    #[test]
+    fn longjmp_returns_false() {
+        assert!(!run(true));
+        assert!(!run(true));
+        assert!(run(false));
+    }
+}
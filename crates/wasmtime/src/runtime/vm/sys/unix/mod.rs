@@ -5,6 +5,8 @@
 
 use core::cell::Cell;
 
+#[cfg(all(has_host_compiler_backend, has_rust_helpers))]
+mod helpers;
 #[cfg(has_virtual_memory)]
 pub mod mmap;
 pub mod traphandlers;
@@ -1,9 +1,12 @@
-#[cfg(has_host_compiler_backend)]
+#[cfg(all(has_host_compiler_backend, not(has_rust_helpers)))]
 use crate::vm::VMContext;
-#[cfg(has_host_compiler_backend)]
+#[cfg(all(has_host_compiler_backend, not(has_rust_helpers)))]
 use core::ptr::NonNull;
 
-#[cfg(has_host_compiler_backend)]
+#[cfg(all(has_host_compiler_backend, has_rust_helpers))]
+pub use super::helpers::{wasmtime_longjmp, wasmtime_setjmp};
+
+#[cfg(all(has_host_compiler_backend, not(has_rust_helpers)))]
 #[link(name = "wasmtime-helpers")]
 unsafe extern "C" {
     #[wasmtime_versioned_export_macros::versioned_link]
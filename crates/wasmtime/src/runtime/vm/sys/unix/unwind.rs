@@ -23,6 +23,15 @@ cfg_if::cfg_if! {
         unsafe extern "C" fn wasmtime_using_libunwind() -> bool {
             false
         }
+    } else if #[cfg(has_rust_helpers)] {
+        unsafe extern "C" {
+            // libunwind import
+            fn __register_frame(fde: *const u8);
+            fn __deregister_frame(fde: *const u8);
+        }
+        unsafe fn wasmtime_using_libunwind() -> bool {
+            super::helpers::using_libunwind()
+        }
     } else {
         unsafe extern "C" {
             // libunwind import
@@ -15,6 +15,11 @@ mod signals;
 #[cfg(all(has_native_signals))]
 pub use self::signals::*;
 
+#[cfg(all(feature = "custom-native-signals", has_native_signals))]
+mod delegation;
+#[cfg(all(feature = "custom-native-signals", has_native_signals))]
+pub use self::delegation::*;
+
 use crate::runtime::module::lookup_code;
 use crate::runtime::store::{ExecutorRef, StoreOpaque};
 use crate::runtime::vm::sys::traphandlers;
@@ -769,16 +774,7 @@ impl CallThreadState {
             }
         }
 
-        // If this fault wasn't in wasm code, then it's not our problem
-        let Some((code, text_offset)) = lookup_code(regs.pc) else {
-            return TrapTest::NotWasm;
-        };
-
-        // If the fault was at a location that was not marked as potentially
-        // trapping, then that's a bug in Cranelift/Winch/etc. Don't try to
-        // catch the trap and pretend this isn't wasm so the program likely
-        // aborts.
-        let Some(trap) = code.lookup_trap_code(text_offset) else {
+        let Some(trap) = self.lookup_jit_trap(regs.pc) else {
             return TrapTest::NotWasm;
         };
 
@@ -791,6 +787,26 @@ impl CallThreadState {
         }
     }
 
+    /// Returns the trap code for a fault at `pc` if it's a trapping
+    /// instruction in wasm code that this activation can unwind from.
+    ///
+    /// Unlike `test_if_trap` this has no side effects on `self`.
+    pub(crate) fn lookup_jit_trap(&self, pc: usize) -> Option<wasmtime_environ::Trap> {
+        // If we haven't even started to handle traps yet, bail out.
+        if self.jmp_buf.get().is_null() {
+            return None;
+        }
+
+        // If this fault wasn't in wasm code, then it's not our problem
+        let (code, text_offset) = lookup_code(pc)?;
+
+        // If the fault was at a location that was not marked as potentially
+        // trapping, then that's a bug in Cranelift/Winch/etc. Don't try to
+        // catch the trap and pretend this isn't wasm so the program likely
+        // aborts.
+        code.lookup_trap_code(text_offset)
+    }
+
     #[cfg(has_host_compiler_backend)]
     pub(crate) fn take_jmp_buf(&self) -> *const u8 {
         self.jmp_buf.replace(ptr::null())
@@ -0,0 +1,127 @@
+//! Support for embedders which own fault handling themselves and delegate
+//! WebAssembly faults to Wasmtime.
+//!
+//! This module is included when the `custom-native-signals` feature is
+//! enabled and is re-exported publicly as `wasmtime::unwinding`.
+
+use crate::Trap;
+use crate::prelude::*;
+use crate::runtime::vm::sys::traphandlers::wasmtime_longjmp;
+use crate::runtime::vm::traphandlers::{TrapRegisters, tls};
+use crate::sync::OnceLock;
+
+/// A function which completes a WebAssembly trap by transferring control to
+/// the `jmp_buf` provided, see [`register_fault_resume`].
+pub type FaultResume = unsafe extern "C" fn(jmp_buf: *const u8) -> !;
+
+static FAULT_RESUME: OnceLock<FaultResume> = OnceLock::new();
+
+/// A fault which [`resolve_fault`] determined to be a WebAssembly trap.
+///
+/// The trap has not yet been recorded by Wasmtime, and nothing happens if this
+/// is dropped. To complete the trap call [`ResolvedTrap::resume`].
+#[derive(Debug)]
+pub struct ResolvedTrap {
+    trap: Trap,
+    pc: usize,
+    fp: usize,
+    faulting_addr: Option<usize>,
+}
+
+impl ResolvedTrap {
+    /// Returns the trap code of the faulting instruction.
+    pub fn trap(&self) -> Trap {
+        self.trap
+    }
+
+    /// Returns the program counter of the faulting instruction.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Returns the faulting data address, if one was provided.
+    pub fn faulting_addr(&self) -> Option<usize> {
+        self.faulting_addr
+    }
+
+    /// Records this trap as the result of the WebAssembly call currently
+    /// executing on this thread and unwinds to that call's entry.
+    ///
+    /// Control is transferred with the function registered with
+    /// [`register_fault_resume`], or `wasmtime_longjmp` if none was
+    /// registered.
+    ///
+    /// # Safety
+    ///
+    /// This must be called on the thread which faulted, from a context where
+    /// it's valid to jump back to the frame which entered WebAssembly, for
+    /// example from a signal handler which permits `longjmp`. Any Rust frames
+    /// between the fault and the entry to WebAssembly are skipped without
+    /// running their destructors.
+    pub unsafe fn resume(self) -> ! {
+        let jmp_buf = tls::with(|info| {
+            let info = info.expect("resumed a trap without any WebAssembly on the stack");
+            let regs = TrapRegisters {
+                pc: self.pc,
+                fp: self.fp,
+            };
+            info.set_jit_trap(regs, self.faulting_addr, self.trap);
+            info.take_jmp_buf()
+        });
+        unsafe { resume_at(jmp_buf) }
+    }
+}
+
+/// Determines whether a native fault is a WebAssembly trap.
+///
+/// This is intended to be called from an embedder's own fault handler with
+/// the program counter, frame pointer, and (for memory faults) data address
+/// of the faulting instruction. If the fault happened at a trapping
+/// instruction in WebAssembly code which was called on this thread then the
+/// trap is returned, and otherwise `None` is returned and the fault should be
+/// handled as it would be without Wasmtime.
+///
+/// This function does not allocate or take locks other than the read lock on
+/// Wasmtime's global code registry, but embedders should still ensure it's
+/// suitable for their fault-handling context.
+pub fn resolve_fault(pc: usize, fp: usize, faulting_addr: Option<usize>) -> Option<ResolvedTrap> {
+    let trap = tls::with(|info| info?.lookup_jit_trap(pc))?;
+    Some(ResolvedTrap {
+        trap,
+        pc,
+        fp,
+        faulting_addr,
+    })
+}
+
+/// Registers the function used to complete WebAssembly traps.
+///
+/// By default traps are completed with `wasmtime_longjmp`. Platforms which
+/// need to leave their fault-handling context first, for example by restoring
+/// an exception frame, can register their own function here which must
+/// eventually call `wasmtime_longjmp` with the `jmp_buf` it's given.
+///
+/// This applies both to [`ResolvedTrap::resume`] and to traps detected by the
+/// handler passed to `wasmtime_init_traps`. Returns an error if a function
+/// was already registered.
+pub fn register_fault_resume(resume: FaultResume) -> Result<()> {
+    let mut registered = false;
+    FAULT_RESUME.get_or_init(|| {
+        registered = true;
+        resume
+    });
+    if !registered {
+        bail!("a fault resume function has already been registered");
+    }
+    Ok(())
+}
+
+/// Transfers control to `jmp_buf` using the registered resume function.
+pub(crate) unsafe fn resume_at(jmp_buf: *const u8) -> ! {
+    unsafe {
+        match FAULT_RESUME.get() {
+            Some(resume) => resume(jmp_buf),
+            None => wasmtime_longjmp(jmp_buf),
+        }
+    }
+}
@@ -1153,6 +1153,14 @@ pub struct VMStoreContext {
     /// on `VMStackChain` for details.
     pub stack_chain: UnsafeCell<VMStackChain>,
 
+    /// The number of linear memory accesses made by WebAssembly compiled with
+    /// `MemoryAccessInstrumentation::Count`.
+    ///
+    /// Unlike the other 64-bit fields this one comes after the pointer-sized
+    /// fields, but the preceding fields happen to keep it 8-byte aligned on
+    /// both 32-bit and 64-bit platforms.
+    pub memory_accesses: UnsafeCell<u64>,
+
     /// The range, in addresses, of the guard page that is currently in use.
     ///
     /// This field is used when signal handlers are run to determine whether a
@@ -1190,6 +1198,7 @@ impl Default for VMStoreContext {
             last_wasm_exit_pc: UnsafeCell::new(0),
             last_wasm_entry_fp: UnsafeCell::new(0),
             stack_chain: UnsafeCell::new(VMStackChain::Absent),
+            memory_accesses: UnsafeCell::new(0),
             async_guard_range: ptr::null_mut()..ptr::null_mut(),
         }
     }
@@ -1244,6 +1253,10 @@ mod test_vmstore_context {
         assert_eq!(
             offset_of!(VMStoreContext, stack_chain),
             usize::from(offsets.ptr.vmstore_context_stack_chain())
+        );
+        assert_eq!(
+            offset_of!(VMStoreContext, memory_accesses),
+            usize::from(offsets.ptr.vmstore_context_memory_accesses())
         )
     }
 }
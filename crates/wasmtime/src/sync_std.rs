@@ -28,6 +28,11 @@ impl<T> OnceLock<T> {
     pub fn get_or_try_init<E>(&self, f: impl FnOnce() -> Result<T, E>) -> Result<&T, E> {
         self.0.get_or_try_init(f)
     }
+
+    #[inline]
+    pub fn get(&self) -> Option<&T> {
+        self.0.get()
+    }
 }
 
 impl<T> Default for OnceLock<T> {
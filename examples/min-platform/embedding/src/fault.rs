@@ -0,0 +1,57 @@
+//! Example of completing WebAssembly traps from an embedder-owned fault
+//! handler.
+//!
+//! The platform in `wasmtime-platform.c` forwards faults to the handler given
+//! to `wasmtime_init_traps`, but platforms which already own their fault
+//! handling can instead forward faults to Wasmtime with
+//! `wasmtime::unwinding::resolve_fault`, as `embedding_handle_fault` does
+//! below. In both cases traps are completed with the function registered
+//! through `wasmtime::unwinding::register_fault_resume`.
+
+use anyhow::Result;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use wasmtime::unwinding;
+
+unsafe extern "C" {
+    fn wasmtime_longjmp(jmp_buf: *const u8) -> !;
+}
+
+/// Number of wasm traps which were completed through `resume`.
+pub static RESUMED_TRAPS: AtomicUsize = AtomicUsize::new(0);
+
+/// Resumes execution at the entry of wasm after a trap.
+///
+/// A real platform might first need to leave its exception context here, for
+/// example by restoring interrupt state.
+unsafe extern "C" fn resume(jmp_buf: *const u8) -> ! {
+    RESUMED_TRAPS.fetch_add(1, Ordering::Relaxed);
+    unsafe { wasmtime_longjmp(jmp_buf) }
+}
+
+pub fn init() -> Result<()> {
+    // Only the first call in this process can register the function.
+    let _ = unwinding::register_fault_resume(resume);
+    Ok(())
+}
+
+/// Fault handler for platforms which own their faults and only delegate
+/// WebAssembly traps to Wasmtime.
+///
+/// Returns if the fault wasn't a WebAssembly trap, in which case it should be
+/// handled as it would be without Wasmtime.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn embedding_handle_fault(
+    ip: usize,
+    fp: usize,
+    has_faulting_addr: bool,
+    faulting_addr: usize,
+) {
+    let faulting_addr = if has_faulting_addr {
+        Some(faulting_addr)
+    } else {
+        None
+    };
+    if let Some(trap) = unwinding::resolve_fault(ip, fp, faulting_addr) {
+        unsafe { trap.resume() }
+    }
+}
@@ -6,9 +6,11 @@ extern crate alloc;
 use alloc::string::ToString;
 use anyhow::Result;
 use core::ptr;
-use wasmtime::{Engine, Instance, Linker, Module, Store};
+use wasmtime::{Engine, Instance, Linker, Module, Store, Trap};
 
 mod allocator;
+#[cfg(feature = "custom")]
+mod fault;
 mod panic;
 
 #[cfg(feature = "wasi")]
@@ -29,6 +31,8 @@ pub unsafe extern "C" fn run(
     simple_add_size: usize,
     simple_host_fn_module: *const u8,
     simple_host_fn_size: usize,
+    trap_module: *const u8,
+    trap_size: usize,
 ) -> usize {
     unsafe {
         let buf = core::slice::from_raw_parts_mut(error_buf, error_size);
@@ -36,7 +40,8 @@ pub unsafe extern "C" fn run(
         let simple_add = core::slice::from_raw_parts(simple_add_module, simple_add_size);
         let simple_host_fn =
             core::slice::from_raw_parts(simple_host_fn_module, simple_host_fn_size);
-        match run_result(smoke, simple_add, simple_host_fn) {
+        let trap = core::slice::from_raw_parts(trap_module, trap_size);
+        match run_result(smoke, simple_add, simple_host_fn, trap) {
             Ok(()) => 0,
             Err(e) => {
                 let msg = format!("{e:?}");
@@ -52,10 +57,12 @@ fn run_result(
     smoke_module: &[u8],
     simple_add_module: &[u8],
     simple_host_fn_module: &[u8],
+    trap_module: &[u8],
 ) -> Result<()> {
     smoke(smoke_module)?;
     simple_add(simple_add_module)?;
     simple_host_fn(simple_host_fn_module)?;
+    trap(trap_module)?;
     Ok(())
 }
 
@@ -97,6 +104,34 @@ fn simple_host_fn(module: &[u8]) -> Result<()> {
     Ok(())
 }
 
+fn trap(module: &[u8]) -> Result<()> {
+    #[cfg(feature = "custom")]
+    fault::init()?;
+
+    let engine = Engine::default();
+    let module = match deserialize(&engine, module)? {
+        Some(module) => module,
+        None => return Ok(()),
+    };
+    let mut store = Store::new(&engine, ());
+    let instance = Linker::new(&engine).instantiate(&mut store, &module)?;
+    let func = instance.get_typed_func::<(), ()>(&mut store, "trap")?;
+    let err = func.call(&mut store, ()).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<Trap>(),
+        Some(&Trap::UnreachableCodeReached)
+    );
+
+    // With native signals the trap above was raised as a fault and completed
+    // by the function registered in `fault::init`.
+    #[cfg(feature = "custom")]
+    assert_eq!(
+        fault::RESUMED_TRAPS.load(core::sync::atomic::Ordering::Relaxed),
+        1
+    );
+    Ok(())
+}
+
 fn deserialize(engine: &Engine, module: &[u8]) -> Result<Option<Module>> {
     // NOTE: deserialize_raw avoids creating a copy of the module code.  See the
     // safety notes before using in your embedding.
@@ -35,7 +35,7 @@ use core::task::{Context, Poll, Waker};
 use wasmtime::component::{Component, Linker, Resource, ResourceTable};
 use wasmtime::{Config, Engine, Store};
 use wasmtime_wasi_io::{
-    IoView,
+    IoView, WakeSignal,
     bytes::Bytes,
     poll::{DynPollable, Pollable, subscribe},
     streams::{DynInputStream, DynOutputStream, InputStream, OutputStream},
@@ -76,8 +76,9 @@ pub unsafe extern "C" fn run_wasi(
 fn run(wasi_component: &[u8]) -> Result<String> {
     // wasmtime-wasi-io requires an async store, because the wasi:io/poll
     // interface will poll as Pending while execution is suspended and it is
-    // waiting for a Pollable to become Ready. This example provides a very
-    // small async executor which is entered below with `block_on`.
+    // waiting for a Pollable to become Ready. This example drives it with the
+    // small executor provided by wasmtime-wasi-io, entered below with
+    // `block_on`.
     let mut config = Config::default();
     config.async_support(true);
     // For future: we could consider turning on fuel in the Config to meter
@@ -417,6 +418,10 @@ impl ExecutorInner {
     }
 }
 
+// Set by wakers, and by the timer interrupt below, whenever `block_on` should
+// poll its future again.
+static WAKE: WakeSignal = WakeSignal::new();
+
 fn block_on<R>(clock: Clock, f: impl Future<Output = Result<R>> + Send + 'static) -> Result<R> {
     // Guard against nested invocations
     if EXECUTOR.0.borrow_mut().is_some() {
@@ -425,39 +430,9 @@ fn block_on<R>(clock: Clock, f: impl Future<Output = Result<R>> + Send + 'static
     let executor = Executor::new();
     *EXECUTOR.0.borrow_mut() = Some(Executor(executor.0.clone()));
 
-    // No special waker needed for this executor.
-    let mut cx = Context::from_waker(Waker::noop());
-    let mut f = core::pin::pin!(f);
-
-    // Drive the Future to completion in the following loop
-    let r = 'outer: loop {
-        // Arbitrary. Could be as little as 1. There's no fuel-based async
-        // yielding in this example so repeated polls is probably not making
-        // progress without "providing input" from the outside environment,
-        // below.
-        const POLLS_PER_CLOCK: usize = 200;
-        for _ in 0..POLLS_PER_CLOCK {
-            match f.as_mut().poll(&mut cx) {
-                Poll::Pending => {}
-                Poll::Ready(r) => break 'outer r,
-            }
-        }
-
-        // This is where a non-example executor would wait for input from the
-        // "outside world". This example checks if the schedule indicates the
-        // guest is waiting on some future deadline and fast-forwards time
-        // until then, because no other input is possible in this example.
-        if let Some(sleep_until) = executor.0.borrow().earliest_deadline() {
-            clock.set(sleep_until);
-        } else {
-            clock.set(clock.get() + 1);
-        }
-
-        // Any wakers which are ready can be waked now.
-        for waker in executor.0.borrow_mut().ready_deadlines(clock.get()) {
-            waker.wake()
-        }
-    };
+    // Drive the Future to completion with wasmtime-wasi-io's executor, which
+    // only polls it again once it has been woken.
+    let r = wasmtime_wasi_io::block_on(&WAKE, |signal| timer_interrupt(&clock, signal), f);
 
     // Clean up guard for nested invocations
     let _ = EXECUTOR
@@ -468,6 +443,30 @@ fn block_on<R>(clock: Clock, f: impl Future<Output = Result<R>> + Send + 'static
     r
 }
 
+// This is where a non-example executor would idle, for example with `wfi`,
+// until an interrupt from the "outside world" arrives. This example has no
+// interrupts, so it simulates the next timer interrupt instead: if the guest
+// is waiting on some future deadline time fast-forwards until then, because
+// no other input is possible in this example.
+fn timer_interrupt(clock: &Clock, signal: &WakeSignal) {
+    let executor = Executor::current();
+    if let Some(sleep_until) = executor.0.borrow().earliest_deadline() {
+        clock.set(sleep_until);
+    } else {
+        clock.set(clock.get() + 1);
+    }
+
+    // Any wakers which are ready can be waked now, as the interrupt handler
+    // would do.
+    for waker in executor.0.borrow_mut().ready_deadlines(clock.get()) {
+        waker.wake()
+    }
+
+    // Each timer tick polls the guest again, even if no deadline was reached,
+    // which is what a real tick interrupt calling `wake_from_irq` would do.
+    signal.wake_from_irq();
+}
+
 // -------------- impls for the bindgen! Host traits ------------------
 // These impls are written directly for WasiCtx, which is fine because this
 // example isn't trying to create reusable library code.
@@ -96,6 +96,13 @@ fn main() -> Result<()> {
             )
         "#,
     )?;
+    let trap = engine.precompile_module(
+        br#"
+            (module
+                (func (export "trap") unreachable)
+            )
+        "#,
+    )?;
 
     // Next is an example of running this embedding, which also serves as test
     // that basic functionality actually works.
@@ -135,6 +142,8 @@ fn main() -> Result<()> {
                 usize,
                 *const u8,
                 usize,
+                *const u8,
+                usize,
             ) -> usize,
         > = lib
             .get(b"run")
@@ -150,6 +159,8 @@ fn main() -> Result<()> {
             simple_add.len(),
             simple_host_fn.as_ptr(),
             simple_host_fn.len(),
+            trap.as_ptr(),
+            trap.len(),
         );
         error_buf.set_len(len);
 
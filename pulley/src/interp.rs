@@ -229,6 +229,51 @@ impl Vm {
     pub fn executing_pc(&self) -> &ExecutingPc {
         &self.executing_pc
     }
+
+    /// Enables or disables counting the instructions this interpreter
+    /// executes.
+    ///
+    /// Counting is deterministic: the same bytecode called with the same
+    /// arguments always executes the same number of instructions, regardless
+    /// of the host or of which interpreter loop is in use. Instructions are
+    /// counted before they execute, and an extended opcode counts as a single
+    /// instruction.
+    #[cfg(feature = "metering")]
+    pub fn set_instruction_metering(&mut self, enabled: bool) {
+        self.state.metering.enabled = enabled;
+    }
+
+    /// Returns the number of instructions executed while metering was
+    /// enabled, see [`Vm::set_instruction_metering`].
+    #[cfg(feature = "metering")]
+    pub fn instructions_executed(&self) -> u64 {
+        self.state.metering.executed
+    }
+
+    /// Sets the number of executed instructions, for example to reset it
+    /// between calls.
+    #[cfg(feature = "metering")]
+    pub fn set_instructions_executed(&mut self, executed: u64) {
+        self.state.metering.executed = executed;
+    }
+
+    /// Sets the number of executed instructions, see
+    /// [`Vm::instructions_executed`], at which execution traps with
+    /// [`TrapKind::InstructionLimitExceeded`], or removes the limit with
+    /// `None`.
+    ///
+    /// The trap is raised at the instruction which would exceed the limit,
+    /// before it executes. The limit only applies while metering is enabled.
+    #[cfg(feature = "metering")]
+    pub fn set_instruction_limit(&mut self, limit: Option<u64>) {
+        self.state.metering.limit = limit;
+    }
+
+    /// Returns the limit set with [`Vm::set_instruction_limit`].
+    #[cfg(feature = "metering")]
+    pub fn instruction_limit(&self) -> Option<u64> {
+        self.state.metering.limit
+    }
 }
 
 impl Drop for Vm {
@@ -750,11 +795,23 @@ pub struct MachineState {
     lr: *mut u8,
     stack: Stack,
     done_reason: Option<DoneReason<()>>,
+    #[cfg(feature = "metering")]
+    metering: Metering,
 }
 
 unsafe impl Send for MachineState {}
 unsafe impl Sync for MachineState {}
 
+/// Counts of the instructions executed by a Pulley virtual machine, see
+/// [`Vm::set_instruction_metering`].
+#[cfg(feature = "metering")]
+#[derive(Default)]
+struct Metering {
+    enabled: bool,
+    executed: u64,
+    limit: Option<u64>,
+}
+
 /// Helper structure to store the state of the Pulley stack.
 ///
 /// The Pulley stack notably needs to be a 16-byte aligned allocation on the
@@ -906,6 +963,8 @@ impl MachineState {
             done_reason: None,
             fp: HOST_RETURN_ADDR,
             lr: HOST_RETURN_ADDR,
+            #[cfg(feature = "metering")]
+            metering: Metering::default(),
         };
 
         let sp = state.stack.top();
@@ -959,6 +1018,7 @@ mod done {
         MemoryOutOfBounds,
         DisabledOpcode,
         StackOverflow,
+        InstructionLimitExceeded,
     }
 
     impl MachineState {
@@ -992,6 +1052,19 @@ mod done {
             ControlFlow::Break(Done { _priv: () })
         }
 
+        /// Finishes execution by recording `DoneReason::Trap` with
+        /// `TrapKind::InstructionLimitExceeded` at the instruction about to
+        /// be executed.
+        #[cfg(feature = "metering")]
+        #[cold]
+        pub fn done_instruction_limit(&mut self) -> ControlFlow<Done> {
+            self.state.done_reason = Some(DoneReason::Trap {
+                pc: self.pc.as_ptr(),
+                kind: Some(TrapKind::InstructionLimitExceeded),
+            });
+            ControlFlow::Break(Done { _priv: () })
+        }
+
         /// Finishes execution by recording `DoneReason::CallIndirectHost`.
         #[cold]
         pub fn done_call_indirect_host(&mut self, id: u8) -> ControlFlow<Done> {
@@ -1173,6 +1246,28 @@ impl Interpreter<'_> {
         // Note that this is a no-op if `feature = "profile"` is disabled.
         self.executing_pc.record(self.pc.as_ptr().as_ptr() as usize);
     }
+
+    /// Counts the instruction about to be executed, trapping instead if the
+    /// instruction limit has been reached.
+    ///
+    /// Note that this is a no-op if `feature = "metering"` is disabled.
+    #[inline]
+    fn meter_instruction(&mut self) -> ControlFlow<Done> {
+        #[cfg(feature = "metering")]
+        {
+            let metering = &mut self.state.metering;
+            if metering.enabled {
+                if metering
+                    .limit
+                    .is_some_and(|limit| metering.executed >= limit)
+                {
+                    return self.done_instruction_limit();
+                }
+                metering.executed += 1;
+            }
+        }
+        ControlFlow::Continue(())
+    }
 }
 
 /// Helper trait to encompass the various addressing modes of Pulley.
@@ -29,6 +29,9 @@ impl Interpreter<'_> {
             //
             // This will then continue indefinitely until the bytecode says it's
             // done. Note that only trusted bytecode is interpreted here.
+            if let ControlFlow::Break(done) = visitor.0.meter_instruction() {
+                break done;
+            }
             match decoder.decode_one(&mut visitor) {
                 Ok(ControlFlow::Continue(())) => {}
                 Ok(ControlFlow::Break(done)) => break done,
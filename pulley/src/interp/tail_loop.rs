@@ -89,6 +89,9 @@ fn dispatch(
     // Perform a dynamic dispatch through a function pointer indexed by
     // opcode.
     let mut debug = debug(state, pc, executing_pc);
+    if let ControlFlow::Break(done) = debug.0.meter_instruction() {
+        return done;
+    }
     debug.before_visit();
     let Ok(opcode) = Opcode::decode(debug.bytecode());
     let handler = OPCODE_HANDLER_TABLE[opcode as usize];
@@ -816,3 +816,79 @@ fn trap() {
     // `dst` should not have been written to the second time.
     assert_eq!(vm.state()[dst].get_u32(), 1);
 }
+
+/// Counts `x0` down from `n` to zero, executing `2 * n + 2` instructions.
+#[cfg(feature = "metering")]
+fn countdown(n: i8) -> Vec<u8> {
+    let dst = XReg::new(0).unwrap();
+    let sub = encoded(&[Op::Xsub32U8(Xsub32U8 {
+        dst,
+        src1: dst,
+        src2: 1,
+    })]);
+    let back = -i32::try_from(sub.len()).unwrap();
+    encoded(&[
+        Op::Xconst8(Xconst8 { dst, imm: n }),
+        Op::Xsub32U8(Xsub32U8 {
+            dst,
+            src1: dst,
+            src2: 1,
+        }),
+        Op::BrIf(BrIf {
+            cond: dst,
+            offset: back.into(),
+        }),
+        Op::Ret(Ret {}),
+    ])
+}
+
+#[test]
+#[cfg(feature = "metering")]
+fn instruction_metering() {
+    for n in [1, 10, 100] {
+        let ops = countdown(n);
+        let mut counts = vec![];
+        for _ in 0..2 {
+            let mut vm = Vm::new();
+            vm.set_instruction_metering(true);
+            match unsafe { vm.call(NonNull::from(&ops[..]).cast(), &[], []) } {
+                DoneReason::ReturnToHost(_) => {}
+                _ => panic!("should not trap"),
+            }
+            counts.push(vm.instructions_executed());
+        }
+        assert_eq!(counts, [2 * n as u64 + 2; 2]);
+    }
+
+    // Nothing is counted while metering is disabled.
+    let ops = countdown(10);
+    let mut vm = Vm::new();
+    match unsafe { vm.call(NonNull::from(&ops[..]).cast(), &[], []) } {
+        DoneReason::ReturnToHost(_) => {}
+        _ => panic!("should not trap"),
+    }
+    assert_eq!(vm.instructions_executed(), 0);
+}
+
+#[test]
+#[cfg(feature = "metering")]
+fn instruction_limit() {
+    let ops = countdown(10);
+    let mut vm = Vm::new();
+    vm.set_instruction_metering(true);
+    vm.set_instruction_limit(Some(5));
+    let base = NonNull::from(&ops[..]).cast::<u8>();
+    match unsafe { vm.call(base, &[], []) } {
+        DoneReason::Trap {
+            pc,
+            kind: Some(interp::TrapKind::InstructionLimitExceeded),
+        } => {
+            // The trap is at the third `xsub32_u8`, which is never executed.
+            let xconst = encoded(&[Op::Xconst8(Xconst8 { dst: x(0), imm: 10 })]);
+            assert_eq!(pc, unsafe { base.add(xconst.len()) });
+        }
+        _ => panic!("should trap"),
+    }
+    assert_eq!(vm.instructions_executed(), 5);
+    assert_eq!(vm.state()[x(0)].get_u32(), 8);
+}
@@ -69,3 +69,182 @@ fn test_tail_call_default() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn platform_capabilities_match_config_validation() -> Result<()> {
+    let caps = Engine::default().platform_capabilities();
+    assert!(!caps.signals_based_traps || caps.native_signals);
+    assert_eq!(caps.pulley_by_default, !caps.host_compiler_backend);
+
+    // The default configuration is always usable.
+    Module::new(&Engine::default(), "(module (memory 1))")?;
+
+    let check = |config: &mut Config, supported: bool, what: &str| -> Result<()> {
+        // Unsupported options are rejected by `Engine::new` already.
+        match Engine::new(config).and_then(|engine| Module::new(&engine, "(module (memory 1))")) {
+            Ok(_) => assert!(supported, "{what} should be rejected"),
+            Err(e) => {
+                assert!(!supported, "{what} should be supported: {e:?}");
+                assert!(
+                    format!("{e:?}").contains("platform_capabilities"),
+                    "unexpected error for {what}: {e:?}"
+                );
+            }
+        }
+        Ok(())
+    };
+
+    let mut config = Config::new();
+    config.signals_based_traps(true);
+    if let Ok(engine) = Engine::new(&config) {
+        if !engine.is_pulley() {
+            assert!(engine.platform_capabilities().signals_based_traps);
+            check(&mut config, caps.native_signals, "signals-based traps")?;
+        }
+    }
+
+    let mut config = Config::new();
+    config.memory_init_cow(true);
+    check(&mut config, caps.virtual_memory, "copy-on-write images")?;
+
+    let mut config = Config::new();
+    config.signals_based_traps(false);
+    config.memory_guard_size(1 << 16);
+    check(&mut config, caps.virtual_memory, "guard pages")?;
+
+    let mut config = Config::new();
+    config.signals_based_traps(false);
+    let engine = Engine::new(&config)?;
+    assert!(!engine.platform_capabilities().signals_based_traps);
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn validate_for_host_reports_every_incompatibility() -> Result<()> {
+    let caps = Engine::default().platform_capabilities();
+    Config::new().validate_for_host()?;
+
+    let incompatibilities = |config: &Config| match config.validate_for_host() {
+        Ok(()) => Vec::new(),
+        Err(ConfigError::Incompatible(list)) => list,
+        Err(e) => panic!("unexpected error: {e}"),
+    };
+    let expected = |unsupported: bool, incompatibility| {
+        if unsupported {
+            vec![incompatibility]
+        } else {
+            vec![]
+        }
+    };
+    let fibers = cfg!(any(
+        windows,
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "x86",
+        target_arch = "arm",
+        target_arch = "s390x",
+        target_arch = "riscv64",
+    ));
+
+    if !caps.pulley_by_default {
+        let mut config = Config::new();
+        config.signals_based_traps(true);
+        assert_eq!(
+            incompatibilities(&config),
+            expected(!caps.native_signals, HostIncompatibility::SignalsBasedTraps)
+        );
+    }
+
+    let mut config = Config::new();
+    config.memory_init_cow(true);
+    assert_eq!(
+        incompatibilities(&config),
+        expected(!caps.virtual_memory, HostIncompatibility::MemoryInitCow)
+    );
+
+    let mut config = Config::new();
+    config.signals_based_traps(false);
+    config.memory_guard_size(1 << 16);
+    assert_eq!(
+        incompatibilities(&config),
+        expected(!caps.virtual_memory, HostIncompatibility::MemoryGuardPages)
+    );
+
+    let mut config = Config::new();
+    config.async_support(true);
+    assert_eq!(
+        incompatibilities(&config),
+        expected(!fibers, HostIncompatibility::AsyncStacks)
+    );
+
+    let mut config = Config::new();
+    config.epoch_interruption(true);
+    assert_eq!(
+        incompatibilities(&config),
+        expected(
+            !cfg!(target_has_atomic = "64"),
+            HostIncompatibility::EpochInterruption
+        )
+    );
+
+    // A target for another host is reported even though it's valid for
+    // cross-compilation.
+    let mut config = Config::new();
+    config.target(if cfg!(target_arch = "s390x") {
+        "x86_64"
+    } else {
+        "s390x"
+    })?;
+    let engine = Engine::new(&config)?;
+    assert!(incompatibilities(&config).contains(&HostIncompatibility::Target));
+    engine.precompile_module(b"(module)")?;
+
+    let mut config = Config::new();
+    config.target(if cfg!(target_pointer_width = "64") {
+        "pulley32"
+    } else {
+        "pulley64"
+    })?;
+    assert!(incompatibilities(&config).contains(&HostIncompatibility::Target));
+
+    // Every incompatibility is reported at once, and `Engine::new` rejects
+    // them all without an explicit target.
+    let mut config = Config::new();
+    config.signals_based_traps(false);
+    config.memory_init_cow(true);
+    config.memory_guard_size(1 << 16);
+    config.async_support(true);
+    config.epoch_interruption(true);
+    let mut all = expected(!caps.virtual_memory, HostIncompatibility::MemoryInitCow);
+    all.extend(expected(
+        !caps.virtual_memory,
+        HostIncompatibility::MemoryGuardPages,
+    ));
+    all.extend(expected(!fibers, HostIncompatibility::AsyncStacks));
+    all.extend(expected(
+        !cfg!(target_has_atomic = "64"),
+        HostIncompatibility::EpochInterruption,
+    ));
+    assert_eq!(incompatibilities(&config), all);
+    match Engine::new(&config) {
+        Ok(_) => assert!(all.is_empty()),
+        Err(e) => {
+            assert!(!all.is_empty(), "unexpected error: {e:?}");
+            match e.downcast_ref::<ConfigError>() {
+                Some(ConfigError::Incompatible(list)) => assert_eq!(*list, all),
+                _ => panic!("unexpected error: {e:?}"),
+            }
+        }
+    }
+
+    // Configurations which are invalid on every host aren't about the host.
+    let mut config = Config::new();
+    config.max_wasm_stack(0);
+    assert!(matches!(
+        config.validate_for_host(),
+        Err(ConfigError::Invalid(_))
+    ));
+    Ok(())
+}
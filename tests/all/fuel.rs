@@ -28,6 +28,26 @@ impl<'a> Parse<'a> for FuelWast<'a> {
 #[cfg_attr(miri, ignore)]
 fn run(config: &mut Config) -> Result<()> {
     config.consume_fuel(true);
+    assert_fuel_wast(config)
+}
+
+#[wasmtime_test(strategies(not(Winch)))]
+#[cfg_attr(miri, ignore)]
+fn fuel_consumption_is_independent_of_check_placement(config: &mut Config) -> Result<()> {
+    config.consume_fuel(true);
+    for placement in [
+        InterruptCheckPlacement::PerFunction,
+        InterruptCheckPlacement::PerNOperators(1),
+        InterruptCheckPlacement::PerNOperators(7),
+        InterruptCheckPlacement::NoneForLeafFunctions,
+    ] {
+        config.interrupt_check_placement(placement);
+        assert_fuel_wast(config)?;
+    }
+    Ok(())
+}
+
+fn assert_fuel_wast(config: &Config) -> Result<()> {
     let test = std::fs::read_to_string("tests/all/fuel.wast")?;
     let buf = ParseBuffer::new(&test)?;
     let mut wast = parser::parse::<FuelWast<'_>>(&buf)?;
@@ -310,3 +330,356 @@ fn ensure_stack_alignment(config: &mut Config) -> Result<()> {
     );
     Ok(())
 }
+
+#[wasmtime_test(strategies(not(Winch)))]
+#[cfg_attr(miri, ignore)]
+fn instrumentation_around_unreachable_code_verifies(config: &mut Config) -> Result<()> {
+    // Fuel and epoch instrumentation is injected by translation hooks around
+    // every operator, including ones which leave translation in an
+    // unreachable state. Make sure that nothing is emitted into the dead
+    // blocks left behind, which the CLIF verifier would reject.
+    config.consume_fuel(true);
+    config.epoch_interruption(true);
+    config.cranelift_debug_verifier(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func $f (param i32) (result i32) local.get 0)
+                (func (export "br_table") (param i32) (result i32)
+                    (block $a (result i32)
+                        (block $b (result i32)
+                            (block $c (result i32)
+                                i32.const 1
+                                local.get 0
+                                br_table $a $b $c
+                                call $f
+                                drop
+                                i32.const 2
+                                unreachable)
+                            i32.const 10
+                            i32.add)
+                        i32.const 20
+                        i32.add))
+                (func (export "unreachable") (param i32)
+                    (if (local.get 0)
+                        (then unreachable call $f drop br 0)
+                        (else (loop unreachable br 0)))
+                    unreachable
+                    i32.const 0
+                    call $f
+                    drop)
+                (func (export "return") (result i32)
+                    i32.const 3
+                    return
+                    call $f)
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    store.set_fuel(10_000)?;
+    store.set_epoch_deadline(1);
+    let instance = Instance::new(&mut store, &module, &[])?;
+
+    let br_table = instance.get_typed_func::<i32, i32>(&mut store, "br_table")?;
+    assert_eq!(br_table.call(&mut store, 0)?, 1);
+    assert_eq!(br_table.call(&mut store, 1)?, 21);
+    assert_eq!(br_table.call(&mut store, 2)?, 31);
+    assert_eq!(br_table.call(&mut store, 100)?, 31);
+
+    let unreachable = instance.get_typed_func::<i32, ()>(&mut store, "unreachable")?;
+    let trap = unreachable.call(&mut store, 1).unwrap_err();
+    assert_eq!(trap.downcast::<Trap>()?, Trap::UnreachableCodeReached);
+
+    let ret = instance.get_typed_func::<(), i32>(&mut store, "return")?;
+    assert_eq!(ret.call(&mut store, ())?, 3);
+    Ok(())
+}
+
+fn call_with_fuel(config: &Config, wat: &str, name: &str, fuel: u64) -> Result<i32> {
+    let engine = Engine::new(config)?;
+    let module = Module::new(&engine, wat)?;
+    let mut store = Store::new(&engine, ());
+    store.set_fuel(fuel)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let func = instance.get_typed_func::<(), i32>(&mut store, name)?;
+    func.call(&mut store, ())
+}
+
+fn is_out_of_fuel(result: Result<i32>) -> bool {
+    result.is_err_and(|e| e.downcast_ref::<Trap>() == Some(&Trap::OutOfFuel))
+}
+
+#[wasmtime_test(strategies(not(Winch)))]
+#[cfg_attr(miri, ignore)]
+fn dense_fuel_checks_interrupt_straight_line_code(config: &mut Config) -> Result<()> {
+    config.consume_fuel(true);
+    let wat = format!(
+        "(module (func (export \"f\") (result i32) {} i32.const 1))",
+        "i32.const 0 drop ".repeat(100),
+    );
+
+    // Without loops or calls the only check is on entry, so running out of
+    // fuel partway through the function goes unnoticed.
+    config.interrupt_check_placement(InterruptCheckPlacement::PerLoop);
+    assert_eq!(call_with_fuel(config, &wat, "f", 10)?, 1);
+
+    config.interrupt_check_placement(InterruptCheckPlacement::PerNOperators(5));
+    assert!(is_out_of_fuel(call_with_fuel(config, &wat, "f", 10)));
+    assert_eq!(call_with_fuel(config, &wat, "f", 200)?, 1);
+    Ok(())
+}
+
+#[wasmtime_test(strategies(not(Winch)))]
+#[cfg_attr(miri, ignore)]
+fn fuel_check_placement_for_leaves_and_loops(config: &mut Config) -> Result<()> {
+    config.consume_fuel(true);
+    let wat = r#"
+        (module
+            (func $leaf (export "leaf") (result i32) i32.const 1)
+            (func (export "caller") (result i32) call $leaf)
+            (func (export "loop") (result i32)
+                (local i32)
+                (local.set 0 (i32.const 100))
+                (loop
+                    (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+                    (br_if 0 (local.get 0)))
+                i32.const 2)
+        )
+    "#;
+
+    config.interrupt_check_placement(InterruptCheckPlacement::PerLoop);
+    assert!(is_out_of_fuel(call_with_fuel(config, wat, "leaf", 0)));
+    assert!(is_out_of_fuel(call_with_fuel(config, wat, "loop", 10)));
+
+    // Leaf functions skip their entry check, but their callers do not.
+    config.interrupt_check_placement(InterruptCheckPlacement::NoneForLeafFunctions);
+    assert_eq!(call_with_fuel(config, wat, "leaf", 0)?, 1);
+    assert!(is_out_of_fuel(call_with_fuel(config, wat, "caller", 0)));
+    assert!(is_out_of_fuel(call_with_fuel(config, wat, "loop", 10)));
+
+    // Only function entries are checked, so the loop runs to completion.
+    config.interrupt_check_placement(InterruptCheckPlacement::PerFunction);
+    assert!(is_out_of_fuel(call_with_fuel(config, wat, "leaf", 0)));
+    assert_eq!(call_with_fuel(config, wat, "loop", 10)?, 2);
+    Ok(())
+}
+
+/// A gas policy with a weight per class of operator, see `weight`.
+#[derive(Clone, Debug, Default)]
+struct Weights {
+    loop_iteration: u64,
+    check_before_calls: bool,
+}
+
+fn weight(op: &wasmparser::Operator<'_>) -> u64 {
+    use wasmparser::Operator::*;
+    match op {
+        I32Const { .. } => 1,
+        LocalGet { .. } | LocalSet { .. } | LocalTee { .. } => 2,
+        I32Add | I32Sub | I32Mul => 5,
+        Block { .. } | Loop { .. } | If { .. } | Else | End | Br { .. } | BrIf { .. } => 3,
+        _ => 7,
+    }
+}
+
+impl GasPolicy for Weights {
+    fn charge(&mut self, op: &wasmparser::Operator<'_>) -> u64 {
+        weight(op)
+    }
+
+    fn charge_loop_iteration(&mut self) -> u64 {
+        self.loop_iteration
+    }
+
+    fn check_before_calls(&self) -> bool {
+        self.check_before_calls
+    }
+
+    fn for_function(&self) -> Box<dyn GasPolicy> {
+        Box::new(self.clone())
+    }
+}
+
+/// Calls the export `f` of `wat` with `arg`, returning its result and the
+/// gas it consumed.
+fn call_with_gas(config: &Config, wat: &str, arg: i32) -> Result<(i32, u64)> {
+    let engine = Engine::new(config)?;
+    let module = Module::new(&engine, wat)?;
+    let mut store = Store::new(&engine, ());
+    store.set_fuel(u64::MAX)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let f = instance.get_typed_func::<i32, i32>(&mut store, "f")?;
+    let result = f.call(&mut store, arg)?;
+    Ok((result, u64::MAX - store.get_fuel()?))
+}
+
+#[wasmtime_test(strategies(not(Winch)))]
+#[cfg_attr(miri, ignore)]
+fn gas_policy_charges_exact_weights(config: &mut Config) -> Result<()> {
+    config.consume_fuel(true);
+    config.gas_policy(Weights {
+        loop_iteration: 10,
+        check_before_calls: false,
+    });
+
+    let straight_line = r#"
+        (module
+            (func (export "f") (param i32) (result i32)
+                local.get 0
+                i32.const 3
+                i32.mul
+                i32.const 1
+                i32.add))
+    "#;
+    let branching = r#"
+        (module
+            (func (export "f") (param i32) (result i32)
+                local.get 0
+                if (result i32)
+                    i32.const 10
+                else
+                    i32.const 20
+                    i32.const 1
+                    i32.add
+                end))
+    "#;
+    let looping = r#"
+        (module
+            (func (export "f") (param i32) (result i32)
+                (local i32)
+                loop
+                    local.get 1
+                    local.get 0
+                    i32.add
+                    local.set 1
+                    local.get 0
+                    i32.const 1
+                    i32.sub
+                    local.tee 0
+                    br_if 0
+                end
+                local.get 1))
+    "#;
+
+    // The `end` of a function is executed on every path, while the `else`
+    // and `end` of an `if` are only charged on the path falling through
+    // them.
+    let straight_line_gas = 2 + 1 + 5 + 1 + 5 + 3;
+    let then_gas = 2 + 3 + 1 + 3 + 3;
+    let else_gas = 2 + 3 + 1 + 1 + 5 + 3 + 3;
+    // The `loop` itself is charged once, and its header on every iteration.
+    let body_gas = 10 + 2 + 2 + 5 + 2 + 2 + 1 + 5 + 2 + 3;
+    let loop_gas = |n: u64| 3 + n * body_gas + 3 + 2 + 3;
+
+    for placement in [
+        InterruptCheckPlacement::PerLoop,
+        InterruptCheckPlacement::PerFunction,
+        InterruptCheckPlacement::PerNOperators(3),
+        InterruptCheckPlacement::NoneForLeafFunctions,
+    ] {
+        config.interrupt_check_placement(placement);
+        assert_eq!(
+            call_with_gas(config, straight_line, 4)?,
+            (13, straight_line_gas)
+        );
+        assert_eq!(call_with_gas(config, branching, 1)?, (10, then_gas));
+        assert_eq!(call_with_gas(config, branching, 0)?, (21, else_gas));
+        for n in [1, 2, 10] {
+            let expected = (n * (n + 1) / 2, loop_gas(u64::try_from(n)?));
+            assert_eq!(call_with_gas(config, looping, n)?, expected);
+        }
+    }
+    Ok(())
+}
+
+#[wasmtime_test(strategies(not(Winch)))]
+#[cfg_attr(miri, ignore)]
+fn gas_policy_checks_before_calls(config: &mut Config) -> Result<()> {
+    config.consume_fuel(true);
+    let wat = r#"
+        (module
+            (import "" "host" (func $host))
+            (func (export "f") (result i32)
+                i32.const 1
+                i32.const 2
+                i32.add
+                drop
+                call $host
+                i32.const 7))
+    "#;
+    // The gas charged before the call includes the call itself.
+    let before_call = 1 + 1 + 5 + 7 + 7;
+    let total = before_call + 1 + 3;
+
+    for check_before_calls in [false, true] {
+        config.gas_policy(Weights {
+            loop_iteration: 0,
+            check_before_calls,
+        });
+        let engine = Engine::new(config)?;
+        let module = Module::new(&engine, wat)?;
+        let mut store = Store::new(&engine, false);
+        let host = Func::wrap(&mut store, |mut caller: Caller<'_, bool>| {
+            *caller.data_mut() = true;
+        });
+        let instance = Instance::new(&mut store, &module, &[host.into()])?;
+        let f = instance.get_typed_func::<(), i32>(&mut store, "f")?;
+
+        store.set_fuel(1_000)?;
+        assert_eq!(f.call(&mut store, ())?, 7);
+        assert_eq!(store.get_fuel()?, 1_000 - total);
+
+        // Without the check the host function runs even though the gas ran
+        // out before the call, which is only noticed at the next check.
+        *store.data_mut() = false;
+        store.set_fuel(before_call - 1)?;
+        let result = f.call(&mut store, ());
+        if check_before_calls {
+            let trap = result.unwrap_err().downcast::<Trap>()?;
+            assert_eq!(trap, Trap::OutOfFuel);
+            assert!(!*store.data());
+        } else {
+            assert_eq!(result?, 7);
+            assert!(*store.data());
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn gas_policy_requires_fuel() {
+    let mut config = Config::new();
+    config.gas_policy(Weights::default());
+    assert!(Engine::new(&config).is_err());
+    config.consume_fuel(true);
+    assert!(Engine::new(&config).is_ok());
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn gas_policy_is_recorded_in_artifacts() -> Result<()> {
+    let engine_with = |policy: Option<Weights>| {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        if let Some(policy) = policy {
+            config.gas_policy(policy);
+        }
+        Engine::new(&config)
+    };
+    let weights = Weights::default();
+    let engine = engine_with(Some(weights.clone()))?;
+    let bytes = engine.precompile_module(b"(module)")?;
+
+    unsafe {
+        Module::deserialize(&engine_with(Some(weights))?, &bytes)?;
+        let other = Weights {
+            loop_iteration: 1,
+            ..Weights::default()
+        };
+        assert!(Module::deserialize(&engine_with(Some(other))?, &bytes).is_err());
+        assert!(Module::deserialize(&engine_with(None)?, &bytes).is_err());
+    }
+    Ok(())
+}
@@ -2358,3 +2358,128 @@ fn wasm_to_host_trampolines_and_subtyping(config: &mut Config) -> Result<()> {
 
     Ok(())
 }
+
+#[wasmtime_test(strategies(not(Winch)))]
+#[cfg_attr(miri, ignore)]
+fn nan_canonicalization_classes(config: &mut Config) -> Result<()> {
+    const NONCANONICAL_NAN: u32 = 0x7fa0_0001;
+    const CANONICAL_NAN: u32 = 0x7fc0_0000;
+
+    let wat = r#"
+        (module
+            (memory 1)
+            (func (export "add") (param i32) (result i32)
+                (i32.reinterpret_f32
+                    (f32.add (f32.reinterpret_i32 (local.get 0)) (f32.const 0))))
+            (func (export "load") (param i32) (result i32)
+                (i32.store (i32.const 0) (local.get 0))
+                (i32.reinterpret_f32 (f32.load (i32.const 0))))
+        )
+    "#;
+    let run = |config: &Config| -> Result<(u32, u32)> {
+        let engine = Engine::new(config)?;
+        let module = Module::new(&engine, wat)?;
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let add = instance.get_typed_func::<u32, u32>(&mut store, "add")?;
+        let load = instance.get_typed_func::<u32, u32>(&mut store, "load")?;
+        Ok((
+            add.call(&mut store, NONCANONICAL_NAN)?,
+            load.call(&mut store, NONCANONICAL_NAN)?,
+        ))
+    };
+
+    config.nan_canonicalization_classes(NanCanonicalizationClasses::ARITHMETIC);
+    let (add, load) = run(config)?;
+    assert_eq!(add, CANONICAL_NAN);
+    assert_eq!(load, NONCANONICAL_NAN);
+
+    config.nan_canonicalization_classes(NanCanonicalizationClasses::all());
+    let (add, load) = run(config)?;
+    assert_eq!(add, CANONICAL_NAN);
+    assert_eq!(load, CANONICAL_NAN);
+
+    config.nan_canonicalization_classes(NanCanonicalizationClasses::LOAD);
+    let (_, load) = run(config)?;
+    assert_eq!(load, CANONICAL_NAN);
+
+    // The per-class setting replaces, rather than refines, the global one.
+    config.cranelift_nan_canonicalization(true);
+    assert!(Engine::new(config).is_err());
+    Ok(())
+}
+
+#[wasmtime_test(strategies(not(Winch)))]
+#[cfg_attr(miri, ignore)]
+fn nontrapping_float_to_int_override(config: &mut Config) -> Result<()> {
+    let wat = r#"
+        (module
+            (func (export "i32_s") (param f32) (result i32)
+                (i32.trunc_f32_s (local.get 0)))
+            (func (export "i32_u") (param f64) (result i32)
+                (i32.trunc_f64_u (local.get 0)))
+            (func (export "i64_s") (param f64) (result i64)
+                (i64.trunc_f64_s (local.get 0)))
+        )
+    "#;
+    // Returns the results of each truncation, or the trap they raised, along
+    // with the fuel consumed by an in-range conversion.
+    let run = |config: &Config| -> Result<(Vec<Result<i64, Trap>>, u64)> {
+        let engine = Engine::new(config)?;
+        let module = Module::new(&engine, wat)?;
+        let mut store = Store::new(&engine, ());
+        store.set_fuel(10_000)?;
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let i32_s = instance.get_typed_func::<f32, i32>(&mut store, "i32_s")?;
+        let i32_u = instance.get_typed_func::<f64, i32>(&mut store, "i32_u")?;
+        let i64_s = instance.get_typed_func::<f64, i64>(&mut store, "i64_s")?;
+
+        let before = store.get_fuel()?;
+        assert_eq!(i32_s.call(&mut store, -3.5)?, -3);
+        let fuel = before - store.get_fuel()?;
+
+        let trap = |e: anyhow::Error| e.downcast::<Trap>().unwrap();
+        let results = vec![
+            i32_s.call(&mut store, 3e10).map(i64::from).map_err(trap),
+            i32_s.call(&mut store, -3e10).map(i64::from).map_err(trap),
+            i32_s
+                .call(&mut store, f32::NAN)
+                .map(i64::from)
+                .map_err(trap),
+            i32_u.call(&mut store, -1.0).map(i64::from).map_err(trap),
+            i32_u.call(&mut store, 5e9).map(i64::from).map_err(trap),
+            i64_s.call(&mut store, 1e300).map_err(trap),
+        ];
+        Ok((results, fuel))
+    };
+
+    config.consume_fuel(true);
+    let (results, default_fuel) = run(config)?;
+    assert_eq!(
+        results,
+        [
+            Err(Trap::IntegerOverflow),
+            Err(Trap::IntegerOverflow),
+            Err(Trap::BadConversionToInteger),
+            Err(Trap::IntegerOverflow),
+            Err(Trap::IntegerOverflow),
+            Err(Trap::IntegerOverflow),
+        ]
+    );
+
+    config.nontrapping_float_to_int_override(true);
+    let (results, override_fuel) = run(config)?;
+    assert_eq!(
+        results,
+        [
+            Ok(i64::from(i32::MAX)),
+            Ok(i64::from(i32::MIN)),
+            Ok(0),
+            Ok(0),
+            Ok(-1),
+            Ok(i64::MAX),
+        ]
+    );
+    assert_eq!(default_fuel, override_fuel);
+    Ok(())
+}
@@ -160,3 +160,109 @@ fn func_new_returns_wrong_store() -> anyhow::Result<()> {
         }
     }
 }
+
+#[cfg(feature = "shadow-stack")]
+#[wasmtime_test_macros::wasmtime_test(strategies(not(Winch)))]
+#[cfg_attr(miri, ignore)]
+fn shadow_stack_funcref_locals(config: &mut Config) -> anyhow::Result<()> {
+    config.shadow_stack(Some(ShadowStackCheck::Trap));
+    let engine = Engine::new(config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (type $t (func (result i32)))
+                (table 1 funcref)
+                (elem declare func $f)
+                (func $f (result i32) i32.const 1)
+                (func (export "g") (result i32) i32.const 2)
+                (func (export "pick") (param funcref i32) (result i32)
+                    (local funcref (ref null func))
+                    (local.set 2 (ref.func $f))
+                    (if (local.get 1)
+                        (then (local.set 2 (local.get 0))))
+                    (loop
+                        (drop (local.tee 3 (local.get 2))))
+                    (table.set (i32.const 0) (local.get 3))
+                    (call_indirect (type $t) (i32.const 0)))
+            )
+        "#,
+    )?;
+
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let g = instance.get_func(&mut store, "g").unwrap();
+    let pick = instance.get_typed_func::<(Option<Func>, i32), i32>(&mut store, "pick")?;
+
+    assert_eq!(pick.call(&mut store, (Some(g), 0))?, 1);
+    assert_eq!(pick.call(&mut store, (Some(g), 1))?, 2);
+    assert_eq!(pick.call(&mut store, (None, 0))?, 1);
+    let trap = pick
+        .call(&mut store, (None, 1))
+        .unwrap_err()
+        .downcast::<Trap>()?;
+    assert_eq!(trap, Trap::IndirectCallToNull);
+    Ok(())
+}
+
+#[wasmtime_test_macros::wasmtime_test(strategies(not(Winch)))]
+#[cfg_attr(miri, ignore)]
+fn call_indirect_inline_caches(config: &mut Config) -> anyhow::Result<()> {
+    config.call_indirect_inline_caches(true);
+    let engine = Engine::new(config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (type $t (func (result i32)))
+                (table (export "t") 2 funcref)
+                (func $a (export "a") (result i32) i32.const 1)
+                (func $b (export "b") (result i32) i32.const 2)
+                (func (export "bad") (param i32) (result i32) local.get 0)
+                (func (export "call") (param i32) (result i32)
+                    (call_indirect (type $t) (local.get 0)))
+                (func (export "tail") (param i32) (result i32)
+                    (return_call_indirect (type $t) (local.get 0)))
+            )
+        "#,
+    )?;
+
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let table = instance.get_table(&mut store, "t").unwrap();
+    let a = instance.get_func(&mut store, "a").unwrap();
+    let b = instance.get_func(&mut store, "b").unwrap();
+    let bad = instance.get_func(&mut store, "bad").unwrap();
+
+    for name in ["call", "tail"] {
+        let call = instance.get_typed_func::<u32, i32>(&mut store, name)?;
+        let trap = |store: &mut Store<()>, index| -> anyhow::Result<Trap> {
+            call.call(store, index).unwrap_err().downcast::<Trap>()
+        };
+
+        // Null entries trap whether or not the cache is still empty.
+        table.set(&mut store, 0, Ref::Func(None))?;
+        assert_eq!(trap(&mut store, 0)?, Trap::IndirectCallToNull);
+
+        // Repeated calls through the same entry hit in the cache, and calls
+        // through a changed entry must observe the new callee.
+        table.set(&mut store, 0, a.into())?;
+        assert_eq!(call.call(&mut store, 0)?, 1);
+        assert_eq!(call.call(&mut store, 0)?, 1);
+        table.set(&mut store, 0, b.into())?;
+        assert_eq!(call.call(&mut store, 0)?, 2);
+        table.set(&mut store, 1, a.into())?;
+        assert_eq!(call.call(&mut store, 1)?, 1);
+        assert_eq!(call.call(&mut store, 0)?, 2);
+
+        // Replacing the cached callee with one of the wrong type or with null
+        // must still trap.
+        assert_eq!(call.call(&mut store, 0)?, 2);
+        table.set(&mut store, 0, bad.into())?;
+        assert_eq!(trap(&mut store, 0)?, Trap::BadSignature);
+        table.set(&mut store, 0, Ref::Func(None))?;
+        assert_eq!(trap(&mut store, 0)?, Trap::IndirectCallToNull);
+        assert_eq!(trap(&mut store, 2)?, Trap::TableOutOfBounds);
+    }
+    Ok(())
+}
@@ -1394,3 +1394,55 @@ fn drc_gc_inbetween_host_calls() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(feature = "shadow-stack")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn shadow_stack_externref_locals_survive_gc() -> Result<()> {
+    let mut config = Config::new();
+    config.wasm_reference_types(true);
+    config.shadow_stack(Some(ShadowStackCheck::Trap));
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "make_ref" (func $make_ref (result externref)))
+                (import "" "gc" (func $gc))
+                (func (export "run") (result externref)
+                    (local externref)
+                    (local.set 0 (call $make_ref))
+                    ;; The local holds the only reference to the `externref`
+                    ;; during the collection, so it must still be in the call's
+                    ;; stack map alongside its shadow copy.
+                    (call $gc)
+                    (local.get 0))
+            )
+        "#,
+    )?;
+
+    let dropped = Arc::new(AtomicBool::new(false));
+    let mut store = Store::new(&engine, ());
+    let mut linker = Linker::new(&engine);
+    let flag = dropped.clone();
+    linker.func_wrap("", "make_ref", move |mut caller: Caller<'_, ()>| {
+        Ok(Some(ExternRef::new(
+            &mut caller,
+            SetFlagOnDrop(flag.clone()),
+        )?))
+    })?;
+    linker.func_wrap("", "gc", |mut caller: Caller<'_, ()>| caller.gc(None))?;
+    let instance = linker.instantiate(&mut store, &module)?;
+    let run = instance.get_typed_func::<(), Option<Rooted<ExternRef>>>(&mut store, "run")?;
+
+    let mut scope = RootScope::new(&mut store);
+    let externref = run.call(&mut scope, ())?.unwrap();
+    assert!(!dropped.load(SeqCst));
+    assert!(
+        externref
+            .data(&scope)?
+            .expect("host data")
+            .is::<SetFlagOnDrop>()
+    );
+    Ok(())
+}
@@ -764,3 +764,109 @@ fn wasi_imports() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn interposed_import_calls() -> Result<()> {
+    let wat = r#"
+        (module
+            (import "env" "add" (func $add (param i32 i32) (result i32)))
+            (import "env" "add_audited" (func (param i32 i32) (result i32)))
+            (import "env" "double" (func $double (param i32) (result i32)))
+            (import "audit" "before" (func (param i32)))
+            (import "audit" "after" (func (param i32)))
+            (func (export "run") (param i32) (result i32)
+                (call $add
+                    (call $double (local.get 0))
+                    (i32.const 1)))
+            (func (export "double_tail") (param i32) (result i32)
+                (return_call $double (local.get 0)))
+        )
+    "#;
+
+    let mut config = Config::new();
+    config
+        .redirect_import_calls("env", "add", "env", "add_audited")
+        .wrap_import_calls(
+            "env",
+            "double",
+            Some(("audit", "before")),
+            Some(("audit", "after")),
+        );
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, wat);
+
+    // A post-call function can't follow a tail call.
+    let err = module.unwrap_err();
+    assert!(
+        format!("{err:?}").contains("cannot be followed by a post-call function"),
+        "{err:?}"
+    );
+
+    let mut config = Config::new();
+    config
+        .redirect_import_calls("env", "add", "env", "add_audited")
+        .wrap_import_calls("env", "double", Some(("audit", "before")), None)
+        .wrap_import_calls("env", "double", None, Some(("audit", "after")));
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, wat)?;
+
+    let mut linker = Linker::<Vec<String>>::new(&engine);
+    linker.func_wrap("env", "add", |a: i32, b: i32| a + b)?;
+    linker.func_wrap(
+        "env",
+        "add_audited",
+        |mut caller: Caller<'_, Vec<String>>, a: i32, b: i32| {
+            caller.data_mut().push(format!("add_audited({a}, {b})"));
+            a + b
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        "double",
+        |mut caller: Caller<'_, Vec<String>>, a: i32| {
+            caller.data_mut().push(format!("double({a})"));
+            a * 2
+        },
+    )?;
+    linker.func_wrap(
+        "audit",
+        "before",
+        |mut caller: Caller<'_, Vec<String>>, a: i32| {
+            caller.data_mut().push(format!("before({a})"));
+        },
+    )?;
+    linker.func_wrap(
+        "audit",
+        "after",
+        |mut caller: Caller<'_, Vec<String>>, a: i32| {
+            caller.data_mut().push(format!("after({a})"));
+        },
+    )?;
+
+    let mut store = Store::new(&engine, Vec::new());
+    let instance = linker.instantiate(&mut store, &module)?;
+    let run = instance.get_typed_func::<i32, i32>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, 5)?, 11);
+    // Only the first rule for `env::double` applies.
+    assert_eq!(
+        store.data(),
+        &["before(5)", "double(5)", "add_audited(10, 1)"]
+    );
+
+    store.data_mut().clear();
+    let double_tail = instance.get_typed_func::<i32, i32>(&mut store, "double_tail")?;
+    assert_eq!(double_tail.call(&mut store, 4)?, 8);
+    assert_eq!(store.data(), &["before(4)", "double(4)"]);
+
+    // Redirect targets must have the same type as the import.
+    let mut config = Config::new();
+    config.redirect_import_calls("env", "double", "audit", "before");
+    let engine = Engine::new(&config)?;
+    let err = Module::new(&engine, wat).unwrap_err();
+    assert!(
+        format!("{err:?}").contains("cannot be interposed with `audit::before`"),
+        "{err:?}"
+    );
+    Ok(())
+}
@@ -773,3 +773,204 @@ fn configure_zero(config: &mut Config) -> Result<()> {
 
     Ok(())
 }
+
+#[wasmtime_test(strategies(not(Winch)))]
+#[cfg_attr(miri, ignore)]
+fn inline_bulk_memory_matches_builtins(config: &mut Config) -> Result<()> {
+    const LENS: [u32; 4] = [0, 1, 7, 32];
+    let mut wat = String::from("(module (memory (export \"memory\") 1)\n");
+    for len in LENS {
+        wat.push_str(&format!(
+            "(func (export \"copy{len}\") (param i32 i32)
+                (memory.copy (local.get 0) (local.get 1) (i32.const {len})))
+             (func (export \"fill{len}\") (param i32 i32)
+                (memory.fill (local.get 0) (local.get 1) (i32.const {len})))\n"
+        ));
+    }
+    wat.push_str(")");
+
+    let page = 0x1_0000;
+    let calls = |len: u32| {
+        [
+            ("copy", 100, 103),
+            ("copy", 103, 100),
+            ("copy", 200, 200),
+            ("fill", 300, 0x1ab),
+            ("copy", page - len, 0),
+            ("copy", 0, page - len),
+            ("copy", page - len + 1, 0),
+            ("copy", 0, page - len + 1),
+            ("copy", page + 1, 0),
+            ("fill", page - len, 7),
+            ("fill", page - len + 1, 7),
+            ("fill", u32::MAX, 7),
+        ]
+    };
+
+    // Runs every call against a fresh instance compiled with `threshold`,
+    // recording whether each one trapped along with the final memory.
+    let mut run = |threshold: u32| -> Result<Vec<(Vec<bool>, Vec<u8>)>> {
+        config.bulk_memory_inline_threshold(threshold);
+        let engine = Engine::new(config)?;
+        let module = Module::new(&engine, &wat)?;
+        let mut results = vec![];
+        for len in LENS {
+            let mut store = Store::new(&engine, ());
+            let instance = Instance::new(&mut store, &module, &[])?;
+            let memory = instance.get_memory(&mut store, "memory").unwrap();
+            for (i, byte) in memory.data_mut(&mut store).iter_mut().enumerate() {
+                *byte = i as u8;
+            }
+            let mut trapped = vec![];
+            for (op, a, b) in calls(len) {
+                let func =
+                    instance.get_typed_func::<(u32, u32), ()>(&mut store, &format!("{op}{len}"))?;
+                match func.call(&mut store, (a, b)) {
+                    Ok(()) => trapped.push(false),
+                    Err(e) => {
+                        assert_eq!(e.downcast::<Trap>()?, Trap::MemoryOutOfBounds);
+                        trapped.push(true);
+                    }
+                }
+            }
+            results.push((trapped, memory.data(&store).to_vec()));
+        }
+        Ok(results)
+    };
+
+    let builtins = run(0)?;
+    let inline = run(64)?;
+    assert!(builtins == inline);
+    Ok(())
+}
+
+#[wasmtime_test(strategies(not(Winch)))]
+#[cfg_attr(miri, ignore)]
+fn memory_access_callback_observes_accesses(config: &mut Config) -> Result<()> {
+    config.memory_access_instrumentation(Some(MemoryAccessInstrumentation::Callback));
+    let engine = Engine::new(config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory 1)
+                (memory $other 1)
+                (func (export "run") (param i32)
+                    (i32.store offset=4 (local.get 0) (i32.const 1))
+                    (drop (i64.load8_u (local.get 0)))
+                    (drop (i32.atomic.rmw.add offset=8 (local.get 0) (i32.const 1)))
+                    (drop (v128.load8x8_s (local.get 0)))
+                    (i64.store16 $other (i32.const 6) (i64.const 2))
+                )
+            )
+        "#,
+    )?;
+
+    let mut store = Store::new(&engine, Vec::new());
+    store.memory_access_callback(|mut store, access| {
+        if access.address == 0x2000 {
+            anyhow::bail!("watchpoint hit");
+        }
+        store.data_mut().push(access);
+        Ok(())
+    });
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<u32, ()>(&mut store, "run")?;
+
+    run.call(&mut store, 0x100)?;
+    let access = |memory, address, size, is_store| MemoryAccess {
+        memory,
+        address,
+        size,
+        is_store,
+    };
+    assert_eq!(
+        *store.data(),
+        [
+            access(0, 0x104, 4, true),
+            access(0, 0x100, 1, false),
+            access(0, 0x108, 4, true),
+            access(0, 0x100, 8, false),
+            access(1, 6, 2, true),
+        ]
+    );
+
+    // An error from the callback traps before the access is made.
+    store.data_mut().clear();
+    let err = run.call(&mut store, 0x1ffc).unwrap_err();
+    assert!(format!("{err:?}").contains("watchpoint hit"), "{err:?}");
+    assert!(store.data().is_empty());
+    Ok(())
+}
+
+#[wasmtime_test(strategies(not(Winch)))]
+#[cfg_attr(miri, ignore)]
+fn memory_access_callback_only_observes_selected_memories(config: &mut Config) -> Result<()> {
+    config.memory_access_instrumentation(Some(MemoryAccessInstrumentation::Callback));
+    config.instrumented_memories(InstrumentedMemories::empty().with(1));
+    let engine = Engine::new(config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory 1)
+                (memory $other 1)
+                (func (export "run") (param i32)
+                    (i32.store (local.get 0) (i32.const 1))
+                    (i32.store $other (local.get 0) (i32.const 2))
+                    (drop (i32.load (local.get 0)))
+                )
+            )
+        "#,
+    )?;
+
+    let mut store = Store::new(&engine, Vec::new());
+    store.memory_access_callback(|mut store, access| {
+        store.data_mut().push(access);
+        Ok(())
+    });
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<u32, ()>(&mut store, "run")?;
+    run.call(&mut store, 0x10)?;
+    assert_eq!(
+        *store.data(),
+        [MemoryAccess {
+            memory: 1,
+            address: 0x10,
+            size: 4,
+            is_store: true,
+        }]
+    );
+    Ok(())
+}
+
+#[wasmtime_test(strategies(not(Winch)))]
+#[cfg_attr(miri, ignore)]
+fn memory_access_count(config: &mut Config) -> Result<()> {
+    config.memory_access_instrumentation(Some(MemoryAccessInstrumentation::Count));
+    let engine = Engine::new(config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory 1)
+                (func (export "run") (param i32)
+                    (loop $l
+                        (i32.store (local.get 0) (i32.load (local.get 0)))
+                        (br_if $l (local.tee 0 (i32.sub (local.get 0) (i32.const 1))))
+                    )
+                )
+            )
+        "#,
+    )?;
+
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<u32, ()>(&mut store, "run")?;
+    assert_eq!(store.memory_accesses(), 0);
+    run.call(&mut store, 10)?;
+    assert_eq!(store.memory_accesses(), 20);
+    run.call(&mut store, 5)?;
+    assert_eq!(store.memory_accesses(), 30);
+    Ok(())
+}
@@ -649,3 +649,198 @@ fn deserialize_raw_fails_for_native() {
         );
     }
 }
+
+#[wasmtime_test(strategies(not(Winch)))]
+#[cfg_attr(miri, ignore)]
+fn v128_locals_are_zero_initialized(config: &mut Config) -> Result<()> {
+    let wat = r#"
+        (module
+            (func $a (export "a") (result i64)
+                (local v128 v128 i32 v128)
+                (i64.or
+                    (i64x2.extract_lane 1 (v128.or (local.get 0) (local.get 1)))
+                    (i64x2.extract_lane 0 (local.get 3))))
+            (func $b (export "b") (param i32) (result i64)
+                (local v128)
+                (if (local.get 0)
+                    (then (local.set 1 (v128.const i64x2 1 1))))
+                (i64x2.extract_lane 0 (local.get 1)))
+            (func (export "c") (result i64)
+                (local v128)
+                (i64.add
+                    (i64.add (call $a) (call $b (i32.const 1)))
+                    (i64x2.extract_lane 1 (local.get 0))))
+        )
+    "#;
+
+    let modes = [
+        V128Zero::FunctionConstant,
+        V128Zero::Splat,
+        V128Zero::ModuleConstant,
+    ];
+    for (i, zero) in modes.into_iter().enumerate() {
+        config.v128_zero(zero);
+        let engine = Engine::new(config)?;
+        let module = Module::new(&engine, wat)?;
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+
+        let a = instance.get_typed_func::<(), i64>(&mut store, "a")?;
+        assert_eq!(a.call(&mut store, ())?, 0);
+        let b = instance.get_typed_func::<i32, i64>(&mut store, "b")?;
+        assert_eq!(b.call(&mut store, 0)?, 0);
+        assert_eq!(b.call(&mut store, 1)?, 1);
+        let c = instance.get_typed_func::<(), i64>(&mut store, "c")?;
+        assert_eq!(c.call(&mut store, ())?, 1);
+
+        // The modes produce different code, so artifacts are not
+        // interchangeable between them.
+        let serialized = module.serialize()?;
+        config.v128_zero(modes[(i + 1) % modes.len()]);
+        let other = Engine::new(config)?;
+        assert!(unsafe { Module::deserialize(&other, &serialized) }.is_err());
+    }
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn v128_zero_code_size() -> Result<()> {
+    // Every function needs a zero `v128` for its locals.
+    let mut wat = String::from("(module\n");
+    for i in 0..32 {
+        wat.push_str(&format!(
+            "(func (export \"f{i}\") (param v128) (result v128)
+                (local v128 v128)
+                (if (i32x4.all_true (local.get 0))
+                    (then (local.set 1 (local.get 0))))
+                (i8x16.add (local.get 1) (local.get 2)))\n"
+        ));
+    }
+    wat.push(')');
+
+    let mut sizes = Vec::new();
+    for zero in [
+        V128Zero::FunctionConstant,
+        V128Zero::Splat,
+        V128Zero::ModuleConstant,
+    ] {
+        let mut config = Config::new();
+        config.v128_zero(zero);
+        let engine = Engine::new(&config)?;
+        let module = Module::new(&engine, &wat)?;
+        let text = module.text();
+        let funcs = module.functions().map(|f| f.len).sum::<usize>();
+        assert!(funcs > 0, "{zero}: no function code");
+        assert!(
+            funcs <= text.len(),
+            "{zero}: {funcs} bytes of functions in {} bytes of text",
+            text.len()
+        );
+
+        if zero == V128Zero::ModuleConstant && cfg!(target_arch = "x86_64") {
+            // The shared constant is emitted once, aligned, after every
+            // function in the text section.
+            let end = text.len() - 16;
+            assert_eq!(text[end..], [0; 16]);
+            assert_eq!((text.as_ptr() as usize + end) % 16, 0);
+            assert!(module.functions().all(|f| f.offset + f.len <= end));
+        }
+        sizes.push(funcs);
+    }
+
+    // x86-64 materializes a constant zero with a register-zeroing idiom, so
+    // there the constant pool never holds it and loading the shared constant
+    // by address only adds code.
+    if cfg!(target_arch = "x86_64") {
+        assert!(sizes[0] <= sizes[2]);
+    }
+    Ok(())
+}
+
+#[wasmtime_test(strategies(not(Winch)))]
+#[cfg_attr(miri, ignore)]
+fn lazily_initialized_locals_read_zero(config: &mut Config) -> Result<()> {
+    let wat = r#"
+        (module
+            (func (export "branch") (param i32) (result i32)
+                (local i32)
+                (if (local.get 0)
+                    (then (local.set 1 (i32.const 7))))
+                (local.get 1))
+            (func (export "loop") (param i32) (result i64)
+                (local i64 i64)
+                (loop $l
+                    (local.set 1 (i64.const 1))
+                    (local.set 2 (i64.add (local.get 2) (local.get 1)))
+                    (br_if $l (local.tee 0 (i32.sub (local.get 0) (i32.const 1)))))
+                (local.get 2))
+            (func (export "tee") (result f64)
+                (local f64 f64)
+                (local.set 1 (f64.add (local.tee 0 (f64.const 1.5)) (local.get 0)))
+                (f64.add (local.get 0) (local.get 1)))
+        )
+    "#;
+
+    for lazy in [false, true] {
+        config.lazy_local_init(lazy);
+        let engine = Engine::new(config)?;
+        let module = Module::new(&engine, wat)?;
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+
+        let branch = instance.get_typed_func::<i32, i32>(&mut store, "branch")?;
+        assert_eq!(branch.call(&mut store, 0)?, 0);
+        assert_eq!(branch.call(&mut store, 1)?, 7);
+        let looping = instance.get_typed_func::<i32, i64>(&mut store, "loop")?;
+        assert_eq!(looping.call(&mut store, 5)?, 5);
+        let tee = instance.get_typed_func::<(), f64>(&mut store, "tee")?;
+        assert_eq!(tee.call(&mut store, ())?, 4.5);
+    }
+    Ok(())
+}
+
+#[wasmtime_test]
+#[cfg_attr(miri, ignore)]
+fn branch_hints(config: &mut Config) -> Result<()> {
+    let wat = r#"
+        (module
+            (func (export "classify") (param i32) (result i32)
+                block
+                    local.get 0
+                    (@metadata.code.branch_hint "\00")
+                    br_if 0
+                    i32.const 1
+                    return
+                end
+                local.get 0
+                i32.const 10
+                i32.gt_u
+                (@metadata.code.branch_hint "\01")
+                if (result i32)
+                    i32.const 2
+                else
+                    i32.const 3
+                end)
+        )
+    "#;
+    let wasm = wat::parse_str(wat)?;
+    assert!(
+        wasmparser::Parser::new(0)
+            .parse_all(&wasm)
+            .any(|payload| matches!(
+                payload,
+                Ok(wasmparser::Payload::CustomSection(s)) if s.name() == "metadata.code.branch_hint"
+            ))
+    );
+
+    let engine = Engine::new(config)?;
+    let module = Module::new(&engine, &wasm)?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let classify = instance.get_typed_func::<i32, i32>(&mut store, "classify")?;
+    assert_eq!(classify.call(&mut store, 0)?, 1);
+    assert_eq!(classify.call(&mut store, 5)?, 3);
+    assert_eq!(classify.call(&mut store, 50)?, 2);
+    Ok(())
+}
@@ -154,3 +154,51 @@ fn detect_precompiled() -> Result<()> {
     );
     Ok(())
 }
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn bounds_check_strategy_mismatch() -> Result<()> {
+    const WAT: &str = r#"
+        (module
+            (memory 1)
+            (func (export "load") (param i32) (result i32)
+                (i32.load (local.get 0))))
+    "#;
+
+    let guard_pages = Engine::default();
+    let caps = guard_pages.platform_capabilities();
+    if !caps.signals_based_traps || !caps.virtual_memory {
+        return Ok(());
+    }
+    let mut config = Config::new();
+    config.signals_based_traps(false).memory_guard_size(0);
+    let explicit = Engine::new(&config)?;
+
+    let guard_pages_module = serialize(&guard_pages, WAT)?;
+    let explicit_module = serialize(&explicit, WAT)?;
+
+    let err = unsafe { Module::deserialize(&explicit, &guard_pages_module) }.unwrap_err();
+    assert!(
+        format!("{err:?}").contains("rely on guard pages"),
+        "bad error: {err:?}"
+    );
+    let err = unsafe { Module::deserialize(&guard_pages, &explicit_module) }.unwrap_err();
+    assert!(
+        format!("{err:?}").contains("explicit memory bounds checks"),
+        "bad error: {err:?}"
+    );
+
+    // Both strategies are usable side by side in one process.
+    for (engine, bytes) in [
+        (&guard_pages, &guard_pages_module),
+        (&explicit, &explicit_module),
+    ] {
+        let mut store = Store::new(engine, ());
+        let instance = unsafe { deserialize_and_instantiate(&mut store, bytes)? };
+        let load = instance.get_typed_func::<i32, i32>(&mut store, "load")?;
+        assert_eq!(load.call(&mut store, 0)?, 0);
+        let trap = load.call(&mut store, 65536).unwrap_err();
+        assert_eq!(trap.downcast::<Trap>()?, Trap::MemoryOutOfBounds);
+    }
+    Ok(())
+}
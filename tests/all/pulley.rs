@@ -545,3 +545,152 @@ fn decode_unaligned() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn native_and_pulley_engines_side_by_side() -> Result<()> {
+    let native = Engine::default();
+    let pulley = Engine::new(&pulley_config())?;
+    assert!(pulley.is_pulley());
+
+    // Hosts without a Cranelift backend only execute Pulley.
+    if native.is_pulley() {
+        return Ok(());
+    }
+
+    let wat = r#"
+        (module
+            (func (export "fib") (param i32) (result i32)
+                (if (result i32) (i32.lt_u (local.get 0) (i32.const 2))
+                    (then (local.get 0))
+                    (else
+                        (i32.add
+                            (call 0 (i32.sub (local.get 0) (i32.const 1)))
+                            (call 0 (i32.sub (local.get 0) (i32.const 2)))))))
+        )
+    "#;
+
+    let native_module = Module::new(&native, wat)?;
+    let pulley_module = Module::new(&pulley, wat)?;
+    for (engine, module) in [(&native, &native_module), (&pulley, &pulley_module)] {
+        let mut store = Store::new(engine, ());
+        let instance = Instance::new(&mut store, module, &[])?;
+        let fib = instance.get_typed_func::<u32, u32>(&mut store, "fib")?;
+        assert_eq!(fib.call(&mut store, 20)?, 6765);
+    }
+
+    // Modules can't be instantiated in a store for the other kind of engine.
+    let mut store = Store::new(&native, ());
+    let err = Instance::new(&mut store, &pulley_module, &[]).unwrap_err();
+    assert!(format!("{err:?}").contains("cannot instantiate a module compiled for"));
+
+    // Precompiled modules are rejected by the other kind of engine.
+    let native_bytes = native_module.serialize()?;
+    let pulley_bytes = pulley_module.serialize()?;
+    unsafe {
+        let err = Module::deserialize(&native, &pulley_bytes).unwrap_err();
+        assert!(format!("{err:?}").contains("compiled for Pulley"));
+        let err = Module::deserialize(&pulley, &native_bytes).unwrap_err();
+        assert!(format!("{err:?}").contains("executes Pulley bytecode"));
+        Module::deserialize(&native, &native_bytes)?;
+        Module::deserialize(&pulley, &pulley_bytes)?;
+    }
+    Ok(())
+}
+
+const COUNTDOWN: &str = r#"
+    (module
+        (func (export "countdown") (param $n i32)
+            (loop $l
+                (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                (br_if $l (local.get $n))))
+    )
+"#;
+
+fn metering_config() -> Config {
+    let mut config = pulley_config();
+    config.pulley_instruction_metering(true);
+    config
+}
+
+fn run_countdown(module: &Module, n: i32) -> Result<u64> {
+    let mut store = Store::new(module.engine(), ());
+    let instance = Instance::new(&mut store, module, &[])?;
+    let countdown = instance.get_typed_func::<i32, ()>(&mut store, "countdown")?;
+    countdown.call(&mut store, n)?;
+    store.pulley_instructions_executed()
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn instruction_metering_counts_are_exact() -> Result<()> {
+    let engine = Engine::new(&metering_config())?;
+    let module = Module::new(&engine, COUNTDOWN)?;
+
+    // Every iteration of the loop executes the same instructions, so the
+    // count grows by the same amount per iteration, and is the same every
+    // time the loop runs.
+    let counts = [1, 2, 10, 100]
+        .into_iter()
+        .map(|n| run_countdown(&module, n))
+        .collect::<Result<Vec<_>>>()?;
+    let per_iteration = counts[1] - counts[0];
+    assert!(per_iteration > 0);
+    for (count, n) in counts.iter().zip([1, 2, 10, 100]) {
+        assert_eq!(*count, counts[0] + (n - 1) * per_iteration);
+    }
+    assert_eq!(run_countdown(&module, 100)?, counts[3]);
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn instruction_limit_traps() -> Result<()> {
+    let engine = Engine::new(&metering_config())?;
+    let module = Module::new(&engine, COUNTDOWN)?;
+    let needed = run_countdown(&module, 10)?;
+
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let countdown = instance.get_typed_func::<i32, ()>(&mut store, "countdown")?;
+
+    // Exactly enough instructions to finish succeeds.
+    let start = store.pulley_instructions_executed()?;
+    store.set_pulley_instruction_limit(Some(start + needed))?;
+    countdown.call(&mut store, 10)?;
+    assert_eq!(store.pulley_instructions_executed()?, start + needed);
+
+    // One fewer traps, with the count stopping at the limit.
+    let start = store.pulley_instructions_executed()?;
+    store.set_pulley_instruction_limit(Some(start + needed - 1))?;
+    let trap = countdown
+        .call(&mut store, 10)
+        .unwrap_err()
+        .downcast::<Trap>()?;
+    assert_eq!(trap, Trap::InstructionLimitExceeded);
+    assert_eq!(store.pulley_instructions_executed()?, start + needed - 1);
+
+    // Raising the limit allows execution to continue.
+    store.set_pulley_instruction_limit(None)?;
+    countdown.call(&mut store, 10)?;
+    Ok(())
+}
+
+#[test]
+fn instruction_metering_requires_pulley() -> Result<()> {
+    let mut config = metering_config();
+    Engine::new(&config)?;
+    config.pulley_instruction_metering(false);
+    let engine = Engine::new(&config)?;
+    let store = Store::new(&engine, ());
+    assert!(store.pulley_instructions_executed().is_err());
+
+    // Hosts without a Cranelift backend only execute Pulley.
+    if Engine::default().is_pulley() {
+        return Ok(());
+    }
+    let mut config = Config::new();
+    config.pulley_instruction_metering(true);
+    assert!(Engine::new(&config).is_err());
+    Ok(())
+}
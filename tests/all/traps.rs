@@ -1833,3 +1833,142 @@ fn return_call_to_aborting_wasm_function_with_stack_adjustments() -> Result<()>
 
     Ok(())
 }
+
+#[test]
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "riscv64",
+    target_arch = "s390x"
+))]
+fn resolve_fault_at_trapping_instruction() -> Result<()> {
+    use wasmtime::unwinding::resolve_fault;
+
+    let engine = Engine::default();
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "probe" (func $probe))
+                (func $trap unreachable)
+                (func (export "run") call $probe)
+            )
+        "#,
+    )?;
+
+    // Find the machine code of `$trap`, which is where a fault for its
+    // `unreachable` would be raised.
+    let text = module.text().as_ptr() as usize;
+    let trap_fn = module
+        .functions()
+        .find(|f| f.name.as_deref() == Some("trap"))
+        .unwrap();
+    let trap_range = text + trap_fn.offset..text + trap_fn.offset + trap_fn.len;
+
+    // Faults are only resolved while wasm is on the stack.
+    assert!(
+        trap_range
+            .clone()
+            .all(|pc| resolve_fault(pc, 0, None).is_none())
+    );
+
+    let mut store = Store::new(&engine, ());
+    let probe = Func::wrap(&mut store, move || {
+        // Simulate a fault at each instruction of `$trap`, one of which must
+        // be the `unreachable`. Faults outside of wasm code are not traps.
+        let traps = trap_range
+            .clone()
+            .filter_map(|pc| resolve_fault(pc, 0, Some(0)))
+            .collect::<Vec<_>>();
+        assert!(
+            traps
+                .iter()
+                .any(|t| t.trap() == Trap::UnreachableCodeReached)
+        );
+        assert!(traps.iter().all(|t| trap_range.contains(&t.pc())));
+        assert!(traps.iter().all(|t| t.faulting_addr() == Some(0)));
+        assert!(resolve_fault(resolve_fault as usize, 0, None).is_none());
+    });
+    let instance = Instance::new(&mut store, &module, &[probe.into()])?;
+    let run = instance.get_typed_func::<(), ()>(&mut store, "run")?;
+
+    // Resolved traps which aren't resumed have no effect on the call.
+    run.call(&mut store, ())?;
+    run.call(&mut store, ())?;
+    Ok(())
+}
+
+#[wasmtime_test(strategies(not(Winch)))]
+fn unreachable_notifies_host(config: &mut Config) -> Result<()> {
+    config.unreachable_lowering(UnreachableLowering::NotifyHost);
+    let engine = Engine::new(config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module $hello_mod
+                (memory 1 1)
+                (func (export "run") (call $hello))
+                (func $hello (unreachable))
+                (func (export "oob") (drop (i32.load offset=0x10000 (i32.const 0))))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, Vec::new());
+    store.trap_context_callback(|mut store, context| store.data_mut().push(context));
+    let instance = Instance::new(&mut store, &module, &[])?;
+
+    // The host is told where the `unreachable` is, and it still traps.
+    let run = instance.get_typed_func::<(), ()>(&mut store, "run")?;
+    let e = run.call(&mut store, ()).unwrap_err();
+    let trace = e.downcast_ref::<WasmBacktrace>().unwrap().frames();
+    assert_eq!(trace[0].module_offset(), Some(0x33));
+    assert_eq!(
+        *store.data(),
+        [TrapContext {
+            func_index: 1,
+            wasm_offset: 0x33,
+            trap: Trap::UnreachableCodeReached,
+        }]
+    );
+    assert_eq!(e.downcast::<Trap>()?, Trap::UnreachableCodeReached);
+
+    // Other unconditional traps aren't reported by default.
+    store.data_mut().clear();
+    let oob = instance.get_typed_func::<(), ()>(&mut store, "oob")?;
+    let e = oob.call(&mut store, ()).unwrap_err();
+    assert_eq!(e.downcast::<Trap>()?, Trap::MemoryOutOfBounds);
+    assert!(store.data().is_empty());
+    Ok(())
+}
+
+#[wasmtime_test(strategies(not(Winch)))]
+fn unconditional_traps_notify_host(config: &mut Config) -> Result<()> {
+    config.unreachable_lowering(UnreachableLowering::NotifyHostOnAllUnconditionalTraps);
+    let engine = Engine::new(config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory 1 1)
+                (func (export "oob") (drop (i32.load offset=0x10000 (i32.const 0))))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, Vec::new());
+    store.trap_context_callback(|mut store, context| store.data_mut().push(context));
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let oob = instance.get_typed_func::<(), ()>(&mut store, "oob")?;
+    let e = oob.call(&mut store, ()).unwrap_err();
+    let trace = e.downcast_ref::<WasmBacktrace>().unwrap().frames();
+    let offset = u32::try_from(trace[0].module_offset().unwrap())?;
+    assert_eq!(
+        *store.data(),
+        [TrapContext {
+            func_index: 0,
+            wasm_offset: offset,
+            trap: Trap::MemoryOutOfBounds,
+        }]
+    );
+    assert_eq!(e.downcast::<Trap>()?, Trap::MemoryOutOfBounds);
+    Ok(())
+}
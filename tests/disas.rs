@@ -34,6 +34,9 @@
 //! ```
 //!
 //! Flags are parsed by the `wasmtime_cli_flags` crate to build a `Config`.
+//! Tests of optional parts of Wasmtime can list the Cargo features they need
+//! in a `required_features` directive and are skipped when those are
+//! disabled.
 //!
 //! Configuration of tests is prefixed with `;;!` comments and must be present
 //! at the start of the file. These comments are then parsed as TOML and
@@ -109,6 +112,14 @@ fn find_tests(path: &Path, dst: &mut Vec<PathBuf>) -> Result<()> {
 
 fn run_test(path: &Path) -> Result<()> {
     let mut test = Test::new(path)?;
+    if !test
+        .config
+        .required_features
+        .iter()
+        .all(|f| feature_enabled(f))
+    {
+        return Ok(());
+    }
     let output = test.compile()?;
 
     assert_output(&test, output)?;
@@ -124,6 +135,17 @@ struct TestConfig {
     flags: Option<TestConfigFlags>,
     objdump: Option<TestConfigFlags>,
     filter: Option<String>,
+    /// Cargo features of this crate without which the test is skipped.
+    #[serde(default)]
+    required_features: Vec<String>,
+}
+
+/// Returns whether the Cargo feature `name` of this crate is enabled.
+fn feature_enabled(name: &str) -> bool {
+    match name {
+        "shadow-stack" => cfg!(feature = "shadow-stack"),
+        _ => panic!("unknown feature `{name}` in `required_features`"),
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -0,0 +1,47 @@
+;;! target = "x86_64"
+
+(module
+  (func (param i32)
+    block
+      local.get 0
+      (@metadata.code.branch_hint "\00")
+      br_if 0
+      local.get 0
+      (@metadata.code.branch_hint "\01")
+      br_if 0
+    end
+    local.get 0
+    (@metadata.code.branch_hint "\00")
+    if
+    end))
+
+;; function u0:0(i64 vmctx, i64, i32) tail {
+;;     gv0 = vmctx
+;;     gv1 = load.i64 notrap aligned readonly gv0+8
+;;     gv2 = load.i64 notrap aligned gv1+16
+;;     stack_limit = gv2
+;;
+;;                                 block0(v0: i64, v1: i64, v2: i32):
+;; @0044                               brif v2, block4, block3
+;;
+;;                                 block4 cold:
+;; @0044                               jump block2
+;;
+;;                                 block3:
+;; @0048                               brif.i32 v2, block2, block5
+;;
+;;                                 block5 cold:
+;; @004a                               jump block2
+;;
+;;                                 block2:
+;; @004d                               brif.i32 v2, block6, block7
+;;
+;;                                 block6 cold:
+;; @004f                               jump block7
+;;
+;;                                 block7:
+;; @0050                               jump block1
+;;
+;;                                 block1:
+;; @0050                               return
+;; }
@@ -0,0 +1,210 @@
+;;! target = "x86_64"
+;;! flags = "-O bulk-memory-inline-threshold=64"
+
+;; Constant-length `memory.copy` and `memory.fill` below the threshold are
+;; translated to loads and stores, after checking that every byte they access
+;; is in bounds.
+
+(module
+  (memory 1)
+
+  (func (param i32 i32)
+    (memory.copy (local.get 0) (local.get 1) (i32.const 0)))
+
+  (func (param i32 i32)
+    (memory.copy (local.get 0) (local.get 1) (i32.const 1)))
+
+  (func (param i32 i32)
+    (memory.copy (local.get 0) (local.get 1) (i32.const 7)))
+
+  (func (param i32 i32)
+    (memory.copy (local.get 0) (local.get 1) (i32.const 32)))
+
+  (func (param i32 i32)
+    (memory.fill (local.get 0) (local.get 1) (i32.const 7))))
+
+;; function u0:0(i64 vmctx, i64, i32, i32) tail {
+;;     gv0 = vmctx
+;;     gv1 = load.i64 notrap aligned readonly gv0+8
+;;     gv2 = load.i64 notrap aligned gv1+16
+;;     gv3 = vmctx
+;;     gv4 = load.i64 notrap aligned gv3+64
+;;     gv5 = load.i64 notrap aligned readonly can_move checked gv3+56
+;;     stack_limit = gv2
+;;
+;;                                 block0(v0: i64, v1: i64, v2: i32, v3: i32):
+;; @0026                               v4 = iconst.i32 0
+;; @0028                               v5 = uextend.i64 v2
+;; @0028                               v6 = iconst.i64 0
+;; @0028                               v7 = iadd v5, v6  ; v6 = 0
+;; @0028                               v8 = load.i64 notrap aligned v0+64
+;; @0028                               v9 = icmp ugt v7, v8
+;; @0028                               trapnz v9, heap_oob
+;; @0028                               v10 = uextend.i64 v3
+;; @0028                               v11 = iconst.i64 0
+;; @0028                               v12 = iadd v10, v11  ; v11 = 0
+;; @0028                               v13 = load.i64 notrap aligned v0+64
+;; @0028                               v14 = icmp ugt v12, v13
+;; @0028                               trapnz v14, heap_oob
+;; @002c                               jump block1
+;;
+;;                                 block1:
+;; @002c                               return
+;; }
+;;
+;; function u0:1(i64 vmctx, i64, i32, i32) tail {
+;;     gv0 = vmctx
+;;     gv1 = load.i64 notrap aligned readonly gv0+8
+;;     gv2 = load.i64 notrap aligned gv1+16
+;;     gv3 = vmctx
+;;     gv4 = load.i64 notrap aligned gv3+64
+;;     gv5 = load.i64 notrap aligned readonly can_move checked gv3+56
+;;     stack_limit = gv2
+;;
+;;                                 block0(v0: i64, v1: i64, v2: i32, v3: i32):
+;; @0033                               v4 = iconst.i32 1
+;; @0035                               v5 = uextend.i64 v2
+;; @0035                               v6 = iconst.i64 1
+;; @0035                               v7 = iadd v5, v6  ; v6 = 1
+;; @0035                               v8 = load.i64 notrap aligned v0+64
+;; @0035                               v9 = icmp ugt v7, v8
+;; @0035                               trapnz v9, heap_oob
+;; @0035                               v10 = uextend.i64 v3
+;; @0035                               v11 = iconst.i64 1
+;; @0035                               v12 = iadd v10, v11  ; v11 = 1
+;; @0035                               v13 = load.i64 notrap aligned v0+64
+;; @0035                               v14 = icmp ugt v12, v13
+;; @0035                               trapnz v14, heap_oob
+;; @0035                               v15 = uextend.i64 v2
+;; @0035                               v16 = load.i64 notrap aligned readonly can_move checked v0+56
+;; @0035                               v17 = iadd v16, v15
+;; @0035                               v18 = uextend.i64 v3
+;; @0035                               v19 = load.i64 notrap aligned readonly can_move checked v0+56
+;; @0035                               v20 = iadd v19, v18
+;; @0035                               v21 = load.i8 little heap v20
+;; @0035                               store little heap v21, v17
+;; @0039                               jump block1
+;;
+;;                                 block1:
+;; @0039                               return
+;; }
+;;
+;; function u0:2(i64 vmctx, i64, i32, i32) tail {
+;;     gv0 = vmctx
+;;     gv1 = load.i64 notrap aligned readonly gv0+8
+;;     gv2 = load.i64 notrap aligned gv1+16
+;;     gv3 = vmctx
+;;     gv4 = load.i64 notrap aligned gv3+64
+;;     gv5 = load.i64 notrap aligned readonly can_move checked gv3+56
+;;     stack_limit = gv2
+;;
+;;                                 block0(v0: i64, v1: i64, v2: i32, v3: i32):
+;; @0040                               v4 = iconst.i32 7
+;; @0042                               v5 = uextend.i64 v2
+;; @0042                               v6 = iconst.i64 7
+;; @0042                               v7 = iadd v5, v6  ; v6 = 7
+;; @0042                               v8 = load.i64 notrap aligned v0+64
+;; @0042                               v9 = icmp ugt v7, v8
+;; @0042                               trapnz v9, heap_oob
+;; @0042                               v10 = uextend.i64 v3
+;; @0042                               v11 = iconst.i64 7
+;; @0042                               v12 = iadd v10, v11  ; v11 = 7
+;; @0042                               v13 = load.i64 notrap aligned v0+64
+;; @0042                               v14 = icmp ugt v12, v13
+;; @0042                               trapnz v14, heap_oob
+;; @0042                               v15 = uextend.i64 v2
+;; @0042                               v16 = load.i64 notrap aligned readonly can_move checked v0+56
+;; @0042                               v17 = iadd v16, v15
+;; @0042                               v18 = uextend.i64 v3
+;; @0042                               v19 = load.i64 notrap aligned readonly can_move checked v0+56
+;; @0042                               v20 = iadd v19, v18
+;; @0042                               v21 = load.i32 little heap v20
+;; @0042                               v22 = load.i16 little heap v20+4
+;; @0042                               v23 = load.i8 little heap v20+6
+;; @0042                               store little heap v21, v17
+;; @0042                               store little heap v22, v17+4
+;; @0042                               store little heap v23, v17+6
+;; @0046                               jump block1
+;;
+;;                                 block1:
+;; @0046                               return
+;; }
+;;
+;; function u0:3(i64 vmctx, i64, i32, i32) tail {
+;;     gv0 = vmctx
+;;     gv1 = load.i64 notrap aligned readonly gv0+8
+;;     gv2 = load.i64 notrap aligned gv1+16
+;;     gv3 = vmctx
+;;     gv4 = load.i64 notrap aligned gv3+64
+;;     gv5 = load.i64 notrap aligned readonly can_move checked gv3+56
+;;     stack_limit = gv2
+;;
+;;                                 block0(v0: i64, v1: i64, v2: i32, v3: i32):
+;; @004d                               v4 = iconst.i32 32
+;; @004f                               v5 = uextend.i64 v2
+;; @004f                               v6 = iconst.i64 32
+;; @004f                               v7 = iadd v5, v6  ; v6 = 32
+;; @004f                               v8 = load.i64 notrap aligned v0+64
+;; @004f                               v9 = icmp ugt v7, v8
+;; @004f                               trapnz v9, heap_oob
+;; @004f                               v10 = uextend.i64 v3
+;; @004f                               v11 = iconst.i64 32
+;; @004f                               v12 = iadd v10, v11  ; v11 = 32
+;; @004f                               v13 = load.i64 notrap aligned v0+64
+;; @004f                               v14 = icmp ugt v12, v13
+;; @004f                               trapnz v14, heap_oob
+;; @004f                               v15 = uextend.i64 v2
+;; @004f                               v16 = load.i64 notrap aligned readonly can_move checked v0+56
+;; @004f                               v17 = iadd v16, v15
+;; @004f                               v18 = uextend.i64 v3
+;; @004f                               v19 = load.i64 notrap aligned readonly can_move checked v0+56
+;; @004f                               v20 = iadd v19, v18
+;; @004f                               v21 = load.i64 little heap v20
+;; @004f                               v22 = load.i64 little heap v20+8
+;; @004f                               v23 = load.i64 little heap v20+16
+;; @004f                               v24 = load.i64 little heap v20+24
+;; @004f                               store little heap v21, v17
+;; @004f                               store little heap v22, v17+8
+;; @004f                               store little heap v23, v17+16
+;; @004f                               store little heap v24, v17+24
+;; @0053                               jump block1
+;;
+;;                                 block1:
+;; @0053                               return
+;; }
+;;
+;; function u0:4(i64 vmctx, i64, i32, i32) tail {
+;;     gv0 = vmctx
+;;     gv1 = load.i64 notrap aligned readonly gv0+8
+;;     gv2 = load.i64 notrap aligned gv1+16
+;;     gv3 = vmctx
+;;     gv4 = load.i64 notrap aligned gv3+64
+;;     gv5 = load.i64 notrap aligned readonly can_move checked gv3+56
+;;     stack_limit = gv2
+;;
+;;                                 block0(v0: i64, v1: i64, v2: i32, v3: i32):
+;; @005a                               v4 = iconst.i32 7
+;; @005c                               v5 = uextend.i64 v2
+;; @005c                               v6 = iconst.i64 7
+;; @005c                               v7 = iadd v5, v6  ; v6 = 7
+;; @005c                               v8 = load.i64 notrap aligned v0+64
+;; @005c                               v9 = icmp ugt v7, v8
+;; @005c                               trapnz v9, heap_oob
+;; @005c                               v10 = uextend.i64 v2
+;; @005c                               v11 = load.i64 notrap aligned readonly can_move checked v0+56
+;; @005c                               v12 = iadd v11, v10
+;; @005c                               v13 = ireduce.i8 v3
+;; @005c                               v14 = uextend.i64 v13
+;; @005c                               v15 = iconst.i64 0x0101_0101_0101_0101
+;; @005c                               v16 = imul v14, v15  ; v15 = 0x0101_0101_0101_0101
+;; @005c                               v17 = ireduce.i32 v16
+;; @005c                               store little heap v17, v12
+;; @005c                               v18 = ireduce.i16 v16
+;; @005c                               store little heap v18, v12+4
+;; @005c                               v19 = ireduce.i8 v16
+;; @005c                               store little heap v19, v12+6
+;; @005f                               jump block1
+;;
+;;                                 block1:
+;; @005f                               return
+;; }
@@ -0,0 +1,70 @@
+;;! target = "x86_64"
+;;! flags = "-O call-indirect-inline-caches"
+
+;; The `call_indirect` compares the table entry against the funcref cached in
+;; its `VMContext` slot and only checks the signature, in a cold block which
+;; then updates the cache, when they differ.
+
+(module
+  (type $ft (func (param f32) (result i32)))
+  (func $foo (param i32 f32) (result i32)
+    (call_indirect (type $ft) (local.get 1) (local.get 0))
+  )
+  (table (;0;) 23 23 funcref)
+)
+
+;; function u0:0(i64 vmctx, i64, i32, f32) -> i32 tail {
+;;     gv0 = vmctx
+;;     gv1 = load.i64 notrap aligned readonly gv0+8
+;;     gv2 = load.i64 notrap aligned gv1+16
+;;     gv3 = vmctx
+;;     gv4 = load.i64 notrap aligned readonly can_move gv3+48
+;;     sig0 = (i64 vmctx, i64, f32) -> i32 tail
+;;     sig1 = (i64 vmctx, i32, i64) -> i64 tail
+;;     fn0 = colocated u1:9 sig1
+;;     stack_limit = gv2
+;;
+;;                                 block0(v0: i64, v1: i64, v2: i32, v3: f32):
+;; @002a                               v5 = iconst.i32 23
+;; @002a                               v6 = icmp uge v2, v5  ; v5 = 23
+;; @002a                               v7 = uextend.i64 v2
+;; @002a                               v8 = load.i64 notrap aligned readonly can_move v0+48
+;;                                     v32 = iconst.i64 3
+;; @002a                               v9 = ishl v7, v32  ; v32 = 3
+;; @002a                               v10 = iadd v8, v9
+;; @002a                               v11 = iconst.i64 0
+;; @002a                               v12 = select_spectre_guard v6, v11, v10  ; v11 = 0
+;; @002a                               v13 = load.i64 user5 aligned table v12
+;;                                     v31 = iconst.i64 -2
+;; @002a                               v14 = band v13, v31  ; v31 = -2
+;; @002a                               brif v13, block3(v14), block2
+;;
+;;                                 block2 cold:
+;; @002a                               v16 = iconst.i32 0
+;; @002a                               v18 = uextend.i64 v2
+;; @002a                               v19 = call fn0(v0, v16, v18)  ; v16 = 0
+;; @002a                               jump block3(v19)
+;;
+;;                                 block3(v15: i64):
+;; @002a                               v21 = load.i64 notrap aligned v0+64
+;; @002a                               v22 = icmp eq v15, v21
+;; @002a                               brif v22, block5, block4
+;;
+;;                                 block4 cold:
+;; @002a                               v24 = load.i64 notrap aligned readonly can_move v0+40
+;; @002a                               v25 = load.i32 notrap aligned readonly can_move v24
+;; @002a                               v26 = load.i32 user6 aligned readonly v15+16
+;; @002a                               v27 = icmp eq v26, v25
+;; @002a                               trapz v27, user7
+;; @002a                               store notrap aligned v15, v0+64
+;; @002a                               jump block5
+;;
+;;                                 block5:
+;; @002a                               v28 = load.i64 user6 aligned readonly v15+8
+;; @002a                               v29 = load.i64 notrap aligned readonly v15+24
+;; @002a                               v30 = call_indirect sig0, v28(v29, v0, v3)
+;; @002d                               jump block1
+;;
+;;                                 block1:
+;; @002d                               return v30
+;; }
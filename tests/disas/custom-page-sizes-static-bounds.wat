@@ -0,0 +1,96 @@
+;;! target = "x86_64"
+;;! flags = "-W custom-page-sizes=y"
+
+;; A memory with 1-byte pages whose minimum and maximum are equal has a constant
+;; byte length, so dynamic accesses are checked with a single compare against a
+;; constant and without loading the memory's current length.
+
+(module
+  (memory 1000 1000 (pagesize 1))
+
+  (func (param i32) (result i32)
+    local.get 0
+    i32.load)
+
+  (func (param i32 i32)
+    local.get 0
+    local.get 1
+    i32.store offset=16)
+
+  (func (result i32)
+    i32.const 100
+    i32.load offset=8)
+)
+
+;; function u0:0(i64 vmctx, i64, i32) -> i32 tail {
+;;     gv0 = vmctx
+;;     gv1 = load.i64 notrap aligned readonly gv0+8
+;;     gv2 = load.i64 notrap aligned gv1+16
+;;     gv3 = vmctx
+;;     gv4 = load.i64 notrap aligned gv3+64
+;;     gv5 = load.i64 notrap aligned readonly can_move checked gv3+56
+;;     stack_limit = gv2
+;;
+;;                                 block0(v0: i64, v1: i64, v2: i32):
+;; @002f                               v4 = uextend.i64 v2
+;; @002f                               v5 = iconst.i64 996
+;; @002f                               v6 = icmp ugt v4, v5  ; v5 = 996
+;; @002f                               v7 = load.i64 notrap aligned readonly can_move checked v0+56
+;; @002f                               v8 = iadd v7, v4
+;; @002f                               v9 = iconst.i64 0
+;; @002f                               v10 = select_spectre_guard v6, v9, v8  ; v9 = 0
+;; @002f                               v11 = load.i32 little heap v10
+;; @0032                               jump block1
+;;
+;;                                 block1:
+;; @0032                               return v11
+;; }
+;;
+;; function u0:1(i64 vmctx, i64, i32, i32) tail {
+;;     gv0 = vmctx
+;;     gv1 = load.i64 notrap aligned readonly gv0+8
+;;     gv2 = load.i64 notrap aligned gv1+16
+;;     gv3 = vmctx
+;;     gv4 = load.i64 notrap aligned gv3+64
+;;     gv5 = load.i64 notrap aligned readonly can_move checked gv3+56
+;;     stack_limit = gv2
+;;
+;;                                 block0(v0: i64, v1: i64, v2: i32, v3: i32):
+;; @0039                               v4 = uextend.i64 v2
+;; @0039                               v5 = iconst.i64 980
+;; @0039                               v6 = icmp ugt v4, v5  ; v5 = 980
+;; @0039                               v7 = load.i64 notrap aligned readonly can_move checked v0+56
+;; @0039                               v8 = iadd v7, v4
+;; @0039                               v9 = iconst.i64 16
+;; @0039                               v10 = iadd v8, v9  ; v9 = 16
+;; @0039                               v11 = iconst.i64 0
+;; @0039                               v12 = select_spectre_guard v6, v11, v10  ; v11 = 0
+;; @0039                               store little heap v3, v12
+;; @003c                               jump block1
+;;
+;;                                 block1:
+;; @003c                               return
+;; }
+;;
+;; function u0:2(i64 vmctx, i64) -> i32 tail {
+;;     gv0 = vmctx
+;;     gv1 = load.i64 notrap aligned readonly gv0+8
+;;     gv2 = load.i64 notrap aligned gv1+16
+;;     gv3 = vmctx
+;;     gv4 = load.i64 notrap aligned gv3+64
+;;     gv5 = load.i64 notrap aligned readonly can_move checked gv3+56
+;;     stack_limit = gv2
+;;
+;;                                 block0(v0: i64, v1: i64):
+;; @003f                               v3 = iconst.i32 100
+;; @0042                               v4 = uextend.i64 v3  ; v3 = 100
+;; @0042                               v5 = load.i64 notrap aligned readonly can_move checked v0+56
+;; @0042                               v6 = iadd v5, v4
+;; @0042                               v7 = iconst.i64 8
+;; @0042                               v8 = iadd v6, v7  ; v7 = 8
+;; @0042                               v9 = load.i32 little heap v8
+;; @0045                               jump block1
+;;
+;;                                 block1:
+;; @0045                               return v9
+;; }
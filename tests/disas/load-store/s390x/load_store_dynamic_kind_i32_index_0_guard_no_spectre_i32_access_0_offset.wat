@@ -31,8 +31,7 @@
 ;;       lg      %r4, 0x40(%r2)
 ;;       llgfr   %r3, %r6
 ;;       aghi    %r4, -4
-;;       clgr    %r3, %r4
-;;       jgh     0x40
+;;       clgrth  %r3, %r4
 ;;       lg      %r6, 0x38(%r2)
 ;;       strv    %r5, 0(%r3, %r6)
 ;;       lmg     %r14, %r15, 0x110(%r15)
@@ -51,8 +50,7 @@
 ;;       lg      %r4, 0x40(%r2)
 ;;       llgfr   %r3, %r5
 ;;       aghi    %r4, -4
-;;       clgr    %r3, %r4
-;;       jgh     0x98
+;;       clgrth  %r3, %r4
 ;;       lg      %r5, 0x38(%r2)
 ;;       lrv     %r2, 0(%r3, %r5)
 ;;       lmg     %r14, %r15, 0x110(%r15)
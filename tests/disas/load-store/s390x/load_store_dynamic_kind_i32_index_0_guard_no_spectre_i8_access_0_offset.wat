@@ -30,8 +30,7 @@
 ;;       lgr     %r6, %r4
 ;;       lg      %r4, 0x40(%r2)
 ;;       llgfr   %r3, %r6
-;;       clgr    %r3, %r4
-;;       jghe    0x3c
+;;       clgrthe %r3, %r4
 ;;       lg      %r4, 0x38(%r2)
 ;;       stc     %r5, 0(%r3, %r4)
 ;;       lmg     %r14, %r15, 0x110(%r15)
@@ -49,8 +48,7 @@
 ;;       lgr     %r5, %r4
 ;;       lg      %r4, 0x40(%r2)
 ;;       llgfr   %r3, %r5
-;;       clgr    %r3, %r4
-;;       jghe    0x90
+;;       clgrthe %r3, %r4
 ;;       lg      %r4, 0x38(%r2)
 ;;       llc     %r2, 0(%r3, %r4)
 ;;       lmg     %r14, %r15, 0x110(%r15)
@@ -31,8 +31,7 @@
 ;;       lg      %r4, 0x40(%r2)
 ;;       llgfr   %r3, %r6
 ;;       aghi    %r4, -0x1001
-;;       clgr    %r3, %r4
-;;       jgh     0x40
+;;       clgrth  %r3, %r4
 ;;       ag      %r3, 0x38(%r2)
 ;;       lghi    %r6, 0x1000
 ;;       stc     %r5, 0(%r6, %r3)
@@ -52,8 +51,7 @@
 ;;       lg      %r4, 0x40(%r2)
 ;;       llgfr   %r3, %r6
 ;;       aghi    %r4, -0x1001
-;;       clgr    %r3, %r4
-;;       jgh     0x9c
+;;       clgrth  %r3, %r4
 ;;       ag      %r3, 0x38(%r2)
 ;;       lghi    %r6, 0x1000
 ;;       llc     %r2, 0(%r6, %r3)
@@ -33,8 +33,7 @@
 ;;       algfr   %r4, %r3
 ;;       jgnle   0x3c
 ;;       lg      %r6, 0x40(%r2)
-;;       clgr    %r4, %r6
-;;       jgh     0x4c
+;;       clgrth  %r4, %r6
 ;;       ag      %r7, 0x38(%r2)
 ;;       llilh   %r2, 0xffff
 ;;       stc     %r5, 0(%r2, %r7)
@@ -54,10 +53,9 @@
 ;;       lgr     %r3, %r4
 ;;       llilf   %r4, 0xffff0001
 ;;       algfr   %r4, %r3
-;;       jgnle   0xa4
+;;       jgnle   0xa0
 ;;       lg      %r6, 0x40(%r2)
-;;       clgr    %r4, %r6
-;;       jgh     0xb4
+;;       clgrth  %r4, %r6
 ;;       ag      %r7, 0x38(%r2)
 ;;       llilh   %r2, 0xffff
 ;;       llc     %r2, 0(%r2, %r7)
@@ -30,8 +30,7 @@
 ;;       lgr     %r6, %r4
 ;;       lg      %r4, 0x40(%r2)
 ;;       llgfr   %r3, %r6
-;;       clgr    %r3, %r4
-;;       jgh     0x3c
+;;       clgrth  %r3, %r4
 ;;       lg      %r4, 0x38(%r2)
 ;;       strv    %r5, 0(%r3, %r4)
 ;;       lmg     %r14, %r15, 0x110(%r15)
@@ -49,8 +48,7 @@
 ;;       lgr     %r5, %r4
 ;;       lg      %r4, 0x40(%r2)
 ;;       llgfr   %r3, %r5
-;;       clgr    %r3, %r4
-;;       jgh     0x90
+;;       clgrth  %r3, %r4
 ;;       lg      %r4, 0x38(%r2)
 ;;       lrv     %r2, 0(%r3, %r4)
 ;;       lmg     %r14, %r15, 0x110(%r15)
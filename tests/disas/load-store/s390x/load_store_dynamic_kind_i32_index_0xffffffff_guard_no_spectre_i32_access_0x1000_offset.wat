@@ -30,8 +30,7 @@
 ;;       lgr     %r6, %r4
 ;;       lg      %r4, 0x40(%r2)
 ;;       llgfr   %r3, %r6
-;;       clgr    %r3, %r4
-;;       jgh     0x3c
+;;       clgrth  %r3, %r4
 ;;       ag      %r3, 0x38(%r2)
 ;;       lghi    %r6, 0x1000
 ;;       strv    %r5, 0(%r6, %r3)
@@ -50,8 +49,7 @@
 ;;       lgr     %r5, %r4
 ;;       lg      %r4, 0x40(%r2)
 ;;       llgfr   %r3, %r5
-;;       clgr    %r3, %r4
-;;       jgh     0x94
+;;       clgrth  %r3, %r4
 ;;       ag      %r3, 0x38(%r2)
 ;;       lghi    %r5, 0x1000
 ;;       lrv     %r2, 0(%r5, %r3)
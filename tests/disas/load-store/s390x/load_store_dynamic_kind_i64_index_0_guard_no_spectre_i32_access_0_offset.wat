@@ -29,8 +29,7 @@
 ;;       stg     %r1, 0(%r15)
 ;;       lg      %r3, 0x40(%r2)
 ;;       aghi    %r3, -4
-;;       clgr    %r4, %r3
-;;       jgh     0x38
+;;       clgrth  %r4, %r3
 ;;       lg      %r6, 0x38(%r2)
 ;;       strv    %r5, 0(%r4, %r6)
 ;;       lmg     %r14, %r15, 0x110(%r15)
@@ -47,8 +46,7 @@
 ;;       stg     %r1, 0(%r15)
 ;;       lg      %r3, 0x40(%r2)
 ;;       aghi    %r3, -4
-;;       clgr    %r4, %r3
-;;       jgh     0x88
+;;       clgrth  %r4, %r3
 ;;       lg      %r5, 0x38(%r2)
 ;;       lrv     %r2, 0(%r4, %r5)
 ;;       lmg     %r14, %r15, 0x110(%r15)
@@ -29,8 +29,7 @@
 ;;       stg     %r1, 0(%r15)
 ;;       lg      %r3, 0x40(%r2)
 ;;       aghi    %r3, -0x1004
-;;       clgr    %r4, %r3
-;;       jgh     0x38
+;;       clgrth  %r4, %r3
 ;;       ag      %r4, 0x38(%r2)
 ;;       lghi    %r6, 0x1000
 ;;       strv    %r5, 0(%r6, %r4)
@@ -48,8 +47,7 @@
 ;;       stg     %r1, 0(%r15)
 ;;       lg      %r3, 0x40(%r2)
 ;;       aghi    %r3, -0x1004
-;;       clgr    %r4, %r3
-;;       jgh     0x8c
+;;       clgrth  %r4, %r3
 ;;       ag      %r4, 0x38(%r2)
 ;;       lghi    %r5, 0x1000
 ;;       lrv     %r2, 0(%r5, %r4)
@@ -28,8 +28,7 @@
 ;;       aghi    %r15, -0xa0
 ;;       stg     %r1, 0(%r15)
 ;;       lg      %r7, 0x40(%r2)
-;;       clgr    %r4, %r7
-;;       jghe    0x34
+;;       clgrthe %r4, %r7
 ;;       lg      %r3, 0x38(%r2)
 ;;       stc     %r5, 0(%r4, %r3)
 ;;       lmg     %r14, %r15, 0x110(%r15)
@@ -45,8 +44,7 @@
 ;;       aghi    %r15, -0xa0
 ;;       stg     %r1, 0(%r15)
 ;;       lg      %r7, 0x40(%r2)
-;;       clgr    %r4, %r7
-;;       jghe    0x80
+;;       clgrthe %r4, %r7
 ;;       lg      %r3, 0x38(%r2)
 ;;       llc     %r2, 0(%r4, %r3)
 ;;       lmg     %r14, %r15, 0x110(%r15)
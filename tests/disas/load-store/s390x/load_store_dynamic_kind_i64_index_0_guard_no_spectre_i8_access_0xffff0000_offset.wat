@@ -31,8 +31,7 @@
 ;;       algfi   %r3, 0xffff0001
 ;;       jgnle   0x34
 ;;       lg      %r6, 0x40(%r2)
-;;       clgr    %r3, %r6
-;;       jgh     0x44
+;;       clgrth  %r3, %r6
 ;;       ag      %r4, 0x38(%r2)
 ;;       llilh   %r6, 0xffff
 ;;       stc     %r5, 0(%r6, %r4)
@@ -50,10 +49,9 @@
 ;;       stg     %r1, 0(%r15)
 ;;       lgr     %r3, %r4
 ;;       algfi   %r3, 0xffff0001
-;;       jgnle   0x94
+;;       jgnle   0x90
 ;;       lg      %r5, 0x40(%r2)
-;;       clgr    %r3, %r5
-;;       jgh     0xa4
+;;       clgrth  %r3, %r5
 ;;       ag      %r4, 0x38(%r2)
 ;;       llilh   %r6, 0xffff
 ;;       llc     %r2, 0(%r6, %r4)
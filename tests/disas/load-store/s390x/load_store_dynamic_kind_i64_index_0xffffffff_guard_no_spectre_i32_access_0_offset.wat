@@ -28,8 +28,7 @@
 ;;       aghi    %r15, -0xa0
 ;;       stg     %r1, 0(%r15)
 ;;       lg      %r7, 0x40(%r2)
-;;       clgr    %r4, %r7
-;;       jgh     0x34
+;;       clgrth  %r4, %r7
 ;;       lg      %r3, 0x38(%r2)
 ;;       strv    %r5, 0(%r4, %r3)
 ;;       lmg     %r14, %r15, 0x110(%r15)
@@ -45,8 +44,7 @@
 ;;       aghi    %r15, -0xa0
 ;;       stg     %r1, 0(%r15)
 ;;       lg      %r7, 0x40(%r2)
-;;       clgr    %r4, %r7
-;;       jgh     0x80
+;;       clgrth  %r4, %r7
 ;;       lg      %r3, 0x38(%r2)
 ;;       lrv     %r2, 0(%r4, %r3)
 ;;       lmg     %r14, %r15, 0x110(%r15)
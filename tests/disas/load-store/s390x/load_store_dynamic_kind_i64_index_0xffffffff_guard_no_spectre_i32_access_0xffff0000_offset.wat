@@ -28,8 +28,7 @@
 ;;       aghi    %r15, -0xa0
 ;;       stg     %r1, 0(%r15)
 ;;       lg      %r3, 0x40(%r2)
-;;       clgr    %r4, %r3
-;;       jgh     0x34
+;;       clgrth  %r4, %r3
 ;;       ag      %r4, 0x38(%r2)
 ;;       llilh   %r6, 0xffff
 ;;       strv    %r5, 0(%r6, %r4)
@@ -46,8 +45,7 @@
 ;;       aghi    %r15, -0xa0
 ;;       stg     %r1, 0(%r15)
 ;;       lg      %r3, 0x40(%r2)
-;;       clgr    %r4, %r3
-;;       jgh     0x84
+;;       clgrth  %r4, %r3
 ;;       ag      %r4, 0x38(%r2)
 ;;       llilh   %r5, 0xffff
 ;;       lrv     %r2, 0(%r5, %r4)
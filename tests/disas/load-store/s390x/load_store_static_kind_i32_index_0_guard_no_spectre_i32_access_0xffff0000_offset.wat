@@ -28,8 +28,7 @@
 ;;       aghi    %r15, -0xa0
 ;;       stg     %r1, 0(%r15)
 ;;       llgfr   %r7, %r4
-;;       clgfi   %r7, 0xfffc
-;;       jgh     0x34
+;;       clgith  %r7, 0xfffc
 ;;       ag      %r7, 0x38(%r2)
 ;;       llilh   %r4, 0xffff
 ;;       strv    %r5, 0(%r4, %r7)
@@ -46,8 +45,7 @@
 ;;       aghi    %r15, -0xa0
 ;;       stg     %r1, 0(%r15)
 ;;       llgfr   %r7, %r4
-;;       clgfi   %r7, 0xfffc
-;;       jgh     0x84
+;;       clgith  %r7, 0xfffc
 ;;       ag      %r7, 0x38(%r2)
 ;;       llilh   %r4, 0xffff
 ;;       lrv     %r2, 0(%r4, %r7)
@@ -27,8 +27,7 @@
 ;;       lgr     %r1, %r15
 ;;       aghi    %r15, -0xa0
 ;;       stg     %r1, 0(%r15)
-;;       clgfi   %r4, 0xfffc
-;;       jgh     0x30
+;;       clgith  %r4, 0xfffc
 ;;       ag      %r4, 0x38(%r2)
 ;;       llilh   %r3, 0xffff
 ;;       strv    %r5, 0(%r3, %r4)
@@ -44,8 +43,7 @@
 ;;       lgr     %r1, %r15
 ;;       aghi    %r15, -0xa0
 ;;       stg     %r1, 0(%r15)
-;;       clgfi   %r4, 0xfffc
-;;       jgh     0x7c
+;;       clgith  %r4, 0xfffc
 ;;       ag      %r4, 0x38(%r2)
 ;;       llilh   %r3, 0xffff
 ;;       lrv     %r2, 0(%r3, %r4)
@@ -0,0 +1,60 @@
+;;! target = "x86_64"
+;;! flags = "-C nan-canonicalization-classes=arithmetic"
+
+;; Only the classes of operators that were asked for have their results
+;; canonicalized: the `f32.add` is followed by the canonicalization sequence but
+;; the `f32.load` is left alone.
+
+(module
+  (memory 1)
+
+  (func (param f32 f32) (result f32)
+    local.get 0
+    local.get 1
+    f32.add)
+
+  (func (param i32) (result f32)
+    local.get 0
+    f32.load)
+)
+
+;; function u0:0(i64 vmctx, i64, f32, f32) -> f32 tail {
+;;     gv0 = vmctx
+;;     gv1 = load.i64 notrap aligned readonly gv0+8
+;;     gv2 = load.i64 notrap aligned gv1+16
+;;     stack_limit = gv2
+;;
+;;                                 block0(v0: i64, v1: i64, v2: f32, v3: f32):
+;; @0029                               v5 = fadd v2, v3
+;; @0029                               v6 = f32const +NaN
+;; @0029                               v7 = scalar_to_vector.f32x4 v6  ; v6 = +NaN
+;; @0029                               v8 = scalar_to_vector.f32x4 v5
+;; @0029                               v9 = fcmp uno v8, v8
+;; @0029                               v10 = bitcast.f32x4 v9
+;; @0029                               v11 = bitselect v10, v7, v8
+;; @0029                               v12 = extractlane v11, 0
+;; @002a                               jump block1
+;;
+;;                                 block1:
+;; @002a                               return v12
+;; }
+;;
+;; function u0:1(i64 vmctx, i64, i32) -> f32 tail {
+;;     gv0 = vmctx
+;;     gv1 = load.i64 notrap aligned readonly gv0+8
+;;     gv2 = load.i64 notrap aligned gv1+16
+;;     gv3 = vmctx
+;;     gv4 = load.i64 notrap aligned gv3+64
+;;     gv5 = load.i64 notrap aligned readonly can_move checked gv3+56
+;;     stack_limit = gv2
+;;
+;;                                 block0(v0: i64, v1: i64, v2: i32):
+;; @002f                               v4 = uextend.i64 v2
+;; @002f                               v5 = load.i64 notrap aligned readonly can_move checked v0+56
+;; @002f                               v6 = iadd v5, v4
+;; @002f                               v7 = load.f32 little heap v6
+;; @0032                               jump block1
+;;
+;;                                 block1:
+;; @0032                               return v7
+;; }
@@ -0,0 +1,49 @@
+;;! target = "x86_64"
+;;! flags = "-W shadow-stack=breakpoint"
+;;! required_features = ["shadow-stack"]
+
+;; A read of a local which doesn't match its shadow copy executes a breakpoint
+;; in a cold block and then continues.
+
+(module
+  (func (param funcref) (result funcref)
+    (local funcref)
+    (local.set 1 (local.get 0))
+    (local.get 1)))
+
+;; function u0:0(i64 vmctx, i64, i64) -> i64 tail {
+;;     ss0 = explicit_slot 8, align = 8
+;;     ss1 = explicit_slot 8, align = 8
+;;     gv0 = vmctx
+;;     gv1 = load.i64 notrap aligned readonly gv0+8
+;;     gv2 = load.i64 notrap aligned gv1+16
+;;     stack_limit = gv2
+;;
+;;                                 block0(v0: i64, v1: i64, v2: i64):
+;; @0018                               stack_store v2, ss0
+;; @0019                               v4 = iconst.i64 0
+;; @0019                               stack_store v4, ss1  ; v4 = 0
+;; @001b                               v5 = stack_load.i64 ss0
+;; @001b                               v6 = icmp ne v5, v2
+;; @001b                               brif v6, block2, block3
+;;
+;;                                 block2 cold:
+;; @001b                               debugtrap
+;; @001b                               jump block3
+;;
+;;                                 block3:
+;; @001d                               stack_store v2, ss1
+;; @001f                               v7 = stack_load.i64 ss1
+;; @001f                               v8 = icmp ne v7, v2
+;; @001f                               brif v8, block4, block5
+;;
+;;                                 block4 cold:
+;; @001f                               debugtrap
+;; @001f                               jump block5
+;;
+;;                                 block5:
+;; @0021                               jump block1
+;;
+;;                                 block1:
+;; @0021                               return v2
+;; }
@@ -0,0 +1,51 @@
+;;! target = "x86_64"
+;;! flags = "-C v128-zero=module"
+
+;; Every function loads its zero `v128` once from the module's shared constant,
+;; no matter how many of its locals need it.
+
+(module
+  (func (result v128)
+    (local v128 i32 v128)
+    local.get 0
+    local.get 2
+    i8x16.add)
+
+  (func (result v128)
+    (local v128)
+    local.get 0)
+)
+
+;; function u0:0(i64 vmctx, i64) -> i8x16 tail {
+;;     gv0 = vmctx
+;;     gv1 = load.i64 notrap aligned readonly gv0+8
+;;     gv2 = load.i64 notrap aligned gv1+16
+;;     gv3 = symbol colocated userextname0
+;;     stack_limit = gv2
+;;
+;;                                 block0(v0: i64, v1: i64):
+;; @0019                               v3 = symbol_value.i64 gv3
+;; @0019                               v4 = load.i8x16 notrap aligned readonly can_move v3
+;; @001b                               v5 = iconst.i32 0
+;; @0023                               v6 = iadd v4, v4
+;; @0025                               jump block1
+;;
+;;                                 block1:
+;; @0025                               return v6
+;; }
+;;
+;; function u0:1(i64 vmctx, i64) -> i8x16 tail {
+;;     gv0 = vmctx
+;;     gv1 = load.i64 notrap aligned readonly gv0+8
+;;     gv2 = load.i64 notrap aligned gv1+16
+;;     gv3 = symbol colocated userextname0
+;;     stack_limit = gv2
+;;
+;;                                 block0(v0: i64, v1: i64):
+;; @0028                               v3 = symbol_value.i64 gv3
+;; @0028                               v4 = load.i8x16 notrap aligned readonly can_move v3
+;; @002c                               jump block1
+;;
+;;                                 block1:
+;; @002c                               return v4
+;; }
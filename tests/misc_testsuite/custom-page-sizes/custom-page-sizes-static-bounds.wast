@@ -0,0 +1,97 @@
+;;! custom_page_sizes = true
+;;! multi_memory = true
+;;! memory64 = true
+
+;; Memories with a page size of 1 whose minimum and maximum are equal have a
+;; statically known byte length, so bounds checks are performed against a
+;; constant. Exercise the boundaries of such a memory with constant and dynamic
+;; indices and with static offsets.
+(module
+  (memory 7 7 (pagesize 1))
+
+  (func (export "load8") (param i32) (result i32)
+    (i32.load8_u (local.get 0)))
+  (func (export "load8_off") (param i32) (result i32)
+    (i32.load8_u offset=3 (local.get 0)))
+  (func (export "load32") (param i32) (result i32)
+    (i32.load (local.get 0)))
+  (func (export "load32_off") (param i32) (result i32)
+    (i32.load offset=2 (local.get 0)))
+  (func (export "load64") (param i32) (result i64)
+    (i64.load (local.get 0)))
+
+  (func (export "store8") (param i32 i32)
+    (i32.store8 (local.get 0) (local.get 1)))
+  (func (export "store32") (param i32 i32)
+    (i32.store (local.get 0) (local.get 1)))
+  (func (export "store32_off") (param i32 i32)
+    (i32.store offset=2 (local.get 0) (local.get 1)))
+
+  (func (export "load32_const_in_bounds") (result i32)
+    (i32.load (i32.const 3)))
+  (func (export "load32_const_oob") (result i32)
+    (i32.load (i32.const 4)))
+  (func (export "store8_const_last") (param i32)
+    (i32.store8 (i32.const 6) (local.get 0)))
+  (func (export "store8_const_oob") (param i32)
+    (i32.store8 (i32.const 7) (local.get 0)))
+
+  (func (export "size") (result i32)
+    memory.size)
+  (func (export "grow") (param i32) (result i32)
+    (memory.grow (local.get 0)))
+)
+
+(assert_return (invoke "size") (i32.const 7))
+(assert_return (invoke "grow" (i32.const 1)) (i32.const -1))
+(assert_return (invoke "grow" (i32.const 0)) (i32.const 7))
+
+;; Dynamic indices, no static offset.
+(assert_return (invoke "load8" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "load8" (i32.const 6)) (i32.const 0))
+(assert_trap (invoke "load8" (i32.const 7)) "out of bounds memory access")
+(assert_trap (invoke "load8" (i32.const -1)) "out of bounds memory access")
+(assert_return (invoke "load32" (i32.const 3)) (i32.const 0))
+(assert_trap (invoke "load32" (i32.const 4)) "out of bounds memory access")
+(assert_trap (invoke "load32" (i32.const -4)) "out of bounds memory access")
+(assert_trap (invoke "load64" (i32.const 0)) "out of bounds memory access")
+
+;; Dynamic indices with a static offset.
+(assert_return (invoke "load8_off" (i32.const 3)) (i32.const 0))
+(assert_trap (invoke "load8_off" (i32.const 4)) "out of bounds memory access")
+(assert_trap (invoke "load8_off" (i32.const -3)) "out of bounds memory access")
+(assert_return (invoke "load32_off" (i32.const 1)) (i32.const 0))
+(assert_trap (invoke "load32_off" (i32.const 2)) "out of bounds memory access")
+
+;; Stores at the edge, and stores that must trap without writing anything.
+(assert_return (invoke "store8" (i32.const 6) (i32.const 0xaa)))
+(assert_return (invoke "load8" (i32.const 6)) (i32.const 0xaa))
+(assert_trap (invoke "store8" (i32.const 7) (i32.const 0xbb)) "out of bounds memory access")
+(assert_return (invoke "store32" (i32.const 0) (i32.const 0x04030201)))
+(assert_return (invoke "load32_off" (i32.const 0)) (i32.const 0x0403))
+(assert_trap (invoke "store32" (i32.const 4) (i32.const -1)) "out of bounds memory access")
+(assert_trap (invoke "store32_off" (i32.const 2) (i32.const -1)) "out of bounds memory access")
+(assert_return (invoke "load32" (i32.const 3)) (i32.const 0xaa000004))
+
+;; Constant indices.
+(assert_return (invoke "load32_const_in_bounds") (i32.const 0xaa000004))
+(assert_trap (invoke "load32_const_oob") "out of bounds memory access")
+(assert_return (invoke "store8_const_last" (i32.const 0xcc)))
+(assert_return (invoke "load8" (i32.const 6)) (i32.const 0xcc))
+(assert_trap (invoke "store8_const_oob" (i32.const 0xcc)) "out of bounds memory access")
+
+;; A 64-bit tiny-page memory with a static size.
+(module
+  (memory i64 3 3 (pagesize 1))
+  (func (export "load8") (param i64) (result i32)
+    (i32.load8_u (local.get 0)))
+  (func (export "load16_off") (param i64) (result i32)
+    (i32.load16_u offset=1 (local.get 0)))
+)
+
+(assert_return (invoke "load8" (i64.const 2)) (i32.const 0))
+(assert_trap (invoke "load8" (i64.const 3)) "out of bounds memory access")
+(assert_trap (invoke "load8" (i64.const -1)) "out of bounds memory access")
+(assert_return (invoke "load16_off" (i64.const 1)) (i32.const 0))
+(assert_trap (invoke "load16_off" (i64.const 2)) "out of bounds memory access")
+(assert_trap (invoke "load16_off" (i64.const 0xffffffffffffffff)) "out of bounds memory access")
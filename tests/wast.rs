@@ -18,12 +18,17 @@ fn main() {
 
     let mut trials = Vec::new();
 
-    let mut add_trial = |test: &WastTest, config: WastConfig| {
+    let mut add_trial = |test: &WastTest, config: WastConfig, lazy_local_init: bool| {
         let trial = Trial::test(
             format!(
-                "{:?}/{}{}{}",
+                "{:?}/{}{}{}{}",
                 config.compiler,
                 if config.pooling { "pooling/" } else { "" },
+                if lazy_local_init {
+                    "lazy-local-init/"
+                } else {
+                    ""
+                },
                 if config.collector != Collector::Auto {
                     format!("{:?}/", config.collector)
                 } else {
@@ -33,7 +38,9 @@ fn main() {
             ),
             {
                 let test = test.clone();
-                move || run_wast(&test, config).map_err(|e| format!("{e:?}").into())
+                move || {
+                    run_wast(&test, config, lazy_local_init).map_err(|e| format!("{e:?}").into())
+                }
             },
         );
 
@@ -67,6 +74,7 @@ fn main() {
                     pooling: false,
                     collector,
                 },
+                false,
             );
         }
 
@@ -80,8 +88,23 @@ fn main() {
                 pooling: true,
                 collector,
             },
+            false,
         );
 
+        // Also run it without eagerly initializing locals, which must not
+        // change the behavior of any test.
+        if compiler == Compiler::CraneliftNative {
+            add_trial(
+                &test,
+                WastConfig {
+                    compiler,
+                    pooling: false,
+                    collector,
+                },
+                true,
+            );
+        }
+
         // If applicable, also run with the null collector in addition to the
         // default collector.
         if test.test_uses_gc_types() {
@@ -92,6 +115,7 @@ fn main() {
                     pooling: false,
                     collector: Collector::Null,
                 },
+                false,
             );
         }
     }
@@ -108,7 +132,7 @@ fn main() {
 // Each of the tests included from `wast_testsuite_tests` will call this
 // function which actually executes the `wast` test suite given the `strategy`
 // to compile it.
-fn run_wast(test: &WastTest, config: WastConfig) -> anyhow::Result<()> {
+fn run_wast(test: &WastTest, config: WastConfig, lazy_local_init: bool) -> anyhow::Result<()> {
     let test_config = test.config.clone();
 
     // Determine whether this test is expected to fail or pass. Regardless the
@@ -136,6 +160,7 @@ fn run_wast(test: &WastTest, config: WastConfig) -> anyhow::Result<()> {
 
     if is_cranelift {
         cfg.cranelift_debug_verifier(true);
+        cfg.lazy_local_init(lazy_local_init);
     }
 
     // By default we'll allocate huge chunks (6gb) of the address space for each